@@ -1,6 +1,13 @@
 use std::error::Error;
+use std::path::Path;
+
+const SPIRV_DIR: &str = "src/shaders/spv";
 
 fn main() -> Result<(), Box<dyn Error>> {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=assets/settings.ini");
+    println!("cargo:rerun-if-changed={}", SPIRV_DIR);
+
     // Copy settings.ini to the output directory
     #[cfg(not(debug_assertions))]
     let out_dir_assets = "target/release/assets";
@@ -16,5 +23,39 @@ fn main() -> Result<(), Box<dyn Error>> {
         Err(e) => println!("Error {} copying settings.ini", e),
     };
 
+    // Hash of all SPIR-V binaries, used to invalidate the on-disk pipeline cache
+    let mut files = Vec::new();
+    collect_files(Path::new(SPIRV_DIR), &mut files)?;
+    files.sort();
+    let mut hash = FNV_OFFSET;
+    for file in &files {
+        hash = fnv1a(hash, file.to_string_lossy().as_bytes());
+        hash = fnv1a(hash, &std::fs::read(file)?);
+    }
+    println!("cargo:rustc-env=SPARKLE_SPIRV_HASH={:016x}", hash);
+
     Ok(())
 }
+
+fn collect_files(dir: &Path, files: &mut Vec<std::path::PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
@@ -17,10 +17,13 @@ use winit::{
     raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawDisplayHandle, RawWindowHandle},
 };
 
-use crate::editor::{EditCommands, Editor, EditorMode, SceneSnapshot};
+use crate::editor::{
+    EditCommands, Editor, EditorMode, GpuFrameStats, RenderToggles, SceneSnapshot,
+};
+use crate::engine::backend::{GpuMemoryHeapStats, GpuPassTiming};
 use crate::engine::geometry::Light;
 use crate::engine::scene_info::NodeInfo;
-use crate::engine::settings::Settings;
+use crate::engine::settings::{Settings, SsaoSettings};
 use crate::input::first_person::FPSController;
 use crate::input::input_handler::{
     Action, ApplicationRequest, Button, InputHandler, Key, ScrollAxis, translate_key,
@@ -55,6 +58,12 @@ pub struct FrameData {
     pub pending_quit: bool,
     /// Whether ray tracing should be used (toggled by the user)
     pub use_ray_tracing: bool,
+    /// SSAO toggle and parameters (F3 and the editor's Render menu)
+    pub ssao: SsaoSettings,
+    /// Temporal anti-aliasing toggle (editor's Render menu)
+    pub taa: bool,
+    /// Screenshot requested (F12 or editor menu)
+    pub pending_screenshot: bool,
 }
 
 /// Sent from render thread back to main thread (rare events).
@@ -69,16 +78,18 @@ pub enum CameraCommand {
 
 /// Timing information sent from render thread to main thread every frame.
 ///
-/// This provides the actual render pass timing (wall-clock) for accurate FPS display.
-///
-/// TODO: Enrich with GPU timestamps via Vulkan timestamp queries for more precise
-/// GPU-side timing breakdown (vertex shader, fragment shader, present wait, etc.)
+/// This provides the actual render pass timing (wall-clock) for accurate FPS display,
+/// plus the GPU time of the latest completed frame when the backend can measure it.
 #[derive(Debug, Clone)]
 pub struct RenderFrameInfo {
     /// Wall-clock time for the entire render frame (from frame start to present complete) in milliseconds
     pub frame_time_ms: f32,
-    /// Optional: GPU time via timestamp queries (when implemented)
+    /// GPU time via timestamp queries (None if unsupported or not yet available)
     pub gpu_time_ms: Option<f32>,
+    /// Per-pass GPU time and pipeline statistics of the same frame
+    pub gpu_passes: Vec<GpuPassTiming>,
+    /// Device memory usage per heap
+    pub gpu_memory: Vec<GpuMemoryHeapStats>,
     /// Scenegraph tree snapshot (None if no scene loaded)
     pub scene_tree: Option<NodeInfo>,
     /// Current lights in the scene
@@ -147,6 +158,8 @@ pub struct App {
     use_ray_tracing: bool,
     /// Whether the GPU supports ray tracing (reported by render thread)
     rt_supported: bool,
+    /// Set by the F12 hotkey, forwarded with the next FrameData
+    screenshot_requested: bool,
 }
 
 /// Render-thread channel endpoints returned by App::new().
@@ -217,6 +230,7 @@ impl App {
             settings,
             use_ray_tracing: true,
             rt_supported: false,
+            screenshot_requested: false,
         };
 
         let channels = RenderChannels {
@@ -423,15 +437,24 @@ impl App {
                 event: key_event, ..
             } => {
                 // F1 toggles mode regardless
-                if key_event.state == ElementState::Pressed && !key_event.repeat {
-                    if let winit::keyboard::PhysicalKey::Code(winit::keyboard::KeyCode::F1) =
-                        key_event.physical_key
-                    {
-                        self.mode = match self.mode {
-                            EditorMode::Editor => EditorMode::Play,
-                            EditorMode::Play => EditorMode::Editor,
-                        };
-                        return;
+                if key_event.state == ElementState::Pressed
+                    && !key_event.repeat
+                    && let winit::keyboard::PhysicalKey::Code(code) = key_event.physical_key
+                {
+                    match code {
+                        winit::keyboard::KeyCode::F1 => {
+                            self.mode = match self.mode {
+                                EditorMode::Editor => EditorMode::Play,
+                                EditorMode::Play => EditorMode::Editor,
+                            };
+                            return;
+                        }
+                        // F12 takes a screenshot in both modes
+                        winit::keyboard::KeyCode::F12 => {
+                            self.screenshot_requested = true;
+                            return;
+                        }
+                        _ => (),
                     }
                 }
 
@@ -721,6 +744,21 @@ impl ApplicationHandler for App {
             .as_ref()
             .map(|i| i.frame_time_ms)
             .unwrap_or(0.0);
+        let (gpu_passes, gpu_memory) = match &self.latest_render_info {
+            Some(info) => (info.gpu_passes.clone(), info.gpu_memory.clone()),
+            None => (Vec::new(), Vec::new()),
+        };
+        let gpu_stats = GpuFrameStats {
+            time_ms: self.latest_render_info.as_ref().and_then(|i| i.gpu_time_ms),
+            passes: &gpu_passes,
+            memory: &gpu_memory,
+        };
+        let mut render = RenderToggles {
+            rt_supported: self.rt_supported,
+            use_ray_tracing: self.use_ray_tracing,
+            ssao: self.settings.ssao,
+            taa: self.settings.taa,
+        };
 
         // Update FPS controller movement (uses delta_t)
         if self.mode == EditorMode::Play {
@@ -749,10 +787,13 @@ impl ApplicationHandler for App {
             (ww, wh),
             delta_t,
             render_frame_time_ms,
+            gpu_stats,
             &self.scene_snapshot,
-            self.rt_supported,
-            &mut self.use_ray_tracing,
+            &mut render,
         );
+        self.use_ray_tracing = render.use_ray_tracing;
+        self.settings.ssao = render.ssao;
+        self.settings.taa = render.taa;
 
         // Check for quit from editor
         if editor.pending_quit {
@@ -790,6 +831,8 @@ impl ApplicationHandler for App {
 
         // Check for scene load request
         let pending_scene_load = editor.pending_scene_load.take();
+        let pending_screenshot =
+            std::mem::take(&mut self.screenshot_requested) || editor.pending_screenshot;
 
         let frame_data = FrameData {
             full_output,
@@ -803,6 +846,9 @@ impl ApplicationHandler for App {
             pending_scene_load,
             pending_quit: false, // We already handled quit above
             use_ray_tracing: self.use_ray_tracing,
+            ssao: self.settings.ssao,
+            taa: self.settings.taa,
+            pending_screenshot,
         };
 
         // Send to render thread (non-blocking with bounded channel)
@@ -502,8 +502,8 @@ pub fn draw_orientation_gizmo(ctx: &egui::Context, view: &glm::Mat4) -> Orientat
     result
 }
 
-/// Draws a visual marker (💡 emoji) for a selected point light.
-/// Only renders if the light is an Area light and its position is in front of the camera.
+/// Draws a visual marker (💡 emoji) for a selected positional light.
+/// Only renders for Area, Point and Spot lights whose position is in front of the camera.
 pub fn draw_selected_light_marker(
     ctx: &egui::Context,
     light: &crate::engine::geometry::Light,
@@ -513,8 +513,8 @@ pub fn draw_selected_light_marker(
 ) {
     use crate::engine::geometry::LightType;
 
-    // Only draw for Area lights (point lights)
-    if light.t != LightType::Area {
+    // Only draw for lights with a position
+    if !matches!(light.t, LightType::Area | LightType::Point | LightType::Spot) {
         return;
     }
 
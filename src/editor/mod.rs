@@ -21,8 +21,10 @@ pub mod undo;
 pub use edit_commands::{EditCommand, EditCommands};
 
 use crate::app_handler::CameraCommand;
+use crate::engine::backend::{GpuMemoryHeapStats, GpuPassTiming};
 use crate::engine::geometry::Light;
 use crate::engine::scene_info::NodeInfo;
+use crate::engine::settings::SsaoSettings;
 use crate::input::CameraSnapshot;
 
 use std::time::Instant;
@@ -55,6 +57,27 @@ impl SceneSnapshot {
     }
 }
 
+/// GPU statistics of the latest completed frame, shown in the viewport overlay.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GpuFrameStats<'a> {
+    /// GPU time of the whole frame, if measured
+    pub time_ms: Option<f32>,
+    /// Per-pass GPU breakdown
+    pub passes: &'a [GpuPassTiming],
+    /// Device memory usage per heap
+    pub memory: &'a [GpuMemoryHeapStats],
+}
+
+/// Render toggles exposed in the Render section of the menu.
+#[derive(Debug, Clone, Copy)]
+pub struct RenderToggles {
+    /// Whether the device supports ray tracing (read-only)
+    pub rt_supported: bool,
+    pub use_ray_tracing: bool,
+    pub ssao: SsaoSettings,
+    pub taa: bool,
+}
+
 /// The main editor state - lives on MAIN THREAD.
 ///
 /// Owns the egui context and UI state. Runs UI each frame and produces
@@ -72,6 +95,8 @@ pub struct Editor {
     pub pending_scene_load: Option<String>,
     /// Set when the user requests quit via the menu.
     pub pending_quit: bool,
+    /// Set when the user requests a screenshot via the menu.
+    pub pending_screenshot: bool,
     /// Set when user toggles mode via menu.
     pub pending_mode_toggle: bool,
     /// Camera commands produced by UI (orientation snap, etc.)
@@ -116,6 +141,7 @@ impl Editor {
             frame_time_ms: 0.0,
             pending_scene_load: None,
             pending_quit: false,
+            pending_screenshot: false,
             pending_mode_toggle: false,
             pending_camera_commands: Vec::new(),
             selected_node: None,
@@ -175,6 +201,9 @@ impl Editor {
     /// * `window_size` - Current window dimensions (width, height)
     /// * `delta_t` - Time since last frame in seconds
     /// * `render_frame_time_ms` - Actual render frame time from render thread (for FPS display)
+    /// * `gpu_stats` - GPU timings and memory usage of the latest completed frame
+    /// * `scene` - Scene snapshot for the hierarchy and light panels
+    /// * `render` - Render toggles, updated with the user's changes
     ///
     /// # Returns
     /// (FullOutput, EditCommands) - to be sent to render thread
@@ -186,9 +215,9 @@ impl Editor {
         _window_size: (u32, u32),
         _delta_t: f32,
         render_frame_time_ms: f32,
+        gpu_stats: GpuFrameStats,
         scene: &SceneSnapshot,
-        rt_supported: bool,
-        use_ray_tracing: &mut bool,
+        render: &mut RenderToggles,
     ) -> (egui::FullOutput, EditCommands) {
         self.mode = mode;
         self.pending_edits.clear();
@@ -219,6 +248,7 @@ impl Editor {
         let frame_time_ms = self.frame_time_ms;
         let mut pending_scene_load = None;
         let mut pending_quit = false;
+        let mut pending_screenshot = false;
         let mut toggle_mode = false;
         let mut pending_save = false;
         let mut pending_load = false;
@@ -232,8 +262,8 @@ impl Editor {
         let mut show_inspector = self.show_inspector;
         let mut show_lights = self.show_lights;
 
-        // RT toggle (local copy; written back to caller at end)
-        let mut use_ray_tracing_local = *use_ray_tracing;
+        // Render toggles (local copy; written back to caller at end)
+        let mut render_local = *render;
 
         // Undo state for the Edit menu
         let can_undo = self.undo_stack.can_undo();
@@ -287,6 +317,7 @@ impl Editor {
                     ctx,
                     &mut pending_scene_load,
                     &mut pending_quit,
+                    &mut pending_screenshot,
                     &mut toggle_mode,
                     &mut pending_save,
                     &mut pending_load,
@@ -299,8 +330,7 @@ impl Editor {
                     &mut show_hierarchy,
                     &mut show_inspector,
                     &mut show_lights,
-                    rt_supported,
-                    &mut use_ray_tracing_local,
+                    &mut render_local,
                 );
 
                 // Floating panels (only when visible)
@@ -335,7 +365,7 @@ impl Editor {
             }
 
             // FPS + frame time overlay (bottom-left, always visible)
-            ui::draw_viewport_overlay(ctx, fps, frame_time_ms);
+            ui::draw_viewport_overlay(ctx, fps, frame_time_ms, &gpu_stats);
 
             // Gizmo mode keys (T/R/S)
             if mode == EditorMode::Editor && !egui_wants_keyboard {
@@ -446,13 +476,14 @@ impl Editor {
         // Update persistent state
         self.pending_scene_load = pending_scene_load;
         self.pending_quit = pending_quit;
+        self.pending_screenshot = pending_screenshot;
         self.pending_mode_toggle = toggle_mode;
         self.selected_node = selected_node;
         self.selected_light = selected_light;
         self.show_hierarchy = show_hierarchy;
         self.show_inspector = show_inspector;
         self.show_lights = show_lights;
-        *use_ray_tracing = use_ray_tracing_local;
+        *render = render_local;
         self.pending_save = pending_save;
         self.pending_load = pending_load;
 
@@ -566,27 +597,28 @@ impl EditorRenderer {
         Self { egui_ctx }
     }
 
-    /// Render the egui overlay from FullOutput.
+    /// Hand the egui overlay from FullOutput to the renderer.
     ///
-    /// This is called on the RENDER THREAD after scene rendering.
-    /// It tessellates the FullOutput and submits it to the GPU backend.
+    /// This is called on the RENDER THREAD before scene rendering.
+    /// It tessellates the FullOutput; the renderer's `egui` pass composites it
+    /// over the frame during `render_scene()`.
     ///
     /// # Arguments
     /// * `full_output` - The egui output produced by Editor::run_ui()
-    /// * `renderer` - The renderer to submit GPU commands to
+    /// * `renderer` - The renderer that draws the overlay
     pub fn render_overlay<B: crate::engine::backend::GpuBackend>(
         &self,
         full_output: &egui::FullOutput,
         renderer: &mut crate::engine::renderer::Renderer<B>,
     ) {
-        let clipped_primitives = self
+        let primitives = self
             .egui_ctx
             .tessellate(full_output.shapes.clone(), full_output.pixels_per_point);
 
-        renderer.backend_mut().render_egui(
-            &full_output.textures_delta,
-            &clipped_primitives,
-            full_output.pixels_per_point,
-        );
+        renderer.set_overlay(crate::engine::render_graph::EguiOverlay {
+            textures_delta: full_output.textures_delta.clone(),
+            primitives,
+            pixels_per_point: full_output.pixels_per_point,
+        });
     }
 }
@@ -8,6 +8,8 @@
 //!   or keyboard shortcuts H / I / L)
 
 use super::transform::DecomposedTransform;
+use super::{GpuFrameStats, RenderToggles};
+use crate::engine::backend::{GpuMemoryHeapStats, GpuPassTiming};
 use crate::engine::geometry::{Light, LightType};
 use crate::engine::scene_info::NodeInfo;
 
@@ -19,6 +21,7 @@ pub fn draw_hamburger_menu(
     ctx: &egui::Context,
     pending_scene_load: &mut Option<String>,
     pending_quit: &mut bool,
+    pending_screenshot: &mut bool,
     toggle_mode: &mut bool,
     pending_save: &mut bool,
     pending_load: &mut bool,
@@ -31,8 +34,7 @@ pub fn draw_hamburger_menu(
     show_hierarchy: &mut bool,
     show_inspector: &mut bool,
     show_lights: &mut bool,
-    rt_supported: bool,
-    use_ray_tracing: &mut bool,
+    render: &mut RenderToggles,
 ) {
     egui::Area::new(egui::Id::new("hamburger_area"))
         .fixed_pos(egui::pos2(8.0, 8.0))
@@ -62,6 +64,10 @@ pub fn draw_hamburger_menu(
                                 *pending_load = true;
                                 ui.close_kind(egui::UiKind::Menu);
                             }
+                            if ui.button("  Save Screenshot  (F12)").clicked() {
+                                *pending_screenshot = true;
+                                ui.close_kind(egui::UiKind::Menu);
+                            }
                             ui.separator();
 
                             //  Edit
@@ -100,9 +106,45 @@ pub fn draw_hamburger_menu(
                             //  Render
                             ui.label(egui::RichText::new("Render").strong());
                             ui.add_enabled(
-                                rt_supported,
-                                egui::Checkbox::new(use_ray_tracing, "  Ray Tracing"),
+                                render.rt_supported,
+                                egui::Checkbox::new(&mut render.use_ray_tracing, "  Ray Tracing"),
                             );
+                            ui.checkbox(&mut render.ssao.enabled, "  SSAO");
+                            if render.ssao.enabled {
+                                egui::Grid::new("ssao_settings").show(ui, |ui| {
+                                    ui.label("    Radius");
+                                    ui.add(
+                                        egui::DragValue::new(&mut render.ssao.radius)
+                                            .speed(0.01)
+                                            .max_decimals(2)
+                                            .range(0.05..=5.0),
+                                    );
+                                    ui.end_row();
+                                    ui.label("    Bias");
+                                    ui.add(
+                                        egui::DragValue::new(&mut render.ssao.bias)
+                                            .speed(0.001)
+                                            .max_decimals(3)
+                                            .range(0.0..=0.5),
+                                    );
+                                    ui.end_row();
+                                    ui.label("    Intensity");
+                                    ui.add(
+                                        egui::DragValue::new(&mut render.ssao.intensity)
+                                            .speed(0.05)
+                                            .max_decimals(2)
+                                            .range(0.1..=8.0),
+                                    );
+                                    ui.end_row();
+                                    ui.label("    Samples");
+                                    ui.add(
+                                        egui::DragValue::new(&mut render.ssao.samples)
+                                            .range(1..=32),
+                                    );
+                                    ui.end_row();
+                                });
+                            }
+                            ui.checkbox(&mut render.taa, "  TAA");
                             ui.separator();
 
                             if ui.button("  Toggle Play Mode  (F1)").clicked() {
@@ -127,6 +169,7 @@ pub fn draw_hamburger_menu(
                                 "T / R / G      Translate / Rotate / Scale",
                                 "H / I / J       Hierarchy / Inspector / Lights",
                                 "F1                Toggle Play Mode",
+                                "F12              Save Screenshot",
                                 "RMB drag    Orbit camera",
                                 "MMB drag    Pan camera",
                                 "Scroll            Zoom",
@@ -139,12 +182,20 @@ pub fn draw_hamburger_menu(
         });
 }
 
-/// Draw the viewport overlay: FPS counter and frame time at the bottom-left.
-pub fn draw_viewport_overlay(ctx: &egui::Context, fps: f32, frame_time_ms: f32) {
+/// Draw the viewport overlay: FPS counter and frame time at the bottom-left,
+/// with a collapsible per-pass GPU breakdown when GPU timings are available
+/// and per-heap device memory usage when the backend tracks it.
+pub fn draw_viewport_overlay(
+    ctx: &egui::Context,
+    fps: f32,
+    frame_time_ms: f32,
+    gpu_stats: &GpuFrameStats,
+) {
     let screen = ctx.content_rect();
 
     egui::Area::new(egui::Id::new("viewport_overlay"))
-        .fixed_pos(egui::pos2(10.0, screen.max.y - 32.0))
+        .pivot(egui::Align2::LEFT_BOTTOM)
+        .fixed_pos(egui::pos2(10.0, screen.max.y - 10.0))
         .order(egui::Order::Foreground)
         .show(ctx, |ui| {
             egui::Frame::new()
@@ -152,9 +203,31 @@ pub fn draw_viewport_overlay(ctx: &egui::Context, fps: f32, frame_time_ms: f32)
                 .corner_radius(4.0)
                 .inner_margin(egui::Margin::same(6))
                 .show(ui, |ui| {
+                    if !gpu_stats.passes.is_empty() {
+                        egui::CollapsingHeader::new(
+                            egui::RichText::new("GPU passes")
+                                .color(egui::Color32::WHITE)
+                                .size(11.0),
+                        )
+                        .id_salt("gpu_passes")
+                        .show(ui, |ui| draw_gpu_pass_table(ui, gpu_stats.passes));
+                    }
+                    if !gpu_stats.memory.is_empty() {
+                        egui::CollapsingHeader::new(
+                            egui::RichText::new("GPU memory")
+                                .color(egui::Color32::WHITE)
+                                .size(11.0),
+                        )
+                        .id_salt("gpu_memory")
+                        .show(ui, |ui| draw_gpu_memory_table(ui, gpu_stats.memory));
+                    }
                     ui.horizontal(|ui| {
+                        let mut text = format!("{:.0} FPS  {:.2} ms", fps, frame_time_ms);
+                        if let Some(gpu_ms) = gpu_stats.time_ms {
+                            text.push_str(&format!("  GPU {:.2} ms", gpu_ms));
+                        }
                         ui.label(
-                            egui::RichText::new(format!("{:.0} FPS  {:.2} ms", fps, frame_time_ms))
+                            egui::RichText::new(text)
                                 .color(egui::Color32::WHITE)
                                 .size(13.0),
                         );
@@ -163,6 +236,92 @@ pub fn draw_viewport_overlay(ctx: &egui::Context, fps: f32, frame_time_ms: f32)
         });
 }
 
+/// Per-pass GPU times, with pipeline statistics when the backend collects them.
+fn draw_gpu_pass_table(ui: &mut egui::Ui, passes: &[GpuPassTiming]) {
+    let grey = egui::Color32::from_gray(200);
+    let with_statistics = passes.iter().any(|p| p.primitives.is_some());
+    egui::Grid::new("gpu_pass_table")
+        .num_columns(if with_statistics { 4 } else { 2 })
+        .spacing([12.0, 2.0])
+        .show(ui, |ui| {
+            for pass in passes {
+                let name = if pass.count > 1 {
+                    format!("{} x{}", pass.name, pass.count)
+                } else {
+                    pass.name.clone()
+                };
+                ui.label(egui::RichText::new(name).size(11.0).color(grey));
+                ui.label(
+                    egui::RichText::new(format!("{:.3} ms", pass.gpu_time_ms))
+                        .size(11.0)
+                        .color(egui::Color32::WHITE),
+                );
+                if with_statistics {
+                    let count = |v: Option<u64>| v.map_or("-".to_string(), |v| v.to_string());
+                    ui.label(
+                        egui::RichText::new(format!("{} prims", count(pass.primitives)))
+                            .size(11.0)
+                            .color(grey),
+                    );
+                    ui.label(
+                        egui::RichText::new(format!("{} frags", count(pass.fragment_invocations)))
+                            .size(11.0)
+                            .color(grey),
+                    );
+                }
+                ui.end_row();
+            }
+        });
+}
+
+/// Per-heap device memory: bytes bound to resources, bytes allocated from the
+/// driver and, with `VK_EXT_memory_budget`, the process usage against the budget.
+fn draw_gpu_memory_table(ui: &mut egui::Ui, heaps: &[GpuMemoryHeapStats]) {
+    let grey = egui::Color32::from_gray(200);
+    let mib = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+    let with_budget = heaps.iter().any(|h| h.budget_bytes.is_some());
+    egui::Grid::new("gpu_memory_table")
+        .num_columns(if with_budget { 4 } else { 3 })
+        .spacing([12.0, 2.0])
+        .show(ui, |ui| {
+            for heap in heaps {
+                let kind = if heap.device_local { "device" } else { "host" };
+                ui.label(
+                    egui::RichText::new(format!("heap {} ({kind})", heap.heap_index))
+                        .size(11.0)
+                        .color(grey),
+                );
+                ui.label(
+                    egui::RichText::new(format!(
+                        "{:.1} / {:.1} MiB",
+                        mib(heap.used_bytes),
+                        mib(heap.allocated_bytes)
+                    ))
+                    .size(11.0)
+                    .color(egui::Color32::WHITE),
+                );
+                ui.label(
+                    egui::RichText::new(format!(
+                        "{} allocs, {} blocks, {} dedicated",
+                        heap.allocation_count, heap.block_count, heap.dedicated_count
+                    ))
+                    .size(11.0)
+                    .color(grey),
+                );
+                if with_budget {
+                    let budget = match (heap.usage_bytes, heap.budget_bytes) {
+                        (Some(usage), Some(budget)) => {
+                            format!("budget {:.0} / {:.0} MiB", mib(usage), mib(budget))
+                        }
+                        _ => "-".to_string(),
+                    };
+                    ui.label(egui::RichText::new(budget).size(11.0).color(grey));
+                }
+                ui.end_row();
+            }
+        });
+}
+
 /// Scene hierarchy as a floating window.
 ///
 /// The `open` flag is toggled by the X button on the window title bar
@@ -359,7 +518,7 @@ pub fn draw_light_window(
                         color: glm::vec3(1.0, 1.0, 1.0),
                         radius: 1.0,
                         penumbra_radius: 0.0,
-                        light_proj: glm::identity(),
+                        ..Light::default()
                     });
                 }
             });
@@ -375,6 +534,8 @@ pub fn draw_light_window(
                         LightType::Ambient => "Ambient",
                         LightType::Directional => "Directional",
                         LightType::Area => "Area",
+                        LightType::Point => "Point",
+                        LightType::Spot => "Spot",
                     };
 
                     let is_selected = *selected_light == Some(idx);
@@ -400,18 +561,23 @@ pub fn draw_light_window(
                                     LightType::Ambient => 0,
                                     LightType::Directional => 1,
                                     LightType::Area => 2,
+                                    LightType::Point => 3,
+                                    LightType::Spot => 4,
                                 };
                                 if egui::ComboBox::from_id_salt(format!("light_type_{}", idx))
                                     .width(100.0)
-                                    .show_index(ui, &mut type_idx, 3, |i| {
-                                        ["Ambient", "Directional", "Area"][i].to_string()
+                                    .show_index(ui, &mut type_idx, 5, |i| {
+                                        ["Ambient", "Directional", "Area", "Point", "Spot"][i]
+                                            .to_string()
                                     })
                                     .changed()
                                 {
                                     edited_light.t = match type_idx {
                                         0 => LightType::Ambient,
                                         1 => LightType::Directional,
-                                        _ => LightType::Area,
+                                        2 => LightType::Area,
+                                        3 => LightType::Point,
+                                        _ => LightType::Spot,
                                     };
                                     changed = true;
                                 }
@@ -473,6 +639,87 @@ pub fn draw_light_window(
                                 });
                             }
 
+                            // Spot direction
+                            if edited_light.t == LightType::Spot {
+                                ui.horizontal(|ui| {
+                                    ui.label("Direction:");
+                                    let mut dir = [
+                                        edited_light.direction.x,
+                                        edited_light.direction.y,
+                                        edited_light.direction.z,
+                                    ];
+                                    for (i, label) in ["X", "Y", "Z"].iter().enumerate() {
+                                        ui.label(*label);
+                                        if ui
+                                            .add(
+                                                egui::DragValue::new(&mut dir[i])
+                                                    .speed(0.01)
+                                                    .max_decimals(3),
+                                            )
+                                            .changed()
+                                        {
+                                            changed = true;
+                                        }
+                                    }
+                                    edited_light.direction = glm::vec3(dir[0], dir[1], dir[2]);
+                                });
+                            }
+
+                            // Range
+                            if matches!(edited_light.t, LightType::Point | LightType::Spot) {
+                                ui.horizontal(|ui| {
+                                    ui.label("Range:");
+                                    if ui
+                                        .add(
+                                            egui::DragValue::new(&mut edited_light.range)
+                                                .speed(0.1)
+                                                .max_decimals(2)
+                                                .range(0.1..=1000.0),
+                                        )
+                                        .changed()
+                                    {
+                                        changed = true;
+                                    }
+                                });
+                            }
+
+                            // Cone angles, edited in degrees
+                            if edited_light.t == LightType::Spot {
+                                ui.horizontal(|ui| {
+                                    ui.label("Cone:");
+                                    let mut inner = edited_light.inner_angle.to_degrees();
+                                    let mut outer = edited_light.outer_angle.to_degrees();
+                                    ui.label("Inner");
+                                    if ui
+                                        .add(
+                                            egui::DragValue::new(&mut inner)
+                                                .speed(0.5)
+                                                .max_decimals(1)
+                                                .range(0.0..=outer)
+                                                .suffix("°"),
+                                        )
+                                        .changed()
+                                    {
+                                        changed = true;
+                                    }
+                                    ui.label("Outer");
+                                    if ui
+                                        .add(
+                                            egui::DragValue::new(&mut outer)
+                                                .speed(0.5)
+                                                .max_decimals(1)
+                                                .range(inner..=85.0)
+                                                .suffix("°"),
+                                        )
+                                        .changed()
+                                    {
+                                        changed = true;
+                                    }
+                                    edited_light.inner_angle = inner.to_radians();
+                                    edited_light.outer_angle = outer.to_radians();
+                                });
+                            }
+
                             // Radius
                             if edited_light.t == LightType::Area {
                                 ui.horizontal(|ui| {
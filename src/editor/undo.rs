@@ -21,8 +21,8 @@ pub enum Command {
     /// Light properties changed.
     UpdateLight {
         index: usize,
-        old_light: Light,
-        new_light: Light,
+        old_light: Box<Light>,
+        new_light: Box<Light>,
     },
     /// Light added (stores the light and its index after insertion).
     AddLight { light: Light, index: usize },
@@ -44,7 +44,7 @@ impl Command {
             Command::UpdateLight {
                 index, new_light, ..
             } => {
-                renderer.update_light(*index, new_light.clone());
+                renderer.update_light(*index, (**new_light).clone());
             }
             Command::AddLight { light, .. } => {
                 renderer.add_light(light.clone());
@@ -68,7 +68,7 @@ impl Command {
             Command::UpdateLight {
                 index, old_light, ..
             } => {
-                renderer.update_light(*index, old_light.clone());
+                renderer.update_light(*index, (**old_light).clone());
             }
             Command::AddLight { index, .. } => {
                 // Undo add = remove the light that was added
@@ -7,6 +7,7 @@
 use super::geometry::{AABB, Vertex};
 
 use std::collections::HashMap;
+use std::path::Path;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
@@ -74,6 +75,109 @@ pub enum TextureFormat {
     // Depth formats
     Depth32Float,
     Depth24Stencil8,
+
+    // Block-compressed formats, sampled only
+    Bc1RgbaUnorm,
+    Bc1RgbaUnormSrgb,
+    Bc3Unorm,
+    Bc3UnormSrgb,
+    Bc4Unorm,
+    Bc5Unorm,
+    Bc7Unorm,
+    Bc7UnormSrgb,
+    Etc2Rgba8Unorm,
+    Etc2Rgba8UnormSrgb,
+    Astc4x4Unorm,
+    Astc4x4UnormSrgb,
+}
+
+impl TextureFormat {
+    /// Size of a single texel in bytes, for compressed formats the size of
+    /// one block (see [`Self::block_size`]).
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            TextureFormat::R8Unorm => 1,
+            TextureFormat::Rg8Unorm => 2,
+            TextureFormat::Rgba8Unorm
+            | TextureFormat::Rgba8UnormSrgb
+            | TextureFormat::Bgra8Unorm
+            | TextureFormat::Bgra8UnormSrgb
+            | TextureFormat::Abgr10Unorm => 4,
+            TextureFormat::R16g16b16a16Float => 8,
+            TextureFormat::Rgba32Float | TextureFormat::Rgba32Uint => 16,
+            TextureFormat::Depth32Float | TextureFormat::Depth24Stencil8 => 4,
+            TextureFormat::Bc1RgbaUnorm
+            | TextureFormat::Bc1RgbaUnormSrgb
+            | TextureFormat::Bc4Unorm => 8,
+            TextureFormat::Bc3Unorm
+            | TextureFormat::Bc3UnormSrgb
+            | TextureFormat::Bc5Unorm
+            | TextureFormat::Bc7Unorm
+            | TextureFormat::Bc7UnormSrgb
+            | TextureFormat::Etc2Rgba8Unorm
+            | TextureFormat::Etc2Rgba8UnormSrgb
+            | TextureFormat::Astc4x4Unorm
+            | TextureFormat::Astc4x4UnormSrgb => 16,
+        }
+    }
+
+    /// Width and height of a compression block in texels, 1 for uncompressed formats.
+    pub fn block_size(&self) -> u32 {
+        if self.is_compressed() { 4 } else { 1 }
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        matches!(
+            self,
+            TextureFormat::Bc1RgbaUnorm
+                | TextureFormat::Bc1RgbaUnormSrgb
+                | TextureFormat::Bc3Unorm
+                | TextureFormat::Bc3UnormSrgb
+                | TextureFormat::Bc4Unorm
+                | TextureFormat::Bc5Unorm
+                | TextureFormat::Bc7Unorm
+                | TextureFormat::Bc7UnormSrgb
+                | TextureFormat::Etc2Rgba8Unorm
+                | TextureFormat::Etc2Rgba8UnormSrgb
+                | TextureFormat::Astc4x4Unorm
+                | TextureFormat::Astc4x4UnormSrgb
+        )
+    }
+
+    /// Byte size of one `width` x `height` image, partial blocks round up.
+    pub fn image_size(&self, width: u32, height: u32) -> usize {
+        let block = self.block_size();
+        width.div_ceil(block) as usize * height.div_ceil(block) as usize * self.bytes_per_pixel()
+    }
+
+    /// Byte size of a tightly packed chain of `mip_levels` images, largest first.
+    pub fn mip_chain_size(&self, width: u32, height: u32, mip_levels: u32) -> usize {
+        (0..mip_levels)
+            .map(|level| self.image_size((width >> level).max(1), (height >> level).max(1)))
+            .sum()
+    }
+
+    pub fn is_depth(&self) -> bool {
+        matches!(
+            self,
+            TextureFormat::Depth32Float | TextureFormat::Depth24Stencil8
+        )
+    }
+
+    pub fn has_stencil(&self) -> bool {
+        *self == TextureFormat::Depth24Stencil8
+    }
+}
+
+/// Shape of a texture's image and of the view it is sampled through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureDimension {
+    D2,
+    D2Array,
+    /// Six faces in +X, -X, +Y, -Y, +Z, -Z order, stored as layers.
+    Cube,
+    CubeArray,
+    D3,
 }
 
 /// Vertex attribute data types.
@@ -115,6 +219,69 @@ pub enum CompareFunc {
     Always,
 }
 
+/// Update applied to a stencil value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StencilOp {
+    Keep,
+    Zero,
+    /// Write the pipeline's reference value.
+    Replace,
+    IncrementClamp,
+    DecrementClamp,
+    Invert,
+    IncrementWrap,
+    DecrementWrap,
+}
+
+/// Stencil test and updates for triangles of one facing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StencilFaceState {
+    /// Compares the reference value against the stored value.
+    pub compare: CompareFunc,
+    /// Applied when the stencil test fails.
+    pub fail_op: StencilOp,
+    /// Applied when the stencil test passes but the depth test fails.
+    pub depth_fail_op: StencilOp,
+    /// Applied when both tests pass.
+    pub pass_op: StencilOp,
+}
+
+impl Default for StencilFaceState {
+    fn default() -> Self {
+        StencilFaceState {
+            compare: CompareFunc::Always,
+            fail_op: StencilOp::Keep,
+            depth_fail_op: StencilOp::Keep,
+            pass_op: StencilOp::Keep,
+        }
+    }
+}
+
+/// Stencil state of a render pipeline, requires a depth format with stencil.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StencilState {
+    pub front: StencilFaceState,
+    pub back: StencilFaceState,
+    /// Bits of the reference and stored values that are compared.
+    pub read_mask: u8,
+    /// Bits of the stored value that the operations may change.
+    pub write_mask: u8,
+    pub reference: u8,
+}
+
+impl StencilState {
+    /// The same test and updates for both facings, all bits compared and written.
+    pub fn both(face: StencilFaceState, reference: u8) -> Self {
+        StencilState {
+            front: face,
+            back: face,
+            read_mask: 0xff,
+            write_mask: 0xff,
+            reference,
+        }
+    }
+}
+
 /// GPU buffer usage.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BufferUsage {
@@ -202,13 +369,148 @@ impl Default for SamplerDesc {
     }
 }
 
-/// Description for creating a 2D texture.
+/// Description for creating a texture.
+///
+/// The upload data holds one mip level after the other, largest first, each
+/// with all of its layers (or depth slices) tightly packed.
 pub struct TextureDesc {
     pub width: u32,
     pub height: u32,
+    /// Depth of a 3D texture, otherwise the number of array layers (6 per cube).
+    pub depth_or_layers: u32,
+    pub dimension: TextureDimension,
     pub format: TextureFormat,
     pub sampler: SamplerDesc,
     pub generate_mipmaps: bool,
+    /// Mip levels contained in the upload data, tightly packed and largest
+    /// first. Compressed formats can't be blitted, so they bring their own chain.
+    pub mip_levels: u32,
+}
+
+impl TextureDesc {
+    /// Byte size of the upload data.
+    pub fn data_size(&self) -> usize {
+        (0..self.mip_levels)
+            .map(|level| {
+                let slices = if self.dimension == TextureDimension::D3 {
+                    (self.depth_or_layers >> level).max(1)
+                } else {
+                    self.depth_or_layers
+                };
+                let (width, height) = ((self.width >> level).max(1), (self.height >> level).max(1));
+                self.format.image_size(width, height) * slices as usize
+            })
+            .sum()
+    }
+
+    /// Check the layer and mip counts against the dimension and the size of `data`.
+    pub fn validate(&self, data: &[u8]) -> Result<(), GpuError> {
+        let layers_valid = match self.dimension {
+            TextureDimension::D2 => self.depth_or_layers == 1,
+            TextureDimension::Cube => self.depth_or_layers == 6,
+            TextureDimension::CubeArray => {
+                self.depth_or_layers > 0 && self.depth_or_layers.is_multiple_of(6)
+            }
+            TextureDimension::D2Array | TextureDimension::D3 => self.depth_or_layers > 0,
+        };
+        if !layers_valid {
+            return Err(GpuError::new(
+                format!(
+                    "{:?} texture cannot have {} layers",
+                    self.dimension, self.depth_or_layers
+                ),
+                GpuErrorKind::ResourceCreation,
+            ));
+        }
+        let max_extent = match self.dimension {
+            TextureDimension::D3 => self.width.max(self.height).max(self.depth_or_layers),
+            _ => self.width.max(self.height),
+        };
+        if self.mip_levels == 0 || self.mip_levels > max_extent.max(1).ilog2() + 1 {
+            return Err(GpuError::new(
+                format!(
+                    "Invalid mip level count {} for a {}x{} texture",
+                    self.mip_levels, self.width, self.height
+                ),
+                GpuErrorKind::ResourceCreation,
+            ));
+        }
+        if data.len() != self.data_size() {
+            return Err(GpuError::new(
+                format!(
+                    "Texture data is {} bytes, {} mip levels of a {}x{}x{} {:?} {:?} texture need {}",
+                    data.len(),
+                    self.mip_levels,
+                    self.width,
+                    self.height,
+                    self.depth_or_layers,
+                    self.format,
+                    self.dimension,
+                    self.data_size()
+                ),
+                GpuErrorKind::ResourceCreation,
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Pixel data copied back from a render target.
+///
+/// Rows are tightly packed (`width * format.bytes_per_pixel()` bytes each),
+/// top row first, in the target's native format.
+pub struct TextureReadback {
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    pub data: Vec<u8>,
+}
+
+/// GPU time and pipeline statistics of one `begin_event`/`end_event` region.
+///
+/// Regions recorded several times per frame under the same name (e.g. per-light
+/// passes) are merged. Nested regions overlap the time of their parents.
+#[derive(Debug, Clone)]
+pub struct GpuPassTiming {
+    pub name: String,
+    /// How often the region was recorded this frame.
+    pub count: u32,
+    pub gpu_time_ms: f32,
+    /// Input assembly primitives, when pipeline statistics are enabled.
+    pub primitives: Option<u64>,
+    /// Fragment shader invocations, when pipeline statistics are enabled.
+    pub fragment_invocations: Option<u64>,
+}
+
+/// GPU timings of a completed frame.
+#[derive(Debug, Clone, Default)]
+pub struct GpuFrameTimings {
+    /// GPU time of the whole frame command buffer.
+    pub gpu_time_ms: f32,
+    /// Per-pass breakdown in recording order.
+    pub passes: Vec<GpuPassTiming>,
+}
+
+/// Device memory usage of one memory heap.
+#[derive(Debug, Clone, Default)]
+pub struct GpuMemoryHeapStats {
+    pub heap_index: u32,
+    pub device_local: bool,
+    pub heap_size: u64,
+    /// Bytes of device memory allocated from the driver.
+    pub allocated_bytes: u64,
+    /// Bytes of `allocated_bytes` bound to live resources.
+    pub used_bytes: u64,
+    /// Shared blocks resources are sub-allocated from.
+    pub block_count: u32,
+    /// Allocations owned by a single resource.
+    pub dedicated_count: u32,
+    /// Live resources, dedicated ones included.
+    pub allocation_count: u32,
+    /// Budget reported by the driver for this process, if available.
+    pub budget_bytes: Option<u64>,
+    /// Process-wide usage reported by the driver, if available.
+    pub usage_bytes: Option<u64>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -225,6 +527,36 @@ pub struct RenderTargetDesc {
     pub format: TextureFormat,
     pub sampler: SamplerDesc,
     pub usage: RenderTargetUsage,
+    /// Samples per pixel, 1 without MSAA. Multisampled targets cannot be
+    /// sampled in shaders, they are resolved into a single-sampled target.
+    pub sample_count: u32,
+    /// One image shared by all frames in flight instead of one per frame, so
+    /// the contents written in one frame can be read in the next.
+    pub persistent: bool,
+    /// Array layers, 1 for a 2D target. Depth targets can have six layers per
+    /// cube instead: they are sampled as a cube map array and each layer (cube
+    /// face) is rendered on its own, see [`DepthAttachment::layer`].
+    pub layers: u32,
+}
+
+impl RenderTargetDesc {
+    /// Check that layered targets are single-sampled depth cube map arrays.
+    pub fn validate(&self) -> Result<(), GpuError> {
+        let valid = self.layers == 1
+            || (self.layers.is_multiple_of(6)
+                && self.usage == RenderTargetUsage::Depth
+                && self.sample_count == 1);
+        if !valid {
+            return Err(GpuError::new(
+                format!(
+                    "A {:?} render target with {} samples cannot have {} layers",
+                    self.usage, self.sample_count, self.layers
+                ),
+                GpuErrorKind::ResourceCreation,
+            ));
+        }
+        Ok(())
+    }
 }
 
 /// Description for creating a GPU buffer.
@@ -330,18 +662,127 @@ pub struct RenderPipelineDesc<'a, ShaderSource> {
     pub depth_compare: CompareFunc,
     pub color_target_formats: &'a [TextureFormat],
     pub depth_format: Option<TextureFormat>,
+    /// `None` disables the stencil test.
+    pub stencil: Option<StencilState>,
+    /// Samples per pixel, must match the `sample_count` of the attachments.
+    pub sample_count: u32,
+}
+
+impl<ShaderSource> RenderPipelineDesc<'_, ShaderSource> {
+    /// Check that a stencil test has a stencil attachment to run against.
+    pub fn validate(&self) -> Result<(), GpuError> {
+        if self.stencil.is_some() && !self.depth_format.is_some_and(|f| f.has_stencil()) {
+            return Err(GpuError::new(
+                format!(
+                    "Pipeline '{}' has a stencil test but its depth format {:?} has no stencil",
+                    self.label, self.depth_format
+                ),
+                GpuErrorKind::ResourceCreation,
+            ));
+        }
+        Ok(())
+    }
 }
 
 /// Description for creating a compute pipeline.
+///
+/// Besides the shared bindless set 0, a compute shader reads the resources of
+/// its own set 1, declared by `bindings` and supplied with every dispatch.
 pub struct ComputePipelineDesc<'a, ShaderSource> {
     pub label: &'a str,
     pub shader_source: &'a ShaderSource,
     /// Value injected as specialization constant 0 (world dimension for scatter shader).
     pub world_dimension: Option<f32>,
+    /// Resources of set 1.
+    pub bindings: &'a [ComputeBindingDesc],
+    /// Size of the push-constant block in bytes, 0 if the shader has none.
+    pub push_constant_size: u32,
+}
+
+/// Kind of a compute resource binding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ComputeBindingType {
+    /// Read-write structured buffer.
+    StorageBuffer,
+    /// Sampled texture with its sampler.
+    Texture,
+    /// Read-write image, a render target with [`RenderTargetUsage::Storage`].
+    StorageImage,
+}
+
+/// A binding of a compute pipeline's set 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ComputeBindingDesc {
+    pub binding: u32,
+    pub ty: ComputeBindingType,
+}
+
+/// A resource bound to a compute dispatch.
+pub enum ComputeResource<'a, B: GpuBackend> {
+    StorageBuffer(&'a B::Buffer),
+    Texture(&'a B::Texture),
+    StorageImage(&'a B::RenderTarget),
+}
+
+/// Pipeline, resources and push constants of a compute dispatch.
+pub struct ComputeDispatch<'a, B: GpuBackend> {
+    pub pipeline: &'a B::Pipeline,
+    /// `(binding, resource)` pairs for set 1 of the pipeline.
+    pub resources: &'a [(u32, ComputeResource<'a, B>)],
+    /// Raw push-constant bytes, at most the pipeline's `push_constant_size`.
+    pub push_constants: &'a [u8],
+}
+
+/// Execution and memory dependency recorded with [`GpuBackend::cmd_compute_barrier`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComputeBarrier {
+    /// Writes of earlier dispatches are visible to later dispatches.
+    ComputeToCompute,
+    /// Compute writes are visible to indirect draws, vertex and fragment shaders.
+    ComputeToGraphics,
+    /// Earlier graphics passes are done with resources later dispatches read or write.
+    GraphicsToCompute,
+}
+
+/// How a pass uses a render target. The render graph derives these from the
+/// pass declarations and records the change between two passes with
+/// [`GpuBackend::cmd_target_barriers`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TargetAccess {
+    /// Not used yet; previous contents may be discarded.
+    Undefined,
+    /// Rendered into as color attachment or resolve target.
+    ColorAttachment,
+    /// Depth attachment with depth writes.
+    DepthAttachment,
+    /// Depth attachment with depth writes disabled.
+    DepthTest,
+    /// Sampled in shaders.
+    ShaderRead,
+    /// Written by a custom pass, which records its own layout transitions.
+    Custom,
+}
+
+impl TargetAccess {
+    /// Whether the access writes the target, so a barrier is needed even if the
+    /// next access is the same.
+    pub fn is_write(self) -> bool {
+        matches!(
+            self,
+            TargetAccess::ColorAttachment | TargetAccess::DepthAttachment | TargetAccess::Custom
+        )
+    }
+}
+
+/// A render target changing from one access to another between two passes.
+pub struct TargetBarrier<'a, B: GpuBackend> {
+    pub target: &'a B::RenderTarget,
+    pub before: TargetAccess,
+    pub after: TargetAccess,
 }
 
 /// Load operation for a render pass attachment.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LoadOp {
     /// Clear the attachment to a specific value.
     Clear,
@@ -354,6 +795,9 @@ pub struct ColorAttachment<'a, B: GpuBackend> {
     pub target: &'a B::RenderTarget,
     pub load_op: LoadOp,
     pub clear_color: [f32; 4],
+    /// Single-sampled target the multisampled `target` is averaged into at the
+    /// end of the pass.
+    pub resolve_target: Option<&'a B::RenderTarget>,
 }
 
 /// A depth/stencil attachment within a render pass.
@@ -362,6 +806,14 @@ pub struct DepthAttachment<'a, B: GpuBackend> {
     pub load_op: LoadOp,
     pub clear_depth: f32,
     pub write_enabled: bool,
+    /// Ignored unless the target has a stencil aspect.
+    pub stencil_load_op: LoadOp,
+    pub clear_stencil: u8,
+    /// Single-sampled target that receives sample 0 of the multisampled
+    /// `target` at the end of the pass.
+    pub resolve_target: Option<&'a B::RenderTarget>,
+    /// Array layer of a layered `target` to render into and clear, 0 otherwise.
+    pub layer: u32,
 }
 
 /// Description for beginning a render pass.
@@ -438,7 +890,25 @@ pub trait GpuBackend: Sized + 'static {
     /// load shaders for procedural generation
     fn load_proc_gen_shaders(&self) -> ProceduralShaders<Self>;
 
-    /// Create a 2D texture from raw pixel data.
+    /// Files a shader source was built from, relative to the SPIR-V directory.
+    /// Used by shader hot reload; empty for backends without on-disk shaders.
+    fn shader_files(&self, _source: &Self::ShaderSource) -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    /// Re-read a shader source from `spirv_dir` for shader hot reload.
+    fn reload_shader_source(
+        &self,
+        _source: &Self::ShaderSource,
+        _spirv_dir: &Path,
+    ) -> Result<Self::ShaderSource, GpuError> {
+        Err(GpuError::new(
+            "Shader hot reload is not supported by this backend",
+            GpuErrorKind::Other,
+        ))
+    }
+
+    /// Create a 2D texture from raw pixel data, holding `desc.mip_levels` levels.
     fn create_texture(&self, desc: &TextureDesc, data: &[u8]) -> Result<Self::Texture, GpuError>;
 
     /// Create a cubemap texture from 6 face images (in +X, -X, +Y, -Y, +Z, -Z order).
@@ -449,6 +919,28 @@ pub trait GpuBackend: Sized + 'static {
         height: u32,
         format: TextureFormat,
         sampler: &SamplerDesc,
+    ) -> Result<Self::Texture, GpuError> {
+        self.create_texture(
+            &TextureDesc {
+                width,
+                height,
+                depth_or_layers: 6,
+                dimension: TextureDimension::Cube,
+                format,
+                sampler: *sampler,
+                generate_mipmaps: false,
+                mip_levels: 1,
+            },
+            &faces.concat(),
+        )
+    }
+
+    /// Create a 2D texture of one array layer (or cube face) of `texture`,
+    /// with its own bindless slot. It shares the image and keeps it alive.
+    fn create_layer_view(
+        &self,
+        texture: &Self::Texture,
+        layer: u32,
     ) -> Result<Self::Texture, GpuError>;
 
     /// Create a GPU buffer, optionally initialized with data.
@@ -474,23 +966,12 @@ pub trait GpuBackend: Sized + 'static {
         desc: &ComputePipelineDesc<Self::ShaderSource>,
     ) -> Result<Self::Pipeline, GpuError>;
 
-    /// Execute a compute pipeline in a one-shot command submission with host synchronization and a pipeline memory barrier.
-    fn execute_compute_one_shot(
+    /// Execute a compute dispatch in a one-shot command submission and wait for it.
+    /// Its writes are visible to all later GPU work.
+    fn dispatch_compute_one_shot(
         &self,
-        pipeline: &Self::Pipeline,
-        buffers: &[(u32, &Self::Buffer)],
-        textures: &[(u32, &Self::Texture)],
+        dispatch: &ComputeDispatch<Self>,
         work_groups: (u32, u32, u32),
-        max_instances: u32,
-        asset_offset: u32,
-        max_height: f32,
-        spawn_height_min: f32,
-        spawn_height_max: f32,
-        slope_max: f32,
-        scale_min: f32,
-        scale_max: f32,
-        tilt_factor: f32,
-        terrain_segments_f: f32,
     ) -> Result<(), GpuError>;
 
     //  Buffer operations
@@ -501,7 +982,8 @@ pub trait GpuBackend: Sized + 'static {
     /// Record a buffer update into the current command buffer.
     /// Data is baked into the command stream so each pass sees correct values
     /// even when the same buffer is updated multiple times per frame.
-    /// Includes a pipeline barrier (transfer write -> uniform read).
+    /// Includes a pipeline barrier (transfer write -> uniform and storage reads
+    /// of graphics and compute shaders).
     /// Must be called outside a render pass.
     fn cmd_update_buffer(&mut self, buffer: &Self::Buffer, data: &[u8]);
 
@@ -519,6 +1001,8 @@ pub trait GpuBackend: Sized + 'static {
     //  Render pass management
 
     /// Begin a render pass with the specified attachments and load operations.
+    /// Attachments are transitioned here as well, for passes recorded outside
+    /// of the render graph's barriers.
     fn begin_render_pass(&mut self, desc: &RenderPassDesc<Self>);
 
     /// End the current render pass.
@@ -561,11 +1045,33 @@ pub trait GpuBackend: Sized + 'static {
         draw_count: u32,
     );
 
-    /// Set the per-draw model matrix
-    fn set_model_matrix(&mut self, model: &glm::Mat4);
+    /// Set the per-draw model matrix, along with the one it was drawn with in the
+    /// previous frame for motion vectors
+    fn set_model_matrix(&mut self, model: &glm::Mat4, prev_model: &glm::Mat4);
 
     fn set_material_properties(&mut self, props: MaterialProperties);
 
+    //  Compute commands (valid outside a render pass)
+
+    /// Record a compute dispatch into the current frame.
+    fn cmd_dispatch(&mut self, dispatch: &ComputeDispatch<Self>, work_groups: (u32, u32, u32));
+
+    /// Record a compute dispatch whose work group counts are read from `args_buffer`
+    /// at `offset` (three `u32`s).
+    fn cmd_dispatch_indirect(
+        &mut self,
+        dispatch: &ComputeDispatch<Self>,
+        args_buffer: &Self::Buffer,
+        offset: u64,
+    );
+
+    /// Record a barrier between compute dispatches and other GPU work.
+    fn cmd_compute_barrier(&mut self, barrier: ComputeBarrier);
+
+    /// Record the barriers and layout transitions of render targets changing
+    /// access between passes. Must be called outside a render pass.
+    fn cmd_target_barriers(&mut self, barriers: &[TargetBarrier<Self>]);
+
     //  Accessors
 
     /// Get the current frame's backbuffer render target.
@@ -580,6 +1086,16 @@ pub trait GpuBackend: Sized + 'static {
     /// Get the current framebuffer resolution (width, height).
     fn resolution(&self) -> (u32, u32);
 
+    /// Highest MSAA sample count supported by color and depth targets alike.
+    fn max_sample_count(&self) -> u32 {
+        1
+    }
+
+    /// Whether textures of `format` can be created and sampled.
+    fn supports_texture_format(&self, format: TextureFormat) -> bool {
+        !format.is_compressed()
+    }
+
     /// Handle a window resize by reconfiguring the surface and recreating
     /// resolution-dependent resources (depth buffer, etc.).
     fn resize(&mut self, width: u32, height: u32);
@@ -587,6 +1103,15 @@ pub trait GpuBackend: Sized + 'static {
     /// wait for GPU to be idle
     fn wait_idle(&self) -> Result<(), GpuError>;
 
+    /// Copy the contents of a color render target back to the CPU.
+    ///
+    /// Waits for the GPU to go idle. Meant to be called between `end_frame()`
+    /// and `present()`, where the backbuffer still holds the finished frame.
+    fn read_render_target(
+        &mut self,
+        target: &Self::RenderTarget,
+    ) -> Result<TextureReadback, GpuError>;
+
     /// Render egui overlay on top of the scene.
     ///
     /// Called by the editor after the scene has been rendered.
@@ -607,6 +1132,18 @@ pub trait GpuBackend: Sized + 'static {
     /// End the current debug event region.
     fn end_event(&self) {}
 
+    /// GPU timings of the most recently completed frame, bracketed by the
+    /// `begin_event`/`end_event` regions. `None` if the backend has no GPU
+    /// profiler or no frame has completed yet.
+    fn gpu_timings(&self) -> Option<GpuFrameTimings> {
+        None
+    }
+
+    /// Device memory usage per heap. Empty if the backend does not track it.
+    fn memory_stats(&self) -> Vec<GpuMemoryHeapStats> {
+        Vec::new()
+    }
+
     // raytracing
     fn has_rt_support(&self) -> bool;
 
@@ -660,17 +1197,21 @@ pub trait GpuBackend: Sized + 'static {
 pub struct Shaders<B: GpuBackend> {
     pub deferred_pre: B::ShaderSource,
     pub shadow: B::ShaderSource,
+    pub ssao: B::ShaderSource,
+    pub ssao_blur: B::ShaderSource,
     pub deferred_light: B::ShaderSource,
     pub forward: B::ShaderSource,
+    pub taa: B::ShaderSource,
     pub output: B::ShaderSource,
     pub skybox: B::ShaderSource,
+    pub light_culling: B::ShaderSource,
 }
 
 pub struct ProceduralShaders<B: GpuBackend> {
     pub scattering: B::ShaderSource,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AccelerationStructureType {
     Blas,
     Tlas,
@@ -688,6 +1229,9 @@ pub struct RtShaders<B: GpuBackend> {
 
 pub struct MaterialProperties {
     pub has_parallax: bool,
+    /// Slot 0 holds a 2D array with the albedo, metallic-roughness and normal
+    /// layers instead of three separate textures.
+    pub layered: bool,
 }
 
 static MATERIAL_ID: AtomicUsize = AtomicUsize::new(0);
@@ -696,6 +1240,7 @@ static MATERIAL_ID: AtomicUsize = AtomicUsize::new(0);
 pub struct Material<B: GpuBackend> {
     textures: HashMap<u32, Rc<B::Texture>>,
     has_parallax: bool,
+    layered: bool,
     id: usize,
 }
 
@@ -704,6 +1249,7 @@ impl<B: GpuBackend> Clone for Material<B> {
         Self {
             textures: self.textures.clone(),
             has_parallax: self.has_parallax.clone(),
+            layered: self.layered,
             id: self.id.clone(),
         }
     }
@@ -714,6 +1260,7 @@ impl<B: GpuBackend> Material<B> {
         Material {
             textures: HashMap::new(),
             has_parallax: false,
+            layered: false,
             id: MATERIAL_ID.fetch_add(1, Ordering::SeqCst),
         }
     }
@@ -724,6 +1271,7 @@ impl<B: GpuBackend> Material<B> {
             backend.bind_texture(*slot, tex);
             backend.set_material_properties(MaterialProperties {
                 has_parallax: self.has_parallax,
+                layered: self.layered,
             });
         }
     }
@@ -739,9 +1287,17 @@ impl<B: GpuBackend> Material<B> {
         self.has_parallax = parallax;
     }
 
+    /// Sample the layers of the 2D array in slot 0 instead of slots 0 to 2.
+    pub fn set_layered(&mut self, layered: bool) {
+        self.layered = layered;
+    }
+
     /// Returns the bindless descriptor index for the albedo texture (slot 0),
-    /// or `u32::MAX` if no albedo texture is registered.
+    /// or `u32::MAX` if no albedo texture is registered or slot 0 holds layers.
     pub fn albedo_bindless_index(&self) -> u32 {
+        if self.layered {
+            return u32::MAX;
+        }
         self.textures
             .get(&0)
             .map(|t| t.bindless_index())
@@ -778,6 +1334,8 @@ pub struct Drawable<B: GpuBackend> {
     pub(crate) index_count: u32,
     pub(crate) model_buffer: Rc<B::Buffer>,
     model_matrix: glm::Mat4,
+    /// Model matrix of the previous frame.
+    prev_model_matrix: glm::Mat4,
     material: Material<B>,
     object_type: ObjType,
     double_sided: bool,
@@ -795,6 +1353,7 @@ impl<B: GpuBackend> Clone for Drawable<B> {
             index_count: self.index_count.clone(),
             model_buffer: self.model_buffer.clone(),
             model_matrix: self.model_matrix.clone(),
+            prev_model_matrix: self.prev_model_matrix,
             material: self.material.clone(),
             object_type: self.object_type.clone(),
             double_sided: self.double_sided.clone(),
@@ -851,6 +1410,7 @@ impl<B: GpuBackend> Drawable<B> {
             index_count: indices.len() as u32,
             model_buffer: Rc::new(model_buffer),
             model_matrix: identity,
+            prev_model_matrix: identity,
             material: Material::new(),
             object_type,
             double_sided: false,
@@ -865,11 +1425,17 @@ impl<B: GpuBackend> Drawable<B> {
         backend.update_buffer(&self.model_buffer, data);
     }
 
+    /// Make the current model matrix the previous frame's one, after a frame
+    /// has been drawn with it.
+    pub fn retire_model(&mut self) {
+        self.prev_model_matrix = self.model_matrix;
+    }
+
     /// Issue draw commands for this mesh.
     pub fn draw(&self, backend: &mut B, bind_material: bool) {
         backend.set_vertex_buffer(&self.vertex_buffer);
         backend.set_index_buffer(&self.index_buffer);
-        backend.set_model_matrix(&self.model_matrix);
+        backend.set_model_matrix(&self.model_matrix, &self.prev_model_matrix);
         backend.bind_uniform(ShaderStage::Vertex, 1, &self.model_buffer);
 
         if bind_material {
@@ -877,6 +1443,7 @@ impl<B: GpuBackend> Drawable<B> {
         }
         backend.set_material_properties(MaterialProperties {
             has_parallax: self.material.has_parallax,
+            layered: self.material.layered,
         });
 
         backend.draw_indexed(self.index_count, 0, 0);
@@ -899,6 +1466,10 @@ impl<B: GpuBackend> Drawable<B> {
         self.material.set_parallax(parallax);
     }
 
+    pub fn set_layered(&mut self, layered: bool) {
+        self.material.set_layered(layered);
+    }
+
     /// Add or replace a texture on this drawable's material.
     pub fn add_texture(&mut self, slot: u32, tex: Rc<B::Texture>) {
         self.material.add_texture(slot, tex);
@@ -1009,10 +1580,11 @@ impl<B: GpuBackend> IndirectDrawable<B> {
         }
         backend.set_material_properties(MaterialProperties {
             has_parallax: self.material.has_parallax,
+            layered: self.material.layered,
         });
 
         // Execute indirect draw — instance SSBO (binding 10) is bound once at load time
-        // via execute_compute_one_shot, not per-frame (avoids MoltenVK descriptor race).
+        // via bind_buffer_to_descriptor, not per-frame (avoids MoltenVK descriptor race).
         backend.draw_indexed_indirect(&self.indirect_command_buffer, 0, 1);
     }
 }
@@ -1,21 +1,21 @@
-/// Push constant layout for the scatter compute shader.
-///
-/// Shared between the Vulkan pipeline layout declaration (`create_compute_pipeline_layout`)
-/// and the dispatch call site (`execute_compute_one_shot`).
-///
-/// **Must stay in sync with `ComputePushConstants` in `scattering_comp.slang`.**
-/// Field order, types, and padding must match exactly — the struct is cast to raw
-/// bytes and written directly to `vkCmdPushConstants`.
-#[repr(C)]
-pub struct ComputePushConstants {
-    pub max_instances: u32,
-    pub asset_offset: u32,
-    pub max_height: f32,
-    pub spawn_height_min: f32,
-    pub spawn_height_max: f32,
-    pub slope_max: f32,
-    pub scale_min: f32,
-    pub scale_max: f32,
-    pub tilt_factor: f32,
-    pub terrain_segments_f: f32,
+use super::gpu_layout::gpu_struct;
+
+gpu_struct! {
+    /// Push constant layout for the scatter compute shader.
+    ///
+    /// Its size is declared as the pipeline's `push_constant_size` and the struct is
+    /// passed as raw bytes through `ComputeDispatch::push_constants`. The Slang side
+    /// is generated into `gpu_types.slang`.
+    pub struct ComputePushConstants: Std430 {
+        pub max_instances: u32,
+        pub asset_offset: u32,
+        pub max_height: f32,
+        pub spawn_height_min: f32,
+        pub spawn_height_max: f32,
+        pub slope_max: f32,
+        pub scale_min: f32,
+        pub scale_max: f32,
+        pub tilt_factor: f32,
+        pub terrain_segments_f: f32,
+    }
 }
@@ -1,26 +1,40 @@
 //! Render pass programs for the sparkle-rs deferred+forward pipeline.
 //!
-//! Each pass struct bundles a GPU pipeline, uniform buffers, and render targets.
+//! Each pass struct bundles a GPU pipeline and implements [`GraphPass`]: it declares
+//! the render graph resources it reads and writes, the graph owns the targets.
 //! All types are generic over [`GpuBackend`] for backend-agnostic rendering.
 //!
 //! The rendering pipeline consists of:
-//! 1. **DeferredPassPre** — G-buffer fill (position, normal+roughness, albedo+metallic MRT)
-//! 2. **SsaoPass** — Screen-space ambient occlusion (SSAO + blur sub-passes)
-//! 3. **ShadowPass** — Directional shadow map generation
-//! 4. **DeferredPassLight** — Fullscreen deferred lighting (reads SSAO result)
-//! 5. **ForwardPass** — Transparent object rendering with forward lighting
-//! 6. **OutputPass** — Composite deferred + forward results to backbuffer
-//! 7. **SkyBoxPass** — Skybox rendering
+//! 1. **DeferredPassPre** — G-buffer fill (position, normal+roughness, albedo+metallic,
+//!    velocity MRT) (with MSAA, **MsaaDepthPass** also fills a multisampled depth buffer)
+//! 2. **LightCullingPass** — Compute pass binning the lights into view-space clusters
+//! 3. **ShadowPass** — Shadow atlas generation for all shadowed lights
+//! 4. **SsaoPass** / **SsaoBlurPass** — Screen-space ambient occlusion of the G-buffer
+//!    and its blur (only while SSAO is enabled)
+//! 5. **DeferredPassLight** — Fullscreen deferred lighting by the lights of each cluster
+//!    (reads the SSAO result)
+//! 6. **ForwardPass** — Transparent object rendering with clustered forward lighting
+//! 7. **TaaPass** — Temporal anti-aliasing: composites deferred + forward results and
+//!    blends them with the reprojected history (only while TAA is enabled)
+//! 8. **OutputPass** — Tonemap the TAA result, or composite deferred + forward
+//!    results, to the backbuffer
+//! 9. **SkyBoxPass** — Skybox rendering, after the resolve with an unjittered projection
+//! 10. **EguiPass** — Editor overlay
+//!
+//! With ray tracing support, **RtShadowPass** replaces the shadow maps.
 //!
 //! Each pass exposes:
 //! - `create(backend, ...)` — construct from a backend and shader bytecode
 //! - `prepare_draw(backend)` — bind the pipeline and uniforms for drawing
 //! - `update(backend)` — upload CPU-side uniform data to GPU buffers
 //! - Setters for CPU-side uniform data (view, proj, light, etc.)
-//! - Accessors for render targets used by subsequent passes
 
 use super::backend::*;
-use super::geometry::{Light, LightType};
+use super::geometry::{Light, LightType, MAX_SHADOW_VIEWS};
+use super::gpu_layout::{Pad, gpu_struct};
+use super::render_graph::*;
+use super::settings::SsaoSettings;
+use super::shader_reload::ReloadedShaders;
 
 // Uniform data structs (CPU-side, #[repr(C)] for GPU upload)
 
@@ -34,12 +48,17 @@ pub(crate) struct ViewProjUniforms {
     pub inv_proj: glm::Mat4,
 }
 
-/// Camera position and SSAO toggle — used by lighting pixel shaders.
-#[repr(C)]
-#[derive(Clone, Copy)]
-pub(crate) struct CameraUniforms {
-    pub camera_pos: glm::Vec3,
-    pub ssao: u32,
+gpu_struct! {
+    /// Camera position and SSAO/TAA toggles — used by the lighting and output
+    /// pixel shaders.
+    #[derive(Clone, Copy)]
+    pub(crate) struct CameraUniforms: Std140 {
+        pub camera_pos: glm::Vec3,
+        pub ssao: u32,
+        /// The output pass reads the TAA result in slot 0 instead of compositing.
+        pub taa: u32,
+        pub _pad: Pad<3>,
+    }
 }
 
 /// Near/far plane distances — used by deferred pre-pass pixel shader.
@@ -59,28 +78,105 @@ pub(crate) struct LightSpaceUniforms {
     pub light_space_matrix: glm::Mat4,
 }
 
-/// GPU-side light data, matching the shader cbuffer `std140` layout.
-///
-/// std140 aligns `float3` members to 16 bytes, so `_pad: float3` in the shader
-/// sits at offset 48 (not 36), pushing `lightSpace` to offset 64. Total = 128 bytes.
-///
-/// Layout (128 bytes):
-/// - offset  0: `position: Vec3` (12) + `t: u32` (4)          = 16 bytes
-/// - offset 16: `color: Vec3` (12)    + `radius: f32` (4)      = 16 bytes
-/// - offset 32: `penumbra_radius: f32` (4) + `_pad: [f32; 3]`  = 16 bytes (repr(C), ends at 48)
-/// - offset 48: `_pad2: [f32; 4]` (16)                         = 16 bytes (bridges to offset 64)
-/// - offset 64: `light_space: Mat4` (64)                        = 64 bytes
-#[repr(C)]
-#[derive(Clone, Copy)]
-pub(crate) struct GpuLight {
-    position: glm::Vec3,
-    t: u32,
-    color: glm::Vec3,
-    radius: f32,
-    penumbra_radius: f32,
-    _pad: [f32; 3],
-    _pad2: [f32; 4],
-    light_space: glm::Mat4,
+gpu_struct! {
+    /// Cluster grid of the view frustum (binding 2) — used by the light culling
+    /// shader to build the cluster light lists and by the lighting pixel shaders
+    /// to find the cluster of a pixel.
+    ///
+    /// The grid has `grid_x * grid_y` screen tiles and `grid_z` depth slices,
+    /// spaced logarithmically between `near` and `far`: a view-space depth `d`
+    /// falls into slice `floor(log(d) * z_scale - z_bias)`.
+    #[derive(Clone, Copy)]
+    pub(crate) struct ClusterUniforms: Std140 {
+        pub view: glm::Mat4,
+        pub inv_proj as "invProj": glm::Mat4,
+        pub screen_size as "screenSize": glm::Vec2,
+        pub near: f32,
+        pub far: f32,
+        pub z_scale as "zScale": f32,
+        pub z_bias as "zBias": f32,
+        /// Number of lights in the light storage buffer.
+        pub light_count as "lightCount": u32,
+        pub max_cluster_lights as "maxClusterLights": u32,
+        pub grid_x as "gridX": u32,
+        pub grid_y as "gridY": u32,
+        pub grid_z as "gridZ": u32,
+        pub _pad: Pad<1>,
+    }
+}
+
+impl ClusterUniforms {
+    /// The cluster grid of a camera with the given view and projection.
+    pub fn new(
+        view: glm::Mat4,
+        proj: &glm::Mat4,
+        screen_size: (u32, u32),
+        near: f32,
+        far: f32,
+        light_count: usize,
+    ) -> Self {
+        let [grid_x, grid_y, grid_z] = CLUSTER_GRID;
+        let log_range = (far / near).ln();
+        ClusterUniforms {
+            view,
+            inv_proj: glm::inverse(proj),
+            screen_size: glm::vec2(screen_size.0 as f32, screen_size.1 as f32),
+            near,
+            far,
+            z_scale: grid_z as f32 / log_range,
+            z_bias: grid_z as f32 * near.ln() / log_range,
+            light_count: light_count.min(MAX_LIGHTS) as u32,
+            max_cluster_lights: MAX_CLUSTER_LIGHTS,
+            grid_x,
+            grid_y,
+            grid_z,
+            _pad: Pad::ZERO,
+        }
+    }
+}
+
+gpu_struct! {
+    /// GPU-side light data, `Light` in the shaders. An element of the light
+    /// storage buffer (binding 17) and of the RT light array (`std430`, same
+    /// offsets as `std140`).
+    ///
+    /// Layout (464 bytes):
+    /// - offset  0: `position: Vec3` (12) + `t: u32` (4)          = 16 bytes
+    /// - offset 16: `color: Vec3` (12)    + `radius: f32` (4)      = 16 bytes
+    /// - offset 32: `direction: Vec3` (12) + `range: f32` (4)      = 16 bytes
+    /// - offset 48: `penumbra_radius: f32` (4) + `shadow_view_count: u32` (4)
+    ///   + `cascade_blend: f32` (4) + `spot_scale: f32` (4)        = 16 bytes
+    /// - offset 64: `spot_offset: f32` (4) + `shadow_tile: u32` (4)
+    ///   + `shadow_cube: u32` (4) + `_pad: Pad<1>` (4)             = 16 bytes
+    /// - offset 80: `light_space: [Mat4; 6]` (384)                 = 384 bytes
+    #[derive(Clone, Copy)]
+    pub(crate) struct GpuLight as "Light": Std140 {
+        position: glm::Vec3,
+        t as "type": u32,
+        color: glm::Vec3,
+        radius: f32,
+        direction: glm::Vec3,
+        range: f32,
+        /// Physical radius of the light source for RT shadow cone spread, decoupled
+        /// from `radius` (attenuation) so both can be tuned independently.
+        penumbra_radius: f32,
+        shadow_view_count as "shadowViewCount": u32,
+        /// Width of the band at a cascade's border blended with the next cascade,
+        /// as a fraction of the cascade.
+        cascade_blend as "cascadeBlend": f32,
+        /// Spot cone falloff `saturate(dot(direction, -L) * spot_scale + spot_offset)`,
+        /// 1 inside the inner cone and 0 outside the outer cone.
+        spot_scale as "spotScale": f32,
+        spot_offset as "spotOffset": f32,
+        /// Shadow atlas tile of the first shadow map view, the others follow it.
+        /// `u32::MAX` if the light casts no shadows.
+        shadow_tile as "shadowTile": u32,
+        /// Cube of a point light in the shadow cube map array, `u32::MAX` if the
+        /// light casts no shadows.
+        shadow_cube as "shadowCube": u32,
+        _pad: Pad<1>,
+        light_space as "lightSpace": [glm::Mat4; MAX_SHADOW_VIEWS],
+    }
 }
 
 impl GpuLight {
@@ -89,16 +185,28 @@ impl GpuLight {
             LightType::Ambient => 0u32,
             LightType::Directional => 1u32,
             LightType::Area => 2u32,
+            LightType::Point => 3u32,
+            LightType::Spot => 4u32,
         };
+        let cos_inner = light.inner_angle.cos();
+        let cos_outer = light.outer_angle.cos();
+        let spot_scale = 1.0 / (cos_inner - cos_outer).max(0.001);
         GpuLight {
             position: light.position,
             t,
             color: light.color,
             radius: light.radius,
+            direction: light.direction.normalize(),
+            range: light.range,
             penumbra_radius: light.penumbra_radius,
-            _pad: [0.0; 3],
-            _pad2: [0.0; 4],
-            light_space: light.light_proj,
+            shadow_view_count: light.shadow_view_count,
+            cascade_blend: SHADOW_CASCADE_BLEND,
+            spot_scale,
+            spot_offset: -cos_outer * spot_scale,
+            shadow_tile: light.shadow_tile.unwrap_or(u32::MAX),
+            shadow_cube: light.shadow_cube.unwrap_or(u32::MAX),
+            _pad: Pad::ZERO,
+            light_space: light.shadow_views,
         }
     }
 }
@@ -116,11 +224,15 @@ fn hash_float(i: u32) -> f32 {
     (hash_u32(i) as f32) / (u32::MAX as f32)
 }
 
-/// Generate 32 hemisphere-distributed sample points for SSAO.
-/// Samples are weighted toward the center for better quality.
-fn generate_ssao_kernel() -> [[f32; 4]; 32] {
-    let mut kernel = [[0.0f32; 4]; 32];
-    for i in 0..32 {
+/// Hemisphere samples of the SSAO kernel, the most [`SsaoSettings::samples`] can ask for.
+pub(crate) const SSAO_KERNEL_SIZE: usize = 32;
+
+/// Generate `count` hemisphere-distributed sample points for SSAO, the rest
+/// of the kernel stays zero. Samples are weighted toward the center for better
+/// quality, spread over the whole radius for any `count`.
+fn generate_ssao_kernel(count: usize) -> [[f32; 4]; SSAO_KERNEL_SIZE] {
+    let mut kernel = [[0.0f32; 4]; SSAO_KERNEL_SIZE];
+    for (i, sample) in kernel.iter_mut().take(count).enumerate() {
         let theta = 2.0 * std::f32::consts::PI * hash_float(i as u32 * 2);
         let cos_phi = hash_float(i as u32 * 2 + 1);
         let sin_phi = (1.0 - cos_phi * cos_phi).sqrt();
@@ -130,12 +242,13 @@ fn generate_ssao_kernel() -> [[f32; 4]; 32] {
         let mut z = cos_phi;
 
         // Scale: more samples closer to the surface
-        let scale = 0.1 + 0.9 * (i as f32 / 32.0) * (i as f32 / 32.0);
+        let t = i as f32 / count as f32;
+        let scale = 0.1 + 0.9 * t * t;
         x *= scale;
         y *= scale;
         z *= scale;
 
-        kernel[i] = [x, y, z, 0.0];
+        *sample = [x, y, z, 0.0];
     }
     kernel
 }
@@ -155,26 +268,148 @@ fn generate_ssao_noise() -> Vec<u8> {
     noise
 }
 
-// SsaoPass
+gpu_struct! {
+    /// SSAO uniforms (binding 19) — used by the SSAO pixel shader.
+    ///
+    /// Layout (672 bytes):
+    /// - `projection: Mat4` (64 bytes)
+    /// - `view: Mat4` (64 bytes)
+    /// - `resolution: [f32; 2]` (8 bytes)
+    /// - `radius: f32` (4 bytes)
+    /// - `bias: f32` (4 bytes)
+    /// - `intensity: f32` (4 bytes) + `sample_count: u32` (4 bytes) + `_pad: Pad<2>` (8 bytes)
+    /// - `kernel: [[f32; 4]; 32]` (512 bytes)
+    #[derive(Clone, Copy)]
+    pub(crate) struct SsaoUniforms: Std140 {
+        projection: glm::Mat4,
+        view: glm::Mat4,
+        resolution: [f32; 2],
+        radius: f32,
+        bias: f32,
+        intensity: f32,
+        /// Kernel samples taken per pixel, at most [`SSAO_KERNEL_SIZE`].
+        sample_count as "sampleCount": u32,
+        _pad: Pad<2>,
+        kernel: [[f32; 4]; SSAO_KERNEL_SIZE],
+    }
+}
 
-/// SSAO uniforms matching the WGSL struct layout.
-///
-/// Layout (656 bytes):
-/// - `projection: Mat4` (64 bytes)
-/// - `view: Mat4` (64 bytes)
-/// - `resolution: [f32; 2]` (8 bytes)
-/// - `radius: f32` (4 bytes)
-/// - `bias: f32` (4 bytes)
-/// - `kernel: [[f32; 4]; 32]` (512 bytes)
-#[repr(C)]
-#[derive(Clone, Copy)]
-struct SsaoUniforms {
-    projection: glm::Mat4,
-    view: glm::Mat4,
-    resolution: [f32; 2],
-    radius: f32,
-    bias: f32,
-    kernel: [[f32; 4]; 32],
+impl SsaoUniforms {
+    /// The SSAO parameters of a camera with the given view and projection.
+    pub fn new(
+        view: glm::Mat4,
+        projection: glm::Mat4,
+        resolution: (u32, u32),
+        settings: &SsaoSettings,
+    ) -> Self {
+        let sample_count = settings.samples.clamp(1, SSAO_KERNEL_SIZE as u32);
+        SsaoUniforms {
+            projection,
+            view,
+            resolution: [resolution.0 as f32, resolution.1 as f32],
+            radius: settings.radius,
+            bias: settings.bias,
+            intensity: settings.intensity,
+            sample_count,
+            _pad: Pad::ZERO,
+            kernel: generate_ssao_kernel(sample_count as usize),
+        }
+    }
+}
+
+gpu_struct! {
+    /// TAA uniforms (binding 20) — used by the deferred pre-pass vertex shader
+    /// for the motion vectors and by the TAA pixel shader.
+    ///
+    /// Both view-projections are without the sub-pixel jitter, so the motion
+    /// vectors only hold the movement of the camera and the objects.
+    #[derive(Clone, Copy)]
+    pub(crate) struct TaaUniforms: Std140 {
+        pub view_proj as "viewProj": glm::Mat4,
+        pub prev_view_proj as "prevViewProj": glm::Mat4,
+        /// Weight of the history in the resolve.
+        pub feedback: f32,
+        /// 0 while the history holds nothing to blend with (first frame, resize,
+        /// camera cut), the resolve then outputs the current frame.
+        pub history_valid as "historyValid": u32,
+        pub _pad: Pad<2>,
+    }
+}
+
+/// Weight of the history in the TAA resolve, the rest is the current frame.
+pub(crate) const TAA_FEEDBACK: f32 = 0.9;
+
+// Render graph resources
+
+/// G-buffer world-space positions (deferred pre-pass output).
+pub const GBUFFER_POSITIONS: ResourceName = "gbuffer_positions";
+/// G-buffer normals + roughness (deferred pre-pass output).
+pub const GBUFFER_NORMAL_ROUGHNESS: ResourceName = "gbuffer_normal_roughness";
+/// G-buffer albedo + metallic (deferred pre-pass output).
+pub const GBUFFER_ALBEDO_METALLIC: ResourceName = "gbuffer_albedo_metallic";
+/// G-buffer motion vectors: screen UV offset since the previous frame (deferred pre-pass output).
+pub const GBUFFER_VELOCITY: ResourceName = "gbuffer_velocity";
+/// Ambient occlusion of the opaque geometry, before the blur.
+pub const SSAO_RAW: ResourceName = "ssao_raw";
+/// Blurred ambient occlusion of the opaque geometry.
+pub const SSAO: ResourceName = "ssao";
+/// Shadow atlas of the shadowed directional, spot and area lights.
+pub const SHADOW_MAP: ResourceName = "shadow_map";
+/// Shadow cube map array of the shadowed point lights.
+pub const SHADOW_CUBES: ResourceName = "shadow_cubes";
+/// Ray traced shadow visibility for all lights.
+pub const RT_SHADOWS: ResourceName = "rt_shadows";
+/// HDR light accumulation of the opaque geometry.
+pub const DEFERRED_LIGHT: ResourceName = "deferred_light";
+/// HDR light accumulation of the transparent geometry.
+pub const FORWARD: ResourceName = "forward";
+/// Anti-aliased HDR composite, the history of the next frame's TAA resolve.
+pub const TAA_RESOLVED: ResourceName = "taa_resolved";
+/// Multisampled depth of the opaque and transparent geometry (MSAA only).
+pub const MSAA_DEPTH: ResourceName = "msaa_depth";
+/// Multisampled forward accumulation, resolved into [`FORWARD`] (MSAA only).
+pub const MSAA_FORWARD: ResourceName = "msaa_forward";
+/// Multisampled final composite, resolved into the backbuffer (MSAA only).
+pub const MSAA_COMPOSITE: ResourceName = "msaa_composite";
+
+/// Pass name of [`ShadowPass`].
+pub const SHADOW_PASS: &str = "shadow";
+/// Pass name of [`RtShadowPass`].
+pub const RT_SHADOW_PASS: &str = "rt_shadows";
+/// Pass name of [`LightCullingPass`].
+pub const LIGHT_CULLING_PASS: &str = "light_culling";
+/// Pass name of [`SsaoPass`].
+pub const SSAO_PASS: &str = "ssao";
+/// Pass name of [`SsaoBlurPass`].
+pub const SSAO_BLUR_PASS: &str = "ssao_blur";
+/// Pass name of [`TaaPass`].
+pub const TAA_PASS: &str = "taa";
+
+/// Maximum number of lights supported by the RT shadow pipeline.
+pub(crate) const MAX_RT_LIGHTS: usize = 12;
+
+/// Capacity of the light storage buffer; further scene lights are ignored.
+pub(crate) const MAX_LIGHTS: usize = 256;
+/// Clusters of the view frustum along x and y (screen tiles) and z (depth slices).
+pub(crate) const CLUSTER_GRID: [u32; 3] = [16, 9, 24];
+/// Number of clusters of the grid.
+pub(crate) const CLUSTER_COUNT: usize =
+    (CLUSTER_GRID[0] * CLUSTER_GRID[1] * CLUSTER_GRID[2]) as usize;
+/// Lights binned into one cluster at most, the others are dropped.
+pub(crate) const MAX_CLUSTER_LIGHTS: u32 = 128;
+/// Size of the cluster light lists (binding 18): per cluster, the light count
+/// followed by `MAX_CLUSTER_LIGHTS` light indices.
+pub(crate) const CLUSTER_LIGHTS_SIZE: usize =
+    CLUSTER_COUNT * (1 + MAX_CLUSTER_LIGHTS as usize) * std::mem::size_of::<u32>();
+
+/// Sample the shadows of the lights: the RT visibility (slot 4) when the RT
+/// pass runs, the shadow atlas (slot 3) and cube maps (slot 6) otherwise.
+fn sample_shadows(builder: &mut PassBuilder) {
+    if builder.is_enabled(RT_SHADOW_PASS) {
+        builder.sample(RT_SHADOWS, 4);
+    } else if builder.is_enabled(SHADOW_PASS) {
+        builder.sample(SHADOW_MAP, 3).sample(SHADOW_CUBES, 6);
+    }
 }
 
 // ForwardPass
@@ -183,19 +418,17 @@ struct SsaoUniforms {
 ///
 /// Vertex uniforms (slot 0): view + projection matrices.
 /// Pixel uniforms (slot 0): camera position + SSAO flag.
-/// Pixel uniforms (slot 1): light data.
-/// Output: `Rgba32Float` render target.
+/// Each fragment is lit by the lights of its cluster (bindings 2, 17 and 18).
+/// Output: [`FORWARD`]; tests and writes the main depth.
+/// With MSAA it renders into [`MSAA_FORWARD`] against [`MSAA_DEPTH`] and
+/// resolves both into [`FORWARD`] and the main depth.
 pub(crate) struct ForwardPass<B: GpuBackend> {
     pipeline: B::Pipeline,
     pipeline_double_sided: B::Pipeline, // Shared UBOs are bound globally
-    render_target: B::RenderTarget,
+    sample_count: u32,
 }
 
 impl<B: GpuBackend> ForwardPass<B> {
-    pub fn render_target(&self) -> &B::RenderTarget {
-        &self.render_target
-    }
-
     /// Bind pipeline and all uniform buffers for drawing.
     pub fn prepare_draw(&self, backend: &mut B) {
         backend.set_pipeline(&self.pipeline);
@@ -213,8 +446,8 @@ impl<B: GpuBackend> ForwardPass<B> {
 
     pub fn create(
         backend: &B,
-        resolution: (u32, u32),
         shader_source: &B::ShaderSource,
+        sample_count: u32,
     ) -> Result<Self, GpuError> {
         let pipeline = backend.create_render_pipeline(&RenderPipelineDesc {
             label: "forward_pass",
@@ -226,6 +459,8 @@ impl<B: GpuBackend> ForwardPass<B> {
             depth_compare: CompareFunc::LessEqual,
             color_target_formats: &[TextureFormat::R16g16b16a16Float],
             depth_format: Some(TextureFormat::Depth32Float),
+            stencil: None,
+            sample_count,
         })?;
 
         let pipeline_double_sided = backend.create_render_pipeline(&RenderPipelineDesc {
@@ -238,64 +473,89 @@ impl<B: GpuBackend> ForwardPass<B> {
             depth_compare: CompareFunc::LessEqual,
             color_target_formats: &[TextureFormat::R16g16b16a16Float],
             depth_format: Some(TextureFormat::Depth32Float),
-        })?;
-
-        let render_target = backend.create_render_target(&RenderTargetDesc {
-            width: resolution.0,
-            height: resolution.1,
-            format: TextureFormat::R16g16b16a16Float,
-            sampler: SamplerDesc::default(),
-            usage: RenderTargetUsage::Color,
+            stencil: None,
+            sample_count,
         })?;
 
         Ok(ForwardPass {
             pipeline,
             pipeline_double_sided,
-            render_target,
+            sample_count,
         })
     }
+}
 
-    /// Recreate resolution-dependent render targets after a window resize.
-    pub fn resize(&mut self, backend: &B, resolution: (u32, u32)) -> Result<(), GpuError> {
-        self.render_target = backend.create_render_target(&RenderTargetDesc {
-            width: resolution.0,
-            height: resolution.1,
-            format: TextureFormat::R16g16b16a16Float,
-            sampler: SamplerDesc::default(),
-            usage: RenderTargetUsage::Color,
-        })?;
+impl<B: GpuBackend> GraphPass<B> for ForwardPass<B> {
+    fn name(&self) -> &'static str {
+        "forward"
+    }
+
+    fn setup(&self, builder: &mut PassBuilder) {
+        let forward = TargetDesc::color(TextureFormat::R16g16b16a16Float);
+        builder.create(FORWARD, forward);
+        if self.sample_count > 1 {
+            builder
+                .create(MSAA_FORWARD, forward.multisampled(self.sample_count))
+                .write_color(MSAA_FORWARD, LoadPolicy::Clear, [0.0, 0.0, 0.0, 0.0])
+                .resolve(MSAA_FORWARD, FORWARD)
+                .write_depth(MSAA_DEPTH, LoadPolicy::Load, 1.0)
+                .resolve(MSAA_DEPTH, MAIN_DEPTH);
+        } else {
+            builder
+                .write_color(FORWARD, LoadPolicy::Clear, [0.0, 0.0, 0.0, 0.0])
+                .write_depth(MAIN_DEPTH, LoadPolicy::Load, 1.0);
+        }
+        sample_shadows(builder);
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext<'_, B>) -> Result<(), GpuError> {
+        self.prepare_draw(ctx.backend);
+        if let Ok(drawables) = ctx.frame.scene.traverse() {
+            let mut last_ds: Option<bool> = None;
+            for drawable in drawables {
+                if drawable.object_type() != ObjType::Transparent {
+                    continue;
+                }
+                let ds = drawable.is_double_sided();
+                if last_ds != Some(ds) {
+                    self.set_pipeline_for(ctx.backend, ds);
+                    last_ds = Some(ds);
+                }
+                drawable.draw(ctx.backend, true);
+            }
+        }
+        Ok(())
+    }
+
+    fn reload_shaders(
+        &mut self,
+        backend: &B,
+        shaders: &ReloadedShaders<B>,
+    ) -> Result<(), GpuError> {
+        if let Some(source) = &shaders.forward {
+            *self = Self::create(backend, source, self.sample_count)?;
+        }
         Ok(())
     }
 }
 
 // DeferredPassPre
 
-/// Deferred pre-pass: fills the G-buffer with position, normal, roughness, albedo, and metallic data.
+/// Deferred pre-pass: fills the G-buffer with position, normal, roughness, albedo, metallic
+/// and velocity data.
 ///
-/// Vertex uniforms (slot 0): view + projection matrices.
+/// Vertex uniforms (slot 0): view + projection matrices; the motion vectors use the
+/// unjittered current and previous view-projections (binding 20) and the previous
+/// model matrices of the moving draws (binding 21, indexed from the push constants).
 /// Pixel uniforms (slot 0): near/far plane distances.
-/// Output: three float MRT targets (position, normal+roughness, albedo+metallic).
+/// Output: four float MRT targets ([`GBUFFER_POSITIONS`], [`GBUFFER_NORMAL_ROUGHNESS`],
+/// [`GBUFFER_ALBEDO_METALLIC`], [`GBUFFER_VELOCITY`]) and the main depth.
 pub(crate) struct DeferredPassPre<B: GpuBackend> {
     pipeline: B::Pipeline,
     pipeline_double_sided: B::Pipeline,
-    positions_target: B::RenderTarget,
-    normal_roughness_target: B::RenderTarget,
-    albedo_metallic_target: B::RenderTarget,
 }
 
 impl<B: GpuBackend> DeferredPassPre<B> {
-    pub fn positions(&self) -> &B::RenderTarget {
-        &self.positions_target
-    }
-
-    pub fn normal_roughness(&self) -> &B::RenderTarget {
-        &self.normal_roughness_target
-    }
-
-    pub fn albedo_metallic(&self) -> &B::RenderTarget {
-        &self.albedo_metallic_target
-    }
-
     /// Bind pipeline and all uniform buffers for drawing.
     pub fn prepare_draw(&self, backend: &mut B) {
         backend.set_pipeline(&self.pipeline);
@@ -312,11 +572,7 @@ impl<B: GpuBackend> DeferredPassPre<B> {
         }
     }
 
-    pub fn create(
-        backend: &B,
-        resolution: (u32, u32),
-        shader_source: &B::ShaderSource,
-    ) -> Result<Self, GpuError> {
+    pub fn create(backend: &B, shader_source: &B::ShaderSource) -> Result<Self, GpuError> {
         let pipeline = backend.create_render_pipeline(&RenderPipelineDesc {
             label: "deferred_pre",
             shader_source,
@@ -329,8 +585,11 @@ impl<B: GpuBackend> DeferredPassPre<B> {
                 TextureFormat::Rgba32Float,
                 TextureFormat::R16g16b16a16Float,
                 TextureFormat::R16g16b16a16Float,
+                TextureFormat::R16g16b16a16Float,
             ],
             depth_format: Some(TextureFormat::Depth32Float),
+            stencil: None,
+            sample_count: 1,
         })?;
 
         let pipeline_double_sided = backend.create_render_pipeline(&RenderPipelineDesc {
@@ -345,64 +604,415 @@ impl<B: GpuBackend> DeferredPassPre<B> {
                 TextureFormat::Rgba32Float,
                 TextureFormat::R16g16b16a16Float,
                 TextureFormat::R16g16b16a16Float,
+                TextureFormat::R16g16b16a16Float,
             ],
             depth_format: Some(TextureFormat::Depth32Float),
+            stencil: None,
+            sample_count: 1,
         })?;
 
-        let positions_target = backend.create_render_target(&RenderTargetDesc {
-            width: resolution.0,
-            height: resolution.1,
-            format: TextureFormat::Rgba32Float,
-            sampler: SamplerDesc::default(),
-            usage: RenderTargetUsage::Color,
-        })?;
-        let normal_roughness_target = backend.create_render_target(&RenderTargetDesc {
-            width: resolution.0,
-            height: resolution.1,
-            format: TextureFormat::R16g16b16a16Float,
-            sampler: SamplerDesc::default(),
-            usage: RenderTargetUsage::Color,
+        Ok(DeferredPassPre {
+            pipeline,
+            pipeline_double_sided,
+        })
+    }
+}
+
+impl<B: GpuBackend> GraphPass<B> for DeferredPassPre<B> {
+    fn name(&self) -> &'static str {
+        "deferred_pre"
+    }
+
+    fn setup(&self, builder: &mut PassBuilder) {
+        let clear = [0.0, 0.0, 0.0, 0.0];
+        builder
+            .create(
+                GBUFFER_POSITIONS,
+                TargetDesc::color(TextureFormat::Rgba32Float),
+            )
+            .create(
+                GBUFFER_NORMAL_ROUGHNESS,
+                TargetDesc::color(TextureFormat::R16g16b16a16Float),
+            )
+            .create(
+                GBUFFER_ALBEDO_METALLIC,
+                TargetDesc::color(TextureFormat::R16g16b16a16Float),
+            )
+            .create(
+                GBUFFER_VELOCITY,
+                TargetDesc::color(TextureFormat::R16g16b16a16Float),
+            )
+            .write_color(GBUFFER_POSITIONS, LoadPolicy::Clear, clear)
+            .write_color(GBUFFER_NORMAL_ROUGHNESS, LoadPolicy::Clear, clear)
+            .write_color(GBUFFER_ALBEDO_METALLIC, LoadPolicy::Clear, clear)
+            .write_color(GBUFFER_VELOCITY, LoadPolicy::Clear, clear)
+            .write_depth(MAIN_DEPTH, LoadPolicy::Clear, 1.0);
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext<'_, B>) -> Result<(), GpuError> {
+        self.prepare_draw(ctx.backend);
+        // Per-drawable pipeline switching for double-sided materials
+        if let Ok(drawables) = ctx.frame.scene.traverse() {
+            let mut last_ds: Option<bool> = None;
+            for drawable in drawables {
+                if drawable.object_type() != ObjType::Opaque {
+                    continue;
+                }
+                let ds = drawable.is_double_sided();
+                if last_ds != Some(ds) {
+                    self.set_pipeline_for(ctx.backend, ds);
+                    last_ds = Some(ds);
+                }
+                drawable.draw(ctx.backend, true);
+            }
+        }
+        Ok(())
+    }
+
+    fn reload_shaders(
+        &mut self,
+        backend: &B,
+        shaders: &ReloadedShaders<B>,
+    ) -> Result<(), GpuError> {
+        if let Some(source) = &shaders.deferred_pre {
+            *self = Self::create(backend, source)?;
+        }
+        Ok(())
+    }
+}
+
+// MsaaDepthPass
+
+/// Multisampled depth pre-pass: renders the opaque geometry into [`MSAA_DEPTH`]
+/// so the MSAA forward and skybox passes are occluded by it. Only part of the
+/// graph when MSAA is enabled.
+///
+/// Uses the deferred pre-pass shaders without color attachments.
+pub(crate) struct MsaaDepthPass<B: GpuBackend> {
+    pipeline: B::Pipeline,
+    pipeline_double_sided: B::Pipeline,
+    sample_count: u32,
+}
+
+impl<B: GpuBackend> MsaaDepthPass<B> {
+    /// Switch pipeline based on whether the drawable is double-sided.
+    pub fn set_pipeline_for(&self, backend: &mut B, double_sided: bool) {
+        if double_sided {
+            backend.set_pipeline(&self.pipeline_double_sided);
+        } else {
+            backend.set_pipeline(&self.pipeline);
+        }
+    }
+
+    pub fn create(
+        backend: &B,
+        shader_source: &B::ShaderSource,
+        sample_count: u32,
+    ) -> Result<Self, GpuError> {
+        let pipeline = backend.create_render_pipeline(&RenderPipelineDesc {
+            label: "msaa_depth",
+            shader_source,
+            vertex_layout: Some(standard_vertex_layout()),
+            blend_mode: BlendMode::None,
+            cull_mode: CullMode::Back,
+            depth_write: true,
+            depth_compare: CompareFunc::LessEqual,
+            color_target_formats: &[],
+            depth_format: Some(TextureFormat::Depth32Float),
+            stencil: None,
+            sample_count,
         })?;
-        let albedo_metallic_target = backend.create_render_target(&RenderTargetDesc {
-            width: resolution.0,
-            height: resolution.1,
-            format: TextureFormat::R16g16b16a16Float,
-            sampler: SamplerDesc::default(),
-            usage: RenderTargetUsage::Color,
+
+        let pipeline_double_sided = backend.create_render_pipeline(&RenderPipelineDesc {
+            label: "msaa_depth_double_sided",
+            shader_source,
+            vertex_layout: Some(standard_vertex_layout()),
+            blend_mode: BlendMode::None,
+            cull_mode: CullMode::None,
+            depth_write: true,
+            depth_compare: CompareFunc::LessEqual,
+            color_target_formats: &[],
+            depth_format: Some(TextureFormat::Depth32Float),
+            stencil: None,
+            sample_count,
         })?;
 
-        Ok(DeferredPassPre {
+        Ok(MsaaDepthPass {
             pipeline,
             pipeline_double_sided,
-            positions_target,
-            normal_roughness_target,
-            albedo_metallic_target,
+            sample_count,
         })
     }
+}
 
-    /// Recreate resolution-dependent render targets after a window resize.
-    pub fn resize(&mut self, backend: &B, resolution: (u32, u32)) -> Result<(), GpuError> {
-        self.positions_target = backend.create_render_target(&RenderTargetDesc {
-            width: resolution.0,
-            height: resolution.1,
-            format: TextureFormat::Rgba32Float,
-            sampler: SamplerDesc::default(),
-            usage: RenderTargetUsage::Color,
-        })?;
-        self.normal_roughness_target = backend.create_render_target(&RenderTargetDesc {
-            width: resolution.0,
-            height: resolution.1,
-            format: TextureFormat::R16g16b16a16Float,
-            sampler: SamplerDesc::default(),
-            usage: RenderTargetUsage::Color,
+impl<B: GpuBackend> GraphPass<B> for MsaaDepthPass<B> {
+    fn name(&self) -> &'static str {
+        "msaa_depth"
+    }
+
+    fn setup(&self, builder: &mut PassBuilder) {
+        builder
+            .create(
+                MSAA_DEPTH,
+                TargetDesc {
+                    usage: RenderTargetUsage::Depth,
+                    ..TargetDesc::color(TextureFormat::Depth32Float)
+                }
+                .multisampled(self.sample_count),
+            )
+            .write_depth(MSAA_DEPTH, LoadPolicy::Clear, 1.0);
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext<'_, B>) -> Result<(), GpuError> {
+        if let Ok(drawables) = ctx.frame.scene.traverse() {
+            let mut last_ds: Option<bool> = None;
+            for drawable in drawables {
+                if drawable.object_type() != ObjType::Opaque {
+                    continue;
+                }
+                let ds = drawable.is_double_sided();
+                if last_ds != Some(ds) {
+                    self.set_pipeline_for(ctx.backend, ds);
+                    last_ds = Some(ds);
+                }
+                // the pre-pass pixel shader still runs and reads the material
+                drawable.draw(ctx.backend, true);
+            }
+        }
+        Ok(())
+    }
+
+    fn reload_shaders(
+        &mut self,
+        backend: &B,
+        shaders: &ReloadedShaders<B>,
+    ) -> Result<(), GpuError> {
+        if let Some(source) = &shaders.deferred_pre {
+            *self = Self::create(backend, source, self.sample_count)?;
+        }
+        Ok(())
+    }
+}
+
+// LightCullingPass
+
+/// Threads per work group of the light culling shader, one per cluster.
+const LIGHT_CULLING_GROUP_SIZE: u32 = 64;
+
+/// Light culling: a compute dispatch that bins the lights of the light storage
+/// buffer (binding 17) into the clusters of the view frustum, one thread per
+/// cluster.
+///
+/// Uniforms (binding 2): the cluster grid, see [`ClusterUniforms`].
+/// Output: the cluster light lists (binding 18), read by [`DeferredPassLight`]
+/// and [`ForwardPass`]. Point and spot lights go into the clusters their range
+/// touches, ambient, directional and area lights into every cluster.
+///
+/// Declares no graph resources; it must be added before the lighting passes.
+pub(crate) struct LightCullingPass<B: GpuBackend> {
+    pipeline: B::Pipeline,
+}
+
+impl<B: GpuBackend> LightCullingPass<B> {
+    pub fn create(backend: &B, shader_source: &B::ShaderSource) -> Result<Self, GpuError> {
+        let pipeline = backend.create_compute_pipeline(&ComputePipelineDesc {
+            label: "light_culling",
+            shader_source,
+            world_dimension: None,
+            bindings: &[],
+            push_constant_size: 0,
         })?;
-        self.albedo_metallic_target = backend.create_render_target(&RenderTargetDesc {
-            width: resolution.0,
-            height: resolution.1,
-            format: TextureFormat::R16g16b16a16Float,
-            sampler: SamplerDesc::default(),
-            usage: RenderTargetUsage::Color,
+        Ok(LightCullingPass { pipeline })
+    }
+}
+
+impl<B: GpuBackend> GraphPass<B> for LightCullingPass<B> {
+    fn name(&self) -> &'static str {
+        LIGHT_CULLING_PASS
+    }
+
+    fn setup(&self, builder: &mut PassBuilder) {
+        builder.custom();
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext<'_, B>) -> Result<(), GpuError> {
+        // The previous frame's lighting passes may still read the light lists
+        ctx.backend.cmd_compute_barrier(ComputeBarrier::GraphicsToCompute);
+        ctx.backend.cmd_dispatch(
+            &ComputeDispatch {
+                pipeline: &self.pipeline,
+                resources: &[],
+                push_constants: &[],
+            },
+            (
+                (CLUSTER_COUNT as u32).div_ceil(LIGHT_CULLING_GROUP_SIZE),
+                1,
+                1,
+            ),
+        );
+        ctx.backend
+            .cmd_compute_barrier(ComputeBarrier::ComputeToGraphics);
+        Ok(())
+    }
+
+    fn reload_shaders(
+        &mut self,
+        backend: &B,
+        shaders: &ReloadedShaders<B>,
+    ) -> Result<(), GpuError> {
+        if let Some(source) = &shaders.light_culling {
+            *self = Self::create(backend, source)?;
+        }
+        Ok(())
+    }
+}
+
+// SsaoPass
+
+/// Screen-space ambient occlusion: fullscreen quad that tests a hemisphere of
+/// kernel samples around each G-buffer position against the G-buffer depth.
+///
+/// Uniforms (binding 19): kernel, radius, bias and intensity, see [`SsaoUniforms`].
+/// Inputs (bound by the render graph): G-buffer positions (slot 0), normal+roughness (slot 1).
+/// A 4x4 noise texture (slot 2) rotates the kernel per pixel.
+/// Output: [`SSAO_RAW`], the unoccluded fraction of the ambient light.
+pub(crate) struct SsaoPass<B: GpuBackend> {
+    pipeline: B::Pipeline,
+    noise: B::Texture,
+}
+
+impl<B: GpuBackend> SsaoPass<B> {
+    pub fn create(backend: &B, shader_source: &B::ShaderSource) -> Result<Self, GpuError> {
+        let pipeline = Self::create_pipeline(backend, shader_source)?;
+        let noise = backend.create_texture(
+            &TextureDesc {
+                width: 4,
+                height: 4,
+                depth_or_layers: 1,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::Rgba8Unorm,
+                sampler: SamplerDesc {
+                    address_u: AddressMode::Repeat,
+                    address_v: AddressMode::Repeat,
+                    filter: FilterMode::Nearest,
+                    compare: None,
+                },
+                generate_mipmaps: false,
+                mip_levels: 1,
+            },
+            &generate_ssao_noise(),
+        )?;
+        Ok(SsaoPass { pipeline, noise })
+    }
+
+    fn create_pipeline(
+        backend: &B,
+        shader_source: &B::ShaderSource,
+    ) -> Result<B::Pipeline, GpuError> {
+        backend.create_render_pipeline(&RenderPipelineDesc {
+            label: "ssao",
+            shader_source,
+            vertex_layout: Some(standard_vertex_layout()),
+            blend_mode: BlendMode::None,
+            cull_mode: CullMode::None,
+            depth_write: false,
+            depth_compare: CompareFunc::Always,
+            color_target_formats: &[TextureFormat::R8Unorm],
+            depth_format: None,
+            stencil: None,
+            sample_count: 1,
+        })
+    }
+}
+
+impl<B: GpuBackend> GraphPass<B> for SsaoPass<B> {
+    fn name(&self) -> &'static str {
+        SSAO_PASS
+    }
+
+    fn setup(&self, builder: &mut PassBuilder) {
+        builder
+            .create(SSAO_RAW, TargetDesc::color(TextureFormat::R8Unorm))
+            .sample(GBUFFER_POSITIONS, 0)
+            .sample(GBUFFER_NORMAL_ROUGHNESS, 1)
+            .write_color(SSAO_RAW, LoadPolicy::Clear, [1.0, 1.0, 1.0, 1.0]);
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext<'_, B>) -> Result<(), GpuError> {
+        ctx.backend.set_pipeline(&self.pipeline);
+        ctx.backend.bind_texture(2, &self.noise);
+        ctx.frame.screen_quad.draw(ctx.backend);
+        Ok(())
+    }
+
+    fn reload_shaders(
+        &mut self,
+        backend: &B,
+        shaders: &ReloadedShaders<B>,
+    ) -> Result<(), GpuError> {
+        if let Some(source) = &shaders.ssao {
+            self.pipeline = Self::create_pipeline(backend, source)?;
+        }
+        Ok(())
+    }
+}
+
+// SsaoBlurPass
+
+/// SSAO blur: fullscreen quad averaging the 4x4 texels around each pixel of
+/// [`SSAO_RAW`], which removes the pattern of the 4x4 noise texture.
+///
+/// No uniforms. Input (bound by the render graph): raw ambient occlusion (slot 0).
+/// Output: [`SSAO`], read by [`DeferredPassLight`].
+pub(crate) struct SsaoBlurPass<B: GpuBackend> {
+    pipeline: B::Pipeline,
+}
+
+impl<B: GpuBackend> SsaoBlurPass<B> {
+    pub fn create(backend: &B, shader_source: &B::ShaderSource) -> Result<Self, GpuError> {
+        let pipeline = backend.create_render_pipeline(&RenderPipelineDesc {
+            label: "ssao_blur",
+            shader_source,
+            vertex_layout: Some(standard_vertex_layout()),
+            blend_mode: BlendMode::None,
+            cull_mode: CullMode::None,
+            depth_write: false,
+            depth_compare: CompareFunc::Always,
+            color_target_formats: &[TextureFormat::R8Unorm],
+            depth_format: None,
+            stencil: None,
+            sample_count: 1,
         })?;
+        Ok(SsaoBlurPass { pipeline })
+    }
+}
+
+impl<B: GpuBackend> GraphPass<B> for SsaoBlurPass<B> {
+    fn name(&self) -> &'static str {
+        SSAO_BLUR_PASS
+    }
+
+    fn setup(&self, builder: &mut PassBuilder) {
+        builder
+            .create(SSAO, TargetDesc::color(TextureFormat::R8Unorm))
+            .sample(SSAO_RAW, 0)
+            .write_color(SSAO, LoadPolicy::Clear, [1.0, 1.0, 1.0, 1.0]);
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext<'_, B>) -> Result<(), GpuError> {
+        ctx.backend.set_pipeline(&self.pipeline);
+        ctx.frame.screen_quad.draw(ctx.backend);
+        Ok(())
+    }
+
+    fn reload_shaders(
+        &mut self,
+        backend: &B,
+        shaders: &ReloadedShaders<B>,
+    ) -> Result<(), GpuError> {
+        if let Some(source) = &shaders.ssao_blur {
+            *self = Self::create(backend, source)?;
+        }
         Ok(())
     }
 }
@@ -412,21 +1022,18 @@ impl<B: GpuBackend> DeferredPassPre<B> {
 /// Deferred lighting pass: fullscreen quad that reads G-buffer and computes lighting.
 ///
 /// Pixel uniforms (slot 0): camera position + SSAO flag.
-/// Pixel uniforms (slot 1): light data.
-/// Inputs (bound by Renderer): G-buffer positions (slot 0), normal+roughness (slot 1),
-///   albedo+metallic (slot 2), shadow map (slot 3), SSAO blurred texture (slot 4).
-/// Output: `R16g16b16a16Float` render target (accumulated light).
+/// Each pixel is lit by the lights of its cluster (bindings 2, 17 and 18) in a
+/// single pass.
+/// Inputs (bound by the render graph): G-buffer positions (slot 0), normal+roughness (slot 1),
+///   albedo+metallic (slot 2), shadow atlas (slot 3) or RT shadows (slot 4), and
+///   the blurred [`SSAO`] (slot 5) scaling the ambient light while SSAO is enabled.
+/// Output: [`DEFERRED_LIGHT`].
 pub(crate) struct DeferredPassLight<B: GpuBackend> {
     pipeline: B::Pipeline,
-    render_target: B::RenderTarget,
     pixel_uniforms: CameraUniforms,
 }
 
 impl<B: GpuBackend> DeferredPassLight<B> {
-    pub fn render_target(&self) -> &B::RenderTarget {
-        &self.render_target
-    }
-
     /// Bind pipeline and all uniform buffers for drawing.
     pub fn prepare_draw(&self, backend: &mut B) {
         backend.set_pipeline(&self.pipeline);
@@ -447,122 +1054,221 @@ impl<B: GpuBackend> DeferredPassLight<B> {
         self.pixel_uniforms.ssao = if enabled { 1 } else { 0 };
     }
 
-    pub fn create(
-        backend: &B,
-        resolution: (u32, u32),
-        shader_source: &B::ShaderSource,
-    ) -> Result<Self, GpuError> {
+    pub fn create(backend: &B, shader_source: &B::ShaderSource) -> Result<Self, GpuError> {
         let pipeline = backend.create_render_pipeline(&RenderPipelineDesc {
             label: "deferred_light",
             shader_source,
             vertex_layout: Some(standard_vertex_layout()),
-            blend_mode: BlendMode::Additive,
+            blend_mode: BlendMode::None,
             cull_mode: CullMode::None,
             depth_write: false,
             depth_compare: CompareFunc::Always,
             color_target_formats: &[TextureFormat::R16g16b16a16Float],
             depth_format: None,
-        })?;
-
-        let render_target = backend.create_render_target(&RenderTargetDesc {
-            width: resolution.0,
-            height: resolution.1,
-            format: TextureFormat::R16g16b16a16Float,
-            sampler: SamplerDesc::default(),
-            usage: RenderTargetUsage::Color,
+            stencil: None,
+            sample_count: 1,
         })?;
 
         Ok(DeferredPassLight {
             pixel_uniforms: CameraUniforms {
                 camera_pos: glm::zero(),
                 ssao: 1,
+                taa: 0,
+                _pad: Pad::ZERO,
             },
             pipeline,
-            render_target,
         })
     }
+}
 
-    /// Recreate resolution-dependent render targets after a window resize.
-    pub fn resize(&mut self, backend: &B, resolution: (u32, u32)) -> Result<(), GpuError> {
-        self.render_target = backend.create_render_target(&RenderTargetDesc {
-            width: resolution.0,
-            height: resolution.1,
-            format: TextureFormat::R16g16b16a16Float,
-            sampler: SamplerDesc::default(),
-            usage: RenderTargetUsage::Color,
-        })?;
+impl<B: GpuBackend> GraphPass<B> for DeferredPassLight<B> {
+    fn name(&self) -> &'static str {
+        "deferred_light"
+    }
+
+    fn setup(&self, builder: &mut PassBuilder) {
+        builder
+            .create(
+                DEFERRED_LIGHT,
+                TargetDesc::color(TextureFormat::R16g16b16a16Float),
+            )
+            .sample(GBUFFER_POSITIONS, 0)
+            .sample(GBUFFER_NORMAL_ROUGHNESS, 1)
+            .sample(GBUFFER_ALBEDO_METALLIC, 2)
+            .write_color(DEFERRED_LIGHT, LoadPolicy::Clear, [0.0, 0.0, 0.0, 0.0]);
+        sample_shadows(builder);
+        if builder.is_enabled(SSAO_BLUR_PASS) {
+            builder.sample(SSAO, 5);
+        }
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext<'_, B>) -> Result<(), GpuError> {
+        self.prepare_draw(ctx.backend);
+        ctx.frame.screen_quad.draw(ctx.backend);
+        Ok(())
+    }
+
+    fn reload_shaders(
+        &mut self,
+        backend: &B,
+        shaders: &ReloadedShaders<B>,
+    ) -> Result<(), GpuError> {
+        if let Some(source) = &shaders.deferred_light {
+            self.pipeline = Self::create(backend, source)?.pipeline;
+        }
         Ok(())
     }
 }
 
 // ShadowPass
 
-pub const SHADOW_MAP_SIZE: u32 = 2048;
-
-/// Shadow mapping pass: renders the scene from the light's perspective into a depth map.
+/// Size of a tile of the shadow atlas, holding one shadow map view at the
+/// resolution of the former single shadow map.
+pub const SHADOW_TILE_SIZE: u32 = 2048;
+/// The shadow atlas is a 4x4 grid of tiles: tile `i` is at `(i % 4, i / 4)`.
+pub const SHADOW_ATLAS_COLUMNS: u32 = 4;
+pub const SHADOW_ATLAS_ROWS: u32 = 4;
+/// Number of tiles of the shadow atlas, shared by the views of the directional,
+/// spot and area lights.
+pub const SHADOW_ATLAS_TILES: u32 = SHADOW_ATLAS_COLUMNS * SHADOW_ATLAS_ROWS;
+/// Fraction of a cascade at its border that fades into the next cascade.
+pub const SHADOW_CASCADE_BLEND: f32 = 0.1;
+/// Size of the faces of the point light shadow cube maps.
+pub const SHADOW_CUBE_SIZE: u32 = 1024;
+/// Cubes of the shadow cube map array, one per shadowed point light.
+pub const MAX_SHADOW_CUBES: u32 = 4;
+
+/// Shadow mapping pass: renders the scene from the perspective of every shadowed
+/// light into depth maps.
+///
+/// Vertex uniforms (slot 0): light-space matrix, one per shadow map view.
+/// Output: two `Depth32Float` targets with a comparison sampler for PCF filtering,
+/// filled in a render pass per view, as the light-space UBO can only be updated
+/// outside render passes:
+/// - [`SHADOW_MAP`], an 8192x8192 atlas. The renderer assigns directional, spot
+///   and area lights a range of tiles (`Light::shadow_tile`), each view renders
+///   into its own tile: orthographic cascades for directional lights, one
+///   perspective view for spot lights.
+/// - [`SHADOW_CUBES`], a cube map array. The renderer assigns each shadowed point
+///   light a cube (`Light::shadow_cube`), whose six faces are rendered into its
+///   layers. The face views are mirrored to match the cube map face orientation,
+///   which swaps their winding.
 ///
-/// Vertex uniforms (slot 0): light-space matrix.
-/// Output: `Depth32Float` shadow map render target (4096x4096).
-/// The shadow map has a comparison sampler for PCF filtering.
+/// Skipped while no light has shadow maps.
 pub(crate) struct ShadowPass<B: GpuBackend> {
     pipeline: B::Pipeline,              // Shared UBOs are bound globally
     pipeline_double_sided: B::Pipeline, // Shared UBOs are bound globally
-    shadow_map: B::RenderTarget,
-    shadow_viewport: ViewportDesc,
+    /// Front-face culling for the mirrored cube face views.
+    pipeline_cube_face: B::Pipeline,
 }
 
 impl<B: GpuBackend> ShadowPass<B> {
-    pub fn shadow_map(&self) -> &B::RenderTarget {
-        &self.shadow_map
-    }
-
-    pub fn viewport(&self) -> &ViewportDesc {
-        &self.shadow_viewport
-    }
-
     /// Bind pipeline and vertex uniform buffer for drawing.
     pub fn prepare_draw(&self, backend: &mut B) {
         backend.set_pipeline(&self.pipeline);
     }
 
-    /// Switch pipeline based on whether the drawable is double-sided.
-    /// Rebinds pass uniforms since set_pipeline() clears all pending bindings.
-    pub fn set_pipeline_for(&self, backend: &mut B, double_sided: bool) {
+    /// Switch pipeline based on whether the drawable is double-sided and the
+    /// view a cube face. Rebinds pass uniforms since set_pipeline() clears all
+    /// pending bindings.
+    pub fn set_pipeline_for(&self, backend: &mut B, double_sided: bool, cube_face: bool) {
         if double_sided {
             backend.set_pipeline(&self.pipeline_double_sided);
+        } else if cube_face {
+            backend.set_pipeline(&self.pipeline_cube_face);
         } else {
             backend.set_pipeline(&self.pipeline);
         }
     }
 
     pub fn create(backend: &B, shader_source: &B::ShaderSource) -> Result<Self, GpuError> {
-        let pipeline = backend.create_render_pipeline(&RenderPipelineDesc {
-            label: "shadow_pass",
-            shader_source,
-            vertex_layout: Some(standard_vertex_layout()),
-            blend_mode: BlendMode::None,
-            cull_mode: CullMode::Front, // Front-face culling reduces shadow acne
-            depth_write: true,
-            depth_compare: CompareFunc::Less,
-            color_target_formats: &[],
-            depth_format: Some(TextureFormat::Depth32Float),
-        })?;
+        let create = |label, cull_mode| {
+            backend.create_render_pipeline(&RenderPipelineDesc {
+                label,
+                shader_source,
+                vertex_layout: Some(standard_vertex_layout()),
+                blend_mode: BlendMode::None,
+                cull_mode,
+                depth_write: true,
+                depth_compare: CompareFunc::Less,
+                color_target_formats: &[],
+                depth_format: Some(TextureFormat::Depth32Float),
+                stencil: None,
+                sample_count: 1,
+            })
+        };
+        Ok(ShadowPass {
+            // Front-face culling reduces shadow acne
+            pipeline: create("shadow_pass", CullMode::Front)?,
+            // Both faces cast shadows for double-sided materials
+            pipeline_double_sided: create("shadow_pass_double_sided", CullMode::None)?,
+            // Mirroring swaps the winding of the front faces
+            pipeline_cube_face: create("shadow_pass_cube_face", CullMode::Back)?,
+        })
+    }
 
-        let pipeline_double_sided = backend.create_render_pipeline(&RenderPipelineDesc {
-            label: "shadow_pass_double_sided",
-            shader_source,
-            vertex_layout: Some(standard_vertex_layout()),
-            blend_mode: BlendMode::None,
-            cull_mode: CullMode::None, // Both faces cast shadows for double-sided materials
-            depth_write: true,
-            depth_compare: CompareFunc::Less,
-            color_target_formats: &[],
-            depth_format: Some(TextureFormat::Depth32Float),
-        })?;
+    /// Render the drawables into one shadow map view: a tile of the atlas or a
+    /// face of a cube map.
+    fn render_view(
+        &self,
+        ctx: &mut PassContext<'_, B>,
+        drawables: &[RenderItem<'_, B>],
+        light_space: &glm::Mat4,
+        view: ShadowView<'_, B>,
+    ) {
+        ctx.backend.cmd_update_buffer(
+            ctx.frame.light_space_buffer,
+            as_bytes(std::slice::from_ref(&LightSpaceUniforms {
+                light_space_matrix: *light_space,
+            })),
+        );
+        ctx.backend.begin_render_pass(&RenderPassDesc {
+            label: SHADOW_PASS,
+            color_targets: vec![],
+            depth_target: Some(DepthAttachment {
+                target: view.target,
+                load_op: view.load_op,
+                clear_depth: 1.0,
+                write_enabled: true,
+                stencil_load_op: view.load_op,
+                clear_stencil: 0,
+                resolve_target: None,
+                layer: view.layer,
+            }),
+        });
+        ctx.backend.set_viewport(&view.viewport);
+        self.prepare_draw(ctx.backend);
+        let mut last_ds: Option<bool> = None;
+        for drawable in drawables {
+            let ds = drawable.is_double_sided();
+            if last_ds != Some(ds) {
+                self.set_pipeline_for(ctx.backend, ds, view.cube_face);
+                last_ds = Some(ds);
+            }
+            drawable.draw(ctx.backend, false);
+        }
+        ctx.backend.end_render_pass();
+    }
+}
 
-        let shadow_map = backend.create_render_target(&RenderTargetDesc {
-            width: SHADOW_MAP_SIZE,
-            height: SHADOW_MAP_SIZE,
+/// Where [`ShadowPass::render_view`] renders a shadow map view.
+struct ShadowView<'a, B: GpuBackend> {
+    target: &'a B::RenderTarget,
+    layer: u32,
+    viewport: ViewportDesc,
+    /// A mirrored face of a cube map.
+    cube_face: bool,
+    load_op: LoadOp,
+}
+
+impl<B: GpuBackend> GraphPass<B> for ShadowPass<B> {
+    fn name(&self) -> &'static str {
+        SHADOW_PASS
+    }
+
+    fn setup(&self, builder: &mut PassBuilder) {
+        let shadow_map = |(width, height), layers| TargetDesc {
+            size: TargetSize::Fixed(width, height),
             format: TextureFormat::Depth32Float,
             sampler: SamplerDesc {
                 address_u: AddressMode::Clamp,
@@ -571,35 +1277,305 @@ impl<B: GpuBackend> ShadowPass<B> {
                 compare: Some(CompareFunc::LessEqual),
             },
             usage: RenderTargetUsage::Depth,
-        })?;
+            sample_count: 1,
+            persistent: false,
+            layers,
+        };
+        let atlas_size = (
+            SHADOW_ATLAS_COLUMNS * SHADOW_TILE_SIZE,
+            SHADOW_ATLAS_ROWS * SHADOW_TILE_SIZE,
+        );
+        builder
+            .create(SHADOW_MAP, shadow_map(atlas_size, 1))
+            .create(
+                SHADOW_CUBES,
+                shadow_map((SHADOW_CUBE_SIZE, SHADOW_CUBE_SIZE), 6 * MAX_SHADOW_CUBES),
+            )
+            .write(SHADOW_MAP, LoadPolicy::Clear)
+            .write(SHADOW_CUBES, LoadPolicy::Clear)
+            .custom();
+    }
+
+    fn should_run(&self, frame: &FrameInputs<'_, B>) -> bool {
+        frame
+            .lights
+            .iter()
+            .any(|l| l.shadow_tile.is_some() || l.shadow_cube.is_some())
+    }
 
-        let shadow_viewport = ViewportDesc {
-            x: 0.0,
-            y: 0.0,
-            width: SHADOW_MAP_SIZE as f32,
-            height: SHADOW_MAP_SIZE as f32,
-            min_depth: 0.0,
-            max_depth: 1.0,
+    fn execute(&mut self, ctx: &mut PassContext<'_, B>) -> Result<(), GpuError> {
+        let (Some(shadow_map), Some(shadow_cubes)) = (
+            ctx.target(SHADOW_MAP).cloned(),
+            ctx.target(SHADOW_CUBES).cloned(),
+        ) else {
+            return Ok(());
         };
+        let drawables = ctx.frame.scene.traverse().unwrap_or_default();
+        let lights = ctx.frame.lights;
+        let tile_views = lights.iter().flat_map(|light| {
+            let views = &light.shadow_views[..light.shadow_view_count as usize];
+            views
+                .iter()
+                .enumerate()
+                .filter_map(move |(view, light_space)| {
+                    light
+                        .shadow_tile
+                        .map(|tile| (tile + view as u32, light_space))
+                })
+        });
+        for (i, (tile, light_space)) in tile_views.enumerate() {
+            let view = ShadowView {
+                target: &shadow_map,
+                layer: 0,
+                viewport: ViewportDesc {
+                    x: (tile % SHADOW_ATLAS_COLUMNS * SHADOW_TILE_SIZE) as f32,
+                    y: (tile / SHADOW_ATLAS_COLUMNS * SHADOW_TILE_SIZE) as f32,
+                    width: SHADOW_TILE_SIZE as f32,
+                    height: SHADOW_TILE_SIZE as f32,
+                    min_depth: 0.0,
+                    max_depth: 1.0,
+                },
+                cube_face: false,
+                // The first view clears the whole atlas
+                load_op: if i == 0 { LoadOp::Clear } else { LoadOp::Load },
+            };
+            self.render_view(ctx, &drawables, light_space, view);
+        }
 
-        Ok(ShadowPass {
+        // Every face is a layer of its own, cleared when it is rendered
+        let face_views = lights.iter().flat_map(|light| {
+            let faces = &light.shadow_views[..light.shadow_view_count as usize];
+            faces
+                .iter()
+                .enumerate()
+                .filter_map(move |(face, light_space)| {
+                    light
+                        .shadow_cube
+                        .map(|cube| (6 * cube + face as u32, light_space))
+                })
+        });
+        for (layer, light_space) in face_views {
+            let view = ShadowView {
+                target: &shadow_cubes,
+                layer,
+                viewport: ViewportDesc {
+                    x: 0.0,
+                    y: 0.0,
+                    width: SHADOW_CUBE_SIZE as f32,
+                    height: SHADOW_CUBE_SIZE as f32,
+                    min_depth: 0.0,
+                    max_depth: 1.0,
+                },
+                cube_face: true,
+                load_op: LoadOp::Clear,
+            };
+            self.render_view(ctx, &drawables, light_space, view);
+        }
+        Ok(())
+    }
+
+    fn reload_shaders(
+        &mut self,
+        backend: &B,
+        shaders: &ReloadedShaders<B>,
+    ) -> Result<(), GpuError> {
+        if let Some(source) = &shaders.shadow {
+            *self = Self::create(backend, source)?;
+        }
+        Ok(())
+    }
+}
+
+// RtShadowPass
+
+/// Ray traced shadows: one dispatch per frame for all non-ambient lights,
+/// replacing the shadow map when the backend supports ray tracing.
+///
+/// Output: [`RT_SHADOWS`], a storage target sampled by the lighting passes (slot 4).
+pub(crate) struct RtShadowPass<B: GpuBackend> {
+    pipeline: B::Pipeline,
+    light_buffer: B::Buffer,
+}
+
+impl<B: GpuBackend> RtShadowPass<B> {
+    pub fn create(backend: &B, rt_shaders: &RtShaders<B>) -> Result<Self, GpuError> {
+        let pipeline = backend.create_rt_pipeline(rt_shaders)?;
+        let light_buffer = backend.create_buffer(
+            &BufferDesc {
+                label: "rt_light_array".to_string(),
+                usage: BufferUsage::Storage,
+                size: std::mem::size_of::<GpuLight>() * MAX_RT_LIGHTS,
+            },
+            None,
+        )?;
+        Ok(RtShadowPass {
             pipeline,
-            pipeline_double_sided,
-            shadow_map,
-            shadow_viewport,
+            light_buffer,
         })
     }
 }
 
+impl<B: GpuBackend> GraphPass<B> for RtShadowPass<B> {
+    fn name(&self) -> &'static str {
+        RT_SHADOW_PASS
+    }
+
+    fn setup(&self, builder: &mut PassBuilder) {
+        builder
+            .create(
+                RT_SHADOWS,
+                TargetDesc {
+                    usage: RenderTargetUsage::Storage,
+                    ..TargetDesc::color(TextureFormat::R16g16b16a16Float)
+                },
+            )
+            .write(RT_SHADOWS, LoadPolicy::Clear)
+            .custom();
+    }
+
+    fn should_run(&self, frame: &FrameInputs<'_, B>) -> bool {
+        frame.rt.is_some()
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext<'_, B>) -> Result<(), GpuError> {
+        let Some(rt) = ctx.frame.rt.as_ref() else {
+            return Ok(());
+        };
+        let rt_lights: Vec<GpuLight> = ctx
+            .frame
+            .lights
+            .iter()
+            .filter(|l| l.t != LightType::Ambient)
+            .take(MAX_RT_LIGHTS)
+            .map(GpuLight::from_light)
+            .collect();
+        if rt_lights.is_empty() {
+            return Ok(());
+        }
+        let Some(output) = ctx.target(RT_SHADOWS).cloned() else {
+            return Ok(());
+        };
+        ctx.backend
+            .cmd_update_buffer(&self.light_buffer, as_bytes(&rt_lights));
+        ctx.backend.dispatch_rays(
+            &self.pipeline,
+            rt.tlas,
+            &output,
+            &self.light_buffer,
+            rt.material_buffer,
+            output.width(),
+            output.height(),
+            rt_lights.len() as u32,
+        );
+        Ok(())
+    }
+
+    fn reload_shaders(
+        &mut self,
+        backend: &B,
+        shaders: &ReloadedShaders<B>,
+    ) -> Result<(), GpuError> {
+        if let Some(rt_shaders) = &shaders.rt {
+            self.pipeline = backend.create_rt_pipeline(rt_shaders)?;
+        }
+        Ok(())
+    }
+}
+
+// TaaPass
+
+/// Temporal anti-aliasing resolve: composites the deferred and forward results
+/// of the jittered frame and blends them with the previous frame's result,
+/// fetched along the motion vectors and clamped to the 3x3 neighbourhood of the
+/// current pixel so disoccluded history does not ghost.
+///
+/// Uniforms (binding 20): feedback and history validity, see [`TaaUniforms`].
+/// Inputs (bound by the render graph): deferred light result (slot 0), forward
+/// result (slot 1), [`GBUFFER_VELOCITY`] (slot 2) and the history of
+/// [`TAA_RESOLVED`] (slot 3).
+/// Output: [`TAA_RESOLVED`], tonemapped by [`OutputPass`].
+pub(crate) struct TaaPass<B: GpuBackend> {
+    pipeline: B::Pipeline,
+}
+
+impl<B: GpuBackend> TaaPass<B> {
+    pub fn create(backend: &B, shader_source: &B::ShaderSource) -> Result<Self, GpuError> {
+        let pipeline = backend.create_render_pipeline(&RenderPipelineDesc {
+            label: "taa",
+            shader_source,
+            vertex_layout: Some(standard_vertex_layout()),
+            blend_mode: BlendMode::None,
+            cull_mode: CullMode::None,
+            depth_write: false,
+            depth_compare: CompareFunc::Always,
+            color_target_formats: &[TextureFormat::R16g16b16a16Float],
+            depth_format: None,
+            stencil: None,
+            sample_count: 1,
+        })?;
+        Ok(TaaPass { pipeline })
+    }
+}
+
+impl<B: GpuBackend> GraphPass<B> for TaaPass<B> {
+    fn name(&self) -> &'static str {
+        TAA_PASS
+    }
+
+    fn setup(&self, builder: &mut PassBuilder) {
+        let resolved = TargetDesc {
+            // The history is fetched between texels, bilinear and clamped to the screen
+            sampler: SamplerDesc {
+                address_u: AddressMode::Clamp,
+                address_v: AddressMode::Clamp,
+                filter: FilterMode::Linear,
+                compare: None,
+            },
+            ..TargetDesc::color(TextureFormat::R16g16b16a16Float)
+        };
+        builder
+            .create(TAA_RESOLVED, resolved.persistent())
+            .sample(DEFERRED_LIGHT, 0)
+            .sample(FORWARD, 1)
+            .sample(GBUFFER_VELOCITY, 2)
+            .sample_history(TAA_RESOLVED, 3)
+            .write_color(TAA_RESOLVED, LoadPolicy::Clear, [0.0, 0.0, 0.0, 0.0]);
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext<'_, B>) -> Result<(), GpuError> {
+        ctx.backend.set_pipeline(&self.pipeline);
+        ctx.frame.screen_quad.draw(ctx.backend);
+        Ok(())
+    }
+
+    fn reload_shaders(
+        &mut self,
+        backend: &B,
+        shaders: &ReloadedShaders<B>,
+    ) -> Result<(), GpuError> {
+        if let Some(source) = &shaders.taa {
+            *self = Self::create(backend, source)?;
+        }
+        Ok(())
+    }
+}
+
 // OutputPass
 
 /// Output compositing pass: blends deferred and forward results to the backbuffer.
 ///
-/// No uniforms. Inputs are bound by the Renderer:
+/// Pixel uniforms (slot 0): the TAA flag of [`CameraUniforms`].
+/// Inputs are bound by the render graph:
 ///   - Deferred light result (slot 0)
 ///   - Forward result (slot 1)
+///
+/// While [`TaaPass`] runs it only tonemaps [`TAA_RESOLVED`] (slot 0), which
+/// already holds the composite.
+/// With MSAA it renders into [`MSAA_COMPOSITE`] and resolves it into the backbuffer.
 pub(crate) struct OutputPass<B: GpuBackend> {
     pipeline: B::Pipeline,
+    backbuffer_format: TextureFormat,
+    sample_count: u32,
 }
 
 impl<B: GpuBackend> OutputPass<B> {
@@ -612,6 +1588,7 @@ impl<B: GpuBackend> OutputPass<B> {
         backend: &B,
         shader_source: &B::ShaderSource,
         backbuffer_format: TextureFormat,
+        sample_count: u32,
     ) -> Result<Self, GpuError> {
         let pipeline = backend.create_render_pipeline(&RenderPipelineDesc {
             label: "output_pass",
@@ -623,9 +1600,56 @@ impl<B: GpuBackend> OutputPass<B> {
             depth_compare: CompareFunc::Always,
             color_target_formats: &[backbuffer_format],
             depth_format: None,
+            stencil: None,
+            sample_count,
         })?;
 
-        Ok(OutputPass { pipeline })
+        Ok(OutputPass {
+            pipeline,
+            backbuffer_format,
+            sample_count,
+        })
+    }
+}
+
+impl<B: GpuBackend> GraphPass<B> for OutputPass<B> {
+    fn name(&self) -> &'static str {
+        "output"
+    }
+
+    fn setup(&self, builder: &mut PassBuilder) {
+        let clear = [0.05, 0.05, 0.05, 1.0];
+        if builder.is_enabled(TAA_PASS) {
+            builder.sample(TAA_RESOLVED, 0);
+        } else {
+            builder.sample(DEFERRED_LIGHT, 0).sample(FORWARD, 1);
+        }
+        if self.sample_count > 1 {
+            let composite = TargetDesc::color(self.backbuffer_format);
+            builder
+                .create(MSAA_COMPOSITE, composite.multisampled(self.sample_count))
+                .write_color(MSAA_COMPOSITE, LoadPolicy::Clear, clear)
+                .resolve(MSAA_COMPOSITE, BACKBUFFER);
+        } else {
+            builder.write_color(BACKBUFFER, LoadPolicy::Clear, clear);
+        }
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext<'_, B>) -> Result<(), GpuError> {
+        self.prepare_draw(ctx.backend);
+        ctx.frame.screen_quad.draw(ctx.backend);
+        Ok(())
+    }
+
+    fn reload_shaders(
+        &mut self,
+        backend: &B,
+        shaders: &ReloadedShaders<B>,
+    ) -> Result<(), GpuError> {
+        if let Some(source) = &shaders.output {
+            *self = Self::create(backend, source, self.backbuffer_format, self.sample_count)?;
+        }
+        Ok(())
     }
 }
 
@@ -636,8 +1660,12 @@ impl<B: GpuBackend> OutputPass<B> {
 /// Vertex uniforms (slot 0): view + projection matrices.
 /// The view matrix should have its translation component removed
 /// (mat3→mat4 conversion) so the skybox moves with the camera.
+/// Skipped while the scene has no skybox. With MSAA it draws into
+/// [`MSAA_COMPOSITE`], tested against [`MSAA_DEPTH`], and resolves again.
 pub(crate) struct SkyBoxPass<B: GpuBackend> {
     pipeline: B::Pipeline,
+    backbuffer_format: TextureFormat,
+    sample_count: u32,
 }
 
 impl<B: GpuBackend> SkyBoxPass<B> {
@@ -650,6 +1678,7 @@ impl<B: GpuBackend> SkyBoxPass<B> {
         backend: &B,
         shader_source: &B::ShaderSource,
         backbuffer_format: TextureFormat,
+        sample_count: u32,
     ) -> Result<Self, GpuError> {
         let pipeline = backend.create_render_pipeline(&RenderPipelineDesc {
             label: "skybox_pass",
@@ -661,8 +1690,87 @@ impl<B: GpuBackend> SkyBoxPass<B> {
             depth_compare: CompareFunc::LessEqual,
             color_target_formats: &[backbuffer_format],
             depth_format: Some(TextureFormat::Depth32Float),
+            stencil: None,
+            sample_count,
         })?;
 
-        Ok(SkyBoxPass { pipeline })
+        Ok(SkyBoxPass {
+            pipeline,
+            backbuffer_format,
+            sample_count,
+        })
+    }
+}
+
+impl<B: GpuBackend> GraphPass<B> for SkyBoxPass<B> {
+    fn name(&self) -> &'static str {
+        "skybox"
+    }
+
+    fn setup(&self, builder: &mut PassBuilder) {
+        if self.sample_count > 1 {
+            builder
+                .write_color(MSAA_COMPOSITE, LoadPolicy::Load, [0.0, 0.0, 0.0, 0.0])
+                .resolve(MSAA_COMPOSITE, BACKBUFFER)
+                .test_depth(MSAA_DEPTH);
+        } else {
+            builder
+                .write_color(BACKBUFFER, LoadPolicy::Load, [0.0, 0.0, 0.0, 0.0])
+                .test_depth(MAIN_DEPTH);
+        }
+    }
+
+    fn should_run(&self, frame: &FrameInputs<'_, B>) -> bool {
+        frame.skybox.is_some()
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext<'_, B>) -> Result<(), GpuError> {
+        if let Some(skybox) = ctx.frame.skybox {
+            self.prepare_draw(ctx.backend);
+            skybox.draw(ctx.backend);
+        }
+        Ok(())
+    }
+
+    fn reload_shaders(
+        &mut self,
+        backend: &B,
+        shaders: &ReloadedShaders<B>,
+    ) -> Result<(), GpuError> {
+        if let Some(source) = &shaders.skybox {
+            *self = Self::create(backend, source, self.backbuffer_format, self.sample_count)?;
+        }
+        Ok(())
+    }
+}
+
+// EguiPass
+
+/// Editor overlay: composites the egui output handed to the renderer over the
+/// finished frame. Records its own render pass; skipped without an overlay.
+pub(crate) struct EguiPass;
+
+impl<B: GpuBackend> GraphPass<B> for EguiPass {
+    fn name(&self) -> &'static str {
+        "egui"
+    }
+
+    fn setup(&self, builder: &mut PassBuilder) {
+        builder.write(BACKBUFFER, LoadPolicy::Load).custom();
+    }
+
+    fn should_run(&self, frame: &FrameInputs<'_, B>) -> bool {
+        frame.overlay.is_some()
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext<'_, B>) -> Result<(), GpuError> {
+        if let Some(overlay) = ctx.frame.overlay {
+            ctx.backend.render_egui(
+                &overlay.textures_delta,
+                &overlay.primitives,
+                overlay.pixels_per_point,
+            );
+        }
+        Ok(())
     }
 }
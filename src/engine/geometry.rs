@@ -85,10 +85,21 @@ pub enum LightType {
     Ambient,
     Directional,
     Area,
+    /// Omnidirectional light at `position`, reaching up to `range`.
+    Point,
+    /// Cone of light at `position` pointing along `direction`, reaching up to `range`.
+    Spot,
 }
 
+/// Maximum number of shadow cascades of a directional light.
+pub const MAX_SHADOW_CASCADES: usize = 4;
+/// Maximum number of shadow map views of a light: the faces of a point light's
+/// cube map.
+pub const MAX_SHADOW_VIEWS: usize = 6;
+
 #[derive(Clone, Debug)]
 pub struct Light {
+    /// The direction the light travels for Directional lights.
     pub position: glm::Vec3,
     pub t: LightType,
     pub color: glm::Vec3,
@@ -99,7 +110,29 @@ pub struct Light {
     /// separately. Only used by the RT closest-hit shader; ignored for
     /// Ambient and Directional lights.
     pub penumbra_radius: f32,
+    /// Direction of the cone axis of Spot lights.
+    pub direction: glm::Vec3,
+    /// Distance at which Point and Spot lights fade out completely; also the far
+    /// plane of their shadow maps.
+    pub range: f32,
+    /// Half angle (radians) of a Spot light's fully lit inner cone.
+    pub inner_angle: f32,
+    /// Half angle (radians) of a Spot light's cone, fading out from `inner_angle`.
+    pub outer_angle: f32,
     pub light_proj: glm::Mat4,
+    /// World to light clip space of each shadow map view (directional cascades,
+    /// point light cube faces), recomputed by the renderer every frame. Only the
+    /// first `shadow_view_count` are used.
+    pub shadow_views: [glm::Mat4; MAX_SHADOW_VIEWS],
+    pub shadow_view_count: u32,
+    /// Shadow atlas tile of the first shadow map view of directional, spot and
+    /// area lights, assigned by the renderer every frame. `None` if the light
+    /// casts no shadows or the atlas is full.
+    pub shadow_tile: Option<u32>,
+    /// Cube of a point light in the shadow cube map array, assigned by the
+    /// renderer every frame. `None` if the light casts no shadows or all cubes
+    /// are taken.
+    pub shadow_cube: Option<u32>,
 }
 
 impl Default for Light {
@@ -110,7 +143,15 @@ impl Default for Light {
             color: glm::zero(),
             radius: 0.0,
             penumbra_radius: 0.0,
+            direction: glm::vec3(0.0, -1.0, 0.0),
+            range: 10.0,
+            inner_angle: 20f32.to_radians(),
+            outer_angle: 30f32.to_radians(),
             light_proj: glm::identity(),
+            shadow_views: [glm::identity(); MAX_SHADOW_VIEWS],
+            shadow_view_count: 1,
+            shadow_tile: None,
+            shadow_cube: None,
         }
     }
 }
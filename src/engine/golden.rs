@@ -0,0 +1,311 @@
+//! Golden-image regression harness for the render pipeline.
+//!
+//! Renders `tests/golden/scenes/reference.gltf` from a fixed camera, reads back
+//! the final output and the intermediate pass targets (G-buffer, light
+//! accumulation) and compares each against the checked-in images in
+//! `tests/golden/<backend>/`, so a regression can be pinned to the pass that
+//! introduced it. On failure the actual image and a diff image are written to
+//! `target/golden-diff/<backend>/`.
+//!
+//! Display-referred targets are stored as PNG, float targets as 32-bit EXR.
+//! Run with `SPARKLE_UPDATE_GOLDEN=1` to (re)write the golden images.
+//!
+//! The Vulkan test needs a device and is ignored by default, run it with
+//! `cargo test golden_reference_scene_vulkan -- --ignored`. Without a device it
+//! fails instead of passing silently.
+
+use std::path::{Path, PathBuf};
+
+use super::backend::{GpuBackend, TextureFormat, TextureReadback};
+use super::renderer::Renderer;
+use super::screenshot;
+use super::settings::{Settings, SsaoSettings};
+use crate::input::CameraSnapshot;
+
+const REFERENCE_SCENE: &str = "tests/golden/scenes/reference.gltf";
+const GOLDEN_DIR: &str = "tests/golden";
+const DIFF_DIR: &str = "target/golden-diff";
+const UPDATE_ENV: &str = "SPARKLE_UPDATE_GOLDEN";
+
+/// Resolution the golden images are rendered at.
+pub const GOLDEN_SIZE: (u32, u32) = (256, 256);
+
+/// Engine settings the golden images are rendered with, with every optional
+/// pass enabled.
+pub fn golden_settings() -> Settings {
+    Settings {
+        resolution: GOLDEN_SIZE,
+        ssao: SsaoSettings {
+            enabled: true,
+            ..SsaoSettings::default()
+        },
+        taa: true,
+        ..Settings::default()
+    }
+}
+
+/// Camera looking down at the reference scene from the front right.
+pub fn reference_camera() -> CameraSnapshot {
+    let eye = glm::vec3(3.0, 2.5, 4.0);
+    let focus = glm::vec3(0.0, 0.4, 0.0);
+    let (near, far) = (0.1, 50.0);
+    let aspect = GOLDEN_SIZE.0 as f32 / GOLDEN_SIZE.1 as f32;
+    CameraSnapshot {
+        view_matrix: glm::look_at_rh(&eye, &focus, &glm::vec3(0.0, 1.0, 0.0)),
+        projection_matrix: glm::perspective_zo(aspect, 60f32.to_radians(), near, far),
+        pos: eye,
+        focus,
+        near,
+        far,
+    }
+}
+
+/// Allowed deviation from a golden image.
+///
+/// A channel matches if `|actual - golden| <= absolute + relative * |golden|`;
+/// a target passes if at most `max_mismatched` of its pixels have a channel
+/// that does not match.
+#[derive(Debug, Clone, Copy)]
+pub struct Tolerance {
+    pub absolute: f32,
+    pub relative: f32,
+    pub max_mismatched: f32,
+}
+
+impl Tolerance {
+    /// 8-bit display output: two code values, rasterization differences on edges.
+    pub const DISPLAY: Tolerance = Tolerance {
+        absolute: 2.0 / 255.0,
+        relative: 0.0,
+        max_mismatched: 0.002,
+    };
+    /// Float targets: half float precision plus some driver slack.
+    pub const FLOAT: Tolerance = Tolerance {
+        absolute: 1e-3,
+        relative: 0.01,
+        max_mismatched: 0.002,
+    };
+
+    pub fn for_format(format: TextureFormat) -> Tolerance {
+        if is_float(format) {
+            Tolerance::FLOAT
+        } else {
+            Tolerance::DISPLAY
+        }
+    }
+}
+
+/// Outcome of comparing one target against its golden image.
+#[derive(Debug)]
+pub struct DiffReport {
+    pub mismatched: usize,
+    pub total: usize,
+    /// Largest error relative to the tolerance; `<= 1.0` means within tolerance.
+    pub max_error: f32,
+}
+
+impl DiffReport {
+    pub fn passes(&self, tolerance: &Tolerance) -> bool {
+        self.mismatched as f32 <= tolerance.max_mismatched * self.total as f32
+    }
+}
+
+fn is_float(format: TextureFormat) -> bool {
+    matches!(
+        format,
+        TextureFormat::R16g16b16a16Float | TextureFormat::Rgba32Float
+    )
+}
+
+/// Compare two images of equal size.
+///
+/// Returns the report and a diff image: mismatching pixels in red (brighter =
+/// further off), matching pixels as a dimmed greyscale of the golden image.
+pub fn compare(
+    actual: &image::Rgba32FImage,
+    golden: &image::Rgba32FImage,
+    tolerance: &Tolerance,
+) -> (DiffReport, image::RgbaImage) {
+    let mut diff = image::RgbaImage::new(golden.width(), golden.height());
+    let mut report = DiffReport {
+        mismatched: 0,
+        total: (golden.width() * golden.height()) as usize,
+        max_error: 0.0,
+    };
+
+    for (x, y, g) in golden.enumerate_pixels() {
+        let a = actual.get_pixel(x, y);
+        let error = (0..4)
+            .map(|c| {
+                let allowed = tolerance.absolute + tolerance.relative * g[c].abs();
+                (a[c] - g[c]).abs() / allowed.max(f32::EPSILON)
+            })
+            .fold(0.0f32, f32::max);
+        report.max_error = report.max_error.max(error);
+
+        let pixel = if error > 1.0 {
+            report.mismatched += 1;
+            let intensity = (128.0 + 127.0 * ((error - 1.0) / 8.0).min(1.0)) as u8;
+            [intensity, 0, 0, 255]
+        } else {
+            let luma = 0.2126 * g[0] + 0.7152 * g[1] + 0.0722 * g[2];
+            let grey = (luma.clamp(0.0, 1.0) * 80.0) as u8;
+            [grey, grey, grey, 255]
+        };
+        diff.put_pixel(x, y, image::Rgba(pixel));
+    }
+
+    (report, diff)
+}
+
+/// Load the reference scene and render one frame, returning the readbacks of
+/// the final output (`"output"`) and all pass targets.
+pub fn render_reference_frame<B: GpuBackend>(
+    backend: B,
+) -> Result<Vec<(String, TextureReadback)>, String> {
+    let mut renderer = Renderer::create(backend, golden_settings()).map_err(|e| e.to_string())?;
+    renderer.init_draw_programs().map_err(|e| e.to_string())?;
+    renderer
+        .load_scene(REFERENCE_SCENE)
+        .map_err(|e| e.to_string())?;
+    // the RT shadow path depends on hardware support, keep the reference on raster
+    renderer.set_use_ray_tracing(false);
+
+    let mut camera = reference_camera();
+    renderer.set_camera_projection(&camera);
+
+    renderer
+        .backend_mut()
+        .begin_frame()
+        .map_err(|e| e.to_string())?;
+    renderer.update_state(0.0, &mut camera);
+    renderer.render_scene(&camera).map_err(|e| e.to_string())?;
+    renderer.finish_frame().map_err(|e| e.to_string())?;
+
+    let mut targets = vec![("output", renderer.backend().backbuffer())];
+    targets.extend(renderer.pass_targets());
+    let mut captures = Vec::new();
+    for (name, target) in targets {
+        let readback = renderer
+            .backend_mut()
+            .read_render_target(&target)
+            .map_err(|e| format!("{name}: {e}"))?;
+        captures.push((name.to_string(), readback));
+    }
+
+    renderer.present().map_err(|e| e.to_string())?;
+    Ok(captures)
+}
+
+fn golden_path(backend_name: &str, name: &str, format: TextureFormat) -> PathBuf {
+    let ext = if is_float(format) { "exr" } else { "png" };
+    Path::new(GOLDEN_DIR)
+        .join(backend_name)
+        .join(format!("{name}.{ext}"))
+}
+
+fn save(image: &image::Rgba32FImage, path: &Path) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let result = if path.extension().is_some_and(|e| e == "exr") {
+        image.save(path)
+    } else {
+        image::DynamicImage::ImageRgba32F(image.clone())
+            .to_rgba8()
+            .save(path)
+    };
+    result.map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+/// Compare captures against the golden images of `backend_name`, or rewrite
+/// them if `SPARKLE_UPDATE_GOLDEN` is set. Collects all failing targets.
+pub fn check_against_golden(
+    backend_name: &str,
+    captures: &[(String, TextureReadback)],
+) -> Result<(), String> {
+    let update = std::env::var_os(UPDATE_ENV).is_some();
+    let mut failures = Vec::new();
+
+    for (name, readback) in captures {
+        let actual = screenshot::readback_to_rgba32f(readback).map_err(|e| e.to_string())?;
+        let path = golden_path(backend_name, name, readback.format);
+
+        if update {
+            save(&actual, &path)?;
+            println!("Golden image written: {}", path.display());
+            continue;
+        }
+
+        let golden = match image::open(&path) {
+            Ok(img) => img.to_rgba32f(),
+            Err(e) => {
+                failures.push(format!(
+                    "{name}: cannot open {} ({e}), run with {UPDATE_ENV}=1 to create it",
+                    path.display()
+                ));
+                continue;
+            }
+        };
+        if golden.dimensions() != actual.dimensions() {
+            failures.push(format!(
+                "{name}: size {:?} does not match golden {:?}",
+                actual.dimensions(),
+                golden.dimensions()
+            ));
+            continue;
+        }
+
+        let tolerance = Tolerance::for_format(readback.format);
+        let (report, diff) = compare(&actual, &golden, &tolerance);
+        if !report.passes(&tolerance) {
+            let out_dir = Path::new(DIFF_DIR).join(backend_name);
+            let actual_path = out_dir.join(path.file_name().unwrap());
+            let diff_path = out_dir.join(format!("{name}_diff.png"));
+            save(&actual, &actual_path)?;
+            diff.save(&diff_path)
+                .map_err(|e| format!("Failed to write {}: {e}", diff_path.display()))?;
+            failures.push(format!(
+                "{name}: {}/{} pixels outside tolerance (max error {:.1}x), see {}",
+                report.mismatched,
+                report.total,
+                report.max_error,
+                diff_path.display()
+            ));
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures.join("\n"))
+    }
+}
+
+#[test]
+#[ignore = "needs a Vulkan device, run with --ignored"]
+fn golden_reference_scene_vulkan() {
+    let backend = crate::engine::vulkan_backend::initialize_headless(
+        GOLDEN_SIZE.0,
+        GOLDEN_SIZE.1,
+        &golden_settings(),
+    )
+    .unwrap_or_else(|e| panic!("No Vulkan device for the golden test: {e}"));
+    let captures = render_reference_frame(backend).unwrap();
+    if let Err(failures) = check_against_golden("vulkan", &captures) {
+        panic!("Golden image mismatch:\n{failures}");
+    }
+}
+
+/// Checks the CPU re-implementation of the passes against itself: catches
+/// changes to the renderer and graph, not to the shaders.
+#[test]
+fn golden_reference_scene_software() {
+    let backend =
+        crate::engine::software_backend::SoftwareBackend::new(GOLDEN_SIZE.0, GOLDEN_SIZE.1);
+    let captures = render_reference_frame(backend).unwrap();
+    if let Err(failures) = check_against_golden("software", &captures) {
+        panic!("Golden image mismatch:\n{failures}");
+    }
+}
+
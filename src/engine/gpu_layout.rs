@@ -0,0 +1,317 @@
+//! Rust-side source of truth for structs shared with the shaders.
+//!
+//! [`gpu_struct!`] declares a `#[repr(C)]` struct together with its GPU layout
+//! (std140, std430 or scalar). At compile time it checks every field offset of
+//! the Rust struct against the offset the layout rules give the matching Slang
+//! member, so a missing padding field is a build error rather than garbage on the
+//! GPU. The same declaration emits the Slang definition; all shared structs are
+//! collected in `src/shaders/slang/modules/gpu_types.slang`, which the shaders
+//! import instead of declaring their own copies.
+//!
+//! The test in this module fails if that file is out of date. Run it with
+//! `SPARKLE_UPDATE_SLANG=1` to regenerate it, then recompile the shaders.
+
+use super::compute_push::ComputePushConstants;
+use super::draw_programs::{CameraUniforms, ClusterUniforms, GpuLight, SsaoUniforms, TaaUniforms};
+use super::vulkan_backend::PushConstants;
+
+/// Memory layout rules of a buffer block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// Uniform buffers.
+    Std140,
+    /// Storage buffers and push constants.
+    Std430,
+    /// `VK_EXT_scalar_block_layout`.
+    Scalar,
+}
+
+impl Layout {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Layout::Std140 => "std140",
+            Layout::Std430 => "std430",
+            Layout::Scalar => "scalar",
+        }
+    }
+}
+
+/// A type usable as a field of a [`gpu_struct!`].
+///
+/// All implementors have the same size in Rust and on the GPU, so only the
+/// alignment depends on the layout.
+pub trait GpuField {
+    /// Slang type name (of the element, for arrays).
+    const SLANG_TYPE: &'static str;
+    /// Array length, `0` for non-arrays.
+    const ARRAY_LEN: usize = 0;
+    /// Number of 32 bit padding words, emitted as individual `uint`s.
+    const PAD_WORDS: usize = 0;
+    /// Base alignment in std140, std430 and scalar layout.
+    const ALIGN: [usize; 3];
+    const SIZE: usize;
+}
+
+macro_rules! gpu_field {
+    ($($ty:ty => $slang:literal, $size:literal, [$std140:literal, $std430:literal, $scalar:literal];)*) => {
+        $(
+            impl GpuField for $ty {
+                const SLANG_TYPE: &'static str = $slang;
+                const ALIGN: [usize; 3] = [$std140, $std430, $scalar];
+                const SIZE: usize = $size;
+            }
+        )*
+    };
+}
+
+gpu_field! {
+    f32 => "float", 4, [4, 4, 4];
+    u32 => "uint", 4, [4, 4, 4];
+    i32 => "int", 4, [4, 4, 4];
+    [f32; 2] => "float2", 8, [8, 8, 4];
+    [f32; 3] => "float3", 12, [16, 16, 4];
+    [f32; 4] => "float4", 16, [16, 16, 4];
+    [f32; 16] => "float4x4", 64, [16, 16, 4];
+    glm::Vec2 => "float2", 8, [8, 8, 4];
+    glm::Vec3 => "float3", 12, [16, 16, 4];
+    glm::Vec4 => "float4", 16, [16, 16, 4];
+    glm::Mat4 => "float4x4", 64, [16, 16, 4];
+}
+
+/// `float4` arrays: the element stride is 16 bytes in every layout.
+impl<const N: usize> GpuField for [[f32; 4]; N] {
+    const SLANG_TYPE: &'static str = "float4";
+    const ARRAY_LEN: usize = N;
+    const ALIGN: [usize; 3] = [16, 16, 4];
+    const SIZE: usize = 16 * N;
+}
+
+/// `float4x4` arrays: the element stride is 64 bytes in every layout.
+impl<const N: usize> GpuField for [glm::Mat4; N] {
+    const SLANG_TYPE: &'static str = "float4x4";
+    const ARRAY_LEN: usize = N;
+    const ALIGN: [usize; 3] = [16, 16, 4];
+    const SIZE: usize = 64 * N;
+}
+
+/// Explicit padding of `N` 32 bit words.
+///
+/// Emitted as `N` scalar members, which never change the alignment of the
+/// following field (a `float3 _pad` would be aligned to 16 bytes in std140).
+#[repr(transparent)]
+#[derive(Clone, Copy)]
+pub struct Pad<const N: usize>([u32; N]);
+
+impl<const N: usize> Pad<N> {
+    pub const ZERO: Self = Pad([0; N]);
+}
+
+impl<const N: usize> Default for Pad<N> {
+    fn default() -> Self {
+        Self::ZERO
+    }
+}
+
+impl<const N: usize> GpuField for Pad<N> {
+    const SLANG_TYPE: &'static str = "uint";
+    const PAD_WORDS: usize = N;
+    const ALIGN: [usize; 3] = [4, 4, 4];
+    const SIZE: usize = 4 * N;
+}
+
+/// One member of a generated Slang struct.
+pub struct SlangField {
+    pub name: &'static str,
+    pub slang_type: &'static str,
+    pub array_len: usize,
+    pub pad_words: usize,
+}
+
+/// A struct declared with [`gpu_struct!`].
+pub trait GpuStruct: Sized {
+    const SLANG_NAME: &'static str;
+    const LAYOUT: Layout;
+
+    fn slang_fields() -> Vec<SlangField>;
+
+    /// The Slang struct definition.
+    fn slang_definition() -> String {
+        let mut out = format!(
+            "/// {} layout, {} bytes\npublic struct {} {{\n",
+            Self::LAYOUT.name(),
+            std::mem::size_of::<Self>(),
+            Self::SLANG_NAME
+        );
+        for field in Self::slang_fields() {
+            if field.pad_words > 0 {
+                for i in 0..field.pad_words {
+                    out += &format!("    public {} {}_{i};\n", field.slang_type, field.name);
+                }
+            } else if field.array_len > 0 {
+                out += &format!(
+                    "    public {} {}[{}];\n",
+                    field.slang_type, field.name, field.array_len
+                );
+            } else {
+                out += &format!("    public {} {};\n", field.slang_type, field.name);
+            }
+        }
+        out += "};\n";
+        out
+    }
+}
+
+pub const fn align_up(offset: usize, align: usize) -> usize {
+    offset.div_ceil(align) * align
+}
+
+/// Declare a `#[repr(C)]` struct shared with the shaders.
+///
+/// ```ignore
+/// gpu_struct! {
+///     pub(crate) struct GpuLight as "Light": Std140 {
+///         position: glm::Vec3,
+///         t as "type": u32,
+///         ..
+///     }
+/// }
+/// ```
+///
+/// `as "name"` renames the struct or a field on the Slang side. Fails to compile
+/// if a Rust field offset differs from the offset in the given layout, or if the
+/// struct size is not the end of its last field rounded up to the struct
+/// alignment (at least 16 bytes in std140).
+macro_rules! gpu_struct {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident $(as $slang_name:literal)? : $layout:ident {
+            $(
+                $(#[$field_meta:meta])*
+                $field_vis:vis $field:ident $(as $slang_field:literal)? : $ty:ty
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[repr(C)]
+        $vis struct $name {
+            $(
+                $(#[$field_meta])*
+                $field_vis $field: $ty,
+            )*
+        }
+
+        impl $crate::engine::gpu_layout::GpuStruct for $name {
+            const SLANG_NAME: &'static str = $crate::engine::gpu_layout::gpu_struct!(
+                @name $($slang_name)?, stringify!($name)
+            );
+            const LAYOUT: $crate::engine::gpu_layout::Layout =
+                $crate::engine::gpu_layout::Layout::$layout;
+
+            fn slang_fields() -> Vec<$crate::engine::gpu_layout::SlangField> {
+                use $crate::engine::gpu_layout::GpuField;
+                vec![$(
+                    $crate::engine::gpu_layout::SlangField {
+                        name: $crate::engine::gpu_layout::gpu_struct!(
+                            @name $($slang_field)?, stringify!($field)
+                        ),
+                        slang_type: <$ty as GpuField>::SLANG_TYPE,
+                        array_len: <$ty as GpuField>::ARRAY_LEN,
+                        pad_words: <$ty as GpuField>::PAD_WORDS,
+                    },
+                )*]
+            }
+        }
+
+        const _: () = {
+            use $crate::engine::gpu_layout::{GpuField, Layout, align_up};
+            let layout = Layout::$layout as usize;
+            let mut offset = 0usize;
+            // std140 rounds the alignment of a struct up to that of a vec4
+            let mut struct_align = if matches!(Layout::$layout, Layout::Std140) { 16 } else { 1 };
+            $(
+                if <$ty as GpuField>::ALIGN[layout] > struct_align {
+                    struct_align = <$ty as GpuField>::ALIGN[layout];
+                }
+                assert!(
+                    <$ty as GpuField>::SIZE == std::mem::size_of::<$ty>(),
+                    concat!("GPU size mismatch: ", stringify!($name), "::", stringify!($field))
+                );
+                offset = align_up(offset, <$ty as GpuField>::ALIGN[layout]);
+                assert!(
+                    offset == std::mem::offset_of!($name, $field),
+                    concat!(
+                        "GPU layout mismatch: ",
+                        stringify!($name), "::", stringify!($field),
+                        " is not at its ", stringify!($layout), " offset"
+                    )
+                );
+                offset += <$ty as GpuField>::SIZE;
+            )*
+            assert!(
+                std::mem::size_of::<$name>() == align_up(offset, struct_align),
+                concat!(
+                    "GPU size mismatch: ", stringify!($name),
+                    " is not padded to its ", stringify!($layout), " alignment"
+                )
+            );
+        };
+    };
+    (@name $name:literal, $default:expr) => {
+        $name
+    };
+    (@name , $default:expr) => {
+        $default
+    };
+}
+
+pub(crate) use gpu_struct;
+
+const GENERATED_HEADER: &str = "\
+// Generated from the Rust structs declared with `gpu_struct!` (src/engine/gpu_layout.rs).
+// Do not edit: run `SPARKLE_UPDATE_SLANG=1 cargo test gpu_types` to regenerate.
+module gpu_types;
+";
+
+/// Contents of `src/shaders/slang/modules/gpu_types.slang`.
+pub fn slang_module() -> String {
+    let definitions = [
+        PushConstants::slang_definition(),
+        ComputePushConstants::slang_definition(),
+        GpuLight::slang_definition(),
+        CameraUniforms::slang_definition(),
+        ClusterUniforms::slang_definition(),
+        SsaoUniforms::slang_definition(),
+        TaaUniforms::slang_definition(),
+    ];
+    let mut out = GENERATED_HEADER.to_string();
+    for definition in definitions {
+        out += "\n";
+        out += &definition;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GENERATED_FILE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/shaders/slang/modules/gpu_types.slang"
+    );
+    const UPDATE_ENV: &str = "SPARKLE_UPDATE_SLANG";
+
+    #[test]
+    fn gpu_types_slang_is_up_to_date() {
+        let generated = slang_module();
+        if std::env::var_os(UPDATE_ENV).is_some() {
+            std::fs::write(GENERATED_FILE, &generated).unwrap();
+            return;
+        }
+        let on_disk = std::fs::read_to_string(GENERATED_FILE).unwrap_or_default();
+        assert!(
+            on_disk == generated,
+            "{GENERATED_FILE} is out of date, run with {UPDATE_ENV}=1 and recompile the shaders"
+        );
+    }
+}
@@ -0,0 +1,1402 @@
+//! In-memory [`GpuBackend`] implementation that records every call.
+//!
+//! `HeadlessBackend` needs no GPU, window or driver. Resource creation hands out
+//! lightweight fake objects with unique ids, and every command is appended to a
+//! log that can be inspected afterwards (pass order, draw counts, bindings, ...).
+//! Buffers keep a CPU copy of their contents so uniform uploads can be checked.
+
+use std::cell::{Cell, Ref, RefCell};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::engine::backend::{
+    AccelerationStructureType, BufferDesc, BufferUsage, ComputeBarrier, ComputeBindingDesc,
+    ComputeBindingType, ComputeDispatch, ComputePipelineDesc, ComputeResource,
+    GpuAccelerationStructure, GpuBackend, GpuBuffer, GpuError, GpuErrorKind, GpuRenderTarget,
+    GpuTexture, LoadOp, MaterialProperties, ProceduralShaders, RenderItem, RenderPassDesc,
+    RenderPipelineDesc, RenderTargetDesc, RenderTargetUsage, RtShaders, SamplerDesc, ShaderStage,
+    Shaders, TargetAccess, TargetBarrier, TextureDesc, TextureDimension, TextureFormat,
+    TextureReadback, ViewportDesc,
+};
+
+/// MSAA sample count the fake device claims to support.
+const HEADLESS_MAX_SAMPLES: u32 = 8;
+
+static HEADLESS_RESOURCE_ID: AtomicUsize = AtomicUsize::new(1);
+
+fn next_resource_id() -> usize {
+    HEADLESS_RESOURCE_ID.fetch_add(1, Ordering::SeqCst)
+}
+
+// Resources
+
+/// Fake texture / render target. Only carries its description and ids.
+#[derive(Debug, Clone)]
+pub struct HeadlessTexture {
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    pub id: usize,
+    pub bindless_index: u32,
+    pub usage: Option<RenderTargetUsage>,
+    pub dimension: TextureDimension,
+    pub depth_or_layers: u32,
+    pub sample_count: u32,
+}
+
+impl GpuTexture for HeadlessTexture {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn bindless_index(&self) -> u32 {
+        self.bindless_index
+    }
+}
+
+impl GpuRenderTarget for HeadlessTexture {}
+
+/// Fake buffer backed by a CPU byte vector.
+#[derive(Debug)]
+pub struct HeadlessBuffer {
+    pub id: usize,
+    pub label: String,
+    pub usage: BufferUsage,
+    data: RefCell<Vec<u8>>,
+}
+
+impl HeadlessBuffer {
+    /// Current contents of the buffer (last data written by an upload).
+    pub fn contents(&self) -> Ref<'_, Vec<u8>> {
+        self.data.borrow()
+    }
+
+    fn write(&self, data: &[u8]) {
+        let mut contents = self.data.borrow_mut();
+        let len = data.len().min(contents.len());
+        contents[..len].copy_from_slice(&data[..len]);
+    }
+}
+
+impl GpuBuffer for HeadlessBuffer {
+    fn size(&self) -> usize {
+        self.data.borrow().len()
+    }
+}
+
+/// What kind of pipeline a [`HeadlessPipeline`] stands in for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeadlessPipelineKind {
+    Render,
+    Compute,
+    RayTracing,
+}
+
+/// Fake pipeline. Keeps the label so the command log stays readable.
+#[derive(Debug, Clone)]
+pub struct HeadlessPipeline {
+    pub id: usize,
+    pub label: String,
+    pub kind: HeadlessPipelineKind,
+    /// Set 1 of a compute pipeline, empty for the other kinds.
+    pub compute_bindings: Vec<ComputeBindingDesc>,
+    pub push_constant_size: u32,
+}
+
+/// Fake acceleration structure.
+#[derive(Debug, Clone)]
+pub struct HeadlessAccelerationStructure {
+    pub id: usize,
+    pub ty: AccelerationStructureType,
+    /// Number of BLAS instances for a TLAS, `0` for a BLAS.
+    pub instance_count: usize,
+    vertex_device_address: u64,
+    index_device_address: u64,
+}
+
+impl GpuAccelerationStructure for HeadlessAccelerationStructure {
+    fn vertex_device_address(&self) -> u64 {
+        self.vertex_device_address
+    }
+
+    fn index_device_address(&self) -> u64 {
+        self.index_device_address
+    }
+}
+
+/// Shader "source" for the headless backend: just a name.
+#[derive(Debug, Clone)]
+pub struct HeadlessShader {
+    pub label: &'static str,
+}
+
+// Command log
+
+/// A color or depth attachment as seen by [`RecordedCommand::BeginRenderPass`].
+#[derive(Debug, Clone, Copy)]
+pub struct RecordedAttachment {
+    pub target: usize,
+    pub load_op: LoadOp,
+    /// `None` for color attachments.
+    pub stencil_load_op: Option<LoadOp>,
+    pub resolve_target: Option<usize>,
+    /// Array layer rendered into, 0 for color attachments.
+    pub layer: u32,
+}
+
+/// One backend call, in the order it was issued.
+#[derive(Debug, Clone)]
+pub enum RecordedCommand {
+    BeginFrame,
+    EndFrame,
+    Present,
+    BeginRenderPass {
+        label: String,
+        color_targets: Vec<RecordedAttachment>,
+        depth_target: Option<RecordedAttachment>,
+    },
+    EndRenderPass,
+    SetPipeline {
+        pipeline: usize,
+        label: String,
+    },
+    SetViewport(ViewportDesc),
+    BindTexture {
+        slot: u32,
+        texture: usize,
+    },
+    BindRenderTargetAsTexture {
+        slot: u32,
+        target: usize,
+    },
+    BindUniform {
+        stage: ShaderStage,
+        slot: u32,
+        buffer: usize,
+    },
+    BindBufferToDescriptor {
+        binding: u32,
+        buffer: usize,
+    },
+    SetVertexBuffer(usize),
+    SetIndexBuffer(usize),
+    DrawIndexed {
+        index_count: u32,
+        first_index: u32,
+        base_vertex: i32,
+    },
+    DrawIndexedIndirect {
+        buffer: usize,
+        offset: u64,
+        draw_count: u32,
+    },
+    SetModelMatrix {
+        model: glm::Mat4,
+        prev_model: glm::Mat4,
+    },
+    SetMaterialProperties {
+        has_parallax: bool,
+        layered: bool,
+    },
+    UpdateBuffer {
+        buffer: usize,
+        size: usize,
+    },
+    CmdUpdateBuffer {
+        buffer: usize,
+        size: usize,
+    },
+    ExecuteCompute {
+        pipeline: usize,
+        work_groups: (u32, u32, u32),
+    },
+    Dispatch {
+        pipeline: usize,
+        work_groups: (u32, u32, u32),
+    },
+    DispatchIndirect {
+        pipeline: usize,
+        buffer: usize,
+        offset: u64,
+    },
+    ComputeBarrier(ComputeBarrier),
+    TargetBarrier {
+        target: usize,
+        before: TargetAccess,
+        after: TargetAccess,
+    },
+    DispatchRays {
+        pipeline: usize,
+        tlas: usize,
+        output: usize,
+        width: u32,
+        height: u32,
+        number_of_lights: u32,
+    },
+    RenderEgui {
+        primitives: usize,
+    },
+    Resize {
+        width: u32,
+        height: u32,
+    },
+    BeginEvent(String),
+    EndEvent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameState {
+    Idle,
+    Recording,
+    Submitted,
+}
+
+// Backend
+
+/// GPU-less backend that records all calls into an inspectable command log.
+pub struct HeadlessBackend {
+    width: u32,
+    height: u32,
+    backbuffer_format: TextureFormat,
+    backbuffer: HeadlessTexture,
+    depth_target: HeadlessTexture,
+    rt_supported: bool,
+    next_bindless_index: Cell<u32>,
+    frame_state: FrameState,
+    in_render_pass: bool,
+    frame_count: u64,
+    commands: RefCell<Vec<RecordedCommand>>,
+}
+
+impl HeadlessBackend {
+    pub fn new(width: u32, height: u32) -> Self {
+        let backbuffer_format = TextureFormat::Bgra8UnormSrgb;
+        HeadlessBackend {
+            width,
+            height,
+            backbuffer_format,
+            backbuffer: Self::swapchain_target(width, height, backbuffer_format),
+            depth_target: Self::swapchain_target(width, height, TextureFormat::Depth32Float),
+            rt_supported: false,
+            next_bindless_index: Cell::new(0),
+            frame_state: FrameState::Idle,
+            in_render_pass: false,
+            frame_count: 0,
+            commands: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Report ray tracing support, so the RT code paths of the renderer get exercised.
+    pub fn with_rt_support(mut self, supported: bool) -> Self {
+        self.rt_supported = supported;
+        self
+    }
+
+    /// Use a different backbuffer format (e.g. `Abgr10Unorm` to mimic an HDR swapchain).
+    pub fn with_backbuffer_format(mut self, format: TextureFormat) -> Self {
+        self.backbuffer_format = format;
+        self.backbuffer.format = format;
+        self
+    }
+
+    /// All commands recorded so far.
+    pub fn commands(&self) -> Ref<'_, Vec<RecordedCommand>> {
+        self.commands.borrow()
+    }
+
+    /// Return the recorded commands and clear the log.
+    pub fn take_commands(&self) -> Vec<RecordedCommand> {
+        self.commands.take()
+    }
+
+    pub fn clear_commands(&self) {
+        self.commands.borrow_mut().clear();
+    }
+
+    /// Number of frames that went through `present`.
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    /// Labels of all render passes in recording order.
+    pub fn render_pass_labels(&self) -> Vec<String> {
+        self.commands
+            .borrow()
+            .iter()
+            .filter_map(|cmd| match cmd {
+                RecordedCommand::BeginRenderPass { label, .. } => Some(label.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Number of draw calls (direct and indirect) recorded so far.
+    pub fn draw_count(&self) -> usize {
+        self.commands
+            .borrow()
+            .iter()
+            .filter(|cmd| {
+                matches!(
+                    cmd,
+                    RecordedCommand::DrawIndexed { .. }
+                        | RecordedCommand::DrawIndexedIndirect { .. }
+                )
+            })
+            .count()
+    }
+
+    fn record(&self, cmd: RecordedCommand) {
+        self.commands.borrow_mut().push(cmd);
+    }
+
+    /// Reject dispatches the Vulkan backend would not record: a pipeline of
+    /// another kind, too many push-constant bytes, or resources that don't
+    /// match the bindings of set 1.
+    fn check_dispatch(dispatch: &ComputeDispatch<Self>) -> Result<(), GpuError> {
+        let pipeline = dispatch.pipeline;
+        let error = |message: String| Err(GpuError::new(message, GpuErrorKind::Other));
+        if pipeline.kind != HeadlessPipelineKind::Compute {
+            return error(format!(
+                "Pipeline '{}' is not a compute pipeline",
+                pipeline.label
+            ));
+        }
+        if dispatch.push_constants.len() as u32 > pipeline.push_constant_size {
+            return error(format!(
+                "{} bytes of push constants exceed the {} bytes of pipeline '{}'",
+                dispatch.push_constants.len(),
+                pipeline.push_constant_size,
+                pipeline.label
+            ));
+        }
+        for desc in &pipeline.compute_bindings {
+            let resource = dispatch
+                .resources
+                .iter()
+                .find(|(binding, _)| *binding == desc.binding)
+                .map(|(_, resource)| resource);
+            let ty = match resource {
+                Some(ComputeResource::StorageBuffer(_)) => ComputeBindingType::StorageBuffer,
+                Some(ComputeResource::Texture(_)) => ComputeBindingType::Texture,
+                Some(ComputeResource::StorageImage(_)) => ComputeBindingType::StorageImage,
+                None => {
+                    return error(format!(
+                        "Binding {} of pipeline '{}' has no resource",
+                        desc.binding, pipeline.label
+                    ));
+                }
+            };
+            if ty != desc.ty {
+                return error(format!(
+                    "Binding {} of pipeline '{}' expects a {:?}, got a {ty:?}",
+                    desc.binding, pipeline.label, desc.ty
+                ));
+            }
+        }
+        if let Some((binding, _)) = dispatch.resources.iter().find(|(binding, _)| {
+            !pipeline
+                .compute_bindings
+                .iter()
+                .any(|d| d.binding == *binding)
+        }) {
+            return error(format!(
+                "Pipeline '{}' has no binding {binding}",
+                pipeline.label
+            ));
+        }
+        Ok(())
+    }
+
+    fn swapchain_target(width: u32, height: u32, format: TextureFormat) -> HeadlessTexture {
+        HeadlessTexture {
+            width,
+            height,
+            format,
+            id: next_resource_id(),
+            bindless_index: u32::MAX,
+            usage: Some(if is_depth_format(format) {
+                RenderTargetUsage::Depth
+            } else {
+                RenderTargetUsage::Color
+            }),
+            dimension: TextureDimension::D2,
+            depth_or_layers: 1,
+            sample_count: 1,
+        }
+    }
+
+    fn allocate_bindless(&self) -> u32 {
+        let idx = self.next_bindless_index.get();
+        self.next_bindless_index.set(idx + 1);
+        idx
+    }
+
+    fn check_size(width: u32, height: u32, what: &str) -> Result<(), GpuError> {
+        if width == 0 || height == 0 {
+            return Err(GpuError::new(
+                format!("Cannot create {what} with zero extent ({width}x{height})"),
+                GpuErrorKind::ResourceCreation,
+            ));
+        }
+        Ok(())
+    }
+
+    fn shader(label: &'static str) -> Vec<HeadlessShader> {
+        vec![HeadlessShader { label }]
+    }
+}
+
+fn is_depth_format(format: TextureFormat) -> bool {
+    matches!(
+        format,
+        TextureFormat::Depth32Float | TextureFormat::Depth24Stencil8
+    )
+}
+
+impl GpuBackend for HeadlessBackend {
+    type Texture = HeadlessTexture;
+
+    type RenderTarget = HeadlessTexture;
+
+    type Buffer = HeadlessBuffer;
+
+    type Pipeline = HeadlessPipeline;
+
+    type ShaderSource = Vec<HeadlessShader>;
+
+    type AccelerationStructure = HeadlessAccelerationStructure;
+
+    fn load_shaders(&self) -> Shaders<Self> {
+        Shaders {
+            deferred_pre: Self::shader("deferred_pre"),
+            shadow: Self::shader("shadow"),
+            ssao: Self::shader("ssao"),
+            ssao_blur: Self::shader("ssao_blur"),
+            deferred_light: Self::shader("deferred_light"),
+            forward: Self::shader("forward"),
+            taa: Self::shader("taa"),
+            output: Self::shader("output"),
+            skybox: Self::shader("skybox"),
+            light_culling: Self::shader("light_culling"),
+        }
+    }
+
+    fn load_proc_gen_shaders(&self) -> ProceduralShaders<Self> {
+        ProceduralShaders {
+            scattering: Self::shader("scattering"),
+        }
+    }
+
+    fn create_texture(&self, desc: &TextureDesc, data: &[u8]) -> Result<Self::Texture, GpuError> {
+        Self::check_size(desc.width, desc.height, "texture")?;
+        desc.validate(data)?;
+        Ok(HeadlessTexture {
+            width: desc.width,
+            height: desc.height,
+            format: desc.format,
+            id: next_resource_id(),
+            bindless_index: self.allocate_bindless(),
+            usage: None,
+            dimension: desc.dimension,
+            depth_or_layers: desc.depth_or_layers,
+            sample_count: 1,
+        })
+    }
+
+    fn create_layer_view(
+        &self,
+        texture: &Self::Texture,
+        layer: u32,
+    ) -> Result<Self::Texture, GpuError> {
+        if texture.dimension == TextureDimension::D3 || layer >= texture.depth_or_layers {
+            return Err(GpuError::new(
+                format!(
+                    "Cannot view layer {layer} of a {:?} texture with {} layers",
+                    texture.dimension, texture.depth_or_layers
+                ),
+                GpuErrorKind::ResourceCreation,
+            ));
+        }
+        Ok(HeadlessTexture {
+            id: next_resource_id(),
+            bindless_index: self.allocate_bindless(),
+            dimension: TextureDimension::D2,
+            depth_or_layers: 1,
+            ..texture.clone()
+        })
+    }
+
+    fn create_buffer(
+        &self,
+        desc: &BufferDesc,
+        data: Option<&[u8]>,
+    ) -> Result<Self::Buffer, GpuError> {
+        if desc.size == 0 {
+            return Err(GpuError::new(
+                format!("Cannot create zero-sized buffer '{}'", desc.label),
+                GpuErrorKind::ResourceCreation,
+            ));
+        }
+        let buffer = HeadlessBuffer {
+            id: next_resource_id(),
+            label: desc.label.clone(),
+            usage: desc.usage,
+            data: RefCell::new(vec![0; desc.size]),
+        };
+        if let Some(data) = data {
+            buffer.write(data);
+        }
+        Ok(buffer)
+    }
+
+    fn create_render_target(
+        &self,
+        desc: &RenderTargetDesc,
+    ) -> Result<Self::RenderTarget, GpuError> {
+        desc.validate()?;
+        Self::check_size(desc.width, desc.height, "render target")?;
+        if desc.format.is_compressed() {
+            return Err(GpuError::new(
+                format!("{:?} cannot be used as a render target", desc.format),
+                GpuErrorKind::ResourceCreation,
+            ));
+        }
+        if desc.sample_count == 0 || desc.sample_count > HEADLESS_MAX_SAMPLES {
+            return Err(GpuError::new(
+                format!("Invalid sample count {}", desc.sample_count),
+                GpuErrorKind::ResourceCreation,
+            ));
+        }
+        // multisampled targets are only resolved, never sampled
+        let bindless_index = if desc.sample_count > 1 {
+            u32::MAX
+        } else {
+            self.allocate_bindless()
+        };
+        Ok(HeadlessTexture {
+            width: desc.width,
+            height: desc.height,
+            format: desc.format,
+            id: next_resource_id(),
+            bindless_index,
+            usage: Some(desc.usage),
+            dimension: if desc.layers > 1 {
+                TextureDimension::CubeArray
+            } else {
+                TextureDimension::D2
+            },
+            depth_or_layers: desc.layers,
+            sample_count: desc.sample_count,
+        })
+    }
+
+    fn create_render_pipeline(
+        &self,
+        desc: &RenderPipelineDesc<Self::ShaderSource>,
+    ) -> Result<Self::Pipeline, GpuError> {
+        desc.validate()?;
+        Ok(HeadlessPipeline {
+            id: next_resource_id(),
+            label: desc.label.to_string(),
+            kind: HeadlessPipelineKind::Render,
+            compute_bindings: Vec::new(),
+            push_constant_size: 0,
+        })
+    }
+
+    fn create_compute_pipeline(
+        &self,
+        desc: &ComputePipelineDesc<Self::ShaderSource>,
+    ) -> Result<Self::Pipeline, GpuError> {
+        Ok(HeadlessPipeline {
+            id: next_resource_id(),
+            label: desc.label.to_string(),
+            kind: HeadlessPipelineKind::Compute,
+            compute_bindings: desc.bindings.to_vec(),
+            push_constant_size: desc.push_constant_size,
+        })
+    }
+
+    fn dispatch_compute_one_shot(
+        &self,
+        dispatch: &ComputeDispatch<Self>,
+        work_groups: (u32, u32, u32),
+    ) -> Result<(), GpuError> {
+        Self::check_dispatch(dispatch)?;
+        self.record(RecordedCommand::ExecuteCompute {
+            pipeline: dispatch.pipeline.id,
+            work_groups,
+        });
+        Ok(())
+    }
+
+    fn update_buffer(&self, buffer: &Self::Buffer, data: &[u8]) {
+        buffer.write(data);
+        self.record(RecordedCommand::UpdateBuffer {
+            buffer: buffer.id,
+            size: data.len(),
+        });
+    }
+
+    fn cmd_update_buffer(&mut self, buffer: &Self::Buffer, data: &[u8]) {
+        buffer.write(data);
+        self.record(RecordedCommand::CmdUpdateBuffer {
+            buffer: buffer.id,
+            size: data.len(),
+        });
+    }
+
+    fn begin_frame(&mut self) -> Result<(), GpuError> {
+        if self.frame_state == FrameState::Recording {
+            return Err(GpuError::new(
+                "begin_frame called while a frame is already being recorded",
+                GpuErrorKind::Other,
+            ));
+        }
+        self.frame_state = FrameState::Recording;
+        self.record(RecordedCommand::BeginFrame);
+        Ok(())
+    }
+
+    fn end_frame(&mut self) -> Result<(), GpuError> {
+        if self.frame_state != FrameState::Recording {
+            return Err(GpuError::new(
+                "end_frame called without an active frame",
+                GpuErrorKind::Other,
+            ));
+        }
+        if self.in_render_pass {
+            return Err(GpuError::new(
+                "end_frame called inside a render pass",
+                GpuErrorKind::RenderPass,
+            ));
+        }
+        self.frame_state = FrameState::Submitted;
+        self.record(RecordedCommand::EndFrame);
+        Ok(())
+    }
+
+    fn present(&mut self) -> Result<(), GpuError> {
+        if self.frame_state != FrameState::Submitted {
+            return Err(GpuError::new(
+                "present called before end_frame",
+                GpuErrorKind::Present,
+            ));
+        }
+        self.frame_state = FrameState::Idle;
+        self.frame_count += 1;
+        self.record(RecordedCommand::Present);
+        Ok(())
+    }
+
+    fn begin_render_pass(&mut self, desc: &RenderPassDesc<Self>) {
+        self.in_render_pass = true;
+        self.record(RecordedCommand::BeginRenderPass {
+            label: desc.label.to_string(),
+            color_targets: desc
+                .color_targets
+                .iter()
+                .map(|c| RecordedAttachment {
+                    target: c.target.id,
+                    load_op: c.load_op,
+                    stencil_load_op: None,
+                    resolve_target: c.resolve_target.map(|t| t.id),
+                    layer: 0,
+                })
+                .collect(),
+            depth_target: desc.depth_target.as_ref().map(|d| RecordedAttachment {
+                target: d.target.id,
+                load_op: d.load_op,
+                stencil_load_op: Some(d.stencil_load_op),
+                resolve_target: d.resolve_target.map(|t| t.id),
+                layer: d.layer,
+            }),
+        });
+    }
+
+    fn end_render_pass(&mut self) {
+        self.in_render_pass = false;
+        self.record(RecordedCommand::EndRenderPass);
+    }
+
+    fn set_pipeline(&mut self, pipeline: &Self::Pipeline) {
+        self.record(RecordedCommand::SetPipeline {
+            pipeline: pipeline.id,
+            label: pipeline.label.clone(),
+        });
+    }
+
+    fn set_viewport(&mut self, viewport: &ViewportDesc) {
+        self.record(RecordedCommand::SetViewport(*viewport));
+    }
+
+    fn bind_texture(&mut self, slot: u32, texture: &Self::Texture) {
+        self.record(RecordedCommand::BindTexture {
+            slot,
+            texture: texture.id,
+        });
+    }
+
+    fn bind_render_target_as_texture(&mut self, slot: u32, target: &Self::RenderTarget) {
+        self.record(RecordedCommand::BindRenderTargetAsTexture {
+            slot,
+            target: target.id,
+        });
+    }
+
+    fn bind_uniform(&mut self, stage: ShaderStage, slot: u32, buffer: &Self::Buffer) {
+        self.record(RecordedCommand::BindUniform {
+            stage,
+            slot,
+            buffer: buffer.id,
+        });
+    }
+
+    fn bind_buffer_to_descriptor(&self, binding: u32, buffer: &Self::Buffer) {
+        self.record(RecordedCommand::BindBufferToDescriptor {
+            binding,
+            buffer: buffer.id,
+        });
+    }
+
+    fn set_vertex_buffer(&mut self, buffer: &Self::Buffer) {
+        self.record(RecordedCommand::SetVertexBuffer(buffer.id));
+    }
+
+    fn set_index_buffer(&mut self, buffer: &Self::Buffer) {
+        self.record(RecordedCommand::SetIndexBuffer(buffer.id));
+    }
+
+    fn draw_indexed(&mut self, index_count: u32, first_index: u32, base_vertex: i32) {
+        self.record(RecordedCommand::DrawIndexed {
+            index_count,
+            first_index,
+            base_vertex,
+        });
+    }
+
+    fn draw_indexed_indirect(
+        &mut self,
+        indirect_commands_buffer: &Self::Buffer,
+        offset: u64,
+        draw_count: u32,
+    ) {
+        self.record(RecordedCommand::DrawIndexedIndirect {
+            buffer: indirect_commands_buffer.id,
+            offset,
+            draw_count,
+        });
+    }
+
+    fn set_model_matrix(&mut self, model: &glm::Mat4, prev_model: &glm::Mat4) {
+        self.record(RecordedCommand::SetModelMatrix {
+            model: *model,
+            prev_model: *prev_model,
+        });
+    }
+
+    fn set_material_properties(&mut self, props: MaterialProperties) {
+        self.record(RecordedCommand::SetMaterialProperties {
+            has_parallax: props.has_parallax,
+            layered: props.layered,
+        });
+    }
+
+    fn cmd_dispatch(&mut self, dispatch: &ComputeDispatch<Self>, work_groups: (u32, u32, u32)) {
+        if Self::check_dispatch(dispatch).is_err() {
+            return;
+        }
+        self.record(RecordedCommand::Dispatch {
+            pipeline: dispatch.pipeline.id,
+            work_groups,
+        });
+    }
+
+    fn cmd_dispatch_indirect(
+        &mut self,
+        dispatch: &ComputeDispatch<Self>,
+        args_buffer: &Self::Buffer,
+        offset: u64,
+    ) {
+        if Self::check_dispatch(dispatch).is_err() {
+            return;
+        }
+        self.record(RecordedCommand::DispatchIndirect {
+            pipeline: dispatch.pipeline.id,
+            buffer: args_buffer.id,
+            offset,
+        });
+    }
+
+    fn cmd_compute_barrier(&mut self, barrier: ComputeBarrier) {
+        self.record(RecordedCommand::ComputeBarrier(barrier));
+    }
+
+    fn cmd_target_barriers(&mut self, barriers: &[TargetBarrier<Self>]) {
+        for barrier in barriers {
+            self.record(RecordedCommand::TargetBarrier {
+                target: barrier.target.id,
+                before: barrier.before,
+                after: barrier.after,
+            });
+        }
+    }
+
+    fn backbuffer(&self) -> Self::RenderTarget {
+        self.backbuffer.clone()
+    }
+
+    fn main_depth_target(&self) -> Self::RenderTarget {
+        self.depth_target.clone()
+    }
+
+    fn default_viewport(&self) -> ViewportDesc {
+        ViewportDesc {
+            x: 0.0,
+            y: 0.0,
+            width: self.width as f32,
+            height: self.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        }
+    }
+
+    fn resolution(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn max_sample_count(&self) -> u32 {
+        HEADLESS_MAX_SAMPLES
+    }
+
+    fn supports_texture_format(&self, _format: TextureFormat) -> bool {
+        true
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        self.width = width;
+        self.height = height;
+        self.backbuffer = Self::swapchain_target(width, height, self.backbuffer_format);
+        self.depth_target = Self::swapchain_target(width, height, TextureFormat::Depth32Float);
+        self.record(RecordedCommand::Resize { width, height });
+    }
+
+    fn wait_idle(&self) -> Result<(), GpuError> {
+        Ok(())
+    }
+
+    /// Nothing is rasterized, so the readback is a pattern instead: every byte
+    /// holds its offset modulo 251, which lets tests check the row layout.
+    fn read_render_target(
+        &mut self,
+        target: &Self::RenderTarget,
+    ) -> Result<TextureReadback, GpuError> {
+        if target.format.is_depth() {
+            return Err(GpuError::new(
+                format!("Readback of depth target {} is not supported", target.id),
+                GpuErrorKind::ResourceUpdate,
+            ));
+        }
+        let size = target.width as usize * target.height as usize * target.format.bytes_per_pixel();
+        Ok(TextureReadback {
+            width: target.width,
+            height: target.height,
+            format: target.format,
+            data: (0..size).map(|i| (i % 251) as u8).collect(),
+        })
+    }
+
+    fn render_egui(
+        &mut self,
+        _textures_delta: &egui::TexturesDelta,
+        clipped_primitives: &[egui::ClippedPrimitive],
+        _pixels_per_point: f32,
+    ) {
+        self.record(RecordedCommand::RenderEgui {
+            primitives: clipped_primitives.len(),
+        });
+    }
+
+    fn begin_event(&self, name: &str) {
+        self.record(RecordedCommand::BeginEvent(name.to_string()));
+    }
+
+    fn end_event(&self) {
+        self.record(RecordedCommand::EndEvent);
+    }
+
+    // RT
+    fn has_rt_support(&self) -> bool {
+        self.rt_supported
+    }
+
+    fn create_blas(
+        &self,
+        ty: AccelerationStructureType,
+        render_items: &[RenderItem<'_, Self>],
+    ) -> Result<Vec<Self::AccelerationStructure>, GpuError> {
+        if !self.rt_supported {
+            return Err(GpuError::new(
+                "Ray tracing not supported on this device",
+                GpuErrorKind::Other,
+            ));
+        }
+        Ok(render_items
+            .iter()
+            .map(|item| HeadlessAccelerationStructure {
+                id: next_resource_id(),
+                ty,
+                instance_count: 0,
+                // fake "addresses": buffer ids are unique and non-zero
+                vertex_device_address: item.vertex_buffer().id as u64,
+                index_device_address: item.index_buffer().id as u64,
+            })
+            .collect())
+    }
+
+    fn create_tlas(
+        &self,
+        blas: &[Self::AccelerationStructure],
+        transforms: &[glm::Mat4],
+        object_types: &[crate::engine::backend::ObjType],
+        _albedo_indices: &[u32],
+    ) -> Result<Self::AccelerationStructure, GpuError> {
+        if !self.rt_supported {
+            return Err(GpuError::new(
+                "Ray tracing not supported on this device",
+                GpuErrorKind::Other,
+            ));
+        }
+        if blas.is_empty() {
+            return Err(GpuError::new(
+                "create_tlas: cannot build TLAS with zero instances",
+                GpuErrorKind::Other,
+            ));
+        }
+        if blas.len() != transforms.len() || blas.len() != object_types.len() {
+            return Err(GpuError::new(
+                "create_tlas: blas, transforms and object_types must have the same length",
+                GpuErrorKind::Other,
+            ));
+        }
+        Ok(HeadlessAccelerationStructure {
+            id: next_resource_id(),
+            ty: AccelerationStructureType::Tlas,
+            instance_count: blas.len(),
+            vertex_device_address: 0,
+            index_device_address: 0,
+        })
+    }
+
+    fn create_rt_pipeline(&self, _shaders: &RtShaders<Self>) -> Result<Self::Pipeline, GpuError> {
+        if !self.rt_supported {
+            return Err(GpuError::new(
+                "Raytracing is not supported!",
+                GpuErrorKind::Other,
+            ));
+        }
+        Ok(HeadlessPipeline {
+            id: next_resource_id(),
+            label: "Raytracing Pipeline".into(),
+            kind: HeadlessPipelineKind::RayTracing,
+            compute_bindings: Vec::new(),
+            push_constant_size: 0,
+        })
+    }
+
+    fn create_rt_output_target(
+        &self,
+        width: u32,
+        height: u32,
+    ) -> Result<Self::RenderTarget, GpuError> {
+        self.create_render_target(&RenderTargetDesc {
+            width,
+            height,
+            format: TextureFormat::R16g16b16a16Float,
+            sampler: SamplerDesc::default(),
+            usage: RenderTargetUsage::Storage,
+            sample_count: 1,
+            persistent: false,
+            layers: 1,
+        })
+    }
+
+    fn dispatch_rays(
+        &mut self,
+        pipeline: &Self::Pipeline,
+        tlas: &Self::AccelerationStructure,
+        output: &Self::RenderTarget,
+        _light_buffer: &Self::Buffer,
+        _material_buffer: &Self::Buffer,
+        width: u32,
+        height: u32,
+        number_of_lights: u32,
+    ) {
+        self.record(RecordedCommand::DispatchRays {
+            pipeline: pipeline.id,
+            tlas: tlas.id,
+            output: output.id,
+            width,
+            height,
+            number_of_lights,
+        });
+    }
+
+    fn load_rt_shaders(&self) -> RtShaders<Self> {
+        RtShaders {
+            raygen: Self::shader("rt_raygen"),
+            miss: Self::shader("rt_miss"),
+            miss_shadow: Self::shader("rt_miss_shadow"),
+            closest_hit: Self::shader("rt_closest_hit"),
+            any_hit: Self::shader("rt_any_hit"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::golden::{golden_settings, reference_camera};
+    use crate::engine::renderer::Renderer;
+    use crate::engine::settings::Settings;
+
+    fn reference_renderer(settings: Settings) -> Renderer<HeadlessBackend> {
+        let mut renderer = Renderer::create(HeadlessBackend::new(64, 64), settings).unwrap();
+        renderer.init_draw_programs().unwrap();
+        renderer
+            .load_scene("tests/golden/scenes/reference.gltf")
+            .unwrap();
+        renderer.set_use_ray_tracing(false);
+        renderer.set_camera_projection(&reference_camera());
+        renderer
+    }
+
+    /// Render one frame and return its commands, which stay in the log.
+    fn render_frame(renderer: &mut Renderer<HeadlessBackend>) -> Vec<RecordedCommand> {
+        let mut camera = reference_camera();
+        renderer.backend().clear_commands();
+        renderer.backend_mut().begin_frame().unwrap();
+        renderer.update_state(0.0, &mut camera);
+        renderer.render_scene(&camera).unwrap();
+        renderer.finish_frame().unwrap();
+        renderer.present().unwrap();
+        renderer.backend().commands().clone()
+    }
+
+    /// Draw calls of each render pass, in recording order.
+    fn draws_per_pass(commands: &[RecordedCommand]) -> Vec<(String, usize)> {
+        let mut passes: Vec<(String, usize)> = Vec::new();
+        for cmd in commands {
+            match cmd {
+                RecordedCommand::BeginRenderPass { label, .. } => passes.push((label.clone(), 0)),
+                RecordedCommand::DrawIndexed { .. }
+                | RecordedCommand::DrawIndexedIndirect { .. } => {
+                    passes.last_mut().unwrap().1 += 1;
+                }
+                _ => (),
+            }
+        }
+        passes
+    }
+
+    #[test]
+    fn frame_runs_passes_in_graph_order() {
+        let mut renderer = reference_renderer(golden_settings());
+        render_frame(&mut renderer);
+        let backend = renderer.backend();
+        assert_eq!(backend.frame_count(), 1);
+        assert_eq!(
+            backend.render_pass_labels(),
+            [
+                "deferred_pre",
+                // one cascade per split and the two lights' atlas tiles
+                "shadow",
+                "shadow",
+                "shadow",
+                "shadow",
+                "shadow",
+                "ssao",
+                "ssao_blur",
+                "deferred_light",
+                "forward",
+                "taa",
+                "output",
+                "skybox",
+            ]
+        );
+
+        // disabled passes are culled from the graph
+        renderer.settings_mut().ssao.enabled = false;
+        renderer.settings_mut().taa = false;
+        render_frame(&mut renderer);
+        let labels = renderer.backend().render_pass_labels();
+        assert!(
+            !labels
+                .iter()
+                .any(|l| l == "ssao" || l == "ssao_blur" || l == "taa")
+        );
+        assert_eq!(labels.first().unwrap(), "deferred_pre");
+        assert_eq!(labels.last().unwrap(), "skybox");
+    }
+
+    #[test]
+    fn imported_scene_is_drawn_once_per_view() {
+        let mut renderer = reference_renderer(golden_settings());
+        let tree = renderer.scene_tree().unwrap();
+        let mut names: Vec<_> = tree.children.iter().map(|c| c.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, ["cube", "ground"]);
+        let drawables: usize = tree.children.iter().map(|c| c.num_drawables).sum();
+        assert_eq!(drawables, 2);
+
+        let commands = render_frame(&mut renderer);
+        for (pass, draws) in draws_per_pass(&commands) {
+            let expected = match pass.as_str() {
+                "deferred_pre" | "shadow" => drawables,
+                // no transparent geometry in the scene
+                "forward" => 0,
+                // fullscreen triangle or sky cube
+                _ => 1,
+            };
+            assert_eq!(draws, expected, "draw calls of pass {pass}");
+        }
+        assert_eq!(renderer.backend().draw_count(), 18);
+    }
+
+    #[test]
+    fn moved_node_has_motion_for_one_frame() {
+        let mut renderer = reference_renderer(golden_settings());
+        // draws of the G-buffer pass, which writes the motion vectors
+        let moving = |commands: &[RecordedCommand]| {
+            let mut in_pre_pass = false;
+            let mut count = 0;
+            for cmd in commands {
+                match cmd {
+                    RecordedCommand::BeginRenderPass { label, .. } => {
+                        in_pre_pass = label == "deferred_pre";
+                    }
+                    RecordedCommand::SetModelMatrix { model, prev_model } => {
+                        count += (in_pre_pass && model != prev_model) as usize;
+                    }
+                    _ => (),
+                }
+            }
+            count
+        };
+        assert_eq!(moving(&render_frame(&mut renderer)), 0);
+
+        renderer.set_node_transform("cube", glm::translation(&glm::vec3(1.0, 0.5, 0.0)));
+        assert!(moving(&render_frame(&mut renderer)) > 0);
+        assert_eq!(moving(&render_frame(&mut renderer)), 0);
+    }
+
+    #[test]
+    fn screenshot_swizzles_bgra_rows_to_rgba() {
+        // odd width, so a padded row pitch would shift every row after the first
+        let (width, height) = (5, 3);
+        let mut renderer =
+            Renderer::create(HeadlessBackend::new(width, height), golden_settings()).unwrap();
+        renderer.init_draw_programs().unwrap();
+        let backbuffer = renderer.backend().backbuffer();
+        let readback = renderer
+            .backend_mut()
+            .read_render_target(&backbuffer)
+            .unwrap();
+        assert_eq!(readback.format, TextureFormat::Bgra8UnormSrgb);
+        assert_eq!(readback.data.len(), (width * height * 4) as usize);
+
+        let dir = std::env::temp_dir().join("sparkle_screenshot_test");
+        let written = renderer.take_screenshot(&dir).unwrap();
+        let png = image::open(&written[0]).unwrap().into_rgba8();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(png.dimensions(), (width, height));
+        for (x, y, pixel) in png.enumerate_pixels() {
+            let offset = ((y * width + x) * 4) as usize;
+            let bgra = &readback.data[offset..offset + 4];
+            assert_eq!(
+                pixel.0,
+                [bgra[2], bgra[1], bgra[0], bgra[3]],
+                "pixel {x}, {y}"
+            );
+        }
+    }
+
+    #[test]
+    fn compute_dispatches_are_checked_against_their_pipeline() {
+        let mut backend = HeadlessBackend::new(4, 4);
+        let shader = HeadlessBackend::shader("cull");
+        let storage = |binding| ComputeBindingDesc {
+            binding,
+            ty: ComputeBindingType::StorageBuffer,
+        };
+        let pipeline = backend
+            .create_compute_pipeline(&ComputePipelineDesc {
+                label: "cull",
+                shader_source: &shader,
+                world_dimension: None,
+                bindings: &[storage(0), storage(1)],
+                push_constant_size: 8,
+            })
+            .unwrap();
+        let buffer = |label: &str| {
+            let desc = BufferDesc {
+                label: label.into(),
+                usage: BufferUsage::Storage,
+                size: 64,
+            };
+            backend.create_buffer(&desc, None).unwrap()
+        };
+        let (input, args) = (buffer("input"), buffer("args"));
+        let resources = [
+            (0, ComputeResource::StorageBuffer(&input)),
+            (1, ComputeResource::StorageBuffer(&args)),
+        ];
+        let dispatch = ComputeDispatch {
+            pipeline: &pipeline,
+            resources: &resources,
+            push_constants: &[0; 8],
+        };
+
+        backend
+            .dispatch_compute_one_shot(&dispatch, (4, 1, 1))
+            .unwrap();
+        backend.begin_frame().unwrap();
+        backend.cmd_dispatch(&dispatch, (2, 2, 1));
+        backend.cmd_compute_barrier(ComputeBarrier::ComputeToCompute);
+        backend.cmd_dispatch_indirect(&dispatch, &args, 16);
+        backend.cmd_compute_barrier(ComputeBarrier::ComputeToGraphics);
+        backend.end_frame().unwrap();
+        let commands = backend.take_commands();
+        assert_eq!(commands.len(), 7);
+        assert!(matches!(
+            commands[0],
+            RecordedCommand::ExecuteCompute { pipeline: id, work_groups: (4, 1, 1) }
+                if id == pipeline.id
+        ));
+        assert!(matches!(
+            commands[2],
+            RecordedCommand::Dispatch { pipeline: id, work_groups: (2, 2, 1) }
+                if id == pipeline.id
+        ));
+        assert!(matches!(
+            commands[3],
+            RecordedCommand::ComputeBarrier(ComputeBarrier::ComputeToCompute)
+        ));
+        assert!(matches!(
+            commands[4],
+            RecordedCommand::DispatchIndirect { pipeline: id, buffer, offset: 16 }
+                if id == pipeline.id && buffer == args.id
+        ));
+
+        // invalid dispatches fail and record nothing
+        let too_many_push_constants = ComputeDispatch {
+            push_constants: &[0; 12],
+            ..dispatch
+        };
+        let missing_binding = ComputeDispatch {
+            resources: &resources[..1],
+            ..dispatch
+        };
+        let texture_binding = [(0, ComputeResource::Texture(&backend.backbuffer()))];
+        let wrong_type = ComputeDispatch {
+            resources: &texture_binding,
+            ..dispatch
+        };
+        let extra = [
+            (0, ComputeResource::StorageBuffer(&input)),
+            (1, ComputeResource::StorageBuffer(&args)),
+            (2, ComputeResource::StorageBuffer(&args)),
+        ];
+        let unknown_binding = ComputeDispatch {
+            resources: &extra,
+            ..dispatch
+        };
+        for invalid in [
+            too_many_push_constants,
+            missing_binding,
+            wrong_type,
+            unknown_binding,
+        ] {
+            assert!(
+                backend
+                    .dispatch_compute_one_shot(&invalid, (1, 1, 1))
+                    .is_err()
+            );
+            backend.begin_frame().unwrap();
+            backend.cmd_dispatch(&invalid, (1, 1, 1));
+            backend.end_frame().unwrap();
+        }
+        assert!(
+            !backend
+                .take_commands()
+                .iter()
+                .any(|c| matches!(c, RecordedCommand::Dispatch { .. }))
+        );
+    }
+
+    #[test]
+    fn textures_of_every_dimension_check_their_data_length() {
+        let backend = HeadlessBackend::new(4, 4);
+        // 8x4 RGBA8 slices: 128 bytes, then 32 and 8 for the next mips
+        let cases = [
+            (TextureDimension::D2Array, 3, 1, 3 * 128),
+            (TextureDimension::Cube, 6, 1, 6 * 128),
+            (TextureDimension::CubeArray, 12, 1, 12 * 128),
+            (TextureDimension::D3, 4, 1, 4 * 128),
+            // array layers keep their count per mip, 3D depth halves
+            (TextureDimension::D2Array, 3, 3, 3 * (128 + 32 + 8)),
+            (TextureDimension::D3, 4, 3, 4 * 128 + 2 * 32 + 8),
+        ];
+        for (dimension, depth_or_layers, mip_levels, size) in cases {
+            let desc = TextureDesc {
+                width: 8,
+                height: 4,
+                depth_or_layers,
+                dimension,
+                format: TextureFormat::Rgba8Unorm,
+                sampler: SamplerDesc::default(),
+                generate_mipmaps: false,
+                mip_levels,
+            };
+            assert_eq!(desc.data_size(), size, "{dimension:?}");
+            let texture = backend.create_texture(&desc, &vec![0; size]).unwrap();
+            assert_eq!(texture.dimension, dimension);
+            assert_eq!(texture.depth_or_layers, depth_or_layers);
+
+            for wrong_size in [size - 1, size + 1] {
+                let error = backend
+                    .create_texture(&desc, &vec![0; wrong_size])
+                    .unwrap_err();
+                assert_eq!(error.kind, GpuErrorKind::ResourceCreation, "{dimension:?}");
+            }
+        }
+
+        // cubes need six faces per cube
+        let desc = TextureDesc {
+            width: 8,
+            height: 4,
+            depth_or_layers: 8,
+            dimension: TextureDimension::CubeArray,
+            format: TextureFormat::Rgba8Unorm,
+            sampler: SamplerDesc::default(),
+            generate_mipmaps: false,
+            mip_levels: 1,
+        };
+        assert!(backend.create_texture(&desc, &vec![0; 8 * 128]).is_err());
+    }
+}
@@ -2,11 +2,19 @@ pub mod backend;
 pub mod compute_push;
 pub mod draw_programs;
 pub mod geometry;
+pub mod gpu_layout;
+#[cfg(test)]
+mod golden;
+pub mod headless_backend;
 pub mod procedural;
+pub mod render_graph;
 pub mod renderer;
 pub mod scene_data;
 pub mod scene_info;
 pub mod scenegraph;
+pub mod screenshot;
+pub mod shader_reload;
 pub mod settings;
 pub mod skybox;
+pub mod software_backend;
 pub mod vulkan_backend;
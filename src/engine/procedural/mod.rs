@@ -5,10 +5,12 @@ use rand::{SeedableRng, RngExt, rngs::SmallRng};
 use crate::{
     engine::{
         backend::{
-            AddressMode, BufferDesc, BufferUsage, ComputePipelineDesc, Drawable, FilterMode,
+            AddressMode, BufferDesc, BufferUsage, ComputeBindingDesc, ComputeBindingType,
+            ComputeDispatch, ComputePipelineDesc, ComputeResource, Drawable, FilterMode,
             GpuBackend, GpuError, GpuErrorKind, IndirectDrawable, ObjType, SamplerDesc,
-            TextureDesc, TextureFormat, as_bytes,
+            TextureDesc, TextureDimension, TextureFormat, as_bytes,
         },
+        compute_push::ComputePushConstants,
         geometry::Vertex,
         scenegraph::{
             Scenegraph,
@@ -45,12 +47,6 @@ pub struct ProceduralConfig {
     pub texture_tile_factor: f32,
 }
 
-struct TerrainTextures<B: GpuBackend> {
-    albedo: B::Texture,
-    normal: B::Texture,
-    roughness: B::Texture,
-}
-
 struct TerrainMesh {
     vertices: Vec<Vertex>,
     indices: Vec<u32>,
@@ -65,83 +61,75 @@ struct DrawIndirectCommand {
     first_instance: u32,
 }
 
+/// Set 1 of the scatter shader: instance transforms, indirect command, heightmap.
+pub const SCATTER_BINDINGS: [ComputeBindingDesc; 3] = [
+    ComputeBindingDesc {
+        binding: 0,
+        ty: ComputeBindingType::StorageBuffer,
+    },
+    ComputeBindingDesc {
+        binding: 1,
+        ty: ComputeBindingType::StorageBuffer,
+    },
+    ComputeBindingDesc {
+        binding: 2,
+        ty: ComputeBindingType::Texture,
+    },
+];
+
 pub fn create_pipeline<B: GpuBackend>(
     backend: &B,
+    shader_source: &B::ShaderSource,
     world_dimension: f32,
 ) -> Result<B::Pipeline, GpuError> {
-    let shaders = backend.load_proc_gen_shaders();
     backend.create_compute_pipeline(&ComputePipelineDesc {
         label: "Scatter Procedural Assets",
-        shader_source: &shaders.scattering,
+        shader_source,
         world_dimension: Some(world_dimension),
+        bindings: &SCATTER_BINDINGS,
+        push_constant_size: std::mem::size_of::<ComputePushConstants>() as u32,
     })
 }
 
-fn load_terrain_textures<B: GpuBackend>(
+/// Load the ground material as one 2D array with the albedo, metallic-roughness
+/// and normal layers, in the order of texture slots 0 to 2.
+///
+/// The layers share a linear format, so the albedo keeps its sRGB encoding and
+/// is decoded by the deferred pre-pass.
+fn load_terrain_layers<B: GpuBackend>(
     backend: &B,
     terrain_dir: &str,
-) -> Result<TerrainTextures<B>, GpuError> {
-    // Albedo
-    let albedo_img = image::open(format!(
-        "{terrain_dir}/textures/forest_ground_04_diff_4k.jpg"
-    ))
-    .map_err(|e| GpuError::new(e.to_string(), GpuErrorKind::Other))?
-    .to_rgba8();
-    let albedo = backend.create_texture(
-        &TextureDesc {
-            format: TextureFormat::Rgba8UnormSrgb,
-            width: albedo_img.width(),
-            height: albedo_img.height(),
-            sampler: SamplerDesc {
-                address_u: AddressMode::Repeat,
-                address_v: AddressMode::Repeat,
-                filter: FilterMode::Anisotropic,
-                compare: None,
-            },
-            generate_mipmaps: false,
-        },
-        albedo_img.as_raw(),
-    )?;
-
-    // Normal
-    let normal_img = image::open(format!(
-        "{terrain_dir}/textures/forest_ground_04_nor_gl_4k.jpg"
-    ))
-    .map_err(|e| GpuError::new(e.to_string(), GpuErrorKind::Other))?
-    .to_rgba8();
-    let normal = backend.create_texture(
-        &TextureDesc {
-            format: TextureFormat::Rgba8Unorm,
-            width: normal_img.width(),
-            height: normal_img.height(),
-            sampler: SamplerDesc {
-                address_u: AddressMode::Repeat,
-                address_v: AddressMode::Repeat,
-                filter: FilterMode::Anisotropic,
-                compare: None,
-            },
-            generate_mipmaps: false,
-        },
-        normal_img.as_raw(),
-    )?;
-
+) -> Result<B::Texture, GpuError> {
+    let load = |name: &str| {
+        image::open(format!(
+            "{terrain_dir}/textures/forest_ground_04_{name}_4k.jpg"
+        ))
+        .map(|img| img.to_rgba8())
+        .map_err(|e| GpuError::new(e.to_string(), GpuErrorKind::Other))
+    };
+    let albedo = load("diff")?;
+    let normal = load("nor_gl")?;
     // Metallic-roughness: remap roughness to G channel, metallic=0
-    let rough_src = image::open(format!(
-        "{terrain_dir}/textures/forest_ground_04_rough_4k.jpg"
-    ))
-    .map_err(|e| GpuError::new(e.to_string(), GpuErrorKind::Other))?
-    .to_rgba8();
-    let mr_pixels: Vec<u8> = rough_src
-        .pixels()
-        .flat_map(|p| {
-            [255u8, p[1], 0u8, 255u8] // R=1, G=roughness, B=metallic=0, A=1
-        })
-        .collect();
-    let mr = backend.create_texture(
+    let mut mr = load("rough")?;
+    for p in mr.pixels_mut() {
+        p.0 = [255u8, p[1], 0u8, 255u8]; // R=1, G=roughness, B=metallic=0, A=1
+    }
+
+    let (width, height) = albedo.dimensions();
+    if normal.dimensions() != (width, height) || mr.dimensions() != (width, height) {
+        return Err(GpuError::new(
+            format!("Terrain textures in {terrain_dir} differ in size"),
+            GpuErrorKind::ResourceCreation,
+        ));
+    }
+    let data = [albedo.into_raw(), mr.into_raw(), normal.into_raw()].concat();
+    backend.create_texture(
         &TextureDesc {
             format: TextureFormat::Rgba8Unorm,
-            width: rough_src.width(),
-            height: rough_src.height(),
+            width,
+            height,
+            depth_or_layers: 3,
+            dimension: TextureDimension::D2Array,
             sampler: SamplerDesc {
                 address_u: AddressMode::Repeat,
                 address_v: AddressMode::Repeat,
@@ -149,15 +137,10 @@ fn load_terrain_textures<B: GpuBackend>(
                 compare: None,
             },
             generate_mipmaps: false,
+            mip_levels: 1,
         },
-        &mr_pixels,
-    )?;
-
-    Ok(TerrainTextures {
-        albedo,
-        normal,
-        roughness: mr,
-    })
+        &data,
+    )
 }
 
 /// Hash a string seed to u64 via FNV-1a.
@@ -286,6 +269,8 @@ fn build_heightmap_texture<B: GpuBackend>(
             format: TextureFormat::Rgba32Float,
             width: dim,
             height: dim,
+            depth_or_layers: 1,
+            dimension: TextureDimension::D2,
             sampler: SamplerDesc {
                 address_u: AddressMode::Clamp,
                 address_v: AddressMode::Clamp,
@@ -295,6 +280,7 @@ fn build_heightmap_texture<B: GpuBackend>(
                 compare: None,
             },
             generate_mipmaps: false,
+            mip_levels: 1,
         },
         &pixel_data,
     )?;
@@ -392,7 +378,7 @@ pub fn load_procedural_world<B: GpuBackend>(
     let heightmap_tex = build_heightmap_texture(backend, &heightmap, config.terrain_segments)?;
 
     // --- Terrain ---
-    let terrain_textures = load_terrain_textures(backend, &config.terrain_dir)?;
+    let terrain_layers = load_terrain_layers(backend, &config.terrain_dir)?;
     let terrain_mesh = generate_terrain_mesh(
         config.terrain_segments,
         config.world_dimension,
@@ -407,9 +393,8 @@ pub fn load_procedural_world<B: GpuBackend>(
         &terrain_mesh.indices,
         ObjType::Opaque,
     )?;
-    terrain.add_texture(0, Rc::new(terrain_textures.albedo));
-    terrain.add_texture(1, Rc::new(terrain_textures.roughness));
-    terrain.add_texture(2, Rc::new(terrain_textures.normal));
+    terrain.add_texture(0, Rc::new(terrain_layers));
+    terrain.set_layered(true);
 
     // --- Instanced assets ---
     let mut instanced_assets = Vec::new();
@@ -450,22 +435,32 @@ pub fn load_procedural_world<B: GpuBackend>(
 
             // Scatter compute: populate instance transforms and instance count
             let dispatch_x = (asset.max_count + 63) / 64;
-            backend.execute_compute_one_shot(
-                pipeline,
-                &[(10, &instance_buf), (11, &cmd_buf)],
-                &[(0, heightmap_tex.as_ref())],
-                (dispatch_x, 1, 1),
-                asset.max_count,
+            let push = ComputePushConstants {
+                max_instances: asset.max_count,
                 asset_offset,
-                config.max_height,
-                asset.spawn_height_min,
-                asset.spawn_height_max,
-                asset.slope_max,
-                asset.scale_min,
-                asset.scale_max,
-                asset.tilt_factor,
-                config.terrain_segments as f32,
+                max_height: config.max_height,
+                spawn_height_min: asset.spawn_height_min,
+                spawn_height_max: asset.spawn_height_max,
+                slope_max: asset.slope_max,
+                scale_min: asset.scale_min,
+                scale_max: asset.scale_max,
+                tilt_factor: asset.tilt_factor,
+                terrain_segments_f: config.terrain_segments as f32,
+            };
+            backend.dispatch_compute_one_shot(
+                &ComputeDispatch {
+                    pipeline,
+                    resources: &[
+                        (0, ComputeResource::StorageBuffer(&instance_buf)),
+                        (1, ComputeResource::StorageBuffer(&cmd_buf)),
+                        (2, ComputeResource::Texture(heightmap_tex.as_ref())),
+                    ],
+                    push_constants: as_bytes(std::slice::from_ref(&push)),
+                },
+                (dispatch_x, 1, 1),
             )?;
+            // vertex shaders read the instance transforms through binding 10
+            backend.bind_buffer_to_descriptor(10, &instance_buf);
             asset_offset += dispatch_x * 64; // advance past all thread IDs used by this asset
 
             instanced_assets.push(IndirectDrawable::from_drawable(
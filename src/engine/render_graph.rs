@@ -0,0 +1,1310 @@
+//! Declarative render graph driving [`Renderer::render`](super::renderer::Renderer).
+//!
+//! Passes implement [`GraphPass`] and declare in [`GraphPass::setup`] which named
+//! resources they create, sample, render into or depth-test against. From these
+//! declarations the graph:
+//! - derives the execution order (writers of a resource run before its readers),
+//! - allocates the transient render targets and recreates the backbuffer-sized
+//!   ones on resize,
+//! - keeps a second target for resources sampled as history and swaps the two
+//!   at the start of every frame, so a pass can read what it wrote last frame,
+//! - works out how each pass accesses every resource and, before the pass runs,
+//!   records the barriers and layout transitions from the previous access,
+//! - binds sampled inputs and opens the render pass of a pass with the declared
+//!   load ops and resolve targets,
+//! - uploads the lights of the frame into the light storage buffer before the
+//!   first pass, so every pass can shade all of them at once.
+//!
+//! Passes can be added, removed or toggled at runtime; the graph recompiles on
+//! the next frame.
+
+use std::collections::{HashMap, HashSet};
+
+use super::backend::*;
+use super::draw_programs::{GpuLight, MAX_LIGHTS};
+use super::geometry::Light;
+use super::scenegraph::Scenegraph;
+use super::shader_reload::ReloadedShaders;
+use super::skybox::Skybox;
+
+/// Name of a graph resource.
+pub type ResourceName = &'static str;
+
+/// The swapchain image of the current frame (imported).
+pub const BACKBUFFER: ResourceName = "backbuffer";
+/// The backend's main depth buffer (imported).
+pub const MAIN_DEPTH: ResourceName = "main_depth";
+
+/// Size of a transient render target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetSize {
+    /// Matches the backbuffer; recreated on resize.
+    Backbuffer,
+    Fixed(u32, u32),
+}
+
+/// Description of a transient render target owned by the graph.
+///
+/// Targets with [`RenderTargetUsage::Storage`] are created through
+/// `create_rt_output_target`, which picks its own format.
+#[derive(Debug, Clone, Copy)]
+pub struct TargetDesc {
+    pub size: TargetSize,
+    pub format: TextureFormat,
+    pub sampler: SamplerDesc,
+    pub usage: RenderTargetUsage,
+    /// Samples per pixel; multisampled targets are resolved, not sampled.
+    pub sample_count: u32,
+    /// The contents outlive the frame, required to sample the target as history.
+    pub persistent: bool,
+    /// Array layers, six per cube for depth cube map arrays; see
+    /// [`RenderTargetDesc::layers`].
+    pub layers: u32,
+}
+
+impl TargetDesc {
+    /// Backbuffer-sized color target with the default sampler.
+    pub fn color(format: TextureFormat) -> Self {
+        TargetDesc {
+            size: TargetSize::Backbuffer,
+            format,
+            sampler: SamplerDesc::default(),
+            usage: RenderTargetUsage::Color,
+            sample_count: 1,
+            persistent: false,
+            layers: 1,
+        }
+    }
+
+    /// The same target with contents that carry over into the next frame.
+    pub fn persistent(self) -> Self {
+        TargetDesc {
+            persistent: true,
+            ..self
+        }
+    }
+
+    /// The same target with `sample_count` samples per pixel.
+    pub fn multisampled(self, sample_count: u32) -> Self {
+        TargetDesc {
+            sample_count,
+            ..self
+        }
+    }
+}
+
+/// How a pass treats the previous contents of a resource it writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadPolicy {
+    /// Clear every time the pass runs.
+    Clear,
+    /// Keep the previous contents.
+    Load,
+}
+
+/// egui output to composite over the frame, handed to the renderer before rendering.
+pub struct EguiOverlay {
+    pub textures_delta: egui::TexturesDelta,
+    pub primitives: Vec<egui::ClippedPrimitive>,
+    pub pixels_per_point: f32,
+}
+
+/// Ray tracing scene data, present when RT shadows can run this frame.
+pub struct RtScene<'a, B: GpuBackend> {
+    pub tlas: &'a B::AccelerationStructure,
+    pub material_buffer: &'a B::Buffer,
+}
+
+/// Per-frame data shared by all passes.
+pub struct FrameInputs<'a, B: GpuBackend> {
+    pub scene: &'a Scenegraph<B>,
+    pub screen_quad: &'a ScreenQuad<B>,
+    pub skybox: Option<&'a Skybox<B>>,
+    /// Lights with their shadow projections and atlas tiles already assigned.
+    pub lights: &'a [Light],
+    /// Light storage buffer (binding 17), filled with the first [`MAX_LIGHTS`]
+    /// lights before the first pass.
+    pub light_buffer: &'a B::Buffer,
+    /// Light-space UBO (binding 3), updated by the shadow pass for each cascade.
+    pub light_space_buffer: &'a B::Buffer,
+    pub rt: Option<RtScene<'a, B>>,
+    pub overlay: Option<&'a EguiOverlay>,
+}
+
+/// What a pass sees while executing.
+pub struct PassContext<'a, B: GpuBackend> {
+    pub backend: &'a mut B,
+    pub frame: &'a FrameInputs<'a, B>,
+    resources: &'a Resources<'a, B>,
+}
+
+impl<B: GpuBackend> PassContext<'_, B> {
+    /// Look up a graph resource by name.
+    pub fn target(&self, name: ResourceName) -> Option<&B::RenderTarget> {
+        self.resources.get(name)
+    }
+}
+
+/// A node of the render graph.
+pub trait GraphPass<B: GpuBackend> {
+    /// Unique pass name, also used as render pass label and debug event.
+    fn name(&self) -> &'static str;
+
+    /// Declare the resources this pass uses.
+    fn setup(&self, builder: &mut PassBuilder);
+
+    /// Whether the pass runs this frame. Skipped passes do not begin a render pass.
+    fn should_run(&self, _frame: &FrameInputs<'_, B>) -> bool {
+        true
+    }
+
+    /// Record the pass. For raster passes the graph has already begun the render
+    /// pass and set a viewport covering the attachments.
+    fn execute(&mut self, ctx: &mut PassContext<'_, B>) -> Result<(), GpuError>;
+
+    /// Rebuild the pipelines whose shaders were hot reloaded. Called between
+    /// frames; on error the pass must keep its previous pipelines.
+    fn reload_shaders(
+        &mut self,
+        _backend: &B,
+        _shaders: &ReloadedShaders<B>,
+    ) -> Result<(), GpuError> {
+        Ok(())
+    }
+}
+
+struct ColorWrite {
+    name: ResourceName,
+    load: LoadPolicy,
+    clear_color: [f32; 4],
+    resolve: Option<ResourceName>,
+}
+
+struct DepthUse {
+    name: ResourceName,
+    load: LoadPolicy,
+    clear_depth: f32,
+    write_enabled: bool,
+    /// `None` loads or clears the stencil together with the depth.
+    clear_stencil: Option<u8>,
+    resolve: Option<ResourceName>,
+}
+
+/// Collects the declarations of one pass during [`GraphPass::setup`].
+pub struct PassBuilder<'a> {
+    enabled_passes: &'a [&'static str],
+    creates: Vec<(ResourceName, TargetDesc)>,
+    samples: Vec<(ResourceName, u32)>,
+    history: Vec<(ResourceName, u32)>,
+    colors: Vec<ColorWrite>,
+    depth: Option<DepthUse>,
+    writes: Vec<(ResourceName, LoadPolicy)>,
+    custom: bool,
+    /// First invalid declaration, reported by [`RenderGraph::compile`].
+    error: Option<String>,
+}
+
+impl<'a> PassBuilder<'a> {
+    fn new(enabled_passes: &'a [&'static str]) -> Self {
+        PassBuilder {
+            enabled_passes,
+            creates: Vec::new(),
+            samples: Vec::new(),
+            history: Vec::new(),
+            colors: Vec::new(),
+            depth: None,
+            writes: Vec::new(),
+            custom: false,
+            error: None,
+        }
+    }
+
+    /// Whether a pass with this name is part of the graph and enabled.
+    pub fn is_enabled(&self, pass: &str) -> bool {
+        self.enabled_passes.contains(&pass)
+    }
+
+    /// Declare a transient target owned by the graph.
+    pub fn create(&mut self, name: ResourceName, desc: TargetDesc) -> &mut Self {
+        self.creates.push((name, desc));
+        self
+    }
+
+    /// Sample a resource in texture slot `slot`.
+    pub fn sample(&mut self, name: ResourceName, slot: u32) -> &mut Self {
+        self.samples.push((name, slot));
+        self
+    }
+
+    /// Sample the contents a persistent resource had at the end of the previous
+    /// frame in texture slot `slot`. Orders nothing: the history is a target of
+    /// its own. Undefined in the first frame and after a resize.
+    pub fn sample_history(&mut self, name: ResourceName, slot: u32) -> &mut Self {
+        self.history.push((name, slot));
+        self
+    }
+
+    /// Render into a color attachment. Attachments keep declaration order.
+    pub fn write_color(
+        &mut self,
+        name: ResourceName,
+        load: LoadPolicy,
+        clear_color: [f32; 4],
+    ) -> &mut Self {
+        self.colors.push(ColorWrite {
+            name,
+            load,
+            clear_color,
+            resolve: None,
+        });
+        self
+    }
+
+    /// Render into a depth attachment with depth writes enabled.
+    pub fn write_depth(
+        &mut self,
+        name: ResourceName,
+        load: LoadPolicy,
+        clear_depth: f32,
+    ) -> &mut Self {
+        self.depth = Some(DepthUse {
+            name,
+            load,
+            clear_depth,
+            write_enabled: true,
+            clear_stencil: None,
+            resolve: None,
+        });
+        self
+    }
+
+    /// Depth-test against a depth attachment without writing it.
+    pub fn test_depth(&mut self, name: ResourceName) -> &mut Self {
+        self.depth = Some(DepthUse {
+            name,
+            load: LoadPolicy::Load,
+            clear_depth: 1.0,
+            write_enabled: false,
+            clear_stencil: None,
+            resolve: None,
+        });
+        self
+    }
+
+    /// Clear the stencil aspect of the depth attachment, declared before, to
+    /// `value` at the start of the pass, whatever happens to its depth.
+    /// Without a depth attachment the graph fails to compile.
+    pub fn clear_stencil(&mut self, value: u8) -> &mut Self {
+        match self.depth.as_mut() {
+            Some(depth) => depth.clear_stencil = Some(value),
+            None => self.fail("clears the stencil but has no depth attachment".to_string()),
+        }
+        self
+    }
+
+    /// Resolve the multisampled attachment `name`, declared before, into the
+    /// single-sampled `into` at the end of the pass. Overwrites all of `into`.
+    /// If `name` is no attachment of the pass the graph fails to compile.
+    pub fn resolve(&mut self, name: ResourceName, into: ResourceName) -> &mut Self {
+        let color = self.colors.iter_mut().find(|c| c.name == name);
+        match (color, self.depth.as_mut()) {
+            (Some(color), _) => color.resolve = Some(into),
+            (None, Some(depth)) if depth.name == name => depth.resolve = Some(into),
+            _ => self.fail(format!(
+                "resolves '{name}', which is not an attachment of the pass"
+            )),
+        }
+        self
+    }
+
+    /// Write a resource outside of a graph-managed render pass (custom passes).
+    pub fn write(&mut self, name: ResourceName, load: LoadPolicy) -> &mut Self {
+        self.writes.push((name, load));
+        self
+    }
+
+    /// The pass records its own commands; the graph does not open a render pass.
+    pub fn custom(&mut self) -> &mut Self {
+        self.custom = true;
+        self
+    }
+
+    fn fail(&mut self, message: String) {
+        self.error.get_or_insert(message);
+    }
+
+    fn finish(self) -> PassDecl {
+        PassDecl {
+            creates: self.creates,
+            samples: self.samples,
+            history: self.history,
+            colors: self.colors,
+            depth: self.depth,
+            writes: self.writes,
+            custom: self.custom,
+        }
+    }
+}
+
+struct PassDecl {
+    creates: Vec<(ResourceName, TargetDesc)>,
+    samples: Vec<(ResourceName, u32)>,
+    history: Vec<(ResourceName, u32)>,
+    colors: Vec<ColorWrite>,
+    depth: Option<DepthUse>,
+    writes: Vec<(ResourceName, LoadPolicy)>,
+    custom: bool,
+}
+
+impl PassDecl {
+    /// Every written resource with its load policy. Resolve targets count as
+    /// loading writes so they follow the passes that produce them otherwise.
+    fn written(&self) -> impl Iterator<Item = (ResourceName, LoadPolicy)> + '_ {
+        let colors = self.colors.iter().map(|c| (c.name, c.load));
+        let depth = self
+            .depth
+            .iter()
+            .filter(|d| d.write_enabled)
+            .map(|d| (d.name, d.load));
+        let resolves = self
+            .colors
+            .iter()
+            .filter_map(|c| c.resolve)
+            .chain(self.depth.iter().filter_map(|d| d.resolve))
+            .map(|name| (name, LoadPolicy::Load));
+        colors
+            .chain(depth)
+            .chain(resolves)
+            .chain(self.writes.iter().copied())
+    }
+
+    /// How the pass accesses each resource, `true` marking the history target of
+    /// a resource. A write wins over a read of the same resource.
+    fn accesses(&self) -> Vec<((ResourceName, bool), TargetAccess)> {
+        let depth_access = |d: &DepthUse| {
+            if d.write_enabled {
+                TargetAccess::DepthAttachment
+            } else {
+                TargetAccess::DepthTest
+            }
+        };
+        let all = self
+            .colors
+            .iter()
+            .flat_map(|c| std::iter::once(c.name).chain(c.resolve))
+            .map(|name| ((name, false), TargetAccess::ColorAttachment))
+            .chain(self.depth.iter().flat_map(|d| {
+                std::iter::once(((d.name, false), depth_access(d))).chain(
+                    d.resolve
+                        .map(|r| ((r, false), TargetAccess::DepthAttachment)),
+                )
+            }))
+            .chain(
+                self.writes
+                    .iter()
+                    .map(|&(name, _)| ((name, false), TargetAccess::Custom)),
+            )
+            .chain(
+                self.samples
+                    .iter()
+                    .map(|&(name, _)| ((name, false), TargetAccess::ShaderRead)),
+            )
+            .chain(
+                self.history
+                    .iter()
+                    .map(|&(name, _)| ((name, true), TargetAccess::ShaderRead)),
+            );
+        let mut accesses: Vec<((ResourceName, bool), TargetAccess)> = Vec::new();
+        for (key, access) in all {
+            if !accesses.iter().any(|(k, _)| *k == key) {
+                accesses.push((key, access));
+            }
+        }
+        accesses
+    }
+
+    /// Every read resource.
+    fn read(&self) -> impl Iterator<Item = ResourceName> + '_ {
+        let depth = self
+            .depth
+            .iter()
+            .filter(|d| !d.write_enabled)
+            .map(|d| d.name);
+        self.samples.iter().map(|s| s.0).chain(depth)
+    }
+}
+
+/// A resource changing access before a pass, worked out by [`RenderGraph::compile`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Transition {
+    resource: ResourceName,
+    /// The transition applies to the history target of the resource.
+    history: bool,
+    before: TargetAccess,
+    after: TargetAccess,
+}
+
+struct Node<B: GpuBackend> {
+    pass: Box<dyn GraphPass<B>>,
+    enabled: bool,
+    decl: Option<PassDecl>,
+}
+
+struct Resources<'a, B: GpuBackend> {
+    backbuffer: B::RenderTarget,
+    main_depth: B::RenderTarget,
+    transients: &'a HashMap<ResourceName, (TargetDesc, B::RenderTarget)>,
+    history: &'a HashMap<ResourceName, B::RenderTarget>,
+}
+
+impl<B: GpuBackend> Resources<'_, B> {
+    fn get(&self, name: ResourceName) -> Option<&B::RenderTarget> {
+        match name {
+            BACKBUFFER => Some(&self.backbuffer),
+            MAIN_DEPTH => Some(&self.main_depth),
+            _ => self.transients.get(name).map(|(_, target)| target),
+        }
+    }
+
+    fn expect(&self, name: ResourceName) -> Result<&B::RenderTarget, GpuError> {
+        self.get(name).ok_or_else(|| {
+            GpuError::new(
+                format!("Render graph resource '{name}' is not allocated"),
+                GpuErrorKind::RenderPass,
+            )
+        })
+    }
+
+    fn expect_history(&self, name: ResourceName) -> Result<&B::RenderTarget, GpuError> {
+        self.history.get(name).ok_or_else(|| {
+            GpuError::new(
+                format!("Render graph resource '{name}' has no history"),
+                GpuErrorKind::RenderPass,
+            )
+        })
+    }
+}
+
+/// The render graph: an ordered set of passes plus the transient targets they share.
+pub struct RenderGraph<B: GpuBackend> {
+    nodes: Vec<Node<B>>,
+    /// Indices of the enabled nodes in execution order.
+    schedule: Vec<usize>,
+    /// Transitions to record before each pass of the schedule.
+    transitions: Vec<Vec<Transition>>,
+    transients: HashMap<ResourceName, (TargetDesc, B::RenderTarget)>,
+    /// Previous frame's targets of the resources sampled as history.
+    history: HashMap<ResourceName, B::RenderTarget>,
+    dirty: bool,
+}
+
+impl<B: GpuBackend> Default for RenderGraph<B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<B: GpuBackend> RenderGraph<B> {
+    pub fn new() -> Self {
+        RenderGraph {
+            nodes: Vec::new(),
+            schedule: Vec::new(),
+            transitions: Vec::new(),
+            transients: HashMap::new(),
+            history: HashMap::new(),
+            dirty: true,
+        }
+    }
+
+    /// Whether the graph has no passes at all.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Append a pass, replacing any pass with the same name. Insertion order
+    /// breaks ties between passes the declarations leave unordered.
+    pub fn add_pass(&mut self, pass: Box<dyn GraphPass<B>>) {
+        let node = Node {
+            pass,
+            enabled: true,
+            decl: None,
+        };
+        match self.position(node.pass.name()) {
+            Some(i) => self.nodes[i] = node,
+            None => self.nodes.push(node),
+        }
+        self.dirty = true;
+    }
+
+    /// Remove a pass by name. Returns it if it was part of the graph.
+    pub fn remove_pass(&mut self, name: &str) -> Option<Box<dyn GraphPass<B>>> {
+        let i = self.position(name)?;
+        self.dirty = true;
+        Some(self.nodes.remove(i).pass)
+    }
+
+    pub fn has_pass(&self, name: &str) -> bool {
+        self.position(name).is_some()
+    }
+
+    /// Enable or disable a pass without removing it.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) {
+        if let Some(i) = self.position(name)
+            && self.nodes[i].enabled != enabled
+        {
+            self.nodes[i].enabled = enabled;
+            self.dirty = true;
+        }
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.position(name).is_some_and(|i| self.nodes[i].enabled)
+    }
+
+    /// Names of the enabled passes in execution order (after the last compile).
+    pub fn execution_order(&self) -> Vec<&'static str> {
+        self.schedule
+            .iter()
+            .map(|&i| self.nodes[i].pass.name())
+            .collect()
+    }
+
+    /// A transient target by name, if allocated.
+    pub fn target(&self, name: ResourceName) -> Option<&B::RenderTarget> {
+        self.transients.get(name).map(|(_, target)| target)
+    }
+
+    /// Recreate the backbuffer-sized transient targets, and their history, at
+    /// the backend's resolution.
+    pub fn resize(&mut self, backend: &B) -> Result<(), GpuError> {
+        for (name, (desc, target)) in &mut self.transients {
+            if desc.size == TargetSize::Backbuffer {
+                *target = create_target(backend, desc)?;
+                if let Some(history) = self.history.get_mut(name) {
+                    *history = create_target(backend, desc)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Hand hot reloaded shaders to all passes. A pass that fails to rebuild
+    /// is logged and keeps running with its previous pipelines; the first
+    /// failure is returned once every pass had its turn.
+    pub fn reload_shaders(
+        &mut self,
+        backend: &B,
+        shaders: &ReloadedShaders<B>,
+    ) -> Result<(), GpuError> {
+        let mut result = Ok(());
+        for node in &mut self.nodes {
+            if let Err(e) = node.pass.reload_shaders(backend, shaders) {
+                eprintln!(
+                    "Shader reload: keeping old pipelines of pass '{}': {e}",
+                    node.pass.name()
+                );
+                result = result.and(Err(e));
+            }
+        }
+        result
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.nodes.iter().position(|n| n.pass.name() == name)
+    }
+
+    /// Re-run the pass setups, order the passes and allocate missing targets.
+    pub fn compile(&mut self, backend: &B) -> Result<(), GpuError> {
+        let enabled: Vec<&'static str> = self
+            .nodes
+            .iter()
+            .filter(|n| n.enabled)
+            .map(|n| n.pass.name())
+            .collect();
+        for node in &mut self.nodes {
+            node.decl = None;
+            if !node.enabled {
+                continue;
+            }
+            let mut builder = PassBuilder::new(&enabled);
+            node.pass.setup(&mut builder);
+            if let Some(error) = builder.error.take() {
+                return Err(graph_error(format!("Pass '{}' {error}", node.pass.name())));
+            }
+            node.decl = Some(builder.finish());
+        }
+
+        let active: Vec<usize> = (0..self.nodes.len())
+            .filter(|&i| self.nodes[i].decl.is_some())
+            .collect();
+        let decl = |i: usize| self.nodes[i].decl.as_ref().unwrap();
+
+        // Resource table: first declaration wins
+        let mut descs: HashMap<ResourceName, TargetDesc> = HashMap::new();
+        for &i in &active {
+            for &(name, desc) in &decl(i).creates {
+                descs.entry(name).or_insert(desc);
+            }
+        }
+        let known = |name: ResourceName| {
+            name == BACKBUFFER || name == MAIN_DEPTH || descs.contains_key(name)
+        };
+
+        let mut history: HashSet<ResourceName> = HashSet::new();
+        for &i in &active {
+            for &(name, _) in &decl(i).history {
+                if !descs.get(name).is_some_and(|d| d.persistent) {
+                    let pass = self.nodes[i].pass.name();
+                    return Err(graph_error(format!(
+                        "Pass '{pass}' samples the history of '{name}', which is no persistent target"
+                    )));
+                }
+                history.insert(name);
+            }
+        }
+
+        // Producers (clearing writes) run before modifiers (loading writes),
+        // both before readers. Passes of the same role keep insertion order.
+        let mut producers: HashMap<ResourceName, Vec<usize>> = HashMap::new();
+        let mut modifiers: HashMap<ResourceName, Vec<usize>> = HashMap::new();
+        let mut readers: HashMap<ResourceName, Vec<usize>> = HashMap::new();
+        for &i in &active {
+            let pass = self.nodes[i].pass.name();
+            for (name, load) in decl(i).written() {
+                if !known(name) {
+                    return Err(graph_error(format!(
+                        "Pass '{pass}' writes '{name}', which no pass creates"
+                    )));
+                }
+                let role = if load == LoadPolicy::Load {
+                    &mut modifiers
+                } else {
+                    &mut producers
+                };
+                role.entry(name).or_default().push(i);
+            }
+            for name in decl(i).read() {
+                readers.entry(name).or_default().push(i);
+            }
+        }
+
+        let mut edges: HashSet<(usize, usize)> = HashSet::new();
+        for (&name, reading) in &readers {
+            let written: Vec<usize> = producers
+                .get(name)
+                .into_iter()
+                .chain(modifiers.get(name))
+                .flatten()
+                .copied()
+                .collect();
+            if written.is_empty() && name != BACKBUFFER && name != MAIN_DEPTH {
+                let pass = self.nodes[reading[0]].pass.name();
+                return Err(graph_error(format!(
+                    "Pass '{pass}' reads '{name}', which no enabled pass writes"
+                )));
+            }
+            for &w in &written {
+                for &r in reading {
+                    edges.insert((w, r));
+                }
+            }
+        }
+        for (name, writing) in &producers {
+            for pair in writing.windows(2) {
+                edges.insert((pair[0], pair[1]));
+            }
+            for &m in modifiers.get(name).into_iter().flatten() {
+                for &p in writing {
+                    edges.insert((p, m));
+                }
+            }
+        }
+        for writing in modifiers.values() {
+            for pair in writing.windows(2) {
+                edges.insert((pair[0], pair[1]));
+            }
+        }
+        edges.retain(|(a, b)| a != b);
+
+        // Kahn's algorithm, always picking the earliest inserted ready pass
+        let mut indegree: HashMap<usize, usize> = active.iter().map(|&i| (i, 0)).collect();
+        for &(_, b) in &edges {
+            *indegree.get_mut(&b).unwrap() += 1;
+        }
+        let mut order = Vec::with_capacity(active.len());
+        while let Some(&next) = active
+            .iter()
+            .find(|i| indegree.get(i) == Some(&0) && !order.contains(*i))
+        {
+            order.push(next);
+            for &(a, b) in &edges {
+                if a == next {
+                    *indegree.get_mut(&b).unwrap() -= 1;
+                }
+            }
+        }
+        if order.len() != active.len() {
+            let stuck: Vec<&str> = active
+                .iter()
+                .filter(|i| !order.contains(i))
+                .map(|&i| self.nodes[i].pass.name())
+                .collect();
+            return Err(graph_error(format!(
+                "Render graph has a dependency cycle between passes {stuck:?}"
+            )));
+        }
+
+        // Transitions between the accesses of consecutive passes. Frames repeat, so a
+        // resource starts in the access it ended the previous frame with; a history
+        // target starts in the one its resource ended with, as the two swap.
+        let accesses: Vec<_> = order.iter().map(|&i| decl(i).accesses()).collect();
+        let mut last: HashMap<(ResourceName, bool), TargetAccess> = HashMap::new();
+        for &(key, access) in accesses.iter().flatten() {
+            last.insert(key, access);
+        }
+        let start = |(name, is_history): (ResourceName, bool)| {
+            // a new swapchain image every frame
+            if name == BACKBUFFER {
+                return TargetAccess::Undefined;
+            }
+            let end = (name, is_history != history.contains(name));
+            last.get(&end).copied().unwrap_or(TargetAccess::Undefined)
+        };
+        let mut state: HashMap<(ResourceName, bool), TargetAccess> = HashMap::new();
+        let transitions = accesses
+            .iter()
+            .map(|pass| {
+                pass.iter()
+                    .filter_map(|&(key, after)| {
+                        let before = state.insert(key, after).unwrap_or_else(|| start(key));
+                        (before != after || after.is_write()).then_some(Transition {
+                            resource: key.0,
+                            history: key.1,
+                            before,
+                            after,
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+
+        // Drop targets nobody declares anymore, allocate new ones
+        self.transients.retain(|name, _| descs.contains_key(name));
+        self.history.retain(|name, _| history.contains(name));
+        for (name, desc) in descs {
+            if !self.transients.contains_key(name) {
+                let target = create_target(backend, &desc)?;
+                self.transients.insert(name, (desc, target));
+            }
+            if history.contains(name) && !self.history.contains_key(name) {
+                self.history.insert(name, create_target(backend, &desc)?);
+            }
+        }
+
+        self.schedule = order;
+        self.transitions = transitions;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Record all enabled passes for one frame, recompiling first if the graph changed.
+    pub fn execute(&mut self, backend: &mut B, frame: &FrameInputs<'_, B>) -> Result<(), GpuError> {
+        if self.dirty {
+            self.compile(backend)?;
+        }
+
+        // Last frame's targets become the history, the passes overwrite the older ones
+        for (name, previous) in &mut self.history {
+            if let Some((_, target)) = self.transients.get_mut(name) {
+                std::mem::swap(target, previous);
+            }
+        }
+
+        let RenderGraph {
+            nodes,
+            schedule,
+            transitions,
+            transients,
+            history,
+            ..
+        } = self;
+        let resources = Resources {
+            backbuffer: backend.backbuffer(),
+            main_depth: backend.main_depth_target(),
+            transients,
+            history,
+        };
+
+        upload_lights(backend, frame);
+        for (&i, transitions) in schedule.iter().zip(transitions.iter()) {
+            record_transitions(backend, &resources, transitions)?;
+            run_pass(backend, frame, &resources, &mut nodes[i])?;
+        }
+        Ok(())
+    }
+}
+
+fn graph_error(message: String) -> GpuError {
+    GpuError::new(message, GpuErrorKind::RenderPass)
+}
+
+fn create_target<B: GpuBackend>(
+    backend: &B,
+    desc: &TargetDesc,
+) -> Result<B::RenderTarget, GpuError> {
+    let (width, height) = match desc.size {
+        TargetSize::Backbuffer => backend.resolution(),
+        TargetSize::Fixed(w, h) => (w, h),
+    };
+    match desc.usage {
+        RenderTargetUsage::Storage => backend.create_rt_output_target(width, height),
+        usage => backend.create_render_target(&RenderTargetDesc {
+            width,
+            height,
+            format: desc.format,
+            sampler: desc.sampler,
+            usage,
+            sample_count: desc.sample_count,
+            persistent: desc.persistent,
+            layers: desc.layers,
+        }),
+    }
+}
+
+fn upload_lights<B: GpuBackend>(backend: &mut B, frame: &FrameInputs<'_, B>) {
+    let lights: Vec<GpuLight> = frame
+        .lights
+        .iter()
+        .take(MAX_LIGHTS)
+        .map(GpuLight::from_light)
+        .collect();
+    if !lights.is_empty() {
+        backend.cmd_update_buffer(frame.light_buffer, as_bytes(&lights));
+    }
+}
+
+/// Record the barriers before a pass. Also done for passes that skip this frame,
+/// so the accesses stay the ones [`RenderGraph::compile`] planned with.
+fn record_transitions<B: GpuBackend>(
+    backend: &mut B,
+    resources: &Resources<'_, B>,
+    transitions: &[Transition],
+) -> Result<(), GpuError> {
+    let mut barriers = Vec::with_capacity(transitions.len());
+    for transition in transitions {
+        let target = if transition.history {
+            resources.expect_history(transition.resource)?
+        } else {
+            resources.expect(transition.resource)?
+        };
+        barriers.push(TargetBarrier {
+            target,
+            before: transition.before,
+            after: transition.after,
+        });
+    }
+    if !barriers.is_empty() {
+        backend.cmd_target_barriers(&barriers);
+    }
+    Ok(())
+}
+
+fn load_op(load: LoadPolicy) -> LoadOp {
+    match load {
+        LoadPolicy::Clear => LoadOp::Clear,
+        LoadPolicy::Load => LoadOp::Load,
+    }
+}
+
+fn run_pass<B: GpuBackend>(
+    backend: &mut B,
+    frame: &FrameInputs<'_, B>,
+    resources: &Resources<'_, B>,
+    node: &mut Node<B>,
+) -> Result<(), GpuError> {
+    if !node.pass.should_run(frame) {
+        return Ok(());
+    }
+    let Some(decl) = node.decl.as_ref() else {
+        return Ok(());
+    };
+    let name = node.pass.name();
+
+    // Bind sampled inputs before the render pass begins
+    for &(resource, slot) in &decl.samples {
+        backend.bind_render_target_as_texture(slot, resources.expect(resource)?);
+    }
+    for &(resource, slot) in &decl.history {
+        backend.bind_render_target_as_texture(slot, resources.expect_history(resource)?);
+    }
+
+    backend.begin_event(name);
+    if !decl.custom {
+        let mut color_targets = Vec::with_capacity(decl.colors.len());
+        for color in &decl.colors {
+            color_targets.push(ColorAttachment {
+                target: resources.expect(color.name)?,
+                load_op: load_op(color.load),
+                clear_color: color.clear_color,
+                resolve_target: color.resolve.map(|r| resources.expect(r)).transpose()?,
+            });
+        }
+        let depth_target = match &decl.depth {
+            Some(depth) => {
+                let load_op = load_op(depth.load);
+                Some(DepthAttachment {
+                    target: resources.expect(depth.name)?,
+                    load_op,
+                    clear_depth: depth.clear_depth,
+                    write_enabled: depth.write_enabled,
+                    stencil_load_op: match depth.clear_stencil {
+                        Some(_) => LoadOp::Clear,
+                        None => load_op,
+                    },
+                    clear_stencil: depth.clear_stencil.unwrap_or(0),
+                    resolve_target: depth.resolve.map(|r| resources.expect(r)).transpose()?,
+                    layer: 0,
+                })
+            }
+            None => None,
+        };
+        let extent = color_targets
+            .first()
+            .map(|c| c.target)
+            .or(depth_target.as_ref().map(|d| d.target))
+            .map(|t: &B::RenderTarget| (t.width(), t.height()))
+            .unwrap_or_else(|| backend.resolution());
+
+        backend.begin_render_pass(&RenderPassDesc {
+            label: name,
+            color_targets,
+            depth_target,
+        });
+        backend.set_viewport(&ViewportDesc {
+            x: 0.0,
+            y: 0.0,
+            width: extent.0 as f32,
+            height: extent.1 as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        });
+    }
+
+    let result = node.pass.execute(&mut PassContext {
+        backend,
+        frame,
+        resources,
+    });
+
+    if !decl.custom {
+        backend.end_render_pass();
+    }
+    backend.end_event();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::headless_backend::{HeadlessBackend, RecordedCommand};
+
+    type Declare = fn(&mut PassBuilder);
+
+    /// Pass that only declares resources and records nothing.
+    struct TestPass {
+        name: &'static str,
+        declare: Declare,
+    }
+
+    impl GraphPass<HeadlessBackend> for TestPass {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn setup(&self, builder: &mut PassBuilder) {
+            (self.declare)(builder)
+        }
+
+        fn execute(&mut self, _ctx: &mut PassContext<'_, HeadlessBackend>) -> Result<(), GpuError> {
+            Ok(())
+        }
+    }
+
+    fn graph(passes: &[(&'static str, Declare)]) -> RenderGraph<HeadlessBackend> {
+        let mut graph = RenderGraph::new();
+        for &(name, declare) in passes {
+            graph.add_pass(Box::new(TestPass { name, declare }));
+        }
+        graph
+    }
+
+    fn compile_error(graph: &mut RenderGraph<HeadlessBackend>) -> String {
+        let backend = HeadlessBackend::new(64, 64);
+        match graph.compile(&backend) {
+            Ok(()) => panic!("graph compiled: {:?}", graph.execution_order()),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    const HDR: TextureFormat = TextureFormat::R16g16b16a16Float;
+
+    #[test]
+    fn writers_run_before_readers() {
+        // inserted in reverse
+        let mut graph = graph(&[
+            ("compose", |b| {
+                b.sample("a", 0).sample("b", 1).write_color(
+                    BACKBUFFER,
+                    LoadPolicy::Clear,
+                    [0.0; 4],
+                );
+            }),
+            ("modify_a", |b| {
+                b.write_color("a", LoadPolicy::Load, [0.0; 4]);
+            }),
+            ("produce_b", |b| {
+                b.create("b", TargetDesc::color(HDR))
+                    .write_color("b", LoadPolicy::Clear, [0.0; 4]);
+            }),
+            ("produce_a", |b| {
+                b.create("a", TargetDesc::color(HDR))
+                    .write_color("a", LoadPolicy::Clear, [0.0; 4]);
+            }),
+        ]);
+        graph.compile(&HeadlessBackend::new(64, 64)).unwrap();
+        assert_eq!(
+            graph.execution_order(),
+            ["produce_b", "produce_a", "modify_a", "compose"]
+        );
+
+        // without the producer the modifier is the only writer left
+        graph.set_enabled("produce_a", false);
+        assert!(compile_error(&mut graph).contains("'a', which no pass creates"));
+    }
+
+    #[test]
+    fn invalid_graphs_fail_to_compile() {
+        let mut cycle = graph(&[
+            ("x", |b| {
+                b.create("a", TargetDesc::color(HDR))
+                    .sample("b", 0)
+                    .write_color("a", LoadPolicy::Clear, [0.0; 4]);
+            }),
+            ("y", |b| {
+                b.create("b", TargetDesc::color(HDR))
+                    .sample("a", 0)
+                    .write_color("b", LoadPolicy::Clear, [0.0; 4]);
+            }),
+        ]);
+        assert!(compile_error(&mut cycle).contains("dependency cycle"));
+
+        let mut unwritten = graph(&[("reader", |b| {
+            b.create("a", TargetDesc::color(HDR)).sample("a", 0);
+        })]);
+        assert!(compile_error(&mut unwritten).contains("which no enabled pass writes"));
+
+        let mut no_history = graph(&[("taa", |b| {
+            b.create("a", TargetDesc::color(HDR))
+                .sample_history("a", 0)
+                .write_color("a", LoadPolicy::Clear, [0.0; 4]);
+        })]);
+        assert!(compile_error(&mut no_history).contains("no persistent target"));
+
+        let mut no_depth = graph(&[("stencil", |b| {
+            b.write_color(BACKBUFFER, LoadPolicy::Clear, [0.0; 4])
+                .clear_stencil(0);
+        })]);
+        assert!(compile_error(&mut no_depth).contains("has no depth attachment"));
+
+        let mut no_attachment = graph(&[("resolve", |b| {
+            b.create("a", TargetDesc::color(HDR))
+                .write_color(BACKBUFFER, LoadPolicy::Clear, [0.0; 4])
+                .resolve("msaa", "a");
+        })]);
+        assert!(compile_error(&mut no_attachment).contains("'msaa', which is not an attachment"));
+    }
+
+    #[test]
+    fn targets_follow_declarations_and_resolution() {
+        let mut graph = graph(&[
+            ("scaled", |b| {
+                b.create("scaled", TargetDesc::color(HDR)).write_color(
+                    "scaled",
+                    LoadPolicy::Clear,
+                    [0.0; 4],
+                );
+            }),
+            ("fixed", |b| {
+                let desc = TargetDesc {
+                    size: TargetSize::Fixed(16, 8),
+                    ..TargetDesc::color(HDR)
+                };
+                b.create("fixed", desc)
+                    .write_color("fixed", LoadPolicy::Clear, [0.0; 4]);
+            }),
+        ]);
+        let mut backend = HeadlessBackend::new(64, 32);
+        graph.compile(&backend).unwrap();
+        let size = |graph: &RenderGraph<HeadlessBackend>, name| {
+            graph.target(name).map(|t| (t.width, t.height))
+        };
+        assert_eq!(size(&graph, "scaled"), Some((64, 32)));
+        assert_eq!(size(&graph, "fixed"), Some((16, 8)));
+
+        // recompiling keeps the allocated targets
+        let id = graph.target("scaled").unwrap().id;
+        graph.compile(&backend).unwrap();
+        assert_eq!(graph.target("scaled").unwrap().id, id);
+
+        backend.resize(128, 96);
+        graph.resize(&backend).unwrap();
+        assert_eq!(size(&graph, "scaled"), Some((128, 96)));
+        assert_eq!(size(&graph, "fixed"), Some((16, 8)));
+
+        // a target nobody declares anymore is released
+        graph.set_enabled("fixed", false);
+        graph.compile(&backend).unwrap();
+        assert_eq!(size(&graph, "fixed"), None);
+    }
+
+    /// Execute `graph` for one frame without scene or lights and return its commands.
+    fn execute_frame(
+        graph: &mut RenderGraph<HeadlessBackend>,
+        backend: &mut HeadlessBackend,
+    ) -> Vec<RecordedCommand> {
+        let scene = Scenegraph::empty();
+        let screen_quad = ScreenQuad::create(backend).unwrap();
+        let buffer = |label: &str| {
+            let desc = BufferDesc {
+                label: label.to_string(),
+                usage: BufferUsage::Uniform,
+                size: 64,
+            };
+            backend.create_buffer(&desc, None).unwrap()
+        };
+        let (light_buffer, light_space_buffer) = (buffer("lights"), buffer("light_space"));
+        let frame = FrameInputs {
+            scene: &scene,
+            screen_quad: &screen_quad,
+            skybox: None,
+            lights: &[],
+            light_buffer: &light_buffer,
+            light_space_buffer: &light_space_buffer,
+            rt: None,
+            overlay: None,
+        };
+        backend.clear_commands();
+        graph.execute(backend, &frame).unwrap();
+        backend.take_commands()
+    }
+
+    /// `(pass, target, before, after)` of every barrier, attributed to the pass it precedes.
+    fn barriers(commands: &[RecordedCommand]) -> Vec<(String, usize, TargetAccess, TargetAccess)> {
+        let mut pending = Vec::new();
+        let mut barriers = Vec::new();
+        for cmd in commands {
+            match cmd {
+                RecordedCommand::TargetBarrier {
+                    target,
+                    before,
+                    after,
+                } => pending.push((*target, *before, *after)),
+                RecordedCommand::BeginEvent(pass) => barriers.extend(
+                    pending
+                        .drain(..)
+                        .map(|(target, before, after)| (pass.clone(), target, before, after)),
+                ),
+                _ => (),
+            }
+        }
+        barriers
+    }
+
+    #[test]
+    fn barriers_follow_resource_accesses() {
+        use TargetAccess::*;
+        let mut graph = graph(&[
+            ("produce", |b| {
+                b.create("a", TargetDesc::color(HDR))
+                    .write_color("a", LoadPolicy::Clear, [0.0; 4])
+                    .write_depth(MAIN_DEPTH, LoadPolicy::Clear, 1.0);
+            }),
+            ("compose", |b| {
+                b.sample("a", 0)
+                    .write_color(BACKBUFFER, LoadPolicy::Clear, [0.0; 4])
+                    .test_depth(MAIN_DEPTH);
+            }),
+            ("overlay", |b| {
+                b.write(BACKBUFFER, LoadPolicy::Load).custom();
+            }),
+        ]);
+        let mut backend = HeadlessBackend::new(64, 64);
+        execute_frame(&mut graph, &mut backend);
+        let commands = execute_frame(&mut graph, &mut backend);
+        let a = graph.target("a").unwrap().id;
+        let backbuffer = backend.backbuffer().id;
+        let depth = backend.main_depth_target().id;
+        let barrier = |pass: &str, target, before, after| (pass.to_string(), target, before, after);
+        assert_eq!(
+            barriers(&commands),
+            [
+                // "a" was last sampled and the depth last tested in the previous frame
+                barrier("produce", a, ShaderRead, ColorAttachment),
+                barrier("produce", depth, DepthTest, DepthAttachment),
+                barrier("compose", backbuffer, Undefined, ColorAttachment),
+                barrier("compose", depth, DepthAttachment, DepthTest),
+                barrier("compose", a, ColorAttachment, ShaderRead),
+                barrier("overlay", backbuffer, ColorAttachment, Custom),
+            ]
+        );
+
+        // a resource and its history swap, and so do their accesses
+        let mut history = graph_with_history();
+        execute_frame(&mut history, &mut backend);
+        let commands = execute_frame(&mut history, &mut backend);
+        let (sampled, written) = sampled_and_written(&commands);
+        assert_eq!(
+            barriers(&commands),
+            [
+                barrier("accumulate", written, ShaderRead, ColorAttachment),
+                barrier("accumulate", sampled, ColorAttachment, ShaderRead),
+            ]
+        );
+    }
+
+    fn graph_with_history() -> RenderGraph<HeadlessBackend> {
+        graph(&[("accumulate", |b| {
+            b.create("acc", TargetDesc::color(HDR).persistent())
+                .sample_history("acc", 3)
+                .write_color("acc", LoadPolicy::Clear, [0.0; 4]);
+        })])
+    }
+
+    /// The history target sampled in slot 3 and the target written by a frame.
+    fn sampled_and_written(commands: &[RecordedCommand]) -> (usize, usize) {
+        let sampled = commands.iter().find_map(|cmd| match cmd {
+            RecordedCommand::BindRenderTargetAsTexture { slot: 3, target } => Some(*target),
+            _ => None,
+        });
+        let written = commands.iter().find_map(|cmd| match cmd {
+            RecordedCommand::BeginRenderPass { color_targets, .. } => Some(color_targets[0].target),
+            _ => None,
+        });
+        (sampled.unwrap(), written.unwrap())
+    }
+
+    #[test]
+    fn history_swaps_every_frame() {
+        let mut graph = graph_with_history();
+        let mut backend = HeadlessBackend::new(64, 64);
+        let mut run_frame = |graph: &mut RenderGraph<HeadlessBackend>| {
+            sampled_and_written(&execute_frame(graph, &mut backend))
+        };
+
+        let (history, current) = run_frame(&mut graph);
+        assert_ne!(history, current);
+        // what was written is sampled in the next frame, and the other way round
+        assert_eq!(run_frame(&mut graph), (current, history));
+        assert_eq!(run_frame(&mut graph), (history, current));
+    }
+}
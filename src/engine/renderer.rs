@@ -1,70 +1,88 @@
 //! The main engine renderer, generic over [`GpuBackend`].
 //!
 //! Orchestrates the full deferred+forward rendering pipeline:
-//! deferred pre-pass -> SSAO -> per-light (shadow -> deferred light -> forward) -> output -> skybox.
+//! deferred pre-pass -> light culling -> shadow maps -> SSAO -> deferred light -> forward
+//! -> TAA -> output -> skybox.
+//! The passes are nodes of a [`RenderGraph`], which orders them and owns their targets.
 //!
-//! The Renderer owns the backend, scenegraph, and all render pass programs.
+//! The Renderer owns the backend, scenegraph, and the render graph.
 //! It does NOT own the window or event loop — those are managed externally
 //! by the winit-based window module.
 
 use super::backend::*;
 use super::draw_programs::*;
-use super::geometry::{Light, LightType};
+use super::geometry::{Light, LightType, MAX_SHADOW_CASCADES};
+use super::gpu_layout::Pad;
+use super::render_graph::{EguiOverlay, FrameInputs, RenderGraph, RtScene};
 use super::scene_data::{self, LightData, NodeTransform, SceneData};
 use super::scene_info::NodeInfo;
 use super::scenegraph::Scenegraph;
+use super::screenshot;
 use super::settings::Settings;
+use super::shader_reload::{self, ReloadedShaders, ShaderWatcher};
 use super::skybox::Skybox;
 
 use crate::import;
 use crate::input::Camera;
 
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::time::Instant;
 
-/// Maximum number of lights supported by the RT shadow pipeline.
-const MAX_RT_LIGHTS: usize = 12;
-
 pub struct Renderer<B: GpuBackend> {
     settings: Settings,
     scene: Scenegraph<B>,
-    shadow_dist: f32,
     screen_quad: ScreenQuad<B>,
-    forward_program: Option<ForwardPass<B>>,
-    deferred_program_pre: Option<DeferredPassPre<B>>,
-    deferred_program_light: Option<DeferredPassLight<B>>,
-    shadow_program: Option<ShadowPass<B>>,
-    skybox_program: Option<SkyBoxPass<B>>,
+    /// Render passes and their transient targets.
+    graph: RenderGraph<B>,
+    /// Shader sources the passes were built from, replaced on hot reload.
+    shaders: Option<Shaders<B>>,
+    rt_shaders: Option<RtShaders<B>>,
+    proc_gen_shaders: ProceduralShaders<B>,
+    /// `None` if hot reload is disabled or the SPIR-V directory is missing.
+    shader_watcher: Option<ShaderWatcher>,
     skybox: Option<Skybox<B>>,
-    output_program: Option<OutputPass<B>>,
+    /// egui output of the current frame, composited by the `egui` pass.
+    overlay: Option<EguiOverlay>,
     /// Path to the currently loaded glTF scene file.
     scene_file: Option<String>,
     backend: B,
     clock: Instant,
-    // Shared UBO buffers bound permanently to descriptor set bindings 0-5
+    // Shared buffers bound permanently to descriptor set bindings 0-5 and 17-20
     ubo_view_proj: B::Buffer,          // binding 0, ViewProjUniforms (128B)
-    ubo_camera_pixel: B::Buffer,       // binding 1, CameraUniforms (16B)
-    ubo_light_data: B::Buffer,         // binding 2, GpuLight (128B)
+    ubo_camera_pixel: B::Buffer,       // binding 1, CameraUniforms (32B)
+    ubo_clusters: B::Buffer,           // binding 2, ClusterUniforms (176B)
     ubo_shadow_light_space: B::Buffer, // binding 3, LightSpaceUniforms (64B)
     ubo_skybox_view_proj: B::Buffer,   // binding 4, ViewProjUniforms (128B)
     ubo_near_far: B::Buffer,           // binding 5, NearFarUniforms (16B)
+    light_buffer: B::Buffer,           // binding 17, GpuLight[MAX_LIGHTS] (116KiB)
+    cluster_lights: B::Buffer,         // binding 18, cluster light lists (1.7MiB)
+    ubo_ssao: B::Buffer,               // binding 19, SsaoUniforms (672B)
+    ubo_taa: B::Buffer,                // binding 20, TaaUniforms (144B)
     // CPU-side copies for partial updates
     view_proj_cpu: ViewProjUniforms,
     camera_pixel_cpu: CameraUniforms,
     skybox_view_proj_cpu: ViewProjUniforms,
     near_far_cpu: NearFarUniforms,
+    /// Frames rendered with TAA, selects the projection jitter.
+    taa_frame: u32,
+    /// Whether the TAA history holds a frame of the current view to blend with.
+    taa_history: bool,
+    /// Unjittered view-projection of the previous frame.
+    prev_view_proj: glm::Mat4,
+    /// Camera position and forward direction of the previous frame, to detect cuts.
+    prev_camera: Option<(glm::Vec3, glm::Vec3)>,
     // Ray tracing acceleration structures (built once after scene load)
     blas: Vec<B::AccelerationStructure>,
     tlas: Option<B::AccelerationStructure>,
-    // Ray tracing pipeline and output
-    rt_pipeline: Option<B::Pipeline>,
-    rt_output: Option<B::RenderTarget>,
-    rt_light_buffer: Option<B::Buffer>,
     /// Per-instance material SSBO: one u32 albedo bindless index per TLAS instance.
     /// Uploaded once at TLAS build time; read by the any-hit shader for alpha cutout.
     rt_material_buffer: Option<B::Buffer>,
     /// Whether to use ray tracing when available. Defaults to `true`.
     use_ray_tracing: bool,
+    /// Scene lights left without shadow maps in the last frame, logged when
+    /// they change.
+    unshadowed_lights: Vec<usize>,
 }
 
 impl<B: GpuBackend> Renderer<B> {
@@ -75,6 +93,12 @@ impl<B: GpuBackend> Renderer<B> {
     /// Without draw programs, the renderer falls back to clearing the screen.
     pub fn create(backend: B, settings: Settings) -> Result<Self, GpuError> {
         let screen_quad = ScreenQuad::create(&backend)?;
+        let proc_gen_shaders = backend.load_proc_gen_shaders();
+        let shader_watcher = if settings.shader_hot_reload {
+            ShaderWatcher::new(shader_reload::SPIRV_DIR)
+        } else {
+            None
+        };
 
         let ubo_desc = |label: &str, size| BufferDesc {
             label: label.to_string(),
@@ -90,8 +114,8 @@ impl<B: GpuBackend> Renderer<B> {
             &ubo_desc("shared_camera_pixel", std::mem::size_of::<CameraUniforms>()),
             None,
         )?;
-        let ubo_light_data = backend.create_buffer(
-            &ubo_desc("shared_light_data", std::mem::size_of::<GpuLight>()),
+        let ubo_clusters = backend.create_buffer(
+            &ubo_desc("shared_clusters", std::mem::size_of::<ClusterUniforms>()),
             None,
         )?;
         let ubo_shadow_light_space = backend.create_buffer(
@@ -113,12 +137,43 @@ impl<B: GpuBackend> Renderer<B> {
             None,
         )?;
 
+        let light_buffer = backend.create_buffer(
+            &BufferDesc {
+                label: "light_buffer".to_string(),
+                usage: BufferUsage::Storage,
+                size: std::mem::size_of::<GpuLight>() * MAX_LIGHTS,
+            },
+            None,
+        )?;
+        // Filled by the light culling pass
+        let cluster_lights = backend.create_buffer(
+            &BufferDesc {
+                label: "cluster_lights".to_string(),
+                usage: BufferUsage::Storage,
+                size: CLUSTER_LIGHTS_SIZE,
+            },
+            None,
+        )?;
+
+        let ubo_ssao = backend.create_buffer(
+            &ubo_desc("shared_ssao", std::mem::size_of::<SsaoUniforms>()),
+            None,
+        )?;
+        let ubo_taa = backend.create_buffer(
+            &ubo_desc("shared_taa", std::mem::size_of::<TaaUniforms>()),
+            None,
+        )?;
+
         backend.bind_buffer_to_descriptor(0, &ubo_view_proj);
         backend.bind_buffer_to_descriptor(1, &ubo_camera_pixel);
-        backend.bind_buffer_to_descriptor(2, &ubo_light_data);
+        backend.bind_buffer_to_descriptor(2, &ubo_clusters);
         backend.bind_buffer_to_descriptor(3, &ubo_shadow_light_space);
         backend.bind_buffer_to_descriptor(4, &ubo_skybox_view_proj);
         backend.bind_buffer_to_descriptor(5, &ubo_near_far);
+        backend.bind_buffer_to_descriptor(17, &light_buffer);
+        backend.bind_buffer_to_descriptor(18, &cluster_lights);
+        backend.bind_buffer_to_descriptor(19, &ubo_ssao);
+        backend.bind_buffer_to_descriptor(20, &ubo_taa);
 
         let identity = glm::Mat4::identity();
         let view_proj_cpu = ViewProjUniforms {
@@ -130,6 +185,8 @@ impl<B: GpuBackend> Renderer<B> {
         let camera_pixel_cpu = CameraUniforms {
             camera_pos: glm::Vec3::zeros(),
             ssao: 0,
+            taa: 0,
+            _pad: Pad::ZERO,
         };
         let skybox_view_proj_cpu = ViewProjUniforms {
             view: identity,
@@ -147,35 +204,40 @@ impl<B: GpuBackend> Renderer<B> {
         Ok(Renderer {
             settings,
             scene: Scenegraph::empty(),
-            shadow_dist: 50.0,
             screen_quad,
-            forward_program: None,
-            deferred_program_pre: None,
-            deferred_program_light: None,
-            shadow_program: None,
-            skybox_program: None,
+            graph: RenderGraph::new(),
+            shaders: None,
+            rt_shaders: None,
+            proc_gen_shaders,
+            shader_watcher,
             skybox: None,
-            output_program: None,
+            overlay: None,
             scene_file: None,
             backend,
             clock: Instant::now(),
             ubo_view_proj,
             ubo_camera_pixel,
-            ubo_light_data,
+            ubo_clusters,
             ubo_shadow_light_space,
             ubo_skybox_view_proj,
             ubo_near_far,
+            light_buffer,
+            cluster_lights,
+            ubo_ssao,
+            ubo_taa,
             view_proj_cpu,
             camera_pixel_cpu,
             skybox_view_proj_cpu,
             near_far_cpu,
+            taa_frame: 0,
+            taa_history: false,
+            prev_view_proj: identity,
+            prev_camera: None,
             blas: Vec::new(),
             tlas: None,
-            rt_pipeline: None,
-            rt_output: None,
-            rt_light_buffer: None,
             rt_material_buffer: None,
             use_ray_tracing: true,
+            unshadowed_lights: Vec::new(),
         })
     }
 
@@ -187,6 +249,21 @@ impl<B: GpuBackend> Renderer<B> {
         &mut self.backend
     }
 
+    pub fn render_graph(&self) -> &RenderGraph<B> {
+        &self.graph
+    }
+
+    /// The render graph, for adding, removing or toggling passes at runtime.
+    pub fn render_graph_mut(&mut self) -> &mut RenderGraph<B> {
+        &mut self.graph
+    }
+
+    /// Hand the egui output of this frame to the renderer; the `egui` pass
+    /// composites it over the scene during `render_scene()`.
+    pub fn set_overlay(&mut self, overlay: EguiOverlay) {
+        self.overlay = Some(overlay);
+    }
+
     pub fn settings(&self) -> &Settings {
         &self.settings
     }
@@ -333,6 +410,73 @@ impl<B: GpuBackend> Renderer<B> {
             &self.ubo_near_far,
             as_bytes(std::slice::from_ref(&self.near_far_cpu)),
         );
+        // A different camera: nothing in the history matches its view
+        self.taa_history = false;
+    }
+
+    /// Rebuild the pipelines whose SPIR-V changed on disk. Call between frames,
+    /// before `begin_frame()`. Failures are logged and the old pipelines kept.
+    pub fn reload_changed_shaders(&mut self) {
+        let Some(watcher) = &mut self.shader_watcher else {
+            return;
+        };
+        let changed = watcher.poll();
+        if changed.is_empty() {
+            return;
+        }
+        let dir = watcher.dir().to_path_buf();
+        println!("Shader reload: {} file(s) changed", changed.len());
+
+        // The scatter pipeline only lives for a procedural load, the next one picks this up
+        if let Some(scattering) = shader_reload::reload_if_changed(
+            &self.backend,
+            &self.proc_gen_shaders.scattering,
+            &changed,
+            &dir,
+        ) {
+            self.proc_gen_shaders.scattering = scattering;
+        }
+
+        let Some(shaders) = &self.shaders else {
+            return;
+        };
+        let reload =
+            |source| shader_reload::reload_if_changed(&self.backend, source, &changed, &dir);
+        let reloaded = ReloadedShaders {
+            deferred_pre: reload(&shaders.deferred_pre),
+            shadow: reload(&shaders.shadow),
+            ssao: reload(&shaders.ssao),
+            ssao_blur: reload(&shaders.ssao_blur),
+            deferred_light: reload(&shaders.deferred_light),
+            forward: reload(&shaders.forward),
+            taa: reload(&shaders.taa),
+            output: reload(&shaders.output),
+            skybox: reload(&shaders.skybox),
+            light_culling: reload(&shaders.light_culling),
+            rt: self.rt_shaders.as_ref().and_then(|rt| {
+                shader_reload::reload_rt_if_changed(&self.backend, rt, &changed, &dir)
+            }),
+        };
+        if reloaded.is_empty() {
+            return;
+        }
+
+        // Frames in flight may still use the old pipelines
+        if let Err(e) = self.backend.wait_idle() {
+            eprintln!("Shader reload: wait_idle failed: {e}");
+            return;
+        }
+        // Rebuild program by program and adopt the sources whose passes built their
+        // pipelines, so a later rebuild from `self.shaders` matches them. A broken
+        // program keeps its old source and pipelines until its files change again.
+        for program in reloaded.per_program() {
+            if self.graph.reload_shaders(&self.backend, &program).is_err() {
+                continue;
+            }
+            if let Some(shaders) = &mut self.shaders {
+                program.adopt(shaders, &mut self.rt_shaders);
+            }
+        }
     }
 
     /// Resize the backend and all resolution-dependent render targets.
@@ -344,113 +488,114 @@ impl<B: GpuBackend> Renderer<B> {
         // backend's actual post-recreation resolution for every resolution-dependent
         // render target so they exactly match the swapchain — otherwise the G-buffer,
         // forward, and RT targets can end up sized to a stale request and only cover part
-        // of the screen.
-        let resolution = self.backend.resolution();
-        if let Some(ref mut dp) = self.deferred_program_pre {
-            if let Err(e) = dp.resize(&self.backend, resolution) {
-                eprintln!("Failed to resize deferred pre targets: {}", e);
-            }
-        }
-        if let Some(ref mut dl) = self.deferred_program_light {
-            if let Err(e) = dl.resize(&self.backend, resolution) {
-                eprintln!("Failed to resize deferred light target: {}", e);
-            }
-        }
-        if let Some(ref mut fwd) = self.forward_program {
-            if let Err(e) = fwd.resize(&self.backend, resolution) {
-                eprintln!("Failed to resize forward target: {}", e);
-            }
-        }
-        if self.rt_output.is_some() {
-            match self
-                .backend
-                .create_rt_output_target(resolution.0, resolution.1)
-            {
-                Ok(rt) => self.rt_output = Some(rt),
-                Err(e) => eprintln!("Failed to resize RT output target: {}", e),
-            }
+        // of the screen. The graph reads it from the backend.
+        if let Err(e) = self.graph.resize(&self.backend) {
+            eprintln!("Failed to resize render graph targets: {}", e);
         }
+        // The history targets were recreated without contents
+        self.taa_history = false;
     }
 
     /// Initialize all draw programs from compiled WGSL shaders.
     ///
-    /// This creates the full rendering pipeline:
-    /// deferred pre → SSAO → shadow → deferred light → forward → output → skybox.
+    /// This creates the full rendering pipeline as render graph passes:
+    /// deferred pre → (MSAA depth) → SSAO → light culling → shadow → deferred light
+    /// → forward → TAA → output → skybox → egui.
     /// After this call, the renderer will use the full pipeline instead of
     /// the fallback clear-to-screen path.
     pub fn init_draw_programs(&mut self) -> Result<(), GpuError> {
-        let resolution = self.backend.resolution();
         let backbuffer_format = self.backend.backbuffer().format();
+        let sample_count = self
+            .settings
+            .msaa_samples
+            .clamp(1, self.backend.max_sample_count());
 
-        let Shaders {
-            deferred_pre,
-            shadow: shadow_wgsl,
-            deferred_light,
-            forward,
-            output,
-            skybox,
-        } = self.backend.load_shaders();
+        let shaders = self.backend.load_shaders();
 
         println!("Initializing draw programs...");
 
         // Deferred pre-pass (G-buffer fill)
-        self.deferred_program_pre = Some(DeferredPassPre::create(
-            &self.backend,
-            resolution,
-            &deferred_pre,
-        )?);
+        let deferred_pre = DeferredPassPre::create(&self.backend, &shaders.deferred_pre)?;
+        self.graph.add_pass(Box::new(deferred_pre));
         println!("  deferred_pre: OK");
 
+        // Multisampled depth of the opaque geometry for the MSAA passes
+        if sample_count > 1 {
+            let msaa_depth =
+                MsaaDepthPass::create(&self.backend, &shaders.deferred_pre, sample_count)?;
+            self.graph.add_pass(Box::new(msaa_depth));
+            println!("  msaa_depth: OK ({sample_count}x)");
+        }
+
+        // Light culling, declares no graph resources: added before the lighting passes
+        let light_culling = LightCullingPass::create(&self.backend, &shaders.light_culling)?;
+        self.graph.add_pass(Box::new(light_culling));
+        println!("  light_culling: OK");
+
+        // Ray tracing pipeline (optional, requires hardware support).
+        // Enabled per frame once a TLAS exists, in place of the shadow pass.
+        if self.backend.has_rt_support() {
+            let rt_shaders = self.backend.load_rt_shaders();
+            let rt_shadows = RtShadowPass::create(&self.backend, &rt_shaders)?;
+            self.graph.add_pass(Box::new(rt_shadows));
+            self.graph.set_enabled(RT_SHADOW_PASS, false);
+            self.rt_shaders = Some(rt_shaders);
+            println!("  rt_pipeline: OK");
+        }
+
         // Shadow mapping pass
-        self.shadow_program = Some(ShadowPass::create(&self.backend, &shadow_wgsl)?);
+        let shadow = ShadowPass::create(&self.backend, &shaders.shadow)?;
+        self.graph.add_pass(Box::new(shadow));
         println!("  shadow: OK");
 
+        // Ambient occlusion of the G-buffer, enabled per frame by the SSAO setting
+        let ssao = SsaoPass::create(&self.backend, &shaders.ssao)?;
+        self.graph.add_pass(Box::new(ssao));
+        let ssao_blur = SsaoBlurPass::create(&self.backend, &shaders.ssao_blur)?;
+        self.graph.add_pass(Box::new(ssao_blur));
+        println!("  ssao: OK");
+
         // Deferred lighting pass
-        self.deferred_program_light = Some(DeferredPassLight::create(
-            &self.backend,
-            resolution,
-            &deferred_light,
-        )?);
+        let deferred_light = DeferredPassLight::create(&self.backend, &shaders.deferred_light)?;
+        self.graph.add_pass(Box::new(deferred_light));
         println!("  deferred_light: OK");
 
         // Forward pass (transparent objects)
-        self.forward_program = Some(ForwardPass::create(&self.backend, resolution, &forward)?);
+        let forward = ForwardPass::create(&self.backend, &shaders.forward, sample_count)?;
+        self.graph.add_pass(Box::new(forward));
         println!("  forward: OK");
 
+        // Temporal anti-aliasing resolve, enabled per frame by the TAA setting
+        let taa = TaaPass::create(&self.backend, &shaders.taa)?;
+        self.graph.add_pass(Box::new(taa));
+        println!("  taa: OK");
+
         // Output composite pass
-        self.output_program = Some(OutputPass::create(
+        let output = OutputPass::create(
             &self.backend,
-            &output,
+            &shaders.output,
             backbuffer_format,
-        )?);
+            sample_count,
+        )?;
+        self.graph.add_pass(Box::new(output));
         println!("  output: OK");
 
         // Skybox pass
-        self.skybox_program = Some(SkyBoxPass::create(
+        let skybox = SkyBoxPass::create(
             &self.backend,
-            &skybox,
+            &shaders.skybox,
             backbuffer_format,
-        )?);
+            sample_count,
+        )?;
+        self.graph.add_pass(Box::new(skybox));
         println!("  skybox: OK");
 
-        // Ray tracing pipeline (optional, requires hardware support)
-        if self.backend.has_rt_support() {
-            let rt_shaders = self.backend.load_rt_shaders();
-            self.rt_pipeline = Some(self.backend.create_rt_pipeline(&rt_shaders)?);
-            self.rt_output = Some(
-                self.backend
-                    .create_rt_output_target(resolution.0, resolution.1)?,
-            );
-            self.rt_light_buffer = Some(self.backend.create_buffer(
-                &BufferDesc {
-                    label: "rt_light_array".to_string(),
-                    usage: BufferUsage::Storage,
-                    size: std::mem::size_of::<GpuLight>() * MAX_RT_LIGHTS,
-                },
-                None,
-            )?);
-            println!("  rt_pipeline: OK");
-        }
+        // Editor overlay
+        self.graph.add_pass(Box::new(EguiPass));
+
+        // Allocate the graph targets up front rather than in the first frame
+        self.graph.compile(&self.backend)?;
+        self.shaders = Some(shaders);
 
         println!("All draw programs initialized.");
         Ok(())
@@ -581,6 +726,7 @@ impl<B: GpuBackend> Renderer<B> {
         println!("Reading scene file...");
         let node = import::load_gltf(scene_file, &self.backend)?;
         println!("Processing scene...");
+        self.unload_scene();
         self.scene.set_root(node);
         self.scene_file = Some(scene_file.to_string());
 
@@ -597,14 +743,7 @@ impl<B: GpuBackend> Renderer<B> {
             color: glm::vec3(23.47, 21.31, 20.79),
             radius: 0.10,
             penumbra_radius: 0.0,
-            light_proj: glm::ortho_zo(
-                -self.shadow_dist,
-                self.shadow_dist,
-                -self.shadow_dist,
-                self.shadow_dist,
-                1.0,
-                2.5 * self.shadow_dist,
-            ),
+            ..Light::default()
         });
 
         // Fire holder lights — 4 wall-mounted brackets along the inner arcade.
@@ -640,19 +779,19 @@ impl<B: GpuBackend> Renderer<B> {
         self.build_tlas();
 
         // Compute scene-wide half-diagonal from world AABB to size the skybox cube.
-        // Falls back to shadow_dist if AABB is empty (e.g. no static meshes).
+        // Falls back to MIN_SKY_SCALE if AABB is empty (e.g. no static meshes).
         let sky_scale = if let Some(root) = self.scene.root() {
             let aabb = root.world_aabb();
             if !aabb.is_empty() {
                 let extent = aabb.max - aabb.min;
                 let half_diag =
                     (extent.x.powi(2) + extent.y.powi(2) + extent.z.powi(2)).sqrt() * 0.5;
-                half_diag.max(self.shadow_dist)
+                half_diag.max(MIN_SKY_SCALE)
             } else {
-                self.shadow_dist
+                MIN_SKY_SCALE
             }
         } else {
-            self.shadow_dist
+            MIN_SKY_SCALE
         };
 
         // Load skybox cubemap
@@ -740,50 +879,47 @@ impl<B: GpuBackend> Renderer<B> {
         };
 
         println!("Creating scatter pipeline...");
-        let pipeline = create_pipeline(&self.backend, config.world_dimension)?;
+        let pipeline = create_pipeline(
+            &self.backend,
+            &self.proc_gen_shaders.scattering,
+            config.world_dimension,
+        )?;
 
         println!("Generating procedural world...");
         let sg = load_procedural_world(&self.backend, &config, &pipeline)?;
         self.scene = sg;
         self.scene_file = Some("__procedural__".into());
+        self.taa_history = false;
 
         self.scene.add_light(Light {
             color: glm::vec3(0.25, 0.25, 0.25),
             ..Light::default()
         });
-        let shadow_dist = self.shadow_dist;
         self.scene.add_light(Light {
             position: glm::vec3(-1.0, -0.3, 0.1).normalize(),
             t: LightType::Directional,
             color: glm::vec3(23.47, 21.31, 20.79),
             radius: 1.0,
             penumbra_radius: 0.0,
-            light_proj: glm::ortho_zo(
-                -shadow_dist,
-                shadow_dist,
-                -shadow_dist,
-                shadow_dist,
-                1.0,
-                2.5 * self.shadow_dist,
-            ),
+            ..Light::default()
         });
 
         self.scene.build_matrices(&self.backend);
 
         // Compute scene-wide half-diagonal from world AABB to size the skybox cube.
-        // Procedural terrain returns empty AABB, so falls back to shadow_dist.
+        // Procedural terrain returns empty AABB, so falls back to MIN_SKY_SCALE.
         let sky_scale = if let Some(root) = self.scene.root() {
             let aabb = root.world_aabb();
             if !aabb.is_empty() {
                 let extent = aabb.max - aabb.min;
                 let half_diag =
                     (extent.x.powi(2) + extent.y.powi(2) + extent.z.powi(2)).sqrt() * 0.5;
-                half_diag.max(self.shadow_dist)
+                half_diag.max(MIN_SKY_SCALE)
             } else {
-                self.shadow_dist
+                MIN_SKY_SCALE
             }
         } else {
-            self.shadow_dist
+            MIN_SKY_SCALE
         };
 
         println!("Loading skybox...");
@@ -815,6 +951,8 @@ impl<B: GpuBackend> Renderer<B> {
             &TextureDesc {
                 width: 1,
                 height: 1,
+                depth_or_layers: 1,
+                dimension: TextureDimension::D2,
                 format: TextureFormat::Rgba8UnormSrgb,
                 sampler: SamplerDesc {
                     address_u: AddressMode::Clamp,
@@ -823,6 +961,7 @@ impl<B: GpuBackend> Renderer<B> {
                     compare: None,
                 },
                 generate_mipmaps: false,
+                mip_levels: 1,
             },
             &[220, 50, 50, 255],
         )?);
@@ -833,6 +972,8 @@ impl<B: GpuBackend> Renderer<B> {
             &TextureDesc {
                 width: 1,
                 height: 1,
+                depth_or_layers: 1,
+                dimension: TextureDimension::D2,
                 format: TextureFormat::Rgba8Unorm,
                 sampler: SamplerDesc {
                     address_u: AddressMode::Clamp,
@@ -841,6 +982,7 @@ impl<B: GpuBackend> Renderer<B> {
                     compare: None,
                 },
                 generate_mipmaps: false,
+                mip_levels: 1,
             },
             &[0, 128, 0, 255],
         )?);
@@ -850,6 +992,8 @@ impl<B: GpuBackend> Renderer<B> {
             &TextureDesc {
                 width: 1,
                 height: 1,
+                depth_or_layers: 1,
+                dimension: TextureDimension::D2,
                 format: TextureFormat::Rgba8Unorm,
                 sampler: SamplerDesc {
                     address_u: AddressMode::Clamp,
@@ -858,6 +1002,7 @@ impl<B: GpuBackend> Renderer<B> {
                     compare: None,
                 },
                 generate_mipmaps: false,
+                mip_levels: 1,
             },
             &[128, 128, 255, 255],
         )?);
@@ -996,6 +1141,7 @@ impl<B: GpuBackend> Renderer<B> {
         root.add_child(node).expect("Unable to add test cube node");
 
         self.scene.set_root(root);
+        self.taa_history = false;
 
         // Ambient light
         self.scene.add_light(Light::default());
@@ -1007,14 +1153,7 @@ impl<B: GpuBackend> Renderer<B> {
             color: glm::vec3(23.47, 21.31, 20.79),
             radius: 1.0,
             penumbra_radius: 0.0,
-            light_proj: glm::ortho_zo(
-                -self.shadow_dist,
-                self.shadow_dist,
-                -self.shadow_dist,
-                self.shadow_dist,
-                1.0,
-                2.5 * self.shadow_dist,
-            ),
+            ..Light::default()
         });
 
         self.scene.build_matrices(&self.backend);
@@ -1023,8 +1162,15 @@ impl<B: GpuBackend> Renderer<B> {
         Ok(())
     }
 
+    /// Drop the scene and everything built from it. The GPU resources are
+    /// destroyed by the backend once no frame in flight uses them.
     pub fn unload_scene(&mut self) {
+        self.tlas = None;
+        self.blas.clear();
+        self.rt_material_buffer = None;
         let _ = self.scene.clear();
+        self.scene.clear_lights();
+        self.taa_history = false;
     }
 
     /// Main per-frame update. Call once per frame from the event loop.
@@ -1036,7 +1182,7 @@ impl<B: GpuBackend> Renderer<B> {
     ///   1. `update_state(dt, camera)` — update camera matrices and uniforms
     ///   2. `render_scene(camera)` — execute all render passes (no present)
     ///   3. `finish_frame()` — submit commands and present
-    /// Between steps 2 and 3, the editor can render its overlay (egui).
+    /// Before step 2, the editor hands its overlay (egui) over via `set_overlay()`.
     pub fn update(&mut self, dt: f32, camera: &mut dyn Camera) -> Result<(), GpuError> {
         self.backend.begin_frame()?;
         self.update_state(dt, camera);
@@ -1059,7 +1205,7 @@ impl<B: GpuBackend> Renderer<B> {
     }
 
     /// Step 3: Submit GPU commands and present the frame.
-    /// Call after render_scene() and any overlay rendering (e.g., egui).
+    /// Call after render_scene().
     pub fn finish_frame(&mut self) -> Result<(), GpuError> {
         self.backend.end_frame()
     }
@@ -1068,15 +1214,84 @@ impl<B: GpuBackend> Renderer<B> {
         self.backend.present()
     }
 
+    /// Intermediate targets written by the passes of the current frame, keyed by name:
+    /// the G-buffer of the deferred pre-pass, the blurred SSAO (if enabled) and the
+    /// HDR light accumulation target.
+    pub fn pass_targets(&self) -> Vec<(&'static str, B::RenderTarget)> {
+        [
+            GBUFFER_POSITIONS,
+            GBUFFER_NORMAL_ROUGHNESS,
+            GBUFFER_ALBEDO_METALLIC,
+            GBUFFER_VELOCITY,
+            SSAO,
+            DEFERRED_LIGHT,
+        ]
+        .into_iter()
+        .filter_map(|name| Some((name, self.graph.target(name)?.clone())))
+        .collect()
+    }
+
+    /// Write the finished frame to `dir`: the backbuffer as sRGB PNG and,
+    /// if the deferred pipeline is active, the HDR light accumulation target as EXR.
+    /// Call between finish_frame() and present(). Returns the written files.
+    pub fn take_screenshot(&mut self, dir: &Path) -> Result<Vec<PathBuf>, GpuError> {
+        std::fs::create_dir_all(dir).map_err(|e| {
+            GpuError::new(
+                format!(
+                    "Failed to create screenshot directory {}: {e}",
+                    dir.display()
+                ),
+                GpuErrorKind::Other,
+            )
+        })?;
+        let mut written = Vec::new();
+
+        let backbuffer = self.backend.backbuffer();
+        let readback = self.backend.read_render_target(&backbuffer)?;
+        let path = screenshot::timestamped_path(dir, "screenshot", "png");
+        screenshot::save_png(&readback, &path)?;
+        written.push(path);
+
+        if let Some(target) = self.graph.target(DEFERRED_LIGHT).cloned() {
+            let readback = self.backend.read_render_target(&target)?;
+            let path = screenshot::timestamped_path(dir, "screenshot_hdr", "exr");
+            screenshot::save_exr(&readback, &path)?;
+            written.push(path);
+        }
+
+        Ok(written)
+    }
+
     /// Upload current camera state to all pass uniform buffers.
     fn update_camera_uniforms(&mut self, camera: &dyn Camera) {
         let view = camera.view_mat();
         let pos = camera.position();
-        let ssao_enabled = self.settings.ssao;
+        let ssao_enabled = self.settings.ssao.enabled;
+        let taa_enabled = self.settings.taa;
+        let (width, height) = self.backend.resolution();
+
+        // Sub-pixel jitter of the projection, so consecutive frames sample different
+        // points of each pixel for the TAA resolve to accumulate
+        let unjittered = camera.projection_mat();
+        let proj = if taa_enabled {
+            let sample = self.taa_frame % TAA_JITTER_SAMPLES + 1;
+            self.taa_frame = self.taa_frame.wrapping_add(1);
+            let jitter = glm::vec3(
+                (halton(sample, 2) - 0.5) * 2.0 / width as f32,
+                (halton(sample, 3) - 0.5) * 2.0 / height as f32,
+                0.0,
+            );
+            glm::translation(&jitter) * unjittered
+        } else {
+            unjittered
+        };
+        let view_proj = unjittered * view;
 
-        // Update shared UBOs (bindings 0, 1, 4)
+        // Update shared UBOs (bindings 0, 1, 2, 4, 19, 20)
         self.view_proj_cpu.view = view;
         self.view_proj_cpu.inv_view = glm::inverse(&view);
+        self.view_proj_cpu.proj = proj;
+        self.view_proj_cpu.inv_proj = glm::inverse(&proj);
         self.backend.update_buffer(
             &self.ubo_view_proj,
             as_bytes(std::slice::from_ref(&self.view_proj_cpu)),
@@ -1084,6 +1299,7 @@ impl<B: GpuBackend> Renderer<B> {
 
         self.camera_pixel_cpu.camera_pos = pos;
         self.camera_pixel_cpu.ssao = ssao_enabled as u32;
+        self.camera_pixel_cpu.taa = taa_enabled as u32;
         self.backend.update_buffer(
             &self.ubo_camera_pixel,
             as_bytes(std::slice::from_ref(&self.camera_pixel_cpu)),
@@ -1094,374 +1310,408 @@ impl<B: GpuBackend> Renderer<B> {
             &self.ubo_skybox_view_proj,
             as_bytes(std::slice::from_ref(&self.skybox_view_proj_cpu)), // This is fine, outside the loop
         );
-    }
-
-    /// Execute the full rendering pipeline for one frame.
-    fn render(&mut self, camera: &dyn Camera) -> Result<(), GpuError> {
-        let depth = self.backend.main_depth_target().clone();
-        let viewport = self.backend.default_viewport();
 
-        // Deferred pre-pass (opaque objects -> G-buffer)
-        if let Some(ref deferred_pre) = self.deferred_program_pre {
-            let positions = deferred_pre.positions().clone();
-            let normal_roughness = deferred_pre.normal_roughness().clone();
-            let albedo_metallic = deferred_pre.albedo_metallic().clone();
+        let (near, far) = camera.near_far();
+        let clusters = ClusterUniforms::new(
+            view,
+            &camera.projection_mat(),
+            self.backend.resolution(),
+            near,
+            far,
+            self.scene.get_lights().len(),
+        );
+        self.backend.update_buffer(
+            &self.ubo_clusters,
+            as_bytes(std::slice::from_ref(&clusters)),
+        );
 
-            self.backend.begin_event("Deferred Pre Pass");
-            self.backend
-                .bind_uniform(ShaderStage::Vertex, 0, &self.ubo_view_proj);
+        if ssao_enabled {
+            let ssao =
+                SsaoUniforms::new(view, proj, self.backend.resolution(), &self.settings.ssao);
             self.backend
-                .bind_uniform(ShaderStage::Fragment, 0, &self.ubo_near_far);
-            self.backend.begin_render_pass(&RenderPassDesc {
-                label: "deferred_pre",
-                color_targets: vec![
-                    ColorAttachment {
-                        target: &positions,
-                        load_op: LoadOp::Clear,
-                        clear_color: [0.0, 0.0, 0.0, 0.0],
-                    },
-                    ColorAttachment {
-                        target: &normal_roughness,
-                        load_op: LoadOp::Clear,
-                        clear_color: [0.0, 0.0, 0.0, 0.0],
-                    },
-                    ColorAttachment {
-                        target: &albedo_metallic,
-                        load_op: LoadOp::Clear,
-                        clear_color: [0.0, 0.0, 0.0, 0.0],
-                    },
-                ],
-                depth_target: Some(DepthAttachment {
-                    target: &depth,
-                    load_op: LoadOp::Clear,
-                    clear_depth: 1.0,
-                    write_enabled: true,
-                }),
-            });
-            self.backend.set_viewport(&viewport);
-            deferred_pre.prepare_draw(&mut self.backend);
-            // Inline draw loop with per-drawable pipeline switching for double-sided materials
-            if let Ok(drawables) = self.scene.traverse() {
-                let mut last_ds: Option<bool> = None;
-                for drawable in drawables {
-                    if drawable.object_type() != ObjType::Opaque {
-                        continue;
-                    }
-                    let ds = drawable.is_double_sided();
-                    if last_ds != Some(ds) {
-                        deferred_pre.set_pipeline_for(&mut self.backend, ds);
-                        last_ds = Some(ds);
-                    }
-                    drawable.draw(&mut self.backend, true);
-                }
-            }
-            self.backend.end_render_pass();
-            self.backend.end_event();
+                .update_buffer(&self.ubo_ssao, as_bytes(std::slice::from_ref(&ssao)));
         }
 
-        // Process lights individually to correctly synchronize shadow mapping and accumulation
-        let lights = self.scene.get_lights().clone();
-        let mut first_light = true;
-
-        // RT shadow: dispatch once before the light loop for all non-ambient lights
-        let use_rt = self.use_ray_tracing
-            && self.rt_pipeline.is_some()
-            && self.tlas.is_some()
-            && self.rt_output.is_some()
-            && self.rt_light_buffer.is_some()
-            && self.rt_material_buffer.is_some();
-        if use_rt {
-            let rt_lights: Vec<GpuLight> = lights
-                .iter()
-                .filter(|l| l.t != LightType::Ambient)
-                .map(|l| GpuLight::from_light(l))
-                .collect();
-            let num_rt_lights = rt_lights.len() as u32;
-            if num_rt_lights > 0 {
-                self.backend.cmd_update_buffer(
-                    self.rt_light_buffer.as_ref().unwrap(),
-                    as_bytes(&rt_lights),
-                );
-                self.backend.dispatch_rays(
-                    self.rt_pipeline.as_ref().unwrap(),
-                    self.tlas.as_ref().unwrap(),
-                    self.rt_output.as_ref().unwrap(),
-                    self.rt_light_buffer.as_ref().unwrap(),
-                    self.rt_material_buffer.as_ref().unwrap(),
-                    viewport.width as u32,
-                    viewport.height as u32,
-                    num_rt_lights,
-                );
-            }
+        // A jump of the camera is a cut: the history shows a different view
+        let forward = -glm::vec4_to_vec3(&self.view_proj_cpu.inv_view.column(2).into_owned());
+        if let Some((prev_pos, prev_forward)) = self.prev_camera
+            && (glm::distance(&pos, &prev_pos) > TAA_CUT_DISTANCE
+                || glm::angle(&forward, &prev_forward) > TAA_CUT_ANGLE)
+        {
+            self.taa_history = false;
         }
+        self.prev_camera = Some((pos, forward));
 
-        for mut light in lights {
-            if light.t != LightType::Ambient {
-                let dir = light.position * (-1.0) * self.shadow_dist;
-                // Use Z-up if light direction is Y-dominant to avoid degenerate look_at.
-                // Threshold 0.99 catches near-parallel (dot > 0.99) and anti-parallel.
-                let dir_norm = dir.normalize();
-                let up = if dir_norm.y.abs() > 0.99 {
-                    glm::vec3(0.0, 0.0, 1.0)
-                } else {
-                    glm::vec3(0.0, 1.0, 0.0)
-                };
-                let focus = camera.focus();
-                let light_view = glm::look_at(&(focus + dir), &focus, &up);
-                // Snap to shadow map texel grid — prevents shadow swimming as camera moves.
-                // Snap in light-VIEW space (world units) so texel_size matches coordinate scale.
-                // rem_euclid avoids sign issues with negative coordinates.
-                let texel_size = (2.0 * self.shadow_dist) / SHADOW_MAP_SIZE as f32;
-                let focus_lv = light_view * glm::vec4(focus.x, focus.y, focus.z, 1.0);
-                let snap_x = focus_lv.x.rem_euclid(texel_size);
-                let snap_y = focus_lv.y.rem_euclid(texel_size);
-                let snap_mat = glm::translation(&glm::vec3(-snap_x, -snap_y, 0.0));
-                light.light_proj = light.light_proj * snap_mat * light_view;
-            }
-
-            self.backend.cmd_update_buffer(
-                &self.ubo_light_data,
-                as_bytes(std::slice::from_ref(&GpuLight::from_light(&light))),
-            );
-
-            // 1. Shadow Mapping for this light (skip when RT shadows are active)
-            if !use_rt {
-                if let Some(ref mut shadow) = self.shadow_program {
-                    if light.t != LightType::Ambient {
-                        self.backend.cmd_update_buffer(
-                            &self.ubo_shadow_light_space,
-                            as_bytes(std::slice::from_ref(&LightSpaceUniforms {
-                                light_space_matrix: light.light_proj,
-                            })),
-                        );
-
-                        self.backend.bind_uniform(
-                            ShaderStage::Vertex,
-                            0,
-                            &self.ubo_shadow_light_space,
-                        );
-                        self.backend.begin_event("Shadow Mapping");
-                        self.backend.begin_render_pass(&RenderPassDesc {
-                            label: "shadow",
-                            color_targets: vec![],
-                            depth_target: Some(DepthAttachment {
-                                target: shadow.shadow_map(),
-                                load_op: LoadOp::Clear, // Always clear for the specific light's map
-                                clear_depth: 1.0,
-                                write_enabled: true,
-                            }),
-                        });
-                        self.backend.set_viewport(shadow.viewport());
-                        shadow.prepare_draw(&mut self.backend);
-                        if let Ok(drawables) = self.scene.traverse() {
-                            let mut last_ds: Option<bool> = None;
-                            for drawable in drawables {
-                                let ds = drawable.is_double_sided();
-                                if last_ds != Some(ds) {
-                                    shadow.set_pipeline_for(&mut self.backend, ds);
-                                    last_ds = Some(ds);
-                                }
-                                drawable.draw(&mut self.backend, false);
-                            }
-                        }
-                        self.backend.end_render_pass();
-                        self.backend.end_event();
-                    }
-                }
-            }
+        if !taa_enabled {
+            // The history stops updating while TAA is off
+            self.taa_history = false;
+            return;
+        }
+        let taa = TaaUniforms {
+            view_proj,
+            prev_view_proj: if self.taa_history {
+                self.prev_view_proj
+            } else {
+                view_proj
+            },
+            feedback: TAA_FEEDBACK,
+            history_valid: self.taa_history as u32,
+            _pad: Pad::ZERO,
+        };
+        self.backend
+            .update_buffer(&self.ubo_taa, as_bytes(std::slice::from_ref(&taa)));
+        self.prev_view_proj = view_proj;
+        self.taa_history = true;
+    }
 
-            // Bind shared inputs for lighting and transparency
-            if !use_rt {
-                if let Some(ref sp) = self.shadow_program {
-                    self.backend
-                        .bind_render_target_as_texture(3, sp.shadow_map());
+    /// Fit the shadow projections of each light: one per cascade for directional
+    /// lights, one per cube face for point lights and the cone for spot lights.
+    /// Then hand out the shadow maps, see [`assign_shadow_maps`].
+    fn frame_lights(&mut self, camera: &dyn Camera) -> Vec<Light> {
+        let mut lights = self.scene.get_lights().clone();
+        for light in lights.iter_mut().filter(|l| l.t != LightType::Ambient) {
+            match light.t {
+                LightType::Point => {
+                    let views = cube_face_views(&light.position, light.range);
+                    light.shadow_views[..6].copy_from_slice(&views);
+                    light.shadow_view_count = 6;
+                    continue;
                 }
-            }
-            if use_rt {
-                if let Some(ref rt_out) = self.rt_output {
-                    self.backend.bind_render_target_as_texture(4, rt_out);
+                LightType::Spot => {
+                    let forward = light.direction.normalize();
+                    // The cone must stay below 180° for a perspective projection
+                    let fov = (2.0 * light.outer_angle).clamp(0.01, 3.0);
+                    let proj = glm::perspective_zo(1.0, fov, SHADOW_NEAR_PLANE, light.range);
+                    light.shadow_views[0] = proj
+                        * glm::look_at(
+                            &light.position,
+                            &(light.position + forward),
+                            &shadow_up(&forward),
+                        );
+                    light.shadow_view_count = 1;
+                    continue;
                 }
+                _ => {}
             }
-            if let Some(ref dp) = self.deferred_program_pre {
-                self.backend
-                    .bind_render_target_as_texture(0, dp.positions());
-                self.backend
-                    .bind_render_target_as_texture(1, dp.normal_roughness());
-                self.backend
-                    .bind_render_target_as_texture(2, dp.albedo_metallic());
-            }
-
-            // 2. Deferred Lighting accumulation
-            if let Some(ref mut def_light) = self.deferred_program_light {
-                let def_light_rt = def_light.render_target().clone();
-
-                // Bind shared inputs (UBOs)
-                self.backend
-                    .bind_uniform(ShaderStage::Fragment, 0, &self.ubo_camera_pixel);
-                self.backend
-                    .bind_uniform(ShaderStage::Fragment, 1, &self.ubo_light_data);
-
-                self.backend.begin_event("Deferred Light Pass");
-                self.backend.begin_render_pass(&RenderPassDesc {
-                    label: "deferred_light",
-                    color_targets: vec![ColorAttachment {
-                        target: &def_light_rt,
-                        load_op: if first_light {
-                            LoadOp::Clear
-                        } else {
-                            LoadOp::Load
-                        },
-                        clear_color: [0.0, 0.0, 0.0, 0.0],
-                    }],
-                    depth_target: None,
-                });
-                self.backend.set_viewport(&viewport);
-                def_light.prepare_draw(&mut self.backend);
-                self.screen_quad.draw(&mut self.backend);
-                self.backend.end_render_pass();
-                self.backend.end_event();
-            }
-
-            // 3. Forward accumulation
-            if let Some(ref mut fwd) = self.forward_program {
-                let fwd_rt = fwd.render_target().clone();
-
-                self.backend
-                    .bind_uniform(ShaderStage::Vertex, 0, &self.ubo_view_proj);
-                self.backend
-                    .bind_uniform(ShaderStage::Fragment, 0, &self.ubo_camera_pixel);
-                self.backend
-                    .bind_uniform(ShaderStage::Fragment, 1, &self.ubo_light_data);
-
-                self.backend.begin_event("Forward Pass");
-                self.backend.begin_render_pass(&RenderPassDesc {
-                    label: "forward",
-                    color_targets: vec![ColorAttachment {
-                        target: &fwd_rt,
-                        load_op: if first_light {
-                            LoadOp::Clear
-                        } else {
-                            LoadOp::Load
-                        },
-                        clear_color: [0.0, 0.0, 0.0, 0.0],
-                    }],
-                    depth_target: Some(DepthAttachment {
-                        target: &depth,
-                        load_op: LoadOp::Load,
-                        clear_depth: 1.0,
-                        write_enabled: true,
-                    }),
-                });
-                self.backend.set_viewport(&viewport);
-                fwd.prepare_draw(&mut self.backend);
-
-                if let Ok(drawables) = self.scene.traverse() {
-                    let mut last_ds: Option<bool> = None;
-                    for drawable in drawables {
-                        if drawable.object_type() != ObjType::Transparent {
-                            continue;
-                        }
-                        let ds = drawable.is_double_sided();
-                        if last_ds != Some(ds) {
-                            fwd.set_pipeline_for(&mut self.backend, ds);
-                            last_ds = Some(ds);
-                        }
-                        drawable.draw(&mut self.backend, true);
-                    }
+            let dir = light.position * (-1.0) * self.settings.shadow_distance;
+            let dir_norm = dir.normalize();
+            let up = shadow_up(&dir_norm);
+            if light.t == LightType::Directional {
+                // Rotation only, so the cascades snap to a texel grid fixed in the world
+                let light_view = glm::look_at(&glm::zero(), &(-dir_norm), &up);
+                let (near, far) = camera.near_far();
+                let far = far.min(self.settings.shadow_distance);
+                let count = (self.settings.shadow_cascades as usize).clamp(1, MAX_SHADOW_CASCADES);
+                let splits = cascade_splits(near, far, count, self.settings.shadow_split_lambda);
+                let mut slice_near = near;
+                for (cascade, &slice_far) in light.shadow_views.iter_mut().zip(&splits) {
+                    *cascade = cascade_light_space(
+                        camera,
+                        &light_view,
+                        slice_near,
+                        slice_far,
+                        self.settings.shadow_distance,
+                    );
+                    slice_near = slice_far;
                 }
-                self.backend.end_render_pass();
-                self.backend.end_event();
+                light.shadow_view_count = count as u32;
+                continue;
             }
-
-            first_light = false;
+            let focus = camera.focus();
+            let light_view = glm::look_at(&(focus + dir), &focus, &up);
+            // Snap to shadow map texel grid — prevents shadow swimming as camera moves.
+            // Snap in light-VIEW space (world units) so texel_size matches coordinate scale.
+            // rem_euclid avoids sign issues with negative coordinates.
+            let dist = self.settings.shadow_distance;
+            let texel_size = (2.0 * dist) / SHADOW_TILE_SIZE as f32;
+            let focus_lv = light_view * glm::vec4(focus.x, focus.y, focus.z, 1.0);
+            let snap_x = focus_lv.x.rem_euclid(texel_size);
+            let snap_y = focus_lv.y.rem_euclid(texel_size);
+            let snap_mat = glm::translation(&glm::vec3(-snap_x, -snap_y, 0.0));
+            light.light_proj =
+                glm::ortho_zo(-dist, dist, -dist, dist, 1.0, 2.5 * dist) * snap_mat * light_view;
+            light.shadow_views[0] = light.light_proj;
+            light.shadow_view_count = 1;
         }
 
-        let backbuffer = self.backend.backbuffer().clone();
-
-        if let Some(ref dl) = self.deferred_program_light {
-            self.backend
-                .bind_render_target_as_texture(0, dl.render_target());
-        }
-        if let Some(ref fwd) = self.forward_program {
-            self.backend
-                .bind_render_target_as_texture(1, fwd.render_target());
+        let unshadowed = assign_shadow_maps(&mut lights);
+        if unshadowed != self.unshadowed_lights {
+            if !unshadowed.is_empty() {
+                eprintln!("Shadow maps full, lights {unshadowed:?} cast no shadows");
+            }
+            self.unshadowed_lights = unshadowed;
         }
-        // Output composite (deferred + forward -> backbuffer)
-        if let Some(ref output) = self.output_program {
-            self.backend.begin_event("Output Composite");
-            self.backend.begin_render_pass(&RenderPassDesc {
-                label: "output",
-                color_targets: vec![ColorAttachment {
-                    target: &backbuffer,
-                    load_op: LoadOp::Clear,
-                    clear_color: [0.05, 0.05, 0.05, 1.0],
-                }],
-                depth_target: None,
-            });
-            self.backend.set_viewport(&viewport);
-            output.prepare_draw(&mut self.backend);
+        lights
+    }
 
-            if let Some(ref dl) = self.deferred_program_light {
-                self.backend
-                    .bind_render_target_as_texture(0, dl.render_target());
-            }
-            if let Some(ref fwd) = self.forward_program {
-                self.backend
-                    .bind_render_target_as_texture(1, fwd.render_target());
-            }
+    /// Execute the full rendering pipeline for one frame.
+    fn render(&mut self, camera: &dyn Camera) -> Result<(), GpuError> {
+        let overlay = self.overlay.take();
 
-            self.screen_quad.draw(&mut self.backend);
-            self.backend.end_render_pass();
-            self.backend.end_event();
-        } else {
+        if self.graph.is_empty() {
             // Fallback: clear backbuffer only (MVP path, no shaders loaded)
+            let backbuffer = self.backend.backbuffer();
+            let depth = self.backend.main_depth_target();
             self.backend.begin_render_pass(&RenderPassDesc {
                 label: "clear",
                 color_targets: vec![ColorAttachment {
                     target: &backbuffer,
                     load_op: LoadOp::Clear,
                     clear_color: [0.05, 0.05, 0.05, 1.0],
+                    resolve_target: None,
                 }],
                 depth_target: Some(DepthAttachment {
                     target: &depth,
                     load_op: LoadOp::Clear,
                     clear_depth: 1.0,
                     write_enabled: true,
+                    stencil_load_op: LoadOp::Clear,
+                    clear_stencil: 0,
+                    resolve_target: None,
+                    layer: 0,
                 }),
             });
             self.backend.end_render_pass();
+            if let Some(overlay) = overlay {
+                self.backend.render_egui(
+                    &overlay.textures_delta,
+                    &overlay.primitives,
+                    overlay.pixels_per_point,
+                );
+            }
+            return Ok(());
         }
 
-        // Skybox
-        if let Some(ref skybox_prog) = self.skybox_program {
-            if let Some(ref skybox) = self.skybox {
-                self.backend.begin_event("Skybox");
-                // self.backend
-                //     .bind_uniform(ShaderStage::Vertex, 0, &skybox_prog.vertex_uniforms_buf);
-                self.backend.begin_render_pass(&RenderPassDesc {
-                    label: "skybox",
-                    color_targets: vec![ColorAttachment {
-                        target: &backbuffer,
-                        load_op: LoadOp::Load,
-                        clear_color: [0.0, 0.0, 0.0, 0.0],
-                    }],
-                    depth_target: Some(DepthAttachment {
-                        target: &depth,
-                        load_op: LoadOp::Load,
-                        clear_depth: 1.0,
-                        write_enabled: false,
-                    }),
-                });
-                skybox_prog.prepare_draw(&mut self.backend);
-                skybox.draw(&mut self.backend);
-                self.backend.end_render_pass();
-                self.backend.end_event();
+        let lights = self.frame_lights(camera);
+        // RT shadows replace the shadow maps once the scene has a TLAS
+        let rt = match (&self.tlas, &self.rt_material_buffer) {
+            (Some(tlas), Some(material_buffer))
+                if self.use_ray_tracing && self.graph.has_pass(RT_SHADOW_PASS) =>
+            {
+                Some(RtScene {
+                    tlas,
+                    material_buffer,
+                })
             }
-        }
+            _ => None,
+        };
+        self.graph.set_enabled(RT_SHADOW_PASS, rt.is_some());
+        self.graph.set_enabled(SHADOW_PASS, rt.is_none());
+        self.graph
+            .set_enabled(SSAO_PASS, self.settings.ssao.enabled);
+        self.graph
+            .set_enabled(SSAO_BLUR_PASS, self.settings.ssao.enabled);
+        self.graph.set_enabled(TAA_PASS, self.settings.taa);
+
+        let frame = FrameInputs {
+            scene: &self.scene,
+            screen_quad: &self.screen_quad,
+            skybox: self.skybox.as_ref(),
+            lights: &lights,
+            light_buffer: &self.light_buffer,
+            light_space_buffer: &self.ubo_shadow_light_space,
+            rt,
+            overlay: overlay.as_ref(),
+        };
+        self.graph.execute(&mut self.backend, &frame)?;
+        // The next frame's motion vectors start from the matrices drawn with now
+        self.scene.retire_motion();
+        Ok(())
 
         // Note: end_frame() and present() are NOT called here.
-        // Use finish_frame() after any overlay rendering (e.g., egui).
+        // Use finish_frame() after rendering.
+    }
+}
 
-        Ok(())
+/// Smallest half-size of the skybox cube, for scenes without static meshes.
+const MIN_SKY_SCALE: f32 = 50.0;
+
+/// Near plane of the perspective shadow maps of point and spot lights.
+const SHADOW_NEAR_PLANE: f32 = 0.05;
+
+/// Length of the Halton sequence the TAA projection jitter cycles through.
+const TAA_JITTER_SAMPLES: u32 = 8;
+/// Camera movement in one frame (world units) treated as a cut.
+const TAA_CUT_DISTANCE: f32 = 2.0;
+/// Camera rotation in one frame (radians) treated as a cut.
+const TAA_CUT_ANGLE: f32 = 0.5;
+
+/// Element `index` (starting at 1) of the Halton sequence in `base`, in [0, 1).
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut f = 1.0;
+    let mut r = 0.0;
+    while index > 0 {
+        f /= base as f32;
+        r += f * (index % base) as f32;
+        index /= base;
+    }
+    r
+}
+
+/// Up vector for a shadow view looking along `forward`: Z-up if the direction is
+/// Y-dominant to avoid a degenerate look_at. Threshold 0.99 catches near-parallel
+/// (dot > 0.99) and anti-parallel.
+fn shadow_up(forward: &glm::Vec3) -> glm::Vec3 {
+    if forward.y.abs() > 0.99 {
+        glm::vec3(0.0, 0.0, 1.0)
+    } else {
+        glm::vec3(0.0, 1.0, 0.0)
+    }
+}
+
+/// Light-space matrices of the cube map faces of a point light's shadow map, in
+/// the order of the layers and of the shaders' face selection: +X, -X, +Y, -Y,
+/// +Z, -Z. Each looks along its axis with the top of the face up, mirrored
+/// horizontally as cube map faces are seen from the inside.
+pub(crate) fn cube_face_views(position: &glm::Vec3, range: f32) -> [glm::Mat4; 6] {
+    let faces = [
+        (glm::vec3(1.0, 0.0, 0.0), glm::vec3(0.0, 1.0, 0.0)),
+        (glm::vec3(-1.0, 0.0, 0.0), glm::vec3(0.0, 1.0, 0.0)),
+        (glm::vec3(0.0, 1.0, 0.0), glm::vec3(0.0, 0.0, -1.0)),
+        (glm::vec3(0.0, -1.0, 0.0), glm::vec3(0.0, 0.0, 1.0)),
+        (glm::vec3(0.0, 0.0, 1.0), glm::vec3(0.0, 1.0, 0.0)),
+        (glm::vec3(0.0, 0.0, -1.0), glm::vec3(0.0, 1.0, 0.0)),
+    ];
+    let proj = glm::scaling(&glm::vec3(-1.0, 1.0, 1.0))
+        * glm::perspective_zo(1.0, std::f32::consts::FRAC_PI_2, SHADOW_NEAR_PLANE, range);
+    faces.map(|(forward, up)| proj * glm::look_at(position, &(position + forward), &up))
+}
+
+/// Hand out the shadow maps in light order: a cube of the shadow cube map array
+/// to each point light, consecutive shadow atlas tiles for the views of the
+/// other lights. Returns the indices of the lights left without, once the cubes
+/// or the tiles run out.
+fn assign_shadow_maps(lights: &mut [Light]) -> Vec<usize> {
+    let (mut next_cube, mut next_tile) = (0, 0);
+    let mut unshadowed = Vec::new();
+    for (i, light) in lights.iter_mut().enumerate().take(MAX_LIGHTS) {
+        if light.t == LightType::Ambient {
+            continue;
+        }
+        if light.t == LightType::Point {
+            if next_cube < MAX_SHADOW_CUBES {
+                light.shadow_cube = Some(next_cube);
+                next_cube += 1;
+                continue;
+            }
+        } else if next_tile + light.shadow_view_count <= SHADOW_ATLAS_TILES {
+            light.shadow_tile = Some(next_tile);
+            next_tile += light.shadow_view_count;
+            continue;
+        }
+        unshadowed.push(i);
+    }
+    unshadowed
+}
+
+/// Far distances of the shadow cascades: the "practical" split scheme, blending
+/// logarithmic and uniform splits of `[near, far]` by `lambda`.
+fn cascade_splits(near: f32, far: f32, count: usize, lambda: f32) -> Vec<f32> {
+    (1..=count)
+        .map(|i| {
+            let t = i as f32 / count as f32;
+            let log = near * (far / near).powf(t);
+            let uniform = near + (far - near) * t;
+            lambda * log + (1.0 - lambda) * uniform
+        })
+        .collect()
+}
+
+/// World to light clip space of the shadow cascade covering the view frustum
+/// slice `[near, far]`.
+///
+/// The cascade bounds the slice's bounding sphere: its radius only depends on the
+/// projection, so the cascade keeps its size (and texel size) while the camera
+/// rotates. The center is snapped to the texel grid of the rotation-only
+/// `light_view` so the shadows don't swim while the camera moves. The depth range
+/// reaches `caster_dist` further towards the light, for casters outside the sphere.
+fn cascade_light_space(
+    camera: &dyn Camera,
+    light_view: &glm::Mat4,
+    near: f32,
+    far: f32,
+    caster_dist: f32,
+) -> glm::Mat4 {
+    let proj = camera.projection_mat();
+    // Squared slope of the frustum's corner rays
+    let k2 = (1.0 / proj[(0, 0)]).powi(2) + (1.0 / proj[(1, 1)]).powi(2);
+    // Smallest sphere centered on the view axis through the near and far corners
+    let center_dist = (0.5 * (near + far) * (1.0 + k2)).min(far);
+    let radius = ((far - center_dist).powi(2) + far * far * k2).sqrt();
+    // Quantized so rounding noise can't change the texel size from frame to frame
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    let inv_view = glm::inverse(&camera.view_mat());
+    let forward = -inv_view.column(2).xyz();
+    let center = inv_view.column(3).xyz() + forward * center_dist;
+
+    let texel_size = (2.0 * radius) / SHADOW_TILE_SIZE as f32;
+    let center_lv = light_view * glm::vec4(center.x, center.y, center.z, 1.0);
+    let x = center_lv.x - center_lv.x.rem_euclid(texel_size);
+    let y = center_lv.y - center_lv.y.rem_euclid(texel_size);
+    // Light-view space looks down -Z
+    glm::ortho_zo(
+        x - radius,
+        x + radius,
+        y - radius,
+        y + radius,
+        -center_lv.z - radius - caster_dist,
+        -center_lv.z + radius,
+    ) * light_view
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cascade_splits_are_monotonic_and_end_at_the_shadow_distance() {
+        let shadow_distance = Settings::default().shadow_distance;
+        for count in 1..=MAX_SHADOW_CASCADES {
+            for lambda in [0.0, 0.5, 0.75, 1.0] {
+                let splits = cascade_splits(0.1, shadow_distance, count, lambda);
+                assert_eq!(splits.len(), count);
+                assert!(splits.windows(2).all(|w| w[0] < w[1]), "{splits:?}");
+                assert!(splits[0] > 0.1);
+                let last = splits[count - 1];
+                assert!((last - shadow_distance).abs() < 1e-3, "{splits:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn point_lights_get_cubes_and_the_others_atlas_tiles() {
+        let light = |t, shadow_view_count| Light {
+            t,
+            shadow_view_count,
+            ..Default::default()
+        };
+        let mut lights = vec![
+            light(LightType::Ambient, 1),
+            light(LightType::Directional, 4),
+        ];
+        lights.extend((0..MAX_SHADOW_CUBES + 1).map(|_| light(LightType::Point, 6)));
+        lights.extend((0..SHADOW_ATLAS_TILES - 4 + 1).map(|_| light(LightType::Spot, 1)));
+
+        let unshadowed = assign_shadow_maps(&mut lights);
+        let cubes = lights
+            .iter()
+            .filter_map(|l| l.shadow_cube)
+            .collect::<Vec<_>>();
+        assert_eq!(cubes, (0..MAX_SHADOW_CUBES).collect::<Vec<_>>());
+        assert!(
+            lights
+                .iter()
+                .all(|l| l.t == LightType::Point || l.shadow_cube.is_none())
+        );
+        assert_eq!(lights[0].shadow_tile, None);
+        assert_eq!(lights[1].shadow_tile, Some(0));
+        assert_eq!(
+            lights[2 + MAX_SHADOW_CUBES as usize + 1].shadow_tile,
+            Some(4)
+        );
+        // the cubes and the tiles ran out for the last point and spot light
+        let last_point = 2 + MAX_SHADOW_CUBES as usize;
+        assert_eq!(unshadowed, vec![last_point, lights.len() - 1]);
     }
 }
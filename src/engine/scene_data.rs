@@ -15,6 +15,34 @@ pub struct LightData {
     pub color: [f32; 3],
     pub radius: f32,
     pub penumbra_radius: f32,
+    // Point and spot light parameters, defaulted for scenes saved before they existed
+    #[serde(default = "default_direction")]
+    pub direction: [f32; 3],
+    #[serde(default = "default_range")]
+    pub range: f32,
+    /// Half angle of the fully lit inner cone, in radians.
+    #[serde(default = "default_inner_angle")]
+    pub inner_angle: f32,
+    /// Half angle of the outer cone, in radians.
+    #[serde(default = "default_outer_angle")]
+    pub outer_angle: f32,
+}
+
+fn default_direction() -> [f32; 3] {
+    let direction = Light::default().direction;
+    [direction.x, direction.y, direction.z]
+}
+
+fn default_range() -> f32 {
+    Light::default().range
+}
+
+fn default_inner_angle() -> f32 {
+    Light::default().inner_angle
+}
+
+fn default_outer_angle() -> f32 {
+    Light::default().outer_angle
 }
 
 /// Serializable light type enum.
@@ -23,6 +51,8 @@ pub enum LightTypeData {
     Ambient,
     Directional,
     Area,
+    Point,
+    Spot,
 }
 
 /// A serializable node transform override.
@@ -57,10 +87,16 @@ impl From<&Light> for LightData {
                 LightType::Ambient => LightTypeData::Ambient,
                 LightType::Directional => LightTypeData::Directional,
                 LightType::Area => LightTypeData::Area,
+                LightType::Point => LightTypeData::Point,
+                LightType::Spot => LightTypeData::Spot,
             },
             color: [light.color.x, light.color.y, light.color.z],
             radius: light.radius,
             penumbra_radius: light.penumbra_radius,
+            direction: [light.direction.x, light.direction.y, light.direction.z],
+            range: light.range,
+            inner_angle: light.inner_angle,
+            outer_angle: light.outer_angle,
         }
     }
 }
@@ -68,8 +104,8 @@ impl From<&Light> for LightData {
 impl LightData {
     /// Convert back to an engine Light.
     ///
-    /// Note: `light_proj` is set to identity — the renderer recomputes it
-    /// each frame for directional lights.
+    /// Note: `light_proj` and the shadow views keep their defaults — the
+    /// renderer recomputes them each frame.
     pub fn to_light(&self) -> Light {
         Light {
             position: glm::vec3(self.position[0], self.position[1], self.position[2]),
@@ -77,11 +113,17 @@ impl LightData {
                 LightTypeData::Ambient => LightType::Ambient,
                 LightTypeData::Directional => LightType::Directional,
                 LightTypeData::Area => LightType::Area,
+                LightTypeData::Point => LightType::Point,
+                LightTypeData::Spot => LightType::Spot,
             },
             color: glm::vec3(self.color[0], self.color[1], self.color[2]),
             radius: self.radius,
             penumbra_radius: self.penumbra_radius,
-            light_proj: glm::identity(),
+            direction: glm::vec3(self.direction[0], self.direction[1], self.direction[2]),
+            range: self.range,
+            inner_angle: self.inner_angle,
+            outer_angle: self.outer_angle,
+            ..Light::default()
         }
     }
 }
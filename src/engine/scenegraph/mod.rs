@@ -11,6 +11,10 @@ pub struct Scenegraph<B: GpuBackend> {
     transform: glm::Mat4,
     lights: Vec<Light>,
     root: Option<Node<B>>,
+    /// Model matrices changed since the last rendered frame.
+    moved: bool,
+    /// A frame was rendered since the root was set.
+    drawn: bool,
 }
 
 impl<B: GpuBackend> Scenegraph<B> {
@@ -19,11 +23,14 @@ impl<B: GpuBackend> Scenegraph<B> {
             transform: glm::identity(),
             root: None,
             lights: Vec::<Light>::new(),
+            moved: false,
+            drawn: false,
         }
     }
 
     pub fn set_root(&mut self, node: Node<B>) {
-        self.root = Some(node)
+        self.root = Some(node);
+        self.drawn = false;
     }
 
     pub fn add_light(&mut self, light: Light) {
@@ -67,6 +74,24 @@ impl<B: GpuBackend> Scenegraph<B> {
     pub fn build_matrices(&mut self, backend: &B) {
         if let Some(root) = &mut self.root {
             root.build_model(backend, &self.transform);
+            // Nothing was drawn before the first matrices: they didn't move
+            if !self.drawn {
+                root.retire_model();
+                return;
+            }
+        }
+        self.moved = true;
+    }
+
+    /// Call after rendering a frame: the model matrices it was drawn with become
+    /// the previous ones, so objects that stopped moving have no motion left.
+    pub fn retire_motion(&mut self) {
+        self.drawn = true;
+        if !std::mem::take(&mut self.moved) {
+            return;
+        }
+        if let Some(root) = &mut self.root {
+            root.retire_model();
         }
     }
 
@@ -107,7 +132,7 @@ impl<B: GpuBackend> Scenegraph<B> {
             return Ok(root);
         }
 
-        todo!()
+        root.get_named_mut(name)
     }
 
     pub fn traverse(&self) -> Result<Vec<RenderItem<'_, B>>, SceneGraphError> {
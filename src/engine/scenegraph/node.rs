@@ -18,7 +18,7 @@ pub struct Node<B: GpuBackend> {
 pub enum NodeData<B: GpuBackend> {
     StandardMesh(Vec<Drawable<B>>),
     ProceduralWorld {
-        terrain: Drawable<B>,
+        terrain: Box<Drawable<B>>,
         instanced_assets: Vec<IndirectDrawable<B>>,
         heightmap: Rc<B::Texture>,
     },
@@ -50,6 +50,15 @@ impl<B: GpuBackend> NodeData<B> {
             }
         }
     }
+
+    fn retire_model(&mut self) {
+        match self {
+            NodeData::StandardMesh(drawables) => {
+                drawables.iter_mut().for_each(|d| d.retire_model())
+            }
+            NodeData::ProceduralWorld { terrain, .. } => terrain.retire_model(),
+        }
+    }
 }
 
 impl<B: GpuBackend> Clone for NodeData<B> {
@@ -119,7 +128,7 @@ impl<B: GpuBackend> Node<B> {
             model: glm::Mat4::identity(),
             model_orig: glm::Mat4::identity(),
             data: NodeData::ProceduralWorld {
-                terrain,
+                terrain: Box::new(terrain),
                 instanced_assets,
                 heightmap,
             },
@@ -143,9 +152,8 @@ impl<B: GpuBackend> Node<B> {
             return Ok(c);
         }
         for (_, node) in &self.children {
-            match node.get_named(name) {
-                Ok(c) => return Ok(c),
-                Err(e) => return Err(e),
+            if let Ok(c) = node.get_named(name) {
+                return Ok(c);
             }
         }
         Err(SceneGraphError::new(name, &ErrorCause::NotFound))
@@ -159,9 +167,8 @@ impl<B: GpuBackend> Node<B> {
             return Ok(self.children.get_mut(name).unwrap());
         }
         for (_, node) in &mut self.children {
-            match node.get_named_mut(name) {
-                Ok(c) => return Ok(c),
-                Err(e) => return Err(e),
+            if let Ok(c) = node.get_named_mut(name) {
+                return Ok(c);
             }
         }
         Err(SceneGraphError::new(name, &ErrorCause::NotFound))
@@ -347,6 +354,14 @@ impl<B: GpuBackend> Node<B> {
             c.build_model(backend, &self.model);
         }
     }
+
+    /// Make the current model matrices of this subtree the previous frame's ones.
+    pub fn retire_model(&mut self) {
+        self.data.retire_model();
+        for c in self.children.values_mut() {
+            c.retire_model();
+        }
+    }
 }
 
 pub fn collect_drawables<B: GpuBackend>(node: &Node<B>, out: &mut Vec<Drawable<B>>) {
@@ -0,0 +1,190 @@
+//! Screenshot export for render target readbacks.
+//!
+//! Display-referred targets (the backbuffer) are written as 8-bit sRGB PNG,
+//! scene-referred HDR targets (`R16g16b16a16Float`) as 16-bit float OpenEXR.
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use exr::prelude::f16;
+
+use super::backend::{GpuError, GpuErrorKind, TextureFormat, TextureReadback};
+
+/// Must match `white_point` in blend.slang, used to undo the HDR10 encoding.
+const HDR10_WHITE_POINT: f32 = 350.0;
+const ST2084_MAX: f32 = 10000.0;
+
+/// Build a file path `<dir>/<prefix>_<unix millis>.<ext>`.
+pub fn timestamped_path(dir: &Path, prefix: &str, ext: &str) -> PathBuf {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    dir.join(format!("{prefix}_{millis}.{ext}"))
+}
+
+/// Convert a display-referred readback to 8-bit sRGB RGBA.
+///
+/// `Bgra8` is swizzled, `Abgr10Unorm` (HDR10 swapchain) is decoded from PQ,
+/// converted back to Rec.709 and tonemapped the same way as the SDR output pass.
+pub fn readback_to_rgba8(readback: &TextureReadback) -> Result<image::RgbaImage, GpuError> {
+    let pixels: Vec<u8> = match readback.format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => readback.data.clone(),
+        TextureFormat::R8Unorm => readback.data.iter().flat_map(|&r| [r, r, r, 255]).collect(),
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => readback
+            .data
+            .chunks_exact(4)
+            .flat_map(|p| [p[2], p[1], p[0], p[3]])
+            .collect(),
+        TextureFormat::Abgr10Unorm => readback
+            .data
+            .chunks_exact(4)
+            .flat_map(|p| hdr10_to_srgb8(u32::from_le_bytes([p[0], p[1], p[2], p[3]])))
+            .collect(),
+        TextureFormat::R16g16b16a16Float => readback
+            .data
+            .chunks_exact(8)
+            .flat_map(|p| {
+                let [r, g, b, a] = rgba16f(p);
+                let c = [r, g, b].map(|c| linear_to_srgb(aces(c)));
+                [c[0], c[1], c[2], a.clamp(0.0, 1.0)].map(unorm8)
+            })
+            .collect(),
+        other => {
+            return Err(GpuError::new(
+                format!("Cannot convert {other:?} readback to sRGB"),
+                GpuErrorKind::Other,
+            ));
+        }
+    };
+
+    image::RgbaImage::from_raw(readback.width, readback.height, pixels).ok_or_else(|| {
+        GpuError::new(
+            "Readback size does not match its dimensions",
+            GpuErrorKind::Other,
+        )
+    })
+}
+
+/// Convert a readback to 32-bit float RGBA without any tonemapping.
+///
+/// Float formats keep their values, 8-bit formats are normalized to [0..1]
+/// after the same conversion as [`readback_to_rgba8`].
+pub fn readback_to_rgba32f(readback: &TextureReadback) -> Result<image::Rgba32FImage, GpuError> {
+    let pixels: Vec<f32> = match readback.format {
+        TextureFormat::R16g16b16a16Float => {
+            readback.data.chunks_exact(8).flat_map(rgba16f).collect()
+        }
+        TextureFormat::Rgba32Float => readback
+            .data
+            .chunks_exact(4)
+            .map(|p| f32::from_le_bytes([p[0], p[1], p[2], p[3]]))
+            .collect(),
+        _ => readback_to_rgba8(readback)?
+            .into_raw()
+            .into_iter()
+            .map(|v| v as f32 / 255.0)
+            .collect(),
+    };
+
+    image::Rgba32FImage::from_raw(readback.width, readback.height, pixels).ok_or_else(|| {
+        GpuError::new(
+            "Readback size does not match its dimensions",
+            GpuErrorKind::Other,
+        )
+    })
+}
+
+/// Write a readback as an sRGB PNG.
+pub fn save_png(readback: &TextureReadback, path: &Path) -> Result<(), GpuError> {
+    readback_to_rgba8(readback)?
+        .save_with_format(path, image::ImageFormat::Png)
+        .map_err(|e| {
+            GpuError::new(
+                format!("Failed to write {}: {e}", path.display()),
+                GpuErrorKind::Other,
+            )
+        })
+}
+
+/// Write an `R16g16b16a16Float` readback as a half float EXR, values unchanged.
+pub fn save_exr(readback: &TextureReadback, path: &Path) -> Result<(), GpuError> {
+    if readback.format != TextureFormat::R16g16b16a16Float {
+        return Err(GpuError::new(
+            format!(
+                "EXR export expects R16g16b16a16Float, got {:?}",
+                readback.format
+            ),
+            GpuErrorKind::Other,
+        ));
+    }
+    let width = readback.width as usize;
+    let data = &readback.data;
+    exr::prelude::write_rgba_file(path, width, readback.height as usize, |x, y| {
+        let offset = (y * width + x) * 8;
+        let half =
+            |i: usize| f16::from_bits(u16::from_le_bytes([data[offset + i], data[offset + i + 1]]));
+        (half(0), half(2), half(4), half(6))
+    })
+    .map_err(|e| {
+        GpuError::new(
+            format!("Failed to write {}: {e}", path.display()),
+            GpuErrorKind::Other,
+        )
+    })
+}
+
+fn rgba16f(p: &[u8]) -> [f32; 4] {
+    [0, 2, 4, 6].map(|i| f16::from_bits(u16::from_le_bytes([p[i], p[i + 1]])).to_f32())
+}
+
+fn unorm8(v: f32) -> u8 {
+    (v.clamp(0.0, 1.0) * 255.0 + 0.5) as u8
+}
+
+/// Invert `liearToHdr10` and apply the SDR output mapping.
+fn hdr10_to_srgb8(packed: u32) -> [u8; 4] {
+    // A2B10G10R10: red in the low bits
+    let r = (packed & 0x3ff) as f32 / 1023.0;
+    let g = ((packed >> 10) & 0x3ff) as f32 / 1023.0;
+    let b = ((packed >> 20) & 0x3ff) as f32 / 1023.0;
+
+    let scale = ST2084_MAX / HDR10_WHITE_POINT;
+    let rec2020 = [r, g, b].map(|c| pq_to_linear(c) * scale);
+
+    // Rec.2020 -> Rec.709 (inverse of the matrix in color_utils.slang)
+    const FROM_2020_TO_709: [[f32; 3]; 3] = [
+        [1.660491, -0.587641, -0.072850],
+        [-0.124551, 1.1329, -0.008349],
+        [-0.018151, -0.100579, 1.11873],
+    ];
+    let rec709 =
+        FROM_2020_TO_709.map(|row| row[0] * rec2020[0] + row[1] * rec2020[1] + row[2] * rec2020[2]);
+
+    let c = rec709.map(|c| unorm8(linear_to_srgb(aces(c.max(0.0)))));
+    [c[0], c[1], c[2], 255]
+}
+
+/// ST.2084 (PQ) EOTF, result normalized to [0..1] of 10000 nits.
+fn pq_to_linear(v: f32) -> f32 {
+    const M1: f32 = 2610.0 / 4096.0 / 4.0;
+    const M2: f32 = 2523.0 / 4096.0 * 128.0;
+    const C1: f32 = 3424.0 / 4096.0;
+    const C2: f32 = 2413.0 / 4096.0 * 32.0;
+    const C3: f32 = 2392.0 / 4096.0 * 32.0;
+
+    let vp = v.powf(1.0 / M2);
+    ((vp - C1).max(0.0) / (C2 - C3 * vp)).powf(1.0 / M1)
+}
+
+fn aces(c: f32) -> f32 {
+    ((c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14)).clamp(0.0, 1.0)
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
@@ -18,13 +18,57 @@ pub struct Settings {
 
     // engine
     pub gpu_validation: bool,
+    /// Collect pipeline statistics (primitives, fragment invocations) per pass.
+    pub gpu_pipeline_statistics: bool,
+    /// Rebuild pipelines when the SPIR-V under `src/shaders/spv` changes.
+    pub shader_hot_reload: bool,
+    /// Upper bound for the bindless 2D texture array, lowered to the device limit.
+    pub max_bindless_textures: u32,
+    /// MSAA samples of the forward pass and the final composite (1, 2, 4 or 8),
+    /// lowered to what the device supports.
+    pub msaa_samples: u32,
+    /// Shadow cascades of directional lights (1 to 4).
+    pub shadow_cascades: u32,
+    /// Blend between logarithmic (1.0) and uniform (0.0) cascade splits.
+    pub shadow_split_lambda: f32,
+    /// View distance covered by the shadow cascades, capped by the camera's far plane.
+    pub shadow_distance: f32,
 
-    pub ssao: bool,
+    pub ssao: SsaoSettings,
+    /// Temporal anti-aliasing: jitters the projection and blends each frame
+    /// with the previous ones. Off by default.
+    pub taa: bool,
 
     pub sync_mode: SyncMode,
     pub hdr_preferred: bool,
 }
 
+/// Screen-space ambient occlusion, applied to the ambient term of the deferred lighting.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SsaoSettings {
+    pub enabled: bool,
+    /// World-space radius of the sample hemisphere.
+    pub radius: f32,
+    /// Depth difference a sample needs before it occludes, against self-occlusion.
+    pub bias: f32,
+    /// Exponent applied to the unoccluded fraction; higher values darken creases.
+    pub intensity: f32,
+    /// Hemisphere samples per pixel (1 to 32).
+    pub samples: u32,
+}
+
+impl Default for SsaoSettings {
+    fn default() -> SsaoSettings {
+        SsaoSettings {
+            enabled: false,
+            radius: 0.5,
+            bias: 0.025,
+            intensity: 1.0,
+            samples: 16,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum SyncMode {
     VSync,
@@ -88,8 +132,90 @@ impl Settings {
                 },
                 _ => (),
             };
+            if let Some(v) = engine_settings.get("PipelineStatistics") {
+                match (v.parse::<bool>(), v.parse::<u32>()) {
+                    (Ok(b), _) => settings.gpu_pipeline_statistics = b,
+                    (_, Ok(i)) => settings.gpu_pipeline_statistics = i == 1,
+                    _ => (),
+                }
+            }
+            if let Some(v) = engine_settings.get("ShaderHotReload") {
+                match (v.parse::<bool>(), v.parse::<u32>()) {
+                    (Ok(b), _) => settings.shader_hot_reload = b,
+                    (_, Ok(i)) => settings.shader_hot_reload = i == 1,
+                    _ => (),
+                }
+            }
+            if let Some(Ok(n)) = engine_settings
+                .get("MaxBindlessTextures")
+                .map(|v| v.parse::<u32>())
+            {
+                settings.max_bindless_textures = n;
+            }
+            match engine_settings.get("MSAA").map(|v| v.parse::<u32>()) {
+                Some(Ok(n @ (1 | 2 | 4 | 8))) => settings.msaa_samples = n,
+                Some(_) => eprintln!("Ignoring MSAA setting, expected 1, 2, 4 or 8"),
+                None => (),
+            }
+            match engine_settings
+                .get("ShadowCascades")
+                .map(|v| v.parse::<u32>())
+            {
+                Some(Ok(n @ 1..=4)) => settings.shadow_cascades = n,
+                Some(_) => eprintln!("Ignoring ShadowCascades setting, expected 1 to 4"),
+                None => (),
+            }
+            match engine_settings
+                .get("ShadowSplitLambda")
+                .map(|v| v.parse::<f32>())
+            {
+                Some(Ok(l)) if (0.0..=1.0).contains(&l) => settings.shadow_split_lambda = l,
+                Some(_) => eprintln!("Ignoring ShadowSplitLambda setting, expected 0.0 to 1.0"),
+                None => (),
+            }
+            if let Some(Ok(d)) = engine_settings
+                .get("ShadowDistance")
+                .map(|v| v.parse::<f32>())
+            {
+                settings.shadow_distance = d;
+            }
+            if let Some(v) = engine_settings.get("SSAO") {
+                match (v.parse::<bool>(), v.parse::<u32>()) {
+                    (Ok(b), _) => settings.ssao.enabled = b,
+                    (_, Ok(i)) => settings.ssao.enabled = i == 1,
+                    _ => (),
+                }
+            }
+            match engine_settings.get("SSAORadius").map(|v| v.parse::<f32>()) {
+                Some(Ok(r)) if r > 0.0 => settings.ssao.radius = r,
+                Some(_) => eprintln!("Ignoring SSAORadius setting, expected a positive value"),
+                None => (),
+            }
+            if let Some(Ok(b)) = engine_settings.get("SSAOBias").map(|v| v.parse::<f32>()) {
+                settings.ssao.bias = b;
+            }
+            match engine_settings
+                .get("SSAOIntensity")
+                .map(|v| v.parse::<f32>())
+            {
+                Some(Ok(i)) if i > 0.0 => settings.ssao.intensity = i,
+                Some(_) => eprintln!("Ignoring SSAOIntensity setting, expected a positive value"),
+                None => (),
+            }
+            match engine_settings.get("SSAOSamples").map(|v| v.parse::<u32>()) {
+                Some(Ok(n @ 1..=32)) => settings.ssao.samples = n,
+                Some(_) => eprintln!("Ignoring SSAOSamples setting, expected 1 to 32"),
+                None => (),
+            }
+            if let Some(v) = engine_settings.get("TAA") {
+                match (v.parse::<bool>(), v.parse::<u32>()) {
+                    (Ok(b), _) => settings.taa = b,
+                    (_, Ok(i)) => settings.taa = i == 1,
+                    _ => (),
+                }
+            }
         }
-        // todo: read sync mode and ssao settings
+        // todo: read sync mode
 
         settings
     }
@@ -104,7 +230,16 @@ impl std::default::Default for Settings {
             camera_fov: 70.0,
             view_distance: 1000.0,
             gpu_validation: false,
-            ssao: false,
+            gpu_pipeline_statistics: false,
+            shader_hot_reload: cfg!(debug_assertions),
+            max_bindless_textures: 16384,
+            msaa_samples: 1,
+            shadow_cascades: 4,
+            shadow_split_lambda: 0.75,
+            shadow_distance: 100.0,
+            ssao: SsaoSettings::default(),
+            // off until it has been checked on a real device
+            taa: false,
             // sync_mode: SyncMode::Mailbox,
             sync_mode: SyncMode::AdaptiveVSync,
             hdr_preferred: true,
@@ -0,0 +1,400 @@
+//! Shader hot reload.
+//!
+//! [`ShaderWatcher`] polls the compiled SPIR-V under `src/shaders/spv` for
+//! modification time changes. The renderer re-reads the affected shader sources
+//! through the backend and hands them to the render graph passes as
+//! [`ReloadedShaders`], between frames. A source or pipeline that fails to build
+//! is logged and the pass keeps its previous pipeline.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use super::backend::{GpuBackend, GpuError, RtShaders, Shaders};
+
+/// The SPIR-V output directory of `tools/compile-shaders` in the source tree.
+pub const SPIRV_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders/spv");
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Polls a directory tree for changed files.
+pub struct ShaderWatcher {
+    dir: PathBuf,
+    mtimes: HashMap<String, SystemTime>,
+    /// Changed at the last poll; reported once a poll sees no further writes,
+    /// so a batch from the shader compiler is picked up as a whole.
+    pending: HashSet<String>,
+    last_poll: Instant,
+}
+
+impl ShaderWatcher {
+    /// Start watching `dir`. Returns `None` if it does not exist, e.g. when the
+    /// binary runs outside the source tree.
+    pub fn new(dir: impl Into<PathBuf>) -> Option<Self> {
+        let dir = dir.into();
+        if !dir.is_dir() {
+            return None;
+        }
+        let mut mtimes = HashMap::new();
+        scan(&dir, &dir, &mut mtimes);
+        Some(ShaderWatcher {
+            dir,
+            mtimes,
+            pending: HashSet::new(),
+            last_poll: Instant::now(),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Files changed since the last report, relative to the watched directory
+    /// with `/` separators. Empty while writes are still in progress.
+    pub fn poll(&mut self) -> HashSet<String> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return HashSet::new();
+        }
+        self.last_poll = Instant::now();
+
+        let mut mtimes = HashMap::new();
+        scan(&self.dir, &self.dir, &mut mtimes);
+        let mut changed = false;
+        for (file, mtime) in &mtimes {
+            if self.mtimes.get(file) != Some(mtime) {
+                self.pending.insert(file.clone());
+                changed = true;
+            }
+        }
+        self.mtimes = mtimes;
+
+        if changed {
+            HashSet::new()
+        } else {
+            std::mem::take(&mut self.pending)
+        }
+    }
+}
+
+fn scan(root: &Path, dir: &Path, mtimes: &mut HashMap<String, SystemTime>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            scan(root, &path, mtimes);
+        } else if let (Ok(relative), Ok(mtime)) = (
+            path.strip_prefix(root),
+            entry.metadata().and_then(|m| m.modified()),
+        ) {
+            let relative = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            mtimes.insert(relative, mtime);
+        }
+    }
+}
+
+/// Shader sources re-read from disk, `None` for programs whose files did not change.
+pub struct ReloadedShaders<B: GpuBackend> {
+    pub deferred_pre: Option<B::ShaderSource>,
+    pub shadow: Option<B::ShaderSource>,
+    pub ssao: Option<B::ShaderSource>,
+    pub ssao_blur: Option<B::ShaderSource>,
+    pub deferred_light: Option<B::ShaderSource>,
+    pub forward: Option<B::ShaderSource>,
+    pub taa: Option<B::ShaderSource>,
+    pub output: Option<B::ShaderSource>,
+    pub skybox: Option<B::ShaderSource>,
+    pub light_culling: Option<B::ShaderSource>,
+    pub rt: Option<RtShaders<B>>,
+}
+
+impl<B: GpuBackend> ReloadedShaders<B> {
+    /// No program reloaded.
+    pub fn none() -> Self {
+        ReloadedShaders {
+            deferred_pre: None,
+            shadow: None,
+            ssao: None,
+            ssao_blur: None,
+            deferred_light: None,
+            forward: None,
+            taa: None,
+            output: None,
+            skybox: None,
+            light_culling: None,
+            rt: None,
+        }
+    }
+
+    /// Split into one set per reloaded program, so each program can be rebuilt
+    /// and adopted on its own.
+    pub fn per_program(self) -> Vec<Self> {
+        let none = Self::none;
+        let programs = [
+            self.deferred_pre.map(|s| Self {
+                deferred_pre: Some(s),
+                ..none()
+            }),
+            self.shadow.map(|s| Self {
+                shadow: Some(s),
+                ..none()
+            }),
+            self.ssao.map(|s| Self {
+                ssao: Some(s),
+                ..none()
+            }),
+            self.ssao_blur.map(|s| Self {
+                ssao_blur: Some(s),
+                ..none()
+            }),
+            self.deferred_light.map(|s| Self {
+                deferred_light: Some(s),
+                ..none()
+            }),
+            self.forward.map(|s| Self {
+                forward: Some(s),
+                ..none()
+            }),
+            self.taa.map(|s| Self {
+                taa: Some(s),
+                ..none()
+            }),
+            self.output.map(|s| Self {
+                output: Some(s),
+                ..none()
+            }),
+            self.skybox.map(|s| Self {
+                skybox: Some(s),
+                ..none()
+            }),
+            self.light_culling.map(|s| Self {
+                light_culling: Some(s),
+                ..none()
+            }),
+            self.rt.map(|s| Self {
+                rt: Some(s),
+                ..none()
+            }),
+        ];
+        programs.into_iter().flatten().collect()
+    }
+
+    /// Replace the sources in `shaders` and `rt_shaders` with the reloaded ones.
+    pub fn adopt(self, shaders: &mut Shaders<B>, rt_shaders: &mut Option<RtShaders<B>>) {
+        let slots = [
+            (&mut shaders.deferred_pre, self.deferred_pre),
+            (&mut shaders.shadow, self.shadow),
+            (&mut shaders.ssao, self.ssao),
+            (&mut shaders.ssao_blur, self.ssao_blur),
+            (&mut shaders.deferred_light, self.deferred_light),
+            (&mut shaders.forward, self.forward),
+            (&mut shaders.taa, self.taa),
+            (&mut shaders.output, self.output),
+            (&mut shaders.skybox, self.skybox),
+            (&mut shaders.light_culling, self.light_culling),
+        ];
+        for (slot, source) in slots {
+            if let Some(source) = source {
+                *slot = source;
+            }
+        }
+        if self.rt.is_some() {
+            *rt_shaders = self.rt;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deferred_pre.is_none()
+            && self.shadow.is_none()
+            && self.ssao.is_none()
+            && self.ssao_blur.is_none()
+            && self.deferred_light.is_none()
+            && self.forward.is_none()
+            && self.taa.is_none()
+            && self.output.is_none()
+            && self.skybox.is_none()
+            && self.light_culling.is_none()
+            && self.rt.is_none()
+    }
+}
+
+/// Whether any file of `source` is in `changed`.
+pub fn is_affected<B: GpuBackend>(
+    backend: &B,
+    source: &B::ShaderSource,
+    changed: &HashSet<String>,
+) -> bool {
+    backend
+        .shader_files(source)
+        .iter()
+        .any(|file| changed.contains(*file))
+}
+
+/// Re-read `source` if one of its files changed. Read errors are logged and
+/// yield `None`, so the caller keeps the previous source.
+pub fn reload_if_changed<B: GpuBackend>(
+    backend: &B,
+    source: &B::ShaderSource,
+    changed: &HashSet<String>,
+    spirv_dir: &Path,
+) -> Option<B::ShaderSource> {
+    if !is_affected(backend, source, changed) {
+        return None;
+    }
+    match backend.reload_shader_source(source, spirv_dir) {
+        Ok(source) => Some(source),
+        Err(e) => {
+            eprintln!("Shader reload failed: {e}");
+            None
+        }
+    }
+}
+
+/// Re-read all ray tracing shaders if one of them changed. The stages share one
+/// pipeline, so they are reloaded together; errors are logged and yield `None`.
+pub fn reload_rt_if_changed<B: GpuBackend>(
+    backend: &B,
+    shaders: &RtShaders<B>,
+    changed: &HashSet<String>,
+    spirv_dir: &Path,
+) -> Option<RtShaders<B>> {
+    let stages = [
+        &shaders.raygen,
+        &shaders.miss,
+        &shaders.miss_shadow,
+        &shaders.closest_hit,
+        &shaders.any_hit,
+    ];
+    if !stages
+        .iter()
+        .any(|source| is_affected(backend, source, changed))
+    {
+        return None;
+    }
+    let reload = |source: &B::ShaderSource| backend.reload_shader_source(source, spirv_dir);
+    let reloaded = (|| {
+        Ok::<_, GpuError>(RtShaders {
+            raygen: reload(&shaders.raygen)?,
+            miss: reload(&shaders.miss)?,
+            miss_shadow: reload(&shaders.miss_shadow)?,
+            closest_hit: reload(&shaders.closest_hit)?,
+            any_hit: reload(&shaders.any_hit)?,
+        })
+    })();
+    match reloaded {
+        Ok(shaders) => Some(shaders),
+        Err(e) => {
+            eprintln!("Shader reload failed: {e}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::engine::headless_backend::{HeadlessBackend, HeadlessShader};
+
+    const SLANG_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders/slang");
+    const MODULES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders/slang/modules");
+
+    /// FNV-1a hash of a shader and every module it imports, transitively, as
+    /// recorded by `tools/compile-shaders` in `sources.txt`.
+    fn slang_source_hash(shader: &Path) -> u64 {
+        let mut files = BTreeMap::new();
+        collect_imports(shader, &mut files);
+        files.values().fold(0xcbf29ce484222325, |hash, source| {
+            source.replace('\r', "").bytes().fold(hash, |hash, b| {
+                (hash ^ b as u64).wrapping_mul(0x100000001b3)
+            })
+        })
+    }
+
+    fn collect_imports(file: &Path, files: &mut BTreeMap<PathBuf, String>) {
+        if files.contains_key(file) {
+            return;
+        }
+        let source =
+            std::fs::read_to_string(file).unwrap_or_else(|e| panic!("{}: {e}", file.display()));
+        files.insert(file.to_path_buf(), source.clone());
+        for line in source.lines() {
+            let line = line.trim_start().trim_start_matches("__exported").trim();
+            let Some(module) = line
+                .strip_prefix("import ")
+                .map(|m| m.trim_end_matches(';').trim())
+            else {
+                continue;
+            };
+            let found = [SLANG_DIR, MODULES_DIR]
+                .iter()
+                .map(|dir| Path::new(dir).join(format!("{module}.slang")))
+                .find(|path| path.is_file());
+            if let Some(path) = found {
+                collect_imports(&path, files);
+            }
+        }
+    }
+
+    #[test]
+    fn spirv_is_up_to_date_with_slang_sources() {
+        let manifest =
+            std::fs::read_to_string(Path::new(SPIRV_DIR).join("sources.txt")).unwrap_or_default();
+        let recorded: HashMap<&str, &str> = manifest
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .collect();
+
+        let mut spirv = HashMap::new();
+        scan(Path::new(SPIRV_DIR), Path::new(SPIRV_DIR), &mut spirv);
+        let mut stale = Vec::new();
+        for file in spirv.keys().filter(|f| f.ends_with(".spv")) {
+            let source = Path::new(SLANG_DIR).join(file.replace(".spv", ".slang"));
+            if !source.is_file() {
+                continue;
+            }
+            let hash = format!("{:016x}", slang_source_hash(&source));
+            if recorded.get(file.as_str()) != Some(&hash.as_str()) {
+                stale.push(file.clone());
+            }
+        }
+        stale.sort();
+        assert!(
+            stale.is_empty(),
+            "SPIR-V older than its Slang source, run tools/compile-shaders: {stale:?}"
+        );
+    }
+
+    #[test]
+    fn each_program_is_adopted_on_its_own() {
+        let backend = HeadlessBackend::new(4, 4);
+        let mut shaders = backend.load_shaders();
+        let mut rt_shaders = None;
+        let reloaded = ReloadedShaders::<HeadlessBackend> {
+            ssao: Some(vec![HeadlessShader { label: "ssao_v2" }]),
+            taa: Some(vec![HeadlessShader { label: "taa_v2" }]),
+            ..ReloadedShaders::none()
+        };
+
+        let programs = reloaded.per_program();
+        assert_eq!(programs.len(), 2);
+        assert!(programs.iter().all(|p| p.ssao.is_some() != p.taa.is_some()));
+
+        // only the first program rebuilt, the other stays on its old source
+        let mut programs = programs.into_iter();
+        programs
+            .next()
+            .unwrap()
+            .adopt(&mut shaders, &mut rt_shaders);
+        assert_eq!(shaders.ssao[0].label, "ssao_v2");
+        assert_eq!(shaders.taa[0].label, "taa");
+        assert_eq!(shaders.shadow[0].label, "shadow");
+        assert!(rt_shaders.is_none());
+    }
+}
@@ -164,13 +164,16 @@ impl<B: GpuBackend> Skybox<B> {
         let rot = glm::rotate(&glm::identity(), 4.78, &glm::vec3(0.0, 1.0, 0.0));
         let rot = glm::rotate(&rot, 1.571, &glm::vec3(0.0, 0.0, -1.0));
         drawable.update_model(backend, &(rot * s));
+        drawable.retire_model();
 
         Ok(Skybox { drawable })
     }
 
-    /// Update the skybox model matrix.
+    /// Update the skybox model matrix. The skybox has no motion vectors, so the
+    /// previous model matrix follows it right away.
     pub fn update_model(&mut self, backend: &B, model: &glm::Mat4) {
         self.drawable.update_model(backend, model);
+        self.drawable.retire_model();
     }
 
     /// Draw the skybox cube. Binds the cubemap texture via the drawable's material.
@@ -0,0 +1,2757 @@
+//! CPU software rasterizer implementing [`GpuBackend`].
+//!
+//! `SoftwareBackend` needs no GPU or driver and actually renders: vertices are
+//! transformed with the model matrix and the bound uniforms, clipped, culled
+//! according to [`CullMode`], rasterized with a top-left fill rule, depth
+//! tested with [`CompareFunc`] and blended with [`BlendMode`]. Shaders are
+//! replaced by built-in CPU equivalents of the engine's passes (see
+//! [`SoftwareProgram`]), so the output follows the Slang shaders closely but is
+//! fully deterministic. It serves as a reference renderer for tests and as a
+//! fallback on machines without Vulkan.
+//!
+//! Its golden images cover the renderer, the render graph and the pass setup,
+//! not the shaders or pipelines: nothing here runs the SPIR-V, so a broken or
+//! stale shader only shows up in the Vulkan golden test.
+//!
+//! Not supported: ray tracing, compute dispatches other than the light culling,
+//! indirect draws (they need the compute culling path), parallax mapping and
+//! mipmapping.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use exr::prelude::f16;
+
+use crate::engine::backend::{
+    AccelerationStructureType, AddressMode, BlendMode, BufferDesc, BufferUsage, CompareFunc,
+    ComputeBarrier, ComputeDispatch, ComputePipelineDesc, CullMode, FilterMode,
+    GpuAccelerationStructure, GpuBackend, GpuBuffer, GpuError, GpuErrorKind, GpuRenderTarget,
+    GpuTexture, LoadOp, MaterialProperties, ProceduralShaders, RenderItem, RenderPassDesc,
+    RenderPipelineDesc, RenderTargetDesc, RenderTargetUsage, RtShaders, SamplerDesc, ShaderStage,
+    Shaders, StencilOp, StencilState, TargetBarrier, TextureDesc, TextureDimension, TextureFormat,
+    TextureReadback, ViewportDesc,
+};
+use crate::engine::draw_programs::{SHADOW_ATLAS_COLUMNS, SHADOW_ATLAS_ROWS};
+use crate::engine::geometry::{MAX_SHADOW_VIEWS, Vertex};
+
+static SOFTWARE_RESOURCE_ID: AtomicUsize = AtomicUsize::new(1);
+
+fn next_resource_id() -> usize {
+    SOFTWARE_RESOURCE_ID.fetch_add(1, Ordering::SeqCst)
+}
+
+/// `gamma` specialization constant of the Vulkan pipelines.
+const GAMMA: f32 = 2.2;
+/// Must match `white_point` in blend.slang.
+const HDR10_WHITE_POINT: f32 = 350.0;
+
+const LIGHT_AMBIENT: u32 = 0;
+const LIGHT_DIRECTIONAL: u32 = 1;
+const LIGHT_AREA: u32 = 2;
+const LIGHT_POINT: u32 = 3;
+const LIGHT_SPOT: u32 = 4;
+
+/// Descriptor bindings of the shared buffers (see `Renderer::create`).
+const BINDING_VIEW_PROJ: u32 = 0;
+const BINDING_CAMERA: u32 = 1;
+const BINDING_CLUSTERS: u32 = 2;
+const BINDING_LIGHT_SPACE: u32 = 3;
+const BINDING_LIGHTS: u32 = 17;
+const BINDING_CLUSTER_LIGHTS: u32 = 18;
+const BINDING_SSAO: u32 = 19;
+const BINDING_TAA: u32 = 20;
+
+/// Size of a `Light` in the light storage buffer.
+const LIGHT_STRIDE: usize = 464;
+
+/// Texture slots, matching `tex0..tex6` of the push constants.
+const TEXTURE_SLOTS: usize = 7;
+/// Color attachments written by the deferred pre-pass.
+const MAX_COLOR_TARGETS: usize = 4;
+
+/// Columns and rows of the shadow atlas grid, to scale tile UVs to atlas UVs.
+const SHADOW_ATLAS_GRID: [f32; 2] = [SHADOW_ATLAS_COLUMNS as f32, SHADOW_ATLAS_ROWS as f32];
+/// Border of a shadow atlas tile (in tile UV) that keeps the PCF taps inside it.
+const SHADOW_TILE_BORDER: f32 = 0.002;
+
+/// First 16 taps of `poissonDisk` in shadow_utils.slang.
+const POISSON_DISK: [[f32; 2]; 16] = [
+    [-0.613392, 0.617481],
+    [0.170019, -0.040254],
+    [-0.299417, 0.791925],
+    [0.645680, 0.493210],
+    [-0.651784, 0.717887],
+    [0.421003, 0.027070],
+    [-0.817194, -0.271096],
+    [-0.705374, -0.668203],
+    [0.977050, -0.108615],
+    [0.063326, 0.142369],
+    [0.203528, 0.214331],
+    [-0.667531, 0.326090],
+    [-0.098422, -0.295755],
+    [-0.885922, 0.215369],
+    [0.566637, 0.605213],
+    [0.039766, -0.396100],
+];
+
+// Resources
+
+/// CPU texture / render target. Texels are stored as RGBA `f32` (depth in `r`),
+/// quantized to the precision of `format` on every write. Clones share the
+/// texel storage, like handles to the same GPU image.
+#[derive(Clone)]
+pub struct SoftwareTexture {
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    pub id: usize,
+    pub bindless_index: u32,
+    pub usage: Option<RenderTargetUsage>,
+    pub is_cubemap: bool,
+    /// Array layers of a layered render target, 1 otherwise.
+    pub layers: u32,
+    sampler: SamplerDesc,
+    /// One layer for 2D textures, six consecutive faces for cubemaps.
+    texels: Rc<RefCell<Vec<[f32; 4]>>>,
+}
+
+impl SoftwareTexture {
+    fn new(
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        sampler: SamplerDesc,
+        texels: Vec<[f32; 4]>,
+    ) -> Self {
+        SoftwareTexture {
+            width,
+            height,
+            format,
+            id: next_resource_id(),
+            bindless_index: u32::MAX,
+            usage: None,
+            is_cubemap: false,
+            layers: 1,
+            sampler,
+            texels: Rc::new(RefCell::new(texels)),
+        }
+    }
+
+    fn texel_count(width: u32, height: u32) -> usize {
+        width as usize * height as usize
+    }
+
+    /// Fetch a single texel of layer 0, zero outside the texture (`Texture2D.Load`).
+    fn load(&self, x: i32, y: i32) -> [f32; 4] {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return [0.0; 4];
+        }
+        self.texels.borrow()[y as usize * self.width as usize + x as usize]
+    }
+
+    fn fetch(&self, layer: usize, x: i32, y: i32) -> [f32; 4] {
+        let x = address(x, self.width, self.sampler.address_u);
+        let y = address(y, self.height, self.sampler.address_v);
+        let layer_size = Self::texel_count(self.width, self.height);
+        self.texels.borrow()[layer * layer_size + y * self.width as usize + x]
+    }
+
+    /// Filtered lookup at normalized coordinates, honoring the sampler.
+    fn sample_layer(&self, layer: usize, uv: glm::Vec2) -> [f32; 4] {
+        let x = uv.x * self.width as f32;
+        let y = uv.y * self.height as f32;
+        if self.sampler.filter == FilterMode::Nearest {
+            return self.fetch(layer, x.floor() as i32, y.floor() as i32);
+        }
+
+        let (x, y) = (x - 0.5, y - 0.5);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i32, y0 as i32);
+        let t00 = self.fetch(layer, x0, y0);
+        let t10 = self.fetch(layer, x0 + 1, y0);
+        let t01 = self.fetch(layer, x0, y0 + 1);
+        let t11 = self.fetch(layer, x0 + 1, y0 + 1);
+        std::array::from_fn(|c| {
+            let top = t00[c] + (t10[c] - t00[c]) * fx;
+            let bottom = t01[c] + (t11[c] - t01[c]) * fx;
+            top + (bottom - top) * fy
+        })
+    }
+
+    fn sample(&self, uv: glm::Vec2) -> [f32; 4] {
+        self.sample_layer(0, uv)
+    }
+
+    /// Cubemap lookup, face selection as in the Vulkan spec.
+    fn sample_cube(&self, dir: glm::Vec3) -> [f32; 4] {
+        cube_face(dir).map_or([0.0; 4], |(face, uv)| self.sample_layer(face, uv))
+    }
+
+    /// Depth comparison lookup in cube `cube` of a cube array target.
+    fn sample_cube_compare(&self, cube: usize, dir: glm::Vec3, reference: f32) -> f32 {
+        cube_face(dir).map_or(1.0, |(face, uv)| {
+            self.sample_compare_layer(6 * cube + face, uv, reference)
+        })
+    }
+
+    /// Depth comparison lookup (`SampleCmpLevelZero`): the fraction of the
+    /// filter footprint where `reference` passes the sampler's compare function.
+    fn sample_compare(&self, uv: glm::Vec2, reference: f32) -> f32 {
+        self.sample_compare_layer(0, uv, reference)
+    }
+
+    fn sample_compare_layer(&self, layer: usize, uv: glm::Vec2, reference: f32) -> f32 {
+        let func = self.sampler.compare.unwrap_or(CompareFunc::LessEqual);
+        let x = uv.x * self.width as f32 - 0.5;
+        let y = uv.y * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i32, y0 as i32);
+        let test = |x, y| {
+            if compare(func, reference, self.fetch(layer, x, y)[0]) {
+                1.0
+            } else {
+                0.0
+            }
+        };
+        if self.sampler.filter == FilterMode::Nearest {
+            return test((x + 0.5).floor() as i32, (y + 0.5).floor() as i32);
+        }
+        let top = test(x0, y0) * (1.0 - fx) + test(x0 + 1, y0) * fx;
+        let bottom = test(x0, y0 + 1) * (1.0 - fx) + test(x0 + 1, y0 + 1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    fn clear(&self, value: [f32; 4]) {
+        let value = quantize(self.format, value);
+        self.texels.borrow_mut().fill(value);
+    }
+
+    /// Clear the depth (`r`) and stencil (`g`) of one layer of a depth target independently.
+    fn clear_depth_stencil(&self, layer: u32, depth: Option<f32>, stencil: Option<u8>) {
+        if depth.is_none() && stencil.is_none() {
+            return;
+        }
+        let layer_size = Self::texel_count(self.width, self.height);
+        let start = layer as usize * layer_size;
+        for texel in &mut self.texels.borrow_mut()[start..start + layer_size] {
+            let depth = depth.unwrap_or(texel[0]);
+            let stencil = stencil.map_or(texel[1], f32::from);
+            *texel = quantize(self.format, [depth, stencil, 0.0, 0.0]);
+        }
+    }
+}
+
+/// Face and face coordinates a cubemap lookup along `dir` reads, as in the Vulkan spec.
+fn cube_face(dir: glm::Vec3) -> Option<(usize, glm::Vec2)> {
+    let (ax, ay, az) = (dir.x.abs(), dir.y.abs(), dir.z.abs());
+    let (face, sc, tc, ma) = if ax >= ay && ax >= az {
+        if dir.x >= 0.0 {
+            (0, -dir.z, -dir.y, ax)
+        } else {
+            (1, dir.z, -dir.y, ax)
+        }
+    } else if ay >= az {
+        if dir.y >= 0.0 {
+            (2, dir.x, dir.z, ay)
+        } else {
+            (3, dir.x, -dir.z, ay)
+        }
+    } else if dir.z >= 0.0 {
+        (4, dir.x, -dir.y, az)
+    } else {
+        (5, -dir.x, -dir.y, az)
+    };
+    if ma == 0.0 {
+        return None;
+    }
+    Some((
+        face,
+        glm::vec2(0.5 * (sc / ma + 1.0), 0.5 * (tc / ma + 1.0)),
+    ))
+}
+
+impl GpuTexture for SoftwareTexture {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn bindless_index(&self) -> u32 {
+        self.bindless_index
+    }
+}
+
+impl GpuRenderTarget for SoftwareTexture {}
+
+/// Buffer backed by a CPU byte vector, shared with the descriptor bindings.
+pub struct SoftwareBuffer {
+    pub id: usize,
+    pub label: String,
+    pub usage: BufferUsage,
+    data: Rc<RefCell<Vec<u8>>>,
+}
+
+impl SoftwareBuffer {
+    fn write(&self, data: &[u8]) {
+        let mut contents = self.data.borrow_mut();
+        let len = data.len().min(contents.len());
+        contents[..len].copy_from_slice(&data[..len]);
+    }
+}
+
+impl GpuBuffer for SoftwareBuffer {
+    fn size(&self) -> usize {
+        self.data.borrow().len()
+    }
+}
+
+/// Built-in CPU replacement for the shaders of one pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoftwareProgram {
+    /// deferred/pre_*.slang: G-buffer fill.
+    DeferredPre,
+    /// shadow_mapping/sm_*.slang: depth only, light space.
+    Shadow,
+    /// ssao/ssao_pixel.slang: fullscreen ambient occlusion from the G-buffer.
+    Ssao,
+    /// ssao/ssao_blur_pixel.slang: fullscreen 4x4 box blur of the ambient occlusion.
+    SsaoBlur,
+    /// deferred/light_*.slang: fullscreen lighting from the G-buffer.
+    DeferredLight,
+    /// main_pass/*.slang: lit transparent geometry.
+    Forward,
+    /// taa/taa_pixel.slang: fullscreen temporal anti-aliasing resolve.
+    Taa,
+    /// compute/light_culling.slang: bins the lights into the view-space clusters.
+    LightCulling,
+    /// blend.slang: fullscreen composite and tonemapping.
+    Output,
+    /// skybox/sky_*.slang: cubemap at the far plane.
+    Skybox,
+    /// Shaders without a CPU equivalent; draws with them are skipped.
+    Unsupported(&'static str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoftwarePipelineKind {
+    Render,
+    Compute,
+}
+
+/// A built-in program plus the fixed-function state it is drawn with.
+#[derive(Debug, Clone)]
+pub struct SoftwarePipeline {
+    pub id: usize,
+    pub label: String,
+    pub kind: SoftwarePipelineKind,
+    pub program: SoftwareProgram,
+    blend_mode: BlendMode,
+    cull_mode: CullMode,
+    depth_write: bool,
+    depth_compare: CompareFunc,
+    stencil: Option<StencilState>,
+    color_target_count: usize,
+}
+
+/// Acceleration structures are never created, ray tracing is not supported.
+pub struct SoftwareAccelerationStructure;
+
+impl GpuAccelerationStructure for SoftwareAccelerationStructure {
+    fn vertex_device_address(&self) -> u64 {
+        0
+    }
+
+    fn index_device_address(&self) -> u64 {
+        0
+    }
+}
+
+// Pipeline stages
+
+/// Shared UBO contents, read from the descriptor bindings at draw time.
+struct DrawUniforms {
+    view: glm::Mat4,
+    proj: glm::Mat4,
+    camera_pos: glm::Vec3,
+    /// `ssao` flag of `CameraUniforms`: the lighting reads the SSAO in slot 5.
+    ssao: bool,
+    /// `taa` flag of `CameraUniforms`: the output reads the TAA result in slot 0.
+    taa: bool,
+    /// Unjittered view-projections of the motion vectors.
+    motion: TaaUniforms,
+    clusters: ClusterGrid,
+    lights: Vec<LightUniforms>,
+    /// Per cluster the light count followed by the light indices.
+    cluster_lights: Rc<RefCell<Vec<u8>>>,
+    light_space_matrix: glm::Mat4,
+}
+
+/// The cluster grid (`ClusterUniforms`).
+struct ClusterGrid {
+    view: glm::Mat4,
+    inv_proj: glm::Mat4,
+    screen_size: glm::Vec2,
+    near: f32,
+    far: f32,
+    z_scale: f32,
+    z_bias: f32,
+    light_count: u32,
+    max_cluster_lights: u32,
+    size: [u32; 3],
+}
+
+impl ClusterGrid {
+    fn read(bytes: &[u8]) -> Self {
+        ClusterGrid {
+            view: read_mat4(bytes, 0),
+            inv_proj: read_mat4(bytes, 64),
+            screen_size: glm::vec2(read_f32(bytes, 128), read_f32(bytes, 132)),
+            near: read_f32(bytes, 136),
+            far: read_f32(bytes, 140),
+            z_scale: read_f32(bytes, 144),
+            z_bias: read_f32(bytes, 148),
+            light_count: read_u32(bytes, 152),
+            max_cluster_lights: read_u32(bytes, 156),
+            size: std::array::from_fn(|i| read_u32(bytes, 160 + 4 * i)),
+        }
+    }
+
+    fn cluster_count(&self) -> usize {
+        self.size.iter().map(|&n| n as usize).product()
+    }
+
+    /// Offset of a cluster's light list in the cluster buffer.
+    fn list_offset(&self, cluster: usize) -> usize {
+        cluster * (1 + self.max_cluster_lights as usize) * 4
+    }
+
+    /// Cluster of a fragment (`clusterIndex` in light.slang).
+    fn cluster_at(&self, frag: glm::Vec2, pos: &glm::Vec4) -> usize {
+        let [gx, gy, gz] = self.size;
+        let depth = -(self.view * pos).z;
+        let tile = |coord: f32, extent: f32, count: u32| {
+            ((coord / extent * count as f32).max(0.0) as u32).min(count.saturating_sub(1))
+        };
+        let x = tile(frag.x, self.screen_size.x, gx);
+        let y = tile(frag.y, self.screen_size.y, gy);
+        let slice = (depth.max(self.near).ln() * self.z_scale - self.z_bias).floor();
+        let z = (slice.max(0.0) as u32).min(gz.saturating_sub(1));
+        (x + gx * (y + gy * z)) as usize
+    }
+
+    /// View-space bounding box of a cluster (`clusterBounds` in light_culling.slang).
+    fn bounds(&self, cluster: usize) -> (glm::Vec3, glm::Vec3) {
+        let [gx, gy, gz] = self.size.map(|n| n as usize);
+        let (x, y, z) = (cluster % gx, cluster / gx % gy, cluster / (gx * gy));
+        let slice_depth = |z: usize| self.near * (self.far / self.near).powf(z as f32 / gz as f32);
+        let depths = [slice_depth(z), slice_depth(z + 1)];
+        let ndc_x = |x: usize| -1.0 + 2.0 * x as f32 / gx as f32;
+        let ndc_y = |y: usize| 1.0 - 2.0 * y as f32 / gy as f32;
+
+        let mut min = glm::vec3(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+        let mut max = -min;
+        for corner in 0..4 {
+            let ndc = glm::vec4(ndc_x(x + corner % 2), ndc_y(y + corner / 2), 1.0, 1.0);
+            let p = self.inv_proj * ndc;
+            let dir = p.xyz() / p.w;
+            for depth in depths {
+                let point = dir * (depth / -dir.z);
+                min = glm::min2(&min, &point);
+                max = glm::max2(&max, &point);
+            }
+        }
+        (min, max)
+    }
+}
+
+/// The SSAO parameters (`SsaoUniforms`).
+struct SsaoUniforms {
+    projection: glm::Mat4,
+    view: glm::Mat4,
+    resolution: glm::Vec2,
+    radius: f32,
+    bias: f32,
+    intensity: f32,
+    sample_count: u32,
+    kernel: [glm::Vec3; 32],
+}
+
+impl SsaoUniforms {
+    fn read(bytes: &[u8]) -> Self {
+        SsaoUniforms {
+            projection: read_mat4(bytes, 0),
+            view: read_mat4(bytes, 64),
+            resolution: glm::vec2(read_f32(bytes, 128), read_f32(bytes, 132)),
+            radius: read_f32(bytes, 136),
+            bias: read_f32(bytes, 140),
+            intensity: read_f32(bytes, 144),
+            sample_count: read_u32(bytes, 148).clamp(1, 32),
+            kernel: std::array::from_fn(|i| read_vec3(bytes, 160 + 16 * i)),
+        }
+    }
+}
+
+/// The TAA parameters (`TaaUniforms`).
+struct TaaUniforms {
+    view_proj: glm::Mat4,
+    prev_view_proj: glm::Mat4,
+    feedback: f32,
+    history_valid: bool,
+}
+
+impl TaaUniforms {
+    fn read(bytes: &[u8]) -> Self {
+        TaaUniforms {
+            view_proj: read_mat4(bytes, 0),
+            prev_view_proj: read_mat4(bytes, 64),
+            feedback: read_f32(bytes, 128),
+            history_valid: read_u32(bytes, 132) != 0,
+        }
+    }
+}
+
+/// The parts of `GpuLight` the shaders read.
+struct LightUniforms {
+    position: glm::Vec3,
+    kind: u32,
+    color: glm::Vec3,
+    radius: f32,
+    direction: glm::Vec3,
+    range: f32,
+    shadow_view_count: u32,
+    cascade_blend: f32,
+    spot_scale: f32,
+    spot_offset: f32,
+    /// `None` for lights without shadow atlas tiles.
+    shadow_tile: Option<u32>,
+    /// `None` for point lights without a shadow cube.
+    shadow_cube: Option<u32>,
+    light_space: [glm::Mat4; MAX_SHADOW_VIEWS],
+}
+
+impl LightUniforms {
+    fn read(bytes: &[u8], offset: usize) -> Self {
+        let read_u32 = |o| read_u32(bytes, offset + o);
+        let read_f32 = |o| read_f32(bytes, offset + o);
+        let read_vec3 = |o| read_vec3(bytes, offset + o);
+        LightUniforms {
+            position: read_vec3(0),
+            kind: read_u32(12),
+            color: read_vec3(16),
+            radius: read_f32(28),
+            direction: read_vec3(32),
+            range: read_f32(44),
+            shadow_view_count: read_u32(52),
+            cascade_blend: read_f32(56),
+            spot_scale: read_f32(60),
+            spot_offset: read_f32(64),
+            shadow_tile: Some(read_u32(68)).filter(|&t| t != u32::MAX),
+            shadow_cube: Some(read_u32(72)).filter(|&c| c != u32::MAX),
+            light_space: std::array::from_fn(|i| read_mat4(bytes, offset + 80 + 64 * i)),
+        }
+    }
+
+    /// Point and spot lights reach up to their range, the others everywhere.
+    fn is_bounded(&self) -> bool {
+        self.kind == LIGHT_POINT || self.kind == LIGHT_SPOT
+    }
+}
+
+/// Vertex shader outputs, interpolated perspective-correct.
+#[derive(Clone, Copy)]
+struct Varyings {
+    /// World position; the local position for the skybox.
+    world_pos: glm::Vec4,
+    /// World position with the previous frame's model matrix.
+    prev_world_pos: glm::Vec4,
+    tex_coord: glm::Vec2,
+    tangent: glm::Vec3,
+    bitangent: glm::Vec3,
+    normal: glm::Vec3,
+}
+
+impl Varyings {
+    fn zero() -> Self {
+        Varyings {
+            world_pos: glm::Vec4::zeros(),
+            prev_world_pos: glm::Vec4::zeros(),
+            tex_coord: glm::Vec2::zeros(),
+            tangent: glm::Vec3::zeros(),
+            bitangent: glm::Vec3::zeros(),
+            normal: glm::Vec3::zeros(),
+        }
+    }
+
+    fn weighted(v: [&Varyings; 3], w: [f32; 3]) -> Self {
+        Varyings {
+            world_pos: v[0].world_pos * w[0] + v[1].world_pos * w[1] + v[2].world_pos * w[2],
+            prev_world_pos: v[0].prev_world_pos * w[0]
+                + v[1].prev_world_pos * w[1]
+                + v[2].prev_world_pos * w[2],
+            tex_coord: v[0].tex_coord * w[0] + v[1].tex_coord * w[1] + v[2].tex_coord * w[2],
+            tangent: v[0].tangent * w[0] + v[1].tangent * w[1] + v[2].tangent * w[2],
+            bitangent: v[0].bitangent * w[0] + v[1].bitangent * w[1] + v[2].bitangent * w[2],
+            normal: v[0].normal * w[0] + v[1].normal * w[1] + v[2].normal * w[2],
+        }
+    }
+
+    fn lerp(a: &Varyings, b: &Varyings, t: f32) -> Self {
+        Varyings::weighted([a, b, b], [1.0 - t, t, 0.0])
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ClipVertex {
+    pos: glm::Vec4,
+    varyings: Varyings,
+}
+
+#[derive(Clone, Copy)]
+struct ScreenVertex {
+    x: f32,
+    y: f32,
+    z: f32,
+    inv_w: f32,
+    varyings: Varyings,
+}
+
+/// Attachments of the active render pass.
+struct PassState {
+    colors: Vec<SoftwareTexture>,
+    depth: Option<SoftwareTexture>,
+    depth_write: bool,
+    /// Layer of `depth` the pass renders into.
+    depth_layer: u32,
+}
+
+/// Pixel rectangle `[x0, x1) x [y0, y1)` draws are limited to.
+#[derive(Clone, Copy)]
+struct ClipRect {
+    x0: i32,
+    y0: i32,
+    x1: i32,
+    y1: i32,
+}
+
+type FragmentOutput = [[f32; 4]; MAX_COLOR_TARGETS];
+
+// Backend
+
+/// GPU-less backend that rasterizes on the CPU.
+pub struct SoftwareBackend {
+    width: u32,
+    height: u32,
+    backbuffer_format: TextureFormat,
+    backbuffer: SoftwareTexture,
+    depth_target: SoftwareTexture,
+    next_bindless_index: std::cell::Cell<u32>,
+    descriptors: RefCell<HashMap<u32, Rc<RefCell<Vec<u8>>>>>,
+    pass: Option<PassState>,
+    pipeline: Option<SoftwarePipeline>,
+    viewport: ViewportDesc,
+    textures: [Option<SoftwareTexture>; TEXTURE_SLOTS],
+    vertex_buffer: Option<Rc<RefCell<Vec<u8>>>>,
+    index_buffer: Option<Rc<RefCell<Vec<u8>>>>,
+    model: glm::Mat4,
+    prev_model: glm::Mat4,
+    frame_count: u64,
+}
+
+impl SoftwareBackend {
+    pub fn new(width: u32, height: u32) -> Self {
+        let backbuffer_format = TextureFormat::Bgra8UnormSrgb;
+        SoftwareBackend {
+            width,
+            height,
+            backbuffer_format,
+            backbuffer: Self::swapchain_target(width, height, backbuffer_format),
+            depth_target: Self::swapchain_target(width, height, TextureFormat::Depth32Float),
+            next_bindless_index: std::cell::Cell::new(0),
+            descriptors: RefCell::new(HashMap::new()),
+            pass: None,
+            pipeline: None,
+            viewport: Self::full_viewport(width, height),
+            textures: Default::default(),
+            vertex_buffer: None,
+            index_buffer: None,
+            model: glm::identity(),
+            prev_model: glm::identity(),
+            frame_count: 0,
+        }
+    }
+
+    /// Use a different backbuffer format (e.g. `Abgr10Unorm` for the HDR10 output path).
+    pub fn with_backbuffer_format(mut self, format: TextureFormat) -> Self {
+        self.backbuffer_format = format;
+        self.backbuffer = Self::swapchain_target(self.width, self.height, format);
+        self
+    }
+
+    /// Number of frames that went through `present`.
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    fn swapchain_target(width: u32, height: u32, format: TextureFormat) -> SoftwareTexture {
+        let mut target = SoftwareTexture::new(
+            width,
+            height,
+            format,
+            SamplerDesc::default(),
+            vec![[0.0; 4]; SoftwareTexture::texel_count(width, height)],
+        );
+        target.usage = Some(if format.is_depth() {
+            RenderTargetUsage::Depth
+        } else {
+            RenderTargetUsage::Color
+        });
+        target
+    }
+
+    fn full_viewport(width: u32, height: u32) -> ViewportDesc {
+        ViewportDesc {
+            x: 0.0,
+            y: 0.0,
+            width: width as f32,
+            height: height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        }
+    }
+
+    fn allocate_bindless(&self) -> u32 {
+        let idx = self.next_bindless_index.get();
+        self.next_bindless_index.set(idx + 1);
+        idx
+    }
+
+    fn check_size(width: u32, height: u32, what: &str) -> Result<(), GpuError> {
+        if width == 0 || height == 0 {
+            return Err(GpuError::new(
+                format!("Cannot create {what} with zero extent ({width}x{height})"),
+                GpuErrorKind::ResourceCreation,
+            ));
+        }
+        Ok(())
+    }
+
+    fn decode_pixels(
+        data: &[u8],
+        texel_count: usize,
+        format: TextureFormat,
+    ) -> Result<Vec<[f32; 4]>, GpuError> {
+        if format.is_compressed() {
+            return Err(Self::not_supported(&format!("{format:?} texture")));
+        }
+        let bpp = format.bytes_per_pixel();
+        if format.is_depth() || data.len() < texel_count * bpp {
+            return Err(GpuError::new(
+                format!(
+                    "Texture data of {} bytes does not hold {texel_count} {format:?} texels",
+                    data.len()
+                ),
+                GpuErrorKind::ResourceCreation,
+            ));
+        }
+        Ok(data[..texel_count * bpp]
+            .chunks_exact(bpp)
+            .map(|p| decode_texel(format, p))
+            .collect())
+    }
+
+    fn not_supported(what: &str) -> GpuError {
+        GpuError::new(
+            format!("{what} is not supported by the software backend"),
+            GpuErrorKind::Other,
+        )
+    }
+
+    fn descriptor(&self, binding: u32) -> Vec<u8> {
+        self.descriptors
+            .borrow()
+            .get(&binding)
+            .map(|data| data.borrow().clone())
+            .unwrap_or_default()
+    }
+
+    fn draw_uniforms(&self) -> DrawUniforms {
+        let view_proj = self.descriptor(BINDING_VIEW_PROJ);
+        let camera = self.descriptor(BINDING_CAMERA);
+        let light_space = self.descriptor(BINDING_LIGHT_SPACE);
+        let clusters = ClusterGrid::read(&self.descriptor(BINDING_CLUSTERS));
+        DrawUniforms {
+            view: read_mat4(&view_proj, 0),
+            proj: read_mat4(&view_proj, 64),
+            camera_pos: read_vec3(&camera, 0),
+            ssao: read_u32(&camera, 12) != 0,
+            taa: read_u32(&camera, 16) != 0,
+            motion: TaaUniforms::read(&self.descriptor(BINDING_TAA)),
+            lights: self.lights(&clusters),
+            cluster_lights: self.descriptor_data(BINDING_CLUSTER_LIGHTS),
+            clusters,
+            light_space_matrix: read_mat4(&light_space, 0),
+        }
+    }
+
+    /// The storage buffer bound to `binding`, shared with the buffer itself.
+    fn descriptor_data(&self, binding: u32) -> Rc<RefCell<Vec<u8>>> {
+        self.descriptors
+            .borrow_mut()
+            .entry(binding)
+            .or_default()
+            .clone()
+    }
+
+    /// The `light_count` lights of the light storage buffer.
+    fn lights(&self, clusters: &ClusterGrid) -> Vec<LightUniforms> {
+        let lights = self.descriptor(BINDING_LIGHTS);
+        (0..clusters.light_count as usize)
+            .map(|i| LightUniforms::read(&lights, i * LIGHT_STRIDE))
+            .collect()
+    }
+
+    /// Light culling (light_culling.slang): writes the lights touching each
+    /// cluster into the cluster light lists.
+    fn cull_lights(&self) {
+        let clusters = ClusterGrid::read(&self.descriptor(BINDING_CLUSTERS));
+        let lights = self.lights(&clusters);
+        let view_lights: Vec<glm::Vec3> = lights
+            .iter()
+            .map(|l| (clusters.view * glm::vec4(l.position.x, l.position.y, l.position.z, 1.0)).xyz())
+            .collect();
+        let data = self.descriptor_data(BINDING_CLUSTER_LIGHTS);
+        let mut data = data.borrow_mut();
+        let mut write = |offset: usize, value: u32| {
+            if let Some(bytes) = data.get_mut(offset..offset + 4) {
+                bytes.copy_from_slice(&value.to_le_bytes());
+            }
+        };
+        for cluster in 0..clusters.cluster_count() {
+            let (min, max) = clusters.bounds(cluster);
+            let list = clusters.list_offset(cluster);
+            let mut count = 0;
+            for (i, (light, center)) in lights.iter().zip(&view_lights).enumerate() {
+                if count == clusters.max_cluster_lights {
+                    break;
+                }
+                if light.is_bounded() {
+                    let closest = glm::clamp_vec(center, &min, &max);
+                    if glm::distance2(&closest, center) > light.range * light.range {
+                        continue;
+                    }
+                }
+                write(list + 4 * (1 + count as usize), i as u32);
+                count += 1;
+            }
+            write(list, count);
+        }
+    }
+
+    /// Viewport (used as scissor, like the Vulkan backend) clipped to the attachments.
+    fn clip_rect(&self, pass: &PassState) -> Option<ClipRect> {
+        let extent = pass
+            .colors
+            .first()
+            .or(pass.depth.as_ref())
+            .map(|t| (t.width as i32, t.height as i32))?;
+        let vp = &self.viewport;
+        let rect = ClipRect {
+            x0: (vp.x.floor() as i32).max(0),
+            y0: (vp.y.floor() as i32).max(0),
+            x1: ((vp.x + vp.width).ceil() as i32).min(extent.0),
+            y1: ((vp.y + vp.height).ceil() as i32).min(extent.1),
+        };
+        (rect.x0 < rect.x1 && rect.y0 < rect.y1).then_some(rect)
+    }
+
+    fn slot(&self, slot: usize) -> Option<&SoftwareTexture> {
+        self.textures.get(slot).and_then(|t| t.as_ref())
+    }
+
+    /// Blend the fragment outputs into the color attachments.
+    fn write_colors(
+        &self,
+        pipeline: &SoftwarePipeline,
+        pass: &PassState,
+        x: i32,
+        y: i32,
+        out: &FragmentOutput,
+    ) {
+        let count = pipeline.color_target_count.min(pass.colors.len());
+        for (target, src) in pass.colors.iter().take(count).zip(out) {
+            let idx = y as usize * target.width as usize + x as usize;
+            let mut texels = target.texels.borrow_mut();
+            let dst = texels[idx];
+            texels[idx] = quantize(target.format, blend(pipeline.blend_mode, *src, dst));
+        }
+    }
+
+    /// Fullscreen programs ignore the vertex data (the shaders use `SV_VertexId`)
+    /// and cover the whole viewport.
+    fn draw_fullscreen(&self, pipeline: &SoftwarePipeline, pass: &PassState) {
+        let Some(rect) = self.clip_rect(pass) else {
+            return;
+        };
+        let uniforms = self.draw_uniforms();
+        let ssao = SsaoUniforms::read(&self.descriptor(BINDING_SSAO));
+        let is_hdr = pass
+            .colors
+            .first()
+            .is_some_and(|t| t.format == TextureFormat::Abgr10Unorm);
+
+        for y in rect.y0..rect.y1 {
+            for x in rect.x0..rect.x1 {
+                let color = match pipeline.program {
+                    SoftwareProgram::Ssao => [self.shade_ssao(&ssao, x, y); 4],
+                    SoftwareProgram::SsaoBlur => [self.shade_ssao_blur(x, y); 4],
+                    SoftwareProgram::DeferredLight => self.shade_deferred_light(&uniforms, x, y),
+                    SoftwareProgram::Taa => self.shade_taa(&uniforms.motion, x, y),
+                    SoftwareProgram::Output => self.shade_output(uniforms.taa, is_hdr, x, y),
+                    _ => return,
+                };
+                let mut out = [[0.0; 4]; MAX_COLOR_TARGETS];
+                out[0] = color;
+                self.write_colors(pipeline, pass, x, y, &out);
+            }
+        }
+    }
+
+    fn draw_mesh(
+        &self,
+        pipeline: &SoftwarePipeline,
+        pass: &PassState,
+        index_count: u32,
+        first_index: u32,
+        base_vertex: i32,
+    ) {
+        let (Some(vertex_buffer), Some(index_buffer)) = (&self.vertex_buffer, &self.index_buffer)
+        else {
+            return;
+        };
+        let Some(rect) = self.clip_rect(pass) else {
+            return;
+        };
+        let uniforms = self.draw_uniforms();
+
+        let vertex_data = vertex_buffer.borrow();
+        let index_data = index_buffer.borrow();
+        let vertex_size = std::mem::size_of::<Vertex>();
+        let vertex_count = vertex_data.len() / vertex_size;
+        let mut cache: Vec<Option<ClipVertex>> = vec![None; vertex_count];
+
+        let indices: Vec<u32> = (first_index..first_index + index_count)
+            .map(|i| read_u32(&index_data, i as usize * 4))
+            .collect();
+
+        for tri in indices.chunks_exact(3) {
+            let mut clip = [None; 3];
+            for (out, &index) in clip.iter_mut().zip(tri) {
+                let index = index as i64 + base_vertex as i64;
+                if index < 0 || index as usize >= vertex_count {
+                    break;
+                }
+                let index = index as usize;
+                let vertex = *cache[index].get_or_insert_with(|| {
+                    let offset = index * vertex_size;
+                    // SAFETY: the range is inside the buffer and `Vertex` is plain old data.
+                    let vertex = unsafe {
+                        std::ptr::read_unaligned(
+                            vertex_data[offset..offset + vertex_size].as_ptr() as *const Vertex
+                        )
+                    };
+                    self.run_vertex(pipeline.program, &uniforms, &vertex)
+                });
+                *out = Some(vertex);
+            }
+            let [Some(a), Some(b), Some(c)] = clip else {
+                continue;
+            };
+
+            let polygon = clip_polygon(vec![a, b, c]);
+            if polygon.len() < 3 {
+                continue;
+            }
+            let screen: Vec<ScreenVertex> = polygon.iter().map(|v| self.to_screen(v)).collect();
+            for i in 1..screen.len() - 1 {
+                self.rasterize_triangle(
+                    pipeline,
+                    pass,
+                    &uniforms,
+                    rect,
+                    [screen[0], screen[i], screen[i + 1]],
+                );
+            }
+        }
+    }
+
+    /// Viewport transform. The Vulkan backend flips the viewport (negative
+    /// height), so NDC +Y ends up at the top of the target.
+    fn to_screen(&self, v: &ClipVertex) -> ScreenVertex {
+        let vp = &self.viewport;
+        let inv_w = 1.0 / v.pos.w;
+        let ndc = v.pos.xyz() * inv_w;
+        ScreenVertex {
+            x: vp.x + (ndc.x + 1.0) * 0.5 * vp.width,
+            y: vp.y + (1.0 - ndc.y) * 0.5 * vp.height,
+            z: vp.min_depth + ndc.z * (vp.max_depth - vp.min_depth),
+            inv_w,
+            varyings: v.varyings,
+        }
+    }
+
+    fn rasterize_triangle(
+        &self,
+        pipeline: &SoftwarePipeline,
+        pass: &PassState,
+        uniforms: &DrawUniforms,
+        rect: ClipRect,
+        tri: [ScreenVertex; 3],
+    ) {
+        let [a, mut b, mut c] = tri;
+        let mut area = edge(&a, &b, &c);
+        if area == 0.0 || !area.is_finite() {
+            return;
+        }
+        // counter-clockwise in NDC is the front face, that is clockwise on the
+        // (y-down) target, which gives a negative area here
+        let front = area < 0.0;
+        match pipeline.cull_mode {
+            CullMode::Back if !front => return,
+            CullMode::Front if front => return,
+            _ => {}
+        }
+        if area < 0.0 {
+            std::mem::swap(&mut b, &mut c);
+            area = -area;
+        }
+        let stencil = pipeline
+            .stencil
+            .filter(|_| pass.depth.as_ref().is_some_and(|d| d.format.has_stencil()))
+            .map(|s| (s, if front { s.front } else { s.back }));
+
+        let x0 = (a.x.min(b.x).min(c.x).floor() as i32).max(rect.x0);
+        let y0 = (a.y.min(b.y).min(c.y).floor() as i32).max(rect.y0);
+        let x1 = (a.x.max(b.x).max(c.x).ceil() as i32 + 1).min(rect.x1);
+        let y1 = (a.y.max(b.y).max(c.y).ceil() as i32 + 1).min(rect.y1);
+
+        let edges = [(&b, &c), (&c, &a), (&a, &b)];
+        let top_left = edges.map(|(p, q)| is_top_left(p, q));
+
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let p = (x as f32 + 0.5, y as f32 + 0.5);
+                let w = edges.map(|(p0, p1)| edge_at(p0, p1, p));
+                if (0..3).any(|i| w[i] < 0.0 || (w[i] == 0.0 && !top_left[i])) {
+                    continue;
+                }
+                let l = w.map(|w| w / area);
+                let z = (l[0] * a.z + l[1] * b.z + l[2] * c.z).clamp(0.0, 1.0);
+
+                let depth_idx = pass.depth.as_ref().map(|d| {
+                    let layer_size = SoftwareTexture::texel_count(d.width, d.height);
+                    pass.depth_layer as usize * layer_size
+                        + y as usize * d.width as usize
+                        + x as usize
+                });
+                if let (Some(depth), Some(idx)) = (&pass.depth, depth_idx) {
+                    let stored = depth.texels.borrow()[idx];
+                    if let Some((state, face)) = &stencil {
+                        let value = stored[1] as u8;
+                        if !compare(
+                            face.compare,
+                            (state.reference & state.read_mask) as f32,
+                            (value & state.read_mask) as f32,
+                        ) {
+                            depth.texels.borrow_mut()[idx][1] =
+                                stencil_update(face.fail_op, value, state) as f32;
+                            continue;
+                        }
+                    }
+                    if !compare(pipeline.depth_compare, z, stored[0]) {
+                        if let Some((state, face)) = &stencil {
+                            depth.texels.borrow_mut()[idx][1] =
+                                stencil_update(face.depth_fail_op, stored[1] as u8, state) as f32;
+                        }
+                        continue;
+                    }
+                }
+
+                let persp = [l[0] * a.inv_w, l[1] * b.inv_w, l[2] * c.inv_w];
+                let sum = persp[0] + persp[1] + persp[2];
+                let varyings = Varyings::weighted(
+                    [&a.varyings, &b.varyings, &c.varyings],
+                    persp.map(|p| p / sum),
+                );
+
+                let frag = glm::vec2(p.0, p.1);
+                let Some(out) = self.run_fragment(pipeline.program, uniforms, frag, &varyings)
+                else {
+                    continue;
+                };
+
+                if let (Some(depth), Some(idx)) = (&pass.depth, depth_idx)
+                    && pipeline.depth_write
+                    && pass.depth_write
+                {
+                    depth.texels.borrow_mut()[idx][0] = z;
+                }
+                if let (Some(depth), Some(idx), Some((state, face))) =
+                    (&pass.depth, depth_idx, &stencil)
+                {
+                    let mut texels = depth.texels.borrow_mut();
+                    texels[idx][1] =
+                        stencil_update(face.pass_op, texels[idx][1] as u8, state) as f32;
+                }
+                self.write_colors(pipeline, pass, x, y, &out);
+            }
+        }
+    }
+
+    // Built-in shaders
+
+    fn run_vertex(
+        &self,
+        program: SoftwareProgram,
+        uniforms: &DrawUniforms,
+        v: &Vertex,
+    ) -> ClipVertex {
+        let local = glm::vec4(v.position.x, v.position.y, v.position.z, 1.0);
+        let world_pos = self.model * local;
+        match program {
+            SoftwareProgram::Shadow => ClipVertex {
+                pos: uniforms.light_space_matrix * world_pos,
+                varyings: Varyings::zero(),
+            },
+            SoftwareProgram::Skybox => {
+                let pos = uniforms.proj * uniforms.view * world_pos;
+                ClipVertex {
+                    // .xyww puts the skybox on the far plane
+                    pos: glm::vec4(pos.x, pos.y, pos.w, pos.w),
+                    varyings: Varyings {
+                        world_pos: local,
+                        ..Varyings::zero()
+                    },
+                }
+            }
+            _ => {
+                // same (transposed) normal matrix as pre_vertex.slang / vertex.slang
+                let normal_mat = glm::transpose(&glm::mat4_to_mat3(&self.model));
+                let tangent =
+                    safe_normalize(&(v.tangent - v.normal * glm::dot(&v.tangent, &v.normal)));
+                ClipVertex {
+                    pos: uniforms.proj * uniforms.view * world_pos,
+                    varyings: Varyings {
+                        world_pos,
+                        prev_world_pos: self.prev_model * local,
+                        tex_coord: v.tex_coord,
+                        tangent: safe_normalize(&(normal_mat * tangent)),
+                        bitangent: safe_normalize(&(normal_mat * v.bitangent)),
+                        normal: safe_normalize(&(normal_mat * v.normal)),
+                    },
+                }
+            }
+        }
+    }
+
+    /// Returns `None` if the fragment is discarded.
+    fn run_fragment(
+        &self,
+        program: SoftwareProgram,
+        uniforms: &DrawUniforms,
+        frag: glm::Vec2,
+        v: &Varyings,
+    ) -> Option<FragmentOutput> {
+        let mut out = [[0.0; 4]; MAX_COLOR_TARGETS];
+        match program {
+            SoftwareProgram::DeferredPre => {
+                let surface = self.sample_surface(v);
+                let n = surface.normal;
+                out[0] = v.world_pos.into();
+                out[1] = [n.x, n.y, n.z, surface.roughness];
+                out[2] = [
+                    surface.albedo.x,
+                    surface.albedo.y,
+                    surface.albedo.z,
+                    surface.metallic,
+                ];
+                // Screen UV offset since the previous frame (pre_pixel.slang)
+                let cur = uniforms.motion.view_proj * v.world_pos;
+                let prev = uniforms.motion.prev_view_proj * v.prev_world_pos;
+                let ndc = cur.xy() / cur.w - prev.xy() / prev.w;
+                out[3] = [0.5 * ndc.x, -0.5 * ndc.y, 0.0, 0.0];
+            }
+            SoftwareProgram::Forward => {
+                let surface = self.sample_surface(v);
+                if surface.alpha < 0.01 {
+                    return None;
+                }
+                let color = self.shade_surface(
+                    uniforms,
+                    frag,
+                    &v.world_pos,
+                    &surface.normal,
+                    &surface.albedo,
+                    surface.metallic,
+                    surface.roughness,
+                    1.0,
+                );
+                out[0] = [color.x, color.y, color.z, surface.alpha];
+            }
+            SoftwareProgram::Skybox => {
+                let sky = self
+                    .slot(0)
+                    .filter(|t| t.is_cubemap)
+                    .map(|t| t.sample_cube(v.world_pos.xyz()))
+                    .unwrap_or([0.0; 4]);
+                out[0] = [sky[0], sky[1], sky[2], 1.0];
+            }
+            _ => {}
+        }
+        Some(out)
+    }
+
+    /// Material lookup shared by the deferred pre-pass and the forward pass.
+    fn sample_surface(&self, v: &Varyings) -> Surface {
+        let sample = |slot| {
+            self.slot(slot)
+                .map(|t| t.sample(v.tex_coord))
+                .unwrap_or([1.0; 4])
+        };
+        let albedo = sample(0);
+        let mr = sample(1);
+        let normal = sample(2);
+
+        let normal = safe_normalize(
+            &(glm::vec3(normal[0], normal[1], normal[2]) * 2.0 - glm::vec3(1.0, 1.0, 1.0)),
+        );
+        // mul(normal, TBN) with the rows T, B, N
+        let normal =
+            safe_normalize(&(v.tangent * normal.x + v.bitangent * normal.y + v.normal * normal.z));
+
+        Surface {
+            albedo: glm::vec3(albedo[0], albedo[1], albedo[2]).map(|c| srgb_to_linear(c, GAMMA)),
+            alpha: albedo[3],
+            normal,
+            // `.gb` of the metallic-roughness texture
+            metallic: mr[1],
+            roughness: mr[2],
+        }
+    }
+
+    /// Lighting of one surface point by the lights of its cluster (pixel.slang /
+    /// light_pixel.slang). `ambient_occlusion` scales the ambient lights.
+    #[allow(clippy::too_many_arguments)]
+    fn shade_surface(
+        &self,
+        uniforms: &DrawUniforms,
+        frag: glm::Vec2,
+        pos: &glm::Vec4,
+        normal: &glm::Vec3,
+        albedo: &glm::Vec3,
+        metallic: f32,
+        roughness: f32,
+        ambient_occlusion: f32,
+    ) -> glm::Vec3 {
+        let f0 = glm::lerp(&glm::vec3(0.04, 0.04, 0.04), albedo, metallic);
+        let view = safe_normalize(&(uniforms.camera_pos - pos.xyz()));
+
+        let clusters = &uniforms.clusters;
+        let list = clusters.list_offset(clusters.cluster_at(frag, pos));
+        let cluster_lights = uniforms.cluster_lights.borrow();
+        let read = |i: usize| {
+            cluster_lights
+                .get(list + 4 * i..list + 4 * i + 4)
+                .map_or(0, |b| u32::from_le_bytes(b.try_into().unwrap()))
+        };
+        let count = read(0).min(clusters.max_cluster_lights) as usize;
+        (1..=count)
+            .filter_map(|i| uniforms.lights.get(read(i) as usize))
+            .map(|light| {
+                if light.kind == LIGHT_AMBIENT {
+                    return albedo * 0.15 * ambient_occlusion;
+                }
+                let shadowed = if light.shadow_tile.is_some() || light.shadow_cube.is_some() {
+                    self.shadow(light, pos, normal)
+                } else {
+                    1.0
+                };
+                brdf(light, &view, normal, &pos.xyz(), albedo, &f0, roughness) * shadowed
+            })
+            .sum()
+    }
+
+    /// PCF lookup (shadow.slang) into the shadow cube array in slot 6 for point
+    /// lights, else into the shadow atlas in slot 3: the cascades of directional
+    /// lights and the view of spot lights, in the tiles following `shadow_tile`.
+    fn shadow(&self, light: &LightUniforms, pos: &glm::Vec4, normal: &glm::Vec3) -> f32 {
+        let view_coords = |view: usize, pos: &glm::Vec4| {
+            let fragment_ls = light.light_space[view] * pos;
+            glm::vec3(
+                0.5 + fragment_ls.x / fragment_ls.w * 0.5,
+                0.5 - fragment_ls.y / fragment_ls.w * 0.5,
+                fragment_ls.z / fragment_ls.w,
+            )
+        };
+        let inside = |c: &glm::Vec3| {
+            let tile = SHADOW_TILE_BORDER..=1.0 - SHADOW_TILE_BORDER;
+            tile.contains(&c.x) && tile.contains(&c.y) && c.z > 0.0 && c.z <= 1.0
+        };
+        let visibility = |c: glm::Vec3, view: usize, bias: f32| -> f32 {
+            let (Some(shadow_map), Some(first_tile)) = (self.slot(3), light.shadow_tile) else {
+                return 1.0;
+            };
+            let tile = first_tile as usize + view;
+            let columns = SHADOW_ATLAS_COLUMNS as usize;
+            let tile = glm::vec2((tile % columns) as f32, (tile / columns) as f32);
+            POISSON_DISK
+                .iter()
+                .map(|offset| {
+                    let uv = (c.xy() + glm::vec2(offset[0], offset[1]) / 500.0 + tile)
+                        .component_div(&glm::Vec2::from(SHADOW_ATLAS_GRID));
+                    shadow_map.sample_compare(uv, c.z - bias).clamp(0.0, 1.0)
+                        / POISSON_DISK.len() as f32
+                })
+                .sum()
+        };
+
+        if light.kind == LIGHT_POINT || light.kind == LIGHT_SPOT {
+            let to_pos = pos.xyz() - light.position;
+            // Cube face along the major axis, in the order +X, -X, +Y, -Y, +Z, -Z
+            let a = to_pos.abs();
+            let view = match light.kind {
+                LIGHT_SPOT => 0,
+                _ if a.x >= a.y && a.x >= a.z => usize::from(to_pos.x <= 0.0),
+                _ if a.y >= a.z => 2 + usize::from(to_pos.y <= 0.0),
+                _ => 4 + usize::from(to_pos.z <= 0.0),
+            };
+            // Normal offset instead of a depth bias
+            let distance = glm::length(&to_pos);
+            let n_dot_l = glm::dot(normal, &(-to_pos / distance.max(0.0001))).clamp(0.0, 1.0);
+            let offset = 0.004 * distance * (1.0 + 2.0 * (1.0 - n_dot_l));
+            let offset_pos = pos.xyz() + normal * offset;
+            let c = view_coords(
+                view,
+                &glm::vec4(offset_pos.x, offset_pos.y, offset_pos.z, 1.0),
+            );
+            if c.z <= 0.0 || c.z > 1.0 || (light.kind == LIGHT_SPOT && !inside(&c)) {
+                return 1.0;
+            }
+            if light.kind == LIGHT_SPOT {
+                return visibility(c, view, 0.00002);
+            }
+            let (Some(shadow_cubes), Some(cube)) = (self.slot(6), light.shadow_cube) else {
+                return 1.0;
+            };
+            // Poisson offsets in the plane facing the light, about the texel
+            // footprint the atlas lookups use
+            let dir = offset_pos - light.position;
+            let axis = if dir.y.abs() < 0.99 * glm::length(&dir) {
+                glm::Vec3::y()
+            } else {
+                glm::Vec3::x()
+            };
+            let tangent = glm::normalize(&glm::cross(&dir, &axis));
+            let bitangent = glm::normalize(&glm::cross(&dir, &tangent));
+            let scale = 2.0 / 500.0 * glm::length(&dir);
+            return POISSON_DISK
+                .iter()
+                .map(|offset| {
+                    let dir = dir + (tangent * offset[0] + bitangent * offset[1]) * scale;
+                    shadow_cubes
+                        .sample_cube_compare(cube as usize, dir, c.z - 0.00002)
+                        .clamp(0.0, 1.0)
+                        / POISSON_DISK.len() as f32
+                })
+                .sum();
+        }
+
+        let bias = (0.005 * (1.0 - glm::dot(normal, &-light.position))).max(0.0005);
+        let count = (light.shadow_view_count as usize).min(MAX_SHADOW_VIEWS);
+        let coords = |cascade: usize| Some(view_coords(cascade, pos)).filter(inside);
+        let Some((cascade, c)) = (0..count).find_map(|i| coords(i).map(|c| (i, c))) else {
+            return 1.0;
+        };
+        let shadowed = visibility(c, cascade, bias);
+        let edge = c.x.min(c.y).min(1.0 - c.x).min(1.0 - c.y) - SHADOW_TILE_BORDER;
+        let fade = edge / light.cascade_blend;
+        let next = (fade < 1.0 && cascade + 1 < count)
+            .then(|| coords(cascade + 1))
+            .flatten();
+        match next {
+            Some(next) => glm::lerp_scalar(visibility(next, cascade + 1, bias), shadowed, fade),
+            None => shadowed,
+        }
+    }
+
+    fn shade_deferred_light(&self, uniforms: &DrawUniforms, x: i32, y: i32) -> [f32; 4] {
+        let load = |slot| self.slot(slot).map(|t| t.load(x, y)).unwrap_or([0.0; 4]);
+        let pos = load(0);
+        let normal_roughness = load(1);
+        let albedo_metallic = load(2);
+
+        let pos = glm::vec4(pos[0], pos[1], pos[2], pos[3]);
+        if glm::length(&pos.xyz()) == 0.0 {
+            return [0.0; 4];
+        }
+        let normal = glm::vec3(
+            normal_roughness[0],
+            normal_roughness[1],
+            normal_roughness[2],
+        );
+        let albedo = glm::vec3(albedo_metallic[0], albedo_metallic[1], albedo_metallic[2]);
+        let ambient_occlusion = if uniforms.ssao {
+            self.slot(5).map_or(1.0, |t| t.load(x, y)[0])
+        } else {
+            1.0
+        };
+        let color = self.shade_surface(
+            uniforms,
+            glm::vec2(x as f32 + 0.5, y as f32 + 0.5),
+            &pos,
+            &normal,
+            &albedo,
+            albedo_metallic[3],
+            normal_roughness[3],
+            ambient_occlusion,
+        );
+        [color.x, color.y, color.z, 1.0]
+    }
+
+    /// Hemisphere SSAO (ssao_pixel.slang): the kernel samples around the G-buffer
+    /// position (slot 0), oriented along its normal (slot 1) and rotated by the
+    /// noise texture (slot 2), tested against the G-buffer depth in view space.
+    fn shade_ssao(&self, ssao: &SsaoUniforms, x: i32, y: i32) -> f32 {
+        let load = |slot, x, y| self.slot(slot).map(|t| t.load(x, y)).unwrap_or([0.0; 4]);
+        let view_pos = |p: [f32; 4]| (ssao.view * glm::vec4(p[0], p[1], p[2], 1.0)).xyz();
+        let pos = load(0, x, y);
+        if glm::length(&glm::vec3(pos[0], pos[1], pos[2])) == 0.0 {
+            return 1.0;
+        }
+        let frag_pos = view_pos(pos);
+        let n = load(1, x, y);
+        let normal = safe_normalize(&(ssao.view * glm::vec4(n[0], n[1], n[2], 0.0)).xyz());
+        let noise = load(2, x.rem_euclid(4), y.rem_euclid(4));
+
+        // Tangent frame around the normal, rotated by the noise
+        let random = glm::vec3(noise[0] * 2.0 - 1.0, noise[1] * 2.0 - 1.0, 0.0);
+        let tangent = safe_normalize(&(random - normal * glm::dot(&random, &normal)));
+        let bitangent = normal.cross(&tangent);
+
+        let mut occlusion = 0.0;
+        for k in ssao.kernel.iter().take(ssao.sample_count as usize) {
+            let sample = frag_pos + (tangent * k.x + bitangent * k.y + normal * k.z) * ssao.radius;
+            let clip = ssao.projection * glm::vec4(sample.x, sample.y, sample.z, 1.0);
+            let uv = glm::vec2(0.5 + 0.5 * clip.x / clip.w, 0.5 - 0.5 * clip.y / clip.w);
+            if clip.w <= 0.0 || !(0.0..1.0).contains(&uv.x) || !(0.0..1.0).contains(&uv.y) {
+                continue;
+            }
+            let texel = uv.component_mul(&ssao.resolution);
+            let occluder = load(0, texel.x as i32, texel.y as i32);
+            if glm::length(&glm::vec3(occluder[0], occluder[1], occluder[2])) == 0.0 {
+                continue;
+            }
+            let sample_depth = view_pos(occluder).z;
+            // Fade out occluders far in front of the sample
+            let range = ssao.radius / (frag_pos.z - sample_depth).abs().max(0.0001);
+            if sample_depth >= sample.z + ssao.bias {
+                occlusion += smoothstep(0.0, 1.0, range);
+            }
+        }
+        (1.0 - occlusion / ssao.sample_count as f32).powf(ssao.intensity)
+    }
+
+    /// 4x4 box blur of the ambient occlusion in slot 0 (ssao_blur_pixel.slang).
+    fn shade_ssao_blur(&self, x: i32, y: i32) -> f32 {
+        let Some(ssao) = self.slot(0) else {
+            return 1.0;
+        };
+        let (max_x, max_y) = (ssao.width as i32 - 1, ssao.height as i32 - 1);
+        let mut sum = 0.0;
+        for dy in -2..2 {
+            for dx in -2..2 {
+                sum += ssao.load((x + dx).clamp(0, max_x), (y + dy).clamp(0, max_y))[0];
+            }
+        }
+        sum / 16.0
+    }
+
+    /// Composite of the deferred (slot 0) and forward (slot 1) results.
+    fn composite(&self, x: i32, y: i32) -> [f32; 3] {
+        let load = |slot| self.slot(slot).map(|t| t.load(x, y)).unwrap_or([0.0; 4]);
+        let def = load(0);
+        let fwd = load(1);
+        std::array::from_fn(|c| fwd[c] * fwd[3] + def[c] * (1.0 - fwd[3]))
+    }
+
+    /// Temporal resolve (taa_pixel.slang): the composite of this frame blended
+    /// with the history (slot 3) at the position the motion vector (slot 2)
+    /// points back to, clamped to the composite's 3x3 neighbourhood.
+    fn shade_taa(&self, taa: &TaaUniforms, x: i32, y: i32) -> [f32; 4] {
+        let current = self.composite(x, y);
+        let opaque = |c: [f32; 3]| [c[0], c[1], c[2], 1.0];
+        let (Some(color), Some(history)) = (self.slot(0), self.slot(3)) else {
+            return opaque(current);
+        };
+        if !taa.history_valid {
+            return opaque(current);
+        }
+
+        let (max_x, max_y) = (color.width as i32 - 1, color.height as i32 - 1);
+        let mut min = current;
+        let mut max = current;
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let c = self.composite((x + dx).clamp(0, max_x), (y + dy).clamp(0, max_y));
+                for i in 0..3 {
+                    min[i] = min[i].min(c[i]);
+                    max[i] = max[i].max(c[i]);
+                }
+            }
+        }
+
+        let velocity = self.slot(2).map_or([0.0; 4], |t| t.load(x, y));
+        let uv = glm::vec2(
+            (x as f32 + 0.5) / color.width as f32 - velocity[0],
+            (y as f32 + 0.5) / color.height as f32 - velocity[1],
+        );
+        // Came from off screen: nothing to blend with
+        if !(0.0..=1.0).contains(&uv.x) || !(0.0..=1.0).contains(&uv.y) {
+            return opaque(current);
+        }
+        let previous = history.sample(uv);
+        opaque(std::array::from_fn(|i| {
+            let clamped = previous[i].clamp(min[i], max[i]);
+            current[i] + (clamped - current[i]) * taa.feedback
+        }))
+    }
+
+    /// Tonemapping of the composite (blend.slang), or of the TAA result in slot 0
+    /// while `taa` is set.
+    fn shade_output(&self, taa: bool, is_hdr: bool, x: i32, y: i32) -> [f32; 4] {
+        let col = if taa {
+            let resolved = self.slot(0).map_or([0.0; 4], |t| t.load(x, y));
+            [resolved[0], resolved[1], resolved[2]]
+        } else {
+            self.composite(x, y)
+        };
+        let col = if is_hdr {
+            linear_to_hdr10(col)
+        } else {
+            col.map(|c| linear_to_srgb(aces(c), GAMMA))
+        };
+        [col[0], col[1], col[2], 1.0]
+    }
+}
+
+struct Surface {
+    albedo: glm::Vec3,
+    alpha: f32,
+    normal: glm::Vec3,
+    metallic: f32,
+    roughness: f32,
+}
+
+// Rasterization helpers
+
+fn edge(a: &ScreenVertex, b: &ScreenVertex, c: &ScreenVertex) -> f32 {
+    edge_at(a, b, (c.x, c.y))
+}
+
+fn edge_at(a: &ScreenVertex, b: &ScreenVertex, p: (f32, f32)) -> f32 {
+    (b.x - a.x) * (p.1 - a.y) - (b.y - a.y) * (p.0 - a.x)
+}
+
+/// Top-left fill rule for an edge of a triangle with positive area (y down).
+fn is_top_left(a: &ScreenVertex, b: &ScreenVertex) -> bool {
+    (a.y == b.y && b.x > a.x) || b.y < a.y
+}
+
+/// Clip a polygon against the view volume `-w <= x, y <= w`, `0 <= z <= w`.
+fn clip_polygon(polygon: Vec<ClipVertex>) -> Vec<ClipVertex> {
+    let planes: [fn(&glm::Vec4) -> f32; 6] = [
+        |p| p.w + p.x,
+        |p| p.w - p.x,
+        |p| p.w + p.y,
+        |p| p.w - p.y,
+        |p| p.z,
+        |p| p.w - p.z,
+    ];
+    planes.iter().fold(polygon, |polygon, distance| {
+        let mut out = Vec::with_capacity(polygon.len() + 1);
+        for (i, current) in polygon.iter().enumerate() {
+            let next = &polygon[(i + 1) % polygon.len()];
+            let (dc, dn) = (distance(&current.pos), distance(&next.pos));
+            if dc >= 0.0 {
+                out.push(*current);
+            }
+            if (dc >= 0.0) != (dn >= 0.0) {
+                let t = dc / (dc - dn);
+                out.push(ClipVertex {
+                    pos: current.pos + (next.pos - current.pos) * t,
+                    varyings: Varyings::lerp(&current.varyings, &next.varyings, t),
+                });
+            }
+        }
+        out
+    })
+}
+
+fn compare(func: CompareFunc, value: f32, reference: f32) -> bool {
+    match func {
+        CompareFunc::Never => false,
+        CompareFunc::Less => value < reference,
+        CompareFunc::LessEqual => value <= reference,
+        CompareFunc::Equal => value == reference,
+        CompareFunc::GreaterEqual => value >= reference,
+        CompareFunc::Greater => value > reference,
+        CompareFunc::Always => true,
+    }
+}
+
+/// Apply a stencil operation, keeping the bits outside the write mask.
+fn stencil_update(op: StencilOp, value: u8, state: &StencilState) -> u8 {
+    let updated = match op {
+        StencilOp::Keep => value,
+        StencilOp::Zero => 0,
+        StencilOp::Replace => state.reference,
+        StencilOp::IncrementClamp => value.saturating_add(1),
+        StencilOp::DecrementClamp => value.saturating_sub(1),
+        StencilOp::Invert => !value,
+        StencilOp::IncrementWrap => value.wrapping_add(1),
+        StencilOp::DecrementWrap => value.wrapping_sub(1),
+    };
+    (value & !state.write_mask) | (updated & state.write_mask)
+}
+
+fn blend(mode: BlendMode, src: [f32; 4], dst: [f32; 4]) -> [f32; 4] {
+    match mode {
+        BlendMode::None => src,
+        BlendMode::Additive => [src[0] + dst[0], src[1] + dst[1], src[2] + dst[2], src[3]],
+        BlendMode::Alpha => {
+            let a = src[3];
+            [
+                src[0] * a + dst[0] * (1.0 - a),
+                src[1] * a + dst[1] * (1.0 - a),
+                src[2] * a + dst[2] * (1.0 - a),
+                a + dst[3] * (1.0 - a),
+            ]
+        }
+    }
+}
+
+fn address(coord: i32, size: u32, mode: AddressMode) -> usize {
+    let size = size as i32;
+    let coord = match mode {
+        AddressMode::Repeat => coord.rem_euclid(size),
+        AddressMode::Mirror => {
+            let period = coord.rem_euclid(2 * size);
+            if period < size {
+                period
+            } else {
+                2 * size - 1 - period
+            }
+        }
+        AddressMode::Clamp => coord.clamp(0, size - 1),
+    };
+    coord as usize
+}
+
+fn safe_normalize(v: &glm::Vec3) -> glm::Vec3 {
+    let len = glm::length(v);
+    if len > 0.0 {
+        v / len
+    } else {
+        glm::Vec3::zeros()
+    }
+}
+
+// Shading helpers (pbr.slang, color_utils.slang)
+
+/// Direction towards the light and its attenuation at `position` (`incidentLight`
+/// in light.slang).
+fn incident_light(light: &LightUniforms, position: &glm::Vec3) -> (glm::Vec3, f32) {
+    if light.kind == LIGHT_DIRECTIONAL {
+        return (safe_normalize(&-light.position), 1.0);
+    }
+    let l = light.position - position;
+    let distance = glm::length(&l);
+    let l = l / distance.max(0.0001);
+    if light.kind == LIGHT_AREA {
+        return (l, light.radius / (distance * distance));
+    }
+    // Inverse square falloff, windowed to reach zero at the range
+    let ratio = distance / light.range;
+    let window = (1.0 - ratio.powi(4)).clamp(0.0, 1.0);
+    let mut attenuation = window * window / (distance * distance).max(0.0001);
+    if light.kind == LIGHT_SPOT {
+        let cone = (glm::dot(&light.direction, &-l) * light.spot_scale + light.spot_offset)
+            .clamp(0.0, 1.0);
+        attenuation *= cone * cone;
+    }
+    (l, attenuation)
+}
+
+fn brdf(
+    light: &LightUniforms,
+    v: &glm::Vec3,
+    n: &glm::Vec3,
+    position: &glm::Vec3,
+    albedo: &glm::Vec3,
+    f0: &glm::Vec3,
+    roughness: f32,
+) -> glm::Vec3 {
+    let (l, attenuation) = incident_light(light, position);
+    let radiance = light.color * attenuation;
+
+    let h = safe_normalize(&(l + v));
+
+    let dot_nv = glm::dot(n, v).abs().clamp(0.001, 1.0);
+    let dot_nl = glm::dot(n, &l).clamp(0.001, 1.0);
+    let dot_nh = glm::dot(n, &h).clamp(0.0, 1.0);
+    let dot_hv = glm::dot(&l, &h).clamp(0.0, 1.0);
+
+    // normal distribution (GGX)
+    let alpha = roughness * roughness;
+    let alpha2 = alpha * alpha;
+    let denom = dot_nh * dot_nh * (alpha2 - 1.0) + 1.0;
+    let d = alpha2 / (PI * denom * denom);
+
+    // geometric shadowing (Schlick-Smith GGX)
+    let r = roughness + 1.0;
+    let k = (r * r) / 8.0;
+    let gl = dot_nl / (dot_nl * (1.0 - k) + k);
+    let gv = dot_nv / (dot_nv * (1.0 - k) + k);
+    let g = gl * gv;
+
+    // fresnel (Schlick)
+    let f = f0 + (glm::vec3(1.0, 1.0, 1.0) - f0) * (1.0 - dot_hv).powf(5.0);
+
+    let k_d = glm::vec3(1.0, 1.0, 1.0) - f;
+    let diffuse = k_d.component_mul(&(albedo / PI)) * dot_nl;
+    let specular = f * g * d / (4.0 * dot_nv);
+
+    (diffuse + specular).component_mul(&radiance)
+}
+
+fn srgb_to_linear(c: f32, gamma: f32) -> f32 {
+    if c < 0.040_448_237 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(gamma)
+    }
+}
+
+fn linear_to_srgb(c: f32, gamma: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / gamma) - 0.055
+    }
+}
+
+/// Hermite interpolation between 0 and 1 (`smoothstep`).
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn aces(c: f32) -> f32 {
+    ((c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14)).clamp(0.0, 1.0)
+}
+
+/// Rec.709 to Rec.2020, then the ST.2084 (PQ) curve (`liearToHdr10`).
+fn linear_to_hdr10(color: [f32; 3]) -> [f32; 3] {
+    const FROM_709_TO_2020: [[f32; 3]; 3] = [
+        [0.627404, 0.329282, 0.0433136],
+        [0.069097, 0.91954, 0.0113612],
+        [0.0163916, 0.0880132, 0.895595],
+    ];
+    const M1: f32 = 2610.0 / 4096.0 / 4.0;
+    const M2: f32 = 2523.0 / 4096.0 * 128.0;
+    const C1: f32 = 3424.0 / 4096.0;
+    const C2: f32 = 2413.0 / 4096.0 * 32.0;
+    const C3: f32 = 2392.0 / 4096.0 * 32.0;
+
+    FROM_709_TO_2020.map(|row| {
+        let mapped = (row[0] * color[0] + row[1] * color[1] + row[2] * color[2])
+            * HDR10_WHITE_POINT
+            / 10000.0;
+        let cp = mapped.abs().powf(M1);
+        ((C1 + C2 * cp) / (1.0 + C3 * cp)).powf(M2)
+    })
+}
+
+// Texel conversion
+
+/// Standard sRGB transfer function, as applied by `*Srgb` image formats.
+fn srgb_encode(c: f32) -> f32 {
+    linear_to_srgb(c.clamp(0.0, 1.0), 2.4)
+}
+
+fn srgb_decode(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn unorm(v: f32, max: f32) -> u32 {
+    (v.clamp(0.0, 1.0) * max + 0.5) as u32
+}
+
+/// Round a texel to what a `format` attachment would store.
+fn quantize(format: TextureFormat, c: [f32; 4]) -> [f32; 4] {
+    let unorm8 = |v: f32| unorm(v, 255.0) as f32 / 255.0;
+    match format {
+        TextureFormat::R8Unorm => [unorm8(c[0]), 0.0, 0.0, 1.0],
+        TextureFormat::Rg8Unorm => [unorm8(c[0]), unorm8(c[1]), 0.0, 1.0],
+        TextureFormat::Rgba8Unorm | TextureFormat::Bgra8Unorm => c.map(unorm8),
+        TextureFormat::Rgba8UnormSrgb | TextureFormat::Bgra8UnormSrgb => {
+            let srgb = |v: f32| srgb_decode(unorm8(srgb_encode(v)));
+            [srgb(c[0]), srgb(c[1]), srgb(c[2]), unorm8(c[3])]
+        }
+        TextureFormat::Abgr10Unorm => {
+            let unorm10 = |v: f32| unorm(v, 1023.0) as f32 / 1023.0;
+            [
+                unorm10(c[0]),
+                unorm10(c[1]),
+                unorm10(c[2]),
+                unorm(c[3], 3.0) as f32 / 3.0,
+            ]
+        }
+        TextureFormat::R16g16b16a16Float => c.map(|v| f16::from_f32(v).to_f32()),
+        TextureFormat::Rgba32Float => c,
+        TextureFormat::Rgba32Uint => c.map(|v| v.max(0.0).trunc()),
+        TextureFormat::Depth32Float => [c[0].clamp(0.0, 1.0), 0.0, 0.0, 0.0],
+        // stencil in g
+        TextureFormat::Depth24Stencil8 => [
+            c[0].clamp(0.0, 1.0),
+            c[1].clamp(0.0, 255.0).round(),
+            0.0,
+            0.0,
+        ],
+        // rejected at creation
+        compressed => unreachable!("{compressed:?} texel"),
+    }
+}
+
+/// Decode one texel of upload data.
+fn decode_texel(format: TextureFormat, p: &[u8]) -> [f32; 4] {
+    let unorm8 = |v: u8| v as f32 / 255.0;
+    let word = |i: usize| u32::from_le_bytes([p[i], p[i + 1], p[i + 2], p[i + 3]]);
+    match format {
+        TextureFormat::R8Unorm => [unorm8(p[0]), 0.0, 0.0, 1.0],
+        TextureFormat::Rg8Unorm => [unorm8(p[0]), unorm8(p[1]), 0.0, 1.0],
+        TextureFormat::Rgba8Unorm => [p[0], p[1], p[2], p[3]].map(unorm8),
+        TextureFormat::Bgra8Unorm => [p[2], p[1], p[0], p[3]].map(unorm8),
+        TextureFormat::Rgba8UnormSrgb | TextureFormat::Bgra8UnormSrgb => {
+            let (r, b) = if format == TextureFormat::Rgba8UnormSrgb {
+                (p[0], p[2])
+            } else {
+                (p[2], p[0])
+            };
+            let srgb = |v| srgb_decode(unorm8(v));
+            [srgb(r), srgb(p[1]), srgb(b), unorm8(p[3])]
+        }
+        TextureFormat::Abgr10Unorm => {
+            let v = word(0);
+            [
+                (v & 0x3ff) as f32 / 1023.0,
+                ((v >> 10) & 0x3ff) as f32 / 1023.0,
+                ((v >> 20) & 0x3ff) as f32 / 1023.0,
+                (v >> 30) as f32 / 3.0,
+            ]
+        }
+        TextureFormat::R16g16b16a16Float => {
+            [0, 2, 4, 6].map(|i| f16::from_bits(u16::from_le_bytes([p[i], p[i + 1]])).to_f32())
+        }
+        TextureFormat::Rgba32Float => [0, 4, 8, 12].map(|i| f32::from_bits(word(i))),
+        TextureFormat::Rgba32Uint => [0, 4, 8, 12].map(|i| word(i) as f32),
+        TextureFormat::Depth32Float | TextureFormat::Depth24Stencil8 => [0.0; 4],
+        compressed => unreachable!("{compressed:?} texel"),
+    }
+}
+
+/// Encode one stored texel in the native byte layout of `format`.
+fn encode_texel(format: TextureFormat, c: [f32; 4], out: &mut Vec<u8>) {
+    let unorm8 = |v: f32| unorm(v, 255.0) as u8;
+    match format {
+        TextureFormat::R8Unorm => out.push(unorm8(c[0])),
+        TextureFormat::Rg8Unorm => out.extend([unorm8(c[0]), unorm8(c[1])]),
+        TextureFormat::Rgba8Unorm => out.extend(c.map(unorm8)),
+        TextureFormat::Bgra8Unorm => out.extend([c[2], c[1], c[0], c[3]].map(unorm8)),
+        TextureFormat::Rgba8UnormSrgb => out.extend(
+            [
+                srgb_encode(c[0]),
+                srgb_encode(c[1]),
+                srgb_encode(c[2]),
+                c[3],
+            ]
+            .map(unorm8),
+        ),
+        TextureFormat::Bgra8UnormSrgb => out.extend(
+            [
+                srgb_encode(c[2]),
+                srgb_encode(c[1]),
+                srgb_encode(c[0]),
+                c[3],
+            ]
+            .map(unorm8),
+        ),
+        TextureFormat::Abgr10Unorm => {
+            let packed = unorm(c[0], 1023.0)
+                | unorm(c[1], 1023.0) << 10
+                | unorm(c[2], 1023.0) << 20
+                | unorm(c[3], 3.0) << 30;
+            out.extend(packed.to_le_bytes());
+        }
+        TextureFormat::R16g16b16a16Float => {
+            for v in c {
+                out.extend(f16::from_f32(v).to_bits().to_le_bytes());
+            }
+        }
+        TextureFormat::Rgba32Float => {
+            for v in c {
+                out.extend(v.to_le_bytes());
+            }
+        }
+        TextureFormat::Rgba32Uint => {
+            for v in c {
+                out.extend((v as u32).to_le_bytes());
+            }
+        }
+        TextureFormat::Depth32Float | TextureFormat::Depth24Stencil8 => {
+            out.extend(c[0].to_le_bytes())
+        }
+        compressed => unreachable!("{compressed:?} texel"),
+    }
+}
+
+// Uniform decoding, missing bytes read as zero
+
+fn read_f32(bytes: &[u8], offset: usize) -> f32 {
+    f32::from_bits(read_u32(bytes, offset))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .unwrap_or(0)
+}
+
+fn read_vec3(bytes: &[u8], offset: usize) -> glm::Vec3 {
+    glm::vec3(
+        read_f32(bytes, offset),
+        read_f32(bytes, offset + 4),
+        read_f32(bytes, offset + 8),
+    )
+}
+
+/// Column-major, as uploaded with `as_bytes(&glm::Mat4)`.
+fn read_mat4(bytes: &[u8], offset: usize) -> glm::Mat4 {
+    glm::Mat4::from_fn(|row, col| read_f32(bytes, offset + (col * 4 + row) * 4))
+}
+
+impl GpuBackend for SoftwareBackend {
+    type Texture = SoftwareTexture;
+
+    type RenderTarget = SoftwareTexture;
+
+    type Buffer = SoftwareBuffer;
+
+    type Pipeline = SoftwarePipeline;
+
+    type ShaderSource = SoftwareProgram;
+
+    type AccelerationStructure = SoftwareAccelerationStructure;
+
+    fn load_shaders(&self) -> Shaders<Self> {
+        Shaders {
+            deferred_pre: SoftwareProgram::DeferredPre,
+            shadow: SoftwareProgram::Shadow,
+            ssao: SoftwareProgram::Ssao,
+            ssao_blur: SoftwareProgram::SsaoBlur,
+            deferred_light: SoftwareProgram::DeferredLight,
+            forward: SoftwareProgram::Forward,
+            taa: SoftwareProgram::Taa,
+            output: SoftwareProgram::Output,
+            skybox: SoftwareProgram::Skybox,
+            light_culling: SoftwareProgram::LightCulling,
+        }
+    }
+
+    fn load_proc_gen_shaders(&self) -> ProceduralShaders<Self> {
+        ProceduralShaders {
+            scattering: SoftwareProgram::Unsupported("scattering"),
+        }
+    }
+
+    fn create_texture(&self, desc: &TextureDesc, data: &[u8]) -> Result<Self::Texture, GpuError> {
+        Self::check_size(desc.width, desc.height, "texture")?;
+        desc.validate(data)?;
+        let is_cubemap = match desc.dimension {
+            TextureDimension::D2 => false,
+            TextureDimension::Cube => true,
+            other => return Err(Self::not_supported(&format!("{other:?} texture"))),
+        };
+        // Only mip level 0 is kept; it comes first and holds every layer.
+        let texel_count = SoftwareTexture::texel_count(desc.width, desc.height);
+        let texels = Self::decode_pixels(
+            data,
+            texel_count * desc.depth_or_layers as usize,
+            desc.format,
+        )?;
+        let mut texture =
+            SoftwareTexture::new(desc.width, desc.height, desc.format, desc.sampler, texels);
+        texture.bindless_index = self.allocate_bindless();
+        texture.is_cubemap = is_cubemap;
+        Ok(texture)
+    }
+
+    fn create_layer_view(
+        &self,
+        texture: &Self::Texture,
+        layer: u32,
+    ) -> Result<Self::Texture, GpuError> {
+        let layers = if texture.is_cubemap { 6 } else { 1 };
+        if layer >= layers {
+            return Err(GpuError::new(
+                format!("Layer {layer} is out of range for a texture with {layers} layers"),
+                GpuErrorKind::ResourceCreation,
+            ));
+        }
+        // Views cannot alias part of the shared storage, so the layer is copied.
+        let texel_count = SoftwareTexture::texel_count(texture.width, texture.height);
+        let start = layer as usize * texel_count;
+        let texels = texture.texels.borrow()[start..start + texel_count].to_vec();
+        let mut view = SoftwareTexture::new(
+            texture.width,
+            texture.height,
+            texture.format,
+            texture.sampler,
+            texels,
+        );
+        view.bindless_index = self.allocate_bindless();
+        Ok(view)
+    }
+
+    fn create_buffer(
+        &self,
+        desc: &BufferDesc,
+        data: Option<&[u8]>,
+    ) -> Result<Self::Buffer, GpuError> {
+        if desc.size == 0 {
+            return Err(GpuError::new(
+                format!("Cannot create zero-sized buffer '{}'", desc.label),
+                GpuErrorKind::ResourceCreation,
+            ));
+        }
+        let buffer = SoftwareBuffer {
+            id: next_resource_id(),
+            label: desc.label.clone(),
+            usage: desc.usage,
+            data: Rc::new(RefCell::new(vec![0; desc.size])),
+        };
+        if let Some(data) = data {
+            buffer.write(data);
+        }
+        Ok(buffer)
+    }
+
+    fn create_render_target(
+        &self,
+        desc: &RenderTargetDesc,
+    ) -> Result<Self::RenderTarget, GpuError> {
+        desc.validate()?;
+        Self::check_size(desc.width, desc.height, "render target")?;
+        if desc.sample_count > 1 {
+            return Err(Self::not_supported("Multisampled render target"));
+        }
+        if desc.format.is_compressed() {
+            return Err(Self::not_supported(&format!(
+                "{:?} render target",
+                desc.format
+            )));
+        }
+        let mut target = SoftwareTexture::new(
+            desc.width,
+            desc.height,
+            desc.format,
+            desc.sampler,
+            vec![
+                [0.0; 4];
+                SoftwareTexture::texel_count(desc.width, desc.height) * desc.layers as usize
+            ],
+        );
+        target.bindless_index = self.allocate_bindless();
+        target.usage = Some(desc.usage);
+        target.layers = desc.layers;
+        Ok(target)
+    }
+
+    fn create_render_pipeline(
+        &self,
+        desc: &RenderPipelineDesc<Self::ShaderSource>,
+    ) -> Result<Self::Pipeline, GpuError> {
+        desc.validate()?;
+        if desc.color_target_formats.len() > MAX_COLOR_TARGETS {
+            return Err(GpuError::new(
+                format!(
+                    "Pipeline '{}' has {} color targets, the software backend supports {MAX_COLOR_TARGETS}",
+                    desc.label,
+                    desc.color_target_formats.len()
+                ),
+                GpuErrorKind::ResourceCreation,
+            ));
+        }
+        Ok(SoftwarePipeline {
+            id: next_resource_id(),
+            label: desc.label.to_string(),
+            kind: SoftwarePipelineKind::Render,
+            program: *desc.shader_source,
+            blend_mode: desc.blend_mode,
+            cull_mode: desc.cull_mode,
+            depth_write: desc.depth_write,
+            depth_compare: desc.depth_compare,
+            stencil: desc.stencil,
+            color_target_count: desc.color_target_formats.len(),
+        })
+    }
+
+    fn create_compute_pipeline(
+        &self,
+        desc: &ComputePipelineDesc<Self::ShaderSource>,
+    ) -> Result<Self::Pipeline, GpuError> {
+        Ok(SoftwarePipeline {
+            id: next_resource_id(),
+            label: desc.label.to_string(),
+            kind: SoftwarePipelineKind::Compute,
+            program: *desc.shader_source,
+            blend_mode: BlendMode::None,
+            cull_mode: CullMode::None,
+            depth_write: false,
+            depth_compare: CompareFunc::Always,
+            stencil: None,
+            color_target_count: 0,
+        })
+    }
+
+    fn dispatch_compute_one_shot(
+        &self,
+        dispatch: &ComputeDispatch<Self>,
+        _work_groups: (u32, u32, u32),
+    ) -> Result<(), GpuError> {
+        Err(Self::not_supported(&format!(
+            "Compute pipeline '{}'",
+            dispatch.pipeline.label
+        )))
+    }
+
+    fn update_buffer(&self, buffer: &Self::Buffer, data: &[u8]) {
+        buffer.write(data);
+    }
+
+    /// Commands execute immediately, so this is the same as `update_buffer`.
+    fn cmd_update_buffer(&mut self, buffer: &Self::Buffer, data: &[u8]) {
+        buffer.write(data);
+    }
+
+    fn begin_frame(&mut self) -> Result<(), GpuError> {
+        Ok(())
+    }
+
+    fn end_frame(&mut self) -> Result<(), GpuError> {
+        if self.pass.is_some() {
+            return Err(GpuError::new(
+                "end_frame called inside a render pass",
+                GpuErrorKind::RenderPass,
+            ));
+        }
+        Ok(())
+    }
+
+    fn present(&mut self) -> Result<(), GpuError> {
+        self.frame_count += 1;
+        Ok(())
+    }
+
+    fn begin_render_pass(&mut self, desc: &RenderPassDesc<Self>) {
+        for color in &desc.color_targets {
+            if color.load_op == LoadOp::Clear {
+                color.target.clear(color.clear_color);
+            }
+        }
+        if let Some(depth) = &desc.depth_target {
+            let clear_stencil =
+                depth.target.format.has_stencil() && depth.stencil_load_op == LoadOp::Clear;
+            depth.target.clear_depth_stencil(
+                depth.layer,
+                (depth.load_op == LoadOp::Clear).then_some(depth.clear_depth),
+                clear_stencil.then_some(depth.clear_stencil),
+            );
+        }
+        self.pass = Some(PassState {
+            colors: desc
+                .color_targets
+                .iter()
+                .map(|c| c.target.clone())
+                .collect(),
+            depth: desc.depth_target.as_ref().map(|d| d.target.clone()),
+            depth_write: desc.depth_target.as_ref().is_some_and(|d| d.write_enabled),
+            depth_layer: desc.depth_target.as_ref().map_or(0, |d| d.layer),
+        });
+        // like a fresh command buffer state, until set_viewport is called
+        let extent = desc
+            .color_targets
+            .first()
+            .map(|c| c.target)
+            .or(desc.depth_target.as_ref().map(|d| d.target))
+            .map(|t| (t.width, t.height))
+            .unwrap_or((self.width, self.height));
+        self.viewport = Self::full_viewport(extent.0, extent.1);
+    }
+
+    fn end_render_pass(&mut self) {
+        self.pass = None;
+    }
+
+    fn set_pipeline(&mut self, pipeline: &Self::Pipeline) {
+        self.pipeline = Some(pipeline.clone());
+    }
+
+    fn set_viewport(&mut self, viewport: &ViewportDesc) {
+        self.viewport = *viewport;
+    }
+
+    fn bind_texture(&mut self, slot: u32, texture: &Self::Texture) {
+        if let Some(entry) = self.textures.get_mut(slot as usize) {
+            *entry = Some(texture.clone());
+        }
+    }
+
+    fn bind_render_target_as_texture(&mut self, slot: u32, target: &Self::RenderTarget) {
+        if let Some(entry) = self.textures.get_mut(slot as usize) {
+            *entry = Some(target.clone());
+        }
+    }
+
+    /// Shaders read the shared UBOs through `bind_buffer_to_descriptor`, as on Vulkan.
+    fn bind_uniform(&mut self, _stage: ShaderStage, _slot: u32, _buffer: &Self::Buffer) {}
+
+    fn bind_buffer_to_descriptor(&self, binding: u32, buffer: &Self::Buffer) {
+        self.descriptors
+            .borrow_mut()
+            .insert(binding, buffer.data.clone());
+    }
+
+    fn set_vertex_buffer(&mut self, buffer: &Self::Buffer) {
+        self.vertex_buffer = Some(buffer.data.clone());
+    }
+
+    fn set_index_buffer(&mut self, buffer: &Self::Buffer) {
+        self.index_buffer = Some(buffer.data.clone());
+    }
+
+    fn draw_indexed(&mut self, index_count: u32, first_index: u32, base_vertex: i32) {
+        let (Some(pipeline), Some(pass)) = (&self.pipeline, &self.pass) else {
+            return;
+        };
+        match pipeline.program {
+            SoftwareProgram::Ssao
+            | SoftwareProgram::SsaoBlur
+            | SoftwareProgram::DeferredLight
+            | SoftwareProgram::Taa
+            | SoftwareProgram::Output => self.draw_fullscreen(pipeline, pass),
+            SoftwareProgram::DeferredPre
+            | SoftwareProgram::Shadow
+            | SoftwareProgram::Forward
+            | SoftwareProgram::Skybox => {
+                self.draw_mesh(pipeline, pass, index_count, first_index, base_vertex)
+            }
+            SoftwareProgram::LightCulling | SoftwareProgram::Unsupported(_) => {}
+        }
+    }
+
+    /// Skipped: the instance transforms come from the compute culling pass.
+    fn draw_indexed_indirect(
+        &mut self,
+        _indirect_commands_buffer: &Self::Buffer,
+        _offset: u64,
+        _draw_count: u32,
+    ) {
+    }
+
+    fn set_model_matrix(&mut self, model: &glm::Mat4, prev_model: &glm::Mat4) {
+        self.model = *model;
+        self.prev_model = *prev_model;
+    }
+
+    fn set_material_properties(&mut self, _props: MaterialProperties) {}
+
+    fn cmd_dispatch(&mut self, dispatch: &ComputeDispatch<Self>, _work_groups: (u32, u32, u32)) {
+        if dispatch.pipeline.program == SoftwareProgram::LightCulling {
+            self.cull_lights();
+        }
+    }
+
+    fn cmd_dispatch_indirect(
+        &mut self,
+        _dispatch: &ComputeDispatch<Self>,
+        _args_buffer: &Self::Buffer,
+        _offset: u64,
+    ) {
+    }
+
+    fn cmd_compute_barrier(&mut self, _barrier: ComputeBarrier) {}
+
+    fn cmd_target_barriers(&mut self, _barriers: &[TargetBarrier<Self>]) {}
+
+    fn backbuffer(&self) -> Self::RenderTarget {
+        self.backbuffer.clone()
+    }
+
+    fn main_depth_target(&self) -> Self::RenderTarget {
+        self.depth_target.clone()
+    }
+
+    fn default_viewport(&self) -> ViewportDesc {
+        Self::full_viewport(self.width, self.height)
+    }
+
+    fn resolution(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        self.width = width;
+        self.height = height;
+        self.backbuffer = Self::swapchain_target(width, height, self.backbuffer_format);
+        self.depth_target = Self::swapchain_target(width, height, TextureFormat::Depth32Float);
+    }
+
+    fn wait_idle(&self) -> Result<(), GpuError> {
+        Ok(())
+    }
+
+    fn read_render_target(
+        &mut self,
+        target: &Self::RenderTarget,
+    ) -> Result<TextureReadback, GpuError> {
+        if target.format.is_depth() {
+            return Err(GpuError::new(
+                format!("Readback of depth target {} is not supported", target.id),
+                GpuErrorKind::ResourceUpdate,
+            ));
+        }
+        let texels = target.texels.borrow();
+        let mut data = Vec::with_capacity(texels.len() * target.format.bytes_per_pixel());
+        for texel in texels.iter() {
+            encode_texel(target.format, *texel, &mut data);
+        }
+        Ok(TextureReadback {
+            width: target.width,
+            height: target.height,
+            format: target.format,
+            data,
+        })
+    }
+
+    // RT
+    fn has_rt_support(&self) -> bool {
+        false
+    }
+
+    fn create_blas(
+        &self,
+        _ty: AccelerationStructureType,
+        _render_items: &[RenderItem<'_, Self>],
+    ) -> Result<Vec<Self::AccelerationStructure>, GpuError> {
+        Err(Self::not_supported("Ray tracing"))
+    }
+
+    fn create_tlas(
+        &self,
+        _blas: &[Self::AccelerationStructure],
+        _transforms: &[glm::Mat4],
+        _object_types: &[crate::engine::backend::ObjType],
+        _albedo_indices: &[u32],
+    ) -> Result<Self::AccelerationStructure, GpuError> {
+        Err(Self::not_supported("Ray tracing"))
+    }
+
+    fn create_rt_pipeline(&self, _shaders: &RtShaders<Self>) -> Result<Self::Pipeline, GpuError> {
+        Err(Self::not_supported("Ray tracing"))
+    }
+
+    fn create_rt_output_target(
+        &self,
+        _width: u32,
+        _height: u32,
+    ) -> Result<Self::RenderTarget, GpuError> {
+        Err(Self::not_supported("Ray tracing"))
+    }
+
+    fn dispatch_rays(
+        &mut self,
+        _pipeline: &Self::Pipeline,
+        _tlas: &Self::AccelerationStructure,
+        _output: &Self::RenderTarget,
+        _light_buffer: &Self::Buffer,
+        _material_buffer: &Self::Buffer,
+        _width: u32,
+        _height: u32,
+        _number_of_lights: u32,
+    ) {
+    }
+
+    fn load_rt_shaders(&self) -> RtShaders<Self> {
+        RtShaders {
+            raygen: SoftwareProgram::Unsupported("rt_raygen"),
+            miss: SoftwareProgram::Unsupported("rt_miss"),
+            miss_shadow: SoftwareProgram::Unsupported("rt_miss_shadow"),
+            closest_hit: SoftwareProgram::Unsupported("rt_closest_hit"),
+            any_hit: SoftwareProgram::Unsupported("rt_any_hit"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::backend::{DepthAttachment, StencilFaceState, as_bytes};
+
+    const SIZE: u32 = 4;
+
+    /// A backend with an identity light space and a depth-stencil target.
+    struct StencilFixture {
+        backend: SoftwareBackend,
+        target: SoftwareTexture,
+    }
+
+    impl StencilFixture {
+        fn new() -> Self {
+            let backend = SoftwareBackend::new(SIZE, SIZE);
+            let identity: glm::Mat4 = glm::identity();
+            let light_space = backend
+                .create_buffer(
+                    &BufferDesc {
+                        label: "light_space".into(),
+                        usage: BufferUsage::Uniform,
+                        size: 64,
+                    },
+                    Some(as_bytes(identity.as_slice())),
+                )
+                .unwrap();
+            backend.bind_buffer_to_descriptor(BINDING_LIGHT_SPACE, &light_space);
+            let target = backend
+                .create_render_target(&RenderTargetDesc {
+                    width: SIZE,
+                    height: SIZE,
+                    format: TextureFormat::Depth24Stencil8,
+                    sampler: SamplerDesc::default(),
+                    usage: RenderTargetUsage::Depth,
+                    sample_count: 1,
+                    persistent: false,
+                    layers: 1,
+                })
+                .unwrap();
+            StencilFixture { backend, target }
+        }
+
+        /// Vertex and index buffer of a full-height quad over NDC `x0..x1` at depth `z`.
+        fn quad(&self, x0: f32, x1: f32, z: f32) -> [SoftwareBuffer; 2] {
+            let zero = glm::vec3(0.0, 0.0, 0.0);
+            let vertices = [(x0, -1.0), (x1, -1.0), (x1, 1.0), (x0, 1.0)].map(|(x, y)| Vertex {
+                position: glm::vec3(x, y, z),
+                normal: zero,
+                tangent: zero,
+                bitangent: zero,
+                tex_coord: glm::vec2(0.0, 0.0),
+            });
+            let indices: [u32; 6] = [0, 1, 2, 0, 2, 3];
+            let buffer = |usage, data: &[u8]| {
+                let desc = BufferDesc {
+                    label: "quad".into(),
+                    usage,
+                    size: data.len(),
+                };
+                self.backend.create_buffer(&desc, Some(data)).unwrap()
+            };
+            [
+                buffer(BufferUsage::Vertex, as_bytes(&vertices)),
+                buffer(BufferUsage::Index, as_bytes(&indices)),
+            ]
+        }
+
+        /// Depth-only pipeline writing every fragment that passes `stencil`.
+        fn pipeline(&self, stencil: StencilState) -> SoftwarePipeline {
+            self.backend
+                .create_render_pipeline(&RenderPipelineDesc {
+                    label: "stencil",
+                    shader_source: &SoftwareProgram::Shadow,
+                    vertex_layout: None,
+                    blend_mode: BlendMode::None,
+                    cull_mode: CullMode::None,
+                    depth_write: true,
+                    depth_compare: CompareFunc::Always,
+                    color_target_formats: &[],
+                    depth_format: Some(TextureFormat::Depth24Stencil8),
+                    stencil: Some(stencil),
+                    sample_count: 1,
+                })
+                .unwrap()
+        }
+
+        /// Run a pass that clears the depth to 1 and the stencil to
+        /// `clear_stencil`, or loads them, and draws `draw`.
+        fn pass(
+            &mut self,
+            clear_depth: bool,
+            clear_stencil: Option<u8>,
+            draw: Option<(&SoftwarePipeline, &[SoftwareBuffer; 2])>,
+        ) {
+            let load_op = |clear| if clear { LoadOp::Clear } else { LoadOp::Load };
+            self.backend.begin_render_pass(&RenderPassDesc {
+                label: "stencil",
+                color_targets: Vec::new(),
+                depth_target: Some(DepthAttachment {
+                    target: &self.target,
+                    load_op: load_op(clear_depth),
+                    clear_depth: 1.0,
+                    write_enabled: true,
+                    stencil_load_op: load_op(clear_stencil.is_some()),
+                    clear_stencil: clear_stencil.unwrap_or(0),
+                    resolve_target: None,
+                    layer: 0,
+                }),
+            });
+            if let Some((pipeline, [vertices, indices])) = draw {
+                self.backend.set_pipeline(pipeline);
+                self.backend.set_vertex_buffer(vertices);
+                self.backend.set_index_buffer(indices);
+                self.backend.draw_indexed(6, 0, 0);
+            }
+            self.backend.end_render_pass();
+        }
+
+        fn stencil(&self) -> Vec<u8> {
+            self.target
+                .texels
+                .borrow()
+                .iter()
+                .map(|t| t[1] as u8)
+                .collect()
+        }
+
+        fn depth(&self) -> Vec<f32> {
+            self.target.texels.borrow().iter().map(|t| t[0]).collect()
+        }
+    }
+
+    /// Row-major values of the target, `left` in the two left columns.
+    fn halves<T: Copy>(left: T, right: T) -> Vec<T> {
+        (0..SIZE * SIZE)
+            .map(|i| if i % SIZE < SIZE / 2 { left } else { right })
+            .collect()
+    }
+
+    fn replace(reference: u8) -> StencilState {
+        StencilState::both(
+            StencilFaceState {
+                pass_op: StencilOp::Replace,
+                ..Default::default()
+            },
+            reference,
+        )
+    }
+
+    #[test]
+    fn stencil_is_replaced_cleared_and_loaded() {
+        let mut fixture = StencilFixture::new();
+        let left = fixture.quad(-1.0, 0.0, 0.5);
+        let right = fixture.quad(0.0, 1.0, 0.5);
+
+        let pipeline = fixture.pipeline(replace(1));
+        fixture.pass(true, Some(3), Some((&pipeline, &left)));
+        assert_eq!(fixture.stencil(), halves(1, 3));
+
+        // loaded stencil keeps the left half
+        let pipeline = fixture.pipeline(replace(2));
+        fixture.pass(false, None, Some((&pipeline, &right)));
+        assert_eq!(fixture.stencil(), halves(1, 2));
+
+        // clearing the stencil alone keeps the depth
+        fixture.pass(false, Some(5), None);
+        assert_eq!(fixture.stencil(), halves(5, 5));
+        assert_eq!(fixture.depth(), halves(0.5, 0.5));
+    }
+
+    #[test]
+    fn stencil_test_masks_pixels() {
+        let mut fixture = StencilFixture::new();
+        let left = fixture.quad(-1.0, 0.0, 0.5);
+        let full = fixture.quad(-1.0, 1.0, 0.25);
+
+        let pipeline = fixture.pipeline(replace(1));
+        fixture.pass(true, Some(0), Some((&pipeline, &left)));
+
+        // only the left half holds the reference, the right half fails and is inverted
+        let masked = StencilState::both(
+            StencilFaceState {
+                compare: CompareFunc::Equal,
+                fail_op: StencilOp::Invert,
+                ..Default::default()
+            },
+            1,
+        );
+        let pipeline = fixture.pipeline(masked);
+        fixture.pass(false, None, Some((&pipeline, &full)));
+        assert_eq!(fixture.depth(), halves(0.25, 1.0));
+        assert_eq!(fixture.stencil(), halves(1, 255));
+    }
+
+    #[test]
+    fn point_light_cube_faces_match_the_cube_map_lookup() {
+        let mut backend = SoftwareBackend::new(SIZE, SIZE);
+        let light_space = backend
+            .create_buffer(
+                &BufferDesc {
+                    label: "light_space".into(),
+                    usage: BufferUsage::Uniform,
+                    size: 64,
+                },
+                None,
+            )
+            .unwrap();
+        backend.bind_buffer_to_descriptor(BINDING_LIGHT_SPACE, &light_space);
+        let cube = backend
+            .create_render_target(&RenderTargetDesc {
+                width: 32,
+                height: 32,
+                format: TextureFormat::Depth32Float,
+                sampler: SamplerDesc {
+                    filter: FilterMode::Nearest,
+                    compare: Some(CompareFunc::LessEqual),
+                    ..Default::default()
+                },
+                usage: RenderTargetUsage::Depth,
+                sample_count: 1,
+                persistent: false,
+                layers: 6,
+            })
+            .unwrap();
+        let pipeline = backend
+            .create_render_pipeline(&RenderPipelineDesc {
+                label: "shadow_cube",
+                shader_source: &SoftwareProgram::Shadow,
+                vertex_layout: None,
+                blend_mode: BlendMode::None,
+                // as the shadow pass culls the mirrored faces
+                cull_mode: CullMode::Back,
+                depth_write: true,
+                depth_compare: CompareFunc::Less,
+                color_target_formats: &[],
+                depth_format: Some(TextureFormat::Depth32Float),
+                stencil: None,
+                sample_count: 1,
+            })
+            .unwrap();
+        let views = crate::engine::renderer::cube_face_views(&glm::vec3(0.0, 0.0, 0.0), 10.0);
+        // depth of the point at distance 4 along `dir` in the face view it is in
+        let depth_behind = |dir: glm::Vec3| {
+            let (face, _) = cube_face(dir).unwrap();
+            let p = views[face] * glm::vec4(4.0 * dir.x, 4.0 * dir.y, 4.0 * dir.z, 1.0);
+            p.z / p.w
+        };
+
+        let axes = [glm::Vec3::x(), glm::Vec3::y(), glm::Vec3::z()];
+        for (axis, sign) in (0..3).flat_map(|a| [(a, 1.0), (a, -1.0)]) {
+            // off the face center, so mirrored lookups miss the occluder
+            let (u, v) = (axes[(axis + 1) % 3], axes[(axis + 2) % 3]);
+            let dir = glm::normalize(&(axes[axis] * sign + u * 0.4 + v * 0.2));
+            let center = dir * 2.0;
+            let tangent = glm::normalize(&glm::cross(&dir, &u));
+            let bitangent = glm::cross(&dir, &tangent);
+            let mut corners = [
+                center + tangent * 0.6,
+                center - tangent * 0.3 + bitangent * 0.5,
+                center - tangent * 0.3 - bitangent * 0.5,
+            ];
+            // facing away from the light, like the back of a closed mesh
+            let normal = glm::cross(&(corners[1] - corners[0]), &(corners[2] - corners[0]));
+            if glm::dot(&normal, &dir) < 0.0 {
+                corners.swap(1, 2);
+            }
+            let zero = glm::vec3(0.0, 0.0, 0.0);
+            let vertices = corners.map(|position| Vertex {
+                position,
+                normal: zero,
+                tangent: zero,
+                bitangent: zero,
+                tex_coord: glm::vec2(0.0, 0.0),
+            });
+            let buffer = |usage, data: &[u8]| {
+                let desc = BufferDesc {
+                    label: "occluder".into(),
+                    usage,
+                    size: data.len(),
+                };
+                backend.create_buffer(&desc, Some(data)).unwrap()
+            };
+            let vertex_buffer = buffer(BufferUsage::Vertex, as_bytes(&vertices));
+            let index_buffer = buffer(BufferUsage::Index, as_bytes(&[0u32, 1, 2]));
+
+            for (layer, view) in views.iter().enumerate() {
+                backend.update_buffer(&light_space, as_bytes(view.as_slice()));
+                backend.begin_render_pass(&RenderPassDesc {
+                    label: "shadow_cube",
+                    color_targets: Vec::new(),
+                    depth_target: Some(DepthAttachment {
+                        target: &cube,
+                        load_op: LoadOp::Clear,
+                        clear_depth: 1.0,
+                        write_enabled: true,
+                        stencil_load_op: LoadOp::Clear,
+                        clear_stencil: 0,
+                        resolve_target: None,
+                        layer: layer as u32,
+                    }),
+                });
+                backend.set_pipeline(&pipeline);
+                backend.set_vertex_buffer(&vertex_buffer);
+                backend.set_index_buffer(&index_buffer);
+                backend.draw_indexed(3, 0, 0);
+                backend.end_render_pass();
+            }
+
+            let lit = |dir: glm::Vec3| cube.sample_cube_compare(0, dir, depth_behind(dir));
+            assert_eq!(lit(dir), 0.0, "occluder along {dir:?}");
+            for mirrored in [u, v] {
+                let offset = 2.0 * glm::dot(&dir, &mirrored) * mirrored;
+                let dir = dir - offset;
+                assert_eq!(lit(dir), 1.0, "mirrored lookup along {dir:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,743 @@
+//! Device memory allocator.
+//!
+//! Buffers and images are sub-allocated from large `VkDeviceMemory` blocks
+//! instead of getting one allocation each, which keeps the allocation count far
+//! below `maxMemoryAllocationCount` on large scenes. Blocks are pooled per
+//! memory type, with linear resources (buffers) and optimally tiled images in
+//! separate pools so `bufferImageGranularity` never applies inside a block.
+//! Render targets and resources the driver prefers dedicated get their own
+//! allocation. Host-visible blocks are mapped once for their whole lifetime.
+//!
+//! With `VK_EXT_memory_budget`, allocations that would need a new block while
+//! the heap is near the budget reported by the driver get an exact-size
+//! allocation instead; the same fallback applies when a whole block cannot be
+//! allocated.
+
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::sync::{Arc, Mutex};
+
+use crate::engine::backend::{GpuError, GpuErrorKind, GpuMemoryHeapStats};
+use crate::util as crate_utils;
+
+/// Size of a regular memory block.
+const BLOCK_SIZE: u64 = 64 * 1024 * 1024;
+/// Heaps up to this size (e.g. the 256 MiB BAR heap) use `heap size / 8` blocks.
+const SMALL_HEAP_SIZE: u64 = 1024 * 1024 * 1024;
+/// Empty blocks kept per pool to avoid allocation churn.
+const MAX_EMPTY_BLOCKS_PER_POOL: usize = 1;
+
+/// Tiling class of a resource; linear and optimal resources never share a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceTiling {
+    /// Buffers and linear images.
+    Linear,
+    /// Optimally tiled images.
+    Optimal,
+}
+
+/// A range of device memory owned by a buffer or image.
+#[derive(Debug, Clone, Copy)]
+pub struct Allocation {
+    pub memory: ash::vk::DeviceMemory,
+    pub offset: ash::vk::DeviceSize,
+    pub size: ash::vk::DeviceSize,
+    /// Host pointer to `offset`, null if the memory is not host visible.
+    pub mapped: *mut c_void,
+    block: usize,
+}
+
+impl Allocation {
+    pub fn null() -> Self {
+        Allocation {
+            memory: ash::vk::DeviceMemory::null(),
+            offset: 0,
+            size: 0,
+            mapped: std::ptr::null_mut(),
+            block: usize::MAX,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.memory == ash::vk::DeviceMemory::null()
+    }
+}
+
+struct Block {
+    memory: ash::vk::DeviceMemory,
+    memory_type: u32,
+    tiling: ResourceTiling,
+    size: u64,
+    mapped: *mut c_void,
+    /// Sorted `(offset, size)` ranges, `None` for standalone blocks.
+    free: Option<Vec<(u64, u64)>>,
+    allocations: u32,
+    used: u64,
+}
+
+impl Block {
+    /// First fit: carve `size` bytes at `alignment` out of the sorted free list.
+    fn allocate(&mut self, size: u64, alignment: u64) -> Option<u64> {
+        let free = self.free.as_mut()?;
+        let (index, offset) = free.iter().enumerate().find_map(|(i, &(start, len))| {
+            let offset = start.next_multiple_of(alignment.max(1));
+            (offset + size <= start + len).then_some((i, offset))
+        })?;
+        let (start, len) = free[index];
+        let mut replacement = Vec::with_capacity(2);
+        if offset > start {
+            replacement.push((start, offset - start));
+        }
+        if offset + size < start + len {
+            replacement.push((offset + size, start + len - offset - size));
+        }
+        free.splice(index..=index, replacement);
+        Some(offset)
+    }
+
+    /// Return a range to the free list, merging it with its neighbours.
+    fn release(&mut self, offset: u64, size: u64) {
+        let Some(free) = self.free.as_mut() else {
+            return;
+        };
+        let index = free.partition_point(|&(start, _)| start < offset);
+        free.insert(index, (offset, size));
+        if index + 1 < free.len() && free[index].0 + free[index].1 == free[index + 1].0 {
+            free[index].1 += free[index + 1].1;
+            free.remove(index + 1);
+        }
+        if index > 0 && free[index - 1].0 + free[index - 1].1 == free[index].0 {
+            free[index - 1].1 += free[index].1;
+            free.remove(index);
+        }
+    }
+}
+
+/// All blocks, with the pooled ones indexed by memory type and tiling.
+#[derive(Default)]
+struct Blocks {
+    slots: Vec<Option<Block>>,
+    /// Pool key → indices into `slots`.
+    pools: HashMap<(u32, ResourceTiling), Vec<usize>>,
+}
+
+impl Blocks {
+    /// Store `block` in the first free slot; pooled blocks also join their pool.
+    fn insert(&mut self, block: Block) -> usize {
+        let key = (block.memory_type, block.tiling);
+        let pooled = block.free.is_some();
+        let index = match self.slots.iter().position(Option::is_none) {
+            Some(index) => {
+                self.slots[index] = Some(block);
+                index
+            }
+            None => {
+                self.slots.push(Some(block));
+                self.slots.len() - 1
+            }
+        };
+        if pooled {
+            self.pools.entry(key).or_default().push(index);
+        }
+        index
+    }
+
+    /// Carve `size` bytes out of the first block of the pool that has room,
+    /// returning the block index and offset.
+    fn sub_allocate(
+        &mut self,
+        memory_type: u32,
+        tiling: ResourceTiling,
+        size: u64,
+        alignment: u64,
+    ) -> Option<(usize, u64)> {
+        let pool = self.pools.get(&(memory_type, tiling))?;
+        pool.iter().find_map(|&index| {
+            let block = self.slots[index].as_mut().expect("pooled block exists");
+            block
+                .allocate(size, alignment)
+                .map(|offset| (index, offset))
+        })
+    }
+
+    /// Number of blocks without allocations in the pool of `key`.
+    fn empty_in_pool(&self, key: (u32, ResourceTiling)) -> usize {
+        self.pools.get(&key).map_or(0, |pool| {
+            pool.iter()
+                .filter(|&&i| self.slots[i].as_ref().is_some_and(|b| b.allocations == 0))
+                .count()
+        })
+    }
+
+    fn remove(&mut self, index: usize) -> Option<Block> {
+        let block = self.slots[index].take()?;
+        if let Some(pool) = self.pools.get_mut(&(block.memory_type, block.tiling)) {
+            pool.retain(|&i| i != index);
+        }
+        Some(block)
+    }
+}
+
+/// Whether `size` more bytes on `heap` would go over the budget reported by the driver.
+fn over_budget(
+    budget: &ash::vk::PhysicalDeviceMemoryBudgetPropertiesEXT,
+    heap: usize,
+    size: u64,
+) -> bool {
+    budget.heap_usage[heap] + size > budget.heap_budget[heap]
+}
+
+struct State {
+    instance: ash::Instance,
+    phys_device: ash::vk::PhysicalDevice,
+    device: ash::Device,
+    memory_properties: ash::vk::PhysicalDeviceMemoryProperties,
+    budget_supported: bool,
+    blocks: Blocks,
+    /// Memory types whose heap budget was already reported as exceeded.
+    over_budget_warned: Vec<bool>,
+}
+
+// SAFETY: the mapped pointers of the blocks point into persistently mapped
+// device memory that lives until the block is freed, and all block state is
+// only accessed through the mutex.
+unsafe impl Send for State {}
+
+/// Cloneable handle to the backend's memory allocator.
+#[derive(Clone)]
+pub struct MemoryAllocator {
+    state: Arc<Mutex<State>>,
+}
+
+impl MemoryAllocator {
+    pub fn new(
+        instance: &ash::Instance,
+        phys_device: ash::vk::PhysicalDevice,
+        device: &ash::Device,
+        budget_supported: bool,
+    ) -> Self {
+        let memory_properties =
+            unsafe { instance.get_physical_device_memory_properties(phys_device) };
+        MemoryAllocator {
+            state: Arc::new(Mutex::new(State {
+                instance: instance.clone(),
+                phys_device,
+                device: device.clone(),
+                memory_properties,
+                budget_supported,
+                blocks: Blocks::default(),
+                over_budget_warned: vec![false; memory_properties.memory_type_count as usize],
+            })),
+        }
+    }
+
+    /// Allocate and bind memory for `buffer`, at an offset aligned to at least
+    /// `min_alignment` (e.g. the shader binding table base alignment).
+    pub fn allocate_buffer(
+        &self,
+        buffer: ash::vk::Buffer,
+        properties: ash::vk::MemoryPropertyFlags,
+        min_alignment: u64,
+    ) -> Result<Allocation, GpuError> {
+        let state = crate_utils::mtx_lock(&self.state);
+        let mut dedicated_reqs = ash::vk::MemoryDedicatedRequirements::default();
+        let mut reqs = ash::vk::MemoryRequirements2::default().push_next(&mut dedicated_reqs);
+        let info = ash::vk::BufferMemoryRequirementsInfo2::default().buffer(buffer);
+        unsafe {
+            state
+                .device
+                .get_buffer_memory_requirements2(&info, &mut reqs)
+        };
+        let mut requirements = reqs.memory_requirements;
+        requirements.alignment = requirements.alignment.max(min_alignment);
+        let dedicated = (dedicated_reqs.prefers_dedicated_allocation == ash::vk::TRUE
+            || dedicated_reqs.requires_dedicated_allocation == ash::vk::TRUE)
+            .then_some(Dedicated::Buffer(buffer));
+        drop(state);
+
+        let allocation =
+            self.allocate(requirements, properties, ResourceTiling::Linear, dedicated)?;
+        let state = crate_utils::mtx_lock(&self.state);
+        unsafe {
+            state
+                .device
+                .bind_buffer_memory(buffer, allocation.memory, allocation.offset)
+        }
+        .map_err(|e| {
+            drop(state);
+            self.free(allocation);
+            GpuError::new(
+                format!("Failed to bind buffer to memory: {e:?}"),
+                GpuErrorKind::ResourceUpdate,
+            )
+        })?;
+        Ok(allocation)
+    }
+
+    /// Allocate and bind memory for `image`. `dedicated` forces a dedicated
+    /// allocation (render targets, which are large and recreated on resize).
+    pub fn allocate_image(
+        &self,
+        image: ash::vk::Image,
+        properties: ash::vk::MemoryPropertyFlags,
+        tiling: ResourceTiling,
+        dedicated: bool,
+    ) -> Result<Allocation, GpuError> {
+        let state = crate_utils::mtx_lock(&self.state);
+        let mut dedicated_reqs = ash::vk::MemoryDedicatedRequirements::default();
+        let mut reqs = ash::vk::MemoryRequirements2::default().push_next(&mut dedicated_reqs);
+        let info = ash::vk::ImageMemoryRequirementsInfo2::default().image(image);
+        unsafe {
+            state
+                .device
+                .get_image_memory_requirements2(&info, &mut reqs)
+        };
+        let requirements = reqs.memory_requirements;
+        let dedicated = (dedicated
+            || dedicated_reqs.prefers_dedicated_allocation == ash::vk::TRUE
+            || dedicated_reqs.requires_dedicated_allocation == ash::vk::TRUE)
+            .then_some(Dedicated::Image(image));
+        drop(state);
+
+        let allocation = self.allocate(requirements, properties, tiling, dedicated)?;
+        let state = crate_utils::mtx_lock(&self.state);
+        unsafe {
+            state
+                .device
+                .bind_image_memory(image, allocation.memory, allocation.offset)
+        }
+        .map_err(|e| {
+            drop(state);
+            self.free(allocation);
+            GpuError::new(
+                format!("Failed to bind memory to image: {e:?}"),
+                GpuErrorKind::ResourceUpdate,
+            )
+        })?;
+        Ok(allocation)
+    }
+
+    fn allocate(
+        &self,
+        requirements: ash::vk::MemoryRequirements,
+        properties: ash::vk::MemoryPropertyFlags,
+        tiling: ResourceTiling,
+        dedicated: Option<Dedicated>,
+    ) -> Result<Allocation, GpuError> {
+        let mut state = crate_utils::mtx_lock(&self.state);
+        let memory_type = state.find_memory_type(requirements.memory_type_bits, properties)?;
+        let block_size = state.block_size(memory_type);
+
+        if dedicated.is_some() || requirements.size > block_size / 2 {
+            let kind = BlockKind::Standalone(dedicated);
+            let index = state.create_block(memory_type, tiling, requirements.size, kind)?;
+            return Ok(state.take(index, 0, requirements.size));
+        }
+
+        if let Some((index, offset)) = state.blocks.sub_allocate(
+            memory_type,
+            tiling,
+            requirements.size,
+            requirements.alignment,
+        ) {
+            return Ok(state.take(index, offset, requirements.size));
+        }
+
+        // Near the budget or out of memory for a whole block, only take what is needed.
+        let standalone = BlockKind::Standalone(None);
+        if state.exceeds_budget(memory_type, block_size) {
+            let index = state.create_block(memory_type, tiling, requirements.size, standalone)?;
+            return Ok(state.take(index, 0, requirements.size));
+        }
+        let index = match state.create_block(memory_type, tiling, block_size, BlockKind::Pooled) {
+            Ok(index) => index,
+            Err(_) => {
+                let index =
+                    state.create_block(memory_type, tiling, requirements.size, standalone)?;
+                return Ok(state.take(index, 0, requirements.size));
+            }
+        };
+        let block = state.blocks.slots[index]
+            .as_mut()
+            .expect("new block exists");
+        let offset = block
+            .allocate(requirements.size, requirements.alignment)
+            .ok_or_else(|| {
+                GpuError::new(
+                    format!(
+                        "Allocation of {} bytes does not fit a new memory block",
+                        requirements.size
+                    ),
+                    GpuErrorKind::ResourceCreation,
+                )
+            })?;
+        Ok(state.take(index, offset, requirements.size))
+    }
+
+    /// Return an allocation. Null allocations are ignored.
+    pub fn free(&self, allocation: Allocation) {
+        if allocation.is_null() {
+            return;
+        }
+        let mut state = crate_utils::mtx_lock(&self.state);
+        let Some(block) = state
+            .blocks
+            .slots
+            .get_mut(allocation.block)
+            .and_then(Option::as_mut)
+        else {
+            return;
+        };
+        block.allocations -= 1;
+        block.used -= allocation.size;
+        block.release(allocation.offset, allocation.size);
+        if block.allocations > 0 {
+            return;
+        }
+
+        let key = (block.memory_type, block.tiling);
+        let is_standalone = block.free.is_none();
+        if is_standalone || state.blocks.empty_in_pool(key) > MAX_EMPTY_BLOCKS_PER_POOL {
+            state.destroy_block(allocation.block);
+        }
+    }
+
+    /// Per-heap usage, with the driver's budget if `VK_EXT_memory_budget` is enabled.
+    pub fn stats(&self) -> Vec<GpuMemoryHeapStats> {
+        let state = crate_utils::mtx_lock(&self.state);
+        let props = &state.memory_properties;
+        let mut heaps = (0..props.memory_heap_count as usize)
+            .map(|i| GpuMemoryHeapStats {
+                heap_index: i as u32,
+                device_local: props.memory_heaps[i]
+                    .flags
+                    .contains(ash::vk::MemoryHeapFlags::DEVICE_LOCAL),
+                heap_size: props.memory_heaps[i].size,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        for block in state.blocks.slots.iter().flatten() {
+            let heap =
+                &mut heaps[props.memory_types[block.memory_type as usize].heap_index as usize];
+            heap.allocated_bytes += block.size;
+            heap.used_bytes += block.used;
+            heap.allocation_count += block.allocations;
+            if block.free.is_some() {
+                heap.block_count += 1;
+            } else {
+                heap.dedicated_count += 1;
+            }
+        }
+        if let Some(budget) = state.budget() {
+            for heap in &mut heaps {
+                heap.budget_bytes = Some(budget.heap_budget[heap.heap_index as usize]);
+                heap.usage_bytes = Some(budget.heap_usage[heap.heap_index as usize]);
+            }
+        }
+        heaps
+    }
+
+    /// Free all device memory. Resources still bound to it must already be destroyed.
+    pub fn destroy(&self) {
+        let mut state = crate_utils::mtx_lock(&self.state);
+        for index in 0..state.blocks.slots.len() {
+            if state.blocks.slots[index].is_some() {
+                state.destroy_block(index);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Dedicated {
+    Buffer(ash::vk::Buffer),
+    Image(ash::vk::Image),
+}
+
+#[derive(Clone, Copy)]
+enum BlockKind {
+    /// Shared block in the pool of its memory type.
+    Pooled,
+    /// Backs exactly one allocation, optionally as a dedicated allocation.
+    Standalone(Option<Dedicated>),
+}
+
+impl State {
+    fn find_memory_type(
+        &self,
+        type_filter: u32,
+        properties: ash::vk::MemoryPropertyFlags,
+    ) -> Result<u32, GpuError> {
+        (0..self.memory_properties.memory_type_count)
+            .find(|&i| {
+                type_filter & (1 << i) != 0
+                    && self.memory_properties.memory_types[i as usize]
+                        .property_flags
+                        .contains(properties)
+            })
+            .ok_or_else(|| GpuError {
+                message: format!(
+                    "No suitable memory type found for filter {type_filter:#034b} and flags {properties:?}"
+                ),
+                kind: GpuErrorKind::ResourceCreation,
+            })
+    }
+
+    fn heap_index(&self, memory_type: u32) -> usize {
+        self.memory_properties.memory_types[memory_type as usize].heap_index as usize
+    }
+
+    fn block_size(&self, memory_type: u32) -> u64 {
+        let heap_size = self.memory_properties.memory_heaps[self.heap_index(memory_type)].size;
+        if heap_size <= SMALL_HEAP_SIZE {
+            (heap_size / 8).min(BLOCK_SIZE)
+        } else {
+            BLOCK_SIZE
+        }
+    }
+
+    fn budget(&self) -> Option<ash::vk::PhysicalDeviceMemoryBudgetPropertiesEXT<'static>> {
+        if !self.budget_supported {
+            return None;
+        }
+        let mut budget = ash::vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+        let mut props = ash::vk::PhysicalDeviceMemoryProperties2::default().push_next(&mut budget);
+        unsafe {
+            self.instance
+                .get_physical_device_memory_properties2(self.phys_device, &mut props)
+        };
+        Some(ash::vk::PhysicalDeviceMemoryBudgetPropertiesEXT {
+            heap_budget: budget.heap_budget,
+            heap_usage: budget.heap_usage,
+            ..Default::default()
+        })
+    }
+
+    /// Whether allocating `size` more bytes from the heap of `memory_type` would
+    /// exceed its budget. Warns once per memory type.
+    fn exceeds_budget(&mut self, memory_type: u32, size: u64) -> bool {
+        let Some(budget) = self.budget() else {
+            return false;
+        };
+        let heap = self.heap_index(memory_type);
+        let exceeds = over_budget(&budget, heap, size);
+        if exceeds && !self.over_budget_warned[memory_type as usize] {
+            self.over_budget_warned[memory_type as usize] = true;
+            eprintln!(
+                "GPU memory heap {heap} is near its budget: {} MiB used of {} MiB",
+                budget.heap_usage[heap] / (1024 * 1024),
+                budget.heap_budget[heap] / (1024 * 1024)
+            );
+        }
+        exceeds
+    }
+
+    fn create_block(
+        &mut self,
+        memory_type: u32,
+        tiling: ResourceTiling,
+        size: u64,
+        kind: BlockKind,
+    ) -> Result<usize, GpuError> {
+        let dedicated = match kind {
+            BlockKind::Standalone(dedicated) => dedicated,
+            BlockKind::Pooled => None,
+        };
+        let mut dedicated_info = ash::vk::MemoryDedicatedAllocateInfo::default();
+        match dedicated {
+            Some(Dedicated::Buffer(buffer)) => dedicated_info.buffer = buffer,
+            Some(Dedicated::Image(image)) => dedicated_info.image = image,
+            None => (),
+        }
+        // bufferDeviceAddress is always enabled, so any block can back an addressable buffer
+        let mut flags_info = ash::vk::MemoryAllocateFlagsInfo {
+            flags: ash::vk::MemoryAllocateFlags::DEVICE_ADDRESS,
+            ..Default::default()
+        };
+        if dedicated.is_some() {
+            flags_info.p_next = &mut dedicated_info as *mut _ as *mut c_void;
+        }
+        let alloc_info = ash::vk::MemoryAllocateInfo {
+            allocation_size: size,
+            memory_type_index: memory_type,
+            p_next: &mut flags_info as *mut _ as *mut c_void,
+            ..Default::default()
+        };
+        let memory = unsafe { self.device.allocate_memory(&alloc_info, None) }.map_err(|e| {
+            GpuError::new(
+                format!(
+                    "Failed to allocate {size} bytes of device memory (type {memory_type}): {e:?}"
+                ),
+                GpuErrorKind::ResourceCreation,
+            )
+        })?;
+
+        let host_visible = self.memory_properties.memory_types[memory_type as usize]
+            .property_flags
+            .contains(ash::vk::MemoryPropertyFlags::HOST_VISIBLE);
+        let mapped = if host_visible {
+            match unsafe {
+                self.device.map_memory(
+                    memory,
+                    0,
+                    ash::vk::WHOLE_SIZE,
+                    ash::vk::MemoryMapFlags::empty(),
+                )
+            } {
+                Ok(ptr) => ptr,
+                Err(e) => {
+                    unsafe { self.device.free_memory(memory, None) };
+                    return Err(GpuError::new(
+                        format!("Failed to map device memory: {e:?}"),
+                        GpuErrorKind::ResourceUpdate,
+                    ));
+                }
+            }
+        } else {
+            std::ptr::null_mut()
+        };
+
+        let block = Block {
+            memory,
+            memory_type,
+            tiling,
+            size,
+            mapped,
+            free: matches!(kind, BlockKind::Pooled).then(|| vec![(0, size)]),
+            allocations: 0,
+            used: 0,
+        };
+        Ok(self.blocks.insert(block))
+    }
+
+    /// Account for a sub-range of block `index` handed out as an allocation.
+    fn take(&mut self, index: usize, offset: u64, size: u64) -> Allocation {
+        let block = self.blocks.slots[index]
+            .as_mut()
+            .expect("allocated block exists");
+        block.allocations += 1;
+        block.used += size;
+        Allocation {
+            memory: block.memory,
+            offset,
+            size,
+            mapped: if block.mapped.is_null() {
+                std::ptr::null_mut()
+            } else {
+                unsafe { block.mapped.add(offset as usize) }
+            },
+            block: index,
+        }
+    }
+
+    fn destroy_block(&mut self, index: usize) {
+        let Some(block) = self.blocks.remove(index) else {
+            return;
+        };
+        // freeing implicitly unmaps
+        unsafe { self.device.free_memory(block.memory, None) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIB: u64 = 1024 * 1024;
+
+    fn block(memory_type: u32, tiling: ResourceTiling, size: u64) -> Block {
+        Block {
+            memory: ash::vk::DeviceMemory::null(),
+            memory_type,
+            tiling,
+            size,
+            mapped: std::ptr::null_mut(),
+            free: Some(vec![(0, size)]),
+            allocations: 0,
+            used: 0,
+        }
+    }
+
+    #[test]
+    fn allocations_are_aligned_first_fit() {
+        let mut b = block(0, ResourceTiling::Linear, 4096);
+        assert_eq!(b.allocate(100, 1), Some(0));
+        assert_eq!(b.allocate(64, 256), Some(256));
+        assert_eq!(b.free, Some(vec![(100, 156), (320, 3776)]));
+        // The gap left by the alignment is used first
+        assert_eq!(b.allocate(50, 4), Some(100));
+        assert_eq!(b.allocate(4096, 1), None);
+        assert_eq!(b.allocate(3776, 1), Some(320));
+        assert_eq!(b.allocate(1, 1), Some(150));
+
+        let mut standalone = block(0, ResourceTiling::Linear, 4096);
+        standalone.free = None;
+        assert_eq!(standalone.allocate(16, 1), None);
+        standalone.release(0, 16);
+        assert_eq!(standalone.free, None);
+    }
+
+    #[test]
+    fn released_ranges_coalesce() {
+        let mut b = block(0, ResourceTiling::Linear, 1024);
+        let offsets: Vec<u64> = (0..4).map(|_| b.allocate(256, 256).unwrap()).collect();
+        assert_eq!(offsets, [0, 256, 512, 768]);
+        assert_eq!(b.free, Some(vec![]));
+
+        b.release(256, 256);
+        b.release(768, 256);
+        assert_eq!(b.free, Some(vec![(256, 256), (768, 256)]));
+        // Merges with the following range
+        b.release(0, 256);
+        assert_eq!(b.free, Some(vec![(0, 512), (768, 256)]));
+        // Merges with both neighbours
+        b.release(512, 256);
+        assert_eq!(b.free, Some(vec![(0, 1024)]));
+        assert_eq!(b.allocate(1024, 1), Some(0));
+    }
+
+    #[test]
+    fn linear_and_optimal_resources_use_separate_blocks() {
+        let mut blocks = Blocks::default();
+        let linear = blocks.insert(block(0, ResourceTiling::Linear, 1024));
+        let optimal = blocks.insert(block(0, ResourceTiling::Optimal, 1024));
+        let mut standalone = block(0, ResourceTiling::Linear, 1024);
+        standalone.free = None;
+        let standalone = blocks.insert(standalone);
+        assert_eq!(blocks.pools[&(0, ResourceTiling::Linear)], [linear]);
+
+        assert_eq!(
+            blocks.sub_allocate(0, ResourceTiling::Linear, 1024, 1),
+            Some((linear, 0))
+        );
+        // The optimal block has room, but linear resources never go there
+        assert_eq!(blocks.sub_allocate(0, ResourceTiling::Linear, 16, 1), None);
+        assert_eq!(
+            blocks.sub_allocate(0, ResourceTiling::Optimal, 16, 1),
+            Some((optimal, 0))
+        );
+        assert_eq!(blocks.sub_allocate(1, ResourceTiling::Optimal, 16, 1), None);
+
+        assert!(blocks.remove(standalone).is_some());
+        assert!(blocks.remove(linear).is_some());
+        assert!(blocks.pools[&(0, ResourceTiling::Linear)].is_empty());
+        assert_eq!(
+            blocks.insert(block(1, ResourceTiling::Linear, 1024)),
+            linear
+        );
+        assert_eq!(blocks.empty_in_pool((1, ResourceTiling::Linear)), 1);
+    }
+
+    #[test]
+    fn allocations_over_the_budget_are_rejected() {
+        let mut budget = ash::vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+        budget.heap_budget[1] = 256 * MIB;
+        budget.heap_usage[1] = 200 * MIB;
+        assert!(over_budget(&budget, 1, BLOCK_SIZE));
+        assert!(!over_budget(&budget, 1, 56 * MIB));
+        assert!(over_budget(&budget, 1, 56 * MIB + 1));
+        // Other heaps are unaffected by the usage of heap 1
+        budget.heap_budget[0] = 8 * 1024 * MIB;
+        assert!(!over_budget(&budget, 0, BLOCK_SIZE));
+    }
+}
@@ -1,19 +1,22 @@
 use std::os::raw::c_void;
 
 use crate::engine::{
-    backend::{BufferUsage, GpuBuffer, GpuError, GpuErrorKind},
-    vulkan_backend::{VulkanBackend, VulkanHandleTracker},
+    backend::{BufferUsage, GpuBuffer, GpuError},
+    vulkan_backend::{
+        FRAMES_IN_FLIGHT, VulkanBackend, VulkanHandleTracker, allocator::Allocation,
+        destruction_queue::PendingDestroy,
+    },
 };
 
 pub struct PerFrameCopy {
     pub buffer: ash::vk::Buffer,
-    pub memory: ash::vk::DeviceMemory,
+    pub allocation: Allocation,
     pub mapped: *mut c_void,
 }
 
 pub struct VulkanBuffer {
     pub buffer: ash::vk::Buffer,
-    pub memory: ash::vk::DeviceMemory,
+    pub allocation: Allocation,
     pub mapped: *mut c_void,
     pub flags: ash::vk::MemoryPropertyFlags,
     pub size: ash::vk::DeviceSize,
@@ -23,43 +26,24 @@ pub struct VulkanBuffer {
     pub is_storage_buffer: bool,
 }
 
-impl VulkanBuffer {
-    pub fn destroy(&self) {
-        let VulkanBuffer {
-            buffer,
-            memory,
-            vulkan_handle_tracker,
-            device_handle,
-            ..
-        } = self;
-
-        unsafe {
-            if *buffer != ash::vk::Buffer::null() {
-                vulkan_handle_tracker.unregister_buffer(*buffer);
-                device_handle.destroy_buffer(*buffer, None);
-            }
-            if *memory != ash::vk::DeviceMemory::null() {
-                vulkan_handle_tracker.unregister_device_memory(*memory);
-                device_handle.free_memory(*memory, None);
-            }
-        }
-
-        if let Some(copies) = &self.per_frame_copies {
-            for copy in copies {
-                unsafe {
-                    if copy.buffer != ash::vk::Buffer::null() {
-                        vulkan_handle_tracker.unregister_buffer(copy.buffer);
-                        device_handle.destroy_buffer(copy.buffer, None);
-                    }
-                    if copy.memory != ash::vk::DeviceMemory::null() {
-                        vulkan_handle_tracker.unregister_device_memory(copy.memory);
-                        device_handle.free_memory(copy.memory, None);
-                    }
-                }
-            }
+impl Drop for VulkanBuffer {
+    fn drop(&mut self) {
+        self.vulkan_handle_tracker
+            .defer_destroy(PendingDestroy::Buffer {
+                buffer: self.buffer,
+                allocation: self.allocation,
+            });
+        for copy in self.per_frame_copies.iter().flatten() {
+            self.vulkan_handle_tracker
+                .defer_destroy(PendingDestroy::Buffer {
+                    buffer: copy.buffer,
+                    allocation: copy.allocation,
+                });
         }
     }
+}
 
+impl VulkanBuffer {
     pub fn is_host_mapable(&self) -> bool {
         host_mappable(self.flags)
     }
@@ -105,40 +89,28 @@ impl VulkanBackend {
         usage: ash::vk::BufferUsageFlags,
         properties: ash::vk::MemoryPropertyFlags,
     ) -> Result<VulkanBuffer, GpuError> {
-        let (buffer, memory) = Self::create_buffer(
-            &self.instance,
+        let (buffer, allocation) = Self::create_buffer(
+            self.vulkan_handle_tracker.allocator(),
             &self.device,
-            self.phys_device,
             size,
-            usage.into(),
+            usage,
             properties,
         )?;
-        let has_host_flags = host_mappable(properties);
-        // println!("Creating buffer with usage {usage:?}. Host mapped: {has_host_flags}");
-        let mapped = if has_host_flags {
-            unsafe {
-                self.device
-                    .map_memory(memory, 0, size, ash::vk::MemoryMapFlags::empty())
-            }
-            .map_err(|e| {
-                GpuError::new(
-                    format!("Failed to map buffer memory: {e:?}"),
-                    GpuErrorKind::ResourceUpdate,
-                )
-            })?
+        // println!("Creating buffer with usage {usage:?}. Host mapped: {}", host_mappable(properties));
+        let mapped = if host_mappable(properties) {
+            allocation.mapped
         } else {
             std::ptr::null_mut()
         };
 
         // Register handles for tracking
         self.vulkan_handle_tracker.register_buffer(buffer);
-        self.vulkan_handle_tracker.register_device_memory(memory);
 
         let is_storage_buffer = usage.contains(ash::vk::BufferUsageFlags::STORAGE_BUFFER);
 
         Ok(VulkanBuffer {
             buffer,
-            memory,
+            allocation,
             mapped,
             flags: properties,
             size,
@@ -150,6 +122,70 @@ impl VulkanBackend {
     }
 }
 
+impl VulkanBackend {
+    /// Create the copies of a buffer for the frames in flight after the first,
+    /// with the size, usage and memory properties of the primary buffer.
+    pub fn create_per_frame_copies(
+        &self,
+        size: ash::vk::DeviceSize,
+        usage: ash::vk::BufferUsageFlags,
+        properties: ash::vk::MemoryPropertyFlags,
+    ) -> Result<Vec<PerFrameCopy>, GpuError> {
+        (1..FRAMES_IN_FLIGHT)
+            .map(|_| {
+                let (buffer, allocation) = Self::create_buffer(
+                    self.vulkan_handle_tracker.allocator(),
+                    &self.device,
+                    size,
+                    usage,
+                    properties,
+                )?;
+                let mapped = if host_mappable(properties) {
+                    allocation.mapped
+                } else {
+                    std::ptr::null_mut()
+                };
+                // Register with tracker so cleanup_leftover catches unfreed copies
+                self.vulkan_handle_tracker.register_buffer(buffer);
+
+                Ok(PerFrameCopy {
+                    buffer,
+                    allocation,
+                    mapped,
+                })
+            })
+            .collect()
+    }
+}
+
+impl VulkanBackend {
+    /// Copy `data` to the start of a device-local buffer through the staging
+    /// ring. `fresh` buffers are not used by any submitted work yet and are
+    /// filled on the transfer queue; otherwise the copy waits for earlier work
+    /// and also goes to the per-frame copy of the current frame.
+    pub fn upload_to_buffer(
+        &self,
+        buffer: &VulkanBuffer,
+        data: &[u8],
+        fresh: bool,
+    ) -> Result<(), GpuError> {
+        let size = data.len() as ash::vk::DeviceSize;
+        let mut uploader = self.uploader.borrow_mut();
+        let staged = uploader.stage(&[data])?;
+        if fresh {
+            return uploader.copy_to_new_buffer(staged, buffer.buffer, size);
+        }
+        uploader.copy_to_buffer_in_use(staged, buffer.buffer, size)?;
+        if let Some(copies) = &buffer.per_frame_copies {
+            let copy_idx = self.frame_idx;
+            if copy_idx > 0 && copy_idx <= copies.len() {
+                uploader.copy_to_buffer_in_use(staged, copies[copy_idx - 1].buffer, size)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 impl GpuBuffer for VulkanBuffer {
     fn size(&self) -> usize {
         self.size as usize
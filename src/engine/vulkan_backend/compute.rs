@@ -0,0 +1,325 @@
+//! Compute pipeline layouts and dispatch recording.
+//!
+//! A compute pipeline sees the bindless set 0 and a push descriptor set 1 with
+//! the bindings its `ComputePipelineDesc` declares. Pipelines with the same
+//! bindings and push-constant size share one layout.
+
+use std::collections::HashMap;
+
+use crate::engine::{
+    backend::{
+        ComputeBarrier, ComputeBindingDesc, ComputeBindingType, ComputeDispatch, ComputeResource,
+        GpuError, GpuErrorKind,
+    },
+    vulkan_backend::VulkanBackend,
+};
+
+#[derive(Clone, Copy)]
+pub struct ComputeLayout {
+    pub descriptor_layout: ash::vk::DescriptorSetLayout,
+    pub pipeline_layout: ash::vk::PipelineLayout,
+}
+
+/// Compute pipeline layouts keyed by set 1 bindings and push-constant size.
+#[derive(Default)]
+pub struct ComputeLayouts {
+    layouts: HashMap<(Vec<ComputeBindingDesc>, u32), ComputeLayout>,
+}
+
+impl ComputeLayouts {
+    pub fn get_or_create(
+        &mut self,
+        device: &ash::Device,
+        bindless_layout: ash::vk::DescriptorSetLayout,
+        bindings: &[ComputeBindingDesc],
+        push_constant_size: u32,
+    ) -> Result<ComputeLayout, GpuError> {
+        let key = (bindings.to_vec(), push_constant_size);
+        if let Some(layout) = self.layouts.get(&key) {
+            return Ok(*layout);
+        }
+        let layout = create_compute_layout(device, bindless_layout, bindings, push_constant_size)?;
+        self.layouts.insert(key, layout);
+        Ok(layout)
+    }
+
+    pub fn destroy(&mut self, device: &ash::Device) {
+        for (_, layout) in self.layouts.drain() {
+            unsafe {
+                device.destroy_pipeline_layout(layout.pipeline_layout, None);
+                device.destroy_descriptor_set_layout(layout.descriptor_layout, None);
+            }
+        }
+    }
+}
+
+fn descriptor_type(ty: ComputeBindingType) -> ash::vk::DescriptorType {
+    match ty {
+        ComputeBindingType::StorageBuffer => ash::vk::DescriptorType::STORAGE_BUFFER,
+        ComputeBindingType::Texture => ash::vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        ComputeBindingType::StorageImage => ash::vk::DescriptorType::STORAGE_IMAGE,
+    }
+}
+
+/// Bindings of the compute push descriptor set (set 1).
+pub fn compute_layout_bindings(
+    bindings: &[ComputeBindingDesc],
+) -> Vec<ash::vk::DescriptorSetLayoutBinding<'static>> {
+    bindings
+        .iter()
+        .map(|desc| ash::vk::DescriptorSetLayoutBinding {
+            binding: desc.binding,
+            descriptor_type: descriptor_type(desc.ty),
+            descriptor_count: 1,
+            stage_flags: ash::vk::ShaderStageFlags::COMPUTE,
+            ..Default::default()
+        })
+        .collect()
+}
+
+pub fn compute_push_constant_range(size: u32) -> ash::vk::PushConstantRange {
+    ash::vk::PushConstantRange {
+        stage_flags: ash::vk::ShaderStageFlags::COMPUTE,
+        offset: 0,
+        size,
+    }
+}
+
+fn create_compute_layout(
+    device: &ash::Device,
+    bindless_layout: ash::vk::DescriptorSetLayout,
+    bindings: &[ComputeBindingDesc],
+    push_constant_size: u32,
+) -> Result<ComputeLayout, GpuError> {
+    let layout_bindings = compute_layout_bindings(bindings);
+    let create_info = ash::vk::DescriptorSetLayoutCreateInfo {
+        flags: ash::vk::DescriptorSetLayoutCreateFlags::PUSH_DESCRIPTOR_KHR,
+        binding_count: layout_bindings.len() as u32,
+        p_bindings: layout_bindings.as_ptr(),
+        ..Default::default()
+    };
+    let descriptor_layout = unsafe { device.create_descriptor_set_layout(&create_info, None) }
+        .map_err(|e| {
+            GpuError::new(
+                format!("Failed to create compute descriptor set layout: {e:?}"),
+                GpuErrorKind::ResourceCreation,
+            )
+        })?;
+
+    let set_layouts = [bindless_layout, descriptor_layout];
+    let push_range = compute_push_constant_range(push_constant_size);
+    let create_info = ash::vk::PipelineLayoutCreateInfo {
+        set_layout_count: set_layouts.len() as u32,
+        p_set_layouts: set_layouts.as_ptr(),
+        push_constant_range_count: if push_constant_size > 0 { 1 } else { 0 },
+        p_push_constant_ranges: &push_range,
+        ..Default::default()
+    };
+    let pipeline_layout =
+        unsafe { device.create_pipeline_layout(&create_info, None) }.map_err(|e| {
+            unsafe { device.destroy_descriptor_set_layout(descriptor_layout, None) };
+            GpuError::new(
+                format!("Failed to create compute pipeline layout: {e:?}"),
+                GpuErrorKind::ResourceCreation,
+            )
+        })?;
+
+    Ok(ComputeLayout {
+        descriptor_layout,
+        pipeline_layout,
+    })
+}
+
+impl VulkanBackend {
+    /// Bind the pipeline, set 0 of frame slot `idx`, the resources and push
+    /// constants of `dispatch`. Textures and storage images are transitioned
+    /// to the layouts compute shaders expect.
+    pub(super) fn record_compute_bindings(
+        &self,
+        cmd: ash::vk::CommandBuffer,
+        idx: usize,
+        dispatch: &ComputeDispatch<Self>,
+    ) -> Result<(), GpuError> {
+        let pipeline = dispatch.pipeline;
+        if pipeline.bind_point != ash::vk::PipelineBindPoint::COMPUTE {
+            return Err(GpuError::new(
+                format!("Pipeline '{}' is not a compute pipeline", pipeline.label),
+                GpuErrorKind::Other,
+            ));
+        }
+        if dispatch.push_constants.len() as u32 > pipeline.push_constant_size {
+            return Err(GpuError::new(
+                format!(
+                    "{} bytes of push constants exceed the {} bytes of pipeline '{}'",
+                    dispatch.push_constants.len(),
+                    pipeline.push_constant_size,
+                    pipeline.label
+                ),
+                GpuErrorKind::Other,
+            ));
+        }
+
+        // descriptor infos must stay in place until the push below
+        let mut buffer_infos = Vec::new();
+        let mut image_infos = Vec::new();
+        for (_, resource) in dispatch.resources {
+            match resource {
+                ComputeResource::StorageBuffer(buffer) => {
+                    buffer_infos.push(ash::vk::DescriptorBufferInfo {
+                        buffer: buffer.frame_buffer(idx),
+                        offset: 0,
+                        range: ash::vk::WHOLE_SIZE,
+                    })
+                }
+                ComputeResource::Texture(texture) => {
+                    let layout = ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+                    self.transition_image_layout(
+                        cmd,
+                        texture.image,
+                        texture.current_layout.get(),
+                        layout,
+                        ash::vk::ImageAspectFlags::COLOR,
+                        1,
+                        texture.mip_levels,
+                    )?;
+                    texture.current_layout.set(layout);
+                    image_infos.push(ash::vk::DescriptorImageInfo {
+                        sampler: texture.sampler,
+                        image_view: texture.image_view,
+                        image_layout: layout,
+                    });
+                }
+                ComputeResource::StorageImage(target) => {
+                    let texture = target.get_target(idx);
+                    let layout = ash::vk::ImageLayout::GENERAL;
+                    self.transition_image_layout(
+                        cmd,
+                        texture.image,
+                        texture.current_layout.get(),
+                        layout,
+                        ash::vk::ImageAspectFlags::COLOR,
+                        1,
+                        1,
+                    )?;
+                    texture.current_layout.set(layout);
+                    image_infos.push(ash::vk::DescriptorImageInfo {
+                        sampler: ash::vk::Sampler::null(),
+                        image_view: texture.image_view,
+                        image_layout: layout,
+                    });
+                }
+            }
+        }
+
+        let mut buffer_infos = buffer_infos.iter();
+        let mut image_infos = image_infos.iter();
+        let writes = dispatch
+            .resources
+            .iter()
+            .map(|(binding, resource)| {
+                let write = ash::vk::WriteDescriptorSet {
+                    dst_binding: *binding,
+                    dst_array_element: 0,
+                    descriptor_count: 1,
+                    ..Default::default()
+                };
+                match resource {
+                    ComputeResource::StorageBuffer(_) => ash::vk::WriteDescriptorSet {
+                        descriptor_type: ash::vk::DescriptorType::STORAGE_BUFFER,
+                        p_buffer_info: buffer_infos.next().unwrap(),
+                        ..write
+                    },
+                    ComputeResource::Texture(_) => ash::vk::WriteDescriptorSet {
+                        descriptor_type: ash::vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                        p_image_info: image_infos.next().unwrap(),
+                        ..write
+                    },
+                    ComputeResource::StorageImage(_) => ash::vk::WriteDescriptorSet {
+                        descriptor_type: ash::vk::DescriptorType::STORAGE_IMAGE,
+                        p_image_info: image_infos.next().unwrap(),
+                        ..write
+                    },
+                }
+            })
+            .collect::<Vec<_>>();
+
+        unsafe {
+            self.device.cmd_bind_pipeline(
+                cmd,
+                ash::vk::PipelineBindPoint::COMPUTE,
+                pipeline.handle,
+            );
+            self.device.cmd_bind_descriptor_sets(
+                cmd,
+                ash::vk::PipelineBindPoint::COMPUTE,
+                pipeline.layout,
+                0,
+                &[self.descriptors.sets[idx]],
+                &[],
+            );
+            if !writes.is_empty() {
+                self.push_descriptor.cmd_push_descriptor_set(
+                    cmd,
+                    ash::vk::PipelineBindPoint::COMPUTE,
+                    pipeline.layout,
+                    1,
+                    &writes,
+                );
+            }
+            if !dispatch.push_constants.is_empty() {
+                self.device.cmd_push_constants(
+                    cmd,
+                    pipeline.layout,
+                    ash::vk::ShaderStageFlags::COMPUTE,
+                    0,
+                    dispatch.push_constants,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn record_compute_barrier(
+        &self,
+        cmd: ash::vk::CommandBuffer,
+        barrier: ComputeBarrier,
+    ) {
+        let (src_stage_mask, src_access_mask, dst_stage_mask, dst_access_mask) = match barrier {
+            ComputeBarrier::ComputeToCompute => (
+                ash::vk::PipelineStageFlags2::COMPUTE_SHADER,
+                ash::vk::AccessFlags2::SHADER_WRITE,
+                ash::vk::PipelineStageFlags2::COMPUTE_SHADER,
+                ash::vk::AccessFlags2::SHADER_READ | ash::vk::AccessFlags2::SHADER_WRITE,
+            ),
+            ComputeBarrier::ComputeToGraphics => (
+                ash::vk::PipelineStageFlags2::COMPUTE_SHADER,
+                ash::vk::AccessFlags2::SHADER_WRITE,
+                ash::vk::PipelineStageFlags2::DRAW_INDIRECT
+                    | ash::vk::PipelineStageFlags2::VERTEX_SHADER
+                    | ash::vk::PipelineStageFlags2::FRAGMENT_SHADER,
+                ash::vk::AccessFlags2::INDIRECT_COMMAND_READ | ash::vk::AccessFlags2::SHADER_READ,
+            ),
+            ComputeBarrier::GraphicsToCompute => (
+                ash::vk::PipelineStageFlags2::ALL_GRAPHICS,
+                ash::vk::AccessFlags2::COLOR_ATTACHMENT_WRITE
+                    | ash::vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE
+                    | ash::vk::AccessFlags2::SHADER_WRITE,
+                ash::vk::PipelineStageFlags2::COMPUTE_SHADER,
+                ash::vk::AccessFlags2::SHADER_READ | ash::vk::AccessFlags2::SHADER_WRITE,
+            ),
+        };
+        let memory_barrier = ash::vk::MemoryBarrier2 {
+            src_stage_mask,
+            src_access_mask,
+            dst_stage_mask,
+            dst_access_mask,
+            ..Default::default()
+        };
+        let dependency_info = ash::vk::DependencyInfo {
+            memory_barrier_count: 1,
+            p_memory_barriers: &memory_barrier,
+            ..Default::default()
+        };
+        unsafe { self.device.cmd_pipeline_barrier2(cmd, &dependency_info) };
+    }
+}
@@ -0,0 +1,143 @@
+//! Deferred destruction of GPU resources.
+//!
+//! Command buffers of frames still in flight may reference a texture, buffer or
+//! acceleration structure after its last handle was dropped. Dropping therefore
+//! only queues the Vulkan handles, tagged with the serial of the frame being
+//! recorded. `begin_frame` destroys everything queued up to the frame whose
+//! fence it just waited on and hands the bindless slots of destroyed textures
+//! back to the texture registry.
+
+use std::collections::VecDeque;
+
+use crate::engine::vulkan_backend::allocator::Allocation;
+
+/// Slot of a texture in one of the bindless descriptor arrays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindlessSlot {
+    Texture2d(u32),
+    Cube(u32),
+    Shadow(u32),
+    Texture2dArray(u32),
+    CubeArray(u32),
+    Volume(u32),
+}
+
+/// Handles of a dropped resource.
+pub enum PendingDestroy {
+    Texture {
+        image: ash::vk::Image,
+        image_view: ash::vk::ImageView,
+        sampler: ash::vk::Sampler,
+        allocation: Allocation,
+        slot: Option<BindlessSlot>,
+    },
+    Buffer {
+        buffer: ash::vk::Buffer,
+        allocation: Allocation,
+    },
+    AccelerationStructure(ash::vk::AccelerationStructureKHR),
+}
+
+pub struct DestructionQueue {
+    /// Serial of the frame being recorded, or of the last submitted one between frames.
+    frame_serial: u64,
+    pending: VecDeque<(u64, PendingDestroy)>,
+    /// Set on backend shutdown, later drops are left to `cleanup_leftover`.
+    closed: bool,
+}
+
+// SAFETY: `Allocation::mapped` points into persistently mapped device memory
+// owned by the allocator; the queue never dereferences it.
+unsafe impl Send for DestructionQueue {}
+
+impl DestructionQueue {
+    pub fn new() -> Self {
+        DestructionQueue {
+            frame_serial: 0,
+            pending: VecDeque::new(),
+            closed: false,
+        }
+    }
+
+    pub fn push(&mut self, resource: PendingDestroy) {
+        if !self.closed {
+            self.pending.push_back((self.frame_serial, resource));
+        }
+    }
+
+    /// Start recording a new frame and return its serial.
+    pub fn next_frame(&mut self) -> u64 {
+        self.frame_serial += 1;
+        self.frame_serial
+    }
+
+    pub fn frame_serial(&self) -> u64 {
+        self.frame_serial
+    }
+
+    /// Remove all resources dropped during frames up to and including `completed`.
+    pub fn take_completed(&mut self, completed: u64) -> Vec<PendingDestroy> {
+        let mut resources = Vec::new();
+        while self
+            .pending
+            .front()
+            .is_some_and(|(serial, _)| *serial <= completed)
+        {
+            if let Some((_, resource)) = self.pending.pop_front() {
+                resources.push(resource);
+            }
+        }
+        resources
+    }
+
+    /// Remove all queued resources and ignore any dropped afterwards.
+    pub fn close(&mut self) -> Vec<PendingDestroy> {
+        self.closed = true;
+        self.pending
+            .drain(..)
+            .map(|(_, resource)| resource)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ash::vk::Handle;
+
+    use super::*;
+
+    fn acceleration_structure(raw: u64) -> PendingDestroy {
+        PendingDestroy::AccelerationStructure(ash::vk::AccelerationStructureKHR::from_raw(raw))
+    }
+
+    fn raw_handles(resources: Vec<PendingDestroy>) -> Vec<u64> {
+        resources
+            .into_iter()
+            .map(|resource| match resource {
+                PendingDestroy::AccelerationStructure(handle) => handle.as_raw(),
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn resources_are_freed_once_their_frame_completed() {
+        let mut queue = DestructionQueue::new();
+        assert_eq!(queue.next_frame(), 1);
+        queue.push(acceleration_structure(1));
+        queue.push(acceleration_structure(2));
+        assert_eq!(queue.next_frame(), 2);
+        queue.push(acceleration_structure(3));
+
+        // frame 1 may still be in flight
+        assert!(queue.take_completed(0).is_empty());
+        assert_eq!(raw_handles(queue.take_completed(1)), [1, 2]);
+        assert!(queue.take_completed(1).is_empty());
+        assert_eq!(raw_handles(queue.take_completed(2)), [3]);
+
+        queue.push(acceleration_structure(4));
+        assert_eq!(raw_handles(queue.close()), [4]);
+        queue.push(acceleration_structure(5));
+        assert!(queue.take_completed(u64::MAX).is_empty());
+    }
+}
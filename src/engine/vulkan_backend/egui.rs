@@ -1,11 +1,17 @@
-use std::{collections::HashMap, ffi::c_void};
+use std::collections::HashMap;
 
 use crate::engine::{
     backend::{GpuError, GpuErrorKind},
-    vulkan_backend::VulkanHandleTracker,
+    vulkan_backend::{VulkanHandleTracker, allocator::Allocation},
 };
 
-use super::{FRAMES_IN_FLIGHT, SHADER_ENTRY_POINT, buffer::VulkanBuffer, create_shader_module};
+use super::{
+    FRAMES_IN_FLIGHT, SHADER_ENTRY_POINT,
+    buffer::VulkanBuffer,
+    create_shader_module,
+    spirv_reflect::{PipelineLayoutDesc, validate_shader},
+    upload::Uploader,
+};
 use crate::engine::vulkan_backend::VulkanBackend;
 
 // Egui vertex format
@@ -21,7 +27,7 @@ const EGUI_MAX_TEXTURES: u32 = 64;
 
 struct EguiTextureInfo {
     image: ash::vk::Image,
-    memory: ash::vk::DeviceMemory,
+    allocation: Allocation,
     image_view: ash::vk::ImageView,
     sampler: ash::vk::Sampler,
     descriptor_set: ash::vk::DescriptorSet,
@@ -42,8 +48,6 @@ pub(crate) struct EguiRenderer {
     device: ash::Device,
     phys_device: ash::vk::PhysicalDevice,
     instance: ash::Instance,
-    queue: ash::vk::Queue,
-    queue_family: u32,
 
     pipeline: ash::vk::Pipeline,
     pipeline_layout: ash::vk::PipelineLayout,
@@ -65,9 +69,8 @@ impl EguiRenderer {
         instance: &ash::Instance,
         device: &ash::Device,
         phys_device: ash::vk::PhysicalDevice,
-        queue: ash::vk::Queue,
-        queue_family: u32,
         swapchain_format: ash::vk::Format,
+        pipeline_cache: ash::vk::PipelineCache,
         vk_handle_tracker: VulkanHandleTracker,
     ) -> Result<Self, GpuError> {
         let device = device.clone();
@@ -75,15 +78,14 @@ impl EguiRenderer {
 
         let desc_set_layout = Self::create_descriptor_set_layout(&device)?;
         let pipeline_layout = Self::create_pipeline_layout(&device, desc_set_layout)?;
-        let pipeline = Self::create_pipeline(&device, pipeline_layout, swapchain_format)?;
+        let pipeline =
+            Self::create_pipeline(&device, pipeline_layout, swapchain_format, pipeline_cache)?;
         let pool = Self::create_descriptor_pool(&device)?;
 
         Ok(EguiRenderer {
             device,
             phys_device,
             instance,
-            queue,
-            queue_family,
             pipeline,
             pipeline_layout,
             descriptor_set_layout: desc_set_layout,
@@ -123,28 +125,54 @@ impl EguiRenderer {
                     .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
             }
         }
-        for buf in &self.vertex_buffers {
-            if let Some(b) = buf {
-                b.destroy();
-            }
-        }
-        for buf in &self.index_buffers {
-            if let Some(b) = buf {
-                b.destroy();
-            }
-        }
+        // vertex and index buffers are queued for destruction when dropped
     }
 
-    fn create_descriptor_set_layout(
-        device: &ash::Device,
-    ) -> Result<ash::vk::DescriptorSetLayout, GpuError> {
-        let binding = ash::vk::DescriptorSetLayoutBinding {
+    pub(super) fn descriptor_set_layout_binding() -> ash::vk::DescriptorSetLayoutBinding<'static> {
+        ash::vk::DescriptorSetLayoutBinding {
             binding: 0,
             descriptor_type: ash::vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
             descriptor_count: 1,
             stage_flags: ash::vk::ShaderStageFlags::FRAGMENT,
             ..Default::default()
-        };
+        }
+    }
+
+    pub(super) fn push_constant_range() -> ash::vk::PushConstantRange {
+        ash::vk::PushConstantRange {
+            stage_flags: ash::vk::ShaderStageFlags::VERTEX,
+            offset: 0,
+            size: EGUI_PUSH_CONSTANTS_SIZE,
+        }
+    }
+
+    pub(super) fn vertex_attributes() -> [ash::vk::VertexInputAttributeDescription; 3] {
+        [
+            ash::vk::VertexInputAttributeDescription {
+                location: 0,
+                binding: 0,
+                format: ash::vk::Format::R32G32_SFLOAT,
+                offset: 0,
+            },
+            ash::vk::VertexInputAttributeDescription {
+                location: 1,
+                binding: 0,
+                format: ash::vk::Format::R32G32_SFLOAT,
+                offset: 8,
+            },
+            ash::vk::VertexInputAttributeDescription {
+                location: 2,
+                binding: 0,
+                format: ash::vk::Format::R8G8B8A8_UNORM,
+                offset: 16,
+            },
+        ]
+    }
+
+    fn create_descriptor_set_layout(
+        device: &ash::Device,
+    ) -> Result<ash::vk::DescriptorSetLayout, GpuError> {
+        let binding = Self::descriptor_set_layout_binding();
         let create_info = ash::vk::DescriptorSetLayoutCreateInfo {
             binding_count: 1,
             p_bindings: &binding,
@@ -182,11 +210,7 @@ impl EguiRenderer {
         device: &ash::Device,
         desc_layout: ash::vk::DescriptorSetLayout,
     ) -> Result<ash::vk::PipelineLayout, GpuError> {
-        let push_range = ash::vk::PushConstantRange {
-            stage_flags: ash::vk::ShaderStageFlags::VERTEX,
-            offset: 0,
-            size: EGUI_PUSH_CONSTANTS_SIZE,
-        };
+        let push_range = Self::push_constant_range();
         let create_info = ash::vk::PipelineLayoutCreateInfo {
             set_layout_count: 1,
             p_set_layouts: &desc_layout,
@@ -206,10 +230,31 @@ impl EguiRenderer {
         device: &ash::Device,
         pipeline_layout: ash::vk::PipelineLayout,
         color_format: ash::vk::Format,
+        pipeline_cache: ash::vk::PipelineCache,
     ) -> Result<ash::vk::Pipeline, GpuError> {
         let vert_code = include_bytes!("../../shaders/spv/egui/egui.vert.spv");
         let frag_code = include_bytes!("../../shaders/spv/egui/egui.frag.spv");
 
+        let vertex_attributes = Self::vertex_attributes();
+        let layout = PipelineLayoutDesc {
+            sets: &[&[Self::descriptor_set_layout_binding()]],
+            push_constants: &[Self::push_constant_range()],
+        };
+        validate_shader(
+            "egui vertex",
+            vert_code,
+            ash::vk::ShaderStageFlags::VERTEX,
+            &layout,
+            Some(&vertex_attributes),
+        )?;
+        validate_shader(
+            "egui fragment",
+            frag_code,
+            ash::vk::ShaderStageFlags::FRAGMENT,
+            &layout,
+            None,
+        )?;
+
         let vert_module = create_shader_module(vert_code, device, "egui vertex")?;
         let frag_module = create_shader_module(frag_code, device, "egui fragment")?;
 
@@ -233,26 +278,6 @@ impl EguiRenderer {
             stride: EGUI_VERTEX_STRIDE,
             input_rate: ash::vk::VertexInputRate::VERTEX,
         };
-        let vertex_attributes = [
-            ash::vk::VertexInputAttributeDescription {
-                location: 0,
-                binding: 0,
-                format: ash::vk::Format::R32G32_SFLOAT,
-                offset: 0,
-            },
-            ash::vk::VertexInputAttributeDescription {
-                location: 1,
-                binding: 0,
-                format: ash::vk::Format::R32G32_SFLOAT,
-                offset: 8,
-            },
-            ash::vk::VertexInputAttributeDescription {
-                location: 2,
-                binding: 0,
-                format: ash::vk::Format::R8G8B8A8_UNORM,
-                offset: 16,
-            },
-        ];
         let vtx_input = ash::vk::PipelineVertexInputStateCreateInfo {
             vertex_binding_description_count: 1,
             p_vertex_binding_descriptions: &vertex_binding,
@@ -339,15 +364,13 @@ impl EguiRenderer {
             ..Default::default()
         };
 
-        let pipelines = unsafe {
-            device.create_graphics_pipelines(ash::vk::PipelineCache::null(), &[info], None)
-        }
-        .map_err(|(_, e)| {
-            GpuError::new(
-                format!("Failed to create egui pipeline: {e:?}"),
-                GpuErrorKind::ResourceCreation,
-            )
-        })?;
+        let pipelines = unsafe { device.create_graphics_pipelines(pipeline_cache, &[info], None) }
+            .map_err(|(_, e)| {
+                GpuError::new(
+                    format!("Failed to create egui pipeline: {e:?}"),
+                    GpuErrorKind::ResourceCreation,
+                )
+            })?;
 
         unsafe {
             device.destroy_shader_module(vert_module, None);
@@ -359,6 +382,7 @@ impl EguiRenderer {
 
     pub fn create_or_update_texture(
         &mut self,
+        uploader: &mut Uploader,
         id: egui::TextureId,
         delta: &egui::epaint::ImageDelta,
     ) {
@@ -366,11 +390,18 @@ impl EguiRenderer {
         if let Some(pos) = delta.pos {
             if let Some(tex) = self.textures.get(&id) {
                 let egui::ImageData::Color(image) = &delta.image;
-                let w = image.size[0] as u32;
-                let h = image.size[1] as u32;
+                let offset = ash::vk::Offset3D {
+                    x: pos[0] as i32,
+                    y: pos[1] as i32,
+                    z: 0,
+                };
+                let extent = ash::vk::Extent3D {
+                    width: image.size[0] as u32,
+                    height: image.size[1] as u32,
+                    depth: 1,
+                };
                 let raw: Vec<u8> = image.pixels.iter().flat_map(|c| c.to_array()).collect();
-                if let Err(e) =
-                    self.upload_texture_sub_region(tex, pos[0] as u32, pos[1] as u32, w, h, &raw)
+                if let Err(e) = self.upload_texture_sub_region(uploader, tex, offset, extent, &raw)
                 {
                     eprintln!("Failed to update egui texture sub-region: {e:?}");
                 }
@@ -394,7 +425,7 @@ impl EguiRenderer {
 
         let format = ash::vk::Format::R8G8B8A8_UNORM;
 
-        match self.upload_texture(width, height, format, &pixels) {
+        match self.upload_texture(uploader, width, height, format, &pixels) {
             Ok(info) => {
                 self.textures.insert(id, info);
             }
@@ -406,52 +437,27 @@ impl EguiRenderer {
 
     fn upload_texture(
         &self,
+        uploader: &mut Uploader,
         width: u32,
         height: u32,
         format: ash::vk::Format,
         pixels: &[u8],
     ) -> Result<EguiTextureInfo, GpuError> {
-        let data_size = (width as u64) * (height as u64) * 4;
-
-        let (staging_buf, staging_mem) = VulkanBackend::create_buffer(
-            &self.instance,
+        let (image, allocation) = VulkanBackend::create_image(
+            self.vk_handle_tracker.allocator(),
             &self.device,
-            self.phys_device,
-            data_size,
-            ash::vk::BufferUsageFlags::TRANSFER_SRC,
-            ash::vk::MemoryPropertyFlags::HOST_VISIBLE
-                | ash::vk::MemoryPropertyFlags::HOST_COHERENT,
-        )?;
-
-        let mapped = unsafe {
-            self.device
-                .map_memory(staging_mem, 0, data_size, ash::vk::MemoryMapFlags::empty())
-                .map_err(|e| {
-                    GpuError::new(
-                        format!("Failed to map staging: {e:?}"),
-                        GpuErrorKind::ResourceUpdate,
-                    )
-                })?
-        };
-        unsafe {
-            mapped.copy_from(pixels.as_ptr() as *const c_void, data_size as usize);
-        }
-        unsafe { self.device.unmap_memory(staging_mem) };
-
-        let (image, memory) = VulkanBackend::create_image(
-            &self.instance,
-            &self.device,
-            self.phys_device,
             width,
             height,
             format,
             1,
+            ash::vk::SampleCountFlags::TYPE_1,
             ash::vk::ImageTiling::OPTIMAL,
             ash::vk::ImageUsageFlags::TRANSFER_DST | ash::vk::ImageUsageFlags::SAMPLED,
             ash::vk::MemoryPropertyFlags::DEVICE_LOCAL,
         )?;
 
-        let (upload_pool, cmd) = self.create_upload_command_buffer()?;
+        let staged = uploader.stage(&[pixels])?;
+        let cmd = uploader.transfer_cmd()?;
 
         layout_transition(
             &self.device,
@@ -463,7 +469,7 @@ impl EguiRenderer {
         );
 
         let copy = ash::vk::BufferImageCopy {
-            buffer_offset: 0,
+            buffer_offset: staged.offset,
             buffer_row_length: 0,
             buffer_image_height: 0,
             image_subresource: ash::vk::ImageSubresourceLayers {
@@ -482,23 +488,26 @@ impl EguiRenderer {
         unsafe {
             self.device.cmd_copy_buffer_to_image(
                 cmd,
-                staging_buf,
+                staged.buffer,
                 image,
                 ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL,
                 &[copy],
             );
         }
 
-        layout_transition(
-            &self.device,
-            cmd,
+        uploader.finish_image(
             image,
-            ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            ash::vk::ImageSubresourceRange {
+                aspect_mask: ash::vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
             ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-            ash::vk::ImageAspectFlags::COLOR,
-        );
-
-        self.submit_and_cleanup_upload(cmd, upload_pool, staging_buf, staging_mem)?;
+            ash::vk::PipelineStageFlags2::FRAGMENT_SHADER,
+            ash::vk::AccessFlags2::SHADER_READ,
+        )?;
 
         let view_info = ash::vk::ImageViewCreateInfo {
             image,
@@ -543,7 +552,7 @@ impl EguiRenderer {
 
         Ok(EguiTextureInfo {
             image,
-            memory,
+            allocation,
             image_view,
             sampler,
             descriptor_set,
@@ -551,44 +560,17 @@ impl EguiRenderer {
     }
 
     /// Upload pixel data into a sub-region of an existing texture.
-    /// Uses a one-shot command buffer with queue_wait_idle.
+    /// Recorded on the main queue since the texture may still be sampled by frames in flight.
     fn upload_texture_sub_region(
         &self,
+        uploader: &mut Uploader,
         tex: &EguiTextureInfo,
-        offset_x: u32,
-        offset_y: u32,
-        width: u32,
-        height: u32,
+        offset: ash::vk::Offset3D,
+        extent: ash::vk::Extent3D,
         pixels: &[u8],
     ) -> Result<(), GpuError> {
-        let data_size = (width as u64) * (height as u64) * 4;
-
-        let (staging_buf, staging_mem) = VulkanBackend::create_buffer(
-            &self.instance,
-            &self.device,
-            self.phys_device,
-            data_size,
-            ash::vk::BufferUsageFlags::TRANSFER_SRC,
-            ash::vk::MemoryPropertyFlags::HOST_VISIBLE
-                | ash::vk::MemoryPropertyFlags::HOST_COHERENT,
-        )?;
-
-        let mapped = unsafe {
-            self.device
-                .map_memory(staging_mem, 0, data_size, ash::vk::MemoryMapFlags::empty())
-                .map_err(|e| {
-                    GpuError::new(
-                        format!("Failed to map staging: {e:?}"),
-                        GpuErrorKind::ResourceUpdate,
-                    )
-                })?
-        };
-        unsafe {
-            mapped.copy_from(pixels.as_ptr() as *const c_void, data_size as usize);
-        }
-        unsafe { self.device.unmap_memory(staging_mem) };
-
-        let (upload_pool, cmd) = self.create_upload_command_buffer()?;
+        let staged = uploader.stage(&[pixels])?;
+        let cmd = uploader.graphics_cmd()?;
 
         // SHADER_READ_ONLY → TRANSFER_DST
         layout_transition(
@@ -602,7 +584,7 @@ impl EguiRenderer {
 
         // Copy
         let copy = ash::vk::BufferImageCopy {
-            buffer_offset: 0,
+            buffer_offset: staged.offset,
             buffer_row_length: 0,
             buffer_image_height: 0,
             image_subresource: ash::vk::ImageSubresourceLayers {
@@ -611,21 +593,13 @@ impl EguiRenderer {
                 base_array_layer: 0,
                 layer_count: 1,
             },
-            image_offset: ash::vk::Offset3D {
-                x: offset_x as i32,
-                y: offset_y as i32,
-                z: 0,
-            },
-            image_extent: ash::vk::Extent3D {
-                width,
-                height,
-                depth: 1,
-            },
+            image_offset: offset,
+            image_extent: extent,
         };
         unsafe {
             self.device.cmd_copy_buffer_to_image(
                 cmd,
-                staging_buf,
+                staged.buffer,
                 tex.image,
                 ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL,
                 &[copy],
@@ -642,103 +616,6 @@ impl EguiRenderer {
             ash::vk::ImageAspectFlags::COLOR,
         );
 
-        self.submit_and_cleanup_upload(cmd, upload_pool, staging_buf, staging_mem)
-    }
-
-    /// Helper: create a transient one-shot command buffer for uploads.
-    fn create_upload_command_buffer(
-        &self,
-    ) -> Result<(ash::vk::CommandPool, ash::vk::CommandBuffer), GpuError> {
-        let pool_info = ash::vk::CommandPoolCreateInfo {
-            flags: ash::vk::CommandPoolCreateFlags::TRANSIENT,
-            queue_family_index: self.queue_family,
-            ..Default::default()
-        };
-        let upload_pool =
-            unsafe { self.device.create_command_pool(&pool_info, None) }.map_err(|e| {
-                GpuError::new(
-                    format!("Failed to create upload pool: {e:?}"),
-                    GpuErrorKind::ResourceCreation,
-                )
-            })?;
-
-        let alloc_info = ash::vk::CommandBufferAllocateInfo {
-            command_pool: upload_pool,
-            level: ash::vk::CommandBufferLevel::PRIMARY,
-            command_buffer_count: 1,
-            ..Default::default()
-        };
-        let cmd_bufs =
-            unsafe { self.device.allocate_command_buffers(&alloc_info) }.map_err(|e| {
-                GpuError::new(
-                    format!("Failed to allocate upload cmd buf: {e:?}"),
-                    GpuErrorKind::ResourceCreation,
-                )
-            })?;
-        let cmd = cmd_bufs[0];
-
-        unsafe {
-            self.device.begin_command_buffer(
-                cmd,
-                &ash::vk::CommandBufferBeginInfo {
-                    flags: ash::vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
-                    ..Default::default()
-                },
-            )
-        }
-        .map_err(|e| {
-            GpuError::new(
-                format!("Failed to begin upload cmd buf: {e:?}"),
-                GpuErrorKind::ResourceUpdate,
-            )
-        })?;
-
-        Ok((upload_pool, cmd))
-    }
-
-    /// Helper: submit a one-shot upload command buffer, wait for completion, clean up resources.
-    fn submit_and_cleanup_upload(
-        &self,
-        cmd: ash::vk::CommandBuffer,
-        upload_pool: ash::vk::CommandPool,
-        staging_buf: ash::vk::Buffer,
-        staging_mem: ash::vk::DeviceMemory,
-    ) -> Result<(), GpuError> {
-        unsafe { self.device.end_command_buffer(cmd) }.map_err(|e| {
-            GpuError::new(
-                format!("Failed to end upload cmd buf: {e:?}"),
-                GpuErrorKind::ResourceUpdate,
-            )
-        })?;
-
-        let submit = ash::vk::SubmitInfo {
-            command_buffer_count: 1,
-            p_command_buffers: &cmd,
-            ..Default::default()
-        };
-        unsafe {
-            self.device
-                .queue_submit(self.queue, &[submit], ash::vk::Fence::null())
-        }
-        .map_err(|e| {
-            GpuError::new(
-                format!("Failed to submit upload: {e:?}"),
-                GpuErrorKind::ResourceUpdate,
-            )
-        })?;
-        unsafe { self.device.queue_wait_idle(self.queue) }.map_err(|e| {
-            GpuError::new(
-                format!("Failed to wait for upload: {e:?}"),
-                GpuErrorKind::ResourceUpdate,
-            )
-        })?;
-
-        unsafe {
-            self.device.destroy_command_pool(upload_pool, None);
-            self.device.destroy_buffer(staging_buf, None);
-            self.device.free_memory(staging_mem, None);
-        }
-
         Ok(())
     }
 
@@ -819,11 +696,8 @@ impl EguiRenderer {
                 vk_handle_tracker.unregister_image(tex.image);
                 device.destroy_image(tex.image, None);
             }
-            if tex.memory != ash::vk::DeviceMemory::null() {
-                vk_handle_tracker.unregister_device_memory(tex.memory);
-                device.free_memory(tex.memory, None);
-            }
         }
+        vk_handle_tracker.allocator().free(tex.allocation);
     }
 
     pub fn ensure_buffer_capacity(
@@ -846,14 +720,11 @@ impl EguiRenderer {
         let new_cap = (needed.max(1) as f64 * 1.5) as usize;
         let size = (new_cap as u64) * (EGUI_VERTEX_STRIDE as u64);
 
-        if let Some(old) = &self.vertex_buffers[buf_idx] {
-            old.destroy();
-        }
+        self.vertex_buffers[buf_idx] = None;
 
-        let (buf, mem) = match VulkanBackend::create_buffer(
-            &self.instance,
+        let (buf, allocation) = match VulkanBackend::create_buffer(
+            self.vk_handle_tracker.allocator(),
             &self.device,
-            self.phys_device,
             size,
             ash::vk::BufferUsageFlags::VERTEX_BUFFER,
             ash::vk::MemoryPropertyFlags::HOST_VISIBLE
@@ -867,17 +738,10 @@ impl EguiRenderer {
             }
         };
 
-        let mapped = unsafe {
-            self.device
-                .map_memory(mem, 0, size, ash::vk::MemoryMapFlags::empty())
-                .ok()
-        }
-        .unwrap_or(std::ptr::null_mut());
-
         self.vertex_buffers[buf_idx] = Some(VulkanBuffer {
             buffer: buf,
-            memory: mem,
-            mapped,
+            allocation,
+            mapped: allocation.mapped,
             flags: ash::vk::MemoryPropertyFlags::HOST_VISIBLE
                 | ash::vk::MemoryPropertyFlags::HOST_COHERENT,
             size,
@@ -893,14 +757,11 @@ impl EguiRenderer {
         let new_cap = (needed.max(1) as f64 * 1.5) as usize;
         let size = (new_cap as u64) * 4; // u32 indices
 
-        if let Some(old) = &self.index_buffers[buf_idx] {
-            old.destroy();
-        }
+        self.index_buffers[buf_idx] = None;
 
-        let (buf, mem) = match VulkanBackend::create_buffer(
-            &self.instance,
+        let (buf, allocation) = match VulkanBackend::create_buffer(
+            self.vk_handle_tracker.allocator(),
             &self.device,
-            self.phys_device,
             size,
             ash::vk::BufferUsageFlags::INDEX_BUFFER,
             ash::vk::MemoryPropertyFlags::HOST_VISIBLE
@@ -914,17 +775,10 @@ impl EguiRenderer {
             }
         };
 
-        let mapped = unsafe {
-            self.device
-                .map_memory(mem, 0, size, ash::vk::MemoryMapFlags::empty())
-                .ok()
-        }
-        .unwrap_or(std::ptr::null_mut());
-
         self.index_buffers[buf_idx] = Some(VulkanBuffer {
             buffer: buf,
-            memory: mem,
-            mapped,
+            allocation,
+            mapped: allocation.mapped,
             flags: ash::vk::MemoryPropertyFlags::HOST_VISIBLE
                 | ash::vk::MemoryPropertyFlags::HOST_COHERENT,
             size,
@@ -1,48 +1,68 @@
 // use std::any::TypeId;
 
-use std::{ffi::CString, mem::offset_of};
+use std::{borrow::Cow, ffi::CString, mem::offset_of, path::Path};
 
 use crate::engine::{
     backend::{
-        AccelerationStructureType, BlendMode, BufferDesc, BufferUsage, ComputePipelineDesc,
-        GpuBackend, GpuError, GpuErrorKind, GpuRenderTarget, GpuTexture, MaterialProperties,
-        ObjType, ProceduralShaders, RenderItem, RenderPassDesc, RenderPipelineDesc,
-        RenderTargetDesc, RenderTargetUsage, RtShaders, SamplerDesc, ShaderStage, Shaders,
-        TextureDesc, TextureFormat, ViewportDesc, as_bytes,
+        AccelerationStructureType, BlendMode, BufferDesc, BufferUsage, ComputeBarrier,
+        ComputeDispatch, ComputePipelineDesc, GpuBackend, GpuError, GpuErrorKind, GpuFrameTimings,
+        GpuMemoryHeapStats, GpuRenderTarget, GpuTexture, MaterialProperties, ObjType,
+        ProceduralShaders, RenderItem, RenderPassDesc, RenderPipelineDesc, RenderTargetDesc,
+        RenderTargetUsage, RtShaders, SamplerDesc, ShaderStage, Shaders, TargetBarrier,
+        TextureDesc, TextureFormat, TextureReadback, ViewportDesc, as_bytes,
     },
-    compute_push::ComputePushConstants,
     geometry::Vertex,
     vulkan_backend::{
-        CurrentFrame, ENABLE_MARKER, FRAMES_IN_FLIGHT, GAMMA_DEFAULT, PushConstants,
-        SHADER_ENTRY_POINT, SpecializationConstants, VulkanBackend,
-        buffer::{self, VulkanBuffer},
-        create_shader_module,
+        CurrentFrame, ENABLE_MARKER, FRAMES_IN_FLIGHT, GAMMA_DEFAULT, MAX_PREV_MODELS,
+        NO_PREV_MODEL, PushConstants, SHADER_ENTRY_POINT, SpecializationConstants, VulkanBackend,
+        allocator::Allocation,
+        bindless_layout_bindings,
+        buffer::VulkanBuffer,
+        compute, create_shader_module,
         egui::{EguiRenderer, build_egui_batches},
+        graphics_push_constant_range,
         rt::{self, IDX_AHIT, IDX_CHIT, IDX_MISS, IDX_MISS_SHADOW, IDX_RAYGEN},
+        spirv_reflect::{PipelineLayoutDesc, validate_shader},
         texture::VulkanTexture,
-        util::gpu_error_out_of_range,
+        util::{self, gpu_error_out_of_range, sample_count_flags, stencil_op_state},
     },
 };
 
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+/// Largest write of a single `vkCmdUpdateBuffer`.
+const CMD_UPDATE_BUFFER_LIMIT: usize = 65536;
+
 pub struct Shader {
     label: &'static str,
     stage: ash::vk::ShaderStageFlags,
-    code: &'static [u8],
+    /// Path relative to `src/shaders/spv`, used by shader hot reload.
+    path: &'static str,
+    code: Cow<'static, [u8]>,
 }
 
 #[derive(Clone)]
 pub enum RenderTarget {
-    Texture(TextureRenderTarget),
-    Swapchain(VulkanTexture),
+    Texture(Box<TextureRenderTarget>),
+    Swapchain(Box<VulkanTexture>),
 }
 
 impl RenderTarget {
-    fn get_target(&self, idx: usize) -> &VulkanTexture {
+    pub(super) fn get_target(&self, idx: usize) -> &VulkanTexture {
         match self {
             RenderTarget::Swapchain(tex) => tex,
             RenderTarget::Texture(tex) => &tex.targets[idx],
         }
     }
+
+    /// The view of `layer` to attach, the whole target if it isn't layered.
+    fn attachment_view(&self, idx: usize, layer: u32) -> ash::vk::ImageView {
+        match self {
+            RenderTarget::Texture(tex) if !tex.layer_views[idx].is_empty() => {
+                tex.layer_views[idx][layer as usize].image_view
+            }
+            _ => self.get_target(idx).image_view,
+        }
+    }
 }
 
 impl GpuTexture for RenderTarget {
@@ -88,6 +108,8 @@ pub struct TextureRenderTarget {
     format: TextureFormat,
     id: usize,
     targets: [VulkanTexture; FRAMES_IN_FLIGHT as usize],
+    /// Per-layer views of `targets` to render into, empty unless layered.
+    layer_views: [Vec<VulkanTexture>; FRAMES_IN_FLIGHT as usize],
 }
 
 impl GpuRenderTarget for RenderTarget {}
@@ -109,71 +131,147 @@ impl GpuBackend for VulkanBackend {
         let deferred_pre_vtx = Shader {
             label: "Deferred Pre VTX",
             stage: ash::vk::ShaderStageFlags::VERTEX,
-            code: include_bytes!("../../shaders/spv/deferred/pre_vertex.spv"),
+            path: "deferred/pre_vertex.spv",
+            code: Cow::Borrowed(include_bytes!("../../shaders/spv/deferred/pre_vertex.spv")),
         };
         let deferred_pre_pxl = Shader {
             label: "Deferred Pre PXL",
             stage: ash::vk::ShaderStageFlags::FRAGMENT,
-            code: include_bytes!("../../shaders/spv/deferred/pre_pixel.spv"),
+            path: "deferred/pre_pixel.spv",
+            code: Cow::Borrowed(include_bytes!("../../shaders/spv/deferred/pre_pixel.spv")),
         };
         let deferred_light_vtx = Shader {
             label: "Deferred Light VTX",
             stage: ash::vk::ShaderStageFlags::VERTEX,
-            code: include_bytes!("../../shaders/spv/deferred/light_vertex.spv"),
+            path: "deferred/light_vertex.spv",
+            code: Cow::Borrowed(include_bytes!(
+                "../../shaders/spv/deferred/light_vertex.spv"
+            )),
         };
         let deferred_light_pxl = Shader {
             label: "Deferred Light PXL",
             stage: ash::vk::ShaderStageFlags::FRAGMENT,
-            code: include_bytes!("../../shaders/spv/deferred/light_pixel.spv"),
+            path: "deferred/light_pixel.spv",
+            code: Cow::Borrowed(include_bytes!("../../shaders/spv/deferred/light_pixel.spv")),
         };
         let forward_vtx = Shader {
             label: "Forward Pass VTX",
             stage: ash::vk::ShaderStageFlags::VERTEX,
-            code: include_bytes!("../../shaders/spv/main_pass/vertex.spv"),
+            path: "main_pass/vertex.spv",
+            code: Cow::Borrowed(include_bytes!("../../shaders/spv/main_pass/vertex.spv")),
         };
         let forward_pxl = Shader {
             label: "Forward Pass PXL",
             stage: ash::vk::ShaderStageFlags::FRAGMENT,
-            code: include_bytes!("../../shaders/spv/main_pass/pixel.spv"),
+            path: "main_pass/pixel.spv",
+            code: Cow::Borrowed(include_bytes!("../../shaders/spv/main_pass/pixel.spv")),
         };
         let sky_vtx = Shader {
             label: "Skybox VTX",
             stage: ash::vk::ShaderStageFlags::VERTEX,
-            code: include_bytes!("../../shaders/spv/skybox/sky_vertex.spv"),
+            path: "skybox/sky_vertex.spv",
+            code: Cow::Borrowed(include_bytes!("../../shaders/spv/skybox/sky_vertex.spv")),
         };
         let sky_pxl = Shader {
             label: "Skybox PXL",
             stage: ash::vk::ShaderStageFlags::FRAGMENT,
-            code: include_bytes!("../../shaders/spv/skybox/sky_pixel.spv"),
+            path: "skybox/sky_pixel.spv",
+            code: Cow::Borrowed(include_bytes!("../../shaders/spv/skybox/sky_pixel.spv")),
         };
         let shadow_vtx = Shader {
             label: "Shadow VTX",
             stage: ash::vk::ShaderStageFlags::VERTEX,
-            code: include_bytes!("../../shaders/spv/shadow_mapping/sm_vert.spv"),
+            path: "shadow_mapping/sm_vert.spv",
+            code: Cow::Borrowed(include_bytes!(
+                "../../shaders/spv/shadow_mapping/sm_vert.spv"
+            )),
         };
         let shadow_pixel = Shader {
             label: "Shadow PXL",
             stage: ash::vk::ShaderStageFlags::FRAGMENT,
-            code: include_bytes!("../../shaders/spv/shadow_mapping/sm_pixel.spv"),
+            path: "shadow_mapping/sm_pixel.spv",
+            code: Cow::Borrowed(include_bytes!(
+                "../../shaders/spv/shadow_mapping/sm_pixel.spv"
+            )),
         };
         let blend = Shader {
             label: "Blend PXL",
             stage: ash::vk::ShaderStageFlags::FRAGMENT,
-            code: include_bytes!("../../shaders/spv/blend.spv"),
+            path: "blend.spv",
+            code: Cow::Borrowed(include_bytes!("../../shaders/spv/blend.spv")),
         };
         let blend_vtx = Shader {
             label: "Blend VTX",
             stage: ash::vk::ShaderStageFlags::VERTEX,
-            code: include_bytes!("../../shaders/spv/deferred/light_vertex.spv"),
+            path: "deferred/light_vertex.spv",
+            code: Cow::Borrowed(include_bytes!(
+                "../../shaders/spv/deferred/light_vertex.spv"
+            )),
+        };
+
+        let ssao_vtx = Shader {
+            label: "SSAO VTX",
+            stage: ash::vk::ShaderStageFlags::VERTEX,
+            path: "deferred/light_vertex.spv",
+            code: Cow::Borrowed(include_bytes!(
+                "../../shaders/spv/deferred/light_vertex.spv"
+            )),
+        };
+        let ssao_pxl = Shader {
+            label: "SSAO PXL",
+            stage: ash::vk::ShaderStageFlags::FRAGMENT,
+            path: "ssao/ssao_pixel.spv",
+            code: Cow::Borrowed(include_bytes!("../../shaders/spv/ssao/ssao_pixel.spv")),
+        };
+        let ssao_blur_vtx = Shader {
+            label: "SSAO Blur VTX",
+            stage: ash::vk::ShaderStageFlags::VERTEX,
+            path: "deferred/light_vertex.spv",
+            code: Cow::Borrowed(include_bytes!(
+                "../../shaders/spv/deferred/light_vertex.spv"
+            )),
+        };
+        let ssao_blur_pxl = Shader {
+            label: "SSAO Blur PXL",
+            stage: ash::vk::ShaderStageFlags::FRAGMENT,
+            path: "ssao/ssao_blur_pixel.spv",
+            code: Cow::Borrowed(include_bytes!("../../shaders/spv/ssao/ssao_blur_pixel.spv")),
+        };
+        let taa_vtx = Shader {
+            label: "TAA VTX",
+            stage: ash::vk::ShaderStageFlags::VERTEX,
+            path: "deferred/light_vertex.spv",
+            code: Cow::Borrowed(include_bytes!(
+                "../../shaders/spv/deferred/light_vertex.spv"
+            )),
+        };
+
+        let light_culling = Shader {
+            label: "Light Culling Comp",
+            stage: ash::vk::ShaderStageFlags::COMPUTE,
+            path: "compute/light_culling.spv",
+            code: Cow::Borrowed(include_bytes!(
+                "../../shaders/spv/compute/light_culling.spv"
+            )),
+        };
+        let taa_pxl = Shader {
+            label: "TAA PXL",
+            stage: ash::vk::ShaderStageFlags::FRAGMENT,
+            path: "taa/taa_pixel.spv",
+            code: Cow::Borrowed(include_bytes!("../../shaders/spv/taa/taa_pixel.spv")),
         };
 
         Shaders {
             deferred_pre: vec![deferred_pre_vtx, deferred_pre_pxl],
+            ssao: vec![ssao_vtx, ssao_pxl],
+            ssao_blur: vec![ssao_blur_vtx, ssao_blur_pxl],
             deferred_light: vec![deferred_light_vtx, deferred_light_pxl],
             forward: vec![forward_vtx, forward_pxl],
+            taa: vec![taa_vtx, taa_pxl],
             shadow: vec![shadow_vtx, shadow_pixel],
             skybox: vec![sky_vtx, sky_pxl],
             output: vec![blend_vtx, blend],
+            light_culling: vec![light_culling],
         }
     }
 
@@ -181,7 +279,10 @@ impl GpuBackend for VulkanBackend {
         let scattering = Shader {
             label: "Scattering Comp",
             stage: ash::vk::ShaderStageFlags::COMPUTE,
-            code: include_bytes!("../../shaders/spv/compute/scattering_comp.spv"),
+            path: "compute/scattering_comp.spv",
+            code: Cow::Borrowed(include_bytes!(
+                "../../shaders/spv/compute/scattering_comp.spv"
+            )),
         };
 
         ProceduralShaders {
@@ -189,30 +290,71 @@ impl GpuBackend for VulkanBackend {
         }
     }
 
+    fn shader_files(&self, source: &Self::ShaderSource) -> Vec<&'static str> {
+        source.iter().map(|shader| shader.path).collect()
+    }
+
+    fn reload_shader_source(
+        &self,
+        source: &Self::ShaderSource,
+        spirv_dir: &Path,
+    ) -> Result<Self::ShaderSource, GpuError> {
+        source
+            .iter()
+            .map(|shader| {
+                let file = spirv_dir.join(shader.path);
+                let code = std::fs::read(&file).map_err(|e| {
+                    GpuError::new(
+                        format!("Failed to read {}: {e}", file.display()),
+                        GpuErrorKind::ShaderCompilation,
+                    )
+                })?;
+                // A file caught mid-write fails here instead of in the driver
+                if code.len() % 4 != 0 || code.get(..4) != Some(&SPIRV_MAGIC.to_le_bytes()) {
+                    return Err(GpuError::new(
+                        format!("{} is not a valid SPIR-V binary", file.display()),
+                        GpuErrorKind::ShaderCompilation,
+                    ));
+                }
+                Ok(Shader {
+                    label: shader.label,
+                    stage: shader.stage,
+                    path: shader.path,
+                    code: Cow::Owned(code),
+                })
+            })
+            .collect()
+    }
+
     fn create_texture(&self, desc: &TextureDesc, data: &[u8]) -> Result<Self::Texture, GpuError> {
         let mut tex = if matches!(
             desc.format,
             TextureFormat::Depth24Stencil8 | TextureFormat::Depth32Float
         ) {
             self.create_depth_texture(desc.width, desc.height, desc.format, &Some(desc.sampler))?
+        } else if !self.supports_texture_format(desc.format) {
+            return Err(GpuError::new(
+                format!(
+                    "{:?} textures are not supported by this device",
+                    desc.format
+                ),
+                GpuErrorKind::ResourceCreation,
+            ));
         } else {
             self.create_vk_texture(desc, data)?
         };
-        self.register_texture(&mut tex);
+        self.register_texture(&mut tex)?;
         Ok(tex)
     }
 
-    fn create_cubemap(
+    fn create_layer_view(
         &self,
-        faces: [&[u8]; 6],
-        width: u32,
-        height: u32,
-        format: TextureFormat,
-        sampler: &SamplerDesc,
+        texture: &Self::Texture,
+        layer: u32,
     ) -> Result<Self::Texture, GpuError> {
-        let mut tex = self.create_vk_cubemap(faces, width, height, format, sampler)?;
-        self.register_texture(&mut tex);
-        Ok(tex)
+        let mut view = self.create_vk_layer_view(texture, layer)?;
+        self.register_texture(&mut view)?;
+        Ok(view)
     }
 
     fn create_buffer(
@@ -239,50 +381,17 @@ impl GpuBackend for VulkanBackend {
         // Per-frame copies prevent GPU data hazards when multiple in-flight
         // frames write to the same uniform buffer via cmd_update_buffer.
         if desc.usage == BufferUsage::Uniform {
-            let copies: Result<Vec<buffer::PerFrameCopy>, GpuError> = (1..FRAMES_IN_FLIGHT)
-                .map(|_| {
-                    let (buf, mem) = VulkanBackend::create_buffer(
-                        &self.instance,
-                        &self.device,
-                        self.phys_device,
-                        desc.size as u64,
-                        usage,
-                        flags,
-                    )?;
-                    let mapped = if buffer::host_mappable(flags) {
-                        unsafe {
-                            self.device.map_memory(
-                                mem,
-                                0,
-                                desc.size as u64,
-                                ash::vk::MemoryMapFlags::empty(),
-                            )
-                        }
-                        .map_err(|e| {
-                            GpuError::new(
-                                format!("Failed to map per-frame copy memory: {e:?}"),
-                                GpuErrorKind::ResourceUpdate,
-                            )
-                        })?
-                    } else {
-                        std::ptr::null_mut()
-                    };
-                    // Register with tracker so cleanup_leftover catches unfreed copies
-                    self.vulkan_handle_tracker.register_buffer(buf);
-                    self.vulkan_handle_tracker.register_device_memory(mem);
-
-                    Ok(buffer::PerFrameCopy {
-                        buffer: buf,
-                        memory: mem,
-                        mapped,
-                    })
-                })
-                .collect();
-            buffer.per_frame_copies = Some(copies?);
+            buffer.per_frame_copies =
+                Some(self.create_per_frame_copies(desc.size as u64, usage, flags)?);
         }
 
         if let Some(data) = data {
-            self.update_buffer(&buffer, data);
+            if buffer.is_host_mapable() {
+                self.update_buffer(&buffer, data);
+            } else {
+                let size = data.len().min(buffer.size as usize);
+                self.upload_to_buffer(&buffer, &data[..size], true)?;
+            }
         }
         Ok(buffer)
     }
@@ -296,31 +405,53 @@ impl GpuBackend for VulkanBackend {
             TextureFormat::Depth32Float | TextureFormat::Depth24Stencil8
         );
 
+        // multisampled targets are never sampled and get no bindless slot
+        let multisampled = desc.sample_count > 1;
+
         // Allocate a SINGLE slot for the entire render target group (all frames)
         let slot = {
             let mut reg = self.texture_registry.borrow_mut();
-            if is_depth {
-                reg.allocate_shadow()
+            if multisampled {
+                u32::MAX
+            } else if is_depth {
+                reg.allocate_shadow()?
             } else {
-                reg.allocate_2d()
+                reg.allocate_2d()?
             }
         };
 
+        let create = || {
+            Self::create_vk_render_target(
+                &self.instance,
+                &self.device,
+                self.phys_device,
+                desc,
+                self.vulkan_handle_tracker.clone(),
+            )
+        };
+        // Persistent targets share one image between all frames in flight
+        let shared = if desc.persistent {
+            Some(create()?)
+        } else {
+            None
+        };
+
         let targets: [VulkanTexture; FRAMES_IN_FLIGHT as usize] = (0..FRAMES_IN_FLIGHT as usize)
             .map(|i| {
-                let mut tex = Self::create_vk_render_target(
-                    &self.instance,
-                    &self.device,
-                    self.phys_device,
-                    desc,
-                    self.vulkan_handle_tracker.clone(),
-                )?;
+                let mut tex = match &shared {
+                    Some(tex) => tex.clone(),
+                    None => create()?,
+                };
 
                 // Link physical texture to the shared slot
                 tex.descriptor_index = slot;
 
-                if is_depth {
-                    // For depth targets (Shadow Maps), we must update BOTH the image and sampler bindings
+                if multisampled {
+                    // resolved into a sampled target instead
+                } else if is_depth {
+                    // For depth targets (Shadow Maps), we must update BOTH the image and sampler bindings;
+                    // layered ones are cube map arrays
+                    let image_binding = if desc.layers > 1 { 22 } else { 8 };
                     let image_info = ash::vk::DescriptorImageInfo {
                         image_view: tex.image_view,
                         image_layout: ash::vk::ImageLayout::DEPTH_READ_ONLY_OPTIMAL,
@@ -333,7 +464,7 @@ impl GpuBackend for VulkanBackend {
                     let writes = [
                         ash::vk::WriteDescriptorSet {
                             dst_set: self.descriptors.sets[i],
-                            dst_binding: 8,
+                            dst_binding: image_binding,
                             dst_array_element: slot,
                             descriptor_type: ash::vk::DescriptorType::SAMPLED_IMAGE,
                             descriptor_count: 1,
@@ -373,20 +504,33 @@ impl GpuBackend for VulkanBackend {
             .collect::<Result<Vec<_>, GpuError>>()?
             .try_into()
             .expect("Vec of size FRAMES_IN_FLIGHT has to fit in array");
+        let layered_count = if desc.layers > 1 { desc.layers } else { 0 };
+        let layer_views: [Vec<VulkanTexture>; FRAMES_IN_FLIGHT as usize] = targets
+            .iter()
+            .map(|tex| {
+                (0..layered_count)
+                    .map(|layer| self.create_vk_layer_view(tex, layer))
+                    .collect()
+            })
+            .collect::<Result<Vec<_>, GpuError>>()?
+            .try_into()
+            .expect("Vec of size FRAMES_IN_FLIGHT has to fit in array");
 
-        Ok(RenderTarget::Texture(TextureRenderTarget {
+        Ok(RenderTarget::Texture(Box::new(TextureRenderTarget {
             width: desc.width,
             height: desc.height,
             format: desc.format,
             id: targets[0].id,
             targets,
-        }))
+            layer_views,
+        })))
     }
 
     fn create_render_pipeline(
         &self,
         desc: &RenderPipelineDesc<Self::ShaderSource>,
     ) -> Result<Self::Pipeline, GpuError> {
+        desc.validate()?;
         let specialization_constants = SpecializationConstants {
             hdr_enabled: if self.swapchain.surface_format.is_hdr {
                 ash::vk::TRUE
@@ -425,12 +569,54 @@ impl GpuBackend for VulkanBackend {
         );
         println!("{:?}", map_entries);
 
+        let (vtx_input_state, attributes) = if let Some(layout) = &desc.vertex_layout {
+            let attributes = layout
+                .attributes
+                .iter()
+                .map(|it| ash::vk::VertexInputAttributeDescription {
+                    binding: 0,
+                    location: it.shader_location,
+                    format: it.format.into(),
+                    offset: it.offset,
+                })
+                .collect::<Vec<_>>();
+
+            (
+                ash::vk::VertexInputBindingDescription {
+                    binding: 0,
+                    stride: layout.stride,
+                    input_rate: ash::vk::VertexInputRate::VERTEX,
+                },
+                attributes,
+            )
+        } else {
+            (
+                ash::vk::VertexInputBindingDescription::default(),
+                Vec::new(),
+            )
+        };
+
+        let bindless_bindings = bindless_layout_bindings(&self.bindless_limits);
+        let layout = PipelineLayoutDesc {
+            sets: &[&bindless_bindings],
+            push_constants: &[graphics_push_constant_range()],
+        };
+        for source in desc.shader_source {
+            validate_shader(
+                source.label,
+                &source.code,
+                source.stage,
+                &layout,
+                desc.vertex_layout.as_ref().map(|_| attributes.as_slice()),
+            )?;
+        }
+
         let shader_modules = desc
             .shader_source
             .iter()
             .map(|source| {
                 println!("Compiling shader: {}", desc.label);
-                let module = create_shader_module(source.code, &self.device, source.label)?;
+                let module = create_shader_module(&source.code, &self.device, source.label)?;
 
                 Ok(ash::vk::PipelineShaderStageCreateInfo {
                     stage: source.stage,
@@ -457,33 +643,6 @@ impl GpuBackend for VulkanBackend {
             ..Default::default()
         };
 
-        let (vtx_input_state, attributes) = if let Some(layout) = &desc.vertex_layout {
-            let attributes = layout
-                .attributes
-                .iter()
-                .map(|it| ash::vk::VertexInputAttributeDescription {
-                    binding: 0,
-                    location: it.shader_location,
-                    format: it.format.into(),
-                    offset: it.offset,
-                })
-                .collect::<Vec<_>>();
-
-            (
-                ash::vk::VertexInputBindingDescription {
-                    binding: 0,
-                    stride: layout.stride,
-                    input_rate: ash::vk::VertexInputRate::VERTEX,
-                },
-                attributes,
-            )
-        } else {
-            (
-                ash::vk::VertexInputBindingDescription::default(),
-                Vec::new(),
-            )
-        };
-
         let pipeline_vtx_input_state = ash::vk::PipelineVertexInputStateCreateInfo {
             vertex_binding_description_count: 1,
             p_vertex_binding_descriptions: &vtx_input_state,
@@ -520,7 +679,7 @@ impl GpuBackend for VulkanBackend {
         };
 
         let multisample_state = ash::vk::PipelineMultisampleStateCreateInfo {
-            rasterization_samples: ash::vk::SampleCountFlags::TYPE_1,
+            rasterization_samples: sample_count_flags(desc.sample_count)?,
             sample_shading_enable: ash::vk::FALSE,
             ..Default::default()
         };
@@ -578,7 +737,15 @@ impl GpuBackend for VulkanBackend {
                 depth_test_enable: ash::vk::TRUE,
                 depth_compare_op: desc.depth_compare.into(),
                 depth_bounds_test_enable: ash::vk::FALSE,
-                stencil_test_enable: ash::vk::FALSE,
+                stencil_test_enable: desc.stencil.is_some() as _,
+                front: desc
+                    .stencil
+                    .map(|s| stencil_op_state(&s, &s.front))
+                    .unwrap_or_default(),
+                back: desc
+                    .stencil
+                    .map(|s| stencil_op_state(&s, &s.back))
+                    .unwrap_or_default(),
                 ..Default::default()
             }
         } else {
@@ -599,6 +766,10 @@ impl GpuBackend for VulkanBackend {
             .depth_format
             .map(Into::into)
             .unwrap_or(ash::vk::Format::UNDEFINED);
+        let stencil_format = match desc.depth_format {
+            Some(format) if format.has_stencil() => depth_format,
+            _ => ash::vk::Format::UNDEFINED,
+        };
 
         let rendering_create_info = ash::vk::PipelineRenderingCreateInfo {
             color_attachment_count: color_formats.len() as _,
@@ -608,6 +779,7 @@ impl GpuBackend for VulkanBackend {
                 color_formats.as_ptr()
             },
             depth_attachment_format: depth_format,
+            stencil_attachment_format: stencil_format,
             ..Default::default()
         };
 
@@ -630,7 +802,7 @@ impl GpuBackend for VulkanBackend {
 
         let pipeline = unsafe {
             self.device.create_graphics_pipelines(
-                ash::vk::PipelineCache::null(),
+                self.pipeline_cache.handle(),
                 &[pipeline_info],
                 None,
             )
@@ -655,6 +827,7 @@ impl GpuBackend for VulkanBackend {
             handle: pipeline[0],
             bind_point: ash::vk::PipelineBindPoint::GRAPHICS,
             layout: self.pipeline_layout,
+            push_constant_size: 0,
             sbt: None,
         })
     }
@@ -684,12 +857,28 @@ impl GpuBackend for VulkanBackend {
             ..Default::default()
         };
 
+        let bindless_bindings = bindless_layout_bindings(&self.bindless_limits);
+        let compute_bindings = compute::compute_layout_bindings(desc.bindings);
+        let push_constants = [compute::compute_push_constant_range(
+            desc.push_constant_size,
+        )];
+        let layout = PipelineLayoutDesc {
+            sets: &[&bindless_bindings, &compute_bindings],
+            push_constants: if desc.push_constant_size > 0 {
+                &push_constants
+            } else {
+                &[]
+            },
+        };
+        let source = &desc.shader_source[0];
+        validate_shader(source.label, &source.code, source.stage, &layout, None)?;
+
         let shader_module = desc
             .shader_source
             .iter()
             .map(|source| {
                 println!("Compiling shader: {}", desc.label);
-                let module = create_shader_module(source.code, &self.device, source.label)?;
+                let module = create_shader_module(&source.code, &self.device, source.label)?;
 
                 Ok(ash::vk::PipelineShaderStageCreateInfo {
                     stage: source.stage,
@@ -702,15 +891,21 @@ impl GpuBackend for VulkanBackend {
             .collect::<Result<Vec<_>, GpuError>>()?[0];
         println!("Compiled shader {} to module", desc.label);
 
+        let compute_layout = self.compute_layouts.borrow_mut().get_or_create(
+            &self.device,
+            self.descriptors.layout,
+            desc.bindings,
+            desc.push_constant_size,
+        )?;
         let pipeline_info = ash::vk::ComputePipelineCreateInfo {
             stage: shader_module,
-            layout: self.compute_pipeline_layout,
+            layout: compute_layout.pipeline_layout,
             ..Default::default()
         };
 
         let pipeline = unsafe {
             self.device.create_compute_pipelines(
-                ash::vk::PipelineCache::null(),
+                self.pipeline_cache.handle(),
                 &[pipeline_info],
                 None,
             )
@@ -735,7 +930,8 @@ impl GpuBackend for VulkanBackend {
             label: desc.label.to_owned(),
             handle: pipeline[0],
             bind_point: ash::vk::PipelineBindPoint::COMPUTE,
-            layout: self.compute_pipeline_layout,
+            layout: compute_layout.pipeline_layout,
+            push_constant_size: desc.push_constant_size,
             sbt: None,
         })
     }
@@ -767,47 +963,11 @@ impl GpuBackend for VulkanBackend {
                     }
                     Ok(())
                 } else {
-                    backend.copy_to_buffer(buffer.memory, data.as_ptr() as *const _, size)?;
+                    backend.copy_to_buffer(&buffer.allocation, data.as_ptr() as *const _, size)?;
                     Ok(())
                 }
             } else {
-                let command_buffer = backend.begin_single_time_commands().unwrap();
-                let (b_staging, m_staging) = VulkanBackend::create_buffer(
-                    &backend.instance,
-                    &backend.device,
-                    backend.phys_device,
-                    size,
-                    ash::vk::BufferUsageFlags::TRANSFER_SRC,
-                    ash::vk::MemoryPropertyFlags::HOST_VISIBLE
-                        | ash::vk::MemoryPropertyFlags::HOST_COHERENT,
-                )
-                .unwrap();
-                backend
-                    .copy_to_buffer(m_staging, data.as_ptr() as *const _, size)
-                    .unwrap();
-                backend.copy_buffer(command_buffer, b_staging, 0, buffer.buffer, 0, size);
-
-                // Only sync the specific copy for this frame
-                if let Some(copies) = &buffer.per_frame_copies {
-                    let copy_idx = backend.frame_idx;
-                    if copy_idx > 0 && copy_idx <= copies.len() {
-                        backend.copy_buffer(
-                            command_buffer,
-                            b_staging,
-                            0,
-                            copies[copy_idx - 1].buffer,
-                            0,
-                            size,
-                        );
-                    }
-                }
-                backend.end_single_time_commands(command_buffer).unwrap();
-
-                unsafe {
-                    backend.device.destroy_buffer(b_staging, None);
-                    backend.device.free_memory(m_staging, None);
-                }
-                Ok(())
+                backend.upload_to_buffer(buffer, &data[..size as usize], false)
             }
         }
         if let Err(e) = update_buffer_safe(&self, buffer, data) {
@@ -828,9 +988,11 @@ impl GpuBackend for VulkanBackend {
         // Pre-barrier: ensure any prior reads finish before the CLEAR write
         let pre_barrier = ash::vk::BufferMemoryBarrier2 {
             src_stage_mask: ash::vk::PipelineStageFlags2::ALL_GRAPHICS
+                | ash::vk::PipelineStageFlags2::COMPUTE_SHADER
                 | ash::vk::PipelineStageFlags2::TRANSFER,
             dst_stage_mask: ash::vk::PipelineStageFlags2::TRANSFER,
             src_access_mask: ash::vk::AccessFlags2::UNIFORM_READ
+                | ash::vk::AccessFlags2::SHADER_STORAGE_READ
                 | ash::vk::AccessFlags2::TRANSFER_WRITE,
             dst_access_mask: ash::vk::AccessFlags2::TRANSFER_WRITE,
             buffer: target,
@@ -847,16 +1009,25 @@ impl GpuBackend for VulkanBackend {
             self.device.cmd_pipeline_barrier2(command_buffer, &pre_info);
         }
         let size = (data.len() as ash::vk::DeviceSize).min(buffer.size);
-        unsafe {
-            self.device
-                .cmd_update_buffer(command_buffer, target, 0, &data[..size as usize]);
+        // vkCmdUpdateBuffer writes at most 64 KiB at a time
+        for (i, chunk) in data[..size as usize]
+            .chunks(CMD_UPDATE_BUFFER_LIMIT)
+            .enumerate()
+        {
+            let offset = (i * CMD_UPDATE_BUFFER_LIMIT) as ash::vk::DeviceSize;
+            unsafe {
+                self.device
+                    .cmd_update_buffer(command_buffer, target, offset, chunk);
+            }
         }
         // Post-barrier: ensure the CLEAR write completes before subsequent reads
         let barrier = ash::vk::BufferMemoryBarrier2 {
             src_stage_mask: ash::vk::PipelineStageFlags2::TRANSFER,
-            dst_stage_mask: ash::vk::PipelineStageFlags2::ALL_GRAPHICS,
+            dst_stage_mask: ash::vk::PipelineStageFlags2::ALL_GRAPHICS
+                | ash::vk::PipelineStageFlags2::COMPUTE_SHADER,
             src_access_mask: ash::vk::AccessFlags2::TRANSFER_WRITE,
-            dst_access_mask: ash::vk::AccessFlags2::UNIFORM_READ,
+            dst_access_mask: ash::vk::AccessFlags2::UNIFORM_READ
+                | ash::vk::AccessFlags2::SHADER_STORAGE_READ,
             buffer: target,
             offset: 0,
             size: ash::vk::WHOLE_SIZE,
@@ -919,26 +1090,31 @@ impl GpuBackend for VulkanBackend {
         if ENABLE_MARKER {
             println!("MARKER ==== AQUIRE NEXT SWAPCHAIN IMAGE");
         }
-        let (swapchain_idx, _optimal) = match unsafe {
-            self.swapchain.fn_ptr.acquire_next_image(
-                self.swapchain.swapchain,
-                u64::MAX,
-                present_semaphore,
-                ash::vk::Fence::null(),
-            )
-        } {
-            Ok(res) => res,
-            Err(e) => {
-                if e == ash::vk::Result::ERROR_OUT_OF_DATE_KHR {
-                    eprintln!(
-                        "[DEBUG] Recreate swapchain: acquire_next_image returned ERROR_OUT_OF_DATE_KHR"
-                    );
-                    return self.recreate_swapchain();
+        let swapchain_idx = if self.swapchain.offscreen {
+            // offscreen backbuffers are per frame in flight, nothing to acquire
+            frame_idx as u32
+        } else {
+            match unsafe {
+                self.swapchain.fn_ptr.acquire_next_image(
+                    self.swapchain.swapchain,
+                    u64::MAX,
+                    present_semaphore,
+                    ash::vk::Fence::null(),
+                )
+            } {
+                Ok((idx, _optimal)) => idx,
+                Err(e) => {
+                    if e == ash::vk::Result::ERROR_OUT_OF_DATE_KHR {
+                        eprintln!(
+                            "[DEBUG] Recreate swapchain: acquire_next_image returned ERROR_OUT_OF_DATE_KHR"
+                        );
+                        return self.recreate_swapchain();
+                    }
+                    return Err(GpuError::new(
+                        format!("Failed to get new swapchain image: {e:?}"),
+                        GpuErrorKind::ResourceUpdate,
+                    ));
                 }
-                return Err(GpuError::new(
-                    format!("Failed to get new swapchain image: {e:?}"),
-                    GpuErrorKind::ResourceUpdate,
-                ));
             }
         };
 
@@ -961,6 +1137,12 @@ impl GpuBackend for VulkanBackend {
             GpuError::new(format!("Reset Fences failed: {e:?}"), GpuErrorKind::Other)
         })?;
 
+        // The fence wait above retired the frame last submitted from this slot
+        // and every frame before it.
+        self.release_destroyed(self.frame_serials[frame_idx]);
+        self.frame_serials[frame_idx] = self.vulkan_handle_tracker.next_frame_serial();
+        self.uploader.get_mut().retire()?;
+
         if ENABLE_MARKER {
             println!("MARKER ==== RESET COMMAND BUFFER");
         }
@@ -1034,6 +1216,10 @@ impl GpuBackend for VulkanBackend {
             .current_layout
             .set(ash::vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL);
 
+        if let Some(profiler) = self.profiler.get_mut() {
+            profiler.begin_frame(command_buffer, frame_idx);
+        }
+
         if ENABLE_MARKER {
             println!("MARKER ==== CMD BIND DESCRIPTOR SETS");
         }
@@ -1056,6 +1242,7 @@ impl GpuBackend for VulkanBackend {
             current_frame.present_sem = present_semaphore;
             current_frame.render_sem = render_semaphore;
             current_frame.pass_targets.clear();
+            current_frame.prev_model_count = 0;
         } else {
             self.current_frame = Some(CurrentFrame {
                 idx: frame_idx,
@@ -1066,6 +1253,7 @@ impl GpuBackend for VulkanBackend {
                 render_sem: render_semaphore,
                 pass_targets: Vec::new(),
                 pending_push: PushConstants::default(),
+                prev_model_count: 0,
             });
         }
 
@@ -1088,19 +1276,27 @@ impl GpuBackend for VulkanBackend {
             ));
         };
 
+        // offscreen backbuffers are left ready for readback instead of presentation
+        let final_layout = if self.swapchain.offscreen {
+            ash::vk::ImageLayout::TRANSFER_SRC_OPTIMAL
+        } else {
+            ash::vk::ImageLayout::PRESENT_SRC_KHR
+        };
         let swapchain_tex = &self.swapchain.swapchain_images[render_idx as usize];
         self.transition_image_layout(
             command_buffer,
             swapchain_tex.image,
             swapchain_tex.current_layout.get(),
-            ash::vk::ImageLayout::PRESENT_SRC_KHR,
+            final_layout,
             ash::vk::ImageAspectFlags::COLOR,
             1,
             swapchain_tex.mip_levels,
         )?;
-        swapchain_tex
-            .current_layout
-            .set(ash::vk::ImageLayout::PRESENT_SRC_KHR);
+        swapchain_tex.current_layout.set(final_layout);
+
+        if let Some(profiler) = self.profiler.get_mut() {
+            profiler.end_frame(command_buffer);
+        }
 
         if ENABLE_MARKER {
             println!("MARKER ==== END COMMAND BUFFER");
@@ -1112,14 +1308,37 @@ impl GpuBackend for VulkanBackend {
             )
         })?;
 
-        let wait_flags = ash::vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT;
+        // without a swapchain there is no acquire to wait on and no present to signal
+        let semaphore_count = if self.swapchain.offscreen { 0 } else { 1 };
+        let mut wait_semaphores = Vec::with_capacity(2);
+        let mut wait_values = Vec::with_capacity(2);
+        let mut wait_flags = Vec::with_capacity(2);
+        if !self.swapchain.offscreen {
+            wait_semaphores.push(present_semaphore);
+            wait_values.push(0);
+            wait_flags.push(ash::vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT);
+        }
+        // uploads recorded up to now, including during this frame
+        let uploader = self.uploader.get_mut();
+        uploader.flush()?;
+        if let Some((timeline, value)) = uploader.wait_target() {
+            wait_semaphores.push(timeline);
+            wait_values.push(value);
+            wait_flags.push(ash::vk::PipelineStageFlags::ALL_COMMANDS);
+        }
+        let mut timeline_info = ash::vk::TimelineSemaphoreSubmitInfo {
+            wait_semaphore_value_count: wait_values.len() as u32,
+            p_wait_semaphore_values: wait_values.as_ptr(),
+            ..Default::default()
+        };
         let submit_info = ash::vk::SubmitInfo {
-            wait_semaphore_count: 1,
-            p_wait_semaphores: &present_semaphore,
-            p_wait_dst_stage_mask: &wait_flags,
+            p_next: &mut timeline_info as *mut _ as *const std::ffi::c_void,
+            wait_semaphore_count: wait_semaphores.len() as u32,
+            p_wait_semaphores: wait_semaphores.as_ptr(),
+            p_wait_dst_stage_mask: wait_flags.as_ptr(),
             command_buffer_count: 1,
             p_command_buffers: &command_buffer,
-            signal_semaphore_count: 1,
+            signal_semaphore_count: semaphore_count,
             p_signal_semaphores: &render_semaphore,
             ..Default::default()
         };
@@ -1172,6 +1391,10 @@ impl GpuBackend for VulkanBackend {
         // never reset/re-begun.
         self.current_frame = None;
 
+        if self.swapchain.offscreen {
+            return Ok(());
+        }
+
         match unsafe {
             self.swapchain
                 .fn_ptr
@@ -1215,8 +1438,12 @@ impl GpuBackend for VulkanBackend {
             return;
         };
 
-        desc.color_targets.iter().for_each(|attachment| {
-            let target = attachment.target.get_target(idx);
+        // resolve targets are written as attachments at the end of the pass
+        let color_targets = desc.color_targets.iter().flat_map(|attachment| {
+            std::iter::once(attachment.target).chain(attachment.resolve_target)
+        });
+        color_targets.for_each(|render_target| {
+            let target = render_target.get_target(idx);
             let old_layout = target.current_layout.get();
             self.transition_image_layout(
                 command_buffer,
@@ -1233,21 +1460,25 @@ impl GpuBackend for VulkanBackend {
                 .set(ash::vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
         });
         if let Some(depth_attachment) = &desc.depth_target {
-            let depth_target = depth_attachment.target.get_target(idx);
-            let old_layout = depth_target.current_layout.get();
-            self.transition_image_layout(
-                command_buffer,
-                depth_target.image,
-                old_layout,
-                ash::vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
-                ash::vk::ImageAspectFlags::DEPTH,
-                1,
-                depth_target.mip_levels,
-            )
-            .unwrap();
-            depth_target
-                .current_layout
-                .set(ash::vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL);
+            let depth_targets =
+                std::iter::once(depth_attachment.target).chain(depth_attachment.resolve_target);
+            for render_target in depth_targets {
+                let depth_target = render_target.get_target(idx);
+                let old_layout = depth_target.current_layout.get();
+                self.transition_image_layout(
+                    command_buffer,
+                    depth_target.image,
+                    old_layout,
+                    ash::vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+                    depth_target.aspect,
+                    depth_target.array_layers,
+                    depth_target.mip_levels,
+                )
+                .unwrap();
+                depth_target
+                    .current_layout
+                    .set(ash::vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL);
+            }
         }
 
         let color_attachments = desc
@@ -1265,9 +1496,19 @@ impl GpuBackend for VulkanBackend {
                 let store_op = ash::vk::AttachmentStoreOp::STORE;
 
                 let image_view = target.image_view;
+                let (resolve_mode, resolve_image_view) = match attachment.resolve_target {
+                    Some(resolve) => (
+                        ash::vk::ResolveModeFlags::AVERAGE,
+                        resolve.get_target(idx).image_view,
+                    ),
+                    None => (ash::vk::ResolveModeFlags::NONE, ash::vk::ImageView::null()),
+                };
                 ash::vk::RenderingAttachmentInfo {
                     image_view,
                     image_layout: ash::vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                    resolve_mode,
+                    resolve_image_view,
+                    resolve_image_layout: ash::vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
                     load_op,
                     store_op,
                     clear_value,
@@ -1276,61 +1517,93 @@ impl GpuBackend for VulkanBackend {
             })
             .collect::<Vec<_>>();
 
-        let (depth_attachment, depth_target_count) = if let Some(attachment) = &desc.depth_target {
-            let target = attachment.target.get_target(idx);
+        let (depth_attachment, stencil_attachment, depth_target_count, stencil_target_count) =
+            if let Some(attachment) = &desc.depth_target {
+                let target = attachment.target.get_target(idx);
+                let has_stencil = target.aspect.contains(ash::vk::ImageAspectFlags::STENCIL);
+                let image_layout = if has_stencil {
+                    ash::vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+                } else {
+                    ash::vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL
+                };
 
-            let clear_value = ash::vk::ClearValue {
-                depth_stencil: ash::vk::ClearDepthStencilValue {
-                    depth: attachment.clear_depth,
-                    stencil: 1,
-                },
-            };
-            let load_op: ash::vk::AttachmentLoadOp = attachment.load_op.into();
-            let store_op = ash::vk::AttachmentStoreOp::STORE;
+                let clear_value = ash::vk::ClearValue {
+                    depth_stencil: ash::vk::ClearDepthStencilValue {
+                        depth: attachment.clear_depth,
+                        stencil: attachment.clear_stencil as u32,
+                    },
+                };
+                let store_op = ash::vk::AttachmentStoreOp::STORE;
 
-            let image_view = target.image_view;
-            (
-                ash::vk::RenderingAttachmentInfo {
+                let image_view = attachment.target.attachment_view(idx, attachment.layer);
+                // averaging depth is not supported everywhere, sample 0 always is
+                let (resolve_mode, resolve_image_view) = match attachment.resolve_target {
+                    Some(resolve) => (
+                        ash::vk::ResolveModeFlags::SAMPLE_ZERO,
+                        resolve.get_target(idx).image_view,
+                    ),
+                    None => (ash::vk::ResolveModeFlags::NONE, ash::vk::ImageView::null()),
+                };
+                let depth_attachment = ash::vk::RenderingAttachmentInfo {
                     image_view,
-                    image_layout: ash::vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
-                    load_op,
+                    image_layout,
+                    resolve_mode,
+                    resolve_image_view,
+                    resolve_image_layout: image_layout,
+                    load_op: attachment.load_op.into(),
                     store_op,
                     clear_value,
                     ..Default::default()
-                },
-                1,
-            )
-        } else {
-            (ash::vk::RenderingAttachmentInfo::default(), 0)
-        };
+                };
+                // the stencil aspect is the same view with its own load op
+                let stencil_attachment = ash::vk::RenderingAttachmentInfo {
+                    load_op: attachment.stencil_load_op.into(),
+                    ..depth_attachment
+                };
+                (depth_attachment, stencil_attachment, 1, has_stencil as u32)
+            } else {
+                (
+                    ash::vk::RenderingAttachmentInfo::default(),
+                    ash::vk::RenderingAttachmentInfo::default(),
+                    0,
+                    0,
+                )
+            };
 
         let depth_target_ptr = if depth_target_count == 1 {
             &depth_attachment
         } else {
             std::ptr::null()
         };
+        let stencil_target_ptr = if stencil_target_count == 1 {
+            &stencil_attachment
+        } else {
+            std::ptr::null()
+        };
 
         let Some(CurrentFrame { pass_targets, .. }) = &mut self.current_frame else {
             return;
         };
         pass_targets.clear();
         for attachment in &desc.color_targets {
-            let target = attachment.target.get_target(idx);
+            for render_target in std::iter::once(attachment.target).chain(attachment.resolve_target)
+            {
+                let target = render_target.get_target(idx);
 
-            pass_targets.push((
-                target.image,
-                ash::vk::ImageAspectFlags::COLOR,
-                target.current_layout.clone(),
-            ));
+                pass_targets.push((
+                    target.image,
+                    ash::vk::ImageAspectFlags::COLOR,
+                    target.current_layout.clone(),
+                ));
+            }
         }
         if let Some(attachment) = &desc.depth_target {
-            let target = attachment.target.get_target(idx);
+            for render_target in std::iter::once(attachment.target).chain(attachment.resolve_target)
+            {
+                let target = render_target.get_target(idx);
 
-            pass_targets.push((
-                target.image,
-                ash::vk::ImageAspectFlags::DEPTH,
-                target.current_layout.clone(),
-            ));
+                pass_targets.push((target.image, target.aspect, target.current_layout.clone()));
+            }
         }
 
         let area = if let Some(att) = desc.color_targets.first() {
@@ -1369,6 +1642,7 @@ impl GpuBackend for VulkanBackend {
             color_attachment_count: color_attachments.len() as u32,
             p_color_attachments: color_attachments.as_ptr(),
             p_depth_attachment: depth_target_ptr,
+            p_stencil_attachment: stencil_target_ptr,
             ..Default::default()
         };
 
@@ -1454,6 +1728,9 @@ impl GpuBackend for VulkanBackend {
             1 => pending_push.tex1 = texture.descriptor_index,
             2 => pending_push.tex2 = texture.descriptor_index,
             3 => pending_push.tex3 = texture.descriptor_index,
+            4 => pending_push.tex4 = texture.descriptor_index,
+            5 => pending_push.tex5 = texture.descriptor_index,
+            6 => pending_push.tex6 = texture.descriptor_index,
             _ => {}
         }
     }
@@ -1482,6 +1759,8 @@ impl GpuBackend for VulkanBackend {
             2 => pending_push.tex2 = target.descriptor_index,
             3 => pending_push.tex3 = target.descriptor_index,
             4 => pending_push.tex4 = target.descriptor_index,
+            5 => pending_push.tex5 = target.descriptor_index,
+            6 => pending_push.tex6 = target.descriptor_index,
             _ => {}
         }
 
@@ -1497,7 +1776,7 @@ impl GpuBackend for VulkanBackend {
             target.current_layout.get(),
             new_layout,
             target.aspect,
-            1,
+            target.array_layers,
             target.mip_levels,
         ) {
             eprintln!("Unable to transition render target to shader read: {e:?}");
@@ -1636,10 +1915,12 @@ impl GpuBackend for VulkanBackend {
                 0,
             );
         }
-        // Only reset the model matrix; keep texture indices as they are often
+        // Only reset the model matrices; keep texture indices as they are often
         // pass-wide or will be overwritten by the next material bind.
         pending_push.model = PushConstants::default().model;
+        pending_push.prev_model_index = PushConstants::default().prev_model_index;
         pending_push.has_parallax = 0;
+        pending_push.layered = 0;
     }
 
     fn draw_indexed_indirect(
@@ -1675,150 +1956,146 @@ impl GpuBackend for VulkanBackend {
                 0, // When stride is 0, Vulkan automatically assumes the commands are tightly packed
             );
         }
-        // Only reset the model matrix; keep texture indices as they are often
+        // Only reset the model matrices; keep texture indices as they are often
         // pass-wide or will be overwritten by the next material bind.
         pending_push.model = PushConstants::default().model;
+        pending_push.prev_model_index = PushConstants::default().prev_model_index;
         pending_push.has_parallax = 0;
+        pending_push.layered = 0;
         pending_push.is_instanced = 0;
     }
 
-    fn execute_compute_one_shot(
+    fn dispatch_compute_one_shot(
         &self,
-        pipeline: &Self::Pipeline,
-        buffers: &[(u32, &Self::Buffer)],
-        textures: &[(u32, &Self::Texture)],
+        dispatch: &ComputeDispatch<Self>,
         work_groups: (u32, u32, u32),
-        max_instances: u32,
-        asset_offset: u32,
-        max_height: f32,
-        spawn_height_min: f32,
-        spawn_height_max: f32,
-        slope_max: f32,
-        scale_min: f32,
-        scale_max: f32,
-        tilt_factor: f32,
-        terrain_segments_f: f32,
     ) -> Result<(), GpuError> {
         let cmdbuff = self.begin_single_time_commands()?;
-        for (binding, buffer) in buffers {
-            self.bind_buffer_to_descriptor(*binding, buffer);
-        }
-        for (binding, texture) in textures {
-            if texture.current_layout.get() != ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL {
-                self.transition_image_layout(
-                    cmdbuff,
-                    texture.image,
-                    texture.current_layout.get(),
-                    ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-                    ash::vk::ImageAspectFlags::COLOR,
-                    1,
-                    texture.mip_levels,
-                )?;
-                texture
-                    .current_layout
-                    .set(ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
-            }
-
-            let info0 = ash::vk::DescriptorImageInfo {
-                sampler: texture.sampler,
-                image_view: texture.image_view,
-                image_layout: texture.current_layout.get(),
-            };
-            let write0 = ash::vk::WriteDescriptorSet {
-                dst_set: self.descriptors.sets[0],
-                dst_binding: 12,
-                dst_array_element: *binding,
-                descriptor_type: ash::vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-                descriptor_count: 1,
-                p_image_info: &info0,
-                ..Default::default()
-            };
-
-            unsafe { self.device.update_descriptor_sets(&[write0], &[]) };
-        }
+        self.record_compute_bindings(cmdbuff, 0, dispatch)?;
         unsafe {
-            self.device.cmd_bind_pipeline(
-                cmdbuff,
-                ash::vk::PipelineBindPoint::COMPUTE,
-                pipeline.handle,
-            );
+            self.device
+                .cmd_dispatch(cmdbuff, work_groups.0, work_groups.1, work_groups.2);
         }
 
+        // make the results visible to everything submitted afterwards
+        let barrier = ash::vk::MemoryBarrier2 {
+            src_stage_mask: ash::vk::PipelineStageFlags2::COMPUTE_SHADER,
+            src_access_mask: ash::vk::AccessFlags2::SHADER_WRITE,
+            dst_stage_mask: ash::vk::PipelineStageFlags2::ALL_COMMANDS,
+            dst_access_mask: ash::vk::AccessFlags2::MEMORY_READ
+                | ash::vk::AccessFlags2::MEMORY_WRITE,
+            ..Default::default()
+        };
+        let dependency_info = ash::vk::DependencyInfo {
+            memory_barrier_count: 1,
+            p_memory_barriers: &barrier,
+            ..Default::default()
+        };
         unsafe {
-            self.device.cmd_bind_descriptor_sets(
-                cmdbuff,
-                ash::vk::PipelineBindPoint::COMPUTE,
-                pipeline.layout,
-                0,
-                &[self.descriptors.sets[0]],
-                &[],
-            );
+            self.device.cmd_pipeline_barrier2(cmdbuff, &dependency_info);
         }
 
-        unsafe {
-            let push = ComputePushConstants {
-                max_instances,
-                asset_offset,
-                max_height,
-                spawn_height_min,
-                spawn_height_max,
-                slope_max,
-                scale_min,
-                scale_max,
-                tilt_factor,
-                terrain_segments_f,
-            };
-            self.device.cmd_push_constants(
-                cmdbuff,
-                pipeline.layout,
-                ash::vk::ShaderStageFlags::COMPUTE,
-                0,
-                as_bytes(std::slice::from_ref(&push)),
-            );
-        }
+        self.end_single_time_commands(cmdbuff)
+    }
 
+    fn cmd_dispatch(&mut self, dispatch: &ComputeDispatch<Self>, work_groups: (u32, u32, u32)) {
+        let Some(CurrentFrame {
+            idx,
+            command_buffer,
+            ..
+        }) = self.current_frame
+        else {
+            eprintln!("[cmd_dispatch] No active frame");
+            return;
+        };
+        if let Err(e) = self.record_compute_bindings(command_buffer, idx, dispatch) {
+            eprintln!("[cmd_dispatch] {e}");
+            return;
+        }
         unsafe {
             self.device
-                .cmd_dispatch(cmdbuff, work_groups.0, work_groups.1, work_groups.2);
+                .cmd_dispatch(command_buffer, work_groups.0, work_groups.1, work_groups.2);
         }
+    }
 
-        let barriers = buffers
-            .iter()
-            .map(|(_, b)| ash::vk::BufferMemoryBarrier2 {
-                src_stage_mask: ash::vk::PipelineStageFlags2::COMPUTE_SHADER,
-                dst_stage_mask: ash::vk::PipelineStageFlags2::DRAW_INDIRECT
-                    | ash::vk::PipelineStageFlags2::VERTEX_SHADER,
-                src_access_mask: ash::vk::AccessFlags2::SHADER_WRITE,
-                dst_access_mask: ash::vk::AccessFlags2::INDIRECT_COMMAND_READ
-                    | ash::vk::AccessFlags2::SHADER_STORAGE_READ,
-                buffer: b.buffer,
-                offset: 0,
-                size: b.size,
-                ..Default::default()
-            })
-            .collect::<Vec<_>>();
-
-        let dependency_info = ash::vk::DependencyInfo {
-            buffer_memory_barrier_count: barriers.len() as u32,
-            p_buffer_memory_barriers: barriers.as_ptr(),
-            ..Default::default()
+    fn cmd_dispatch_indirect(
+        &mut self,
+        dispatch: &ComputeDispatch<Self>,
+        args_buffer: &Self::Buffer,
+        offset: u64,
+    ) {
+        let Some(CurrentFrame {
+            idx,
+            command_buffer,
+            ..
+        }) = self.current_frame
+        else {
+            eprintln!("[cmd_dispatch_indirect] No active frame");
+            return;
         };
-
+        if let Err(e) = self.record_compute_bindings(command_buffer, idx, dispatch) {
+            eprintln!("[cmd_dispatch_indirect] {e}");
+            return;
+        }
         unsafe {
-            self.device.cmd_pipeline_barrier2(cmdbuff, &dependency_info);
+            self.device.cmd_dispatch_indirect(
+                command_buffer,
+                args_buffer.frame_buffer(idx),
+                offset,
+            );
         }
+    }
 
-        self.end_single_time_commands(cmdbuff)
+    fn cmd_compute_barrier(&mut self, barrier: ComputeBarrier) {
+        let Some(CurrentFrame { command_buffer, .. }) = self.current_frame else {
+            return;
+        };
+        self.record_compute_barrier(command_buffer, barrier);
     }
 
-    fn set_model_matrix(&mut self, model: &glm::Mat4) {
-        let Some(CurrentFrame { pending_push, .. }) = &mut self.current_frame else {
+    fn cmd_target_barriers(&mut self, barriers: &[TargetBarrier<Self>]) {
+        let Some(CurrentFrame {
+            idx,
+            command_buffer,
+            ..
+        }) = self.current_frame
+        else {
+            return;
+        };
+        self.record_target_barriers(command_buffer, idx, barriers);
+    }
+
+    fn set_model_matrix(&mut self, model: &glm::Mat4, prev_model: &glm::Mat4) {
+        let Some(CurrentFrame {
+            idx,
+            pending_push,
+            prev_model_count,
+            ..
+        }) = &mut self.current_frame
+        else {
             return;
         };
         let data = as_bytes(std::slice::from_ref(model));
         pending_push.model.copy_from_slice(unsafe {
             std::slice::from_raw_parts(data.as_ptr() as *const f32, 16)
         });
+
+        // Moving draws read their previous model matrix from the frame's copy of
+        // the buffer, which no other frame in flight uses
+        pending_push.prev_model_index = NO_PREV_MODEL;
+        let Some(prev_models) = &self.prev_models else {
+            return;
+        };
+        if prev_model == model || *prev_model_count == MAX_PREV_MODELS {
+            return;
+        }
+        unsafe {
+            (prev_models.frame_mapped(*idx) as *mut glm::Mat4)
+                .add(*prev_model_count)
+                .write_unaligned(*prev_model);
+        }
+        pending_push.prev_model_index = *prev_model_count as u32;
+        *prev_model_count += 1;
     }
 
     fn set_material_properties(&mut self, props: MaterialProperties) {
@@ -1829,7 +2106,12 @@ impl GpuBackend for VulkanBackend {
             ash::vk::TRUE
         } else {
             ash::vk::FALSE
-        }
+        };
+        pending_push.layered = if props.layered {
+            ash::vk::TRUE
+        } else {
+            ash::vk::FALSE
+        };
     }
 
     fn backbuffer(&self) -> Self::RenderTarget {
@@ -1839,7 +2121,7 @@ impl GpuBackend for VulkanBackend {
             &self.swapchain.swapchain_images[0]
         };
 
-        RenderTarget::Swapchain(image.clone())
+        RenderTarget::Swapchain(Box::new(image.clone()))
     }
 
     fn main_depth_target(&self) -> Self::RenderTarget {
@@ -1848,7 +2130,7 @@ impl GpuBackend for VulkanBackend {
         } else {
             &self.depth_targets[0]
         };
-        RenderTarget::Swapchain(image.clone())
+        RenderTarget::Swapchain(Box::new(image.clone()))
     }
 
     fn default_viewport(&self) -> ViewportDesc {
@@ -1867,19 +2149,169 @@ impl GpuBackend for VulkanBackend {
         (extent.width, extent.height)
     }
 
+    fn max_sample_count(&self) -> u32 {
+        let limits = unsafe {
+            self.instance
+                .get_physical_device_properties(self.phys_device)
+                .limits
+        };
+        util::max_sample_count(
+            limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts,
+        )
+    }
+
+    fn supports_texture_format(&self, format: TextureFormat) -> bool {
+        let features = unsafe {
+            self.instance
+                .get_physical_device_format_properties(self.phys_device, format.into())
+                .optimal_tiling_features
+        };
+        features.contains(
+            ash::vk::FormatFeatureFlags::SAMPLED_IMAGE
+                | ash::vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR
+                | ash::vk::FormatFeatureFlags::TRANSFER_DST,
+        )
+    }
+
     // recreate swapchain reads new width & height from window
-    fn resize(&mut self, _width: u32, _height: u32) {
+    // (offscreen backbuffers have no window and take the requested size)
+    fn resize(&mut self, width: u32, height: u32) {
+        if self.swapchain.offscreen {
+            if width == 0 || height == 0 {
+                return;
+            }
+            self.swapchain.swapchain_extent = ash::vk::Extent2D { width, height };
+        }
         if let Err(e) = self.recreate_swapchain() {
             println!("Failed to recreate swapchain on resize: {e:?}")
         }
     }
 
     fn wait_idle(&self) -> Result<(), GpuError> {
+        self.uploader.borrow_mut().wait_all()?;
         unsafe { self.device.device_wait_idle() }.map_err(|e| {
             GpuError::new(
                 format!("Failed to wait for device idle: {e:?}"),
                 GpuErrorKind::Other,
             )
+        })?;
+        // the frame being recorded has not been submitted yet
+        let serial = self.vulkan_handle_tracker.frame_serial();
+        let completed = match self.current_frame {
+            Some(_) => serial.saturating_sub(1),
+            None => serial,
+        };
+        self.release_destroyed(completed);
+        Ok(())
+    }
+
+    fn read_render_target(
+        &mut self,
+        target: &Self::RenderTarget,
+    ) -> Result<TextureReadback, GpuError> {
+        // per-frame targets: read the copy written by the frame in flight, or the last submitted one
+        let idx = match self.current_frame {
+            Some(CurrentFrame { idx, .. }) => idx,
+            None => (self.frame_idx + FRAMES_IN_FLIGHT as usize - 1) % FRAMES_IN_FLIGHT as usize,
+        };
+        let tex = target.get_target(idx);
+        if tex.format.is_depth() {
+            return Err(GpuError::new(
+                format!("Readback of depth target {} is not supported", tex.id),
+                GpuErrorKind::ResourceUpdate,
+            ));
+        }
+
+        self.wait_idle()?;
+
+        let size = tex.width as usize * tex.height as usize * tex.format.bytes_per_pixel();
+        let (staging, staging_allocation) = VulkanBackend::create_buffer(
+            self.vulkan_handle_tracker.allocator(),
+            &self.device,
+            size as ash::vk::DeviceSize,
+            ash::vk::BufferUsageFlags::TRANSFER_DST,
+            ash::vk::MemoryPropertyFlags::HOST_VISIBLE
+                | ash::vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+
+        let read_back = || -> Result<Vec<u8>, GpuError> {
+            let old_layout = tex.current_layout.get();
+            // nothing meaningful to restore for an image that was never written
+            let restore_layout = if old_layout == ash::vk::ImageLayout::UNDEFINED {
+                ash::vk::ImageLayout::TRANSFER_SRC_OPTIMAL
+            } else {
+                old_layout
+            };
+
+            let command_buffer = self.begin_single_time_commands()?;
+            self.transition_image_layout(
+                command_buffer,
+                tex.image,
+                old_layout,
+                ash::vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                tex.aspect,
+                1,
+                tex.mip_levels,
+            )?;
+            let region = ash::vk::BufferImageCopy {
+                buffer_offset: 0,
+                buffer_row_length: 0,
+                buffer_image_height: 0,
+                image_subresource: ash::vk::ImageSubresourceLayers {
+                    aspect_mask: tex.aspect,
+                    mip_level: 0,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                },
+                image_offset: ash::vk::Offset3D { x: 0, y: 0, z: 0 },
+                image_extent: ash::vk::Extent3D {
+                    width: tex.width,
+                    height: tex.height,
+                    depth: 1,
+                },
+            };
+            unsafe {
+                self.device.cmd_copy_image_to_buffer(
+                    command_buffer,
+                    tex.image,
+                    ash::vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    staging,
+                    &[region],
+                );
+            }
+            self.transition_image_layout(
+                command_buffer,
+                tex.image,
+                ash::vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                restore_layout,
+                tex.aspect,
+                1,
+                tex.mip_levels,
+            )?;
+            self.end_single_time_commands(command_buffer)?;
+            tex.current_layout.set(restore_layout);
+
+            let mut data = vec![0u8; size];
+            unsafe {
+                data.as_mut_ptr()
+                    .copy_from_nonoverlapping(staging_allocation.mapped as *const u8, size);
+            }
+            Ok(data)
+        };
+        let result = read_back();
+
+        unsafe {
+            self.device.destroy_buffer(staging, None);
+        }
+        self.vulkan_handle_tracker
+            .allocator()
+            .free(staging_allocation);
+
+        Ok(TextureReadback {
+            width: tex.width,
+            height: tex.height,
+            format: tex.format,
+            data: result?,
         })
     }
 
@@ -1887,6 +2319,9 @@ impl GpuBackend for VulkanBackend {
         let Some(CurrentFrame { command_buffer, .. }) = self.current_frame else {
             return;
         };
+        if let Some(profiler) = self.profiler.borrow_mut().as_mut() {
+            profiler.begin_region(command_buffer, name);
+        }
         let (Some(debug_utils_ext), true) = (
             &self.device.debug_utils_ext,
             self.instance.validation_enabled,
@@ -1919,6 +2354,9 @@ impl GpuBackend for VulkanBackend {
         let Some(CurrentFrame { command_buffer, .. }) = self.current_frame else {
             return;
         };
+        if let Some(profiler) = self.profiler.borrow_mut().as_mut() {
+            profiler.end_region(command_buffer);
+        }
         let (Some(debug_utils_ext), true) = (
             &self.device.debug_utils_ext,
             self.instance.validation_enabled,
@@ -1946,6 +2384,14 @@ impl GpuBackend for VulkanBackend {
         let _ = unsafe { CString::from_raw(c_str) }; // ensure its cleaned up again
     }
 
+    fn gpu_timings(&self) -> Option<GpuFrameTimings> {
+        self.profiler.borrow().as_ref()?.latest().cloned()
+    }
+
+    fn memory_stats(&self) -> Vec<GpuMemoryHeapStats> {
+        self.vulkan_handle_tracker.allocator().stats()
+    }
+
     fn render_egui(
         &mut self,
         textures_delta: &egui::TexturesDelta,
@@ -1969,9 +2415,8 @@ impl GpuBackend for VulkanBackend {
                 &self.instance,
                 &self.device,
                 self.phys_device,
-                self.queue,
-                self.device.main_queue_index,
                 self.swapchain.surface_format.format.format,
+                self.pipeline_cache.handle(),
                 self.vulkan_handle_tracker.clone(),
             ) {
                 Ok(r) => {
@@ -1987,8 +2432,9 @@ impl GpuBackend for VulkanBackend {
         // Texture updates
         {
             let r = self.egui_renderer.as_mut().unwrap();
+            let uploader = self.uploader.get_mut();
             for (id, delta) in &textures_delta.set {
-                r.create_or_update_texture(*id, delta);
+                r.create_or_update_texture(uploader, *id, delta);
             }
             for id in &textures_delta.free {
                 r.free_texture(id);
@@ -2219,50 +2665,15 @@ impl GpuBackend for VulkanBackend {
             .unwrap_or(0);
         let scratch_size = (max_scratch_raw + align - 1) & !(align - 1);
 
-        let mut scratch_alloc_flags = ash::vk::MemoryAllocateFlagsInfo {
-            flags: ash::vk::MemoryAllocateFlags::DEVICE_ADDRESS,
-            ..Default::default()
-        };
-        let (scratch_buf, scratch_mem) = {
-            let create_info = ash::vk::BufferCreateInfo {
-                size: scratch_size,
-                usage: ash::vk::BufferUsageFlags::STORAGE_BUFFER
-                    | ash::vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
-                sharing_mode: ash::vk::SharingMode::EXCLUSIVE,
-                ..Default::default()
-            };
-            let buf = unsafe { self.device.create_buffer(&create_info, None) }.map_err(|e| {
-                GpuError::new(
-                    format!("Failed to create AS scratch buffer: {e:?}"),
-                    GpuErrorKind::ResourceCreation,
-                )
-            })?;
-            let mem_reqs = unsafe { self.device.get_buffer_memory_requirements(buf) };
-            let alloc_info = ash::vk::MemoryAllocateInfo {
-                allocation_size: mem_reqs.size,
-                memory_type_index: VulkanBackend::find_memory_type(
-                    &self.instance,
-                    self.phys_device,
-                    mem_reqs.memory_type_bits,
-                    ash::vk::MemoryPropertyFlags::DEVICE_LOCAL,
-                )?,
-                p_next: &mut scratch_alloc_flags as *mut _ as *mut _,
-                ..Default::default()
-            };
-            let mem = unsafe { self.device.allocate_memory(&alloc_info, None) }.map_err(|e| {
-                GpuError::new(
-                    format!("Failed to allocate AS scratch memory: {e:?}"),
-                    GpuErrorKind::ResourceCreation,
-                )
-            })?;
-            unsafe { self.device.bind_buffer_memory(buf, mem, 0) }.map_err(|e| {
-                GpuError::new(
-                    format!("Failed to bind AS scratch memory: {e:?}"),
-                    GpuErrorKind::ResourceCreation,
-                )
-            })?;
-            (buf, mem)
-        };
+        let (scratch_buf, scratch_allocation) = VulkanBackend::create_buffer_aligned(
+            self.vulkan_handle_tracker.allocator(),
+            &self.device,
+            scratch_size,
+            ash::vk::BufferUsageFlags::STORAGE_BUFFER
+                | ash::vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            ash::vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            align,
+        )?;
 
         let scratch_address = unsafe {
             self.device
@@ -2276,7 +2687,7 @@ impl GpuBackend for VulkanBackend {
         struct BuiltItem {
             accel: ash::vk::AccelerationStructureKHR,
             as_buf: ash::vk::Buffer,
-            as_mem: ash::vk::DeviceMemory,
+            as_allocation: Allocation,
             as_size: ash::vk::DeviceSize,
             vertex_device_address: u64,
             index_device_address: u64,
@@ -2285,10 +2696,9 @@ impl GpuBackend for VulkanBackend {
         let mut built: Vec<BuiltItem> = Vec::with_capacity(items.len());
 
         for item in &items {
-            let (as_buf, as_mem) = VulkanBackend::create_buffer(
-                &self.instance,
+            let (as_buf, as_allocation) = VulkanBackend::create_buffer(
+                self.vulkan_handle_tracker.allocator(),
                 &self.device,
-                self.phys_device,
                 item.size_info.acceleration_structure_size,
                 ash::vk::BufferUsageFlags::ACCELERATION_STRUCTURE_STORAGE_KHR
                     | ash::vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
@@ -2296,7 +2706,6 @@ impl GpuBackend for VulkanBackend {
             )?;
 
             self.vulkan_handle_tracker.register_buffer(as_buf);
-            self.vulkan_handle_tracker.register_device_memory(as_mem);
 
             let as_create_info = ash::vk::AccelerationStructureCreateInfoKHR {
                 buffer: as_buf,
@@ -2318,7 +2727,7 @@ impl GpuBackend for VulkanBackend {
             built.push(BuiltItem {
                 accel,
                 as_buf,
-                as_mem,
+                as_allocation,
                 as_size: item.size_info.acceleration_structure_size,
                 vertex_device_address: item.vertex_device_address,
                 index_device_address: item.index_device_address,
@@ -2383,8 +2792,10 @@ impl GpuBackend for VulkanBackend {
         // Cleanup scratch, assemble results.
         unsafe {
             self.device.destroy_buffer(scratch_buf, None);
-            self.device.free_memory(scratch_mem, None);
         }
+        self.vulkan_handle_tracker
+            .allocator()
+            .free(scratch_allocation);
 
         let results = built
             .into_iter()
@@ -2392,7 +2803,7 @@ impl GpuBackend for VulkanBackend {
                 handle: b.accel,
                 buffer: VulkanBuffer {
                     buffer: b.as_buf,
-                    memory: b.as_mem,
+                    allocation: b.as_allocation,
                     mapped: std::ptr::null_mut(),
                     flags: ash::vk::MemoryPropertyFlags::DEVICE_LOCAL,
                     size: b.as_size,
@@ -2508,72 +2919,24 @@ impl GpuBackend for VulkanBackend {
         let instance_data_size = (std::mem::size_of::<ash::vk::AccelerationStructureInstanceKHR>()
             * instances.len()) as ash::vk::DeviceSize;
 
-        let mut instance_alloc_flags = ash::vk::MemoryAllocateFlagsInfo {
-            flags: ash::vk::MemoryAllocateFlags::DEVICE_ADDRESS,
-            ..Default::default()
-        };
-        let (instance_buf, instance_mem) = {
-            let create_info = ash::vk::BufferCreateInfo {
-                size: instance_data_size,
-                usage: ash::vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR
-                    | ash::vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
-                sharing_mode: ash::vk::SharingMode::EXCLUSIVE,
-                ..Default::default()
-            };
-            let buf = unsafe { self.device.create_buffer(&create_info, None) }.map_err(|e| {
-                GpuError::new(
-                    format!("Failed to create TLAS instance buffer: {e:?}"),
-                    GpuErrorKind::ResourceCreation,
-                )
-            })?;
-            let mem_reqs = unsafe { self.device.get_buffer_memory_requirements(buf) };
-            let alloc_info = ash::vk::MemoryAllocateInfo {
-                allocation_size: mem_reqs.size,
-                memory_type_index: VulkanBackend::find_memory_type(
-                    &self.instance,
-                    self.phys_device,
-                    mem_reqs.memory_type_bits,
-                    ash::vk::MemoryPropertyFlags::HOST_VISIBLE
-                        | ash::vk::MemoryPropertyFlags::HOST_COHERENT,
-                )?,
-                p_next: &mut instance_alloc_flags as *mut _ as *mut _,
-                ..Default::default()
-            };
-            let mem = unsafe { self.device.allocate_memory(&alloc_info, None) }.map_err(|e| {
-                GpuError::new(
-                    format!("Failed to allocate TLAS instance memory: {e:?}"),
-                    GpuErrorKind::ResourceCreation,
-                )
-            })?;
-            unsafe { self.device.bind_buffer_memory(buf, mem, 0) }.map_err(|e| {
-                GpuError::new(
-                    format!("Failed to bind TLAS instance memory: {e:?}"),
-                    GpuErrorKind::ResourceCreation,
-                )
-            })?;
-
-            // Copy instance data
-            let ptr = unsafe {
-                self.device
-                    .map_memory(mem, 0, instance_data_size, ash::vk::MemoryMapFlags::empty())
-            }
-            .map_err(|e| {
-                GpuError::new(
-                    format!("Failed to map TLAS instance memory: {e:?}"),
-                    GpuErrorKind::ResourceCreation,
-                )
-            })?;
-            unsafe {
-                std::ptr::copy_nonoverlapping(
-                    instances.as_ptr() as *const u8,
-                    ptr as *mut u8,
-                    instance_data_size as usize,
-                );
-                self.device.unmap_memory(mem);
-            }
-
-            (buf, mem)
-        };
+        // instance data must be 16 byte aligned
+        let (instance_buf, instance_allocation) = VulkanBackend::create_buffer_aligned(
+            self.vulkan_handle_tracker.allocator(),
+            &self.device,
+            instance_data_size,
+            ash::vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR
+                | ash::vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            ash::vk::MemoryPropertyFlags::HOST_VISIBLE
+                | ash::vk::MemoryPropertyFlags::HOST_COHERENT,
+            16,
+        )?;
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                instances.as_ptr() as *const u8,
+                instance_allocation.mapped as *mut u8,
+                instance_data_size as usize,
+            );
+        }
 
         let instance_address = unsafe {
             self.device
@@ -2623,50 +2986,15 @@ impl GpuBackend for VulkanBackend {
         let align = properties.min_scratch_offset_alignment as u64;
         let scratch_size = (size_info.build_scratch_size + align - 1) & !(align - 1);
 
-        let mut scratch_alloc_flags = ash::vk::MemoryAllocateFlagsInfo {
-            flags: ash::vk::MemoryAllocateFlags::DEVICE_ADDRESS,
-            ..Default::default()
-        };
-        let (scratch_buf, scratch_mem) = {
-            let create_info = ash::vk::BufferCreateInfo {
-                size: scratch_size,
-                usage: ash::vk::BufferUsageFlags::STORAGE_BUFFER
-                    | ash::vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
-                sharing_mode: ash::vk::SharingMode::EXCLUSIVE,
-                ..Default::default()
-            };
-            let buf = unsafe { self.device.create_buffer(&create_info, None) }.map_err(|e| {
-                GpuError::new(
-                    format!("Failed to create TLAS scratch buffer: {e:?}"),
-                    GpuErrorKind::ResourceCreation,
-                )
-            })?;
-            let mem_reqs = unsafe { self.device.get_buffer_memory_requirements(buf) };
-            let alloc_info = ash::vk::MemoryAllocateInfo {
-                allocation_size: mem_reqs.size,
-                memory_type_index: VulkanBackend::find_memory_type(
-                    &self.instance,
-                    self.phys_device,
-                    mem_reqs.memory_type_bits,
-                    ash::vk::MemoryPropertyFlags::DEVICE_LOCAL,
-                )?,
-                p_next: &mut scratch_alloc_flags as *mut _ as *mut _,
-                ..Default::default()
-            };
-            let mem = unsafe { self.device.allocate_memory(&alloc_info, None) }.map_err(|e| {
-                GpuError::new(
-                    format!("Failed to allocate TLAS scratch memory: {e:?}"),
-                    GpuErrorKind::ResourceCreation,
-                )
-            })?;
-            unsafe { self.device.bind_buffer_memory(buf, mem, 0) }.map_err(|e| {
-                GpuError::new(
-                    format!("Failed to bind TLAS scratch memory: {e:?}"),
-                    GpuErrorKind::ResourceCreation,
-                )
-            })?;
-            (buf, mem)
-        };
+        let (scratch_buf, scratch_allocation) = VulkanBackend::create_buffer_aligned(
+            self.vulkan_handle_tracker.allocator(),
+            &self.device,
+            scratch_size,
+            ash::vk::BufferUsageFlags::STORAGE_BUFFER
+                | ash::vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            ash::vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            align,
+        )?;
         let scratch_address = unsafe {
             self.device
                 .get_buffer_device_address(&ash::vk::BufferDeviceAddressInfo {
@@ -2675,10 +3003,9 @@ impl GpuBackend for VulkanBackend {
                 })
         };
 
-        let (as_buf, as_mem) = VulkanBackend::create_buffer(
-            &self.instance,
+        let (as_buf, as_allocation) = VulkanBackend::create_buffer(
+            self.vulkan_handle_tracker.allocator(),
             &self.device,
-            self.phys_device,
             size_info.acceleration_structure_size,
             ash::vk::BufferUsageFlags::ACCELERATION_STRUCTURE_STORAGE_KHR
                 | ash::vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
@@ -2686,7 +3013,6 @@ impl GpuBackend for VulkanBackend {
         )?;
 
         self.vulkan_handle_tracker.register_buffer(as_buf);
-        self.vulkan_handle_tracker.register_device_memory(as_mem);
 
         let as_create_info = ash::vk::AccelerationStructureCreateInfoKHR {
             buffer: as_buf,
@@ -2733,16 +3059,17 @@ impl GpuBackend for VulkanBackend {
         // Cleanup
         unsafe {
             self.device.destroy_buffer(scratch_buf, None);
-            self.device.free_memory(scratch_mem, None);
             self.device.destroy_buffer(instance_buf, None);
-            self.device.free_memory(instance_mem, None);
         }
+        let allocator = self.vulkan_handle_tracker.allocator();
+        allocator.free(scratch_allocation);
+        allocator.free(instance_allocation);
 
         Ok(rt::AccelerationStructure {
             handle: accel,
             buffer: VulkanBuffer {
                 buffer: as_buf,
-                memory: as_mem,
+                allocation: as_allocation,
                 mapped: std::ptr::null_mut(),
                 flags: ash::vk::MemoryPropertyFlags::DEVICE_LOCAL,
                 size: size_info.acceleration_structure_size,
@@ -2767,25 +3094,42 @@ impl GpuBackend for VulkanBackend {
             });
         };
 
+        let bindless_bindings = bindless_layout_bindings(&self.bindless_limits);
+        let rt_bindings = rt::rt_layout_bindings();
+        let layout = PipelineLayoutDesc {
+            sets: &[&bindless_bindings, &rt_bindings],
+            push_constants: &[rt::rt_push_constant_range()],
+        };
+        for source in [
+            &shaders.raygen,
+            &shaders.miss,
+            &shaders.miss_shadow,
+            &shaders.closest_hit,
+            &shaders.any_hit,
+        ] {
+            let shader = &source[0];
+            validate_shader(shader.label, &shader.code, shader.stage, &layout, None)?;
+        }
+
         let raygen_module = create_shader_module(
-            shaders.raygen[0].code,
+            &shaders.raygen[0].code,
             &self.device,
             shaders.raygen[0].label,
         )?;
         let miss_module =
-            create_shader_module(shaders.miss[0].code, &self.device, shaders.miss[0].label)?;
+            create_shader_module(&shaders.miss[0].code, &self.device, shaders.miss[0].label)?;
         let miss_shadow_module = create_shader_module(
-            shaders.miss_shadow[0].code,
+            &shaders.miss_shadow[0].code,
             &self.device,
             shaders.miss_shadow[0].label,
         )?;
         let chit_module = create_shader_module(
-            shaders.closest_hit[0].code,
+            &shaders.closest_hit[0].code,
             &self.device,
             shaders.closest_hit[0].label,
         )?;
         let ahit_module = create_shader_module(
-            shaders.any_hit[0].code,
+            &shaders.any_hit[0].code,
             &self.device,
             shaders.any_hit[0].label,
         )?;
@@ -2890,7 +3234,7 @@ impl GpuBackend for VulkanBackend {
         let pipelines = unsafe {
             rt_feature.pipeline_loader.create_ray_tracing_pipelines(
                 ash::vk::DeferredOperationKHR::null(),
-                ash::vk::PipelineCache::null(),
+                self.pipeline_cache.handle(),
                 &[create_info],
                 None,
             )
@@ -2932,6 +3276,7 @@ impl GpuBackend for VulkanBackend {
             handle: pipeline,
             bind_point: ash::vk::PipelineBindPoint::RAY_TRACING_KHR,
             layout: rt_feature.pipeline_layout,
+            push_constant_size: 0,
             sbt: Some(sbt),
         })
     }
@@ -2947,6 +3292,9 @@ impl GpuBackend for VulkanBackend {
             format: TextureFormat::R16g16b16a16Float,
             sampler: SamplerDesc::default(),
             usage: RenderTargetUsage::Storage,
+            sample_count: 1,
+            persistent: false,
+            layers: 1,
         })
     }
 
@@ -3183,36 +3531,43 @@ impl GpuBackend for VulkanBackend {
             raygen: vec![Shader {
                 label: "RT raygen",
                 stage: ash::vk::ShaderStageFlags::RAYGEN_KHR,
-                code: include_bytes!("../../shaders/spv/rt/raygen.spv"),
+                path: "rt/raygen.spv",
+                code: Cow::Borrowed(include_bytes!("../../shaders/spv/rt/raygen.spv")),
             }],
             miss: vec![Shader {
                 label: "RT Miss",
                 stage: ash::vk::ShaderStageFlags::MISS_KHR,
-                code: include_bytes!("../../shaders/spv/rt/miss.spv"),
+                path: "rt/miss.spv",
+                code: Cow::Borrowed(include_bytes!("../../shaders/spv/rt/miss.spv")),
             }],
             miss_shadow: vec![Shader {
                 label: "RT raygen",
                 stage: ash::vk::ShaderStageFlags::MISS_KHR,
-                code: include_bytes!("../../shaders/spv/rt/miss_shadow.spv"),
+                path: "rt/miss_shadow.spv",
+                code: Cow::Borrowed(include_bytes!("../../shaders/spv/rt/miss_shadow.spv")),
             }],
             closest_hit: vec![Shader {
                 label: "RT raygen",
                 stage: ash::vk::ShaderStageFlags::CLOSEST_HIT_KHR,
-                code: include_bytes!("../../shaders/spv/rt/closest_hit.spv"),
+                path: "rt/closest_hit.spv",
+                code: Cow::Borrowed(include_bytes!("../../shaders/spv/rt/closest_hit.spv")),
             }],
             any_hit: vec![Shader {
                 label: "RT Any Hit",
                 stage: ash::vk::ShaderStageFlags::ANY_HIT_KHR,
-                code: include_bytes!("../../shaders/spv/rt/any_hit.spv"),
+                path: "rt/any_hit.spv",
+                code: Cow::Borrowed(include_bytes!("../../shaders/spv/rt/any_hit.spv")),
             }],
         }
     }
 }
 
 pub struct VulkanPipeline {
-    label: String,
-    handle: ash::vk::Pipeline,
-    bind_point: ash::vk::PipelineBindPoint,
-    layout: ash::vk::PipelineLayout,
+    pub(super) label: String,
+    pub(super) handle: ash::vk::Pipeline,
+    pub(super) bind_point: ash::vk::PipelineBindPoint,
+    pub(super) layout: ash::vk::PipelineLayout,
+    /// Size of the push-constant range of compute pipelines.
+    pub(super) push_constant_size: u32,
     sbt: Option<rt::RtSbt>,
 }
@@ -0,0 +1,341 @@
+//! Query-pool based GPU profiler.
+//!
+//! Every `begin_event`/`end_event` region is bracketed by two timestamps and,
+//! if enabled, a pipeline statistics query. Each frame in flight owns its own
+//! query pools; they are read back when the frame slot comes around again,
+//! after its fence has been waited on, so resolving never stalls.
+
+use crate::engine::{
+    backend::{GpuError, GpuErrorKind, GpuFrameTimings, GpuPassTiming},
+    vulkan_backend::FRAMES_IN_FLIGHT,
+};
+
+/// Timestamps per frame: frame begin/end plus two per region.
+const MAX_TIMESTAMPS: u32 = 512;
+/// Pipeline statistics queries per frame (top-level regions only).
+const MAX_STATISTICS_QUERIES: u32 = 128;
+
+const FRAME_BEGIN: u32 = 0;
+const FRAME_END: u32 = 1;
+
+struct Region {
+    name: String,
+    begin: u32,
+    end: Option<u32>,
+    statistics: Option<u32>,
+}
+
+struct FrameQueries {
+    timestamps: ash::vk::QueryPool,
+    statistics: Option<ash::vk::QueryPool>,
+    regions: Vec<Region>,
+    next_timestamp: u32,
+    next_statistics: u32,
+    /// Queries were submitted and not read back yet.
+    pending: bool,
+}
+
+pub(super) struct GpuProfiler {
+    device: ash::Device,
+    /// Nanoseconds per timestamp tick.
+    timestamp_period: f32,
+    timestamp_mask: u64,
+    frames: Vec<FrameQueries>,
+    /// Frame slot being recorded.
+    current: Option<usize>,
+    /// Indices of the open regions of the current frame, innermost last.
+    open: Vec<usize>,
+    latest: Option<GpuFrameTimings>,
+}
+
+impl GpuProfiler {
+    /// Create the query pools. `timestamp_valid_bits` is the main queue family's
+    /// value; `0` means the queue does not support timestamps.
+    pub(super) fn create(
+        device: &ash::Device,
+        timestamp_period: f32,
+        timestamp_valid_bits: u32,
+        with_statistics: bool,
+    ) -> Result<Option<GpuProfiler>, GpuError> {
+        if timestamp_valid_bits == 0 {
+            println!("GPU profiler disabled: main queue has no timestamp support");
+            return Ok(None);
+        }
+
+        let mut frames = Vec::with_capacity(FRAMES_IN_FLIGHT as usize);
+        for _ in 0..FRAMES_IN_FLIGHT {
+            let timestamps = create_query_pool(
+                device,
+                ash::vk::QueryType::TIMESTAMP,
+                MAX_TIMESTAMPS,
+                ash::vk::QueryPipelineStatisticFlags::empty(),
+            )?;
+            let statistics = if with_statistics {
+                Some(create_query_pool(
+                    device,
+                    ash::vk::QueryType::PIPELINE_STATISTICS,
+                    MAX_STATISTICS_QUERIES,
+                    ash::vk::QueryPipelineStatisticFlags::INPUT_ASSEMBLY_PRIMITIVES
+                        | ash::vk::QueryPipelineStatisticFlags::FRAGMENT_SHADER_INVOCATIONS,
+                )?)
+            } else {
+                None
+            };
+            frames.push(FrameQueries {
+                timestamps,
+                statistics,
+                regions: Vec::new(),
+                next_timestamp: 0,
+                next_statistics: 0,
+                pending: false,
+            });
+        }
+
+        Ok(Some(GpuProfiler {
+            device: device.clone(),
+            timestamp_period,
+            timestamp_mask: if timestamp_valid_bits >= 64 {
+                u64::MAX
+            } else {
+                (1u64 << timestamp_valid_bits) - 1
+            },
+            frames,
+            current: None,
+            open: Vec::new(),
+            latest: None,
+        }))
+    }
+
+    pub(super) fn latest(&self) -> Option<&GpuFrameTimings> {
+        self.latest.as_ref()
+    }
+
+    /// Resolve the previous use of `frame_idx` and start recording into it.
+    /// Call right after the frame's fence was waited on and its command buffer begun.
+    pub(super) fn begin_frame(&mut self, command_buffer: ash::vk::CommandBuffer, frame_idx: usize) {
+        if self.frames[frame_idx].pending
+            && let Some(timings) = self.resolve(frame_idx)
+        {
+            self.latest = Some(timings);
+        }
+
+        let frame = &mut self.frames[frame_idx];
+        frame.regions.clear();
+        frame.pending = false;
+        frame.next_timestamp = FRAME_END + 1;
+        frame.next_statistics = 0;
+        unsafe {
+            self.device
+                .cmd_reset_query_pool(command_buffer, frame.timestamps, 0, MAX_TIMESTAMPS);
+            if let Some(pool) = frame.statistics {
+                self.device
+                    .cmd_reset_query_pool(command_buffer, pool, 0, MAX_STATISTICS_QUERIES);
+            }
+            self.device.cmd_write_timestamp(
+                command_buffer,
+                ash::vk::PipelineStageFlags::TOP_OF_PIPE,
+                frame.timestamps,
+                FRAME_BEGIN,
+            );
+        }
+        self.current = Some(frame_idx);
+        self.open.clear();
+    }
+
+    /// Close open regions and write the frame end timestamp.
+    /// Call before the frame's command buffer is ended.
+    pub(super) fn end_frame(&mut self, command_buffer: ash::vk::CommandBuffer) {
+        while !self.open.is_empty() {
+            self.end_region(command_buffer);
+        }
+        let Some(frame_idx) = self.current.take() else {
+            return;
+        };
+        let frame = &mut self.frames[frame_idx];
+        unsafe {
+            self.device.cmd_write_timestamp(
+                command_buffer,
+                ash::vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                frame.timestamps,
+                FRAME_END,
+            );
+        }
+        frame.pending = true;
+    }
+
+    pub(super) fn begin_region(&mut self, command_buffer: ash::vk::CommandBuffer, name: &str) {
+        let Some(frame_idx) = self.current else {
+            return;
+        };
+        let frame = &mut self.frames[frame_idx];
+        if frame.next_timestamp + 2 > MAX_TIMESTAMPS {
+            // Out of queries: the region is still tracked so end_region stays balanced
+            self.open.push(usize::MAX);
+            return;
+        }
+        let begin = frame.next_timestamp;
+        frame.next_timestamp += 2;
+
+        // Statistics queries of one pool must not nest: only top-level regions get one
+        let nested = self.open.iter().any(|&i| i != usize::MAX);
+        let statistics = match frame.statistics {
+            Some(_) if !nested && frame.next_statistics < MAX_STATISTICS_QUERIES => {
+                frame.next_statistics += 1;
+                Some(frame.next_statistics - 1)
+            }
+            _ => None,
+        };
+
+        unsafe {
+            self.device.cmd_write_timestamp(
+                command_buffer,
+                ash::vk::PipelineStageFlags::TOP_OF_PIPE,
+                frame.timestamps,
+                begin,
+            );
+            if let (Some(pool), Some(query)) = (frame.statistics, statistics) {
+                self.device.cmd_begin_query(
+                    command_buffer,
+                    pool,
+                    query,
+                    ash::vk::QueryControlFlags::empty(),
+                );
+            }
+        }
+        frame.regions.push(Region {
+            name: name.to_string(),
+            begin,
+            end: None,
+            statistics,
+        });
+        self.open.push(frame.regions.len() - 1);
+    }
+
+    pub(super) fn end_region(&mut self, command_buffer: ash::vk::CommandBuffer) {
+        let (Some(frame_idx), Some(region_idx)) = (self.current, self.open.pop()) else {
+            return;
+        };
+        if region_idx == usize::MAX {
+            return;
+        }
+        let frame = &mut self.frames[frame_idx];
+        let region = &mut frame.regions[region_idx];
+        let end = region.begin + 1;
+        unsafe {
+            if let (Some(pool), Some(query)) = (frame.statistics, region.statistics) {
+                self.device.cmd_end_query(command_buffer, pool, query);
+            }
+            self.device.cmd_write_timestamp(
+                command_buffer,
+                ash::vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                frame.timestamps,
+                end,
+            );
+        }
+        region.end = Some(end);
+    }
+
+    /// Read back the queries of a completed frame. Returns `None` if the results
+    /// are not available, without waiting for them.
+    fn resolve(&self, frame_idx: usize) -> Option<GpuFrameTimings> {
+        let frame = &self.frames[frame_idx];
+        let mut timestamps = vec![0u64; frame.next_timestamp as usize];
+        unsafe {
+            self.device.get_query_pool_results(
+                frame.timestamps,
+                0,
+                &mut timestamps,
+                ash::vk::QueryResultFlags::TYPE_64,
+            )
+        }
+        .ok()?;
+
+        let mut statistics = vec![[0u64; 2]; frame.next_statistics as usize];
+        let statistics_ok = match frame.statistics {
+            Some(pool) if !statistics.is_empty() => unsafe {
+                self.device
+                    .get_query_pool_results(
+                        pool,
+                        0,
+                        &mut statistics,
+                        ash::vk::QueryResultFlags::TYPE_64,
+                    )
+                    .is_ok()
+            },
+            _ => false,
+        };
+
+        let elapsed_ms = |begin: u32, end: u32| {
+            let ticks = timestamps[end as usize].wrapping_sub(timestamps[begin as usize])
+                & self.timestamp_mask;
+            ticks as f64 * self.timestamp_period as f64 / 1_000_000.0
+        };
+
+        let mut passes: Vec<GpuPassTiming> = Vec::new();
+        for region in &frame.regions {
+            let Some(end) = region.end else {
+                continue;
+            };
+            let gpu_time_ms = elapsed_ms(region.begin, end) as f32;
+            // Pipeline statistics: [input assembly primitives, fragment shader invocations]
+            let stats = region
+                .statistics
+                .filter(|_| statistics_ok)
+                .map(|q| statistics[q as usize]);
+
+            match passes.iter_mut().find(|p| p.name == region.name) {
+                Some(pass) => {
+                    pass.count += 1;
+                    pass.gpu_time_ms += gpu_time_ms;
+                    if let Some([primitives, fragments]) = stats {
+                        *pass.primitives.get_or_insert(0) += primitives;
+                        *pass.fragment_invocations.get_or_insert(0) += fragments;
+                    }
+                }
+                None => passes.push(GpuPassTiming {
+                    name: region.name.clone(),
+                    count: 1,
+                    gpu_time_ms,
+                    primitives: stats.map(|s| s[0]),
+                    fragment_invocations: stats.map(|s| s[1]),
+                }),
+            }
+        }
+
+        Some(GpuFrameTimings {
+            gpu_time_ms: elapsed_ms(FRAME_BEGIN, FRAME_END) as f32,
+            passes,
+        })
+    }
+
+    pub(super) fn destroy(&mut self) {
+        for frame in self.frames.drain(..) {
+            unsafe {
+                self.device.destroy_query_pool(frame.timestamps, None);
+                if let Some(pool) = frame.statistics {
+                    self.device.destroy_query_pool(pool, None);
+                }
+            }
+        }
+    }
+}
+
+fn create_query_pool(
+    device: &ash::Device,
+    query_type: ash::vk::QueryType,
+    query_count: u32,
+    pipeline_statistics: ash::vk::QueryPipelineStatisticFlags,
+) -> Result<ash::vk::QueryPool, GpuError> {
+    let create_info = ash::vk::QueryPoolCreateInfo {
+        query_type,
+        query_count,
+        pipeline_statistics,
+        ..Default::default()
+    };
+    unsafe { device.create_query_pool(&create_info, None) }.map_err(|e| {
+        GpuError::new(
+            format!("Failed to create {query_type:?} query pool: {e:?}"),
+            GpuErrorKind::ResourceCreation,
+        )
+    })
+}
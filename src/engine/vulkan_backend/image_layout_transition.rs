@@ -1,5 +1,5 @@
 use crate::engine::{
-    backend::{GpuError, GpuErrorKind},
+    backend::{GpuError, GpuErrorKind, TargetAccess, TargetBarrier},
     vulkan_backend::{ENABLE_MARKER, VulkanBackend},
 };
 
@@ -71,6 +71,40 @@ impl VulkanBackend {
                 src_stage_mask = ash::vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT;
                 dst_stage_mask = ash::vk::PipelineStageFlags2::BOTTOM_OF_PIPE;
             }
+            (
+                ash::vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                ash::vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            ) => {
+                src_access_mask = ash::vk::AccessFlags2::COLOR_ATTACHMENT_WRITE;
+                dst_access_mask = ash::vk::AccessFlags2::TRANSFER_READ;
+                src_stage_mask = ash::vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT;
+                dst_stage_mask = ash::vk::PipelineStageFlags2::TRANSFER;
+            }
+            // readback: images are copied out of whatever layout the frame left them in
+            (
+                ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+                | ash::vk::ImageLayout::PRESENT_SRC_KHR
+                | ash::vk::ImageLayout::GENERAL,
+                ash::vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            ) => {
+                src_access_mask = ash::vk::AccessFlags2::MEMORY_WRITE;
+                dst_access_mask = ash::vk::AccessFlags2::TRANSFER_READ;
+                src_stage_mask = ash::vk::PipelineStageFlags2::ALL_COMMANDS;
+                dst_stage_mask = ash::vk::PipelineStageFlags2::TRANSFER;
+            }
+            (
+                ash::vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+                | ash::vk::ImageLayout::PRESENT_SRC_KHR
+                | ash::vk::ImageLayout::GENERAL
+                | ash::vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ) => {
+                src_access_mask = ash::vk::AccessFlags2::TRANSFER_READ;
+                dst_access_mask =
+                    ash::vk::AccessFlags2::MEMORY_READ | ash::vk::AccessFlags2::MEMORY_WRITE;
+                src_stage_mask = ash::vk::PipelineStageFlags2::TRANSFER;
+                dst_stage_mask = ash::vk::PipelineStageFlags2::ALL_COMMANDS;
+            }
             (ash::vk::ImageLayout::UNDEFINED, ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL) => {
                 dst_access_mask = ash::vk::AccessFlags2::TRANSFER_WRITE;
 
@@ -169,13 +203,16 @@ impl VulkanBackend {
                 src_access_mask = ash::vk::AccessFlags2::empty();
                 dst_access_mask = ash::vk::AccessFlags2::SHADER_READ;
                 src_stage_mask = ash::vk::PipelineStageFlags2::TOP_OF_PIPE;
-                dst_stage_mask = ash::vk::PipelineStageFlags2::FRAGMENT_SHADER;
+                dst_stage_mask = ash::vk::PipelineStageFlags2::FRAGMENT_SHADER
+                    | ash::vk::PipelineStageFlags2::COMPUTE_SHADER;
             }
+            // storage images are written by RT and compute shaders
             (ash::vk::ImageLayout::UNDEFINED, ash::vk::ImageLayout::GENERAL) => {
                 src_access_mask = ash::vk::AccessFlags2::empty();
                 dst_access_mask = ash::vk::AccessFlags2::SHADER_WRITE;
                 src_stage_mask = ash::vk::PipelineStageFlags2::TOP_OF_PIPE;
-                dst_stage_mask = ash::vk::PipelineStageFlags2::RAY_TRACING_SHADER_KHR;
+                dst_stage_mask = ash::vk::PipelineStageFlags2::RAY_TRACING_SHADER_KHR
+                    | ash::vk::PipelineStageFlags2::COMPUTE_SHADER;
             }
             (
                 ash::vk::ImageLayout::GENERAL,
@@ -183,8 +220,10 @@ impl VulkanBackend {
             ) => {
                 src_access_mask = ash::vk::AccessFlags2::SHADER_WRITE;
                 dst_access_mask = ash::vk::AccessFlags2::SHADER_READ;
-                src_stage_mask = ash::vk::PipelineStageFlags2::RAY_TRACING_SHADER_KHR;
-                dst_stage_mask = ash::vk::PipelineStageFlags2::FRAGMENT_SHADER;
+                src_stage_mask = ash::vk::PipelineStageFlags2::RAY_TRACING_SHADER_KHR
+                    | ash::vk::PipelineStageFlags2::COMPUTE_SHADER;
+                dst_stage_mask = ash::vk::PipelineStageFlags2::FRAGMENT_SHADER
+                    | ash::vk::PipelineStageFlags2::COMPUTE_SHADER;
             }
             (
                 ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
@@ -192,8 +231,10 @@ impl VulkanBackend {
             ) => {
                 src_access_mask = ash::vk::AccessFlags2::SHADER_READ;
                 dst_access_mask = ash::vk::AccessFlags2::SHADER_WRITE;
-                src_stage_mask = ash::vk::PipelineStageFlags2::FRAGMENT_SHADER;
-                dst_stage_mask = ash::vk::PipelineStageFlags2::RAY_TRACING_SHADER_KHR;
+                src_stage_mask = ash::vk::PipelineStageFlags2::FRAGMENT_SHADER
+                    | ash::vk::PipelineStageFlags2::COMPUTE_SHADER;
+                dst_stage_mask = ash::vk::PipelineStageFlags2::RAY_TRACING_SHADER_KHR
+                    | ash::vk::PipelineStageFlags2::COMPUTE_SHADER;
             }
             _ => {
                 // panic!("Invalid layout transition {old_layout:?} -> {new_layout:?}");
@@ -208,6 +249,13 @@ impl VulkanBackend {
             println!("MARKER ==== TRANSITION IMAGE\n  {old_layout:?} -> {new_layout:?}");
         }
 
+        // depth layouts name the depth aspect only, stencil formats move both
+        let (old_layout, new_layout) = if aspect.contains(ash::vk::ImageAspectFlags::STENCIL) {
+            (with_stencil(old_layout), with_stencil(new_layout))
+        } else {
+            (old_layout, new_layout)
+        };
+
         let barrier = ash::vk::ImageMemoryBarrier2 {
             src_stage_mask,
             src_access_mask,
@@ -243,3 +291,136 @@ impl VulkanBackend {
         Ok(())
     }
 }
+
+impl VulkanBackend {
+    /// Record the barriers of render targets changing access between render graph
+    /// passes in one pipeline barrier, and track their new layouts.
+    pub(super) fn record_target_barriers(
+        &self,
+        command_buffer: ash::vk::CommandBuffer,
+        idx: usize,
+        barriers: &[TargetBarrier<Self>],
+    ) {
+        let image_barriers: Vec<ash::vk::ImageMemoryBarrier2> = barriers
+            .iter()
+            .map(|barrier| {
+                let tex = barrier.target.get_target(idx);
+                let is_depth = tex.aspect.contains(ash::vk::ImageAspectFlags::DEPTH);
+                let (_, src_stage_mask, src_access_mask) = access_scope(barrier.before, is_depth);
+                let (layout, dst_stage_mask, dst_access_mask) =
+                    access_scope(barrier.after, is_depth);
+                let old_layout = tex.current_layout.get();
+                let new_layout = layout.unwrap_or(old_layout);
+                tex.current_layout.set(new_layout);
+
+                let (old_layout, new_layout) =
+                    if tex.aspect.contains(ash::vk::ImageAspectFlags::STENCIL) {
+                        (with_stencil(old_layout), with_stencil(new_layout))
+                    } else {
+                        (old_layout, new_layout)
+                    };
+                ash::vk::ImageMemoryBarrier2 {
+                    src_stage_mask,
+                    src_access_mask,
+                    dst_stage_mask,
+                    dst_access_mask,
+                    old_layout,
+                    new_layout,
+                    src_queue_family_index: ash::vk::QUEUE_FAMILY_IGNORED,
+                    dst_queue_family_index: ash::vk::QUEUE_FAMILY_IGNORED,
+                    image: tex.image,
+                    subresource_range: ash::vk::ImageSubresourceRange {
+                        aspect_mask: tex.aspect,
+                        base_mip_level: 0,
+                        level_count: tex.mip_levels,
+                        base_array_layer: 0,
+                        layer_count: tex.array_layers,
+                    },
+                    ..Default::default()
+                }
+            })
+            .collect();
+        if image_barriers.is_empty() {
+            return;
+        }
+        if ENABLE_MARKER {
+            println!("MARKER ==== TARGET BARRIERS ({})", image_barriers.len());
+        }
+
+        let dependency_info = ash::vk::DependencyInfo {
+            image_memory_barrier_count: image_barriers.len() as u32,
+            p_image_memory_barriers: image_barriers.as_ptr(),
+            ..Default::default()
+        };
+        unsafe {
+            self.device
+                .cmd_pipeline_barrier2(command_buffer, &dependency_info);
+        };
+    }
+}
+
+/// Layout, stages and accesses of a render target access. `None` keeps the
+/// current layout.
+fn access_scope(
+    access: TargetAccess,
+    is_depth: bool,
+) -> (
+    Option<ash::vk::ImageLayout>,
+    ash::vk::PipelineStageFlags2,
+    ash::vk::AccessFlags2,
+) {
+    let fragment_tests = ash::vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS
+        | ash::vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS;
+    match access {
+        // the previous frame may still use the target
+        TargetAccess::Undefined => (
+            None,
+            ash::vk::PipelineStageFlags2::ALL_COMMANDS,
+            ash::vk::AccessFlags2::empty(),
+        ),
+        TargetAccess::ColorAttachment => (
+            Some(ash::vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL),
+            ash::vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            ash::vk::AccessFlags2::COLOR_ATTACHMENT_READ
+                | ash::vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+        ),
+        TargetAccess::DepthAttachment => (
+            Some(ash::vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL),
+            fragment_tests,
+            ash::vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ
+                | ash::vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        ),
+        TargetAccess::DepthTest => (
+            Some(ash::vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL),
+            fragment_tests,
+            ash::vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ,
+        ),
+        // sampled by fragment, compute or ray tracing shaders
+        TargetAccess::ShaderRead => (
+            Some(if is_depth {
+                ash::vk::ImageLayout::DEPTH_READ_ONLY_OPTIMAL
+            } else {
+                ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+            }),
+            ash::vk::PipelineStageFlags2::ALL_COMMANDS,
+            ash::vk::AccessFlags2::SHADER_SAMPLED_READ,
+        ),
+        TargetAccess::Custom => (
+            None,
+            ash::vk::PipelineStageFlags2::ALL_COMMANDS,
+            ash::vk::AccessFlags2::MEMORY_READ | ash::vk::AccessFlags2::MEMORY_WRITE,
+        ),
+    }
+}
+
+fn with_stencil(layout: ash::vk::ImageLayout) -> ash::vk::ImageLayout {
+    match layout {
+        ash::vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL => {
+            ash::vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+        }
+        ash::vk::ImageLayout::DEPTH_READ_ONLY_OPTIMAL => {
+            ash::vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+        }
+        layout => layout,
+    }
+}
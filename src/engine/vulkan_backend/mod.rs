@@ -11,23 +11,36 @@ use crate::{
     app_handler::Window,
     engine::{
         backend::{
-            GpuError, GpuErrorKind, RenderTargetDesc, RenderTargetUsage, SamplerDesc, TextureFormat,
+            GpuBackend, GpuError, GpuErrorKind, RenderTargetDesc, RenderTargetUsage, SamplerDesc,
+            TextureFormat,
         },
-        compute_push::ComputePushConstants,
+        gpu_layout::{Pad, gpu_struct},
         settings::{Settings, SyncMode},
-        vulkan_backend::texture::VulkanTexture,
+        vulkan_backend::{
+            allocator::MemoryAllocator,
+            buffer::VulkanBuffer,
+            destruction_queue::{BindlessSlot, DestructionQueue, PendingDestroy},
+            texture::VulkanTexture,
+        },
     },
     util as crate_utils,
 };
 
 use winit::raw_window_handle::RawWindowHandle;
 
+mod allocator;
 mod buffer;
+mod compute;
+mod destruction_queue;
 mod egui;
 mod gpu_backend_impl;
+mod gpu_profiler;
 mod image_layout_transition;
+mod pipeline_cache;
 mod rt;
+mod spirv_reflect;
 mod texture;
+mod upload;
 mod util;
 
 const ENABLE_MARKER: bool = false;
@@ -74,14 +87,24 @@ impl Deref for Instance {
 struct LogicalDevice {
     device: ash::Device,
     main_queue_index: u32,
+    /// Dedicated transfer queue family used for uploads, if the device has one.
+    transfer_queue_index: Option<u32>,
     debug_utils_ext: Option<ash::ext::debug_utils::Device>,
     rt_supported: bool,
+    pipeline_statistics_supported: bool,
+    /// `VK_EXT_memory_budget` is enabled.
+    memory_budget_supported: bool,
 }
 
 impl LogicalDevice {
     fn get_main_queue(&self) -> ash::vk::Queue {
         unsafe { self.device.get_device_queue(self.main_queue_index, 0) }
     }
+
+    fn get_transfer_queue(&self) -> Option<(ash::vk::Queue, u32)> {
+        self.transfer_queue_index
+            .map(|idx| (unsafe { self.device.get_device_queue(idx, 0) }, idx))
+    }
 }
 
 impl Deref for LogicalDevice {
@@ -100,6 +123,8 @@ struct Swapchain {
     surface_format: SurfaceFormat,
     surface: ash::vk::SurfaceKHR,
     sync_mode: SyncMode,
+    /// No surface: `swapchain_images` are engine-owned offscreen images (one per frame in flight).
+    offscreen: bool,
 }
 
 struct SurfaceFormat {
@@ -147,6 +172,8 @@ struct CurrentFrame {
         Rc<Cell<ash::vk::ImageLayout>>,
     )>,
     pending_push: PushConstants,
+    /// Previous model matrices written to the frame's copy of `prev_models`.
+    prev_model_count: usize,
 }
 
 struct CommandPool {
@@ -154,116 +181,268 @@ struct CommandPool {
     short_lived: ash::vk::CommandPool,
 }
 
-const MAX_BINDLESS_TEXTURES: u32 = 1024;
-const MAX_BINDLESS_CUBEMAPS: u32 = 4;
-const MAX_BINDLESS_SHADOW_IMAGES: u32 = 4;
-const MAX_BINDLESS_COMPARISON_SAMPLERS: u32 = 4;
+/// Sampled image descriptors kept free for bindings outside the bindless arrays.
+const RESERVED_SAMPLED_IMAGES: u32 = 16;
+
+/// Sizes of the bindless descriptor arrays in set 0.
+///
+/// The arrays are `PARTIALLY_BOUND`, so they are created once at the largest
+/// size the device allows (capped by `MaxBindlessTextures` in `[Engine]`) and
+/// never need to grow.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct BindlessLimits {
+    pub textures: u32,
+    pub cubemaps: u32,
+    /// Shadow images, each paired with a comparison sampler of the same index.
+    /// 2D shadow maps and shadow cube map arrays share the indices.
+    pub shadow_images: u32,
+    pub texture_arrays: u32,
+    pub cube_arrays: u32,
+    /// 3D textures.
+    pub volumes: u32,
+}
+
+impl Default for BindlessLimits {
+    fn default() -> Self {
+        BindlessLimits {
+            textures: 1024,
+            cubemaps: 4,
+            shadow_images: 4,
+            texture_arrays: 64,
+            cube_arrays: 8,
+            volumes: 16,
+        }
+    }
+}
+
+impl BindlessLimits {
+    fn for_device(
+        instance: &ash::Instance,
+        phys_device: ash::vk::PhysicalDevice,
+        max_textures: u32,
+    ) -> Self {
+        let mut vk12_props = ash::vk::PhysicalDeviceVulkan12Properties::default();
+        let mut props2 = ash::vk::PhysicalDeviceProperties2 {
+            p_next: &mut vk12_props as *mut _ as *mut _,
+            ..Default::default()
+        };
+        unsafe { instance.get_physical_device_properties2(phys_device, &mut props2) };
+
+        // combined image samplers count against both image and sampler limits
+        let device_max = [
+            vk12_props.max_descriptor_set_update_after_bind_sampled_images,
+            vk12_props.max_per_stage_descriptor_update_after_bind_sampled_images,
+            vk12_props.max_descriptor_set_update_after_bind_samplers,
+            vk12_props.max_per_stage_descriptor_update_after_bind_samplers,
+        ]
+        .into_iter()
+        .min()
+        .unwrap_or(0);
+
+        Self::from_device_max(device_max, max_textures)
+    }
+
+    /// Split the sampled image descriptors of a device between the arrays.
+    ///
+    /// Cubemaps and shadow images get 1/64 of them each, at least the defaults
+    /// and at most `MAX_SMALL_ARRAY`; the 2D textures get what the other arrays
+    /// leave over, up to `max_textures`.
+    fn from_device_max(device_max: u32, max_textures: u32) -> Self {
+        const MAX_SMALL_ARRAY: u32 = 64;
+        let defaults = BindlessLimits::default();
+        let share = |min: u32| (device_max / 64).clamp(min, MAX_SMALL_ARRAY);
+        let limits = BindlessLimits {
+            cubemaps: share(defaults.cubemaps),
+            shadow_images: share(defaults.shadow_images),
+            ..defaults
+        };
+        let others = limits.cubemaps
+            + limits.shadow_images * 3
+            + limits.texture_arrays
+            + limits.cube_arrays
+            + limits.volumes
+            + RESERVED_SAMPLED_IMAGES;
+        BindlessLimits {
+            textures: max_textures.min(device_max.saturating_sub(others)).max(1),
+            ..limits
+        }
+    }
+}
 
 struct TextureRegistry {
+    limits: BindlessLimits,
     next_2d: u32,
     free_2d: Vec<u32>,
     next_cube: u32,
     free_cube: Vec<u32>,
     next_shadow: u32,
     free_shadow: Vec<u32>,
+    next_2d_array: u32,
+    free_2d_array: Vec<u32>,
+    next_cube_array: u32,
+    free_cube_array: Vec<u32>,
+    next_volume: u32,
+    free_volume: Vec<u32>,
 }
 
 impl TextureRegistry {
-    fn new() -> Self {
+    fn new(limits: BindlessLimits) -> Self {
         TextureRegistry {
+            limits,
             next_2d: 0,
             free_2d: Vec::new(),
             next_cube: 0,
             free_cube: Vec::new(),
             next_shadow: 0,
             free_shadow: Vec::new(),
+            next_2d_array: 0,
+            free_2d_array: Vec::new(),
+            next_cube_array: 0,
+            free_cube_array: Vec::new(),
+            next_volume: 0,
+            free_volume: Vec::new(),
         }
     }
 
-    fn allocate_2d(&mut self) -> u32 {
-        if let Some(slot) = self.free_2d.pop() {
-            slot
-        } else {
-            let slot = self.next_2d;
-            assert!(
-                slot < MAX_BINDLESS_TEXTURES,
-                "Exceeded max bindless 2D texture slots"
-            );
-            self.next_2d += 1;
-            slot
-        }
+    fn allocate_2d(&mut self) -> Result<u32, GpuError> {
+        Self::allocate(
+            &mut self.free_2d,
+            &mut self.next_2d,
+            self.limits.textures,
+            "2D texture",
+        )
     }
 
-    fn allocate_cube(&mut self) -> u32 {
-        if let Some(slot) = self.free_cube.pop() {
-            slot
-        } else {
-            let slot = self.next_cube;
-            assert!(
-                slot < MAX_BINDLESS_CUBEMAPS,
-                "Exceeded max bindless cubemap slots"
-            );
-            self.next_cube += 1;
-            slot
-        }
+    fn allocate_cube(&mut self) -> Result<u32, GpuError> {
+        Self::allocate(
+            &mut self.free_cube,
+            &mut self.next_cube,
+            self.limits.cubemaps,
+            "cubemap",
+        )
     }
 
-    fn allocate_shadow(&mut self) -> u32 {
-        if let Some(slot) = self.free_shadow.pop() {
-            slot
-        } else {
-            let slot = self.next_shadow;
-            assert!(
-                slot < MAX_BINDLESS_SHADOW_IMAGES,
-                "Exceeded max bindless shadow image slots"
-            );
-            self.next_shadow += 1;
-            slot
-        }
+    fn allocate_shadow(&mut self) -> Result<u32, GpuError> {
+        Self::allocate(
+            &mut self.free_shadow,
+            &mut self.next_shadow,
+            self.limits.shadow_images,
+            "shadow image",
+        )
+    }
+
+    fn allocate_2d_array(&mut self) -> Result<u32, GpuError> {
+        Self::allocate(
+            &mut self.free_2d_array,
+            &mut self.next_2d_array,
+            self.limits.texture_arrays,
+            "2D texture array",
+        )
+    }
+
+    fn allocate_cube_array(&mut self) -> Result<u32, GpuError> {
+        Self::allocate(
+            &mut self.free_cube_array,
+            &mut self.next_cube_array,
+            self.limits.cube_arrays,
+            "cubemap array",
+        )
     }
 
-    #[allow(dead_code)]
-    fn release_2d(&mut self, slot: u32) {
-        self.free_2d.push(slot);
+    fn allocate_volume(&mut self) -> Result<u32, GpuError> {
+        Self::allocate(
+            &mut self.free_volume,
+            &mut self.next_volume,
+            self.limits.volumes,
+            "3D texture",
+        )
     }
 
-    #[allow(dead_code)]
-    fn release_cube(&mut self, slot: u32) {
-        self.free_cube.push(slot);
+    fn allocate(
+        free: &mut Vec<u32>,
+        next: &mut u32,
+        max: u32,
+        kind: &str,
+    ) -> Result<u32, GpuError> {
+        if let Some(slot) = free.pop() {
+            return Ok(slot);
+        }
+        if *next >= max {
+            return Err(GpuError::new(
+                format!("All {max} bindless {kind} slots are in use"),
+                GpuErrorKind::ResourceCreation,
+            ));
+        }
+        *next += 1;
+        Ok(*next - 1)
     }
 
-    #[allow(dead_code)]
-    fn release_shadow(&mut self, slot: u32) {
-        self.free_shadow.push(slot);
+    fn release(&mut self, slot: BindlessSlot) {
+        match slot {
+            BindlessSlot::Texture2d(slot) => self.free_2d.push(slot),
+            BindlessSlot::Cube(slot) => self.free_cube.push(slot),
+            BindlessSlot::Shadow(slot) => self.free_shadow.push(slot),
+            BindlessSlot::Texture2dArray(slot) => self.free_2d_array.push(slot),
+            BindlessSlot::CubeArray(slot) => self.free_cube_array.push(slot),
+            BindlessSlot::Volume(slot) => self.free_volume.push(slot),
+        }
     }
 }
 
-#[repr(C)]
-#[derive(Clone, Copy)]
-pub(crate) struct PushConstants {
-    model: [f32; 16],
-    tex0: u32,
-    tex1: u32,
-    tex2: u32,
-    tex3: u32,
-    tex4: u32,
-    has_parallax: u32,
-    is_instanced: u32,
+gpu_struct! {
+    /// Per-draw push constants of the raster passes (`PushConstants` in `gpu_types.slang`).
+    #[derive(Clone, Copy)]
+    pub(crate) struct PushConstants: Std430 {
+        model: [f32; 16],
+        tex0: u32,
+        tex1: u32,
+        tex2: u32,
+        tex3: u32,
+        tex4: u32,
+        tex5: u32,
+        /// Shadow cube map array of the point lights, in the binding 22 array.
+        tex6: u32,
+        has_parallax: u32,
+        is_instanced: u32,
+        /// Index of the previous frame's model matrix in the buffer at
+        /// [`PREV_MODEL_BINDING`], [`NO_PREV_MODEL`] if the draw did not move.
+        prev_model_index: u32,
+        /// Non-zero if `tex0` indexes the 2D texture arrays and holds the
+        /// albedo, metallic-roughness and normal layers.
+        layered: u32,
+        _pad: Pad<1>,
+    }
 }
 
+// The minimum `maxPushConstantsSize` every device guarantees
+const _: () = assert!(std::mem::size_of::<PushConstants>() <= 128);
+
+/// Binding of the previous model matrices of the moving draws of a frame.
+pub(crate) const PREV_MODEL_BINDING: u32 = 21;
+/// Moving draws per frame with motion vectors; later ones are drawn without.
+const MAX_PREV_MODELS: usize = 4096;
+/// `PushConstants::prev_model_index` of draws whose model matrix did not change.
+const NO_PREV_MODEL: u32 = u32::MAX;
+
 impl Default for PushConstants {
     fn default() -> Self {
+        let identity = [
+            1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+        ];
         PushConstants {
-            model: [
-                1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
-            ],
+            model: identity,
             tex0: 0,
             tex1: 0,
             tex2: 0,
             tex3: 0,
             tex4: u32::MAX,
+            tex5: u32::MAX,
+            tex6: u32::MAX,
             has_parallax: 0,
             is_instanced: 0,
+            prev_model_index: NO_PREV_MODEL,
+            layered: 0,
+            _pad: Pad::ZERO,
         }
     }
 }
@@ -291,16 +470,18 @@ pub struct VulkanHandleTracker {
     active_samplers: Arc<Mutex<HashSet<ash::vk::Sampler>>>,
     active_image_views: Arc<Mutex<HashSet<ash::vk::ImageView>>>,
     active_images: Arc<Mutex<HashSet<ash::vk::Image>>>,
-    active_device_memory: Arc<Mutex<HashSet<ash::vk::DeviceMemory>>>,
+    allocator: MemoryAllocator,
     active_buffers: Arc<Mutex<HashSet<ash::vk::Buffer>>>,
     active_pipelines: Arc<Mutex<HashSet<ash::vk::Pipeline>>>,
     acceleration_structures: Arc<Mutex<HashSet<ash::vk::AccelerationStructureKHR>>>,
+    destruction_queue: Arc<Mutex<DestructionQueue>>,
 }
 
 impl VulkanHandleTracker {
     pub fn new(
         device: ash::Device,
         ac_device: Option<ash::khr::acceleration_structure::Device>,
+        allocator: MemoryAllocator,
     ) -> Self {
         VulkanHandleTracker {
             device: Arc::new(Mutex::new(device)),
@@ -308,10 +489,11 @@ impl VulkanHandleTracker {
             active_samplers: Arc::new(Mutex::new(HashSet::new())),
             active_image_views: Arc::new(Mutex::new(HashSet::new())),
             active_images: Arc::new(Mutex::new(HashSet::new())),
-            active_device_memory: Arc::new(Mutex::new(HashSet::new())),
+            allocator,
             active_buffers: Arc::new(Mutex::new(HashSet::new())),
             active_pipelines: Arc::new(Mutex::new(HashSet::new())),
             acceleration_structures: Arc::new(Mutex::new(HashSet::new())),
+            destruction_queue: Arc::new(Mutex::new(DestructionQueue::new())),
         }
     }
 
@@ -339,12 +521,9 @@ impl VulkanHandleTracker {
         crate_utils::mtx_lock(&self.active_images).remove(&image);
     }
 
-    pub fn register_device_memory(&self, mem: ash::vk::DeviceMemory) {
-        crate_utils::mtx_lock(&self.active_device_memory).insert(mem);
-    }
-
-    pub fn unregister_device_memory(&self, mem: ash::vk::DeviceMemory) {
-        crate_utils::mtx_lock(&self.active_device_memory).remove(&mem);
+    /// Device memory of all buffers and images is allocated from here.
+    pub fn allocator(&self) -> &MemoryAllocator {
+        &self.allocator
     }
 
     pub fn register_buffer(&self, buffer: ash::vk::Buffer) {
@@ -371,6 +550,85 @@ impl VulkanHandleTracker {
         crate_utils::mtx_lock(&self.acceleration_structures).remove(&structure);
     }
 
+    /// Queue the handles of a dropped resource until the frames in flight are done with it.
+    pub fn defer_destroy(&self, resource: PendingDestroy) {
+        crate_utils::mtx_lock(&self.destruction_queue).push(resource);
+    }
+
+    /// Start recording a new frame and return its serial.
+    pub fn next_frame_serial(&self) -> u64 {
+        crate_utils::mtx_lock(&self.destruction_queue).next_frame()
+    }
+
+    pub fn frame_serial(&self) -> u64 {
+        crate_utils::mtx_lock(&self.destruction_queue).frame_serial()
+    }
+
+    /// Destroy resources dropped during frames up to `completed_serial`.
+    /// Returns the bindless slots that became free.
+    pub fn destroy_completed(&self, completed_serial: u64) -> Vec<BindlessSlot> {
+        let resources =
+            crate_utils::mtx_lock(&self.destruction_queue).take_completed(completed_serial);
+        self.destroy_pending(resources)
+    }
+
+    /// Destroy every queued resource. Resources dropped afterwards are only
+    /// destroyed by `cleanup_leftover`. The device must be idle.
+    pub fn destroy_all_pending(&self) {
+        let resources = crate_utils::mtx_lock(&self.destruction_queue).close();
+        self.destroy_pending(resources);
+    }
+
+    fn destroy_pending(&self, resources: Vec<PendingDestroy>) -> Vec<BindlessSlot> {
+        let mut free_slots = Vec::new();
+        if resources.is_empty() {
+            return free_slots;
+        }
+        let device = crate_utils::mtx_lock(&self.device);
+        for resource in resources {
+            match resource {
+                PendingDestroy::Texture {
+                    image,
+                    image_view,
+                    sampler,
+                    allocation,
+                    slot,
+                } => {
+                    unsafe {
+                        if sampler != ash::vk::Sampler::null() {
+                            self.unregister_sampler(sampler);
+                            device.destroy_sampler(sampler, None);
+                        }
+                        if image_view != ash::vk::ImageView::null() {
+                            self.unregister_image_view(image_view);
+                            device.destroy_image_view(image_view, None);
+                        }
+                        if image != ash::vk::Image::null() {
+                            self.unregister_image(image);
+                            device.destroy_image(image, None);
+                        }
+                    }
+                    self.allocator.free(allocation);
+                    free_slots.extend(slot);
+                }
+                PendingDestroy::Buffer { buffer, allocation } => {
+                    if buffer != ash::vk::Buffer::null() {
+                        self.unregister_buffer(buffer);
+                        unsafe { device.destroy_buffer(buffer, None) };
+                    }
+                    self.allocator.free(allocation);
+                }
+                PendingDestroy::AccelerationStructure(structure) => {
+                    if let Some(ac_device) = crate_utils::mtx_lock(&self.ac_device).as_ref() {
+                        self.unregister_acceleration_structure(structure);
+                        unsafe { ac_device.destroy_acceleration_structure(structure, None) };
+                    }
+                }
+            }
+        }
+        free_slots
+    }
+
     pub fn cleanup_leftover(&self) {
         for pipeline in crate_utils::mtx_lock(&self.active_pipelines).drain() {
             unsafe { crate_utils::mtx_lock(&self.device).destroy_pipeline(pipeline, None) };
@@ -388,9 +646,6 @@ impl VulkanHandleTracker {
         for buffer in crate_utils::mtx_lock(&self.active_buffers).drain() {
             unsafe { crate_utils::mtx_lock(&self.device).destroy_buffer(buffer, None) };
         }
-        for mem in crate_utils::mtx_lock(&self.active_device_memory).drain() {
-            unsafe { crate_utils::mtx_lock(&self.device).free_memory(mem, None) };
-        }
         if let Some(ac_device) = crate_utils::mtx_lock(&self.ac_device).as_ref() {
             for accel_struct in crate_utils::mtx_lock(&self.acceleration_structures).drain() {
                 unsafe {
@@ -398,11 +653,13 @@ impl VulkanHandleTracker {
                 }
             }
         }
+        self.allocator.destroy();
     }
 }
 
 pub struct VulkanBackend {
-    window: Arc<Window>,
+    /// `None` when running headless (offscreen backbuffer, no surface).
+    window: Option<Arc<Window>>,
     context: ash::Entry,
     instance: Instance,
     phys_device: ash::vk::PhysicalDevice,
@@ -412,7 +669,8 @@ pub struct VulkanBackend {
     queue: ash::vk::Queue,
     // graphics_pipeline: ash::vk::Pipeline,
     pipeline_layout: ash::vk::PipelineLayout,
-    compute_pipeline_layout: ash::vk::PipelineLayout,
+    /// Shared by compute pipelines with the same set 1 bindings and push constants.
+    compute_layouts: RefCell<compute::ComputeLayouts>,
     command_pool: CommandPool,
     command_buffers: [ash::vk::CommandBuffer; FRAMES_IN_FLIGHT as usize],
     descriptors: Descriptors,
@@ -420,11 +678,22 @@ pub struct VulkanBackend {
     khr_sync: ash::khr::synchronization2::Device,
     push_descriptor: ash::khr::push_descriptor::Device,
     frame_idx: usize,
+    /// Serial of the frame last submitted from each frame-in-flight slot.
+    frame_serials: [u64; FRAMES_IN_FLIGHT as usize],
     current_frame: Option<CurrentFrame>,
     texture_registry: RefCell<TextureRegistry>,
+    bindless_limits: BindlessLimits,
     egui_renderer: Option<egui::EguiRenderer>,
     vulkan_handle_tracker: VulkanHandleTracker,
     rt_feature: Option<rt::RtFeature>,
+    /// Per-pass GPU timings; `None` if the main queue has no timestamp support.
+    profiler: RefCell<Option<gpu_profiler::GpuProfiler>>,
+    /// Shared by all pipeline creation paths, persisted on drop.
+    pipeline_cache: pipeline_cache::PipelineCache,
+    /// Batched staging uploads of texture and buffer data.
+    uploader: RefCell<upload::Uploader>,
+    /// Previous model matrices of the moving draws, one copy per frame in flight.
+    prev_models: Option<VulkanBuffer>,
 }
 
 impl Drop for VulkanBackend {
@@ -442,10 +711,22 @@ impl Drop for VulkanBackend {
             self.texture_registry.borrow_mut().free_2d.clear();
             self.texture_registry.borrow_mut().free_cube.clear();
             self.texture_registry.borrow_mut().free_shadow.clear();
+            self.texture_registry.borrow_mut().free_2d_array.clear();
+            self.texture_registry.borrow_mut().free_cube_array.clear();
+            self.texture_registry.borrow_mut().free_volume.clear();
 
             // Take current_frame
             self.current_frame = None;
 
+            if let Some(mut profiler) = self.profiler.get_mut().take() {
+                profiler.destroy();
+            }
+
+            self.uploader.get_mut().destroy();
+
+            self.pipeline_cache.save();
+            self.pipeline_cache.destroy();
+
             // Destroy sync objects
             for fence in &self.sync_objects.draw_fences {
                 self.device.destroy_fence(*fence, None);
@@ -473,8 +754,7 @@ impl Drop for VulkanBackend {
             // self.device.destroy_pipeline(self.graphics_pipeline, None);
             self.device
                 .destroy_pipeline_layout(self.pipeline_layout, None);
-            self.device
-                .destroy_pipeline_layout(self.compute_pipeline_layout, None);
+            self.compute_layouts.get_mut().destroy(&self.device);
 
             // Destroy RT feature layouts
             if let Some(rt) = &self.rt_feature {
@@ -484,14 +764,16 @@ impl Drop for VulkanBackend {
                     .destroy_descriptor_set_layout(rt.descriptor_layout, None);
             }
 
-            let emtpy_target =
-                VulkanTexture::null(self.device.clone(), self.vulkan_handle_tracker.clone());
-            // Destroy depth targets (take ownership to call destroy)
-            for depth_target in std::mem::replace(
+            let emtpy_target = VulkanTexture::null(self.device.clone());
+            // Drop depth targets, offscreen backbuffers and the previous model matrices,
+            // destroyed with the pending queue below
+            drop(self.prev_models.take());
+            drop(std::mem::replace(
                 &mut self.depth_targets,
                 [emtpy_target.clone(), emtpy_target],
-            ) {
-                depth_target.destroy();
+            ));
+            if self.swapchain.offscreen {
+                self.swapchain.swapchain_images.clear();
             }
 
             // Destroy swapchain image views and shared sampler
@@ -510,9 +792,11 @@ impl Drop for VulkanBackend {
                 }
             }
 
-            self.swapchain
-                .fn_ptr
-                .destroy_swapchain(self.swapchain.swapchain, None);
+            if !self.swapchain.offscreen {
+                self.swapchain
+                    .fn_ptr
+                    .destroy_swapchain(self.swapchain.swapchain, None);
+            }
             self.swapchain.swapchain = ash::vk::SwapchainKHR::null();
 
             // Save surface handle for later destruction (after device)
@@ -527,15 +811,18 @@ impl Drop for VulkanBackend {
                 self.instance.debug_messenger = None;
             }
 
-            // Destroy remaining tracked resources
+            // Destroy dropped resources, then whatever is still alive
+            self.vulkan_handle_tracker.destroy_all_pending();
             self.vulkan_handle_tracker.cleanup_leftover();
 
             // Destroy device before surface (Vulkan spec: all device resources must be freed first)
             self.device.destroy_device(None);
 
             // Destroy surface after device, before instance
-            let surface_ext = ash::khr::surface::Instance::new(&self.context, &self.instance);
-            surface_ext.destroy_surface(surface_handle, None);
+            if surface_handle != ash::vk::SurfaceKHR::null() {
+                let surface_ext = ash::khr::surface::Instance::new(&self.context, &self.instance);
+                surface_ext.destroy_surface(surface_handle, None);
+            }
 
             // Instance dropped by its own Drop impl
             self.instance.destroy_instance(None);
@@ -544,26 +831,58 @@ impl Drop for VulkanBackend {
 }
 
 pub fn initialize(window: Arc<Window>, settings: &Settings) -> Result<VulkanBackend, GpuError> {
+    let size = window.inner_size();
+    initialize_with_target(Some(window), (size.width, size.height), settings)
+}
+
+/// Initialize the backend without a window or swapchain.
+///
+/// The backbuffer is an offscreen image of the requested size, `present` only
+/// advances the frame. Works on software drivers such as lavapipe.
+pub fn initialize_headless(
+    width: u32,
+    height: u32,
+    settings: &Settings,
+) -> Result<VulkanBackend, GpuError> {
+    initialize_with_target(None, (width, height), settings)
+}
+
+fn initialize_with_target(
+    window: Option<Arc<Window>>,
+    extent: (u32, u32),
+    settings: &Settings,
+) -> Result<VulkanBackend, GpuError> {
     let enable_validation = settings.gpu_validation;
     let sync_mode = settings.sync_mode;
+    let headless = window.is_none();
 
     let context = unsafe { ash::Entry::load() }
         .map_err(|_| GpuError::new("Failed to load Vulkan entry", GpuErrorKind::Other))?;
 
-    let mut instance = create_instance(&context, window.h_wnd(), enable_validation)?;
+    let mut instance = create_instance(
+        &context,
+        window.as_ref().map(|w| w.h_wnd()),
+        enable_validation,
+    )?;
     println!("Vulkan instance created successfully");
 
     if instance.validation_enabled {
         instance.debug_messenger = Some(setup_debug_messenger(&context, &instance)?);
     }
 
-    let (physical_device, rt_props) = get_physical_device(&instance)?;
+    let (physical_device, rt_props) = get_physical_device(&instance, headless)?;
 
     let rt_supported = rt_props.is_some();
     println!("Physical device selected successfully");
 
-    let surface = util::create_surface(&context, &instance, &window)?;
-    println!("Surface created successfully");
+    let surface = match &window {
+        Some(window) => {
+            let surface = util::create_surface(&context, &instance, window)?;
+            println!("Surface created successfully");
+            Some(surface)
+        }
+        None => None,
+    };
 
     let logical_device =
         create_logical_device(&context, &instance, physical_device, surface, rt_supported)?;
@@ -580,23 +899,37 @@ pub fn initialize(window: Arc<Window>, settings: &Settings) -> Result<VulkanBack
     } else {
         None
     };
-    let vk_handle_tracker = VulkanHandleTracker::new(logical_device.device.clone(), ac_device);
-
-    let (swapchain, depth_targets) = create_swapchain_and_depth_buffer(
-        &context,
+    let allocator = MemoryAllocator::new(
         &instance,
-        {
-            let size = window.inner_size();
-            (size.width, size.height)
-        },
         physical_device,
-        &logical_device,
-        surface,
-        sync_mode,
-        settings.hdr_preferred,
-        vk_handle_tracker.clone(),
-        ash::vk::SwapchainKHR::null(),
-    )?;
+        &logical_device.device,
+        logical_device.memory_budget_supported,
+    );
+    let vk_handle_tracker =
+        VulkanHandleTracker::new(logical_device.device.clone(), ac_device, allocator);
+
+    let (swapchain, depth_targets) = match surface {
+        Some(surface) => create_swapchain_and_depth_buffer(
+            &context,
+            &instance,
+            extent,
+            physical_device,
+            &logical_device,
+            surface,
+            sync_mode,
+            settings.hdr_preferred,
+            vk_handle_tracker.clone(),
+            ash::vk::SwapchainKHR::null(),
+        )?,
+        None => create_offscreen_backbuffer_and_depth_buffer(
+            &instance,
+            extent,
+            physical_device,
+            &logical_device,
+            sync_mode,
+            vk_handle_tracker.clone(),
+        )?,
+    };
     println!("Swapchain and depth buffer created successfully");
 
     // let pipeline = create_graphics_pipeline(&logical_device, &swapchain)?;
@@ -605,6 +938,17 @@ pub fn initialize(window: Arc<Window>, settings: &Settings) -> Result<VulkanBack
     let command_pool = create_command_pool(&logical_device)?;
     println!("Command pools created successfully");
 
+    let uploader = upload::Uploader::new(
+        &logical_device,
+        vk_handle_tracker.allocator().clone(),
+        queue,
+        logical_device.main_queue_index,
+        logical_device.get_transfer_queue(),
+    )?;
+    if uploader.has_transfer_queue() {
+        println!("Uploads use a dedicated transfer queue");
+    }
+
     let command_buffers = create_command_buffers(&logical_device, command_pool.render_pool)?
         .try_into()
         .map_err(|_| {
@@ -619,9 +963,12 @@ pub fn initialize(window: Arc<Window>, settings: &Settings) -> Result<VulkanBack
     let khr_sync = ash::khr::synchronization2::Device::new(&instance, &logical_device);
     let push_descriptor = ash::khr::push_descriptor::Device::new(&instance, &logical_device);
 
-    let desc_pool = create_descriptor_pool(&logical_device)?;
+    let bindless_limits =
+        BindlessLimits::for_device(&instance, physical_device, settings.max_bindless_textures);
+    println!("Bindless texture slots: {}", bindless_limits.textures);
+    let desc_pool = create_descriptor_pool(&logical_device, &bindless_limits)?;
 
-    let desc_set_layout = create_descriptor_set_layout(&logical_device)?;
+    let desc_set_layout = create_descriptor_set_layout(&logical_device, &bindless_limits)?;
 
     let rt_feature = if let Some(props) = rt_props {
         let loader = ash::khr::ray_tracing_pipeline::Device::new(&instance, &logical_device);
@@ -647,10 +994,25 @@ pub fn initialize(window: Arc<Window>, settings: &Settings) -> Result<VulkanBack
             )
         })?;
 
+    let device_properties = unsafe { instance.get_physical_device_properties(physical_device) };
     let pipeline_layout = create_pipeline_layout(&logical_device, desc_set_layout)?;
-    let compute_pipeline_layout = create_compute_pipeline_layout(&logical_device, desc_set_layout)?;
+    let pipeline_cache = pipeline_cache::PipelineCache::load(&logical_device, &device_properties)?;
+
+    let timestamp_period = device_properties.limits.timestamp_period;
+    let timestamp_valid_bits =
+        unsafe { instance.get_physical_device_queue_family_properties(physical_device) }
+            [logical_device.main_queue_index as usize]
+            .timestamp_valid_bits;
+    let with_statistics =
+        settings.gpu_pipeline_statistics && logical_device.pipeline_statistics_supported;
+    let profiler = gpu_profiler::GpuProfiler::create(
+        &logical_device,
+        timestamp_period,
+        timestamp_valid_bits,
+        with_statistics,
+    )?;
 
-    Ok(VulkanBackend {
+    let mut backend = VulkanBackend {
         window,
         context,
         instance,
@@ -661,7 +1023,7 @@ pub fn initialize(window: Arc<Window>, settings: &Settings) -> Result<VulkanBack
         queue,
         // graphics_pipeline: pipeline,
         pipeline_layout,
-        compute_pipeline_layout,
+        compute_layouts: RefCell::new(compute::ComputeLayouts::default()),
         command_pool,
         command_buffers,
         descriptors: Descriptors {
@@ -673,48 +1035,45 @@ pub fn initialize(window: Arc<Window>, settings: &Settings) -> Result<VulkanBack
         khr_sync,
         push_descriptor,
         frame_idx: 0,
+        frame_serials: [0; FRAMES_IN_FLIGHT as usize],
         current_frame: None,
-        texture_registry: RefCell::new(TextureRegistry::new()),
+        texture_registry: RefCell::new(TextureRegistry::new(bindless_limits)),
+        bindless_limits,
         egui_renderer: None,
         vulkan_handle_tracker: vk_handle_tracker,
         rt_feature,
-    })
+        profiler: RefCell::new(profiler),
+        pipeline_cache,
+        uploader: RefCell::new(uploader),
+        prev_models: None,
+    };
+
+    let size = (std::mem::size_of::<glm::Mat4>() * MAX_PREV_MODELS) as u64;
+    let usage = ash::vk::BufferUsageFlags::STORAGE_BUFFER;
+    let flags = ash::vk::MemoryPropertyFlags::DEVICE_LOCAL
+        | ash::vk::MemoryPropertyFlags::HOST_VISIBLE
+        | ash::vk::MemoryPropertyFlags::HOST_COHERENT;
+    let mut prev_models = backend.create_vulkan_buffer(size, usage, flags)?;
+    prev_models.per_frame_copies = Some(backend.create_per_frame_copies(size, usage, flags)?);
+    backend.bind_buffer_to_descriptor(PREV_MODEL_BINDING, &prev_models);
+    backend.prev_models = Some(prev_models);
+
+    Ok(backend)
 }
 
-fn create_pipeline_layout(
-    device: &LogicalDevice,
-    descriptor_layout: ash::vk::DescriptorSetLayout,
-) -> Result<ash::vk::PipelineLayout, GpuError> {
-    let push_range = ash::vk::PushConstantRange {
+fn graphics_push_constant_range() -> ash::vk::PushConstantRange {
+    ash::vk::PushConstantRange {
         stage_flags: ash::vk::ShaderStageFlags::VERTEX | ash::vk::ShaderStageFlags::FRAGMENT,
         offset: 0,
         size: std::mem::size_of::<PushConstants>() as u32,
-    };
-    let create_info = ash::vk::PipelineLayoutCreateInfo {
-        set_layout_count: 1,
-        p_set_layouts: &descriptor_layout,
-        push_constant_range_count: 1,
-        p_push_constant_ranges: &push_range,
-        ..Default::default()
-    };
-
-    unsafe { device.create_pipeline_layout(&create_info, None) }.map_err(|e| {
-        GpuError::new(
-            format!("Failed to create pipeline layout: {e:?}"),
-            GpuErrorKind::ResourceCreation,
-        )
-    })
+    }
 }
 
-fn create_compute_pipeline_layout(
+fn create_pipeline_layout(
     device: &LogicalDevice,
     descriptor_layout: ash::vk::DescriptorSetLayout,
 ) -> Result<ash::vk::PipelineLayout, GpuError> {
-    let push_range = ash::vk::PushConstantRange {
-        stage_flags: ash::vk::ShaderStageFlags::COMPUTE,
-        offset: 0,
-        size: std::mem::size_of::<ComputePushConstants>() as u32, // maxInstances + assetOffset + maxHeight + spawn params + scale + tilt + terrainSegmentsF
-    };
+    let push_range = graphics_push_constant_range();
     let create_info = ash::vk::PipelineLayoutCreateInfo {
         set_layout_count: 1,
         p_set_layouts: &descriptor_layout,
@@ -722,9 +1081,10 @@ fn create_compute_pipeline_layout(
         p_push_constant_ranges: &push_range,
         ..Default::default()
     };
+
     unsafe { device.create_pipeline_layout(&create_info, None) }.map_err(|e| {
         GpuError::new(
-            format!("Failed to create compute pipeline layout: {e:?}"),
+            format!("Failed to create pipeline layout: {e:?}"),
             GpuErrorKind::ResourceCreation,
         )
     })
@@ -760,13 +1120,14 @@ fn create_descriptor_sets(
     }
 }
 
-fn create_descriptor_set_layout(
-    device: &LogicalDevice,
-) -> Result<ash::vk::DescriptorSetLayout, GpuError> {
+/// Bindings of the bindless descriptor set (set 0) shared by all pipelines.
+fn bindless_layout_bindings(
+    limits: &BindlessLimits,
+) -> [ash::vk::DescriptorSetLayoutBinding<'static>; 23] {
     // Binding 0: Main ViewProj UBO (view+proj, 128B) — deferred_pre vtx, forward vtx
-    // Binding 1: Camera pixel UBO (cameraPos+ssao, 16B) — deferred_light pxl, forward pxl
-    // Binding 2: Light data UBO (Light struct, 96B) — deferred_light pxl, forward pxl
-    // Binding 3: Shadow LightSpace UBO (lightSpaceMatrix, 64B) — shadow vtx
+    // Binding 1: Camera pixel UBO (cameraPos+ssao+taa, 32B) — deferred_light pxl, forward pxl, output pxl
+    // Binding 2: Cluster grid UBO (ClusterUniforms, 176B) — light culling comp, deferred_light pxl, forward pxl
+    // Binding 3: Shadow LightSpace UBO (lightSpaceMatrix of a cascade, 64B) — shadow vtx
     // Binding 4: Skybox ViewProj UBO (view+proj, 128B) — skybox vtx
     // Binding 5: DeferredPre NearFar UBO (near/far, 16B) — deferred_pre pxl
     // Binding 6: Global 2D texture array (CIS[1024]) — all pixel shaders
@@ -776,7 +1137,16 @@ fn create_descriptor_set_layout(
     // Binding 10: instance transforms (procedural gen)
     // Binding 11: structured buffers (procedural gen) - draw commands
     // Binding 12: texture binding for compute
-    let bindings = [
+    // Binding 14: 2D texture arrays (CIS[64])
+    // Binding 15: Cubemap arrays (CIS[8])
+    // Binding 16: 3D textures (CIS[16])
+    // Binding 17: Light storage buffer (Light[MAX_LIGHTS]) — light culling comp, lighting pxl
+    // Binding 18: Cluster light lists (count + indices per cluster) — light culling comp, lighting pxl
+    // Binding 19: SSAO UBO (SsaoUniforms, 672B) — ssao pxl
+    // Binding 20: TAA UBO (TaaUniforms, 144B) — deferred_pre vtx, taa pxl
+    // Binding 21: Previous model matrices (STORAGE_BUFFER) — deferred_pre vtx
+    // Binding 22: Shadow depth cube map arrays (SAMPLED_IMAGE[4]) — shadow module
+    [
         ash::vk::DescriptorSetLayoutBinding {
             binding: 0,
             descriptor_type: ash::vk::DescriptorType::UNIFORM_BUFFER,
@@ -797,7 +1167,7 @@ fn create_descriptor_set_layout(
             binding: 2,
             descriptor_type: ash::vk::DescriptorType::UNIFORM_BUFFER,
             descriptor_count: 1,
-            stage_flags: ash::vk::ShaderStageFlags::FRAGMENT,
+            stage_flags: ash::vk::ShaderStageFlags::FRAGMENT | ash::vk::ShaderStageFlags::COMPUTE,
             ..Default::default()
         },
         ash::vk::DescriptorSetLayoutBinding {
@@ -824,7 +1194,7 @@ fn create_descriptor_set_layout(
         ash::vk::DescriptorSetLayoutBinding {
             binding: 6,
             descriptor_type: ash::vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-            descriptor_count: MAX_BINDLESS_TEXTURES,
+            descriptor_count: limits.textures,
             stage_flags: ash::vk::ShaderStageFlags::VERTEX
                 | ash::vk::ShaderStageFlags::FRAGMENT
                 | ash::vk::ShaderStageFlags::ANY_HIT_KHR,
@@ -833,21 +1203,21 @@ fn create_descriptor_set_layout(
         ash::vk::DescriptorSetLayoutBinding {
             binding: 7,
             descriptor_type: ash::vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-            descriptor_count: MAX_BINDLESS_CUBEMAPS,
+            descriptor_count: limits.cubemaps,
             stage_flags: ash::vk::ShaderStageFlags::FRAGMENT,
             ..Default::default()
         },
         ash::vk::DescriptorSetLayoutBinding {
             binding: 8,
             descriptor_type: ash::vk::DescriptorType::SAMPLED_IMAGE,
-            descriptor_count: MAX_BINDLESS_SHADOW_IMAGES,
+            descriptor_count: limits.shadow_images,
             stage_flags: ash::vk::ShaderStageFlags::FRAGMENT,
             ..Default::default()
         },
         ash::vk::DescriptorSetLayoutBinding {
             binding: 9,
             descriptor_type: ash::vk::DescriptorType::SAMPLER,
-            descriptor_count: MAX_BINDLESS_COMPARISON_SAMPLERS,
+            descriptor_count: limits.shadow_images,
             stage_flags: ash::vk::ShaderStageFlags::FRAGMENT,
             ..Default::default()
         },
@@ -881,7 +1251,83 @@ fn create_descriptor_set_layout(
                 | ash::vk::ShaderStageFlags::CLOSEST_HIT_KHR,
             ..Default::default()
         },
-    ];
+        ash::vk::DescriptorSetLayoutBinding {
+            binding: 14,
+            descriptor_type: ash::vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: limits.texture_arrays,
+            stage_flags: ash::vk::ShaderStageFlags::VERTEX
+                | ash::vk::ShaderStageFlags::FRAGMENT
+                | ash::vk::ShaderStageFlags::COMPUTE,
+            ..Default::default()
+        },
+        ash::vk::DescriptorSetLayoutBinding {
+            binding: 15,
+            descriptor_type: ash::vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: limits.cube_arrays,
+            stage_flags: ash::vk::ShaderStageFlags::VERTEX
+                | ash::vk::ShaderStageFlags::FRAGMENT
+                | ash::vk::ShaderStageFlags::COMPUTE,
+            ..Default::default()
+        },
+        ash::vk::DescriptorSetLayoutBinding {
+            binding: 16,
+            descriptor_type: ash::vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: limits.volumes,
+            stage_flags: ash::vk::ShaderStageFlags::VERTEX
+                | ash::vk::ShaderStageFlags::FRAGMENT
+                | ash::vk::ShaderStageFlags::COMPUTE,
+            ..Default::default()
+        },
+        ash::vk::DescriptorSetLayoutBinding {
+            binding: 17,
+            descriptor_type: ash::vk::DescriptorType::STORAGE_BUFFER,
+            descriptor_count: 1,
+            stage_flags: ash::vk::ShaderStageFlags::FRAGMENT | ash::vk::ShaderStageFlags::COMPUTE,
+            ..Default::default()
+        },
+        ash::vk::DescriptorSetLayoutBinding {
+            binding: 18,
+            descriptor_type: ash::vk::DescriptorType::STORAGE_BUFFER,
+            descriptor_count: 1,
+            stage_flags: ash::vk::ShaderStageFlags::FRAGMENT | ash::vk::ShaderStageFlags::COMPUTE,
+            ..Default::default()
+        },
+        ash::vk::DescriptorSetLayoutBinding {
+            binding: 19,
+            descriptor_type: ash::vk::DescriptorType::UNIFORM_BUFFER,
+            descriptor_count: 1,
+            stage_flags: ash::vk::ShaderStageFlags::FRAGMENT,
+            ..Default::default()
+        },
+        ash::vk::DescriptorSetLayoutBinding {
+            binding: 20,
+            descriptor_type: ash::vk::DescriptorType::UNIFORM_BUFFER,
+            descriptor_count: 1,
+            stage_flags: ash::vk::ShaderStageFlags::VERTEX | ash::vk::ShaderStageFlags::FRAGMENT,
+            ..Default::default()
+        },
+        ash::vk::DescriptorSetLayoutBinding {
+            binding: PREV_MODEL_BINDING,
+            descriptor_type: ash::vk::DescriptorType::STORAGE_BUFFER,
+            descriptor_count: 1,
+            stage_flags: ash::vk::ShaderStageFlags::VERTEX,
+            ..Default::default()
+        },
+        ash::vk::DescriptorSetLayoutBinding {
+            binding: 22,
+            descriptor_type: ash::vk::DescriptorType::SAMPLED_IMAGE,
+            descriptor_count: limits.shadow_images,
+            stage_flags: ash::vk::ShaderStageFlags::FRAGMENT,
+            ..Default::default()
+        },
+    ]
+}
+
+fn create_descriptor_set_layout(
+    device: &LogicalDevice,
+    limits: &BindlessLimits,
+) -> Result<ash::vk::DescriptorSetLayout, GpuError> {
+    let bindings = bindless_layout_bindings(limits);
 
     let binding_flags = ash::vk::DescriptorBindingFlags::PARTIALLY_BOUND
         | ash::vk::DescriptorBindingFlags::UPDATE_AFTER_BIND;
@@ -911,28 +1357,37 @@ fn create_descriptor_set_layout(
     })
 }
 
-fn create_descriptor_pool(device: &LogicalDevice) -> Result<ash::vk::DescriptorPool, GpuError> {
-    // 6 UBOs per set * FRAMES_IN_FLIGHT
+fn create_descriptor_pool(
+    device: &LogicalDevice,
+    limits: &BindlessLimits,
+) -> Result<ash::vk::DescriptorPool, GpuError> {
+    // 8 UBOs per set * FRAMES_IN_FLIGHT
     let uniform_pool_info = ash::vk::DescriptorPoolSize {
         ty: ash::vk::DescriptorType::UNIFORM_BUFFER,
-        descriptor_count: 6 * FRAMES_IN_FLIGHT,
+        descriptor_count: 8 * FRAMES_IN_FLIGHT,
     };
-    // 1024 + 4 CIS per set * FRAMES_IN_FLIGHT
+    // textures + cubemaps + binding 12 + arrays and volumes CIS per set * FRAMES_IN_FLIGHT
     let cis_pool_info = ash::vk::DescriptorPoolSize {
         ty: ash::vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-        descriptor_count: (MAX_BINDLESS_TEXTURES + MAX_BINDLESS_CUBEMAPS + 1) * FRAMES_IN_FLIGHT,
+        descriptor_count: (limits.textures
+            + limits.cubemaps
+            + 1
+            + limits.texture_arrays
+            + limits.cube_arrays
+            + limits.volumes)
+            * FRAMES_IN_FLIGHT,
     };
     let sampled_image_pool_info = ash::vk::DescriptorPoolSize {
         ty: ash::vk::DescriptorType::SAMPLED_IMAGE,
-        descriptor_count: MAX_BINDLESS_SHADOW_IMAGES * FRAMES_IN_FLIGHT,
+        descriptor_count: 2 * limits.shadow_images * FRAMES_IN_FLIGHT, // bindings 8, 22
     };
     let sampler_pool_info = ash::vk::DescriptorPoolSize {
         ty: ash::vk::DescriptorType::SAMPLER,
-        descriptor_count: MAX_BINDLESS_COMPARISON_SAMPLERS * FRAMES_IN_FLIGHT,
+        descriptor_count: limits.shadow_images * FRAMES_IN_FLIGHT,
     };
     let storage_pool_infos = ash::vk::DescriptorPoolSize {
         ty: ash::vk::DescriptorType::STORAGE_BUFFER,
-        descriptor_count: 3 * FRAMES_IN_FLIGHT, // bindings 10, 11, 13
+        descriptor_count: 6 * FRAMES_IN_FLIGHT, // bindings 10, 11, 13, 17, 18, 21
     };
     let pool_sizes = [
         uniform_pool_info,
@@ -1333,6 +1788,10 @@ fn create_swapchain_and_depth_buffer(
 
     let engine_fmt: TextureFormat = swap_format.format.format.try_into()?;
 
+    // TRANSFER_SRC allows screenshots to read the swapchain image back
+    let image_usage = ash::vk::ImageUsageFlags::COLOR_ATTACHMENT
+        | (surface_capababilities.supported_usage_flags & ash::vk::ImageUsageFlags::TRANSFER_SRC);
+
     let create_info = ash::vk::SwapchainCreateInfoKHR {
         surface,
         min_image_count: swap_image_count,
@@ -1340,7 +1799,7 @@ fn create_swapchain_and_depth_buffer(
         image_color_space: swap_format.color_space,
         image_extent: swap_extent,
         image_array_layers: 1,
-        image_usage: ash::vk::ImageUsageFlags::COLOR_ATTACHMENT,
+        image_usage,
         image_sharing_mode: ash::vk::SharingMode::EXCLUSIVE,
         pre_transform: surface_capababilities.current_transform,
         composite_alpha: ash::vk::CompositeAlphaFlagsKHR::OPAQUE,
@@ -1400,71 +1859,161 @@ fn create_swapchain_and_depth_buffer(
         .map(|(img, view)| VulkanTexture {
             image: img,
             image_view: view,
-            mem: ash::vk::DeviceMemory::null(),
             sampler,
             width: swap_extent.width,
             height: swap_extent.height,
             format: engine_fmt,
             aspect: ash::vk::ImageAspectFlags::COLOR,
             mip_levels: 1,
+            array_layers: 1,
             view_type: ash::vk::ImageViewType::TYPE_2D,
             compare_enabled: false,
             id: 0,
             descriptor_index: u32::MAX,
             device_handle: logical_device.device.clone(),
             current_layout: Rc::new(Cell::new(ash::vk::ImageLayout::UNDEFINED)),
-            vullkan_handle_tracker: vk_handle_tracker.clone(),
+            owner: None,
         })
         .collect::<Vec<_>>();
     println!("Swapchain textures created successfully");
 
-    // depth targets
-    let depth_target_desc = RenderTargetDesc {
-        width: swap_extent.width,
-        height: swap_extent.height,
-        format: TextureFormat::Depth32Float,
+    let depth_targets = create_depth_targets(
+        instance,
+        physical_device,
+        logical_device,
+        swap_extent,
+        vk_handle_tracker,
+    )?;
+    println!("Depth targets created successfully");
+
+    Ok((
+        Swapchain {
+            fn_ptr: swapchain_khr,
+            swapchain,
+            swapchain_images,
+            swapchain_extent: swap_extent,
+            surface_format: swap_format,
+            surface,
+            sync_mode,
+            offscreen: false,
+        },
+        depth_targets,
+    ))
+}
+
+/// Headless counterpart of [`create_swapchain_and_depth_buffer`]: one offscreen
+/// BGRA8 sRGB color target per frame in flight stands in for the swapchain images.
+fn create_offscreen_backbuffer_and_depth_buffer(
+    instance: &ash::Instance,
+    extent: (u32, u32),
+    physical_device: ash::vk::PhysicalDevice,
+    logical_device: &LogicalDevice,
+    sync_mode: SyncMode,
+    vk_handle_tracker: VulkanHandleTracker,
+) -> Result<(Swapchain, [VulkanTexture; FRAMES_IN_FLIGHT as usize]), GpuError> {
+    let (width, height) = extent;
+    if width == 0 || height == 0 {
+        return Err(GpuError::new(
+            format!("Invalid offscreen backbuffer size {width}x{height}"),
+            GpuErrorKind::ResourceCreation,
+        ));
+    }
+    let swap_extent = ash::vk::Extent2D { width, height };
+
+    let backbuffer_desc = RenderTargetDesc {
+        width,
+        height,
+        format: TextureFormat::Bgra8UnormSrgb,
         sampler: SamplerDesc::default(),
-        usage: RenderTargetUsage::Depth,
+        usage: RenderTargetUsage::Color,
+        sample_count: 1,
+        persistent: false,
+        layers: 1,
     };
-    let depth_targets = (0..FRAMES_IN_FLIGHT)
+    let swapchain_images = (0..FRAMES_IN_FLIGHT)
         .map(|_| {
             VulkanBackend::create_vk_render_target(
                 instance,
                 logical_device,
                 physical_device,
-                &depth_target_desc,
+                &backbuffer_desc,
                 vk_handle_tracker.clone(),
             )
         })
         .collect::<Result<Vec<_>, GpuError>>()?;
-    let depth_targets: [VulkanTexture; FRAMES_IN_FLIGHT as usize] =
-        depth_targets.try_into().map_err(|_| {
-            GpuError::new(
-                "Unable to create expected amount of depth targets",
-                GpuErrorKind::ResourceCreation,
-            )
-        })?;
+    println!("Offscreen backbuffers created successfully ({width}x{height})");
+
+    let depth_targets = create_depth_targets(
+        instance,
+        physical_device,
+        logical_device,
+        swap_extent,
+        vk_handle_tracker,
+    )?;
     println!("Depth targets created successfully");
 
     Ok((
         Swapchain {
-            fn_ptr: swapchain_khr,
-            swapchain,
+            fn_ptr: ash::khr::swapchain::Device::new(instance, logical_device),
+            swapchain: ash::vk::SwapchainKHR::null(),
             swapchain_images,
             swapchain_extent: swap_extent,
-            surface_format: swap_format,
-            surface,
+            surface_format: SurfaceFormat {
+                format: ash::vk::SurfaceFormatKHR {
+                    format: ash::vk::Format::B8G8R8A8_SRGB,
+                    color_space: ash::vk::ColorSpaceKHR::SRGB_NONLINEAR,
+                },
+                is_hdr: false,
+            },
+            surface: ash::vk::SurfaceKHR::null(),
             sync_mode,
+            offscreen: true,
         },
         depth_targets,
     ))
 }
 
+fn create_depth_targets(
+    instance: &ash::Instance,
+    physical_device: ash::vk::PhysicalDevice,
+    logical_device: &LogicalDevice,
+    extent: ash::vk::Extent2D,
+    vk_handle_tracker: VulkanHandleTracker,
+) -> Result<[VulkanTexture; FRAMES_IN_FLIGHT as usize], GpuError> {
+    let depth_target_desc = RenderTargetDesc {
+        width: extent.width,
+        height: extent.height,
+        format: TextureFormat::Depth32Float,
+        sampler: SamplerDesc::default(),
+        usage: RenderTargetUsage::Depth,
+        sample_count: 1,
+        persistent: false,
+        layers: 1,
+    };
+    let depth_targets = (0..FRAMES_IN_FLIGHT)
+        .map(|_| {
+            VulkanBackend::create_vk_render_target(
+                instance,
+                logical_device,
+                physical_device,
+                &depth_target_desc,
+                vk_handle_tracker.clone(),
+            )
+        })
+        .collect::<Result<Vec<_>, GpuError>>()?;
+    depth_targets.try_into().map_err(|_| {
+        GpuError::new(
+            "Unable to create expected amount of depth targets",
+            GpuErrorKind::ResourceCreation,
+        )
+    })
+}
+
 fn create_logical_device(
     context: &ash::Entry,
     instance: &Instance,
     physical_device: ash::vk::PhysicalDevice,
-    surface: ash::vk::SurfaceKHR,
+    surface: Option<ash::vk::SurfaceKHR>,
     with_rt: bool,
 ) -> Result<LogicalDevice, GpuError> {
     let queue_fam_props =
@@ -1474,6 +2023,11 @@ fn create_logical_device(
 
     let Some((idx, _)) = queue_fam_props.iter().enumerate().find(|(idx, q)| {
         let idx = *idx as u32;
+        // headless: nothing to present to
+        let Some(surface) = surface else {
+            return q.queue_flags.contains(ash::vk::QueueFlags::GRAPHICS)
+                && q.queue_flags.contains(ash::vk::QueueFlags::COMPUTE);
+        };
         let surface_support = match unsafe {
             khr_instance.get_physical_device_surface_support(physical_device, idx, surface)
         } {
@@ -1496,13 +2050,37 @@ fn create_logical_device(
         ));
     };
 
+    // optional: a transfer-only family (DMA engine) for asynchronous uploads,
+    // else a non-graphics family with transfer support
+    let transfer_queue_index = [
+        ash::vk::QueueFlags::GRAPHICS | ash::vk::QueueFlags::COMPUTE,
+        ash::vk::QueueFlags::GRAPHICS,
+    ]
+    .iter()
+    .find_map(|excluded| {
+        queue_fam_props.iter().position(|q| {
+            q.queue_flags.contains(ash::vk::QueueFlags::TRANSFER)
+                && !q.queue_flags.intersects(*excluded)
+                && q.queue_count > 0
+        })
+    })
+    .map(|idx| idx as u32);
+
     let prio = 0.5f32;
-    let queue_info = ash::vk::DeviceQueueCreateInfo {
+    let mut queue_infos = vec![ash::vk::DeviceQueueCreateInfo {
         queue_family_index: idx as u32,
         p_queue_priorities: &prio,
         queue_count: 1,
         ..Default::default()
-    };
+    }];
+    if let Some(transfer_idx) = transfer_queue_index {
+        queue_infos.push(ash::vk::DeviceQueueCreateInfo {
+            queue_family_index: transfer_idx,
+            p_queue_priorities: &prio,
+            queue_count: 1,
+            ..Default::default()
+        });
+    }
 
     let mut accel_features = ash::vk::PhysicalDeviceAccelerationStructureFeaturesKHR {
         acceleration_structure: ash::vk::TRUE,
@@ -1516,6 +2094,7 @@ fn create_logical_device(
 
     let mut vk_12_feats = ash::vk::PhysicalDeviceVulkan12Features {
         buffer_device_address: ash::vk::TRUE,
+        timeline_semaphore: ash::vk::TRUE,
         ..Default::default()
     };
 
@@ -1551,9 +2130,14 @@ fn create_logical_device(
         p_next: &mut indexing_feats as *mut _ as *mut std::ffi::c_void,
         ..Default::default()
     };
+    let supported_features = unsafe { instance.get_physical_device_features(physical_device) };
     let mut base_struct = ash::vk::PhysicalDeviceFeatures2 {
         features: ash::vk::PhysicalDeviceFeatures {
             sampler_anisotropy: ash::vk::TRUE,
+            // optional: per-pass pipeline statistics in the GPU profiler
+            pipeline_statistics_query: supported_features.pipeline_statistics_query,
+            // optional: cube array textures
+            image_cube_array: supported_features.image_cube_array,
             shader_int16: ash::vk::TRUE,
             shader_sampled_image_array_dynamic_indexing: ash::vk::TRUE,
             shader_uniform_buffer_array_dynamic_indexing: ash::vk::TRUE,
@@ -1562,17 +2146,28 @@ fn create_logical_device(
         p_next: &mut shader_float16_feats as *mut _ as *mut std::ffi::c_void,
         ..Default::default()
     };
+    let required_exts = required_device_extensions(surface.is_none());
     let required_exts = if with_rt {
-        REQUIRED_EXTS.iter().chain(RT_EXTS.iter())
+        required_exts.iter().chain(RT_EXTS.iter())
     } else {
-        REQUIRED_EXTS.iter().chain([].iter())
+        required_exts.iter().chain([].iter())
     };
-    let device_exts_ptr = required_exts.map(|ext| ext.as_ptr()).collect::<Vec<_>>();
+    let mut device_exts_ptr = required_exts.map(|ext| ext.as_ptr()).collect::<Vec<_>>();
+
+    // optional: heap budgets for the memory allocator
+    let memory_budget_supported =
+        unsafe { instance.enumerate_device_extension_properties(physical_device) }
+            .unwrap_or_default()
+            .iter()
+            .any(|ext| ext.extension_name_as_c_str() == Ok(ash::vk::EXT_MEMORY_BUDGET_NAME));
+    if memory_budget_supported {
+        device_exts_ptr.push(ash::vk::EXT_MEMORY_BUDGET_NAME.as_ptr());
+    }
 
     let create_info = ash::vk::DeviceCreateInfo {
         p_next: &mut base_struct as *mut _ as *mut std::ffi::c_void,
-        queue_create_info_count: 1,
-        p_queue_create_infos: &queue_info,
+        queue_create_info_count: queue_infos.len() as u32,
+        p_queue_create_infos: queue_infos.as_ptr(),
         enabled_extension_count: device_exts_ptr.len() as u32,
         pp_enabled_extension_names: device_exts_ptr.as_ptr(),
         ..Default::default()
@@ -1595,14 +2190,29 @@ fn create_logical_device(
     Ok(LogicalDevice {
         device,
         main_queue_index: idx as u32,
+        transfer_queue_index,
         debug_utils_ext,
         rt_supported: with_rt,
+        pipeline_statistics_supported: supported_features.pipeline_statistics_query
+            == ash::vk::TRUE,
+        memory_budget_supported,
     })
 }
 
+/// Device extensions we can't run without. Headless devices don't need `VK_KHR_swapchain`.
+fn required_device_extensions(headless: bool) -> Vec<&'static CStr> {
+    REQUIRED_EXTS
+        .iter()
+        .copied()
+        .filter(|ext| !headless || *ext != ash::vk::KHR_SWAPCHAIN_NAME)
+        .collect()
+}
+
 fn get_physical_device(
     instance: &ash::Instance,
+    headless: bool,
 ) -> Result<(ash::vk::PhysicalDevice, Option<rt::RtDeviceProperties>), GpuError> {
+    let required_exts = required_device_extensions(headless);
     let devices = unsafe { instance.enumerate_physical_devices() }.map_err(|e| {
         GpuError::new(
             format!("Failed to enumerate physical devices: {:?}", e),
@@ -1663,7 +2273,7 @@ fn get_physical_device(
                     .unwrap_or_default()
             };
 
-            if let Some(ext) = REQUIRED_EXTS.iter().find(|ext| {
+            if let Some(ext) = required_exts.iter().find(|ext| {
                 !device_exts.iter().any(|prop| {
                     let extension_name = unsafe { CStr::from_ptr(prop.extension_name.as_ptr()) };
                     extension_name == **ext
@@ -1810,7 +2420,7 @@ fn get_physical_device(
 
 fn create_instance(
     context: &ash::Entry,
-    hwnd: RawWindowHandle,
+    hwnd: Option<RawWindowHandle>,
     enable_validation: bool,
 ) -> Result<Instance, GpuError> {
     let app_name = "Sparkle VK";
@@ -1831,7 +2441,10 @@ fn create_instance(
         ..Default::default()
     };
 
-    let mut instance_exts = util::get_instance_extensions(hwnd)?;
+    let mut instance_exts = match hwnd {
+        Some(hwnd) => util::get_instance_extensions(hwnd)?,
+        None => Vec::new(),
+    };
 
     let extension_properties = unsafe { context.enumerate_instance_extension_properties(None) }
         .map_err(|_| {
@@ -1942,3 +2555,60 @@ fn setup_debug_messenger(
             .map_err(|_| GpuError::new("Failed to create debug messenger", GpuErrorKind::Other))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_arrays_scale_with_device_limits() {
+        let small = BindlessLimits::from_device_max(256, 1024);
+        assert_eq!(small.cubemaps, BindlessLimits::default().cubemaps);
+        assert_eq!(small.shadow_images, BindlessLimits::default().shadow_images);
+        assert_eq!(
+            small.textures,
+            256 - 4 - 12 - 64 - 8 - 16 - RESERVED_SAMPLED_IMAGES
+        );
+
+        let medium = BindlessLimits::from_device_max(2048, 1024);
+        assert_eq!((medium.cubemaps, medium.shadow_images), (32, 32));
+        assert_eq!(medium.textures, 1024);
+
+        let large = BindlessLimits::from_device_max(500_000, 1024);
+        assert_eq!((large.cubemaps, large.shadow_images), (64, 64));
+        assert_eq!(large.textures, 1024);
+    }
+
+    #[test]
+    fn allocating_past_the_limit_fails() {
+        let mut registry = TextureRegistry::new(BindlessLimits {
+            textures: 2,
+            ..Default::default()
+        });
+        registry.allocate_2d().unwrap();
+        registry.allocate_2d().unwrap();
+        let error = registry.allocate_2d().unwrap_err();
+        assert_eq!(error.kind, GpuErrorKind::ResourceCreation);
+
+        registry.release(BindlessSlot::Texture2d(0));
+        assert_eq!(registry.allocate_2d().unwrap(), 0);
+    }
+
+    #[test]
+    fn released_registry_slots_are_reused() {
+        let mut registry = TextureRegistry::new(BindlessLimits::default());
+        let slots: Vec<_> = (0..3).map(|_| registry.allocate_2d().unwrap()).collect();
+        assert_eq!(slots, [0, 1, 2]);
+
+        registry.release(BindlessSlot::Texture2d(1));
+        assert_eq!(registry.allocate_2d().unwrap(), 1);
+        assert_eq!(registry.allocate_2d().unwrap(), 3);
+
+        // each descriptor array has its own slots
+        registry.release(BindlessSlot::Texture2d(0));
+        assert_eq!(registry.allocate_cube().unwrap(), 0);
+        registry.release(BindlessSlot::Cube(0));
+        assert_eq!(registry.allocate_cube().unwrap(), 0);
+        assert_eq!(registry.allocate_2d().unwrap(), 0);
+    }
+}
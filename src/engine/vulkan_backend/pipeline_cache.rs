@@ -0,0 +1,224 @@
+//! Persistent `VkPipelineCache`.
+//!
+//! The cache lives in a file next to the executable. It is only reused if it
+//! was written for the same device (vendor id, device id, pipeline cache UUID)
+//! and the same SPIR-V: `build.rs` hashes `src/shaders/spv` into
+//! `SPARKLE_SPIRV_HASH`, so recompiled shaders start from an empty cache.
+
+use std::path::PathBuf;
+
+use crate::engine::backend::{GpuError, GpuErrorKind};
+
+const PIPELINE_CACHE_FILE: &str = "pipeline_cache.bin";
+const MAGIC: &[u8; 8] = b"SPKLPSO1";
+const SPIRV_HASH: &str = env!("SPARKLE_SPIRV_HASH");
+
+/// Our header: magic + SPIR-V hash.
+const FILE_HEADER_SIZE: usize = 16;
+/// `VkPipelineCacheHeaderVersionOne`
+const VK_HEADER_SIZE: usize = 32;
+
+pub(super) struct PipelineCache {
+    device: ash::Device,
+    cache: ash::vk::PipelineCache,
+    path: Option<PathBuf>,
+}
+
+impl PipelineCache {
+    /// Create the pipeline cache, seeded from disk if a compatible file exists.
+    pub(super) fn load(
+        device: &ash::Device,
+        properties: &ash::vk::PhysicalDeviceProperties,
+    ) -> Result<PipelineCache, GpuError> {
+        let path = cache_path();
+        let initial_data = path
+            .as_ref()
+            .and_then(|p| std::fs::read(p).ok())
+            .and_then(|data| validate(&data, properties).map(<[u8]>::to_vec))
+            .unwrap_or_default();
+
+        let create_info = ash::vk::PipelineCacheCreateInfo {
+            initial_data_size: initial_data.len(),
+            p_initial_data: initial_data.as_ptr() as *const std::ffi::c_void,
+            ..Default::default()
+        };
+        let cache = match unsafe { device.create_pipeline_cache(&create_info, None) } {
+            Ok(cache) => cache,
+            // the driver rejected the data despite a matching header: start empty
+            Err(_) if !initial_data.is_empty() => unsafe {
+                device.create_pipeline_cache(&ash::vk::PipelineCacheCreateInfo::default(), None)
+            }
+            .map_err(|e| {
+                GpuError::new(
+                    format!("Failed to create pipeline cache: {e:?}"),
+                    GpuErrorKind::ResourceCreation,
+                )
+            })?,
+            Err(e) => {
+                return Err(GpuError::new(
+                    format!("Failed to create pipeline cache: {e:?}"),
+                    GpuErrorKind::ResourceCreation,
+                ));
+            }
+        };
+
+        if initial_data.is_empty() {
+            println!("Pipeline cache: starting empty");
+        } else {
+            println!("Pipeline cache: loaded {} bytes", initial_data.len());
+        }
+
+        Ok(PipelineCache {
+            device: device.clone(),
+            cache,
+            path,
+        })
+    }
+
+    pub(super) fn handle(&self) -> ash::vk::PipelineCache {
+        self.cache
+    }
+
+    /// Write the current cache contents to disk. Failures are reported, not fatal.
+    pub(super) fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let data = match unsafe { self.device.get_pipeline_cache_data(self.cache) } {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("Failed to read pipeline cache data: {e:?}");
+                return;
+            }
+        };
+        let mut file = Vec::with_capacity(FILE_HEADER_SIZE + data.len());
+        file.extend_from_slice(MAGIC);
+        file.extend_from_slice(&spirv_hash().to_le_bytes());
+        file.extend_from_slice(&data);
+        if let Err(e) = std::fs::write(path, file) {
+            eprintln!("Failed to write pipeline cache {}: {e}", path.display());
+        }
+    }
+
+    pub(super) fn destroy(&mut self) {
+        unsafe { self.device.destroy_pipeline_cache(self.cache, None) };
+        self.cache = ash::vk::PipelineCache::null();
+    }
+}
+
+fn cache_path() -> Option<PathBuf> {
+    let mut path = std::env::current_exe().ok()?.parent()?.to_path_buf();
+    path.push(PIPELINE_CACHE_FILE);
+    Some(path)
+}
+
+fn spirv_hash() -> u64 {
+    u64::from_str_radix(SPIRV_HASH, 16).unwrap_or(0)
+}
+
+/// Return the Vulkan cache data if the file matches this build and device.
+fn validate<'a>(
+    data: &'a [u8],
+    properties: &ash::vk::PhysicalDeviceProperties,
+) -> Option<&'a [u8]> {
+    if data.len() < FILE_HEADER_SIZE + VK_HEADER_SIZE || &data[..8] != MAGIC {
+        return None;
+    }
+    let read_u32 = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+    let hash = u64::from_le_bytes(data[8..16].try_into().unwrap());
+    if hash != spirv_hash() {
+        println!("Pipeline cache: shaders changed, discarding");
+        return None;
+    }
+
+    let vk = FILE_HEADER_SIZE;
+    let header_size = read_u32(vk) as usize;
+    let header_version = read_u32(vk + 4);
+    let vendor_id = read_u32(vk + 8);
+    let device_id = read_u32(vk + 12);
+    let uuid = &data[vk + 16..vk + 32];
+    if header_size < VK_HEADER_SIZE
+        || header_version != ash::vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32
+        || vendor_id != properties.vendor_id
+        || device_id != properties.device_id
+        || uuid != properties.pipeline_cache_uuid
+    {
+        println!("Pipeline cache: written for a different device or driver, discarding");
+        return None;
+    }
+
+    Some(&data[vk..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties() -> ash::vk::PhysicalDeviceProperties {
+        ash::vk::PhysicalDeviceProperties {
+            vendor_id: 0x10de,
+            device_id: 0x2684,
+            pipeline_cache_uuid: [7; ash::vk::UUID_SIZE],
+            ..Default::default()
+        }
+    }
+
+    /// A cache file as `save` writes it, with a Vulkan header for `properties`
+    /// followed by some driver data.
+    fn cache_file(properties: &ash::vk::PhysicalDeviceProperties, hash: u64) -> Vec<u8> {
+        let mut file = MAGIC.to_vec();
+        file.extend_from_slice(&hash.to_le_bytes());
+        file.extend_from_slice(&(VK_HEADER_SIZE as u32).to_le_bytes());
+        file.extend_from_slice(
+            &(ash::vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32).to_le_bytes(),
+        );
+        file.extend_from_slice(&properties.vendor_id.to_le_bytes());
+        file.extend_from_slice(&properties.device_id.to_le_bytes());
+        file.extend_from_slice(&properties.pipeline_cache_uuid);
+        file.extend_from_slice(&[1, 2, 3, 4]);
+        file
+    }
+
+    #[test]
+    fn matching_cache_is_reused_without_our_header() {
+        let file = cache_file(&properties(), spirv_hash());
+        assert_eq!(
+            validate(&file, &properties()),
+            Some(&file[FILE_HEADER_SIZE..])
+        );
+    }
+
+    #[test]
+    fn foreign_or_truncated_files_are_discarded() {
+        let mut wrong_magic = cache_file(&properties(), spirv_hash());
+        wrong_magic[..8].copy_from_slice(b"SPKLPSO0");
+        assert_eq!(validate(&wrong_magic, &properties()), None);
+
+        let file = cache_file(&properties(), spirv_hash());
+        let short = &file[..FILE_HEADER_SIZE + VK_HEADER_SIZE - 1];
+        assert_eq!(validate(short, &properties()), None);
+        assert_eq!(validate(&file[..4], &properties()), None);
+    }
+
+    #[test]
+    fn cache_of_other_shaders_is_discarded() {
+        let file = cache_file(&properties(), spirv_hash().wrapping_add(1));
+        assert_eq!(validate(&file, &properties()), None);
+    }
+
+    #[test]
+    fn cache_of_other_device_is_discarded() {
+        let file = cache_file(&properties(), spirv_hash());
+        let other_vendor = ash::vk::PhysicalDeviceProperties {
+            vendor_id: 0x1002,
+            ..properties()
+        };
+        assert_eq!(validate(&file, &other_vendor), None);
+
+        let other_driver = ash::vk::PhysicalDeviceProperties {
+            pipeline_cache_uuid: [8; ash::vk::UUID_SIZE],
+            ..properties()
+        };
+        assert_eq!(validate(&file, &other_driver), None);
+    }
+}
@@ -1,6 +1,6 @@
 use crate::engine::{
     backend::{AccelerationStructureType, GpuAccelerationStructure, GpuError, GpuErrorKind},
-    vulkan_backend::{VulkanBackend, buffer::VulkanBuffer},
+    vulkan_backend::{VulkanBackend, buffer::VulkanBuffer, destruction_queue::PendingDestroy},
 };
 
 pub const IDX_RAYGEN: u32 = 0;
@@ -47,6 +47,15 @@ pub struct AccelerationStructure {
     pub(super) index_device_address: u64,
 }
 
+impl Drop for AccelerationStructure {
+    fn drop(&mut self) {
+        // queued before the backing buffer, which is dropped right after
+        self.buffer
+            .vulkan_handle_tracker
+            .defer_destroy(PendingDestroy::AccelerationStructure(self.handle));
+    }
+}
+
 impl GpuAccelerationStructure for AccelerationStructure {
     fn vertex_device_address(&self) -> u64 {
         self.vertex_device_address
@@ -66,10 +75,9 @@ impl Into<ash::vk::AccelerationStructureTypeKHR> for AccelerationStructureType {
     }
 }
 
-pub fn create_rt_descriptor_layout(
-    device: &ash::Device,
-) -> Result<ash::vk::DescriptorSetLayout, GpuError> {
-    let bindings = [
+/// Bindings of the RT push descriptor set (set 1).
+pub fn rt_layout_bindings() -> [ash::vk::DescriptorSetLayoutBinding<'static>; 4] {
+    [
         ash::vk::DescriptorSetLayoutBinding {
             binding: 0,
             descriptor_type: ash::vk::DescriptorType::ACCELERATION_STRUCTURE_KHR,
@@ -100,7 +108,13 @@ pub fn create_rt_descriptor_layout(
             stage_flags: ash::vk::ShaderStageFlags::ANY_HIT_KHR,
             ..Default::default()
         },
-    ];
+    ]
+}
+
+pub fn create_rt_descriptor_layout(
+    device: &ash::Device,
+) -> Result<ash::vk::DescriptorSetLayout, GpuError> {
+    let bindings = rt_layout_bindings();
 
     let create_info = ash::vk::DescriptorSetLayoutCreateInfo {
         flags: ash::vk::DescriptorSetLayoutCreateFlags::PUSH_DESCRIPTOR_KHR,
@@ -117,6 +131,17 @@ pub fn create_rt_descriptor_layout(
     })
 }
 
+pub fn rt_push_constant_range() -> ash::vk::PushConstantRange {
+    ash::vk::PushConstantRange {
+        stage_flags: ash::vk::ShaderStageFlags::RAYGEN_KHR
+            | ash::vk::ShaderStageFlags::MISS_KHR
+            | ash::vk::ShaderStageFlags::CLOSEST_HIT_KHR
+            | ash::vk::ShaderStageFlags::ANY_HIT_KHR,
+        offset: 0,
+        size: 16, // frame_index: u32, width: u32, height: u32, number_of_lights: u32
+    }
+}
+
 pub fn create_pipeline_layout(
     device: &ash::Device,
     set0: ash::vk::DescriptorSetLayout,
@@ -124,14 +149,7 @@ pub fn create_pipeline_layout(
 ) -> Result<ash::vk::PipelineLayout, GpuError> {
     let set_layouts = [set0, rt_set];
 
-    let push_range = ash::vk::PushConstantRange {
-        stage_flags: ash::vk::ShaderStageFlags::RAYGEN_KHR
-            | ash::vk::ShaderStageFlags::MISS_KHR
-            | ash::vk::ShaderStageFlags::CLOSEST_HIT_KHR
-            | ash::vk::ShaderStageFlags::ANY_HIT_KHR,
-        offset: 0,
-        size: 16, // frame_index: u32, width: u32, height: u32, number_of_lights: u32
-    };
+    let push_range = rt_push_constant_range();
 
     let create_info = ash::vk::PipelineLayoutCreateInfo {
         set_layout_count: set_layouts.len() as u32,
@@ -183,31 +201,21 @@ impl VulkanBackend {
             )
         })?;
 
-        let (sbt_buf, sbt_mem) = VulkanBackend::create_buffer(
-            &self.instance,
+        let (sbt_buf, sbt_allocation) = VulkanBackend::create_buffer_aligned(
+            self.vulkan_handle_tracker.allocator(),
             &self.device,
-            self.phys_device,
             total_size,
             ash::vk::BufferUsageFlags::SHADER_BINDING_TABLE_KHR
                 | ash::vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS
                 | ash::vk::BufferUsageFlags::TRANSFER_DST,
             ash::vk::MemoryPropertyFlags::HOST_VISIBLE | ash::vk::MemoryPropertyFlags::HOST_COHERENT,
+            base_alignment,
         )?;
 
         self.vulkan_handle_tracker.register_buffer(sbt_buf);
-        self.vulkan_handle_tracker.register_device_memory(sbt_mem);
 
-        // map and write each handle at its region offset
-        let mapped = unsafe {
-            self.device
-                .map_memory(sbt_mem, 0, total_size, ash::vk::MemoryMapFlags::empty())
-        }
-        .map_err(|e| {
-            GpuError::new(
-                format!("Failed to map SBT memory: {e:?}"),
-                GpuErrorKind::ResourceUpdate,
-            )
-        })? as *mut u8;
+        // write each handle at its region offset
+        let mapped = sbt_allocation.mapped as *mut u8;
 
         unsafe {
             let hs = handle_size as usize;
@@ -257,7 +265,7 @@ impl VulkanBackend {
 
         let buffer = VulkanBuffer {
             buffer: sbt_buf,
-            memory: sbt_mem,
+            allocation: sbt_allocation,
             mapped: mapped as *mut std::ffi::c_void,
             flags: ash::vk::MemoryPropertyFlags::HOST_VISIBLE
                 | ash::vk::MemoryPropertyFlags::HOST_COHERENT,
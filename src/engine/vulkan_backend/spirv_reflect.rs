@@ -0,0 +1,949 @@
+//! Minimal SPIR-V reflection.
+//!
+//! Extracts the descriptor bindings, push-constant block and vertex inputs of a
+//! shader module and checks them against the hard-coded pipeline layouts before
+//! a pipeline is created. A shader that reads a binding the layout does not
+//! declare (or declares with a different type or stage) is undefined behaviour
+//! in Vulkan; here it becomes a [`GpuError`] naming the shader and the binding.
+
+use std::collections::HashMap;
+
+use crate::engine::backend::{GpuError, GpuErrorKind};
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+const HEADER_WORDS: usize = 5;
+
+// Opcodes
+const OP_NAME: u32 = 5;
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_BOOL: u32 = 20;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+const OP_TYPE_ACCELERATION_STRUCTURE: u32 = 5341;
+
+// Decorations
+const DECORATION_BLOCK: u32 = 2;
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BUILT_IN: u32 = 11;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+// Storage classes
+const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_INPUT: u32 = 1;
+const STORAGE_UNIFORM: u32 = 2;
+const STORAGE_PUSH_CONSTANT: u32 = 9;
+const STORAGE_STORAGE_BUFFER: u32 = 12;
+
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+/// Descriptor kind as seen by the shader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ResourceKind {
+    UniformBuffer,
+    StorageBuffer,
+    CombinedImageSampler,
+    SampledImage,
+    StorageImage,
+    Sampler,
+    UniformTexelBuffer,
+    StorageTexelBuffer,
+    InputAttachment,
+    AccelerationStructure,
+}
+
+impl ResourceKind {
+    /// Whether a descriptor of type `ty` satisfies this shader resource.
+    fn accepts(self, ty: ash::vk::DescriptorType) -> bool {
+        use ash::vk::DescriptorType as T;
+        match self {
+            ResourceKind::UniformBuffer => {
+                matches!(ty, T::UNIFORM_BUFFER | T::UNIFORM_BUFFER_DYNAMIC)
+            }
+            ResourceKind::StorageBuffer => {
+                matches!(ty, T::STORAGE_BUFFER | T::STORAGE_BUFFER_DYNAMIC)
+            }
+            ResourceKind::CombinedImageSampler => ty == T::COMBINED_IMAGE_SAMPLER,
+            // a combined image sampler can also be read through separate image/sampler variables
+            ResourceKind::SampledImage => {
+                matches!(ty, T::SAMPLED_IMAGE | T::COMBINED_IMAGE_SAMPLER)
+            }
+            ResourceKind::Sampler => matches!(ty, T::SAMPLER | T::COMBINED_IMAGE_SAMPLER),
+            ResourceKind::StorageImage => ty == T::STORAGE_IMAGE,
+            ResourceKind::UniformTexelBuffer => ty == T::UNIFORM_TEXEL_BUFFER,
+            ResourceKind::StorageTexelBuffer => ty == T::STORAGE_TEXEL_BUFFER,
+            ResourceKind::InputAttachment => ty == T::INPUT_ATTACHMENT,
+            ResourceKind::AccelerationStructure => ty == T::ACCELERATION_STRUCTURE_KHR,
+        }
+    }
+}
+
+/// Numeric type of a vertex input or vertex attribute format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum NumericType {
+    Float,
+    Sint,
+    Uint,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct ReflectedBinding {
+    pub name: String,
+    pub set: u32,
+    pub binding: u32,
+    pub kind: ResourceKind,
+    /// Array length, `None` for runtime-sized (bindless) arrays. `1` for non-arrays.
+    pub count: Option<u32>,
+    /// Array stride of the runtime array a storage buffer ends with, i.e. the
+    /// element size of a `StructuredBuffer<T>`.
+    pub stride: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct ReflectedInput {
+    pub name: String,
+    pub location: u32,
+    pub numeric: NumericType,
+}
+
+/// The resource interface of one shader entry point.
+#[derive(Debug, Clone)]
+pub(super) struct ShaderInterface {
+    pub stage: ash::vk::ShaderStageFlags,
+    pub bindings: Vec<ReflectedBinding>,
+    /// Byte range `[start, end)` of the push-constant block, if the shader has one.
+    pub push_constants: Option<(u32, u32)>,
+    /// User vertex inputs (vertex shaders only, built-ins excluded).
+    pub inputs: Vec<ReflectedInput>,
+}
+
+/// The parts of a pipeline layout a shader interface is checked against.
+pub(super) struct PipelineLayoutDesc<'a> {
+    /// Bindings of each descriptor set, indexed by set number.
+    pub sets: &'a [&'a [ash::vk::DescriptorSetLayoutBinding<'a>]],
+    pub push_constants: &'a [ash::vk::PushConstantRange],
+}
+
+#[derive(Debug, Clone)]
+enum Type {
+    Scalar { numeric: NumericType, width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Image { dim: u32, sampled: u32 },
+    Sampler,
+    SampledImage,
+    Array { element: u32, length: Option<u32> },
+    Struct { members: Vec<u32> },
+    Pointer { pointee: u32 },
+    AccelerationStructure,
+    Other,
+}
+
+#[derive(Default)]
+struct Decorations {
+    set: Option<u32>,
+    binding: Option<u32>,
+    location: Option<u32>,
+    built_in: bool,
+    block: bool,
+    buffer_block: bool,
+    array_stride: Option<u32>,
+}
+
+#[derive(Default, Clone, Copy)]
+struct MemberDecorations {
+    offset: Option<u32>,
+    matrix_stride: Option<u32>,
+}
+
+struct Variable {
+    id: u32,
+    pointer_type: u32,
+    storage_class: u32,
+}
+
+struct Module {
+    names: HashMap<u32, String>,
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, u32>,
+    decorations: HashMap<u32, Decorations>,
+    member_decorations: HashMap<(u32, u32), MemberDecorations>,
+    variables: Vec<Variable>,
+}
+
+fn parse_error(label: &str, message: &str) -> GpuError {
+    GpuError::new(
+        format!("Invalid SPIR-V in shader {label}: {message}"),
+        GpuErrorKind::ShaderCompilation,
+    )
+}
+
+/// Reflect the (single) entry point of a SPIR-V binary.
+pub(super) fn reflect(code: &[u8], label: &str) -> Result<ShaderInterface, GpuError> {
+    if !code.len().is_multiple_of(4) || code.len() < HEADER_WORDS * 4 {
+        return Err(parse_error(label, "not a SPIR-V word stream"));
+    }
+    let words = code
+        .chunks_exact(4)
+        .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+        .collect::<Vec<_>>();
+    if words[0] != SPIRV_MAGIC {
+        return Err(parse_error(label, "bad magic number"));
+    }
+    let version = words[1];
+
+    let mut module = Module {
+        names: HashMap::new(),
+        types: HashMap::new(),
+        constants: HashMap::new(),
+        decorations: HashMap::new(),
+        member_decorations: HashMap::new(),
+        variables: Vec::new(),
+    };
+    let mut entry_point: Option<(u32, Vec<u32>)> = None;
+
+    let mut i = HEADER_WORDS;
+    while i < words.len() {
+        let word_count = (words[i] >> 16) as usize;
+        let opcode = words[i] & 0xffff;
+        if word_count == 0 || i + word_count > words.len() {
+            return Err(parse_error(label, "truncated instruction"));
+        }
+        let ops = &words[i + 1..i + word_count];
+        i += word_count;
+
+        match opcode {
+            OP_NAME if !ops.is_empty() => {
+                module.names.insert(ops[0], parse_string(&ops[1..]).0);
+            }
+            OP_ENTRY_POINT if ops.len() >= 2 => {
+                if entry_point.is_some() {
+                    return Err(parse_error(label, "more than one entry point"));
+                }
+                let (_, string_words) = parse_string(&ops[2..]);
+                entry_point = Some((ops[0], ops[2 + string_words..].to_vec()));
+            }
+            OP_TYPE_BOOL if !ops.is_empty() => {
+                module.types.insert(ops[0], Type::Other);
+            }
+            OP_TYPE_INT if ops.len() >= 3 => {
+                let numeric = if ops[2] != 0 {
+                    NumericType::Sint
+                } else {
+                    NumericType::Uint
+                };
+                module.types.insert(
+                    ops[0],
+                    Type::Scalar {
+                        numeric,
+                        width: ops[1],
+                    },
+                );
+            }
+            OP_TYPE_FLOAT if ops.len() >= 2 => {
+                module.types.insert(
+                    ops[0],
+                    Type::Scalar {
+                        numeric: NumericType::Float,
+                        width: ops[1],
+                    },
+                );
+            }
+            OP_TYPE_VECTOR if ops.len() >= 3 => {
+                module.types.insert(
+                    ops[0],
+                    Type::Vector {
+                        component: ops[1],
+                        count: ops[2],
+                    },
+                );
+            }
+            OP_TYPE_MATRIX if ops.len() >= 3 => {
+                module.types.insert(
+                    ops[0],
+                    Type::Matrix {
+                        column: ops[1],
+                        count: ops[2],
+                    },
+                );
+            }
+            OP_TYPE_IMAGE if ops.len() >= 8 => {
+                module.types.insert(
+                    ops[0],
+                    Type::Image {
+                        dim: ops[2],
+                        sampled: ops[6],
+                    },
+                );
+            }
+            OP_TYPE_SAMPLER if !ops.is_empty() => {
+                module.types.insert(ops[0], Type::Sampler);
+            }
+            OP_TYPE_SAMPLED_IMAGE if !ops.is_empty() => {
+                module.types.insert(ops[0], Type::SampledImage);
+            }
+            OP_TYPE_ARRAY if ops.len() >= 3 => {
+                // the length constant is always declared before the array type;
+                // spec constant lengths are treated like runtime arrays
+                let length = module.constants.get(&ops[2]).copied();
+                module.types.insert(
+                    ops[0],
+                    Type::Array {
+                        element: ops[1],
+                        length,
+                    },
+                );
+            }
+            OP_TYPE_RUNTIME_ARRAY if ops.len() >= 2 => {
+                module.types.insert(
+                    ops[0],
+                    Type::Array {
+                        element: ops[1],
+                        length: None,
+                    },
+                );
+            }
+            OP_TYPE_STRUCT if !ops.is_empty() => {
+                module.types.insert(
+                    ops[0],
+                    Type::Struct {
+                        members: ops[1..].to_vec(),
+                    },
+                );
+            }
+            OP_TYPE_POINTER if ops.len() >= 3 => {
+                module
+                    .types
+                    .insert(ops[0], Type::Pointer { pointee: ops[2] });
+            }
+            OP_TYPE_ACCELERATION_STRUCTURE if !ops.is_empty() => {
+                module.types.insert(ops[0], Type::AccelerationStructure);
+            }
+            // 32 bit integer constants are enough for array lengths
+            OP_CONSTANT if ops.len() >= 3 => {
+                module.constants.insert(ops[1], ops[2]);
+            }
+            OP_VARIABLE if ops.len() >= 3 => {
+                module.variables.push(Variable {
+                    id: ops[1],
+                    pointer_type: ops[0],
+                    storage_class: ops[2],
+                });
+            }
+            OP_DECORATE if ops.len() >= 2 => {
+                let decorations = module.decorations.entry(ops[0]).or_default();
+                let value = ops.get(2).copied();
+                match ops[1] {
+                    DECORATION_BLOCK => decorations.block = true,
+                    DECORATION_BUFFER_BLOCK => decorations.buffer_block = true,
+                    DECORATION_ARRAY_STRIDE => decorations.array_stride = value,
+                    DECORATION_BUILT_IN => decorations.built_in = true,
+                    DECORATION_LOCATION => decorations.location = value,
+                    DECORATION_BINDING => decorations.binding = value,
+                    DECORATION_DESCRIPTOR_SET => decorations.set = value,
+                    _ => (),
+                }
+            }
+            OP_MEMBER_DECORATE if ops.len() >= 3 => {
+                let decorations = module
+                    .member_decorations
+                    .entry((ops[0], ops[1]))
+                    .or_default();
+                let value = ops.get(3).copied();
+                match ops[2] {
+                    DECORATION_OFFSET => decorations.offset = value,
+                    DECORATION_MATRIX_STRIDE => decorations.matrix_stride = value,
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    }
+
+    let Some((execution_model, interface)) = entry_point else {
+        return Err(parse_error(label, "no entry point"));
+    };
+    let stage = stage_of(execution_model).ok_or_else(|| {
+        parse_error(
+            label,
+            &format!("unsupported execution model {execution_model}"),
+        )
+    })?;
+
+    // Since SPIR-V 1.4 the entry point lists every global it uses; before that only
+    // inputs and outputs, so all resource variables of the module count as used.
+    let lists_all_globals = version >= 0x0001_0400;
+
+    let mut result = ShaderInterface {
+        stage,
+        bindings: Vec::new(),
+        push_constants: None,
+        inputs: Vec::new(),
+    };
+    for var in &module.variables {
+        let in_interface = interface.contains(&var.id);
+        let Some(Type::Pointer { pointee }) = module.types.get(&var.pointer_type) else {
+            continue;
+        };
+        let pointee = *pointee;
+        let name = module.name(var.id, pointee);
+        match var.storage_class {
+            STORAGE_UNIFORM_CONSTANT | STORAGE_UNIFORM | STORAGE_STORAGE_BUFFER => {
+                if lists_all_globals && !in_interface {
+                    continue;
+                }
+                let decorations = module.decorations.get(&var.id);
+                let (Some(set), Some(binding)) = (
+                    decorations.and_then(|d| d.set),
+                    decorations.and_then(|d| d.binding),
+                ) else {
+                    return Err(parse_error(
+                        label,
+                        &format!("resource `{name}` has no descriptor set or binding"),
+                    ));
+                };
+                let (element, count) = module.strip_array(pointee);
+                let kind = module
+                    .resource_kind(element, var.storage_class)
+                    .ok_or_else(|| {
+                        parse_error(label, &format!("unsupported type of resource `{name}`"))
+                    })?;
+                result.bindings.push(ReflectedBinding {
+                    name,
+                    set,
+                    binding,
+                    kind,
+                    count,
+                    stride: module.runtime_array_stride(element),
+                });
+            }
+            STORAGE_PUSH_CONSTANT => {
+                if lists_all_globals && !in_interface {
+                    continue;
+                }
+                result.push_constants = module.struct_range(pointee);
+            }
+            STORAGE_INPUT if stage == ash::vk::ShaderStageFlags::VERTEX && in_interface => {
+                let decorations = module.decorations.get(&var.id);
+                if decorations.is_some_and(|d| d.built_in) {
+                    continue;
+                }
+                let Some(location) = decorations.and_then(|d| d.location) else {
+                    continue;
+                };
+                let Some(numeric) = module.numeric_type(pointee) else {
+                    return Err(parse_error(
+                        label,
+                        &format!("unsupported type of vertex input `{name}`"),
+                    ));
+                };
+                result.inputs.push(ReflectedInput {
+                    name,
+                    location,
+                    numeric,
+                });
+            }
+            _ => (),
+        }
+    }
+    result.bindings.sort_by_key(|b| (b.set, b.binding));
+    result.inputs.sort_by_key(|i| i.location);
+    Ok(result)
+}
+
+impl Module {
+    /// Variable name, falling back to the name of its type (cbuffers are often
+    /// anonymous variables of a named block).
+    fn name(&self, var: u32, pointee: u32) -> String {
+        self.names
+            .get(&var)
+            .filter(|n| !n.is_empty())
+            .or_else(|| self.names.get(&pointee))
+            .cloned()
+            .unwrap_or_else(|| format!("%{var}"))
+    }
+
+    /// Unwrap (nested) arrays, returning the element type and total element count.
+    fn strip_array(&self, mut ty: u32) -> (u32, Option<u32>) {
+        let mut count = Some(1u32);
+        while let Some(Type::Array { element, length }) = self.types.get(&ty) {
+            count = match (count, length) {
+                (Some(c), Some(l)) => Some(c * l),
+                _ => None,
+            };
+            ty = *element;
+        }
+        (ty, count)
+    }
+
+    fn resource_kind(&self, ty: u32, storage_class: u32) -> Option<ResourceKind> {
+        let decorations = self.decorations.get(&ty);
+        match self.types.get(&ty)? {
+            Type::Struct { .. } if storage_class == STORAGE_STORAGE_BUFFER => {
+                Some(ResourceKind::StorageBuffer)
+            }
+            Type::Struct { .. } if decorations.is_some_and(|d| d.buffer_block) => {
+                Some(ResourceKind::StorageBuffer)
+            }
+            Type::Struct { .. } if decorations.is_some_and(|d| d.block) => {
+                Some(ResourceKind::UniformBuffer)
+            }
+            Type::SampledImage => Some(ResourceKind::CombinedImageSampler),
+            Type::Sampler => Some(ResourceKind::Sampler),
+            Type::AccelerationStructure => Some(ResourceKind::AccelerationStructure),
+            Type::Image { dim, sampled } => Some(match (*dim, *sampled) {
+                (DIM_SUBPASS_DATA, _) => ResourceKind::InputAttachment,
+                (DIM_BUFFER, 2) => ResourceKind::StorageTexelBuffer,
+                (DIM_BUFFER, _) => ResourceKind::UniformTexelBuffer,
+                (_, 2) => ResourceKind::StorageImage,
+                _ => ResourceKind::SampledImage,
+            }),
+            _ => None,
+        }
+    }
+
+    fn runtime_array_stride(&self, ty: u32) -> Option<u32> {
+        let Type::Struct { members } = self.types.get(&ty)? else {
+            return None;
+        };
+        let last = *members.last()?;
+        match self.types.get(&last)? {
+            Type::Array { length: None, .. } => self.decorations.get(&last)?.array_stride,
+            _ => None,
+        }
+    }
+
+    fn numeric_type(&self, ty: u32) -> Option<NumericType> {
+        match self.types.get(&ty)? {
+            Type::Scalar { numeric, .. } => Some(*numeric),
+            Type::Vector { component, .. } => self.numeric_type(*component),
+            _ => None,
+        }
+    }
+
+    /// Byte range `[start, end)` covered by the members of a block.
+    fn struct_range(&self, ty: u32) -> Option<(u32, u32)> {
+        let Type::Struct { members } = self.types.get(&ty)? else {
+            return None;
+        };
+        let mut range: Option<(u32, u32)> = None;
+        for (index, member) in members.iter().enumerate() {
+            let decorations = self
+                .member_decorations
+                .get(&(ty, index as u32))
+                .copied()
+                .unwrap_or_default();
+            let offset = decorations.offset.unwrap_or(0);
+            let end = offset + self.size_of(*member, decorations.matrix_stride);
+            range = Some(match range {
+                Some((start, prev_end)) => (start.min(offset), prev_end.max(end)),
+                None => (offset, end),
+            });
+        }
+        range
+    }
+
+    /// Size in bytes of an explicitly laid out type.
+    fn size_of(&self, ty: u32, matrix_stride: Option<u32>) -> u32 {
+        match self.types.get(&ty) {
+            Some(Type::Scalar { width, .. }) => width / 8,
+            Some(Type::Vector { component, count }) => count * self.size_of(*component, None),
+            Some(Type::Matrix { column, count }) => {
+                count * matrix_stride.unwrap_or_else(|| self.size_of(*column, None))
+            }
+            Some(Type::Array { element, length }) => {
+                let stride = self
+                    .decorations
+                    .get(&ty)
+                    .and_then(|d| d.array_stride)
+                    .unwrap_or_else(|| self.size_of(*element, matrix_stride));
+                stride * length.unwrap_or(0)
+            }
+            Some(Type::Struct { .. }) => self.struct_range(ty).map_or(0, |(_, end)| end),
+            _ => 0,
+        }
+    }
+}
+
+/// Decode a nul-terminated literal string, returning it and the number of words used.
+fn parse_string(words: &[u32]) -> (String, usize) {
+    let mut bytes = Vec::new();
+    for (i, word) in words.iter().enumerate() {
+        for b in word.to_le_bytes() {
+            if b == 0 {
+                return (String::from_utf8_lossy(&bytes).into_owned(), i + 1);
+            }
+            bytes.push(b);
+        }
+    }
+    (String::from_utf8_lossy(&bytes).into_owned(), words.len())
+}
+
+fn stage_of(execution_model: u32) -> Option<ash::vk::ShaderStageFlags> {
+    use ash::vk::ShaderStageFlags as S;
+    Some(match execution_model {
+        0 => S::VERTEX,
+        4 => S::FRAGMENT,
+        5 => S::COMPUTE,
+        5313 => S::RAYGEN_KHR,
+        5314 => S::INTERSECTION_KHR,
+        5315 => S::ANY_HIT_KHR,
+        5316 => S::CLOSEST_HIT_KHR,
+        5317 => S::MISS_KHR,
+        5318 => S::CALLABLE_KHR,
+        _ => return None,
+    })
+}
+
+/// Numeric type a vertex attribute format is read as in the shader.
+pub(super) fn format_numeric_type(format: ash::vk::Format) -> Option<NumericType> {
+    use ash::vk::Format as F;
+    match format {
+        F::R32_SFLOAT
+        | F::R32G32_SFLOAT
+        | F::R32G32B32_SFLOAT
+        | F::R32G32B32A32_SFLOAT
+        | F::R16G16_SFLOAT
+        | F::R16G16B16A16_SFLOAT
+        | F::R8G8B8A8_UNORM
+        | F::R8G8B8A8_SNORM
+        | F::B8G8R8A8_UNORM
+        | F::R16G16_UNORM
+        | F::R16G16B16A16_UNORM => Some(NumericType::Float),
+        F::R32_UINT
+        | F::R32G32_UINT
+        | F::R32G32B32_UINT
+        | F::R32G32B32A32_UINT
+        | F::R8G8B8A8_UINT
+        | F::R16G16B16A16_UINT => Some(NumericType::Uint),
+        F::R32_SINT
+        | F::R32G32_SINT
+        | F::R32G32B32_SINT
+        | F::R32G32B32A32_SINT
+        | F::R8G8B8A8_SINT
+        | F::R16G16B16A16_SINT => Some(NumericType::Sint),
+        _ => None,
+    }
+}
+
+fn mismatch(label: &str, message: String) -> GpuError {
+    GpuError::new(
+        format!("Shader {label} does not match the pipeline layout: {message}"),
+        GpuErrorKind::ShaderCompilation,
+    )
+}
+
+/// Reflect `code` and check it against the pipeline layout and, for vertex
+/// shaders, the vertex input attributes (`None` if the pipeline has no vertex
+/// input state). `expected_stage` is the stage the module is bound to.
+pub(super) fn validate_shader(
+    label: &str,
+    code: &[u8],
+    expected_stage: ash::vk::ShaderStageFlags,
+    layout: &PipelineLayoutDesc,
+    vertex_attributes: Option<&[ash::vk::VertexInputAttributeDescription]>,
+) -> Result<(), GpuError> {
+    let interface = reflect(code, label)?;
+    if interface.stage != expected_stage {
+        return Err(mismatch(
+            label,
+            format!(
+                "entry point is a {:?} shader but is bound as {:?}",
+                interface.stage, expected_stage
+            ),
+        ));
+    }
+    validate_interface(label, &interface, layout, vertex_attributes)
+}
+
+pub(super) fn validate_interface(
+    label: &str,
+    interface: &ShaderInterface,
+    layout: &PipelineLayoutDesc,
+    vertex_attributes: Option<&[ash::vk::VertexInputAttributeDescription]>,
+) -> Result<(), GpuError> {
+    let stage = interface.stage;
+
+    for reflected in &interface.bindings {
+        let what = format!(
+            "`{}` (set {}, binding {})",
+            reflected.name, reflected.set, reflected.binding
+        );
+        let Some(declared) = layout
+            .sets
+            .get(reflected.set as usize)
+            .and_then(|set| set.iter().find(|b| b.binding == reflected.binding))
+        else {
+            return Err(mismatch(label, format!("{what} is not declared")));
+        };
+        if !reflected.kind.accepts(declared.descriptor_type) {
+            return Err(mismatch(
+                label,
+                format!(
+                    "{what} is a {:?} in the shader but {:?} in the layout",
+                    reflected.kind, declared.descriptor_type
+                ),
+            ));
+        }
+        if !declared.stage_flags.contains(stage) {
+            return Err(mismatch(
+                label,
+                format!(
+                    "{what} is not visible to the {stage:?} stage (layout: {:?})",
+                    declared.stage_flags
+                ),
+            ));
+        }
+        match reflected.count {
+            Some(count) if count > declared.descriptor_count => {
+                return Err(mismatch(
+                    label,
+                    format!(
+                        "{what} has {count} elements but the layout declares {}",
+                        declared.descriptor_count
+                    ),
+                ));
+            }
+            None if declared.descriptor_count == 0 => {
+                return Err(mismatch(label, format!("{what} has no descriptors")));
+            }
+            _ => (),
+        }
+    }
+
+    if let Some((start, end)) = interface.push_constants {
+        let covered = layout.push_constants.iter().any(|range| {
+            range.stage_flags.contains(stage)
+                && range.offset <= start
+                && end <= range.offset + range.size
+        });
+        if !covered {
+            return Err(mismatch(
+                label,
+                format!(
+                    "push constants [{start}, {end}) are not covered by a range for the {stage:?} stage"
+                ),
+            ));
+        }
+    }
+
+    if stage == ash::vk::ShaderStageFlags::VERTEX && !interface.inputs.is_empty() {
+        let Some(attributes) = vertex_attributes else {
+            return Err(mismatch(
+                label,
+                format!(
+                    "the shader reads {} vertex inputs but the pipeline has no vertex layout",
+                    interface.inputs.len()
+                ),
+            ));
+        };
+        for input in &interface.inputs {
+            let Some(attribute) = attributes.iter().find(|a| a.location == input.location) else {
+                return Err(mismatch(
+                    label,
+                    format!(
+                        "vertex input `{}` (location {}) has no vertex attribute",
+                        input.name, input.location
+                    ),
+                ));
+            };
+            if format_numeric_type(attribute.format) != Some(input.numeric) {
+                return Err(mismatch(
+                    label,
+                    format!(
+                        "vertex input `{}` (location {}) is {:?} but the attribute format is {:?}",
+                        input.name, input.location, input.numeric, attribute.format
+                    ),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::backend::standard_vertex_layout;
+    use crate::engine::compute_push::ComputePushConstants;
+    use crate::engine::draw_programs::GpuLight;
+    use crate::engine::procedural::SCATTER_BINDINGS;
+    use crate::engine::shader_reload::SPIRV_DIR;
+    use crate::engine::vulkan_backend::{
+        BindlessLimits, bindless_layout_bindings, compute, egui::EguiRenderer,
+        graphics_push_constant_range, rt,
+    };
+
+    /// A missing or empty blob fails the test instead of skipping the shader.
+    fn read(path: &str) -> Vec<u8> {
+        let code = std::fs::read(format!("{SPIRV_DIR}/{path}"))
+            .unwrap_or_else(|e| panic!("{path}: {e}, run tools/compile-shaders"));
+        assert!(
+            !code.is_empty(),
+            "{path} is empty, run tools/compile-shaders"
+        );
+        code
+    }
+
+    fn check(
+        path: &str,
+        layout: &PipelineLayoutDesc,
+        attributes: Option<&[ash::vk::VertexInputAttributeDescription]>,
+    ) {
+        let interface = reflect(&read(path), path).unwrap();
+        if let Err(e) = validate_interface(path, &interface, layout, attributes) {
+            panic!("{e}");
+        }
+    }
+
+    /// The checked-in SPIR-V of every shader the backend loads matches the
+    /// layouts the pipelines are created with.
+    #[test]
+    fn shaders_match_pipeline_layouts() {
+        let bindless = bindless_layout_bindings(&BindlessLimits::default());
+        let attributes = standard_vertex_layout()
+            .attributes
+            .iter()
+            .map(|it| ash::vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: it.shader_location,
+                format: it.format.into(),
+                offset: it.offset,
+            })
+            .collect::<Vec<_>>();
+
+        let graphics = PipelineLayoutDesc {
+            sets: &[&bindless],
+            push_constants: &[graphics_push_constant_range()],
+        };
+        for path in [
+            "deferred/pre_vertex.spv",
+            "deferred/pre_pixel.spv",
+            "deferred/light_vertex.spv",
+            "deferred/light_pixel.spv",
+            "main_pass/vertex.spv",
+            "main_pass/pixel.spv",
+            "skybox/sky_vertex.spv",
+            "skybox/sky_pixel.spv",
+            "shadow_mapping/sm_vert.spv",
+            "shadow_mapping/sm_pixel.spv",
+            "ssao/ssao_pixel.spv",
+            "ssao/ssao_blur_pixel.spv",
+            "taa/taa_pixel.spv",
+            "blend.spv",
+        ] {
+            check(path, &graphics, Some(&attributes));
+        }
+
+        let compute_bindings = compute::compute_layout_bindings(&SCATTER_BINDINGS);
+        let push_size = std::mem::size_of::<ComputePushConstants>() as u32;
+        let compute = PipelineLayoutDesc {
+            sets: &[&bindless, &compute_bindings],
+            push_constants: &[compute::compute_push_constant_range(push_size)],
+        };
+        check("compute/scattering_comp.spv", &compute, None);
+
+        // Light culling only uses set 0
+        let no_bindings = compute::compute_layout_bindings(&[]);
+        let light_culling = PipelineLayoutDesc {
+            sets: &[&bindless, &no_bindings],
+            push_constants: &[],
+        };
+        check("compute/light_culling.spv", &light_culling, None);
+
+        let rt_bindings = rt::rt_layout_bindings();
+        let rt = PipelineLayoutDesc {
+            sets: &[&bindless, &rt_bindings],
+            push_constants: &[rt::rt_push_constant_range()],
+        };
+        for path in [
+            "rt/raygen.spv",
+            "rt/miss.spv",
+            "rt/miss_shadow.spv",
+            "rt/closest_hit.spv",
+            "rt/any_hit.spv",
+        ] {
+            check(path, &rt, None);
+        }
+
+        let egui = PipelineLayoutDesc {
+            sets: &[&[EguiRenderer::descriptor_set_layout_binding()]],
+            push_constants: &[EguiRenderer::push_constant_range()],
+        };
+        let egui_attributes = EguiRenderer::vertex_attributes();
+        check("egui/egui.vert.spv", &egui, Some(&egui_attributes));
+        check("egui/egui.frag.spv", &egui, None);
+    }
+
+    /// The light buffers (set 0 binding 17, set 1 binding 2 of the RT layout) have
+    /// the stride of the Rust `GpuLight`, so shaders compiled before a layout
+    /// change are caught.
+    #[test]
+    fn light_buffer_stride_matches_gpu_light() {
+        let expected = std::mem::size_of::<GpuLight>() as u32;
+        for (path, set, binding) in [
+            ("deferred/light_pixel.spv", 0, 17),
+            ("main_pass/pixel.spv", 0, 17),
+            ("compute/light_culling.spv", 0, 17),
+            ("rt/closest_hit.spv", 1, 2),
+        ] {
+            let interface = reflect(&read(path), path).unwrap();
+            let lights = interface
+                .bindings
+                .iter()
+                .find(|b| b.set == set && b.binding == binding)
+                .unwrap_or_else(|| panic!("{path} does not read the light buffer"));
+            assert_eq!(
+                lights.stride,
+                Some(expected),
+                "{path}: stride of `{}` differs from GpuLight, run tools/compile-shaders",
+                lights.name
+            );
+        }
+    }
+
+    #[test]
+    fn mismatches_are_reported() {
+        let bindless = bindless_layout_bindings(&BindlessLimits::default());
+        let interface = reflect(&read("main_pass/pixel.spv"), "pixel").unwrap();
+
+        // the forward pixel shader reads push constants and set 0 bindings
+        let no_push = PipelineLayoutDesc {
+            sets: &[&bindless],
+            push_constants: &[],
+        };
+        assert!(validate_interface("pixel", &interface, &no_push, None).is_err());
+        let no_sets = PipelineLayoutDesc {
+            sets: &[],
+            push_constants: &[graphics_push_constant_range()],
+        };
+        assert!(validate_interface("pixel", &interface, &no_sets, None).is_err());
+
+        // vertex inputs without a vertex layout
+        let vertex = reflect(&read("main_pass/vertex.spv"), "vertex").unwrap();
+        let graphics = PipelineLayoutDesc {
+            sets: &[&bindless],
+            push_constants: &[graphics_push_constant_range()],
+        };
+        assert!(validate_interface("vertex", &vertex, &graphics, None).is_err());
+    }
+}
@@ -2,12 +2,15 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use std::{cell::Cell, rc::Rc};
 
 use crate::engine::vulkan_backend::VulkanHandleTracker;
+use crate::engine::vulkan_backend::allocator::{Allocation, ResourceTiling};
+use crate::engine::vulkan_backend::destruction_queue::{BindlessSlot, PendingDestroy};
 use crate::engine::{
     backend::{
         AddressMode, CompareFunc, FilterMode, GpuError, GpuErrorKind, GpuRenderTarget, GpuTexture,
-        RenderTargetDesc, SamplerDesc, TextureDesc, TextureFormat,
+        RenderTargetDesc, RenderTargetUsage, SamplerDesc, TextureDesc, TextureDimension,
+        TextureFormat,
     },
-    vulkan_backend::VulkanBackend,
+    vulkan_backend::{VulkanBackend, util::sample_count_flags},
 };
 
 static TEXTURE_ID: AtomicUsize = AtomicUsize::new(1);
@@ -15,7 +18,6 @@ static TEXTURE_ID: AtomicUsize = AtomicUsize::new(1);
 #[derive(Clone)]
 pub struct VulkanTexture {
     pub image: ash::vk::Image,
-    pub mem: ash::vk::DeviceMemory,
     pub image_view: ash::vk::ImageView,
     pub sampler: ash::vk::Sampler,
     pub width: u32,
@@ -23,6 +25,8 @@ pub struct VulkanTexture {
     pub format: TextureFormat,
     pub aspect: ash::vk::ImageAspectFlags,
     pub mip_levels: u32,
+    /// Array layers of the image, six per cube.
+    pub array_layers: u32,
     pub view_type: ash::vk::ImageViewType,
     pub compare_enabled: bool,
     pub id: usize,
@@ -31,8 +35,71 @@ pub struct VulkanTexture {
     pub descriptor_index: u32,
     pub device_handle: ash::Device,
     pub current_layout: Rc<Cell<ash::vk::ImageLayout>>,
+    /// Shared by all clones, destroys the handles once the last one is dropped.
+    /// `None` for handles owned elsewhere (swapchain images) and null textures.
+    pub owner: Option<Rc<TextureOwner>>,
+}
 
-    pub vullkan_handle_tracker: VulkanHandleTracker,
+/// Owns the Vulkan handles and bindless slot of a texture.
+pub struct TextureOwner {
+    image: ash::vk::Image,
+    image_view: ash::vk::ImageView,
+    sampler: ash::vk::Sampler,
+    allocation: Allocation,
+    slot: Cell<Option<BindlessSlot>>,
+    /// Texture whose image a layer view points into.
+    parent: Option<Rc<TextureOwner>>,
+    vulkan_handle_tracker: VulkanHandleTracker,
+}
+
+impl TextureOwner {
+    pub fn new(
+        image: ash::vk::Image,
+        image_view: ash::vk::ImageView,
+        sampler: ash::vk::Sampler,
+        allocation: Allocation,
+        vulkan_handle_tracker: VulkanHandleTracker,
+    ) -> Rc<Self> {
+        Rc::new(TextureOwner {
+            image,
+            image_view,
+            sampler,
+            allocation,
+            slot: Cell::new(None),
+            parent: None,
+            vulkan_handle_tracker,
+        })
+    }
+
+    /// Owner of a view into `parent`'s image, which only destroys the view.
+    pub fn view(
+        image_view: ash::vk::ImageView,
+        parent: Option<Rc<TextureOwner>>,
+        vulkan_handle_tracker: VulkanHandleTracker,
+    ) -> Rc<Self> {
+        Rc::new(TextureOwner {
+            image: ash::vk::Image::null(),
+            image_view,
+            sampler: ash::vk::Sampler::null(),
+            allocation: Allocation::null(),
+            slot: Cell::new(None),
+            parent,
+            vulkan_handle_tracker,
+        })
+    }
+}
+
+impl Drop for TextureOwner {
+    fn drop(&mut self) {
+        self.vulkan_handle_tracker
+            .defer_destroy(PendingDestroy::Texture {
+                image: self.image,
+                image_view: self.image_view,
+                sampler: self.sampler,
+                allocation: self.allocation,
+                slot: self.slot.get(),
+            });
+    }
 }
 
 impl GpuTexture for VulkanTexture {
@@ -58,10 +125,9 @@ impl GpuTexture for VulkanTexture {
 }
 
 impl VulkanTexture {
-    pub fn null(device: ash::Device, vulkan_handle_tracker: VulkanHandleTracker) -> Self {
+    pub fn null(device: ash::Device) -> Self {
         Self {
             image: ash::vk::Image::null(),
-            mem: ash::vk::DeviceMemory::null(),
             image_view: ash::vk::ImageView::null(),
             sampler: ash::vk::Sampler::null(),
             width: 0,
@@ -69,43 +135,27 @@ impl VulkanTexture {
             format: TextureFormat::Rgba8Unorm,
             aspect: ash::vk::ImageAspectFlags::empty(),
             mip_levels: 1,
+            array_layers: 1,
             view_type: ash::vk::ImageViewType::TYPE_2D,
             compare_enabled: false,
             id: TEXTURE_ID.fetch_add(1, Ordering::SeqCst),
             descriptor_index: u32::MAX,
             device_handle: device,
             current_layout: Rc::new(Cell::new(ash::vk::ImageLayout::UNDEFINED)),
-            vullkan_handle_tracker: vulkan_handle_tracker,
+            owner: None,
         }
     }
-    pub fn destroy(self) {
-        let VulkanTexture {
-            image,
-            mem,
-            image_view,
-            sampler,
-            vullkan_handle_tracker,
-            device_handle,
-            ..
-        } = self;
 
-        unsafe {
-            if sampler != ash::vk::Sampler::null() {
-                vullkan_handle_tracker.unregister_sampler(sampler);
-                device_handle.destroy_sampler(sampler, None);
-            }
-            if image_view != ash::vk::ImageView::null() {
-                vullkan_handle_tracker.unregister_image_view(image_view);
-                device_handle.destroy_image_view(image_view, None);
-            }
-            if image != ash::vk::Image::null() {
-                vullkan_handle_tracker.unregister_image(image);
-                device_handle.destroy_image(image, None);
-            }
-            if mem != ash::vk::DeviceMemory::null() {
-                vullkan_handle_tracker.unregister_device_memory(mem);
-                device_handle.free_memory(mem, None);
-            }
+    fn set_bindless_slot(&mut self, slot: BindlessSlot) {
+        let (BindlessSlot::Texture2d(index)
+        | BindlessSlot::Cube(index)
+        | BindlessSlot::Shadow(index)
+        | BindlessSlot::Texture2dArray(index)
+        | BindlessSlot::CubeArray(index)
+        | BindlessSlot::Volume(index)) = slot;
+        self.descriptor_index = index;
+        if let Some(owner) = &self.owner {
+            owner.slot.set(Some(slot));
         }
     }
 }
@@ -113,17 +163,18 @@ impl VulkanTexture {
 impl VulkanBackend {
     /// Assign a permanent bindless descriptor slot to a texture and write it
     /// into every per-frame descriptor set. Skips textures without a sampler
-    /// (depth-only attachments).
-    pub fn register_texture(&self, tex: &mut VulkanTexture) {
+    /// (depth-only attachments). Fails once all slots of the texture's kind are
+    /// in use.
+    pub fn register_texture(&self, tex: &mut VulkanTexture) -> Result<(), GpuError> {
         if tex.sampler == ash::vk::Sampler::null() {
-            return;
+            return Ok(());
         }
 
         let mut reg = self.texture_registry.borrow_mut();
 
         if tex.compare_enabled {
-            let slot = reg.allocate_shadow();
-            tex.descriptor_index = slot;
+            let slot = reg.allocate_shadow()?;
+            tex.set_bindless_slot(BindlessSlot::Shadow(slot));
             for set in &self.descriptors.sets {
                 let image_info = ash::vk::DescriptorImageInfo {
                     image_view: tex.image_view,
@@ -156,29 +207,21 @@ impl VulkanBackend {
                 ];
                 unsafe { self.device.update_descriptor_sets(&writes, &[]) };
             }
-        } else if tex.view_type == ash::vk::ImageViewType::CUBE {
-            let slot = reg.allocate_cube();
-            tex.descriptor_index = slot;
-            for set in &self.descriptors.sets {
-                let info = ash::vk::DescriptorImageInfo {
-                    image_view: tex.image_view,
-                    image_layout: ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-                    sampler: tex.sampler,
-                };
-                let write = ash::vk::WriteDescriptorSet {
-                    dst_set: *set,
-                    dst_binding: 7,
-                    dst_array_element: slot,
-                    descriptor_type: ash::vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-                    descriptor_count: 1,
-                    p_image_info: &info,
-                    ..Default::default()
-                };
-                unsafe { self.device.update_descriptor_sets(&[write], &[]) };
-            }
         } else {
-            let slot = reg.allocate_2d();
-            tex.descriptor_index = slot;
+            let (slot, binding) = match tex.view_type {
+                ash::vk::ImageViewType::CUBE => (BindlessSlot::Cube(reg.allocate_cube()?), 7),
+                ash::vk::ImageViewType::TYPE_2D_ARRAY => {
+                    (BindlessSlot::Texture2dArray(reg.allocate_2d_array()?), 14)
+                }
+                ash::vk::ImageViewType::CUBE_ARRAY => {
+                    (BindlessSlot::CubeArray(reg.allocate_cube_array()?), 15)
+                }
+                ash::vk::ImageViewType::TYPE_3D => {
+                    (BindlessSlot::Volume(reg.allocate_volume()?), 16)
+                }
+                _ => (BindlessSlot::Texture2d(reg.allocate_2d()?), 6),
+            };
+            tex.set_bindless_slot(slot);
             for set in &self.descriptors.sets {
                 let info = ash::vk::DescriptorImageInfo {
                     image_view: tex.image_view,
@@ -187,8 +230,8 @@ impl VulkanBackend {
                 };
                 let write = ash::vk::WriteDescriptorSet {
                     dst_set: *set,
-                    dst_binding: 6,
-                    dst_array_element: slot,
+                    dst_binding: binding,
+                    dst_array_element: tex.descriptor_index,
                     descriptor_type: ash::vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
                     descriptor_count: 1,
                     p_image_info: &info,
@@ -197,6 +240,19 @@ impl VulkanBackend {
                 unsafe { self.device.update_descriptor_sets(&[write], &[]) };
             }
         }
+        Ok(())
+    }
+
+    /// Destroy resources dropped during frames up to `completed_serial` and
+    /// return the bindless slots of destroyed textures to the registry.
+    pub fn release_destroyed(&self, completed_serial: u64) {
+        let free_slots = self
+            .vulkan_handle_tracker
+            .destroy_completed(completed_serial);
+        let mut reg = self.texture_registry.borrow_mut();
+        for slot in free_slots {
+            reg.release(slot);
+        }
     }
 
     pub fn create_vk_render_target(
@@ -206,11 +262,28 @@ impl VulkanBackend {
         info: &RenderTargetDesc,
         vulkan_handle_tracker: VulkanHandleTracker,
     ) -> Result<VulkanTexture, GpuError> {
+        info.validate()?;
         let format: ash::vk::Format = info.format.into();
+        if info.format.is_compressed() {
+            return Err(GpuError::new(
+                format!("{:?} cannot be used as a render target", info.format),
+                GpuErrorKind::ResourceCreation,
+            ));
+        }
+        let samples = sample_count_flags(info.sample_count)?;
+        let multisampled = info.sample_count > 1;
+        if multisampled && info.usage == RenderTargetUsage::Storage {
+            return Err(GpuError::new(
+                "Storage render targets cannot be multisampled",
+                GpuErrorKind::ResourceCreation,
+            ));
+        }
 
         let (image_usage, aspect_mask) = match info.usage {
             crate::engine::backend::RenderTargetUsage::Color => (
-                ash::vk::ImageUsageFlags::COLOR_ATTACHMENT | ash::vk::ImageUsageFlags::SAMPLED,
+                ash::vk::ImageUsageFlags::COLOR_ATTACHMENT
+                    | ash::vk::ImageUsageFlags::SAMPLED
+                    | ash::vk::ImageUsageFlags::TRANSFER_SRC,
                 ash::vk::ImageAspectFlags::COLOR,
             ),
             crate::engine::backend::RenderTargetUsage::Depth => {
@@ -236,29 +309,75 @@ impl VulkanBackend {
             ),
         };
 
-        let (rt, rt_mem) = Self::create_image(
-            instance,
-            device,
-            phys_device,
-            info.width,
-            info.height,
+        // multisampled targets are only rendered to and resolved
+        let image_usage = if multisampled {
+            image_usage
+                & !(ash::vk::ImageUsageFlags::SAMPLED | ash::vk::ImageUsageFlags::TRANSFER_SRC)
+        } else {
+            image_usage
+        };
+
+        // layered targets are cube map arrays, sampled as a whole and
+        // rendered through a view per layer
+        let layered = info.layers > 1;
+        if layered
+            && unsafe {
+                instance
+                    .get_physical_device_features(phys_device)
+                    .image_cube_array
+            } == ash::vk::FALSE
+        {
+            return Err(GpuError::new(
+                "Cube array render targets are not supported by this device",
+                GpuErrorKind::ResourceCreation,
+            ));
+        }
+        let (view_type, flags) = if layered {
+            (
+                ash::vk::ImageViewType::CUBE_ARRAY,
+                ash::vk::ImageCreateFlags::CUBE_COMPATIBLE,
+            )
+        } else {
+            (
+                ash::vk::ImageViewType::TYPE_2D,
+                ash::vk::ImageCreateFlags::empty(),
+            )
+        };
+
+        let image_create_info = ash::vk::ImageCreateInfo {
+            image_type: ash::vk::ImageType::TYPE_2D,
             format,
-            1,
-            ash::vk::ImageTiling::OPTIMAL,
-            image_usage,
+            extent: ash::vk::Extent3D {
+                width: info.width,
+                height: info.height,
+                depth: 1,
+            },
+            mip_levels: 1,
+            array_layers: info.layers,
+            samples,
+            tiling: ash::vk::ImageTiling::OPTIMAL,
+            usage: image_usage,
+            sharing_mode: ash::vk::SharingMode::EXCLUSIVE,
+            flags,
+            ..Default::default()
+        };
+        let (rt, rt_allocation) = Self::create_image_from_info(
+            vulkan_handle_tracker.allocator(),
+            device,
+            &image_create_info,
             ash::vk::MemoryPropertyFlags::DEVICE_LOCAL,
         )?;
 
         let view_create_info = ash::vk::ImageViewCreateInfo {
             image: rt,
-            view_type: ash::vk::ImageViewType::TYPE_2D,
+            view_type,
             format,
             subresource_range: ash::vk::ImageSubresourceRange {
                 aspect_mask,
                 base_mip_level: 0,
                 level_count: 1,
                 base_array_layer: 0,
-                layer_count: 1,
+                layer_count: info.layers,
             },
             ..Default::default()
         };
@@ -282,13 +401,11 @@ impl VulkanBackend {
 
         // Register all resources with handle tracker so cleanup_leftover catches them
         vulkan_handle_tracker.register_image(rt);
-        vulkan_handle_tracker.register_device_memory(rt_mem);
         vulkan_handle_tracker.register_image_view(image_view);
         vulkan_handle_tracker.register_sampler(sampler);
 
         Ok(VulkanTexture {
             image: rt,
-            mem: rt_mem,
             image_view,
             sampler,
             width: info.width,
@@ -296,13 +413,20 @@ impl VulkanBackend {
             format: info.format,
             aspect: aspect_mask,
             mip_levels: 1,
+            array_layers: info.layers,
             id: TEXTURE_ID.fetch_add(1, Ordering::SeqCst),
             compare_enabled: info.sampler.compare.is_some(),
             view_type: view_create_info.view_type,
             descriptor_index: u32::MAX,
             device_handle: device.clone(),
             current_layout: Rc::new(Cell::new(ash::vk::ImageLayout::UNDEFINED)),
-            vullkan_handle_tracker: vulkan_handle_tracker,
+            owner: Some(TextureOwner::new(
+                rt,
+                image_view,
+                sampler,
+                rt_allocation,
+                vulkan_handle_tracker,
+            )),
         })
     }
 
@@ -311,85 +435,179 @@ impl VulkanBackend {
         info: &TextureDesc,
         image_data: &[u8],
     ) -> Result<VulkanTexture, GpuError> {
-        let image_size = image_data.len() as ash::vk::DeviceSize;
-        let (staging_buff, staging_mem) = Self::create_buffer(
-            &self.instance,
-            &self.device,
-            self.phys_device,
-            image_size,
-            ash::vk::BufferUsageFlags::TRANSFER_SRC,
-            ash::vk::MemoryPropertyFlags::HOST_VISIBLE
-                | ash::vk::MemoryPropertyFlags::HOST_COHERENT,
-        )?;
-
-        // copy image to staging memory
-        self.copy_to_buffer(staging_mem, image_data.as_ptr() as *const _, image_size)?;
-
+        info.validate(image_data)?;
         let format: ash::vk::Format = info.format.into();
-        let mip_levels = calculate_mip_levels(info.width, info.height);
-        let (tex_image, tex_mem) = Self::create_image(
-            &self.instance,
-            &self.device,
-            self.phys_device,
-            info.width,
-            info.height,
-            format,
-            mip_levels,
-            ash::vk::ImageTiling::OPTIMAL,
-            ash::vk::ImageUsageFlags::TRANSFER_SRC
-                | ash::vk::ImageUsageFlags::TRANSFER_DST
-                | ash::vk::ImageUsageFlags::SAMPLED,
-            ash::vk::MemoryPropertyFlags::DEVICE_LOCAL,
-        )?;
-
-        let command_buff = self.begin_single_time_commands()?;
-        self.transition_image_layout(
-            command_buff,
-            tex_image,
-            ash::vk::ImageLayout::UNDEFINED,
-            ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-            ash::vk::ImageAspectFlags::COLOR,
-            1,
-            mip_levels,
-        )?;
-        self.copy_buffer_to_image(
-            command_buff,
-            staging_buff,
-            0,
-            tex_image,
-            info.width,
-            info.height,
-            0,
-            1,
-        );
+        let (image_type, view_type, flags) = match info.dimension {
+            TextureDimension::D2 => (
+                ash::vk::ImageType::TYPE_2D,
+                ash::vk::ImageViewType::TYPE_2D,
+                ash::vk::ImageCreateFlags::empty(),
+            ),
+            TextureDimension::D2Array => (
+                ash::vk::ImageType::TYPE_2D,
+                ash::vk::ImageViewType::TYPE_2D_ARRAY,
+                ash::vk::ImageCreateFlags::empty(),
+            ),
+            TextureDimension::Cube => (
+                ash::vk::ImageType::TYPE_2D,
+                ash::vk::ImageViewType::CUBE,
+                ash::vk::ImageCreateFlags::CUBE_COMPATIBLE,
+            ),
+            TextureDimension::CubeArray => (
+                ash::vk::ImageType::TYPE_2D,
+                ash::vk::ImageViewType::CUBE_ARRAY,
+                ash::vk::ImageCreateFlags::CUBE_COMPATIBLE,
+            ),
+            TextureDimension::D3 => (
+                ash::vk::ImageType::TYPE_3D,
+                ash::vk::ImageViewType::TYPE_3D,
+                ash::vk::ImageCreateFlags::empty(),
+            ),
+        };
+        if info.dimension == TextureDimension::CubeArray
+            && unsafe {
+                self.instance
+                    .get_physical_device_features(self.phys_device)
+                    .image_cube_array
+            } == ash::vk::FALSE
+        {
+            return Err(GpuError::new(
+                "Cube array textures are not supported by this device",
+                GpuErrorKind::ResourceCreation,
+            ));
+        }
+        let (depth, layers) = if info.dimension == TextureDimension::D3 {
+            (info.depth_or_layers, 1)
+        } else {
+            (1, info.depth_or_layers)
+        };
 
-        self.generate_mipmaps(
-            command_buff,
-            tex_image,
+        // a single uncompressed level gets its chain blitted on the GPU; 3D
+        // blits would need to filter across slices, so volumes keep one level
+        let generate_mips = info.mip_levels == 1
+            && !info.format.is_compressed()
+            && info.dimension != TextureDimension::D3;
+        let mip_levels = if generate_mips {
+            calculate_mip_levels(info.width, info.height)
+        } else {
+            info.mip_levels
+        };
+        let mut usage = ash::vk::ImageUsageFlags::TRANSFER_DST | ash::vk::ImageUsageFlags::SAMPLED;
+        if generate_mips {
+            usage |= ash::vk::ImageUsageFlags::TRANSFER_SRC;
+        }
+        let image_create_info = ash::vk::ImageCreateInfo {
+            image_type,
             format,
-            info.width,
-            info.height,
+            extent: ash::vk::Extent3D {
+                width: info.width,
+                height: info.height,
+                depth,
+            },
             mip_levels,
-        )?;
-
-        self.end_single_time_commands(command_buff)?;
+            array_layers: layers,
+            samples: ash::vk::SampleCountFlags::TYPE_1,
+            tiling: ash::vk::ImageTiling::OPTIMAL,
+            usage,
+            sharing_mode: ash::vk::SharingMode::EXCLUSIVE,
+            flags,
+            ..Default::default()
+        };
+        let tex_image =
+            unsafe { self.device.create_image(&image_create_info, None) }.map_err(|e| {
+                GpuError::new(
+                    format!("Failed to create {:?} texture image: {e:?}", info.dimension),
+                    GpuErrorKind::ResourceCreation,
+                )
+            })?;
+        let tex_allocation = self
+            .vulkan_handle_tracker
+            .allocator()
+            .allocate_image(
+                tex_image,
+                ash::vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                ResourceTiling::Optimal,
+                false,
+            )
+            .inspect_err(|_| unsafe { self.device.destroy_image(tex_image, None) })?;
 
-        // Free staging buffer after upload is complete
-        unsafe {
-            self.device.destroy_buffer(staging_buff, None);
-            self.device.free_memory(staging_mem, None);
+        {
+            let mut uploader = self.uploader.borrow_mut();
+            let staged = uploader.stage(&[image_data])?;
+            let cmd = uploader.transfer_cmd()?;
+            self.transition_image_layout(
+                cmd,
+                tex_image,
+                ash::vk::ImageLayout::UNDEFINED,
+                ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                ash::vk::ImageAspectFlags::COLOR,
+                layers,
+                mip_levels,
+            )?;
+            // each level holds all of its layers (or slices) back to back
+            let mut level_offset = staged.offset;
+            for level in 0..info.mip_levels {
+                let (width, height) = ((info.width >> level).max(1), (info.height >> level).max(1));
+                let depth = (depth >> level).max(1);
+                self.copy_buffer_to_image(
+                    cmd,
+                    staged.buffer,
+                    level_offset,
+                    tex_image,
+                    ash::vk::ImageSubresourceLayers {
+                        aspect_mask: ash::vk::ImageAspectFlags::COLOR,
+                        mip_level: level,
+                        base_array_layer: 0,
+                        layer_count: layers,
+                    },
+                    ash::vk::Extent3D {
+                        width,
+                        height,
+                        depth,
+                    },
+                );
+                level_offset +=
+                    (info.format.image_size(width, height) * (depth * layers) as usize) as u64;
+            }
+            let range = ash::vk::ImageSubresourceRange {
+                aspect_mask: ash::vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: mip_levels,
+                base_array_layer: 0,
+                layer_count: layers,
+            };
+            if generate_mips {
+                // mip blits need the main queue
+                uploader.finish_image(
+                    tex_image,
+                    range,
+                    ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    ash::vk::PipelineStageFlags2::TRANSFER,
+                    ash::vk::AccessFlags2::TRANSFER_READ | ash::vk::AccessFlags2::TRANSFER_WRITE,
+                )?;
+                let cmd = uploader.graphics_cmd()?;
+                self.generate_mipmaps(cmd, tex_image, format, info.width, info.height, range)?;
+            } else {
+                uploader.finish_image(
+                    tex_image,
+                    range,
+                    ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    ash::vk::PipelineStageFlags2::FRAGMENT_SHADER,
+                    ash::vk::AccessFlags2::SHADER_READ,
+                )?;
+            }
         }
 
         let view_create_info = ash::vk::ImageViewCreateInfo {
             image: tex_image,
-            view_type: ash::vk::ImageViewType::TYPE_2D,
+            view_type,
             format,
             subresource_range: ash::vk::ImageSubresourceRange {
                 aspect_mask: ash::vk::ImageAspectFlags::COLOR,
                 base_mip_level: 0,
                 level_count: mip_levels,
                 base_array_layer: 0,
-                layer_count: 1,
+                layer_count: layers,
             },
             ..Default::default()
         };
@@ -415,13 +633,11 @@ impl VulkanBackend {
 
         // Register handles for tracking
         self.vulkan_handle_tracker.register_image(tex_image);
-        self.vulkan_handle_tracker.register_device_memory(tex_mem);
         self.vulkan_handle_tracker.register_image_view(image_view);
         self.vulkan_handle_tracker.register_sampler(sampler);
 
         Ok(VulkanTexture {
             image: tex_image,
-            mem: tex_mem,
             image_view,
             sampler,
             width: info.width,
@@ -429,195 +645,75 @@ impl VulkanBackend {
             format: info.format,
             aspect: ash::vk::ImageAspectFlags::COLOR,
             mip_levels,
+            array_layers: layers,
             id: TEXTURE_ID.fetch_add(1, Ordering::SeqCst),
             compare_enabled: info.sampler.compare.is_some(),
-            view_type: view_create_info.view_type,
+            view_type,
             descriptor_index: u32::MAX,
             device_handle: self.device.device.clone(),
             current_layout: Rc::new(Cell::new(ash::vk::ImageLayout::UNDEFINED)),
-            vullkan_handle_tracker: self.vulkan_handle_tracker.clone(),
+            owner: Some(TextureOwner::new(
+                tex_image,
+                image_view,
+                sampler,
+                tex_allocation,
+                self.vulkan_handle_tracker.clone(),
+            )),
         })
     }
 
-    pub fn create_vk_cubemap(
+    /// Create a 2D view of one layer (or cube face) of a sampled texture. The
+    /// view shares the texture's image and sampler and keeps them alive.
+    pub fn create_vk_layer_view(
         &self,
-        faces: [&[u8]; 6],
-        width: u32,
-        height: u32,
-        format: TextureFormat,
-        sampler_desc: &SamplerDesc,
+        texture: &VulkanTexture,
+        layer: u32,
     ) -> Result<VulkanTexture, GpuError> {
-        let vk_format: ash::vk::Format = format.into();
-        let total_size = faces.iter().map(|f| f.len() as u64).sum();
-        let (staging, staging_mem) = Self::create_buffer(
-            &self.instance,
-            &self.device,
-            self.phys_device,
-            total_size,
-            ash::vk::BufferUsageFlags::TRANSFER_SRC,
-            ash::vk::MemoryPropertyFlags::HOST_VISIBLE
-                | ash::vk::MemoryPropertyFlags::HOST_COHERENT,
-        )?;
-
-        let data_ptr = unsafe {
-            self.device
-                .map_memory(staging_mem, 0, total_size, ash::vk::MemoryMapFlags::empty())
-        }
-        .map_err(|e| {
-            GpuError::new(
-                format!("Failed to map cubemap staging memory: {e:?}"),
-                GpuErrorKind::ResourceUpdate,
-            )
-        })?;
-        let mut offset = 0u64;
-        for face_data in &faces {
-            unsafe {
-                data_ptr
-                    .add(offset as usize)
-                    .copy_from(face_data.as_ptr() as *const _, face_data.len());
-            }
-            offset += face_data.len() as u64;
-        }
-        unsafe { self.device.unmap_memory(staging_mem) }
-
-        let image_create_info = ash::vk::ImageCreateInfo {
-            image_type: ash::vk::ImageType::TYPE_2D,
-            format: vk_format,
-            extent: ash::vk::Extent3D {
-                width,
-                height,
-                depth: 1,
-            },
-            mip_levels: 1,
-            array_layers: 6,
-            samples: ash::vk::SampleCountFlags::TYPE_1,
-            tiling: ash::vk::ImageTiling::OPTIMAL,
-            usage: ash::vk::ImageUsageFlags::TRANSFER_DST | ash::vk::ImageUsageFlags::SAMPLED,
-            sharing_mode: ash::vk::SharingMode::EXCLUSIVE,
-            flags: ash::vk::ImageCreateFlags::CUBE_COMPATIBLE,
-            ..Default::default()
-        };
-
-        let cubemap =
-            unsafe { self.device.create_image(&image_create_info, None) }.map_err(|e| {
-                GpuError::new(
-                    format!("Failed to create cubemap image: {e:?}"),
-                    GpuErrorKind::ResourceCreation,
-                )
-            })?;
-
-        let mem_reqs = unsafe { self.device.get_image_memory_requirements(cubemap) };
-        let alloc_info = ash::vk::MemoryAllocateInfo {
-            allocation_size: mem_reqs.size,
-            memory_type_index: Self::find_memory_type(
-                &self.instance,
-                self.phys_device,
-                mem_reqs.memory_type_bits,
-                ash::vk::MemoryPropertyFlags::DEVICE_LOCAL,
-            )?,
-            ..Default::default()
-        };
-        let cubemap_mem =
-            unsafe { self.device.allocate_memory(&alloc_info, None) }.map_err(|e| {
-                GpuError::new(
-                    format!("Failed to allocate cubemap memory: {e:?}"),
-                    GpuErrorKind::ResourceCreation,
-                )
-            })?;
-        unsafe { self.device.bind_image_memory(cubemap, cubemap_mem, 0) }.map_err(|e| {
-            GpuError::new(
-                format!("Failed to bind cubemap memory: {e:?}"),
-                GpuErrorKind::ResourceUpdate,
-            )
-        })?;
-
-        let cmd = self.begin_single_time_commands()?;
-        self.transition_image_layout(
-            cmd,
-            cubemap,
-            ash::vk::ImageLayout::UNDEFINED,
-            ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-            ash::vk::ImageAspectFlags::COLOR,
-            6,
-            1,
-        )?;
-        let mut face_offset = 0u64;
-        for i in 0..6 {
-            let face_size = faces[i as usize].len() as u64;
-            self.copy_buffer_to_image(cmd, staging, face_offset, cubemap, width, height, i, 1);
-            face_offset += face_size;
-        }
-        self.transition_image_layout(
-            cmd,
-            cubemap,
-            ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-            ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-            ash::vk::ImageAspectFlags::COLOR,
-            6,
-            1,
-        )?;
-        self.end_single_time_commands(cmd)?;
-
-        // Free staging buffer after upload is complete
-        unsafe {
-            self.device.destroy_buffer(staging, None);
-            self.device.free_memory(staging_mem, None);
+        if texture.view_type == ash::vk::ImageViewType::TYPE_3D || layer >= texture.array_layers {
+            return Err(GpuError::new(
+                format!(
+                    "Cannot view layer {layer} of a {:?} texture with {} layers",
+                    texture.view_type, texture.array_layers
+                ),
+                GpuErrorKind::ResourceCreation,
+            ));
         }
-
         let view_create_info = ash::vk::ImageViewCreateInfo {
-            image: cubemap,
-            view_type: ash::vk::ImageViewType::CUBE,
-            format: vk_format,
+            image: texture.image,
+            view_type: ash::vk::ImageViewType::TYPE_2D,
+            format: texture.format.into(),
             subresource_range: ash::vk::ImageSubresourceRange {
-                aspect_mask: ash::vk::ImageAspectFlags::COLOR,
+                aspect_mask: texture.aspect,
                 base_mip_level: 0,
-                level_count: 1,
-                base_array_layer: 0,
-                layer_count: 6,
+                level_count: texture.mip_levels,
+                base_array_layer: layer,
+                layer_count: 1,
             },
             ..Default::default()
         };
-
         let image_view = unsafe { self.device.create_image_view(&view_create_info, None) }
             .map_err(|e| {
                 GpuError::new(
-                    format!("Failed to create cubemap ImageView: {e:?}"),
+                    format!("Failed to create layer ImageView: {e:?}"),
                     GpuErrorKind::ResourceCreation,
                 )
             })?;
-
-        let sampler_info = sampler_desc.into_vk(&self.instance, self.phys_device, None);
-        let sampler = unsafe { self.device.create_sampler(&sampler_info, None) }.map_err(|e| {
-            GpuError::new(
-                format!("Failed to create cubemap sampler: {e:?}"),
-                GpuErrorKind::ResourceCreation,
-            )
-        })?;
-
-        // Register handles for tracking
-        self.vulkan_handle_tracker.register_image(cubemap);
-        self.vulkan_handle_tracker
-            .register_device_memory(cubemap_mem);
         self.vulkan_handle_tracker.register_image_view(image_view);
-        self.vulkan_handle_tracker.register_sampler(sampler);
+
+        let owner = TextureOwner::view(
+            image_view,
+            texture.owner.clone(),
+            self.vulkan_handle_tracker.clone(),
+        );
 
         Ok(VulkanTexture {
-            image: cubemap,
-            mem: cubemap_mem,
             image_view,
-            sampler,
-            width,
-            height,
-            format,
-            aspect: ash::vk::ImageAspectFlags::COLOR,
-            mip_levels: 1,
+            view_type: ash::vk::ImageViewType::TYPE_2D,
+            array_layers: 1,
             id: TEXTURE_ID.fetch_add(1, Ordering::SeqCst),
-            compare_enabled: sampler_desc.compare.is_some(),
-            view_type: ash::vk::ImageViewType::CUBE,
             descriptor_index: u32::MAX,
-            device_handle: self.device.device.clone(),
-            current_layout: Rc::new(Cell::new(ash::vk::ImageLayout::UNDEFINED)),
-            vullkan_handle_tracker: self.vulkan_handle_tracker.clone(),
+            owner: Some(owner),
+            ..texture.clone()
         })
     }
 
@@ -640,14 +736,14 @@ impl VulkanBackend {
 
         let vk_format: ash::vk::Format = format.into();
 
-        let (depth_img, mem) = Self::create_image(
-            &self.instance,
+        let (depth_img, allocation) = Self::create_image(
+            self.vulkan_handle_tracker.allocator(),
             &self.device,
-            self.phys_device,
             width,
             height,
             vk_format,
             1,
+            ash::vk::SampleCountFlags::TYPE_1,
             ash::vk::ImageTiling::OPTIMAL,
             ash::vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | ash::vk::ImageUsageFlags::SAMPLED,
             ash::vk::MemoryPropertyFlags::DEVICE_LOCAL,
@@ -693,7 +789,6 @@ impl VulkanBackend {
 
         // Register handles for tracking
         self.vulkan_handle_tracker.register_image(depth_img);
-        self.vulkan_handle_tracker.register_device_memory(mem);
         self.vulkan_handle_tracker.register_image_view(image_view);
         if sampler != ash::vk::Sampler::null() {
             self.vulkan_handle_tracker.register_sampler(sampler);
@@ -701,7 +796,6 @@ impl VulkanBackend {
 
         Ok(VulkanTexture {
             image: depth_img,
-            mem,
             image_view,
             sampler,
             width,
@@ -709,13 +803,20 @@ impl VulkanBackend {
             format,
             aspect: ash::vk::ImageAspectFlags::DEPTH,
             mip_levels: 1,
+            array_layers: 1,
             id: TEXTURE_ID.fetch_add(1, Ordering::SeqCst),
             compare_enabled,
             view_type: view_create_info.view_type,
             descriptor_index: u32::MAX,
             device_handle: self.device.device.clone(),
             current_layout: Rc::new(Cell::new(ash::vk::ImageLayout::UNDEFINED)),
-            vullkan_handle_tracker: self.vulkan_handle_tracker.clone(),
+            owner: Some(TextureOwner::new(
+                depth_img,
+                image_view,
+                sampler,
+                allocation,
+                self.vulkan_handle_tracker.clone(),
+            )),
         })
     }
 
@@ -726,8 +827,9 @@ impl VulkanBackend {
         format: ash::vk::Format,
         width: u32,
         height: u32,
-        mip_levels: u32,
+        range: ash::vk::ImageSubresourceRange,
     ) -> Result<(), GpuError> {
+        let (mip_levels, layer_count) = (range.level_count, range.layer_count);
         let format_props = unsafe {
             self.instance
                 .get_physical_device_format_properties(self.phys_device, format)
@@ -753,7 +855,7 @@ impl VulkanBackend {
             subresource_range: ash::vk::ImageSubresourceRange {
                 aspect_mask: ash::vk::ImageAspectFlags::COLOR,
                 base_array_layer: 0,
-                layer_count: 1,
+                layer_count,
                 level_count: 1,
                 base_mip_level: 0,
             },
@@ -811,13 +913,13 @@ impl VulkanBackend {
                     aspect_mask: ash::vk::ImageAspectFlags::COLOR,
                     mip_level: i - 1,
                     base_array_layer: 0,
-                    layer_count: 1,
+                    layer_count,
                 },
                 dst_subresource: ash::vk::ImageSubresourceLayers {
                     aspect_mask: ash::vk::ImageAspectFlags::COLOR,
                     mip_level: i,
                     base_array_layer: 0,
-                    layer_count: 1,
+                    layer_count,
                 },
                 src_offsets: offsets,
                 dst_offsets,
@@ -890,7 +992,6 @@ impl std::fmt::Debug for VulkanTexture {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("VulkanTexture")
             .field("image", &self.image)
-            .field("mem", &self.mem)
             .field("image_view", &self.image_view)
             .field("sampler", &self.sampler)
             .field("width", &self.width)
@@ -921,6 +1022,18 @@ impl Into<ash::vk::Format> for TextureFormat {
             TextureFormat::Depth32Float => ash::vk::Format::D32_SFLOAT,
             TextureFormat::Depth24Stencil8 => ash::vk::Format::D24_UNORM_S8_UINT,
             TextureFormat::Abgr10Unorm => ash::vk::Format::A2B10G10R10_UNORM_PACK32,
+            TextureFormat::Bc1RgbaUnorm => ash::vk::Format::BC1_RGBA_UNORM_BLOCK,
+            TextureFormat::Bc1RgbaUnormSrgb => ash::vk::Format::BC1_RGBA_SRGB_BLOCK,
+            TextureFormat::Bc3Unorm => ash::vk::Format::BC3_UNORM_BLOCK,
+            TextureFormat::Bc3UnormSrgb => ash::vk::Format::BC3_SRGB_BLOCK,
+            TextureFormat::Bc4Unorm => ash::vk::Format::BC4_UNORM_BLOCK,
+            TextureFormat::Bc5Unorm => ash::vk::Format::BC5_UNORM_BLOCK,
+            TextureFormat::Bc7Unorm => ash::vk::Format::BC7_UNORM_BLOCK,
+            TextureFormat::Bc7UnormSrgb => ash::vk::Format::BC7_SRGB_BLOCK,
+            TextureFormat::Etc2Rgba8Unorm => ash::vk::Format::ETC2_R8G8B8A8_UNORM_BLOCK,
+            TextureFormat::Etc2Rgba8UnormSrgb => ash::vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK,
+            TextureFormat::Astc4x4Unorm => ash::vk::Format::ASTC_4X4_UNORM_BLOCK,
+            TextureFormat::Astc4x4UnormSrgb => ash::vk::Format::ASTC_4X4_SRGB_BLOCK,
         }
     }
 }
@@ -946,6 +1059,18 @@ impl TryFrom<ash::vk::Format> for TextureFormat {
             ash::vk::Format::D32_SFLOAT => TextureFormat::Depth32Float,
             ash::vk::Format::D24_UNORM_S8_UINT => TextureFormat::Depth24Stencil8,
             ash::vk::Format::A2B10G10R10_UNORM_PACK32 => TextureFormat::Abgr10Unorm,
+            ash::vk::Format::BC1_RGBA_UNORM_BLOCK => TextureFormat::Bc1RgbaUnorm,
+            ash::vk::Format::BC1_RGBA_SRGB_BLOCK => TextureFormat::Bc1RgbaUnormSrgb,
+            ash::vk::Format::BC3_UNORM_BLOCK => TextureFormat::Bc3Unorm,
+            ash::vk::Format::BC3_SRGB_BLOCK => TextureFormat::Bc3UnormSrgb,
+            ash::vk::Format::BC4_UNORM_BLOCK => TextureFormat::Bc4Unorm,
+            ash::vk::Format::BC5_UNORM_BLOCK => TextureFormat::Bc5Unorm,
+            ash::vk::Format::BC7_UNORM_BLOCK => TextureFormat::Bc7Unorm,
+            ash::vk::Format::BC7_SRGB_BLOCK => TextureFormat::Bc7UnormSrgb,
+            ash::vk::Format::ETC2_R8G8B8A8_UNORM_BLOCK => TextureFormat::Etc2Rgba8Unorm,
+            ash::vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK => TextureFormat::Etc2Rgba8UnormSrgb,
+            ash::vk::Format::ASTC_4X4_UNORM_BLOCK => TextureFormat::Astc4x4Unorm,
+            ash::vk::Format::ASTC_4X4_SRGB_BLOCK => TextureFormat::Astc4x4UnormSrgb,
             f => {
                 return Err(GpuError::new(
                     format!("VK Format {f:?} is not yet supported"),
@@ -0,0 +1,668 @@
+//! Batched staging uploads.
+//!
+//! Texture and buffer data is copied into a persistently mapped staging ring
+//! and the copy commands of many resources are recorded into one batch instead
+//! of a blocking submit each. With a dedicated transfer queue family the copies
+//! run there; the resources are then released to the main queue family and
+//! acquired by a second command buffer on the main queue, which also takes the
+//! work that needs a graphics queue (mipmap blits, sub-region updates of images
+//! in use).
+//!
+//! Every batch signals `timeline` on the main queue. Frames and one-off submits
+//! wait on the last signalled value, and the staging memory of a batch is
+//! reused once the host has seen its value.
+
+use std::collections::VecDeque;
+
+use crate::engine::{
+    backend::{GpuError, GpuErrorKind},
+    vulkan_backend::{
+        VulkanBackend,
+        allocator::{Allocation, MemoryAllocator},
+    },
+};
+
+/// Size of the persistent staging ring. Larger uploads get a temporary buffer.
+const STAGING_RING_SIZE: u64 = 64 * 1024 * 1024;
+/// Offset alignment of staged data, covers texel sizes and `bufferOffset` rules.
+const STAGING_ALIGNMENT: u64 = 16;
+
+/// Location of staged data, the source of a copy command.
+#[derive(Clone, Copy)]
+pub struct StagingSlice {
+    pub buffer: ash::vk::Buffer,
+    pub offset: ash::vk::DeviceSize,
+}
+
+struct TransferQueue {
+    queue: ash::vk::Queue,
+    family: u32,
+    pool: ash::vk::CommandPool,
+    /// Signalled by the transfer submit of a batch, waited on by its main queue submit.
+    timeline: ash::vk::Semaphore,
+    free_cmds: Vec<ash::vk::CommandBuffer>,
+}
+
+#[derive(Default)]
+struct Batch {
+    /// Copies on the transfer queue, null if unused or without a transfer queue.
+    transfer_cmd: ash::vk::CommandBuffer,
+    /// Main queue commands; without a transfer queue the copies go here as well.
+    graphics_cmd: ash::vk::CommandBuffer,
+    /// Staging ring position after the last slice of this batch.
+    ring_end: u64,
+    /// Staging buffers for uploads that don't fit into the ring.
+    temp_buffers: Vec<(ash::vk::Buffer, Allocation)>,
+    value: u64,
+}
+
+pub struct Uploader {
+    device: ash::Device,
+    allocator: MemoryAllocator,
+    queue: ash::vk::Queue,
+    family: u32,
+    pool: ash::vk::CommandPool,
+    timeline: ash::vk::Semaphore,
+    free_cmds: Vec<ash::vk::CommandBuffer>,
+    /// `None` if the device has no dedicated transfer queue family.
+    transfer: Option<TransferQueue>,
+
+    ring_buffer: ash::vk::Buffer,
+    ring_allocation: Allocation,
+    /// Monotonic positions, the ring offset is `position % STAGING_RING_SIZE`.
+    ring_head: u64,
+    ring_tail: u64,
+
+    open: Option<Batch>,
+    in_flight: VecDeque<Batch>,
+    /// Timeline value of the last submitted batch.
+    submitted: u64,
+}
+
+impl Uploader {
+    pub fn new(
+        device: &ash::Device,
+        allocator: MemoryAllocator,
+        queue: ash::vk::Queue,
+        family: u32,
+        transfer: Option<(ash::vk::Queue, u32)>,
+    ) -> Result<Self, GpuError> {
+        let pool = create_command_pool(device, family)?;
+        let timeline = create_timeline_semaphore(device)?;
+        let transfer = match transfer {
+            Some((queue, family)) => Some(TransferQueue {
+                queue,
+                family,
+                pool: create_command_pool(device, family)?,
+                timeline: create_timeline_semaphore(device)?,
+                free_cmds: Vec::new(),
+            }),
+            None => None,
+        };
+        let (ring_buffer, ring_allocation) = VulkanBackend::create_buffer(
+            &allocator,
+            device,
+            STAGING_RING_SIZE,
+            ash::vk::BufferUsageFlags::TRANSFER_SRC,
+            ash::vk::MemoryPropertyFlags::HOST_VISIBLE
+                | ash::vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+
+        Ok(Uploader {
+            device: device.clone(),
+            allocator,
+            queue,
+            family,
+            pool,
+            timeline,
+            free_cmds: Vec::new(),
+            transfer,
+            ring_buffer,
+            ring_allocation,
+            ring_head: 0,
+            ring_tail: 0,
+            open: None,
+            in_flight: VecDeque::new(),
+            submitted: 0,
+        })
+    }
+
+    pub fn has_transfer_queue(&self) -> bool {
+        self.transfer.is_some()
+    }
+
+    /// Copy `parts` back to back into staging memory.
+    ///
+    /// May submit the open batch and wait for older ones to free ring space, so
+    /// stage all data of a resource before recording its copies.
+    pub fn stage(&mut self, parts: &[&[u8]]) -> Result<StagingSlice, GpuError> {
+        let size: u64 = parts.iter().map(|part| part.len() as u64).sum();
+        let (slice, dst) = if size > STAGING_RING_SIZE {
+            let (buffer, allocation) = VulkanBackend::create_buffer(
+                &self.allocator,
+                &self.device,
+                size,
+                ash::vk::BufferUsageFlags::TRANSFER_SRC,
+                ash::vk::MemoryPropertyFlags::HOST_VISIBLE
+                    | ash::vk::MemoryPropertyFlags::HOST_COHERENT,
+            )?;
+            self.open
+                .get_or_insert_with(Batch::default)
+                .temp_buffers
+                .push((buffer, allocation));
+            (
+                StagingSlice { buffer, offset: 0 },
+                allocation.mapped as *mut u8,
+            )
+        } else {
+            let offset = self.allocate_ring(size)?;
+            let dst = unsafe { (self.ring_allocation.mapped as *mut u8).add(offset as usize) };
+            let slice = StagingSlice {
+                buffer: self.ring_buffer,
+                offset,
+            };
+            (slice, dst)
+        };
+
+        let mut written = 0;
+        for part in parts {
+            // SAFETY: `dst` points to at least `size` bytes of mapped staging memory
+            unsafe {
+                dst.add(written)
+                    .copy_from_nonoverlapping(part.as_ptr(), part.len());
+            }
+            written += part.len();
+        }
+        Ok(slice)
+    }
+
+    /// Command buffer for copies of the open batch, on the transfer queue if there is one.
+    pub fn transfer_cmd(&mut self) -> Result<ash::vk::CommandBuffer, GpuError> {
+        let Some(transfer) = &mut self.transfer else {
+            return self.graphics_cmd();
+        };
+        let batch = self.open.get_or_insert_with(Batch::default);
+        if batch.transfer_cmd == ash::vk::CommandBuffer::null() {
+            batch.transfer_cmd =
+                begin_command_buffer(&self.device, transfer.pool, &mut transfer.free_cmds)?;
+        }
+        Ok(batch.transfer_cmd)
+    }
+
+    /// Command buffer of the open batch on the main queue, submitted after its copies.
+    pub fn graphics_cmd(&mut self) -> Result<ash::vk::CommandBuffer, GpuError> {
+        let batch = self.open.get_or_insert_with(Batch::default);
+        if batch.graphics_cmd == ash::vk::CommandBuffer::null() {
+            batch.graphics_cmd =
+                begin_command_buffer(&self.device, self.pool, &mut self.free_cmds)?;
+        }
+        Ok(batch.graphics_cmd)
+    }
+
+    /// Make the copies into `image` (in `TRANSFER_DST_OPTIMAL`) available to the
+    /// main queue in `new_layout`. With a transfer queue this is a queue family
+    /// ownership transfer, otherwise a plain barrier.
+    pub fn finish_image(
+        &mut self,
+        image: ash::vk::Image,
+        subresource_range: ash::vk::ImageSubresourceRange,
+        new_layout: ash::vk::ImageLayout,
+        dst_stage_mask: ash::vk::PipelineStageFlags2,
+        dst_access_mask: ash::vk::AccessFlags2,
+    ) -> Result<(), GpuError> {
+        let barrier = ash::vk::ImageMemoryBarrier2 {
+            src_stage_mask: ash::vk::PipelineStageFlags2::TRANSFER,
+            src_access_mask: ash::vk::AccessFlags2::TRANSFER_WRITE,
+            dst_stage_mask,
+            dst_access_mask,
+            old_layout: ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            new_layout,
+            src_queue_family_index: ash::vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: ash::vk::QUEUE_FAMILY_IGNORED,
+            image,
+            subresource_range,
+            ..Default::default()
+        };
+        let Some(src_family) = self.transfer.as_ref().map(|t| t.family) else {
+            let cmd = self.graphics_cmd()?;
+            self.image_barrier(cmd, &barrier);
+            return Ok(());
+        };
+
+        let release = ash::vk::ImageMemoryBarrier2 {
+            dst_stage_mask: ash::vk::PipelineStageFlags2::NONE,
+            dst_access_mask: ash::vk::AccessFlags2::NONE,
+            src_queue_family_index: src_family,
+            dst_queue_family_index: self.family,
+            ..barrier
+        };
+        let acquire = ash::vk::ImageMemoryBarrier2 {
+            src_stage_mask: ash::vk::PipelineStageFlags2::NONE,
+            src_access_mask: ash::vk::AccessFlags2::NONE,
+            src_queue_family_index: src_family,
+            dst_queue_family_index: self.family,
+            ..barrier
+        };
+        let cmd = self.transfer_cmd()?;
+        self.image_barrier(cmd, &release);
+        let cmd = self.graphics_cmd()?;
+        self.image_barrier(cmd, &acquire);
+        Ok(())
+    }
+
+    /// Make the copies into `buffer` available to all later reads on the main queue.
+    pub fn finish_buffer(&mut self, buffer: ash::vk::Buffer) -> Result<(), GpuError> {
+        let barrier = ash::vk::BufferMemoryBarrier2 {
+            src_stage_mask: ash::vk::PipelineStageFlags2::TRANSFER,
+            src_access_mask: ash::vk::AccessFlags2::TRANSFER_WRITE,
+            dst_stage_mask: ash::vk::PipelineStageFlags2::ALL_COMMANDS,
+            dst_access_mask: ash::vk::AccessFlags2::MEMORY_READ,
+            src_queue_family_index: ash::vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: ash::vk::QUEUE_FAMILY_IGNORED,
+            buffer,
+            offset: 0,
+            size: ash::vk::WHOLE_SIZE,
+            ..Default::default()
+        };
+        let Some(src_family) = self.transfer.as_ref().map(|t| t.family) else {
+            let cmd = self.graphics_cmd()?;
+            self.buffer_barrier(cmd, &barrier);
+            return Ok(());
+        };
+
+        let release = ash::vk::BufferMemoryBarrier2 {
+            dst_stage_mask: ash::vk::PipelineStageFlags2::NONE,
+            dst_access_mask: ash::vk::AccessFlags2::NONE,
+            src_queue_family_index: src_family,
+            dst_queue_family_index: self.family,
+            ..barrier
+        };
+        let acquire = ash::vk::BufferMemoryBarrier2 {
+            src_stage_mask: ash::vk::PipelineStageFlags2::NONE,
+            src_access_mask: ash::vk::AccessFlags2::NONE,
+            src_queue_family_index: src_family,
+            dst_queue_family_index: self.family,
+            ..barrier
+        };
+        let cmd = self.transfer_cmd()?;
+        self.buffer_barrier(cmd, &release);
+        let cmd = self.graphics_cmd()?;
+        self.buffer_barrier(cmd, &acquire);
+        Ok(())
+    }
+
+    /// Copy staged data into a buffer no submitted work has used yet, on the
+    /// transfer queue if there is one.
+    pub fn copy_to_new_buffer(
+        &mut self,
+        src: StagingSlice,
+        dst: ash::vk::Buffer,
+        size: ash::vk::DeviceSize,
+    ) -> Result<(), GpuError> {
+        let cmd = self.transfer_cmd()?;
+        let region = ash::vk::BufferCopy {
+            src_offset: src.offset,
+            dst_offset: 0,
+            size,
+        };
+        unsafe { self.device.cmd_copy_buffer(cmd, src.buffer, dst, &[region]) };
+        self.finish_buffer(dst)
+    }
+
+    /// Copy staged data into a buffer that frames in flight may still read. Runs
+    /// on the main queue after all previously submitted work.
+    pub fn copy_to_buffer_in_use(
+        &mut self,
+        src: StagingSlice,
+        dst: ash::vk::Buffer,
+        size: ash::vk::DeviceSize,
+    ) -> Result<(), GpuError> {
+        let cmd = self.graphics_cmd()?;
+        let before = ash::vk::BufferMemoryBarrier2 {
+            src_stage_mask: ash::vk::PipelineStageFlags2::ALL_COMMANDS,
+            src_access_mask: ash::vk::AccessFlags2::NONE,
+            dst_stage_mask: ash::vk::PipelineStageFlags2::TRANSFER,
+            dst_access_mask: ash::vk::AccessFlags2::TRANSFER_WRITE,
+            src_queue_family_index: ash::vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: ash::vk::QUEUE_FAMILY_IGNORED,
+            buffer: dst,
+            offset: 0,
+            size: ash::vk::WHOLE_SIZE,
+            ..Default::default()
+        };
+        self.buffer_barrier(cmd, &before);
+        let region = ash::vk::BufferCopy {
+            src_offset: src.offset,
+            dst_offset: 0,
+            size,
+        };
+        unsafe { self.device.cmd_copy_buffer(cmd, src.buffer, dst, &[region]) };
+        let after = ash::vk::BufferMemoryBarrier2 {
+            src_stage_mask: ash::vk::PipelineStageFlags2::TRANSFER,
+            src_access_mask: ash::vk::AccessFlags2::TRANSFER_WRITE,
+            dst_stage_mask: ash::vk::PipelineStageFlags2::ALL_COMMANDS,
+            dst_access_mask: ash::vk::AccessFlags2::MEMORY_READ,
+            ..before
+        };
+        self.buffer_barrier(cmd, &after);
+        Ok(())
+    }
+
+    /// Submit the open batch, if it recorded anything.
+    pub fn flush(&mut self) -> Result<(), GpuError> {
+        let Some(mut batch) = self.open.take() else {
+            return Ok(());
+        };
+        if batch.transfer_cmd == ash::vk::CommandBuffer::null()
+            && batch.graphics_cmd == ash::vk::CommandBuffer::null()
+        {
+            self.open = Some(batch);
+            return Ok(());
+        }
+
+        let value = self.submitted + 1;
+        batch.value = value;
+        batch.ring_end = self.ring_head;
+
+        for cmd in [batch.transfer_cmd, batch.graphics_cmd] {
+            if cmd != ash::vk::CommandBuffer::null() {
+                unsafe { self.device.end_command_buffer(cmd) }.map_err(|e| {
+                    GpuError::new(
+                        format!("Failed to end upload command buffer: {e:?}"),
+                        GpuErrorKind::ResourceUpdate,
+                    )
+                })?;
+            }
+        }
+
+        let mut wait_semaphore = None;
+        if let Some(transfer) = &self.transfer
+            && batch.transfer_cmd != ash::vk::CommandBuffer::null()
+        {
+            submit(
+                &self.device,
+                transfer.queue,
+                batch.transfer_cmd,
+                None,
+                (transfer.timeline, value),
+            )?;
+            wait_semaphore = Some((transfer.timeline, value));
+        }
+        submit(
+            &self.device,
+            self.queue,
+            batch.graphics_cmd,
+            wait_semaphore,
+            (self.timeline, value),
+        )?;
+
+        self.submitted = value;
+        self.in_flight.push_back(batch);
+        Ok(())
+    }
+
+    /// Timeline semaphore and value that cover every submitted upload.
+    /// `None` if nothing was ever uploaded.
+    pub fn wait_target(&self) -> Option<(ash::vk::Semaphore, u64)> {
+        (self.submitted > 0).then_some((self.timeline, self.submitted))
+    }
+
+    /// Recycle command buffers and staging memory of completed batches.
+    pub fn retire(&mut self) -> Result<(), GpuError> {
+        if self.in_flight.is_empty() {
+            return Ok(());
+        }
+        let completed =
+            unsafe { self.device.get_semaphore_counter_value(self.timeline) }.map_err(|e| {
+                GpuError::new(
+                    format!("Failed to query upload timeline: {e:?}"),
+                    GpuErrorKind::Other,
+                )
+            })?;
+        while self
+            .in_flight
+            .front()
+            .is_some_and(|batch| batch.value <= completed)
+        {
+            let Some(batch) = self.in_flight.pop_front() else {
+                break;
+            };
+            self.ring_tail = batch.ring_end;
+            self.recycle(batch);
+        }
+        Ok(())
+    }
+
+    /// Block until all submitted uploads are complete.
+    pub fn wait_all(&mut self) -> Result<(), GpuError> {
+        self.flush()?;
+        if let Some((_, value)) = self.wait_target() {
+            self.wait(value)?;
+        }
+        self.retire()
+    }
+
+    /// Free everything. The device must be idle.
+    pub fn destroy(&mut self) {
+        let batches: Vec<Batch> = self.in_flight.drain(..).chain(self.open.take()).collect();
+        for batch in batches {
+            for (buffer, allocation) in batch.temp_buffers {
+                unsafe { self.device.destroy_buffer(buffer, None) };
+                self.allocator.free(allocation);
+            }
+        }
+        unsafe {
+            self.device.destroy_buffer(self.ring_buffer, None);
+            self.device.destroy_command_pool(self.pool, None);
+            self.device.destroy_semaphore(self.timeline, None);
+            if let Some(transfer) = &self.transfer {
+                self.device.destroy_command_pool(transfer.pool, None);
+                self.device.destroy_semaphore(transfer.timeline, None);
+            }
+        }
+        self.allocator.free(self.ring_allocation);
+        self.ring_allocation = Allocation::null();
+    }
+
+    /// Reserve `size` bytes of the ring and return their offset.
+    fn allocate_ring(&mut self, size: u64) -> Result<u64, GpuError> {
+        loop {
+            if self.in_flight.is_empty() && self.ring_head == self.ring_tail {
+                self.ring_head = 0;
+                self.ring_tail = 0;
+            }
+            let mut start = self.ring_head.next_multiple_of(STAGING_ALIGNMENT);
+            let offset = start % STAGING_RING_SIZE;
+            if offset + size > STAGING_RING_SIZE {
+                // no wrapping inside a slice
+                start += STAGING_RING_SIZE - offset;
+            }
+            if start + size - self.ring_tail <= STAGING_RING_SIZE {
+                self.ring_head = start + size;
+                return Ok(start % STAGING_RING_SIZE);
+            }
+
+            if self.in_flight.is_empty() {
+                // the open batch holds the rest of the ring
+                self.flush()?;
+                if self.in_flight.is_empty() {
+                    return Err(GpuError::new(
+                        "Staging ring exhausted without pending uploads",
+                        GpuErrorKind::ResourceUpdate,
+                    ));
+                }
+            }
+            if let Some(value) = self.in_flight.front().map(|batch| batch.value) {
+                self.wait(value)?;
+            }
+            self.retire()?;
+        }
+    }
+
+    fn wait(&self, value: u64) -> Result<(), GpuError> {
+        let wait_info = ash::vk::SemaphoreWaitInfo {
+            semaphore_count: 1,
+            p_semaphores: &self.timeline,
+            p_values: &value,
+            ..Default::default()
+        };
+        unsafe { self.device.wait_semaphores(&wait_info, u64::MAX) }.map_err(|e| {
+            GpuError::new(
+                format!("Failed to wait for uploads: {e:?}"),
+                GpuErrorKind::Other,
+            )
+        })
+    }
+
+    fn recycle(&mut self, batch: Batch) {
+        for (buffer, allocation) in batch.temp_buffers {
+            unsafe { self.device.destroy_buffer(buffer, None) };
+            self.allocator.free(allocation);
+        }
+        if batch.graphics_cmd != ash::vk::CommandBuffer::null() {
+            self.free_cmds.push(batch.graphics_cmd);
+        }
+        if let Some(transfer) = &mut self.transfer
+            && batch.transfer_cmd != ash::vk::CommandBuffer::null()
+        {
+            transfer.free_cmds.push(batch.transfer_cmd);
+        }
+    }
+
+    fn image_barrier(&self, cmd: ash::vk::CommandBuffer, barrier: &ash::vk::ImageMemoryBarrier2) {
+        let dependency_info = ash::vk::DependencyInfo {
+            image_memory_barrier_count: 1,
+            p_image_memory_barriers: barrier,
+            ..Default::default()
+        };
+        unsafe { self.device.cmd_pipeline_barrier2(cmd, &dependency_info) };
+    }
+
+    fn buffer_barrier(&self, cmd: ash::vk::CommandBuffer, barrier: &ash::vk::BufferMemoryBarrier2) {
+        let dependency_info = ash::vk::DependencyInfo {
+            buffer_memory_barrier_count: 1,
+            p_buffer_memory_barriers: barrier,
+            ..Default::default()
+        };
+        unsafe { self.device.cmd_pipeline_barrier2(cmd, &dependency_info) };
+    }
+}
+
+fn create_command_pool(
+    device: &ash::Device,
+    family: u32,
+) -> Result<ash::vk::CommandPool, GpuError> {
+    let pool_info = ash::vk::CommandPoolCreateInfo {
+        flags: ash::vk::CommandPoolCreateFlags::TRANSIENT
+            | ash::vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+        queue_family_index: family,
+        ..Default::default()
+    };
+    unsafe { device.create_command_pool(&pool_info, None) }.map_err(|e| {
+        GpuError::new(
+            format!("Failed to create upload command pool: {e:?}"),
+            GpuErrorKind::ResourceCreation,
+        )
+    })
+}
+
+fn create_timeline_semaphore(device: &ash::Device) -> Result<ash::vk::Semaphore, GpuError> {
+    let mut type_info = ash::vk::SemaphoreTypeCreateInfo {
+        semaphore_type: ash::vk::SemaphoreType::TIMELINE,
+        initial_value: 0,
+        ..Default::default()
+    };
+    let create_info = ash::vk::SemaphoreCreateInfo {
+        p_next: &mut type_info as *mut _ as *const std::ffi::c_void,
+        ..Default::default()
+    };
+    unsafe { device.create_semaphore(&create_info, None) }.map_err(|e| {
+        GpuError::new(
+            format!("Failed to create upload timeline semaphore: {e:?}"),
+            GpuErrorKind::ResourceCreation,
+        )
+    })
+}
+
+/// Reuse a recycled command buffer or allocate a new one, and begin it.
+fn begin_command_buffer(
+    device: &ash::Device,
+    pool: ash::vk::CommandPool,
+    free_cmds: &mut Vec<ash::vk::CommandBuffer>,
+) -> Result<ash::vk::CommandBuffer, GpuError> {
+    let cmd = match free_cmds.pop() {
+        Some(cmd) => {
+            unsafe { device.reset_command_buffer(cmd, ash::vk::CommandBufferResetFlags::empty()) }
+                .map_err(|e| {
+                    GpuError::new(
+                        format!("Failed to reset upload command buffer: {e:?}"),
+                        GpuErrorKind::ResourceUpdate,
+                    )
+                })?;
+            cmd
+        }
+        None => {
+            let alloc_info = ash::vk::CommandBufferAllocateInfo {
+                command_pool: pool,
+                level: ash::vk::CommandBufferLevel::PRIMARY,
+                command_buffer_count: 1,
+                ..Default::default()
+            };
+            let cmds = unsafe { device.allocate_command_buffers(&alloc_info) }.map_err(|e| {
+                GpuError::new(
+                    format!("Failed to allocate upload command buffer: {e:?}"),
+                    GpuErrorKind::ResourceCreation,
+                )
+            })?;
+            cmds[0]
+        }
+    };
+
+    let begin_info = ash::vk::CommandBufferBeginInfo {
+        flags: ash::vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+        ..Default::default()
+    };
+    unsafe { device.begin_command_buffer(cmd, &begin_info) }.map_err(|e| {
+        GpuError::new(
+            format!("Failed to begin upload command buffer: {e:?}"),
+            GpuErrorKind::ResourceUpdate,
+        )
+    })?;
+    Ok(cmd)
+}
+
+/// Submit `cmd` (may be null) after an optional timeline wait, signalling `signal`.
+fn submit(
+    device: &ash::Device,
+    queue: ash::vk::Queue,
+    cmd: ash::vk::CommandBuffer,
+    wait: Option<(ash::vk::Semaphore, u64)>,
+    signal: (ash::vk::Semaphore, u64),
+) -> Result<(), GpuError> {
+    let (wait_semaphore, wait_value) = wait.unwrap_or_default();
+    let wait_stage = ash::vk::PipelineStageFlags::ALL_COMMANDS;
+    let mut timeline_info = ash::vk::TimelineSemaphoreSubmitInfo {
+        wait_semaphore_value_count: wait.is_some() as u32,
+        p_wait_semaphore_values: &wait_value,
+        signal_semaphore_value_count: 1,
+        p_signal_semaphore_values: &signal.1,
+        ..Default::default()
+    };
+    let submit_info = ash::vk::SubmitInfo {
+        p_next: &mut timeline_info as *mut _ as *const std::ffi::c_void,
+        wait_semaphore_count: wait.is_some() as u32,
+        p_wait_semaphores: &wait_semaphore,
+        p_wait_dst_stage_mask: &wait_stage,
+        command_buffer_count: (cmd != ash::vk::CommandBuffer::null()) as u32,
+        p_command_buffers: &cmd,
+        signal_semaphore_count: 1,
+        p_signal_semaphores: &signal.0,
+        ..Default::default()
+    };
+    unsafe { device.queue_submit(queue, &[submit_info], ash::vk::Fence::null()) }.map_err(|e| {
+        GpuError::new(
+            format!("Failed to submit uploads: {e:?}"),
+            GpuErrorKind::ResourceUpdate,
+        )
+    })
+}
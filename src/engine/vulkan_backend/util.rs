@@ -6,11 +6,14 @@ use crate::{
     app_handler::Window,
     engine::{
         backend::{
-            CompareFunc, CullMode, GpuError, GpuErrorKind, LoadOp, VertexFormat, ViewportDesc,
+            CompareFunc, CullMode, GpuError, GpuErrorKind, LoadOp, StencilFaceState, StencilOp,
+            StencilState, VertexFormat, ViewportDesc,
         },
         settings::SyncMode,
         vulkan_backend::{
-            SurfaceFormat, Swapchain, VulkanBackend, create_swapchain_and_depth_buffer,
+            SurfaceFormat, Swapchain, VulkanBackend,
+            allocator::{Allocation, MemoryAllocator, ResourceTiling},
+            create_offscreen_backbuffer_and_depth_buffer, create_swapchain_and_depth_buffer,
         },
     },
 };
@@ -387,18 +390,20 @@ pub fn load_shader_blob(path: impl AsRef<std::path::Path>) -> Result<Vec<u8>, Gp
 }
 
 impl VulkanBackend {
+    /// Create a 2D image and bind it to memory from `allocator`. Render targets
+    /// (attachments and storage images) get a dedicated allocation.
     pub fn create_image(
-        instance: &ash::Instance,
+        allocator: &MemoryAllocator,
         device: &ash::Device,
-        phys_device: ash::vk::PhysicalDevice,
         width: u32,
         height: u32,
         format: ash::vk::Format,
         mip_levels: u32,
+        samples: ash::vk::SampleCountFlags,
         tiling: ash::vk::ImageTiling,
         usage: ash::vk::ImageUsageFlags,
         properties: ash::vk::MemoryPropertyFlags,
-    ) -> Result<(ash::vk::Image, ash::vk::DeviceMemory), GpuError> {
+    ) -> Result<(ash::vk::Image, Allocation), GpuError> {
         let create_info = ash::vk::ImageCreateInfo {
             image_type: ash::vk::ImageType::TYPE_2D,
             format,
@@ -409,54 +414,71 @@ impl VulkanBackend {
             },
             mip_levels,
             array_layers: 1,
-            samples: ash::vk::SampleCountFlags::TYPE_1,
+            samples,
             tiling,
             usage,
             sharing_mode: ash::vk::SharingMode::EXCLUSIVE,
             ..Default::default()
         };
+        Self::create_image_from_info(allocator, device, &create_info, properties)
+    }
+
+    /// [`Self::create_image`] from a full `create_info`, e.g. for the layers
+    /// and `CUBE_COMPATIBLE` flag of cube map arrays.
+    pub fn create_image_from_info(
+        allocator: &MemoryAllocator,
+        device: &ash::Device,
+        create_info: &ash::vk::ImageCreateInfo,
+        properties: ash::vk::MemoryPropertyFlags,
+    ) -> Result<(ash::vk::Image, Allocation), GpuError> {
+        let (tiling, usage) = (create_info.tiling, create_info.usage);
 
-        let image = unsafe { device.create_image(&create_info, None) }.map_err(|e| {
+        let image = unsafe { device.create_image(create_info, None) }.map_err(|e| {
             GpuError::new(
                 format!("Failed to create image resource: {e:?}"),
                 GpuErrorKind::ResourceCreation,
             )
         })?;
 
-        let mem_reqs = unsafe { device.get_image_memory_requirements(image) };
-        let alloc_info = ash::vk::MemoryAllocateInfo {
-            allocation_size: mem_reqs.size,
-            memory_type_index: Self::find_memory_type(
-                instance,
-                phys_device,
-                mem_reqs.memory_type_bits,
-                properties,
-            )?,
-            ..Default::default()
+        let resource_tiling = if tiling == ash::vk::ImageTiling::LINEAR {
+            ResourceTiling::Linear
+        } else {
+            ResourceTiling::Optimal
         };
-        let device_mem = unsafe { device.allocate_memory(&alloc_info, None) }.map_err(|e| {
-            GpuError::new(
-                format!("Failed to allocate Image Memory: {e:?}"),
-                GpuErrorKind::ResourceCreation,
-            )
-        })?;
-        unsafe { device.bind_image_memory(image, device_mem, 0) }.map_err(|e| {
-            GpuError::new(
-                format!("Failed to bind memory to image: {e:?}"),
-                GpuErrorKind::ResourceUpdate,
-            )
-        })?;
+        let is_render_target = usage.intersects(
+            ash::vk::ImageUsageFlags::COLOR_ATTACHMENT
+                | ash::vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+                | ash::vk::ImageUsageFlags::STORAGE,
+        );
+        let allocation = allocator
+            .allocate_image(image, properties, resource_tiling, is_render_target)
+            .inspect_err(|_| unsafe { device.destroy_image(image, None) })?;
 
-        Ok((image, device_mem))
+        Ok((image, allocation))
     }
+
+    /// Create a buffer and bind it to memory from `allocator`. Host-visible
+    /// buffers are mapped through [`Allocation::mapped`].
     pub fn create_buffer(
-        instance: &ash::Instance,
+        allocator: &MemoryAllocator,
         device: &ash::Device,
-        phys_device: ash::vk::PhysicalDevice,
         device_size: ash::vk::DeviceSize,
         usage: ash::vk::BufferUsageFlags,
         properties: ash::vk::MemoryPropertyFlags,
-    ) -> Result<(ash::vk::Buffer, ash::vk::DeviceMemory), GpuError> {
+    ) -> Result<(ash::vk::Buffer, Allocation), GpuError> {
+        Self::create_buffer_aligned(allocator, device, device_size, usage, properties, 1)
+    }
+
+    /// [`Self::create_buffer`] with the memory offset (and so the device
+    /// address) aligned to at least `min_alignment`.
+    pub fn create_buffer_aligned(
+        allocator: &MemoryAllocator,
+        device: &ash::Device,
+        device_size: ash::vk::DeviceSize,
+        usage: ash::vk::BufferUsageFlags,
+        properties: ash::vk::MemoryPropertyFlags,
+        min_alignment: u64,
+    ) -> Result<(ash::vk::Buffer, Allocation), GpuError> {
         let create_info = ash::vk::BufferCreateInfo {
             size: device_size,
             usage,
@@ -470,80 +492,11 @@ impl VulkanBackend {
             )
         })?;
 
-        let mem_reqs = unsafe { device.get_buffer_memory_requirements(buffer) };
+        let allocation = allocator
+            .allocate_buffer(buffer, properties, min_alignment)
+            .inspect_err(|_| unsafe { device.destroy_buffer(buffer, None) })?;
 
-        let needs_device_address = usage.contains(ash::vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS);
-        let mut alloc_flags = ash::vk::MemoryAllocateFlagsInfo {
-            flags: ash::vk::MemoryAllocateFlags::DEVICE_ADDRESS,
-            ..Default::default()
-        };
-        let alloc_info = ash::vk::MemoryAllocateInfo {
-            allocation_size: mem_reqs.size,
-            memory_type_index: Self::find_memory_type(
-                instance,
-                phys_device,
-                mem_reqs.memory_type_bits,
-                properties,
-            )?,
-            p_next: if needs_device_address {
-                &mut alloc_flags as *mut _ as *mut std::ffi::c_void
-            } else {
-                std::ptr::null_mut()
-            },
-            ..Default::default()
-        };
-        let device_mem = unsafe { device.allocate_memory(&alloc_info, None) }.map_err(|e| {
-            GpuError::new(
-                format!("Failed to allocate buffer: {e:?}"),
-                GpuErrorKind::ResourceCreation,
-            )
-        })?;
-
-        unsafe { device.bind_buffer_memory(buffer, device_mem, 0) }.map_err(|e| {
-            GpuError::new(
-                format!("Failed to bind buffer to memory: {e:?}"),
-                GpuErrorKind::ResourceUpdate,
-            )
-        })?;
-
-        Ok((buffer, device_mem))
-    }
-
-    pub fn find_memory_type(
-        instance: &ash::Instance,
-        phys_device: ash::vk::PhysicalDevice,
-        type_filter: u32,
-        properties: ash::vk::MemoryPropertyFlags,
-    ) -> Result<u32, GpuError> {
-        let mut mem_props = ash::vk::PhysicalDeviceMemoryProperties2 {
-            ..Default::default()
-        };
-        unsafe {
-            instance.get_physical_device_memory_properties2(phys_device, &mut mem_props);
-        }
-        // find memory type where the type_filter bit is set to 1
-        for i in 0..mem_props.memory_properties.memory_type_count {
-            let props = mem_props.memory_properties.memory_types[i as usize];
-            let flags_match = type_filter & (1 << i) != 0;
-            let props_contained = props.property_flags & properties == properties;
-
-            // println!(
-            //     "Checking memory at [{i}]:
-            //         flags: {:?} [{:?}]
-            //         filter_flags_check: {}",
-            //     props.property_flags, props_contained, flags_match,
-            // );
-            if flags_match && props_contained {
-                return Ok(i);
-            }
-        }
-
-        Err(GpuError {
-            message: format!(
-                "No suitable memory type found for filter {type_filter:#034b} and flags {properties:?}"
-            ),
-            kind: GpuErrorKind::ResourceCreation,
-        })
+        Ok((buffer, allocation))
     }
 
     pub fn begin_single_time_commands(&self) -> Result<ash::vk::CommandBuffer, GpuError> {
@@ -600,7 +553,24 @@ impl VulkanBackend {
             )
         })?;
 
+        // pending uploads may be inputs of these commands
+        let upload_wait = {
+            let mut uploader = self.uploader.borrow_mut();
+            uploader.flush()?;
+            uploader.wait_target()
+        };
+        let (upload_semaphore, upload_value) = upload_wait.unwrap_or_default();
+        let wait_stage = ash::vk::PipelineStageFlags::ALL_COMMANDS;
+        let mut timeline_info = ash::vk::TimelineSemaphoreSubmitInfo {
+            wait_semaphore_value_count: upload_wait.is_some() as u32,
+            p_wait_semaphore_values: &upload_value,
+            ..Default::default()
+        };
         let submit_info = ash::vk::SubmitInfo {
+            p_next: &mut timeline_info as *mut _ as *const std::ffi::c_void,
+            wait_semaphore_count: upload_wait.is_some() as u32,
+            p_wait_semaphores: &upload_semaphore,
+            p_wait_dst_stage_mask: &wait_stage,
             command_buffer_count: 1,
             p_command_buffers: &command_buffer,
             ..Default::default()
@@ -628,7 +598,7 @@ impl VulkanBackend {
             self.device
                 .free_command_buffers(self.command_pool.short_lived, &[command_buffer]);
         }
-        Ok(())
+        self.uploader.borrow_mut().retire()
     }
 
     pub fn copy_buffer_cmd(
@@ -669,33 +639,24 @@ impl VulkanBackend {
         }
     }
 
+    /// Copy tightly packed texels at `src_offset` into the color `subresource`
+    /// of `dst`, which must be in `TRANSFER_DST_OPTIMAL` layout.
     pub fn copy_buffer_to_image(
         &self,
         command_buffer: ash::vk::CommandBuffer,
         src: ash::vk::Buffer,
         src_offset: ash::vk::DeviceSize,
         dst: ash::vk::Image,
-        width: u32,
-        height: u32,
-        base_array_layer: u32,
-        layer_count: u32,
+        subresource: ash::vk::ImageSubresourceLayers,
+        extent: ash::vk::Extent3D,
     ) {
         let copy_region = ash::vk::BufferImageCopy {
             buffer_offset: src_offset,
             buffer_row_length: 0,
             buffer_image_height: 0,
-            image_subresource: ash::vk::ImageSubresourceLayers {
-                aspect_mask: ash::vk::ImageAspectFlags::COLOR,
-                mip_level: 0,
-                base_array_layer,
-                layer_count,
-            },
+            image_subresource: subresource,
             image_offset: ash::vk::Offset3D { x: 0, y: 0, z: 0 },
-            image_extent: ash::vk::Extent3D {
-                width,
-                height,
-                depth: 1,
-            },
+            image_extent: extent,
         };
 
         unsafe {
@@ -711,25 +672,21 @@ impl VulkanBackend {
 
     pub fn copy_to_buffer(
         &self,
-        staging_mem: ash::vk::DeviceMemory,
+        staging: &Allocation,
         data: *const c_void,
         size: ash::vk::DeviceSize,
     ) -> Result<(), GpuError> {
-        let data_ptr = unsafe {
-            self.device
-                .map_memory(staging_mem, 0, size, ash::vk::MemoryMapFlags::empty())
-        }
-        .map_err(|e| {
-            GpuError::new(
-                format!("Map device memory failed: {e:?}"),
+        if staging.mapped.is_null() || size > staging.size {
+            return Err(GpuError::new(
+                format!(
+                    "Cannot copy {size} bytes to a staging allocation of {} bytes",
+                    staging.size
+                ),
                 GpuErrorKind::ResourceUpdate,
-            )
-        })?;
-        unsafe {
-            data_ptr.copy_from(data, size as usize);
+            ));
         }
         unsafe {
-            self.device.unmap_memory(staging_mem);
+            staging.mapped.copy_from(data, size as usize);
         }
 
         Ok(())
@@ -758,39 +715,66 @@ impl VulkanBackend {
             surface_format: SurfaceFormat::default(),
             surface: ash::vk::SurfaceKHR::null(),
             sync_mode: self.swapchain.sync_mode,
+            offscreen: self.swapchain.offscreen,
         };
         std::mem::swap(&mut old, &mut self.swapchain);
 
-        let (mut new_swapchain, mut new_depth) = create_swapchain_and_depth_buffer(
-            &self.context,
-            &self.instance,
-            // Use old extent as fallback size hint — surface capabilities.current_extent
-            // is used on macOS (MoltenVK always provides it), so this is just a fallback.
-            (old.swapchain_extent.width, old.swapchain_extent.height),
-            self.phys_device,
-            &self.device,
-            old.surface,
-            old.sync_mode,
-            old.surface_format.is_hdr,
-            self.vulkan_handle_tracker.clone(),
-            old.swapchain,
-        )?;
+        let (mut new_swapchain, mut new_depth) = if old.offscreen {
+            create_offscreen_backbuffer_and_depth_buffer(
+                &self.instance,
+                (old.swapchain_extent.width, old.swapchain_extent.height),
+                self.phys_device,
+                &self.device,
+                old.sync_mode,
+                self.vulkan_handle_tracker.clone(),
+            )?
+        } else {
+            create_swapchain_and_depth_buffer(
+                &self.context,
+                &self.instance,
+                // Use old extent as fallback size hint — surface capabilities.current_extent
+                // is used on macOS (MoltenVK always provides it), so this is just a fallback.
+                (old.swapchain_extent.width, old.swapchain_extent.height),
+                self.phys_device,
+                &self.device,
+                old.surface,
+                old.sync_mode,
+                old.surface_format.is_hdr,
+                self.vulkan_handle_tracker.clone(),
+                old.swapchain,
+            )?
+        };
 
         std::mem::swap(&mut self.swapchain, &mut new_swapchain);
         std::mem::swap(&mut self.depth_targets, &mut new_depth);
 
-        for depth in new_depth {
-            depth.destroy();
-        }
-
-        unsafe {
-            self.swapchain.fn_ptr.destroy_swapchain(old.swapchain, None);
+        // old depth targets and offscreen backbuffers are destroyed once dropped
+        if !old.offscreen {
+            unsafe {
+                self.swapchain.fn_ptr.destroy_swapchain(old.swapchain, None);
+            }
         }
 
         Ok(())
     }
 }
 
+/// Vulkan sample count flag for `count` samples per pixel.
+pub fn sample_count_flags(count: u32) -> Result<ash::vk::SampleCountFlags, GpuError> {
+    match count {
+        1 | 2 | 4 | 8 | 16 | 32 | 64 => Ok(ash::vk::SampleCountFlags::from_raw(count)),
+        _ => Err(GpuError::new(
+            format!("Invalid sample count {count}, expected a power of two up to 64"),
+            GpuErrorKind::ResourceCreation,
+        )),
+    }
+}
+
+/// Highest sample count contained in `counts`.
+pub fn max_sample_count(counts: ash::vk::SampleCountFlags) -> u32 {
+    1 << (31 - counts.as_raw().max(1).leading_zeros())
+}
+
 pub fn gpu_error_out_of_range(resource_name: &str, idx: usize, len: usize) -> GpuError {
     GpuError::new(
         format!("Index {idx} outside of range for {resource_name} with length {len}"),
@@ -848,6 +832,37 @@ impl Into<ash::vk::CompareOp> for CompareFunc {
     }
 }
 
+impl From<StencilOp> for ash::vk::StencilOp {
+    fn from(op: StencilOp) -> Self {
+        match op {
+            StencilOp::Keep => ash::vk::StencilOp::KEEP,
+            StencilOp::Zero => ash::vk::StencilOp::ZERO,
+            StencilOp::Replace => ash::vk::StencilOp::REPLACE,
+            StencilOp::IncrementClamp => ash::vk::StencilOp::INCREMENT_AND_CLAMP,
+            StencilOp::DecrementClamp => ash::vk::StencilOp::DECREMENT_AND_CLAMP,
+            StencilOp::Invert => ash::vk::StencilOp::INVERT,
+            StencilOp::IncrementWrap => ash::vk::StencilOp::INCREMENT_AND_WRAP,
+            StencilOp::DecrementWrap => ash::vk::StencilOp::DECREMENT_AND_WRAP,
+        }
+    }
+}
+
+/// Vulkan state of one facing of `stencil`.
+pub fn stencil_op_state(
+    stencil: &StencilState,
+    face: &StencilFaceState,
+) -> ash::vk::StencilOpState {
+    ash::vk::StencilOpState {
+        fail_op: face.fail_op.into(),
+        pass_op: face.pass_op.into(),
+        depth_fail_op: face.depth_fail_op.into(),
+        compare_op: face.compare.into(),
+        compare_mask: stencil.read_mask as u32,
+        write_mask: stencil.write_mask as u32,
+        reference: stencil.reference as u32,
+    }
+}
+
 impl Into<ash::vk::CullModeFlags> for CullMode {
     fn into(self) -> ash::vk::CullModeFlags {
         match self {
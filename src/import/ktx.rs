@@ -0,0 +1,234 @@
+// KTX2 container loading
+//
+// Reads 2D textures with their pre-built mip chains. Levels may be Zstandard
+// supercompressed; Basis Universal payloads (BasisLZ / UASTC) would need a
+// transcoder and are rejected so callers can fall back to another source.
+
+use crate::engine::backend::TextureFormat;
+use crate::import::ImportError;
+
+/// A decoded KTX2 texture, ready for `GpuBackend::create_texture`.
+pub struct KtxTexture {
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    pub mip_levels: u32,
+    /// All mip levels, tightly packed and largest first.
+    pub data: Vec<u8>,
+}
+
+pub fn load_ktx2(bytes: &[u8]) -> Result<KtxTexture, ImportError> {
+    let reader =
+        ktx2::Reader::new(bytes).map_err(|e| ImportError::from("KTX2 Parse", &format!("{e:?}")))?;
+    let header = reader.header();
+
+    if header.supercompression_scheme == Some(ktx2::SupercompressionScheme::BasisLZ) {
+        return Err(ImportError::from(
+            "KTX2 Format",
+            "BasisLZ textures need transcoding, which is not supported",
+        ));
+    }
+    let format = match header.format {
+        Some(format) => texture_format(format)?,
+        // UASTC and other formats described only by their DFD
+        None => {
+            return Err(ImportError::from(
+                "KTX2 Format",
+                "Textures without a Vulkan format need transcoding, which is not supported",
+            ));
+        }
+    };
+    if header.pixel_depth > 1 || header.layer_count > 1 || header.face_count != 1 {
+        return Err(ImportError::from(
+            "KTX2 Format",
+            "Only 2D textures without array layers or faces are supported",
+        ));
+    }
+
+    let (width, height) = (header.pixel_width, header.pixel_height.max(1));
+    let mut data =
+        Vec::with_capacity(format.mip_chain_size(width, height, header.level_count.max(1)));
+    let mut mip_levels = 0;
+    for (level, level_data) in reader.levels().enumerate() {
+        let expected_size = format.image_size((width >> level).max(1), (height >> level).max(1));
+        let start = data.len();
+        match header.supercompression_scheme {
+            None => data.extend_from_slice(level_data.data),
+            Some(ktx2::SupercompressionScheme::Zstandard) => {
+                data.reserve(expected_size);
+                ruzstd::decoding::FrameDecoder::new()
+                    .decode_all_to_vec(level_data.data, &mut data)
+                    .map_err(|e| ImportError::from("KTX2 Zstandard", &format!("{e}")))?;
+            }
+            Some(scheme) => {
+                return Err(ImportError::from(
+                    "KTX2 Format",
+                    &format!("Supercompression {scheme:?} is not supported"),
+                ));
+            }
+        }
+        if data.len() - start != expected_size {
+            return Err(ImportError::from(
+                "KTX2 Parse",
+                &format!(
+                    "Mip level {level} holds {} bytes, expected {expected_size}",
+                    data.len() - start
+                ),
+            ));
+        }
+        mip_levels += 1;
+    }
+
+    Ok(KtxTexture {
+        width,
+        height,
+        format,
+        mip_levels,
+        data,
+    })
+}
+
+fn texture_format(format: ktx2::Format) -> Result<TextureFormat, ImportError> {
+    let format = match format {
+        ktx2::Format::R8_UNORM => TextureFormat::R8Unorm,
+        ktx2::Format::R8G8_UNORM => TextureFormat::Rg8Unorm,
+        ktx2::Format::R8G8B8A8_UNORM => TextureFormat::Rgba8Unorm,
+        ktx2::Format::R8G8B8A8_SRGB => TextureFormat::Rgba8UnormSrgb,
+        ktx2::Format::B8G8R8A8_UNORM => TextureFormat::Bgra8Unorm,
+        ktx2::Format::B8G8R8A8_SRGB => TextureFormat::Bgra8UnormSrgb,
+        ktx2::Format::R16G16B16A16_SFLOAT => TextureFormat::R16g16b16a16Float,
+        ktx2::Format::R32G32B32A32_SFLOAT => TextureFormat::Rgba32Float,
+        ktx2::Format::BC1_RGBA_UNORM_BLOCK => TextureFormat::Bc1RgbaUnorm,
+        ktx2::Format::BC1_RGBA_SRGB_BLOCK => TextureFormat::Bc1RgbaUnormSrgb,
+        ktx2::Format::BC3_UNORM_BLOCK => TextureFormat::Bc3Unorm,
+        ktx2::Format::BC3_SRGB_BLOCK => TextureFormat::Bc3UnormSrgb,
+        ktx2::Format::BC4_UNORM_BLOCK => TextureFormat::Bc4Unorm,
+        ktx2::Format::BC5_UNORM_BLOCK => TextureFormat::Bc5Unorm,
+        ktx2::Format::BC7_UNORM_BLOCK => TextureFormat::Bc7Unorm,
+        ktx2::Format::BC7_SRGB_BLOCK => TextureFormat::Bc7UnormSrgb,
+        ktx2::Format::ETC2_R8G8B8A8_UNORM_BLOCK => TextureFormat::Etc2Rgba8Unorm,
+        ktx2::Format::ETC2_R8G8B8A8_SRGB_BLOCK => TextureFormat::Etc2Rgba8UnormSrgb,
+        ktx2::Format::ASTC_4x4_UNORM_BLOCK => TextureFormat::Astc4x4Unorm,
+        ktx2::Format::ASTC_4x4_SRGB_BLOCK => TextureFormat::Astc4x4UnormSrgb,
+        other => {
+            return Err(ImportError::from(
+                "KTX2 Format",
+                &format!("Format {other:?} is not supported"),
+            ));
+        }
+    };
+    Ok(format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 4x2 RGBA8 mip chain: 32, 8 and 4 bytes.
+    fn rgba_mips() -> Vec<Vec<u8>> {
+        [32, 8, 4]
+            .into_iter()
+            .enumerate()
+            .map(|(level, size)| vec![level as u8 + 1; size])
+            .collect()
+    }
+
+    /// A KTX2 file holding `levels`, largest first.
+    fn ktx2_file(
+        format: Option<ktx2::Format>,
+        scheme: Option<ktx2::SupercompressionScheme>,
+        levels: &[Vec<u8>],
+    ) -> Vec<u8> {
+        // header, level index, then a DFD holding only its total size
+        let dfd_offset = ktx2::Header::LENGTH + levels.len() * ktx2::LevelIndex::LENGTH;
+        let header = ktx2::Header {
+            format,
+            type_size: 1,
+            pixel_width: 4,
+            pixel_height: 2,
+            pixel_depth: 0,
+            layer_count: 0,
+            face_count: 1,
+            level_count: levels.len() as u32,
+            supercompression_scheme: scheme,
+            index: ktx2::Index {
+                dfd_byte_offset: dfd_offset as u32,
+                dfd_byte_length: 4,
+                kvd_byte_offset: 0,
+                kvd_byte_length: 0,
+                sgd_byte_offset: 0,
+                sgd_byte_length: 0,
+            },
+        };
+        let mut file = header.as_bytes().to_vec();
+        let mut offset = dfd_offset + 4;
+        for level in levels {
+            let index = ktx2::LevelIndex {
+                byte_offset: offset as u64,
+                byte_length: level.len() as u64,
+                uncompressed_byte_length: 0,
+            };
+            file.extend_from_slice(&index.as_bytes());
+            offset += level.len();
+        }
+        file.extend_from_slice(&4u32.to_le_bytes());
+        for level in levels {
+            file.extend_from_slice(level);
+        }
+        file
+    }
+
+    #[test]
+    fn uncompressed_levels_are_packed_largest_first() {
+        let file = ktx2_file(Some(ktx2::Format::R8G8B8A8_UNORM), None, &rgba_mips());
+        let ktx = load_ktx2(&file).unwrap();
+        assert_eq!((ktx.width, ktx.height), (4, 2));
+        assert_eq!(ktx.format, TextureFormat::Rgba8Unorm);
+        assert_eq!(ktx.mip_levels, 3);
+        assert_eq!(ktx.data, rgba_mips().concat());
+    }
+
+    #[test]
+    fn zstandard_levels_are_decompressed() {
+        let compressed: Vec<_> = rgba_mips()
+            .iter()
+            .map(|level| {
+                ruzstd::encoding::compress_to_vec(
+                    level.as_slice(),
+                    ruzstd::encoding::CompressionLevel::Fastest,
+                )
+            })
+            .collect();
+        let file = ktx2_file(
+            Some(ktx2::Format::R8G8B8A8_UNORM),
+            Some(ktx2::SupercompressionScheme::Zstandard),
+            &compressed,
+        );
+        let ktx = load_ktx2(&file).unwrap();
+        assert_eq!(ktx.mip_levels, 3);
+        assert_eq!(ktx.data, rgba_mips().concat());
+    }
+
+    #[test]
+    fn basis_lz_is_rejected() {
+        let file = ktx2_file(
+            None,
+            Some(ktx2::SupercompressionScheme::BasisLZ),
+            &rgba_mips(),
+        );
+        let error = load_ktx2(&file).err().unwrap().to_string();
+        assert!(error.contains("BasisLZ"), "{error}");
+    }
+
+    #[test]
+    fn wrong_mip_size_is_rejected() {
+        let mut levels = rgba_mips();
+        levels[1].truncate(4);
+        let file = ktx2_file(Some(ktx2::Format::R8G8B8A8_UNORM), None, &levels);
+        let error = load_ktx2(&file).err().unwrap().to_string();
+        assert!(
+            error.contains("Mip level 1 holds 4 bytes, expected 8"),
+            "{error}"
+        );
+    }
+}
@@ -3,12 +3,16 @@
 // Loads glTF scenes into the engine's scenegraph, creating backend-agnostic
 // GPU resources (textures, vertex/index buffers, drawables) via the GpuBackend trait.
 
+mod ktx;
+
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 use crate::engine::backend::*;
 use crate::engine::geometry::Vertex;
 use crate::engine::scenegraph::node::Node;
+use ktx::KtxTexture;
 
 #[derive(Debug, Clone)]
 pub struct ImportError {
@@ -38,8 +42,9 @@ impl From<GpuError> for ImportError {
 }
 
 struct GltfImporter<'a, B: GpuBackend> {
+    document: &'a gltf::Document,
+    base: PathBuf,
     buffers: Vec<gltf::buffer::Data>,
-    images: Vec<gltf::image::Data>,
     backend: &'a B,
     texture_buffer: HashMap<usize, (Rc<B::Texture>, bool)>,
     missing_tex: Rc<B::Texture>,
@@ -47,10 +52,17 @@ struct GltfImporter<'a, B: GpuBackend> {
 }
 
 pub fn load_gltf<B: GpuBackend>(path: &str, backend: &B) -> Result<Node<B>, ImportError> {
-    let (gltf, buffers, images) = match gltf::import(path) {
+    // images are decoded per texture, KTX2 sources are unknown to gltf::import
+    let gltf::Gltf { document, blob } = match gltf::Gltf::open(path) {
         Ok(g) => g,
         Err(e) => return Err(ImportError::from("GLTF Import Error", &format!("{}", e))),
     };
+    let base = Path::new(path)
+        .parent()
+        .unwrap_or_else(|| Path::new("./"))
+        .to_path_buf();
+    let buffers = gltf::import_buffers(&document, Some(&base), blob)
+        .map_err(|e| ImportError::from("GLTF Import Error", &format!("{}", e)))?;
 
     // Load fallback "missing texture" placeholder
     let img = image::open("images/textures/missing_tex.png")
@@ -64,6 +76,8 @@ pub fn load_gltf<B: GpuBackend>(path: &str, backend: &B) -> Result<Node<B>, Impo
                 &TextureDesc {
                     width: w,
                     height: h,
+                    depth_or_layers: 1,
+                    dimension: TextureDimension::D2,
                     format: TextureFormat::Rgba8Unorm,
                     sampler: SamplerDesc {
                         address_u: AddressMode::Clamp,
@@ -72,6 +86,7 @@ pub fn load_gltf<B: GpuBackend>(path: &str, backend: &B) -> Result<Node<B>, Impo
                         compare: None,
                     },
                     generate_mipmaps: false,
+                    mip_levels: 1,
                 },
                 &pixels,
             )
@@ -85,6 +100,8 @@ pub fn load_gltf<B: GpuBackend>(path: &str, backend: &B) -> Result<Node<B>, Impo
                 &TextureDesc {
                     width: 1,
                     height: 1,
+                    depth_or_layers: 1,
+                    dimension: TextureDimension::D2,
                     format: TextureFormat::Rgba8Unorm,
                     sampler: SamplerDesc {
                         address_u: AddressMode::Clamp,
@@ -93,6 +110,7 @@ pub fn load_gltf<B: GpuBackend>(path: &str, backend: &B) -> Result<Node<B>, Impo
                         compare: None,
                     },
                     generate_mipmaps: false,
+                    mip_levels: 1,
                 },
                 &[128, 128, 255, 255],
             )
@@ -100,8 +118,9 @@ pub fn load_gltf<B: GpuBackend>(path: &str, backend: &B) -> Result<Node<B>, Impo
     );
 
     let mut importer = GltfImporter {
+        document: &document,
+        base,
         buffers,
-        images,
         backend,
         texture_buffer: HashMap::new(),
         missing_tex,
@@ -109,7 +128,7 @@ pub fn load_gltf<B: GpuBackend>(path: &str, backend: &B) -> Result<Node<B>, Impo
     };
 
     let mut root = Node::create_standard_mesh(None, glm::identity(), None);
-    for scene in gltf.scenes() {
+    for scene in document.scenes() {
         for node in scene.nodes() {
             importer.process_node(node, &mut root)?;
         }
@@ -208,6 +227,13 @@ impl<'a, B: GpuBackend> GltfImporter<'a, B> {
                         }
                         None => (self.missing_tex.clone(), false),
                     };
+                    // compressed textures can't be scanned for alpha, so blended and
+                    // alpha-tested materials go to the forward pass, which discards
+                    let transparent = transparent
+                        || matches!(
+                            mat.alpha_mode(),
+                            gltf::material::AlphaMode::Blend | gltf::material::AlphaMode::Mask
+                        );
                     let tex_mr = match pbr.metallic_roughness_texture() {
                         Some(info) => {
                             let tx = info.texture();
@@ -375,13 +401,53 @@ impl<'a, B: GpuBackend> GltfImporter<'a, B> {
             return (tex.clone(), *transparent);
         }
 
-        let img = gltf_tex.source();
-        let img_raw = &self.images[img.index()];
-        let mut image_data: Vec<u8> = Vec::new();
-
         let sampler = gltf_tex.sampler();
-        let address_u = gltf_address_mode(sampler.wrap_s());
-        let address_v = gltf_address_mode(sampler.wrap_t());
+        let sampler = SamplerDesc {
+            address_u: gltf_address_mode(sampler.wrap_s()),
+            address_v: gltf_address_mode(sampler.wrap_t()),
+            filter: FilterMode::Anisotropic,
+            compare: None,
+        };
+
+        if let Some(ktx) = self.compressed_image(&gltf_tex) {
+            let tex = self.create_texture(
+                index,
+                &TextureDesc {
+                    width: ktx.width,
+                    height: ktx.height,
+                    depth_or_layers: 1,
+                    dimension: TextureDimension::D2,
+                    format: ktx.format,
+                    sampler,
+                    generate_mipmaps: false,
+                    mip_levels: ktx.mip_levels,
+                },
+                &ktx.data,
+            );
+            self.texture_buffer.insert(index, (tex.clone(), false));
+            return (tex, false);
+        }
+
+        let decoded = gltf_tex
+            .source()
+            .ok_or_else(|| ImportError::from("Image Load", "No image source the backend can use"));
+        let img_raw = match decoded.and_then(|img| {
+            gltf::image::Data::from_source(img.source(), Some(&self.base), &self.buffers)
+                .map_err(|e| ImportError::from("Image Load", &format!("{}", e)))
+        }) {
+            Ok(img_raw) => img_raw,
+            Err(e) => {
+                eprintln!(
+                    "Unable to load texture with index {}: {}, using fallback",
+                    index, e
+                );
+                let fallback = self.missing_tex.clone();
+                self.texture_buffer.insert(index, (fallback.clone(), false));
+                return (fallback, false);
+            }
+        };
+        let img_raw = &img_raw;
+        let mut image_data: Vec<u8> = Vec::new();
 
         let mut transparent = false;
         let (img_data, format): (&[u8], TextureFormat) = match img_raw.format {
@@ -418,36 +484,92 @@ impl<'a, B: GpuBackend> GltfImporter<'a, B> {
             ),
         };
 
-        let tex = Rc::new(
-            self.backend
-                .create_texture(
-                    &TextureDesc {
-                        width: img_raw.width,
-                        height: img_raw.height,
-                        format,
-                        sampler: SamplerDesc {
-                            address_u,
-                            address_v,
-                            filter: FilterMode::Anisotropic,
-                            compare: None,
-                        },
-                        generate_mipmaps: false,
-                    },
-                    img_data,
-                )
-                .unwrap_or_else(|e| {
-                    panic!(
-                        "Unable to load texture with index {}: {}",
-                        gltf_tex.index(),
-                        e
-                    )
-                }),
+        let tex = self.create_texture(
+            index,
+            &TextureDesc {
+                width: img_raw.width,
+                height: img_raw.height,
+                depth_or_layers: 1,
+                dimension: TextureDimension::D2,
+                format,
+                sampler,
+                generate_mipmaps: false,
+                mip_levels: 1,
+            },
+            img_data,
         );
+        let transparent = transparent && !Rc::ptr_eq(&tex, &self.missing_tex);
 
         self.texture_buffer
             .insert(index, (tex.clone(), transparent));
         (tex, transparent)
     }
+
+    /// Create a texture, falling back to the placeholder if the backend refuses it.
+    fn create_texture(&self, index: usize, desc: &TextureDesc, data: &[u8]) -> Rc<B::Texture> {
+        match self.backend.create_texture(desc, data) {
+            Ok(tex) => Rc::new(tex),
+            Err(e) => {
+                // e.g. bindless slots exhausted, keep the rest of the scene
+                eprintln!(
+                    "Unable to load texture with index {}: {}, using fallback",
+                    index, e
+                );
+                self.missing_tex.clone()
+            }
+        }
+    }
+
+    /// Find a KTX2 image for the texture in a format the backend can sample:
+    /// the `KHR_texture_basisu` source, a KTX2 core source, or a `.ktx2` file
+    /// next to a PNG/JPEG source.
+    fn compressed_image(&self, gltf_tex: &gltf::Texture) -> Option<KtxTexture> {
+        let basisu = gltf_tex
+            .extension_value("KHR_texture_basisu")
+            .and_then(|ext| ext["source"].as_u64())
+            .and_then(|source| self.document.images().nth(source as usize));
+        let mut candidates = Vec::new();
+        for image in basisu.into_iter().chain(gltf_tex.source()) {
+            match image.source() {
+                gltf::image::Source::View {
+                    view,
+                    mime_type: "image/ktx2",
+                } => {
+                    let buffer = &self.buffers[view.buffer().index()];
+                    candidates.push(Ok(
+                        buffer[view.offset()..view.offset() + view.length()].to_vec()
+                    ));
+                }
+                gltf::image::Source::Uri { uri, .. } if !uri.starts_with("data:") => {
+                    let path = self.base.join(uri);
+                    let path = if uri.ends_with(".ktx2") {
+                        path
+                    } else {
+                        path.with_extension("ktx2")
+                    };
+                    if path.is_file() {
+                        candidates.push(std::fs::read(&path).map_err(|e| {
+                            ImportError::from("Image Load", &format!("{}: {}", path.display(), e))
+                        }));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for bytes in candidates {
+            match bytes.and_then(|bytes| ktx::load_ktx2(&bytes)) {
+                Ok(ktx) if self.backend.supports_texture_format(ktx.format) => return Some(ktx),
+                Ok(ktx) => eprintln!(
+                    "Texture {} is {:?}, which the backend can't sample",
+                    gltf_tex.index(),
+                    ktx.format
+                ),
+                Err(e) => eprintln!("Skipping KTX2 image of texture {}: {}", gltf_tex.index(), e),
+            }
+        }
+        None
+    }
 }
 
 impl std::fmt::Display for ImportError {
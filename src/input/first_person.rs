@@ -81,7 +81,7 @@ impl InputHandler for FPSController {
             self.pos = self.pos + -self.move_speed * delta_t * self.get_right();
         }
 
-        settings.ssao = self.settings.ssao;
+        settings.ssao.enabled = self.settings.ssao;
     }
     fn handle_key(&mut self, key: Key, action: Action) -> ApplicationRequest {
         match self.keybinds.get(&key) {
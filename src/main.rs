@@ -27,6 +27,9 @@ use crate::{
     },
 };
 
+/// Directory screenshots are written to, relative to the working directory.
+const SCREENSHOT_DIR: &str = "screenshots";
+
 fn pause() {
     let mut stdin = std::io::stdin();
     let mut stdout = std::io::stdout();
@@ -81,6 +84,9 @@ fn drain_latest(receiver: &Receiver<FrameData>, quit_flag: &AtomicBool) -> Resul
                     .free
                     .extend(latest.full_output.textures_delta.free);
 
+                // Keep one-shot requests from dropped frames
+                newer.pending_screenshot |= latest.pending_screenshot;
+
                 latest = newer;
             }
             Err(TryRecvError::Empty) => break,
@@ -199,9 +205,10 @@ fn vk_render_loop(channels: RenderChannels, settings: Settings, procedural_mode:
             renderer.resize(frame.window_size.0, frame.window_size.1);
         }
 
-        // Begin frame timing measurement (wall-clock)
-        // TODO: Enrich with GPU timestamps via Vulkan timestamp queries for more precise
-        // GPU-side timing breakdown (vertex shader, fragment shader, present wait, etc.)
+        // Safe point between frames: swap in pipelines of changed shaders
+        renderer.reload_changed_shaders();
+
+        // Begin frame timing measurement (wall-clock); GPU time comes from the backend profiler
         let frame_start = Instant::now();
 
         // Begin GPU frame
@@ -216,10 +223,15 @@ fn vk_render_loop(channels: RenderChannels, settings: Settings, procedural_mode:
 
         // Apply RT toggle from UI
         renderer.set_use_ray_tracing(frame.use_ray_tracing);
+        renderer.settings_mut().ssao = frame.ssao;
+        renderer.settings_mut().taa = frame.taa;
 
         // Check quit from editor (if any quit command was sent)
         // Note: quit is now primarily handled on main thread via pending_quit
 
+        // egui overlay using FullOutput from main thread, drawn by the render graph
+        editor_renderer.render_overlay(&frame.full_output, &mut renderer);
+
         // Update scene + render
         let mut cam = frame.camera.clone();
         renderer.update_state(frame.delta_t, &mut cam);
@@ -229,22 +241,33 @@ fn vk_render_loop(channels: RenderChannels, settings: Settings, procedural_mode:
             break;
         }
 
-        // Render egui overlay using FullOutput from main thread
-        editor_renderer.render_overlay(&frame.full_output, &mut renderer);
-
         // Finish + present
         if let Err(e) = renderer.finish_frame() {
             eprintln!("Frame finish error: {}", e);
         }
+        // Readback needs the submitted frame, before present hands the image away
+        if frame.pending_screenshot {
+            match renderer.take_screenshot(std::path::Path::new(SCREENSHOT_DIR)) {
+                Ok(files) => {
+                    for f in files {
+                        println!("Screenshot saved: {}", f.display());
+                    }
+                }
+                Err(e) => eprintln!("Screenshot failed: {e}"),
+            }
+        }
         if let Err(e) = renderer.present() {
             eprintln!("Frame present error: {e}");
         }
 
         // Calculate frame time and send to main thread
         let frame_time_ms = frame_start.elapsed().as_secs_f32() * 1000.0;
+        let gpu_timings = renderer.backend().gpu_timings();
         let render_info = RenderFrameInfo {
             frame_time_ms,
-            gpu_time_ms: None, // TODO: Add GPU timestamp queries
+            gpu_time_ms: gpu_timings.as_ref().map(|t| t.gpu_time_ms),
+            gpu_passes: gpu_timings.map(|t| t.passes).unwrap_or_default(),
+            gpu_memory: renderer.backend().memory_stats(),
             scene_tree: renderer.scene_tree(),
             scene_lights: renderer.lights().clone(),
             rt_supported: renderer.backend().has_rt_support(),
@@ -31,6 +31,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let spv_out = std::path::PathBuf::from("src/shaders/spv");
     std::fs::create_dir_all(&spv_out).unwrap();
+    let mut manifest = Vec::new();
     for shader in &shaders {
         let path_str = shader.display().to_string();
 
@@ -63,12 +64,79 @@ fn main() -> Result<(), Box<dyn Error>> {
         std::fs::create_dir_all(out.parent().unwrap()).unwrap();
         std::fs::write(&out, code.as_slice()).unwrap();
 
+        let relative = out.strip_prefix(&spv_out).unwrap().to_string_lossy();
+        manifest.push(format!(
+            "{} {:016x}",
+            relative.replace('\\', "/"),
+            source_hash(shader)?
+        ));
+
         println!("Compiled: {} -> {}", shader.display(), out.display());
     }
 
+    // Hashes of the sources each binary was compiled from, checked by the
+    // crate's tests to catch SPIR-V that is stale against its Slang source
+    manifest.sort();
+    manifest.push(String::new());
+    std::fs::write(spv_out.join(SOURCES_MANIFEST), manifest.join("\n"))?;
+
+    Ok(())
+}
+
+const SOURCES_MANIFEST: &str = "sources.txt";
+const SEARCH_PATHS: [&str; 2] = ["src/shaders/slang", "src/shaders/slang/modules"];
+
+/// FNV-1a hash of a shader and every module it imports, transitively. Must
+/// match `slang_source_hash` in the crate's shader reload tests.
+fn source_hash(shader: &std::path::Path) -> std::io::Result<u64> {
+    let mut files = std::collections::BTreeMap::new();
+    collect_imports(shader, &mut files)?;
+    let mut hash = FNV_OFFSET;
+    for source in files.values() {
+        hash = fnv1a(hash, source.replace('\r', "").as_bytes());
+    }
+    Ok(hash)
+}
+
+fn collect_imports(
+    file: &std::path::Path,
+    files: &mut std::collections::BTreeMap<std::path::PathBuf, String>,
+) -> std::io::Result<()> {
+    if files.contains_key(file) {
+        return Ok(());
+    }
+    let source = std::fs::read_to_string(file)?;
+    files.insert(file.to_path_buf(), source.clone());
+    for line in source.lines() {
+        let line = line.trim_start().trim_start_matches("__exported").trim();
+        let Some(module) = line
+            .strip_prefix("import ")
+            .map(|m| m.trim_end_matches(';').trim())
+        else {
+            continue;
+        };
+        let found = SEARCH_PATHS
+            .iter()
+            .map(|dir| std::path::Path::new(dir).join(format!("{module}.slang")))
+            .find(|path| path.is_file());
+        if let Some(path) = found {
+            collect_imports(&path, files)?;
+        }
+    }
     Ok(())
 }
 
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
 fn find_slang_files(dir: impl AsRef<std::path::Path>) -> Vec<std::path::PathBuf> {
     let mut files = Vec::new();
     if let Ok(entries) = std::fs::read_dir(dir) {